@@ -0,0 +1,58 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+#![no_main]
+
+use std::sync::{Mutex, OnceLock};
+
+use hyperlight_host::func::{ParameterType, ReturnType};
+use hyperlight_host::sandbox::fuzzing::{FuzzOutcome, GuestFuzzer};
+use hyperlight_host::sandbox::uninitialized::GuestBinary;
+use hyperlight_host::{MultiUseSandbox, UninitializedSandbox};
+use hyperlight_testing::simple_guest_for_fuzzing_as_string;
+use libfuzzer_sys::{Corpus, fuzz_target};
+static FUZZER: OnceLock<Mutex<GuestFuzzer>> = OnceLock::new();
+
+// This fuzz target drives a `GuestFuzzer` with libFuzzer's inputs, so that every input runs
+// against the same snapshot of the guest and guest crashes are reported to libFuzzer.
+fuzz_target!(
+    init: {
+        let u_sbox = UninitializedSandbox::new(
+            GuestBinary::FilePath(simple_guest_for_fuzzing_as_string().expect("Guest Binary Missing")),
+            None,
+        )
+        .unwrap();
+
+        let mu_sbox: MultiUseSandbox = u_sbox.evolve().unwrap();
+        let fuzzer = GuestFuzzer::new(
+            mu_sbox,
+            "PrintOutput",
+            vec![ParameterType::String],
+            ReturnType::Int,
+        )
+        .unwrap();
+        FUZZER.set(Mutex::new(fuzzer)).unwrap();
+    },
+
+    |data: &[u8]| -> Corpus {
+        let mut fuzzer = FUZZER.get().unwrap().lock().unwrap();
+        match fuzzer.run_input(data).unwrap() {
+            FuzzOutcome::Crashed(crash) => panic!("{:?} crashed the guest: {}", crash.args, crash.error),
+            FuzzOutcome::Rejected => Corpus::Reject,
+            FuzzOutcome::Passed { .. } => Corpus::Keep,
+        }
+    }
+);
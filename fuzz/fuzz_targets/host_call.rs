@@ -56,11 +56,11 @@ fuzz_target!(
                 // we are randomly generating the function name and parameters
                 // to call with.
                 HyperlightError::HostFunctionNotFound(_) => {}
-                HyperlightError::GuestError(ErrorCode::HostFunctionError, msg) if msg == format!("HostFunction {} was not found", host_func_name) => {}
+                HyperlightError::GuestError { code: ErrorCode::HostFunctionError, message, .. } if message == format!("HostFunction {} was not found", host_func_name) => {}
                 HyperlightError::UnexpectedNoOfArguments(_, _) => {},
-                HyperlightError::GuestError(ErrorCode::HostFunctionError, msg) if msg.contains("The number of arguments to the function is wrong") => {}
+                HyperlightError::GuestError { code: ErrorCode::HostFunctionError, message, .. } if message.contains("The number of arguments to the function is wrong") => {}
                 HyperlightError::ParameterValueConversionFailure(_, _) => {},
-                HyperlightError::GuestError(ErrorCode::HostFunctionError, msg) if msg.contains("Failed To Convert Parameter Value") => {}
+                HyperlightError::GuestError { code: ErrorCode::HostFunctionError, message, .. } if message.contains("Failed To Convert Parameter Value") => {}
 
                 // any other error should be reported
                 _ => panic!("Guest Aborted with Unexpected Error: {:?}", e),
@@ -0,0 +1,71 @@
+/*
+Copyright 2025 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt::Write as _;
+
+use crate::{Interface, args_tuple, params_list};
+
+/// Generate the guest-side bindings for `interface`
+pub(crate) fn generate(interface: &Interface) -> String {
+    let mut out = String::from("// Generated by hyperlight-codegen. Do not edit.\n\n");
+
+    for func in &interface.host_functions {
+        let ret = func.ret.rust_type();
+        let _ = writeln!(out, "/// Call the host function `{}`", func.name);
+        out.push_str("#[allow(dead_code)]\n");
+        let _ = writeln!(
+            out,
+            "pub fn {}({}) -> ::hyperlight_guest::error::Result<{ret}> {{",
+            func.rust_name,
+            params_list(func)
+        );
+        let _ = writeln!(
+            out,
+            "    ::hyperlight_guest_bin::host_comm::call_host::<{ret}>(\"{}\", {})",
+            func.name,
+            args_tuple(func)
+        );
+        out.push_str("}\n\n");
+    }
+
+    out.push_str("/// The functions exported by the guest\n");
+    out.push_str("#[allow(dead_code)]\n");
+    out.push_str("pub trait Guest: 'static {\n");
+    for func in &interface.guest_functions {
+        let _ = writeln!(out, "    /// Implement the guest function `{}`", func.name);
+        let _ = writeln!(
+            out,
+            "    fn {}({}) -> ::hyperlight_guest::error::Result<{}>;",
+            func.rust_name,
+            params_list(func),
+            func.ret.rust_type()
+        );
+    }
+    out.push_str("}\n\n");
+
+    out.push_str("/// Register the functions of `G`, so that the host can call them\n");
+    out.push_str("#[allow(dead_code)]\n");
+    out.push_str("pub fn register_guest<G: Guest>() {\n");
+    for func in &interface.guest_functions {
+        let _ = writeln!(
+            out,
+            "    ::hyperlight_guest_bin::guest_function::register::register_fn(\"{}\", G::{});",
+            func.name, func.rust_name
+        );
+    }
+    out.push_str("}\n");
+    out
+}
@@ -0,0 +1,106 @@
+/*
+Copyright 2025 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt::Write as _;
+
+use crate::{Function, Interface, args_tuple, params_list};
+
+/// The signature of the trait method for `func`, taking `&mut self`
+fn method_signature(func: &Function) -> String {
+    let mut params = String::from("&mut self");
+    if !func.params.is_empty() {
+        params.push_str(", ");
+        params.push_str(&params_list(func));
+    }
+    format!(
+        "fn {}({params}) -> ::hyperlight_host::Result<{}>",
+        func.rust_name,
+        func.ret.rust_type()
+    )
+}
+
+/// Generate the host-side bindings for `interface`
+pub(crate) fn generate(interface: &Interface) -> String {
+    let mut out = String::from("// Generated by hyperlight-codegen. Do not edit.\n\n");
+
+    out.push_str("/// The functions exported by the guest\n");
+    out.push_str("#[allow(dead_code)]\n");
+    out.push_str("pub trait Guest {\n");
+    for func in &interface.guest_functions {
+        let _ = writeln!(out, "    /// Call the guest function `{}`", func.name);
+        let _ = writeln!(out, "    {};", method_signature(func));
+    }
+    out.push_str("}\n\n");
+
+    out.push_str("impl Guest for ::hyperlight_host::MultiUseSandbox {\n");
+    for func in &interface.guest_functions {
+        let _ = writeln!(out, "    {} {{", method_signature(func));
+        let _ = writeln!(
+            out,
+            "        self.call::<{}>(\"{}\", {})",
+            func.ret.rust_type(),
+            func.name,
+            args_tuple(func)
+        );
+        out.push_str("    }\n");
+    }
+    out.push_str("}\n\n");
+
+    out.push_str("/// The functions the host provides to the guest\n");
+    out.push_str("#[allow(dead_code)]\n");
+    out.push_str("pub trait Host: Send + 'static {\n");
+    for func in &interface.host_functions {
+        let _ = writeln!(out, "    /// Implement the host function `{}`", func.name);
+        let _ = writeln!(out, "    {};", method_signature(func));
+    }
+    out.push_str("}\n\n");
+
+    out.push_str(
+        "/// Register the functions of `host` with `sandbox`, so that the guest can call them\n",
+    );
+    out.push_str("#[allow(dead_code, unused_variables)]\n");
+    out.push_str("pub fn register_host<H: Host>(\n");
+    out.push_str("    sandbox: &mut impl ::hyperlight_host::func::Registerable,\n");
+    out.push_str("    host: H,\n");
+    out.push_str(") -> ::hyperlight_host::Result<()> {\n");
+    out.push_str("    let host = ::std::sync::Arc::new(::std::sync::Mutex::new(host));\n");
+    for func in &interface.host_functions {
+        let args: Vec<&str> = func.params.iter().map(|(name, _)| name.as_str()).collect();
+        out.push_str("    {\n");
+        out.push_str("        let host = host.clone();\n");
+        let _ = writeln!(
+            out,
+            "        sandbox.register_host_function(\"{}\", move |{}| {{",
+            func.name,
+            params_list(func)
+        );
+        out.push_str("            host.lock()\n");
+        out.push_str(
+            "                .map_err(|e| ::hyperlight_host::HyperlightError::LockAttemptFailed(e.to_string()))?\n",
+        );
+        let _ = writeln!(
+            out,
+            "                .{}({})",
+            func.rust_name,
+            args.join(", ")
+        );
+        out.push_str("        })?;\n");
+        out.push_str("    }\n");
+    }
+    out.push_str("    Ok(())\n");
+    out.push_str("}\n");
+    out
+}
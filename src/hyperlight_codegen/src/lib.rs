@@ -0,0 +1,336 @@
+/*
+Copyright 2025 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! # Typed bindings generator
+//!
+//! Generates strongly-typed Rust bindings for the functions a Hyperlight
+//! guest exports and the host functions it calls, from a small interface
+//! description, so that neither side has to refer to functions by name or
+//! pack their arguments by hand.
+//!
+//! An interface is a list of function signatures, each marked with the
+//! side that implements it:
+//!
+//! ```text
+//! // Functions exported by the guest
+//! guest fn Echo(message: String) -> String;
+//! guest fn Add(a: i32, b: i32) -> i32;
+//!
+//! // Functions provided by the host
+//! host fn GetTime() -> u64;
+//! host fn Log(level: u32, message: String);
+//! ```
+//!
+//! The name of each function is the name it is registered and called
+//! with. The supported parameter and return types are `String`, `i32`,
+//! `u32`, `i64`, `u64`, `f32`, `f64`, `bool` and `Vec<u8>`; a function
+//! without a return type returns `()`.
+//!
+//! The bindings are generated from a build script, and included in the
+//! crate that uses them:
+//!
+//! ```no_run
+//! // build.rs
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR")?);
+//! hyperlight_codegen::generate_host_bindings("interface.hlidl", out_dir.join("bindings.rs"))?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ```ignore
+//! // src/main.rs
+//! include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+//! ```
+//!
+//! ## Host bindings
+//!
+//! The host bindings contain:
+//! - a `Guest` trait with a method for each guest function, implemented
+//!   for `MultiUseSandbox`, so that `sandbox.add(1, 2)` calls the guest
+//!   function `Add`
+//! - a `Host` trait with a method for each host function, and a
+//!   `register_host()` function that registers an implementation of it
+//!   with an `UninitializedSandbox` or a `MultiUseSandbox`
+//!
+//! ## Guest bindings
+//!
+//! The guest bindings, for guests built on `hyperlight-guest-bin`,
+//! contain:
+//! - a function for each host function, so that `get_time()` calls the
+//!   host function `GetTime`
+//! - a `Guest` trait with an associated function for each guest function,
+//!   and a `register_guest()` function that registers an implementation
+//!   of it, to be called from the guest's `hyperlight_main`
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+mod guest;
+mod host;
+mod parse;
+
+pub use parse::ParseError;
+
+/// The parameter and return types functions can have
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Type {
+    /// `()`, only valid as a return type
+    Void,
+    /// `String`
+    String,
+    /// `i32`
+    Int,
+    /// `u32`
+    UInt,
+    /// `i64`
+    Long,
+    /// `u64`
+    ULong,
+    /// `f32`
+    Float,
+    /// `f64`
+    Double,
+    /// `bool`
+    Bool,
+    /// `Vec<u8>`
+    VecBytes,
+}
+
+impl Type {
+    /// The Rust type representing this type
+    pub fn rust_type(self) -> &'static str {
+        match self {
+            Type::Void => "()",
+            Type::String => "String",
+            Type::Int => "i32",
+            Type::UInt => "u32",
+            Type::Long => "i64",
+            Type::ULong => "u64",
+            Type::Float => "f32",
+            Type::Double => "f64",
+            Type::Bool => "bool",
+            Type::VecBytes => "Vec<u8>",
+        }
+    }
+}
+
+/// Which side of the sandbox implements a function
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Side {
+    /// The function is exported by the guest and called by the host
+    Guest,
+    /// The function is provided by the host and called by the guest
+    Host,
+}
+
+/// A function declared in an interface
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Function {
+    /// The name the function is registered and called with
+    pub name: String,
+    /// The name of the function in the generated bindings, which is `name`
+    /// converted to snake case
+    pub rust_name: String,
+    /// The names and types of the parameters of the function
+    pub params: Vec<(String, Type)>,
+    /// The return type of the function
+    pub ret: Type,
+}
+
+/// An interface between a host and a guest
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Interface {
+    /// The functions exported by the guest
+    pub guest_functions: Vec<Function>,
+    /// The functions provided by the host
+    pub host_functions: Vec<Function>,
+}
+
+impl Interface {
+    /// Parse an interface description
+    pub fn parse(src: &str) -> Result<Self, ParseError> {
+        parse::parse(src)
+    }
+
+    /// Generate the host-side bindings for the interface
+    pub fn host_bindings(&self) -> String {
+        host::generate(self)
+    }
+
+    /// Generate the guest-side bindings for the interface
+    pub fn guest_bindings(&self) -> String {
+        guest::generate(self)
+    }
+}
+
+/// An error generating bindings
+#[derive(Debug)]
+pub enum Error {
+    /// The interface description could not be read or the bindings could
+    /// not be written
+    Io(PathBuf, std::io::Error),
+    /// The interface description is invalid
+    Parse(PathBuf, ParseError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            Error::Parse(path, e) => write!(f, "{}:{}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(_, e) => Some(e),
+            Error::Parse(_, e) => Some(e),
+        }
+    }
+}
+
+/// Generate the host-side bindings for the interface described in the
+/// file `idl`, and write them to the file `out`.
+///
+/// Meant to be called from a build script, which is rerun whenever `idl`
+/// changes.
+pub fn generate_host_bindings(idl: impl AsRef<Path>, out: impl AsRef<Path>) -> Result<(), Error> {
+    generate(idl.as_ref(), out.as_ref(), Interface::host_bindings)
+}
+
+/// Generate the guest-side bindings for the interface described in the
+/// file `idl`, and write them to the file `out`.
+///
+/// Meant to be called from a build script, which is rerun whenever `idl`
+/// changes.
+pub fn generate_guest_bindings(idl: impl AsRef<Path>, out: impl AsRef<Path>) -> Result<(), Error> {
+    generate(idl.as_ref(), out.as_ref(), Interface::guest_bindings)
+}
+
+fn generate(idl: &Path, out: &Path, bindings: fn(&Interface) -> String) -> Result<(), Error> {
+    println!("cargo:rerun-if-changed={}", idl.display());
+    let src = std::fs::read_to_string(idl).map_err(|e| Error::Io(idl.to_path_buf(), e))?;
+    let interface = Interface::parse(&src).map_err(|e| Error::Parse(idl.to_path_buf(), e))?;
+    std::fs::write(out, bindings(&interface)).map_err(|e| Error::Io(out.to_path_buf(), e))
+}
+
+/// The arguments of a call to `func`, as a tuple
+fn args_tuple(func: &Function) -> String {
+    let names: Vec<&str> = func.params.iter().map(|(name, _)| name.as_str()).collect();
+    match names.as_slice() {
+        [] => "()".to_string(),
+        [name] => format!("({name},)"),
+        names => format!("({})", names.join(", ")),
+    }
+}
+
+/// The parameters of `func`, as they appear in its signature
+fn params_list(func: &Function) -> String {
+    func.params
+        .iter()
+        .map(|(name, ty)| format!("{name}: {}", ty.rust_type()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Interface, Type};
+
+    const IDL: &str = "
+        // Functions exported by the guest
+        guest fn Echo(message: String) -> String;
+        guest fn AddNumbers(a: i32, b: i32) -> i32;
+        guest fn Reset();
+
+        // Functions provided by the host
+        host fn GetTime() -> u64;
+        host fn Log(level: u32, message: String);
+        host fn Hash(data: Vec<u8>) -> Vec<u8>;
+    ";
+
+    #[test]
+    fn parses_interface() {
+        let interface = Interface::parse(IDL).unwrap();
+        let names: Vec<_> = interface
+            .guest_functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.rust_name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("Echo", "echo"),
+                ("AddNumbers", "add_numbers"),
+                ("Reset", "reset")
+            ]
+        );
+        let hash = &interface.host_functions[2];
+        assert_eq!(hash.params, [("data".to_string(), Type::VecBytes)]);
+        assert_eq!(hash.ret, Type::VecBytes);
+        assert_eq!(interface.host_functions[1].ret, Type::Void);
+    }
+
+    #[test]
+    fn rejects_invalid_interfaces() {
+        let err = |src: &str| Interface::parse(src).unwrap_err().to_string();
+        assert_eq!(
+            err("guest fn Echo(message: String) -> String"),
+            "1: expected `;`, found end of input"
+        );
+        assert_eq!(
+            err("guest fn Echo(message: str);"),
+            "1: unsupported type `str`"
+        );
+        assert_eq!(
+            err("\nguest fn Echo(a: i32, a: i32);"),
+            "2: duplicate parameter `a`"
+        );
+        assert_eq!(
+            err("guest fn Echo();\nguest fn echo();"),
+            "2: function `echo` has the same binding name as another function"
+        );
+        assert_eq!(err("guest fn Echo(v: ());"), "1: unsupported type `(`");
+        assert_eq!(
+            err("other fn Echo();"),
+            "1: expected `guest` or `host`, found `other`"
+        );
+    }
+
+    #[test]
+    fn generates_valid_rust() {
+        let interface = Interface::parse(IDL).unwrap();
+
+        let host = interface.host_bindings();
+        syn::parse_file(&host).unwrap();
+        assert!(host.contains(
+            "fn add_numbers(&mut self, a: i32, b: i32) -> ::hyperlight_host::Result<i32> {\n        self.call::<i32>(\"AddNumbers\", (a, b))"
+        ));
+        assert!(host.contains("sandbox.register_host_function(\"GetTime\", move || {"));
+
+        let guest = interface.guest_bindings();
+        syn::parse_file(&guest).unwrap();
+        assert!(guest.contains(
+            "pub fn log(level: u32, message: String) -> ::hyperlight_guest::error::Result<()> {\n    ::hyperlight_guest_bin::host_comm::call_host::<()>(\"Log\", (level, message))"
+        ));
+        assert!(guest.contains(
+            "::hyperlight_guest_bin::guest_function::register::register_fn(\"Echo\", G::echo);"
+        ));
+    }
+}
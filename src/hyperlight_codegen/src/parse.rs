@@ -0,0 +1,311 @@
+/*
+Copyright 2025 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::{Function, Interface, Side, Type};
+
+/// Identifiers that cannot be used as the names of generated functions or
+/// parameters
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while", "abstract", "become", "box", "do", "final", "gen", "macro", "override",
+    "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+/// An error in an interface description
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// The line the error is on, starting from 1
+    pub line: usize,
+    /// A description of the error
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token<'a> {
+    Ident(&'a str),
+    Punct(&'static str),
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(s) => write!(f, "`{s}`"),
+            Token::Punct(s) => write!(f, "`{s}`"),
+        }
+    }
+}
+
+const PUNCTS: &[&str] = &["->", "(", ")", ":", ",", ";", "<", ">"];
+
+/// Split `src` into tokens, each with the line it is on
+fn tokenize(src: &str) -> Result<Vec<(Token<'_>, usize)>, ParseError> {
+    let mut tokens = vec![];
+    for (i, line) in src.lines().enumerate() {
+        let line_no = i + 1;
+        let mut rest = line.split("//").next().unwrap_or_default().trim_start();
+        while !rest.is_empty() {
+            if let Some(punct) = PUNCTS.iter().find(|p| rest.starts_with(**p)) {
+                tokens.push((Token::Punct(punct), line_no));
+                rest = &rest[punct.len()..];
+            } else if rest.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                tokens.push((Token::Ident(&rest[..len]), line_no));
+                rest = &rest[len..];
+            } else {
+                let c = rest.chars().next().unwrap_or_default();
+                return Err(ParseError {
+                    line: line_no,
+                    message: format!("unexpected character `{c}`"),
+                });
+            }
+            rest = rest.trim_start();
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token<'a>, usize)>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError {
+            line: self.line(),
+            message: message.into(),
+        })
+    }
+
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn found(&self) -> String {
+        match self.peek() {
+            Some(token) => token.to_string(),
+            None => "end of input".to_string(),
+        }
+    }
+
+    fn eat(&mut self, punct: &'static str) -> bool {
+        if self.peek() == Some(&Token::Punct(punct)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &'static str) -> Result<(), ParseError> {
+        if !self.eat(punct) {
+            return self.error(format!("expected `{punct}`, found {}", self.found()));
+        }
+        Ok(())
+    }
+
+    fn ident(&mut self, what: &str) -> Result<&'a str, ParseError> {
+        match self.peek() {
+            Some(Token::Ident(ident)) => {
+                let ident = *ident;
+                self.pos += 1;
+                Ok(ident)
+            }
+            _ => self.error(format!("expected {what}, found {}", self.found())),
+        }
+    }
+
+    fn ty(&mut self) -> Result<Type, ParseError> {
+        let ty = match self.peek() {
+            Some(Token::Ident("String")) => Type::String,
+            Some(Token::Ident("i32")) => Type::Int,
+            Some(Token::Ident("u32")) => Type::UInt,
+            Some(Token::Ident("i64")) => Type::Long,
+            Some(Token::Ident("u64")) => Type::ULong,
+            Some(Token::Ident("f32")) => Type::Float,
+            Some(Token::Ident("f64")) => Type::Double,
+            Some(Token::Ident("bool")) => Type::Bool,
+            Some(Token::Ident("Vec")) => {
+                self.pos += 1;
+                self.expect("<")?;
+                if self.peek() != Some(&Token::Ident("u8")) {
+                    return self.error(format!("unsupported type `Vec<{}>`", self.found()));
+                }
+                self.pos += 1;
+                self.expect(">")?;
+                return Ok(Type::VecBytes);
+            }
+            Some(Token::Ident(ty)) | Some(Token::Punct(ty)) => {
+                return self.error(format!("unsupported type `{ty}`"));
+            }
+            None => return self.error("expected a type, found end of input"),
+        };
+        self.pos += 1;
+        Ok(ty)
+    }
+
+    fn function(&mut self) -> Result<(Side, Function), ParseError> {
+        let side = match self.peek() {
+            Some(Token::Ident("guest")) => Side::Guest,
+            Some(Token::Ident("host")) => Side::Host,
+            _ => {
+                return self.error(format!(
+                    "expected `guest` or `host`, found {}",
+                    self.found()
+                ));
+            }
+        };
+        self.pos += 1;
+        if self.ident("`fn`")? != "fn" {
+            self.pos -= 1;
+            return self.error(format!("expected `fn`, found {}", self.found()));
+        }
+        let name = self.ident("a function name")?;
+        let rust_name = to_snake_case(name);
+        if KEYWORDS.contains(&rust_name.as_str()) {
+            self.pos -= 1;
+            return self.error(format!("function `{name}` is named after a keyword"));
+        }
+
+        self.expect("(")?;
+        let mut params: Vec<(String, Type)> = vec![];
+        while !self.eat(")") {
+            let param = self.ident("a parameter name")?;
+            if KEYWORDS.contains(&param) {
+                self.pos -= 1;
+                return self.error(format!("parameter `{param}` is named after a keyword"));
+            }
+            if params.iter().any(|(name, _)| name == param) {
+                self.pos -= 1;
+                return self.error(format!("duplicate parameter `{param}`"));
+            }
+            self.expect(":")?;
+            params.push((param.to_string(), self.ty()?));
+            if !self.eat(",") {
+                self.expect(")")?;
+                break;
+            }
+        }
+
+        let ret = if self.eat("->") {
+            self.ty()?
+        } else {
+            Type::Void
+        };
+        self.expect(";")?;
+
+        Ok((
+            side,
+            Function {
+                name: name.to_string(),
+                rust_name,
+                params,
+                ret,
+            },
+        ))
+    }
+}
+
+/// Convert a function name such as `GetHTTPResponse` to snake case, as in
+/// `get_http_response`
+fn to_snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 && !out.ends_with('_') {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+            if prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_lower)
+            {
+                out.push('_');
+            }
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    out
+}
+
+pub(crate) fn parse(src: &str) -> Result<Interface, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(src)?,
+        pos: 0,
+    };
+    let mut interface = Interface::default();
+    let mut names = HashSet::new();
+    while parser.peek().is_some() {
+        let start = parser.pos;
+        let (side, func) = parser.function()?;
+        let line = parser.tokens[start].1;
+        let functions = match side {
+            Side::Guest => &mut interface.guest_functions,
+            Side::Host => &mut interface.host_functions,
+        };
+        if functions.iter().any(|f| f.name == func.name) {
+            return Err(ParseError {
+                line,
+                message: format!("duplicate function `{}`", func.name),
+            });
+        }
+        if !names.insert((side, func.rust_name.clone())) {
+            return Err(ParseError {
+                line,
+                message: format!(
+                    "function `{}` has the same binding name as another function",
+                    func.name
+                ),
+            });
+        }
+        functions.push(func);
+    }
+    Ok(interface)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_snake_case;
+
+    #[test]
+    fn converts_names_to_snake_case() {
+        assert_eq!(to_snake_case("Echo"), "echo");
+        assert_eq!(to_snake_case("GetHTTPResponse"), "get_http_response");
+        assert_eq!(to_snake_case("print_output"), "print_output");
+        assert_eq!(to_snake_case("Add2Numbers"), "add2_numbers");
+    }
+}
@@ -0,0 +1,568 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The wire formats used to pass function calls, and the results of host
+//! function calls, through the input and output buffers shared by the host
+//! and the guest.
+//!
+//! Every message is prefixed with its size as a little-endian `u32`, which
+//! does not count the prefix itself, so that the buffers can be managed
+//! without knowing which format is in use.
+//!
+//! The set of formats is closed: the host picks one of the
+//! [`CallEncoding`]s, and the guest must have been built with support for
+//! it. [`FlatBuffers`] is always supported, and [`Postcard`] with the
+//! `postcard` feature.
+//!
+//! The results of guest function calls are not encoded with these formats:
+//! guest functions return their results already encoded as flatbuffers,
+//! whichever format is chosen.
+
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+use anyhow::Result;
+use flatbuffers::FlatBufferBuilder;
+
+use crate::flatbuffer_wrappers::function_call::{FunctionCall, FunctionCallType, ParameterRef};
+use crate::flatbuffer_wrappers::function_types::{FunctionCallResult, ReturnType};
+use crate::flatbuffer_wrappers::util::estimate_flatbuffer_capacity_borrowed;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A way of encoding function calls and their results.
+///
+/// This is sealed, and only implemented by the formats in this module and
+/// by [`CallEncoding`], as both the host and the guest must know every
+/// format that can be chosen.
+pub trait WireFormat: sealed::Sealed {
+    /// Encode a call to `function_name` with `parameters`
+    fn encode_function_call(
+        &self,
+        function_name: &str,
+        parameters: &[ParameterRef],
+        function_call_type: FunctionCallType,
+        expected_return_type: ReturnType,
+    ) -> Result<EncodedMessage>;
+
+    /// Decode a function call encoded with
+    /// [`encode_function_call()`](Self::encode_function_call)
+    fn decode_function_call(&self, data: &[u8]) -> Result<FunctionCall>;
+
+    /// Encode the result of a function call
+    fn encode_function_call_result(&self, result: &FunctionCallResult) -> Result<EncodedMessage>;
+
+    /// Decode a function call result encoded with
+    /// [`encode_function_call_result()`](Self::encode_function_call_result)
+    fn decode_function_call_result(&self, data: &[u8]) -> Result<FunctionCallResult>;
+}
+
+/// The default wire format, which uses the flatbuffers schemas in
+/// `src/schema`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlatBuffers;
+
+impl sealed::Sealed for FlatBuffers {}
+
+impl WireFormat for FlatBuffers {
+    fn encode_function_call(
+        &self,
+        function_name: &str,
+        parameters: &[ParameterRef],
+        function_call_type: FunctionCallType,
+        expected_return_type: ReturnType,
+    ) -> Result<EncodedMessage> {
+        let capacity = estimate_flatbuffer_capacity_borrowed(function_name, parameters);
+        let mut builder = FlatBufferBuilder::with_capacity(capacity);
+        FunctionCall::encode_borrowed(
+            &mut builder,
+            function_name,
+            parameters,
+            function_call_type,
+            expected_return_type,
+        );
+        Ok(finished_data(builder))
+    }
+
+    fn decode_function_call(&self, data: &[u8]) -> Result<FunctionCall> {
+        FunctionCall::try_from(data)
+    }
+
+    fn encode_function_call_result(&self, result: &FunctionCallResult) -> Result<EncodedMessage> {
+        let mut builder = FlatBufferBuilder::new();
+        result.encode(&mut builder);
+        Ok(finished_data(builder))
+    }
+
+    fn decode_function_call_result(&self, data: &[u8]) -> Result<FunctionCallResult> {
+        FunctionCallResult::try_from(data)
+    }
+}
+
+/// Take the finished data out of a builder without copying it. A
+/// flatbuffer is built back to front, so it ends its buffer, and starts at
+/// the builder's head.
+fn finished_data(builder: FlatBufferBuilder) -> EncodedMessage {
+    let (data, start) = builder.collapse();
+    EncodedMessage { data, start }
+}
+
+/// An encoded message, which dereferences to its bytes. The message may
+/// only fill the end of its buffer, so that it can be taken out of the
+/// encoder without being copied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedMessage {
+    data: Vec<u8>,
+    /// Where the message starts in `data`
+    start: usize,
+}
+
+impl EncodedMessage {
+    /// The message in a buffer of its own, which moves it to the start of
+    /// its buffer if it is not there already
+    pub fn into_vec(mut self) -> Vec<u8> {
+        self.data.drain(..self.start);
+        self.data
+    }
+}
+
+impl From<Vec<u8>> for EncodedMessage {
+    fn from(data: Vec<u8>) -> Self {
+        Self { data, start: 0 }
+    }
+}
+
+impl Deref for EncodedMessage {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data[self.start..]
+    }
+}
+
+impl AsRef<[u8]> for EncodedMessage {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+/// A more compact wire format, which serialises the function call types
+/// with [postcard](https://docs.rs/postcard). It is cheaper to encode and
+/// decode than [`FlatBuffers`], but the encoding is tied to the Rust
+/// definitions of the types, so it is only suitable when both the host and
+/// the guest are built from the same version of this crate.
+#[cfg(feature = "postcard")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Postcard;
+
+#[cfg(feature = "postcard")]
+impl sealed::Sealed for Postcard {}
+
+#[cfg(feature = "postcard")]
+mod postcard_format {
+    use anyhow::{Result, anyhow, bail};
+    use serde::ser::{SerializeStruct, Serializer};
+    use serde::{Deserialize, Serialize};
+
+    use super::{EncodedMessage, Postcard, WireFormat};
+    use crate::flatbuffer_wrappers::function_call::{FunctionCall, FunctionCallType, ParameterRef};
+    use crate::flatbuffer_wrappers::function_types::{FunctionCallResult, ReturnType};
+
+    /// Messages shorter than this are padded, as the buffers expect every
+    /// message to be at least 8 bytes long including its size prefix
+    const MIN_MESSAGE_SIZE: usize = 4;
+
+    /// The index of [`ParameterValue::VecBytes`] among the variants of
+    /// `ParameterValue`, which borrowed byte slices are serialised as
+    ///
+    /// [`ParameterValue::VecBytes`]: crate::flatbuffer_wrappers::function_types::ParameterValue::VecBytes
+    const VEC_BYTES_VARIANT_INDEX: u32 = 8;
+
+    /// A [`FunctionCall`] whose parameters may be borrowed, which is
+    /// serialised exactly as the `FunctionCall` itself would be
+    struct FunctionCallRef<'a> {
+        function_name: &'a str,
+        parameters: &'a [ParameterRef<'a>],
+        function_call_type: FunctionCallType,
+        expected_return_type: ReturnType,
+    }
+
+    impl Serialize for FunctionCallRef<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut call = serializer.serialize_struct("FunctionCall", 4)?;
+            call.serialize_field("function_name", self.function_name)?;
+            call.serialize_field("parameters", &Some(self.parameters))?;
+            call.serialize_field("function_call_type", &self.function_call_type)?;
+            call.serialize_field("expected_return_type", &self.expected_return_type)?;
+            call.end()
+        }
+    }
+
+    impl Serialize for ParameterRef<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            struct Bytes<'a>(&'a [u8]);
+            impl Serialize for Bytes<'_> {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_bytes(self.0)
+                }
+            }
+
+            match self {
+                ParameterRef::Value(value) => value.serialize(serializer),
+                ParameterRef::Bytes(bytes) => serializer.serialize_newtype_variant(
+                    "ParameterValue",
+                    VEC_BYTES_VARIANT_INDEX,
+                    "VecBytes",
+                    &Bytes(bytes),
+                ),
+            }
+        }
+    }
+
+    fn encode(value: &impl Serialize) -> Result<EncodedMessage> {
+        // Leave room for the size prefix, which is filled in once the size
+        // is known
+        let mut data = postcard::to_extend(value, alloc::vec![0; 4])
+            .map_err(|e| anyhow!("Failed to serialise message: {e}"))?;
+        if data.len() < 4 + MIN_MESSAGE_SIZE {
+            data.resize(4 + MIN_MESSAGE_SIZE, 0);
+        }
+        let size = (data.len() - 4) as u32;
+        data[..4].copy_from_slice(&size.to_le_bytes());
+        Ok(data.into())
+    }
+
+    fn decode<'a, T: Deserialize<'a>>(data: &'a [u8]) -> Result<T> {
+        let (prefix, rest) = data
+            .split_first_chunk::<4>()
+            .ok_or_else(|| anyhow!("message is too short to hold its size"))?;
+        let size = u32::from_le_bytes(*prefix) as usize;
+        let Some(message) = rest.get(..size) else {
+            bail!(
+                "message size {} exceeds the buffer size {}",
+                size,
+                rest.len()
+            );
+        };
+        // Any padding after the message is ignored
+        let (value, _) = postcard::take_from_bytes(message)
+            .map_err(|e| anyhow!("Failed to deserialise message: {e}"))?;
+        Ok(value)
+    }
+
+    impl WireFormat for Postcard {
+        fn encode_function_call(
+            &self,
+            function_name: &str,
+            parameters: &[ParameterRef],
+            function_call_type: FunctionCallType,
+            expected_return_type: ReturnType,
+        ) -> Result<EncodedMessage> {
+            encode(&FunctionCallRef {
+                function_name,
+                parameters,
+                function_call_type,
+                expected_return_type,
+            })
+        }
+
+        fn decode_function_call(&self, data: &[u8]) -> Result<FunctionCall> {
+            decode(data)
+        }
+
+        fn encode_function_call_result(
+            &self,
+            result: &FunctionCallResult,
+        ) -> Result<EncodedMessage> {
+            encode(result)
+        }
+
+        fn decode_function_call_result(&self, data: &[u8]) -> Result<FunctionCallResult> {
+            decode(data)
+        }
+    }
+}
+
+/// Serialise byte vectors as bytes rather than as a sequence of `u8`s,
+/// which postcard encodes the same way but handles much faster
+#[cfg(feature = "postcard")]
+pub(crate) mod bytes {
+    use alloc::vec::Vec;
+    use core::fmt;
+
+    use serde::de::{Deserializer, Error, SeqAccess, Visitor};
+    use serde::ser::Serializer;
+
+    pub(crate) fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        struct BytesVisitor;
+        impl<'de> Visitor<'de> for BytesVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a byte array")
+            }
+
+            fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+                Ok(v.to_vec())
+            }
+
+            fn visit_byte_buf<E: Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+                Ok(v)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(b) = seq.next_element()? {
+                    bytes.push(b);
+                }
+                Ok(bytes)
+            }
+        }
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+/// Sent by the guest in place of the result of a guest function call that
+/// does not fit in the output buffer. The guest keeps the encoded result in
+/// its own memory until the next guest call, and the host reads it from
+/// there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResultDescriptor {
+    /// The guest virtual address of the encoded result
+    pub gva: u64,
+    /// The size of the encoded result in bytes
+    pub len: u64,
+}
+
+impl ResultDescriptor {
+    /// The size of an encoded descriptor, including its size prefix
+    pub const ENCODED_SIZE: usize = 24;
+
+    /// Stored where a flatbuffer keeps the offset of its root table, which
+    /// is always less than the size of the flatbuffer, so that a descriptor
+    /// cannot be mistaken for a result
+    const MAGIC: u32 = u32::MAX;
+
+    /// Encode the descriptor as a size-prefixed message
+    pub fn encode(&self) -> [u8; Self::ENCODED_SIZE] {
+        let mut data = [0; Self::ENCODED_SIZE];
+        data[..4].copy_from_slice(&(Self::ENCODED_SIZE as u32 - 4).to_le_bytes());
+        data[4..8].copy_from_slice(&Self::MAGIC.to_le_bytes());
+        data[8..16].copy_from_slice(&self.gva.to_le_bytes());
+        data[16..].copy_from_slice(&self.len.to_le_bytes());
+        data
+    }
+
+    /// Decode a message as a descriptor, or return `None` if it is not one
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::ENCODED_SIZE
+            || data[..4] != (Self::ENCODED_SIZE as u32 - 4).to_le_bytes()
+            || data[4..8] != Self::MAGIC.to_le_bytes()
+        {
+            return None;
+        }
+        Some(Self {
+            gva: u64::from_le_bytes(data[8..16].try_into().ok()?),
+            len: u64::from_le_bytes(data[16..].try_into().ok()?),
+        })
+    }
+}
+
+/// The wire format the host and the guest use for function calls, which
+/// the host chooses when the sandbox is created and passes to the guest
+/// through the [`HyperlightPEB`](crate::mem::HyperlightPEB). A guest built
+/// without support for the chosen encoding fails to initialise with
+/// [`ErrorCode::UnsupportedCallEncoding`](crate::flatbuffer_wrappers::guest_error::ErrorCode::UnsupportedCallEncoding).
+///
+/// Only function calls, and the results of host function calls, are
+/// encoded with the chosen encoding. Results returned from guest functions
+/// are always encoded with [`FlatBuffers`], since guest functions return
+/// them already encoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(u64)]
+pub enum CallEncoding {
+    /// See [`FlatBuffers`]
+    #[default]
+    FlatBuffers = 0,
+    /// See [`Postcard`]
+    #[cfg(feature = "postcard")]
+    Postcard = 1,
+}
+
+impl CallEncoding {
+    /// The encoding with the given identifier, if it is known and enabled
+    pub fn from_id(id: u64) -> Option<Self> {
+        match id {
+            0 => Some(Self::FlatBuffers),
+            #[cfg(feature = "postcard")]
+            1 => Some(Self::Postcard),
+            _ => None,
+        }
+    }
+
+    /// The identifier of the encoding, as passed to the guest
+    pub fn id(self) -> u64 {
+        self as u64
+    }
+
+    fn format(self) -> &'static dyn WireFormat {
+        match self {
+            Self::FlatBuffers => &FlatBuffers,
+            #[cfg(feature = "postcard")]
+            Self::Postcard => &Postcard,
+        }
+    }
+}
+
+impl sealed::Sealed for CallEncoding {}
+
+impl WireFormat for CallEncoding {
+    fn encode_function_call(
+        &self,
+        function_name: &str,
+        parameters: &[ParameterRef],
+        function_call_type: FunctionCallType,
+        expected_return_type: ReturnType,
+    ) -> Result<EncodedMessage> {
+        self.format().encode_function_call(
+            function_name,
+            parameters,
+            function_call_type,
+            expected_return_type,
+        )
+    }
+
+    fn decode_function_call(&self, data: &[u8]) -> Result<FunctionCall> {
+        self.format().decode_function_call(data)
+    }
+
+    fn encode_function_call_result(&self, result: &FunctionCallResult) -> Result<EncodedMessage> {
+        self.format().encode_function_call_result(result)
+    }
+
+    fn decode_function_call_result(&self, data: &[u8]) -> Result<FunctionCallResult> {
+        self.format().decode_function_call_result(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::collections::BTreeMap;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    use super::{CallEncoding, ResultDescriptor, WireFormat};
+    use crate::flatbuffer_wrappers::function_call::{FunctionCallType, ParameterRef};
+    use crate::flatbuffer_wrappers::function_types::{
+        FunctionCallResult, ParameterValue, ReturnType, ReturnValue,
+    };
+    use crate::flatbuffer_wrappers::guest_error::{ErrorCode, GuestError};
+    use crate::flatbuffer_wrappers::value::Value;
+
+    fn encodings() -> impl Iterator<Item = CallEncoding> {
+        [
+            CallEncoding::FlatBuffers,
+            #[cfg(feature = "postcard")]
+            CallEncoding::Postcard,
+        ]
+        .into_iter()
+    }
+
+    #[test]
+    fn round_trips_calls_and_results() {
+        let value = Value::Map(BTreeMap::from([(
+            "key".to_string(),
+            Value::Option(Some(Value::VecBytes(vec![1, 2]).into())),
+        )]));
+        let params = [
+            ParameterRef::Value(ParameterValue::Int(-1)),
+            ParameterRef::Value(ParameterValue::String("hello".to_string())),
+            ParameterRef::Bytes(&[1, 2, 3]),
+            ParameterRef::Value(ParameterValue::Value(value.clone())),
+        ];
+        for encoding in encodings() {
+            assert_eq!(CallEncoding::from_id(encoding.id()), Some(encoding));
+
+            let data = encoding
+                .encode_function_call(
+                    "Echo",
+                    &params,
+                    FunctionCallType::Host,
+                    ReturnType::VecBytes,
+                )
+                .unwrap();
+            let size = u32::from_le_bytes(data[..4].try_into().unwrap()) as usize;
+            assert_eq!(size + 4, data.len());
+
+            let call = encoding.decode_function_call(&data).unwrap();
+            assert_eq!(call.function_name, "Echo");
+            assert_eq!(call.function_call_type(), FunctionCallType::Host);
+            assert_eq!(call.expected_return_type, ReturnType::VecBytes);
+            assert_eq!(
+                call.parameters.unwrap(),
+                [
+                    ParameterValue::Int(-1),
+                    ParameterValue::String("hello".to_string()),
+                    ParameterValue::VecBytes(vec![1, 2, 3]),
+                    ParameterValue::Value(value.clone()),
+                ]
+            );
+
+            let data = encoding
+                .encode_function_call_result(&FunctionCallResult::new(Ok(ReturnValue::Void(()))))
+                .unwrap();
+            assert!(data.len() >= 8);
+            let result = encoding.decode_function_call_result(&data).unwrap();
+            assert_eq!(result.into_inner().unwrap(), ReturnValue::Void(()));
+
+            let error = GuestError::new(ErrorCode::HostFunctionError, "failed".to_string());
+            let data = encoding
+                .encode_function_call_result(&FunctionCallResult::new(Err(error)))
+                .unwrap();
+            let error = encoding
+                .decode_function_call_result(&data)
+                .unwrap()
+                .into_inner()
+                .unwrap_err();
+            assert_eq!(error.code, ErrorCode::HostFunctionError);
+            assert_eq!(error.message, "failed");
+        }
+    }
+
+    #[test]
+    fn result_descriptors_are_not_mistaken_for_results() {
+        let descriptor = ResultDescriptor {
+            gva: 0x1234_5678_9abc,
+            len: 1 << 33,
+        };
+        let data = descriptor.encode();
+        assert_eq!(ResultDescriptor::decode(&data), Some(descriptor));
+
+        let result = FunctionCallResult::new(Ok(ReturnValue::ULong(u64::MAX)));
+        let data = CallEncoding::FlatBuffers
+            .encode_function_call_result(&result)
+            .unwrap();
+        assert_eq!(ResultDescriptor::decode(&data), None);
+    }
+}
@@ -0,0 +1,166 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The paravirtual clocks the hypervisors keep up to date in guest
+//! memory, so that the guest can read the time without exiting the VM.
+//!
+//! The guest asks for a clock with an MSR write of the GPA of the clock
+//! page in scratch memory, at
+//! [`SCRATCH_TOP_CLOCK_PAGE_OFFSET`](crate::layout::SCRATCH_TOP_CLOCK_PAGE_OFFSET).
+//! KVM then writes a [`PvclockVcpuTimeInfo`] there, while Hyper-V, under
+//! MSHV and WHP, overlays the page with a [`HvReferenceTscPage`].
+
+/// The KVM MSR the guest writes the GPA of its [`PvclockVcpuTimeInfo`] to,
+/// with bit 0 set to enable it
+pub const MSR_KVM_SYSTEM_TIME_NEW: u32 = 0x4b56_4d01;
+/// The Hyper-V MSR the guest writes the GPA of its [`HvReferenceTscPage`]
+/// to, with bit 0 set to enable it
+pub const HV_X64_MSR_REFERENCE_TSC: u32 = 0x4000_0021;
+/// The Hyper-V MSR the guest can read the reference time from, when the
+/// reference TSC page is not valid
+pub const HV_X64_MSR_TIME_REF_COUNT: u32 = 0x4000_0020;
+
+/// The length of a Hyper-V reference time tick, in nanoseconds
+pub const HV_REFERENCE_TIME_TICK_NS: u64 = 100;
+
+/// The time information KVM keeps up to date for a vCPU, as `struct
+/// pvclock_vcpu_time_info` in Linux
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PvclockVcpuTimeInfo {
+    /// Odd while KVM is updating the other fields
+    pub version: u32,
+    pub pad0: u32,
+    /// The TSC at which `system_time` was taken
+    pub tsc_timestamp: u64,
+    /// The guest's clock at `tsc_timestamp`, in nanoseconds
+    pub system_time: u64,
+    /// The multiplier, as a 32.32 fixed point number, from shifted TSC
+    /// ticks to nanoseconds
+    pub tsc_to_system_mul: u32,
+    /// The shift applied to TSC ticks before they are multiplied
+    pub tsc_shift: i8,
+    pub flags: u8,
+    pub pad: [u8; 2],
+}
+
+impl PvclockVcpuTimeInfo {
+    /// Returns whether KVM has filled in the time information
+    pub fn is_valid(&self) -> bool {
+        self.version != 0 && self.tsc_to_system_mul != 0
+    }
+
+    /// The guest's clock, in nanoseconds, when the TSC reads `tsc`
+    pub fn nanos(&self, tsc: u64) -> u64 {
+        let delta = tsc.wrapping_sub(self.tsc_timestamp);
+        let delta = if self.tsc_shift < 0 {
+            delta >> -self.tsc_shift
+        } else {
+            delta << self.tsc_shift
+        };
+        let scaled = ((delta as u128 * self.tsc_to_system_mul as u128) >> 32) as u64;
+        self.system_time.wrapping_add(scaled)
+    }
+}
+
+/// The page Hyper-V overlays at the GPA written to
+/// [`HV_X64_MSR_REFERENCE_TSC`], from which the reference time, in
+/// [`HV_REFERENCE_TIME_TICK_NS`] ticks, can be computed from the TSC
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HvReferenceTscPage {
+    /// Changes whenever Hyper-V updates the other fields. Zero when the
+    /// page is not valid, and the reference time must be read from
+    /// [`HV_X64_MSR_TIME_REF_COUNT`] instead.
+    pub tsc_sequence: u32,
+    pub reserved: u32,
+    /// The multiplier, as a 0.64 fixed point number, from TSC ticks to
+    /// reference time ticks
+    pub tsc_scale: u64,
+    /// The reference time when the TSC reads zero
+    pub tsc_offset: i64,
+}
+
+impl HvReferenceTscPage {
+    /// Returns whether Hyper-V has filled in the page, and it can be used
+    pub fn is_valid(&self) -> bool {
+        self.tsc_sequence != 0 && self.tsc_sequence != u32::MAX
+    }
+
+    /// The reference time, in nanoseconds, when the TSC reads `tsc`
+    pub fn nanos(&self, tsc: u64) -> u64 {
+        let ticks = ((tsc as u128 * self.tsc_scale as u128) >> 64) as u64;
+        ticks.wrapping_add_signed(self.tsc_offset) * HV_REFERENCE_TIME_TICK_NS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HvReferenceTscPage, PvclockVcpuTimeInfo};
+
+    #[test]
+    fn pvclock_scales_tsc_to_nanos() {
+        // A 2 GHz TSC: two ticks per nanosecond
+        let info = PvclockVcpuTimeInfo {
+            version: 2,
+            tsc_timestamp: 1_000,
+            system_time: 5_000,
+            tsc_to_system_mul: 1 << 31,
+            tsc_shift: 0,
+            ..Default::default()
+        };
+        assert!(info.is_valid());
+        assert_eq!(info.nanos(1_000), 5_000);
+        assert_eq!(info.nanos(3_000), 6_000);
+
+        // The ticks are shifted before they are multiplied
+        let shifted = PvclockVcpuTimeInfo {
+            tsc_to_system_mul: 1 << 30,
+            tsc_shift: 1,
+            ..info
+        };
+        assert_eq!(shifted.nanos(3_000), 6_000);
+        let shifted = PvclockVcpuTimeInfo {
+            tsc_to_system_mul: 1 << 31,
+            tsc_shift: -1,
+            ..info
+        };
+        assert_eq!(shifted.nanos(5_000), 6_000);
+
+        assert!(!PvclockVcpuTimeInfo::default().is_valid());
+    }
+
+    #[test]
+    fn reference_tsc_page_scales_tsc_to_nanos() {
+        // A 1 GHz TSC: 100 ticks per reference time tick
+        let page = HvReferenceTscPage {
+            tsc_sequence: 1,
+            tsc_scale: u64::MAX / 100 + 1,
+            tsc_offset: 10,
+            ..Default::default()
+        };
+        assert!(page.is_valid());
+        assert_eq!(page.nanos(0), 1_000);
+        assert_eq!(page.nanos(1_000), 2_000);
+
+        assert!(!HvReferenceTscPage::default().is_valid());
+        let invalid = HvReferenceTscPage {
+            tsc_sequence: u32::MAX,
+            ..page
+        };
+        assert!(!invalid.is_valid());
+    }
+}
@@ -28,10 +28,23 @@ use crate::flatbuffers::hyperlight::generated::{
     FunctionCallType as FbFunctionCallType, Parameter, ParameterArgs,
     ParameterValue as FbParameterValue, hlbool, hlboolArgs, hldouble, hldoubleArgs, hlfloat,
     hlfloatArgs, hlint, hlintArgs, hllong, hllongArgs, hlstring, hlstringArgs, hluint, hluintArgs,
-    hlulong, hlulongArgs, hlvecbytes, hlvecbytesArgs,
+    hlulong, hlulongArgs, hlvalue, hlvalueArgs, hlvecbytes, hlvecbytesArgs, hlvecdouble,
+    hlvecdoubleArgs, hlvecfloat, hlvecfloatArgs,
 };
 
+/// The name of the guest function the host calls to ask the guest to shut
+/// down gracefully. Guests opt in to handling it by registering a guest
+/// function under this name, which takes no arguments and returns nothing.
+pub const SHUTDOWN_FUNCTION_NAME: &str = "__HyperlightShutdown";
+
+/// The name of the guest function the host calls once the guest has been
+/// initialised, to find out which guest functions it has registered. It
+/// takes no arguments and returns the functions' definitions, serialised
+/// as [`HostFunctionDetails`](super::host_function_details::HostFunctionDetails).
+pub const GUEST_FUNCTIONS_FUNCTION_NAME: &str = "__HyperlightGuestFunctions";
+
 /// The type of function call.
+#[cfg_attr(feature = "postcard", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum FunctionCallType {
     /// The function call is to a guest function.
@@ -40,7 +53,25 @@ pub enum FunctionCallType {
     Host,
 }
 
+/// A parameter of a function call that may borrow its data from the
+/// caller, for use with [`FunctionCall::encode_borrowed()`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterRef<'a> {
+    /// An owned parameter value
+    Value(ParameterValue),
+    /// A borrowed byte slice, which the callee receives as a
+    /// [`ParameterValue::VecBytes`]
+    Bytes(&'a [u8]),
+}
+
+impl From<ParameterValue> for ParameterRef<'_> {
+    fn from(value: ParameterValue) -> Self {
+        ParameterRef::Value(value)
+    }
+}
+
 /// `Functioncall` represents a call to a function in the guest or host.
+#[cfg_attr(feature = "postcard", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone)]
 pub struct FunctionCall {
     /// The function name
@@ -81,139 +112,243 @@ impl FunctionCall {
     /// does not reset the state of the builder. If you want to reuse the builder,
     /// you'll need to reset it first.
     pub fn encode<'a>(&self, builder: &'a mut FlatBufferBuilder) -> &'a [u8] {
-        let function_name = builder.create_string(&self.function_name);
+        let parameter_offsets: Vec<WIPOffset<Parameter>> = self
+            .parameters
+            .iter()
+            .flatten()
+            .map(|param| encode_parameter(builder, param))
+            .collect();
+        finish_function_call(
+            builder,
+            &self.function_name,
+            parameter_offsets,
+            &self.function_call_type,
+            self.expected_return_type,
+        )
+    }
 
-        let function_call_type = match self.function_call_type {
-            FunctionCallType::Guest => FbFunctionCallType::guest,
-            FunctionCallType::Host => FbFunctionCallType::host,
-        };
+    /// Encodes a call to `function_name` with `parameters` into the given
+    /// builder and returns the encoded data, as [`encode()`](Self::encode)
+    /// does for a `FunctionCall`. Borrowed byte slices among the parameters
+    /// are copied into the builder from the caller's buffer, which avoids
+    /// building an intermediate `Vec<u8>` for them.
+    ///
+    /// The same notes about reusing the builder apply.
+    pub fn encode_borrowed<'a>(
+        builder: &'a mut FlatBufferBuilder,
+        function_name: &str,
+        parameters: &[ParameterRef],
+        function_call_type: FunctionCallType,
+        expected_return_type: ReturnType,
+    ) -> &'a [u8] {
+        let parameter_offsets: Vec<WIPOffset<Parameter>> = parameters
+            .iter()
+            .map(|param| match param {
+                ParameterRef::Value(value) => encode_parameter(builder, value),
+                ParameterRef::Bytes(bytes) => encode_bytes_parameter(builder, bytes),
+            })
+            .collect();
+        finish_function_call(
+            builder,
+            function_name,
+            parameter_offsets,
+            &function_call_type,
+            expected_return_type,
+        )
+    }
+}
 
-        let expected_return_type = self.expected_return_type.into();
-
-        let parameters = match &self.parameters {
-            Some(p) if !p.is_empty() => {
-                let parameter_offsets: Vec<WIPOffset<Parameter>> = p
-                    .iter()
-                    .map(|param| match param {
-                        ParameterValue::Int(i) => {
-                            let hlint = hlint::create(builder, &hlintArgs { value: *i });
-                            Parameter::create(
-                                builder,
-                                &ParameterArgs {
-                                    value_type: FbParameterValue::hlint,
-                                    value: Some(hlint.as_union_value()),
-                                },
-                            )
-                        }
-                        ParameterValue::UInt(ui) => {
-                            let hluint = hluint::create(builder, &hluintArgs { value: *ui });
-                            Parameter::create(
-                                builder,
-                                &ParameterArgs {
-                                    value_type: FbParameterValue::hluint,
-                                    value: Some(hluint.as_union_value()),
-                                },
-                            )
-                        }
-                        ParameterValue::Long(l) => {
-                            let hllong = hllong::create(builder, &hllongArgs { value: *l });
-                            Parameter::create(
-                                builder,
-                                &ParameterArgs {
-                                    value_type: FbParameterValue::hllong,
-                                    value: Some(hllong.as_union_value()),
-                                },
-                            )
-                        }
-                        ParameterValue::ULong(ul) => {
-                            let hlulong = hlulong::create(builder, &hlulongArgs { value: *ul });
-                            Parameter::create(
-                                builder,
-                                &ParameterArgs {
-                                    value_type: FbParameterValue::hlulong,
-                                    value: Some(hlulong.as_union_value()),
-                                },
-                            )
-                        }
-                        ParameterValue::Float(f) => {
-                            let hlfloat = hlfloat::create(builder, &hlfloatArgs { value: *f });
-                            Parameter::create(
-                                builder,
-                                &ParameterArgs {
-                                    value_type: FbParameterValue::hlfloat,
-                                    value: Some(hlfloat.as_union_value()),
-                                },
-                            )
-                        }
-                        ParameterValue::Double(d) => {
-                            let hldouble = hldouble::create(builder, &hldoubleArgs { value: *d });
-                            Parameter::create(
-                                builder,
-                                &ParameterArgs {
-                                    value_type: FbParameterValue::hldouble,
-                                    value: Some(hldouble.as_union_value()),
-                                },
-                            )
-                        }
-                        ParameterValue::Bool(b) => {
-                            let hlbool = hlbool::create(builder, &hlboolArgs { value: *b });
-                            Parameter::create(
-                                builder,
-                                &ParameterArgs {
-                                    value_type: FbParameterValue::hlbool,
-                                    value: Some(hlbool.as_union_value()),
-                                },
-                            )
-                        }
-                        ParameterValue::String(s) => {
-                            let val = builder.create_string(s.as_str());
-                            let hlstring =
-                                hlstring::create(builder, &hlstringArgs { value: Some(val) });
-                            Parameter::create(
-                                builder,
-                                &ParameterArgs {
-                                    value_type: FbParameterValue::hlstring,
-                                    value: Some(hlstring.as_union_value()),
-                                },
-                            )
-                        }
-                        ParameterValue::VecBytes(v) => {
-                            let vec_bytes = builder.create_vector(v);
-                            let hlvecbytes = hlvecbytes::create(
-                                builder,
-                                &hlvecbytesArgs {
-                                    value: Some(vec_bytes),
-                                },
-                            );
-                            Parameter::create(
-                                builder,
-                                &ParameterArgs {
-                                    value_type: FbParameterValue::hlvecbytes,
-                                    value: Some(hlvecbytes.as_union_value()),
-                                },
-                            )
-                        }
-                    })
-                    .collect();
-                Some(builder.create_vector(&parameter_offsets))
-            }
-            _ => None,
-        };
+fn finish_function_call<'a, 'b>(
+    builder: &'a mut FlatBufferBuilder<'b>,
+    function_name: &str,
+    parameter_offsets: Vec<WIPOffset<Parameter<'b>>>,
+    function_call_type: &FunctionCallType,
+    expected_return_type: ReturnType,
+) -> &'a [u8] {
+    let function_name = builder.create_string(function_name);
 
-        let function_call = FbFunctionCall::create(
-            builder,
-            &FbFunctionCallArgs {
-                function_name: Some(function_name),
-                parameters,
-                function_call_type,
-                expected_return_type,
-            },
-        );
-        builder.finish_size_prefixed(function_call, None);
-        builder.finished_data()
+    let function_call_type = match function_call_type {
+        FunctionCallType::Guest => FbFunctionCallType::guest,
+        FunctionCallType::Host => FbFunctionCallType::host,
+    };
+
+    let parameters =
+        (!parameter_offsets.is_empty()).then(|| builder.create_vector(&parameter_offsets));
+
+    let function_call = FbFunctionCall::create(
+        builder,
+        &FbFunctionCallArgs {
+            function_name: Some(function_name),
+            parameters,
+            function_call_type,
+            expected_return_type: expected_return_type.into(),
+        },
+    );
+    builder.finish_size_prefixed(function_call, None);
+    builder.finished_data()
+}
+
+/// Encodes a single parameter value into the given builder
+fn encode_parameter<'a>(
+    builder: &mut FlatBufferBuilder<'a>,
+    param: &ParameterValue,
+) -> WIPOffset<Parameter<'a>> {
+    match param {
+        ParameterValue::Int(i) => {
+            let hlint = hlint::create(builder, &hlintArgs { value: *i });
+            Parameter::create(
+                builder,
+                &ParameterArgs {
+                    value_type: FbParameterValue::hlint,
+                    value: Some(hlint.as_union_value()),
+                },
+            )
+        }
+        ParameterValue::UInt(ui) => {
+            let hluint = hluint::create(builder, &hluintArgs { value: *ui });
+            Parameter::create(
+                builder,
+                &ParameterArgs {
+                    value_type: FbParameterValue::hluint,
+                    value: Some(hluint.as_union_value()),
+                },
+            )
+        }
+        ParameterValue::Long(l) => {
+            let hllong = hllong::create(builder, &hllongArgs { value: *l });
+            Parameter::create(
+                builder,
+                &ParameterArgs {
+                    value_type: FbParameterValue::hllong,
+                    value: Some(hllong.as_union_value()),
+                },
+            )
+        }
+        ParameterValue::ULong(ul) => {
+            let hlulong = hlulong::create(builder, &hlulongArgs { value: *ul });
+            Parameter::create(
+                builder,
+                &ParameterArgs {
+                    value_type: FbParameterValue::hlulong,
+                    value: Some(hlulong.as_union_value()),
+                },
+            )
+        }
+        ParameterValue::Float(f) => {
+            let hlfloat = hlfloat::create(builder, &hlfloatArgs { value: *f });
+            Parameter::create(
+                builder,
+                &ParameterArgs {
+                    value_type: FbParameterValue::hlfloat,
+                    value: Some(hlfloat.as_union_value()),
+                },
+            )
+        }
+        ParameterValue::Double(d) => {
+            let hldouble = hldouble::create(builder, &hldoubleArgs { value: *d });
+            Parameter::create(
+                builder,
+                &ParameterArgs {
+                    value_type: FbParameterValue::hldouble,
+                    value: Some(hldouble.as_union_value()),
+                },
+            )
+        }
+        ParameterValue::Bool(b) => {
+            let hlbool = hlbool::create(builder, &hlboolArgs { value: *b });
+            Parameter::create(
+                builder,
+                &ParameterArgs {
+                    value_type: FbParameterValue::hlbool,
+                    value: Some(hlbool.as_union_value()),
+                },
+            )
+        }
+        ParameterValue::String(s) => {
+            let val = builder.create_string(s.as_str());
+            let hlstring = hlstring::create(builder, &hlstringArgs { value: Some(val) });
+            Parameter::create(
+                builder,
+                &ParameterArgs {
+                    value_type: FbParameterValue::hlstring,
+                    value: Some(hlstring.as_union_value()),
+                },
+            )
+        }
+        ParameterValue::VecBytes(v) => encode_bytes_parameter(builder, v),
+        ParameterValue::Value(v) => {
+            let encoded = builder.create_vector(&v.encode());
+            let hlvalue = hlvalue::create(
+                builder,
+                &hlvalueArgs {
+                    value: Some(encoded),
+                },
+            );
+            Parameter::create(
+                builder,
+                &ParameterArgs {
+                    value_type: FbParameterValue::hlvalue,
+                    value: Some(hlvalue.as_union_value()),
+                },
+            )
+        }
+        ParameterValue::VecFloat(v) => {
+            let vec_float = builder.create_vector(v);
+            let hlvecfloat = hlvecfloat::create(
+                builder,
+                &hlvecfloatArgs {
+                    value: Some(vec_float),
+                },
+            );
+            Parameter::create(
+                builder,
+                &ParameterArgs {
+                    value_type: FbParameterValue::hlvecfloat,
+                    value: Some(hlvecfloat.as_union_value()),
+                },
+            )
+        }
+        ParameterValue::VecDouble(v) => {
+            let vec_double = builder.create_vector(v);
+            let hlvecdouble = hlvecdouble::create(
+                builder,
+                &hlvecdoubleArgs {
+                    value: Some(vec_double),
+                },
+            );
+            Parameter::create(
+                builder,
+                &ParameterArgs {
+                    value_type: FbParameterValue::hlvecdouble,
+                    value: Some(hlvecdouble.as_union_value()),
+                },
+            )
+        }
     }
 }
 
+/// Encodes a byte vector parameter into the given builder, copying it
+/// straight from `bytes`
+fn encode_bytes_parameter<'a>(
+    builder: &mut FlatBufferBuilder<'a>,
+    bytes: &[u8],
+) -> WIPOffset<Parameter<'a>> {
+    let vec_bytes = builder.create_vector(bytes);
+    let hlvecbytes = hlvecbytes::create(
+        builder,
+        &hlvecbytesArgs {
+            value: Some(vec_bytes),
+        },
+    );
+    Parameter::create(
+        builder,
+        &ParameterArgs {
+            value_type: FbParameterValue::hlvecbytes,
+            value: Some(hlvecbytes.as_union_value()),
+        },
+    )
+}
+
 #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
 pub fn validate_guest_function_call_buffer(function_call_buffer: &[u8]) -> Result<()> {
     let guest_function_call_fb = size_prefixed_root::<FbFunctionCall>(function_call_buffer)
@@ -327,4 +462,92 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn encode_borrowed_matches_encode() -> Result<()> {
+        let bytes = vec![1u8, 2, 3, 4];
+        let mut builder = FlatBufferBuilder::new();
+        let owned = FunctionCall::new(
+            "GetSizePrefixedBuffer".to_string(),
+            Some(vec![
+                ParameterValue::VecBytes(bytes.clone()),
+                ParameterValue::Int(5),
+            ]),
+            FunctionCallType::Guest,
+            ReturnType::VecBytes,
+        )
+        .encode(&mut builder)
+        .to_vec();
+
+        let mut builder = FlatBufferBuilder::new();
+        let borrowed = FunctionCall::encode_borrowed(
+            &mut builder,
+            "GetSizePrefixedBuffer",
+            &[
+                ParameterRef::Bytes(&bytes),
+                ParameterRef::Value(ParameterValue::Int(5)),
+            ],
+            FunctionCallType::Guest,
+            ReturnType::VecBytes,
+        );
+        assert_eq!(borrowed, owned.as_slice());
+
+        let function_call = FunctionCall::try_from(borrowed)?;
+        assert_eq!(
+            function_call.parameters,
+            Some(vec![
+                ParameterValue::VecBytes(bytes),
+                ParameterValue::Int(5)
+            ])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn float_vectors_are_aligned_in_the_buffer() -> Result<()> {
+        let floats = vec![1.5f32, -2.25, 3.0];
+        let doubles = vec![core::f64::consts::PI, -0.5];
+        let mut builder = FlatBufferBuilder::new();
+        let data = FunctionCall::new(
+            "Dot".to_string(),
+            Some(vec![
+                ParameterValue::VecBytes(vec![1, 2, 3]),
+                ParameterValue::VecFloat(floats.clone()),
+                ParameterValue::VecDouble(doubles.clone()),
+            ]),
+            FunctionCallType::Guest,
+            ReturnType::Double,
+        )
+        .encode(&mut builder);
+        assert_eq!(data.len() % 8, 0);
+
+        let call = size_prefixed_root::<FbFunctionCall>(data).unwrap();
+        let parameters = call.parameters().unwrap();
+        let offset = |bytes: &[u8]| bytes.as_ptr() as usize - data.as_ptr() as usize;
+        let vec_float = parameters
+            .get(1)
+            .value_as_hlvecfloat()
+            .unwrap()
+            .value()
+            .unwrap();
+        assert_eq!(offset(vec_float.bytes()) % 4, 0);
+        let vec_double = parameters
+            .get(2)
+            .value_as_hlvecdouble()
+            .unwrap()
+            .value()
+            .unwrap();
+        assert_eq!(offset(vec_double.bytes()) % 8, 0);
+
+        let function_call = FunctionCall::try_from(data)?;
+        assert_eq!(
+            function_call.parameters,
+            Some(vec![
+                ParameterValue::VecBytes(vec![1, 2, 3]),
+                ParameterValue::VecFloat(floats),
+                ParameterValue::VecDouble(doubles),
+            ])
+        );
+        Ok(())
+    }
 }
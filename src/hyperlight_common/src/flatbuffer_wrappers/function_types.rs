@@ -23,6 +23,7 @@ use flatbuffers::size_prefixed_root;
 use tracing::{Span, instrument};
 
 use super::guest_error::GuestError;
+use super::value::Value;
 use crate::flatbuffers::hyperlight::generated::{
     FunctionCallResult as FbFunctionCallResult, FunctionCallResultArgs as FbFunctionCallResultArgs,
     FunctionCallResultType, Parameter, ParameterType as FbParameterType,
@@ -30,9 +31,10 @@ use crate::flatbuffers::hyperlight::generated::{
     ReturnValueBox, ReturnValueBoxArgs, hlbool, hlboolArgs, hldouble, hldoubleArgs, hlfloat,
     hlfloatArgs, hlint, hlintArgs, hllong, hllongArgs, hlsizeprefixedbuffer,
     hlsizeprefixedbufferArgs, hlstring, hlstringArgs, hluint, hluintArgs, hlulong, hlulongArgs,
-    hlvoid, hlvoidArgs,
+    hlvalue, hlvalueArgs, hlvoid, hlvoidArgs,
 };
 
+#[cfg_attr(feature = "postcard", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionCallResult(core::result::Result<ReturnValue, GuestError>);
 
 impl FunctionCallResult {
@@ -99,6 +101,11 @@ impl FunctionCallResult {
                         let off = hlvoid::create(builder, &hlvoidArgs {});
                         (Some(off.as_union_value()), FbReturnValue::hlvoid)
                     }
+                    ReturnValue::Value(v) => {
+                        let val = builder.create_vector(&v.encode());
+                        let off = hlvalue::create(builder, &hlvalueArgs { value: Some(val) });
+                        (Some(off.as_union_value()), FbReturnValue::hlvalue)
+                    }
                 };
                 let rv_box =
                     ReturnValueBox::create(builder, &ReturnValueBoxArgs { value, value_type });
@@ -116,11 +123,14 @@ impl FunctionCallResult {
                 // Encode GuestError
                 let code: crate::flatbuffers::hyperlight::generated::ErrorCode = ge.code.into();
                 let msg = builder.create_string(&ge.message);
+                let backtrace =
+                    (!ge.backtrace.is_empty()).then(|| builder.create_vector(&ge.backtrace));
                 let guest_error = crate::flatbuffers::hyperlight::generated::GuestError::create(
                     builder,
                     &crate::flatbuffers::hyperlight::generated::GuestErrorArgs {
                         code,
                         message: Some(msg),
+                        backtrace,
                     },
                 );
                 let fcr = FbFunctionCallResult::create(
@@ -170,10 +180,15 @@ impl TryFrom<&[u8]> for FunctionCallResult {
                     .message()
                     .map(|s| s.to_string())
                     .unwrap_or_default();
+                let backtrace = guest_error_table
+                    .backtrace()
+                    .map(|frames| frames.iter().collect())
+                    .unwrap_or_default();
                 Ok(FunctionCallResult(Err(GuestError::new(
                     code.into(),
                     message,
-                ))))
+                )
+                .with_backtrace(backtrace))))
             }
             other => {
                 bail!("Unexpected function call result type: {:?}", other)
@@ -184,6 +199,7 @@ impl TryFrom<&[u8]> for FunctionCallResult {
 
 /// Supported parameter types with values for function calling.
 #[cfg_attr(feature = "fuzzing", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "postcard", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub enum ParameterValue {
     /// i32
@@ -203,7 +219,15 @@ pub enum ParameterValue {
     /// bool
     Bool(bool),
     /// `Vec<u8>`
-    VecBytes(Vec<u8>),
+    VecBytes(
+        #[cfg_attr(feature = "postcard", serde(with = "crate::call_encoding::bytes"))] Vec<u8>,
+    ),
+    /// A structured value: a struct, an optional or a map
+    Value(Value),
+    /// `Vec<f32>`
+    VecFloat(Vec<f32>),
+    /// `Vec<f64>`
+    VecDouble(Vec<f64>),
 }
 
 /// Supported parameter types for function calling.
@@ -228,9 +252,16 @@ pub enum ParameterType {
     Bool,
     /// `Vec<u8>`
     VecBytes,
+    /// A structured value: a struct, an optional or a map
+    Value,
+    /// `Vec<f32>`
+    VecFloat,
+    /// `Vec<f64>`
+    VecDouble,
 }
 
 /// Supported return types with values from function calling.
+#[cfg_attr(feature = "postcard", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub enum ReturnValue {
     /// i32
@@ -252,11 +283,16 @@ pub enum ReturnValue {
     /// ()
     Void(()),
     /// `Vec<u8>`
-    VecBytes(Vec<u8>),
+    VecBytes(
+        #[cfg_attr(feature = "postcard", serde(with = "crate::call_encoding::bytes"))] Vec<u8>,
+    ),
+    /// A structured value: a struct, an optional or a map
+    Value(Value),
 }
 
 /// Supported return types from function calling.
 #[cfg_attr(feature = "fuzzing", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "postcard", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
 #[repr(C)]
 pub enum ReturnType {
@@ -281,6 +317,8 @@ pub enum ReturnType {
     Void,
     /// `Vec<u8>`
     VecBytes,
+    /// A structured value: a struct, an optional or a map
+    Value,
 }
 
 impl From<&ParameterValue> for ParameterType {
@@ -296,6 +334,9 @@ impl From<&ParameterValue> for ParameterType {
             ParameterValue::String(_) => ParameterType::String,
             ParameterValue::Bool(_) => ParameterType::Bool,
             ParameterValue::VecBytes(_) => ParameterType::VecBytes,
+            ParameterValue::Value(_) => ParameterType::Value,
+            ParameterValue::VecFloat(_) => ParameterType::VecFloat,
+            ParameterValue::VecDouble(_) => ParameterType::VecDouble,
         }
     }
 }
@@ -334,6 +375,18 @@ impl TryFrom<Parameter<'_>> for ParameterValue {
             FbParameterValue::hlvecbytes => param.value_as_hlvecbytes().map(|hlvecbytes| {
                 ParameterValue::VecBytes(hlvecbytes.value().unwrap_or_default().bytes().to_vec())
             }),
+            FbParameterValue::hlvalue => match param.value_as_hlvalue() {
+                Some(hlvalue) => Some(ParameterValue::Value(Value::decode(
+                    hlvalue.value().unwrap_or_default().bytes(),
+                )?)),
+                None => None,
+            },
+            FbParameterValue::hlvecfloat => param.value_as_hlvecfloat().map(|hlvecfloat| {
+                ParameterValue::VecFloat(hlvecfloat.value().unwrap_or_default().iter().collect())
+            }),
+            FbParameterValue::hlvecdouble => param.value_as_hlvecdouble().map(|hlvecdouble| {
+                ParameterValue::VecDouble(hlvecdouble.value().unwrap_or_default().iter().collect())
+            }),
             other => {
                 bail!("Unexpected flatbuffer parameter value type: {:?}", other);
             }
@@ -355,6 +408,9 @@ impl From<ParameterType> for FbParameterType {
             ParameterType::String => FbParameterType::hlstring,
             ParameterType::Bool => FbParameterType::hlbool,
             ParameterType::VecBytes => FbParameterType::hlvecbytes,
+            ParameterType::Value => FbParameterType::hlvalue,
+            ParameterType::VecFloat => FbParameterType::hlvecfloat,
+            ParameterType::VecDouble => FbParameterType::hlvecdouble,
         }
     }
 }
@@ -373,6 +429,7 @@ impl From<ReturnType> for FbReturnType {
             ReturnType::Bool => FbReturnType::hlbool,
             ReturnType::Void => FbReturnType::hlvoid,
             ReturnType::VecBytes => FbReturnType::hlsizeprefixedbuffer,
+            ReturnType::Value => FbReturnType::hlvalue,
         }
     }
 }
@@ -391,6 +448,9 @@ impl TryFrom<FbParameterType> for ParameterType {
             FbParameterType::hlstring => Ok(ParameterType::String),
             FbParameterType::hlbool => Ok(ParameterType::Bool),
             FbParameterType::hlvecbytes => Ok(ParameterType::VecBytes),
+            FbParameterType::hlvalue => Ok(ParameterType::Value),
+            FbParameterType::hlvecfloat => Ok(ParameterType::VecFloat),
+            FbParameterType::hlvecdouble => Ok(ParameterType::VecDouble),
             _ => {
                 bail!("Unexpected flatbuffer parameter type: {:?}", value)
             }
@@ -413,6 +473,7 @@ impl TryFrom<FbReturnType> for ReturnType {
             FbReturnType::hlbool => Ok(ReturnType::Bool),
             FbReturnType::hlvoid => Ok(ReturnType::Void),
             FbReturnType::hlsizeprefixedbuffer => Ok(ReturnType::VecBytes),
+            FbReturnType::hlvalue => Ok(ReturnType::Value),
             _ => {
                 bail!("Unexpected flatbuffer return type: {:?}", value)
             }
@@ -537,6 +598,45 @@ impl TryFrom<ParameterValue> for Vec<u8> {
     }
 }
 
+impl TryFrom<ParameterValue> for Vec<f32> {
+    type Error = Error;
+    #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
+    fn try_from(value: ParameterValue) -> Result<Self> {
+        match value {
+            ParameterValue::VecFloat(v) => Ok(v),
+            _ => {
+                bail!("Unexpected parameter value type: {:?}", value)
+            }
+        }
+    }
+}
+
+impl TryFrom<ParameterValue> for Vec<f64> {
+    type Error = Error;
+    #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
+    fn try_from(value: ParameterValue) -> Result<Self> {
+        match value {
+            ParameterValue::VecDouble(v) => Ok(v),
+            _ => {
+                bail!("Unexpected parameter value type: {:?}", value)
+            }
+        }
+    }
+}
+
+impl TryFrom<ParameterValue> for Value {
+    type Error = Error;
+    #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
+    fn try_from(value: ParameterValue) -> Result<Self> {
+        match value {
+            ParameterValue::Value(v) => Ok(v),
+            _ => {
+                bail!("Unexpected parameter value type: {:?}", value)
+            }
+        }
+    }
+}
+
 impl TryFrom<ReturnValue> for i32 {
     type Error = Error;
     #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
@@ -654,6 +754,19 @@ impl TryFrom<ReturnValue> for Vec<u8> {
     }
 }
 
+impl TryFrom<ReturnValue> for Value {
+    type Error = Error;
+    #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
+    fn try_from(value: ReturnValue) -> Result<Self> {
+        match value {
+            ReturnValue::Value(v) => Ok(v),
+            _ => {
+                bail!("Unexpected return value type: {:?}", value)
+            }
+        }
+    }
+}
+
 impl TryFrom<ReturnValue> for () {
     type Error = Error;
     #[cfg_attr(feature = "tracing", instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace"))]
@@ -731,6 +844,14 @@ impl TryFrom<ReturnValueBox<'_>> for ReturnValue {
                 };
                 Ok(ReturnValue::VecBytes(hlvecbytes.unwrap_or(Vec::new())))
             }
+            FbReturnValue::hlvalue => {
+                let hlvalue = return_value_box
+                    .value_as_hlvalue()
+                    .ok_or_else(|| anyhow!("Failed to get hlvalue from return value"))?;
+                Ok(ReturnValue::Value(Value::decode(
+                    hlvalue.value().unwrap_or_default().bytes(),
+                )?))
+            }
             other => {
                 bail!("Unexpected flatbuffer return value type: {:?}", other)
             }
@@ -946,6 +1067,28 @@ impl TryFrom<&ReturnValue> for Vec<u8> {
                 builder.finish_size_prefixed(fcr, None);
                 builder.finished_data().to_vec()
             }
+            ReturnValue::Value(v) => {
+                let off = {
+                    let val = builder.create_vector(&v.encode());
+                    hlvalue::create(&mut builder, &hlvalueArgs { value: Some(val) })
+                };
+                let rv_box = ReturnValueBox::create(
+                    &mut builder,
+                    &ReturnValueBoxArgs {
+                        value: Some(off.as_union_value()),
+                        value_type: FbReturnValue::hlvalue,
+                    },
+                );
+                let fcr = FbFunctionCallResult::create(
+                    &mut builder,
+                    &FbFunctionCallResultArgs {
+                        result: Some(rv_box.as_union_value()),
+                        result_type: FunctionCallResultType::ReturnValueBox,
+                    },
+                );
+                builder.finish_size_prefixed(fcr, None);
+                builder.finished_data().to_vec()
+            }
         };
 
         Ok(result_bytes)
@@ -982,5 +1125,18 @@ mod tests {
         let error = function_call_result.into_inner().unwrap_err();
         assert_eq!(error.code, test_error.code);
         assert_eq!(error.message, test_error.message);
+        assert!(error.backtrace.is_empty());
+    }
+
+    #[test]
+    fn encode_error_result_with_backtrace() {
+        let mut builder = FlatBufferBuilder::new();
+        let test_error = GuestError::new(ErrorCode::GuestError, "failed".to_string())
+            .with_backtrace(alloc::vec![0x1234, 0x5678]);
+        let test_data = FunctionCallResult::new(Err(test_error)).encode(&mut builder);
+
+        let function_call_result = FunctionCallResult::try_from(test_data).unwrap();
+        let error = function_call_result.into_inner().unwrap_err();
+        assert_eq!(error.backtrace, [0x1234, 0x5678]);
     }
 }
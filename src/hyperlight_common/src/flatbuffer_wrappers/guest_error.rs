@@ -17,6 +17,7 @@ limitations under the License.
 extern crate flatbuffers;
 
 use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
 #[cfg(feature = "tracing")]
 use tracing::{Span, instrument};
@@ -24,6 +25,11 @@ use tracing::{Span, instrument};
 use crate::flatbuffers::hyperlight::generated::ErrorCode as FbErrorCode;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "postcard",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "u64", into = "u64")
+)]
 #[repr(C)]
 /// `ErrorCode` represents an error that occurred in the Hyperlight Guest.
 pub enum ErrorCode {
@@ -43,6 +49,13 @@ pub enum ErrorCode {
     GuestError = 15,
     ArrayLengthParamIsMissing = 16,
     HostFunctionError = 17,
+    HostFunctionPermissionDenied = 18,
+    HostFunctionThrottled = 19,
+    AbiMismatch = 20,
+    HostFunctionTimedOut = 21,
+    HostFunctionPanicked = 22,
+    GuestPanicked = 23,
+    UnsupportedCallEncoding = 24,
 }
 
 impl From<ErrorCode> for FbErrorCode {
@@ -68,6 +81,13 @@ impl From<ErrorCode> for FbErrorCode {
             ErrorCode::GuestError => Self::GuestError,
             ErrorCode::ArrayLengthParamIsMissing => Self::ArrayLengthParamIsMissing,
             ErrorCode::HostFunctionError => Self::HostError,
+            ErrorCode::HostFunctionPermissionDenied => Self::HostFunctionPermissionDenied,
+            ErrorCode::HostFunctionThrottled => Self::HostFunctionThrottled,
+            ErrorCode::AbiMismatch => Self::AbiMismatch,
+            ErrorCode::HostFunctionTimedOut => Self::HostFunctionTimedOut,
+            ErrorCode::HostFunctionPanicked => Self::HostFunctionPanicked,
+            ErrorCode::GuestPanicked => Self::GuestPanicked,
+            ErrorCode::UnsupportedCallEncoding => Self::UnsupportedCallEncoding,
         }
     }
 }
@@ -94,6 +114,13 @@ impl From<FbErrorCode> for ErrorCode {
             FbErrorCode::GuestError => Self::GuestError,
             FbErrorCode::ArrayLengthParamIsMissing => Self::ArrayLengthParamIsMissing,
             FbErrorCode::HostError => Self::HostFunctionError,
+            FbErrorCode::HostFunctionPermissionDenied => Self::HostFunctionPermissionDenied,
+            FbErrorCode::HostFunctionThrottled => Self::HostFunctionThrottled,
+            FbErrorCode::AbiMismatch => Self::AbiMismatch,
+            FbErrorCode::HostFunctionTimedOut => Self::HostFunctionTimedOut,
+            FbErrorCode::HostFunctionPanicked => Self::HostFunctionPanicked,
+            FbErrorCode::GuestPanicked => Self::GuestPanicked,
+            FbErrorCode::UnsupportedCallEncoding => Self::UnsupportedCallEncoding,
             _ => Self::UnknownError,
         }
     }
@@ -118,6 +145,13 @@ impl From<u64> for ErrorCode {
             15 => Self::GuestError,
             16 => Self::ArrayLengthParamIsMissing,
             17 => Self::HostFunctionError,
+            18 => Self::HostFunctionPermissionDenied,
+            19 => Self::HostFunctionThrottled,
+            20 => Self::AbiMismatch,
+            21 => Self::HostFunctionTimedOut,
+            22 => Self::HostFunctionPanicked,
+            23 => Self::GuestPanicked,
+            24 => Self::UnsupportedCallEncoding,
             _ => Self::UnknownError,
         }
     }
@@ -142,6 +176,13 @@ impl From<ErrorCode> for u64 {
             ErrorCode::GuestError => 15,
             ErrorCode::ArrayLengthParamIsMissing => 16,
             ErrorCode::HostFunctionError => 17,
+            ErrorCode::HostFunctionPermissionDenied => 18,
+            ErrorCode::HostFunctionThrottled => 19,
+            ErrorCode::AbiMismatch => 20,
+            ErrorCode::HostFunctionTimedOut => 21,
+            ErrorCode::HostFunctionPanicked => 22,
+            ErrorCode::GuestPanicked => 23,
+            ErrorCode::UnsupportedCallEncoding => 24,
         }
     }
 }
@@ -169,23 +210,46 @@ impl From<ErrorCode> for String {
             ErrorCode::GuestError => "GuestError".to_string(),
             ErrorCode::ArrayLengthParamIsMissing => "ArrayLengthParamIsMissing".to_string(),
             ErrorCode::HostFunctionError => "HostFunctionError".to_string(),
+            ErrorCode::HostFunctionPermissionDenied => "HostFunctionPermissionDenied".to_string(),
+            ErrorCode::HostFunctionThrottled => "HostFunctionThrottled".to_string(),
+            ErrorCode::AbiMismatch => "AbiMismatch".to_string(),
+            ErrorCode::HostFunctionTimedOut => "HostFunctionTimedOut".to_string(),
+            ErrorCode::HostFunctionPanicked => "HostFunctionPanicked".to_string(),
+            ErrorCode::GuestPanicked => "GuestPanicked".to_string(),
+            ErrorCode::UnsupportedCallEncoding => "UnsupportedCallEncoding".to_string(),
         }
     }
 }
 
 /// `GuestError` represents an error that occurred in the Hyperlight Guest.
+#[cfg_attr(feature = "postcard", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, Clone)]
 pub struct GuestError {
     /// The error code.
     pub code: ErrorCode,
     /// The error message.
     pub message: String,
+    /// The return addresses of the guest's stack frames where the error
+    /// was raised, innermost first, if the guest captured them.
+    #[cfg_attr(feature = "postcard", serde(default))]
+    pub backtrace: Vec<u64>,
 }
 
 impl GuestError {
     #[cfg_attr(feature = "tracing", instrument(skip_all, parent = Span::current(), level= "Trace"))]
     pub fn new(code: ErrorCode, message: String) -> Self {
-        Self { code, message }
+        Self {
+            code,
+            message,
+            backtrace: Vec::new(),
+        }
+    }
+
+    /// Attach the return addresses of the guest's stack frames where the
+    /// error was raised, innermost first
+    pub fn with_backtrace(mut self, backtrace: Vec<u64>) -> Self {
+        self.backtrace = backtrace;
+        self
     }
 }
 
@@ -195,6 +259,7 @@ impl Default for GuestError {
         Self {
             code: ErrorCode::NoError,
             message: String::new(),
+            backtrace: Vec::new(),
         }
     }
 }
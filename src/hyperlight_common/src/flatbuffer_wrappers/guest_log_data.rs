@@ -24,7 +24,8 @@ use tracing::{Span, instrument};
 
 use super::guest_log_level::LogLevel;
 use crate::flatbuffers::hyperlight::generated::{
-    GuestLogData as FbGuestLogData, GuestLogDataArgs as FbGuestLogDataArgs, LogLevel as FbLogLevel,
+    GuestLogData as FbGuestLogData, GuestLogDataArgs as FbGuestLogDataArgs, KeyValue as FbKeyValue,
+    KeyValueArgs as FbKeyValueArgs, LogLevel as FbLogLevel,
 };
 
 /// The guest log data for a VM sandbox
@@ -37,6 +38,11 @@ pub struct GuestLogData {
     pub caller: String,
     pub source_file: String,
     pub line: u32,
+    /// The target of the record, which is empty if the guest did not
+    /// give one
+    pub target: String,
+    /// The structured fields of the record, as key/value pairs
+    pub fields: Vec<(String, String)>,
 }
 
 impl GuestLogData {
@@ -56,8 +62,22 @@ impl GuestLogData {
             caller,
             source_file,
             line,
+            target: String::new(),
+            fields: Vec::new(),
         }
     }
+
+    /// Set the target of the record
+    pub fn with_target(mut self, target: String) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Set the structured fields of the record
+    pub fn with_fields(mut self, fields: Vec<(String, String)>) -> Self {
+        self.fields = fields;
+        self
+    }
 }
 
 impl TryFrom<&[u8]> for GuestLogData {
@@ -72,6 +92,17 @@ impl TryFrom<&[u8]> for GuestLogData {
         let caller = convert_generated_option("caller", gld_gen.caller())?;
         let source_file = convert_generated_option("source file", gld_gen.source_file())?;
         let line = gld_gen.line();
+        // Guests built before records had a target and fields send neither
+        let target = gld_gen.target().unwrap_or_default().to_string();
+        let fields = gld_gen
+            .fields()
+            .map(|fields| {
+                fields
+                    .iter()
+                    .map(|kv| (kv.key().to_string(), kv.value().to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
 
         Ok(GuestLogData {
             message,
@@ -80,6 +111,8 @@ impl TryFrom<&[u8]> for GuestLogData {
             caller,
             source_file,
             line,
+            target,
+            fields,
         })
     }
 }
@@ -94,6 +127,19 @@ impl TryFrom<&GuestLogData> for Vec<u8> {
         let caller = builder.create_string(&value.caller);
         let source_file = builder.create_string(&value.source_file);
         let level = FbLogLevel::from(&value.level);
+        let target = builder.create_string(&value.target);
+        let fields: Vec<_> = value
+            .fields
+            .iter()
+            .map(|(key, value)| {
+                let args = FbKeyValueArgs {
+                    key: Some(builder.create_string(key)),
+                    value: Some(builder.create_string(value)),
+                };
+                FbKeyValue::create(&mut builder, &args)
+            })
+            .collect();
+        let fields = builder.create_vector(&fields);
 
         let guest_log_data_fb = FbGuestLogData::create(
             &mut builder,
@@ -104,6 +150,8 @@ impl TryFrom<&GuestLogData> for Vec<u8> {
                 caller: Some(caller),
                 source_file: Some(source_file),
                 line: value.line,
+                target: Some(target),
+                fields: Some(fields),
             },
         );
         builder.finish_size_prefixed(guest_log_data_fb, None);
@@ -126,3 +174,32 @@ fn convert_generated_option(field_name: &str, opt: Option<&str>) -> Result<Strin
     opt.map(|s| s.to_string())
         .ok_or_else(|| anyhow!("Missing field: {}", field_name))
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::GuestLogData;
+    use crate::flatbuffer_wrappers::guest_log_level::LogLevel;
+
+    #[test]
+    fn target_and_fields_round_trip() {
+        let data = GuestLogData::new(
+            "request served".to_string(),
+            "guest::server".to_string(),
+            LogLevel::Information,
+            "guest::server".to_string(),
+            "server.rs".to_string(),
+            42,
+        )
+        .with_target("http".to_string())
+        .with_fields(vec![
+            ("status".to_string(), "200".to_string()),
+            ("path".to_string(), "/index.html".to_string()),
+        ]);
+        let bytes: Vec<u8> = (&data).try_into().unwrap();
+        assert_eq!(GuestLogData::try_from(bytes.as_slice()).unwrap(), data);
+    }
+}
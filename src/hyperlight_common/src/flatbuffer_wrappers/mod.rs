@@ -29,3 +29,5 @@ pub mod host_function_definition;
 /// cbindgen:ignore
 pub mod host_function_details;
 pub mod util;
+/// cbindgen:ignore
+pub mod value;
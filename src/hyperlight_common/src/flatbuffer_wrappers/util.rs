@@ -18,7 +18,9 @@ use alloc::vec::Vec;
 
 use flatbuffers::FlatBufferBuilder;
 
+use crate::flatbuffer_wrappers::function_call::ParameterRef;
 use crate::flatbuffer_wrappers::function_types::ParameterValue;
+use crate::flatbuffer_wrappers::value::Value;
 use crate::flatbuffers::hyperlight::generated::{
     FunctionCallResult as FbFunctionCallResult, FunctionCallResultArgs as FbFunctionCallResultArgs,
     FunctionCallResultType as FbFunctionCallResultType, ReturnValue as FbReturnValue,
@@ -28,8 +30,8 @@ use crate::flatbuffers::hyperlight::generated::{
     hllongArgs as FbhllongArgs, hlsizeprefixedbuffer as Fbhlsizeprefixedbuffer,
     hlsizeprefixedbufferArgs as FbhlsizeprefixedbufferArgs, hlstring as Fbhlstring,
     hlstringArgs as FbhlstringArgs, hluint as Fbhluint, hluintArgs as FbhluintArgs,
-    hlulong as Fbhlulong, hlulongArgs as FbhlulongArgs, hlvoid as Fbhlvoid,
-    hlvoidArgs as FbhlvoidArgs,
+    hlulong as Fbhlulong, hlulongArgs as FbhlulongArgs, hlvalue as Fbhlvalue,
+    hlvalueArgs as FbhlvalueArgs, hlvoid as Fbhlvoid, hlvoidArgs as FbhlvoidArgs,
 };
 
 /// Flatbuffer-encodes the given value
@@ -232,6 +234,29 @@ impl FlatbufferSerializable for bool {
     }
 }
 
+impl FlatbufferSerializable for &Value {
+    fn serialize(&self, builder: &mut FlatBufferBuilder) -> FbFunctionCallResultArgs {
+        let vec_off = builder.create_vector(&self.encode());
+        let off = Fbhlvalue::create(
+            builder,
+            &FbhlvalueArgs {
+                value: Some(vec_off),
+            },
+        );
+        let rv_box = ReturnValueBox::create(
+            builder,
+            &ReturnValueBoxArgs {
+                value_type: FbReturnValue::hlvalue,
+                value: Some(off.as_union_value()),
+            },
+        );
+        FbFunctionCallResultArgs {
+            result_type: FbFunctionCallResultType::ReturnValueBox,
+            result: Some(rv_box.as_union_value()),
+        }
+    }
+}
+
 /// Estimates the required buffer capacity for encoding a FunctionCall with the given parameters.
 /// This helps avoid reallocation during FlatBuffer encoding when passing large slices and strings.
 ///
@@ -246,26 +271,62 @@ impl FlatbufferSerializable for bool {
 /// on https://flatbuffers.dev/internals/ and https://github.com/dvidelabs/flatcc/blob/f064cefb2034d1e7407407ce32a6085c322212a7/doc/binary-format.md#flatbuffers-binary-format
 #[inline] // allow cross-crate inlining (for hyperlight-host calls)
 pub fn estimate_flatbuffer_capacity(function_name: &str, args: &[ParameterValue]) -> usize {
+    estimate_capacity(
+        function_name,
+        args.len(),
+        args.iter().map(estimate_parameter_size),
+    )
+}
+
+/// Estimates the required buffer capacity for encoding a FunctionCall with
+/// the given, possibly borrowed, parameters. See
+/// [`estimate_flatbuffer_capacity()`].
+#[inline]
+pub fn estimate_flatbuffer_capacity_borrowed(function_name: &str, args: &[ParameterRef]) -> usize {
+    estimate_capacity(
+        function_name,
+        args.len(),
+        args.iter().map(|arg| match arg {
+            ParameterRef::Value(v) => estimate_parameter_size(v),
+            ParameterRef::Bytes(v) => v.len() + 20,
+        }),
+    )
+}
+
+#[inline]
+fn estimate_parameter_size(arg: &ParameterValue) -> usize {
+    match arg {
+        ParameterValue::String(s) => s.len() + 20,
+        ParameterValue::VecBytes(v) => v.len() + 20,
+        ParameterValue::Value(v) => v.encoded_len() + 20,
+        ParameterValue::VecFloat(v) => v.len() * 4 + 20,
+        ParameterValue::VecDouble(v) => v.len() * 8 + 24,
+        ParameterValue::Int(_) | ParameterValue::UInt(_) => 16,
+        ParameterValue::Long(_) | ParameterValue::ULong(_) => 20,
+        ParameterValue::Float(_) => 16,
+        ParameterValue::Double(_) => 20,
+        ParameterValue::Bool(_) => 12,
+    }
+}
+
+#[inline]
+fn estimate_capacity(
+    function_name: &str,
+    num_args: usize,
+    arg_sizes: impl Iterator<Item = usize>,
+) -> usize {
     let mut estimated_capacity = 20;
 
     // Function name overhead
     estimated_capacity += function_name.len() + 12;
 
     // Parameters vector overhead
-    estimated_capacity += 12 + args.len() * 6;
+    estimated_capacity += 12 + num_args * 6;
 
     // Per-parameter overhead
-    for arg in args {
+    for size in arg_sizes {
         estimated_capacity += 16; // Base parameter structure
-        estimated_capacity += match arg {
-            ParameterValue::String(s) => s.len() + 20,
-            ParameterValue::VecBytes(v) => v.len() + 20,
-            ParameterValue::Int(_) | ParameterValue::UInt(_) => 16,
-            ParameterValue::Long(_) | ParameterValue::ULong(_) => 20,
-            ParameterValue::Float(_) => 16,
-            ParameterValue::Double(_) => 20,
-            ParameterValue::Bool(_) => 12,
-        };
+        estimated_capacity += size;
     }
 
     // match how vec grows
@@ -0,0 +1,511 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Structured values that can be passed to and returned from guest and
+//! host functions: structs, optionals and maps with string keys, nested to
+//! any depth up to [`Value::MAX_DEPTH`].
+//!
+//! A [`Value`] is carried in the `hlvalue` flatbuffer table as a byte
+//! vector, using a small self-describing encoding. Every value starts with
+//! a one byte tag, followed by:
+//!
+//! - `Int`, `UInt`, `Long`, `ULong`, `Float`, `Double`: the value in little
+//!   endian byte order
+//! - `Bool`: a single `0` or `1` byte
+//! - `String`, `VecBytes`: a `u32` little endian length and then the bytes
+//! - `None`: nothing
+//! - `Some`: the contained value
+//! - `Struct`: a `u32` little endian field count and then the fields, in
+//!   order
+//! - `Map`: a `u32` little endian entry count and then, for each entry in
+//!   ascending key order, the key encoded as a string and then the value
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use anyhow::{Result, anyhow, bail};
+
+const TAG_INT: u8 = 0;
+const TAG_UINT: u8 = 1;
+const TAG_LONG: u8 = 2;
+const TAG_ULONG: u8 = 3;
+const TAG_FLOAT: u8 = 4;
+const TAG_DOUBLE: u8 = 5;
+const TAG_STRING: u8 = 6;
+const TAG_BOOL: u8 = 7;
+const TAG_VECBYTES: u8 = 8;
+const TAG_NONE: u8 = 9;
+const TAG_SOME: u8 = 10;
+const TAG_STRUCT: u8 = 11;
+const TAG_MAP: u8 = 12;
+
+/// A structured value that can be passed as a parameter to, or returned
+/// from, a guest or host function.
+#[cfg_attr(feature = "fuzzing", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "postcard", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// i32
+    Int(i32),
+    /// u32
+    UInt(u32),
+    /// i64
+    Long(i64),
+    /// u64
+    ULong(u64),
+    /// f32
+    Float(f32),
+    /// f64
+    Double(f64),
+    /// String
+    String(String),
+    /// bool
+    Bool(bool),
+    /// `Vec<u8>`
+    VecBytes(
+        #[cfg_attr(feature = "postcard", serde(with = "crate::call_encoding::bytes"))] Vec<u8>,
+    ),
+    /// An optional value
+    Option(Option<Box<Value>>),
+    /// The fields of a struct, in declaration order
+    Struct(Vec<Value>),
+    /// A map from strings to values
+    Map(BTreeMap<String, Value>),
+}
+
+impl Value {
+    /// The maximum depth to which values may be nested
+    pub const MAX_DEPTH: usize = 64;
+
+    /// Encodes the value into its binary representation
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode_into(&mut buf);
+        buf
+    }
+
+    /// The number of bytes [`Value::encode()`] produces for the value
+    pub fn encoded_len(&self) -> usize {
+        1 + match self {
+            Value::Int(_) | Value::UInt(_) | Value::Float(_) => 4,
+            Value::Long(_) | Value::ULong(_) | Value::Double(_) => 8,
+            Value::Bool(_) => 1,
+            Value::String(v) => 4 + v.len(),
+            Value::VecBytes(v) => 4 + v.len(),
+            Value::Option(None) => 0,
+            Value::Option(Some(v)) => v.encoded_len(),
+            Value::Struct(fields) => 4 + fields.iter().map(Value::encoded_len).sum::<usize>(),
+            Value::Map(entries) => {
+                4 + entries
+                    .iter()
+                    .map(|(k, v)| 4 + k.len() + v.encoded_len())
+                    .sum::<usize>()
+            }
+        }
+    }
+
+    /// Decodes a value from its binary representation, failing if `bytes`
+    /// is not exactly one well-formed value
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes, depth: 0 };
+        let value = reader.value()?;
+        if !reader.bytes.is_empty() {
+            bail!(
+                "{} unexpected trailing bytes after structured value",
+                reader.bytes.len()
+            );
+        }
+        Ok(value)
+    }
+
+    /// Returns the fields of a `Struct` value, failing if the value is not
+    /// a struct with exactly `N` fields
+    pub fn into_fields<const N: usize>(self) -> Result<[Value; N]> {
+        match self {
+            Value::Struct(fields) => <[Value; N]>::try_from(fields).map_err(|fields| {
+                anyhow!(
+                    "expected a struct with {} fields but got {} fields",
+                    N,
+                    fields.len()
+                )
+            }),
+            other => bail!("expected a struct but got {:?}", other),
+        }
+    }
+
+    fn encode_into(&self, buf: &mut Vec<u8>) {
+        match self {
+            Value::Int(v) => {
+                buf.push(TAG_INT);
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+            Value::UInt(v) => {
+                buf.push(TAG_UINT);
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+            Value::Long(v) => {
+                buf.push(TAG_LONG);
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+            Value::ULong(v) => {
+                buf.push(TAG_ULONG);
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+            Value::Float(v) => {
+                buf.push(TAG_FLOAT);
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+            Value::Double(v) => {
+                buf.push(TAG_DOUBLE);
+                buf.extend_from_slice(&v.to_le_bytes());
+            }
+            Value::String(v) => {
+                buf.push(TAG_STRING);
+                encode_bytes(buf, v.as_bytes());
+            }
+            Value::Bool(v) => {
+                buf.push(TAG_BOOL);
+                buf.push(*v as u8);
+            }
+            Value::VecBytes(v) => {
+                buf.push(TAG_VECBYTES);
+                encode_bytes(buf, v);
+            }
+            Value::Option(None) => buf.push(TAG_NONE),
+            Value::Option(Some(v)) => {
+                buf.push(TAG_SOME);
+                v.encode_into(buf);
+            }
+            Value::Struct(fields) => {
+                buf.push(TAG_STRUCT);
+                buf.extend_from_slice(&(fields.len() as u32).to_le_bytes());
+                for field in fields {
+                    field.encode_into(buf);
+                }
+            }
+            Value::Map(entries) => {
+                buf.push(TAG_MAP);
+                buf.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+                for (key, value) in entries {
+                    encode_bytes(buf, key.as_bytes());
+                    value.encode_into(buf);
+                }
+            }
+        }
+    }
+}
+
+fn encode_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.bytes.len() {
+            bail!(
+                "structured value is truncated: needed {} bytes but only {} remain",
+                len,
+                self.bytes.len()
+            );
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        Ok(u32::from_le_bytes(self.array()?) as usize)
+    }
+
+    /// Reads the number of elements of a struct or map. Every element takes
+    /// at least one byte, so a count larger than the number of remaining
+    /// bytes cannot be valid and is rejected before anything is allocated.
+    fn count(&mut self) -> Result<usize> {
+        let count = self.len()?;
+        if count > self.bytes.len() {
+            bail!(
+                "structured value has {} elements but only {} bytes remain",
+                count,
+                self.bytes.len()
+            );
+        }
+        Ok(count)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.len()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|e| anyhow!("structured value contains an invalid string: {}", e))
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        if self.depth == Value::MAX_DEPTH {
+            bail!(
+                "structured value is nested more than {} levels deep",
+                Value::MAX_DEPTH
+            );
+        }
+        self.depth += 1;
+        let [tag] = self.array()?;
+        let value = match tag {
+            TAG_INT => Value::Int(i32::from_le_bytes(self.array()?)),
+            TAG_UINT => Value::UInt(u32::from_le_bytes(self.array()?)),
+            TAG_LONG => Value::Long(i64::from_le_bytes(self.array()?)),
+            TAG_ULONG => Value::ULong(u64::from_le_bytes(self.array()?)),
+            TAG_FLOAT => Value::Float(f32::from_le_bytes(self.array()?)),
+            TAG_DOUBLE => Value::Double(f64::from_le_bytes(self.array()?)),
+            TAG_STRING => Value::String(self.string()?),
+            TAG_BOOL => match self.array()? {
+                [0] => Value::Bool(false),
+                [1] => Value::Bool(true),
+                [other] => bail!("structured value contains an invalid bool {}", other),
+            },
+            TAG_VECBYTES => {
+                let len = self.len()?;
+                Value::VecBytes(self.take(len)?.to_vec())
+            }
+            TAG_NONE => Value::Option(None),
+            TAG_SOME => Value::Option(Some(Box::new(self.value()?))),
+            TAG_STRUCT => {
+                let count = self.count()?;
+                let mut fields = Vec::with_capacity(count);
+                for _ in 0..count {
+                    fields.push(self.value()?);
+                }
+                Value::Struct(fields)
+            }
+            TAG_MAP => {
+                let count = self.count()?;
+                let mut entries = BTreeMap::new();
+                for _ in 0..count {
+                    let key = self.string()?;
+                    let value = self.value()?;
+                    if entries.insert(key, value).is_some() {
+                        bail!("structured value contains a map with duplicate keys");
+                    }
+                }
+                Value::Map(entries)
+            }
+            other => bail!("structured value has unknown tag {}", other),
+        };
+        self.depth -= 1;
+        Ok(value)
+    }
+}
+
+/// A Rust type that can be converted to and from a [`Value`].
+///
+/// This is implemented for all the scalar parameter types, for `Value`
+/// itself, and for `Option<T>` and `BTreeMap<String, T>` of other
+/// `StructuredType`s. Implement it for your own structs to pass them to
+/// and from functions wrapped in [`Structured`]:
+///
+/// ```
+/// use hyperlight_common::flatbuffer_wrappers::value::{StructuredType, Value};
+///
+/// #[derive(Clone)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// impl StructuredType for Point {
+///     fn into_structured_value(self) -> Value {
+///         Value::Struct(vec![self.x.into_structured_value(), self.y.into_structured_value()])
+///     }
+///
+///     fn from_structured_value(value: Value) -> anyhow::Result<Self> {
+///         let [x, y] = value.into_fields()?;
+///         Ok(Point {
+///             x: i32::from_structured_value(x)?,
+///             y: i32::from_structured_value(y)?,
+///         })
+///     }
+/// }
+/// ```
+pub trait StructuredType: Sized {
+    /// Converts `self` into a [`Value`]
+    fn into_structured_value(self) -> Value;
+
+    /// Converts a [`Value`] back into `Self`
+    fn from_structured_value(value: Value) -> Result<Self>;
+}
+
+macro_rules! impl_structured_type {
+    ($type:ty, $enum:ident) => {
+        impl StructuredType for $type {
+            fn into_structured_value(self) -> Value {
+                Value::$enum(self)
+            }
+
+            fn from_structured_value(value: Value) -> Result<Self> {
+                match value {
+                    Value::$enum(v) => Ok(v),
+                    other => bail!("expected {} but got {:?}", stringify!($type), other),
+                }
+            }
+        }
+    };
+}
+
+impl_structured_type!(i32, Int);
+impl_structured_type!(u32, UInt);
+impl_structured_type!(i64, Long);
+impl_structured_type!(u64, ULong);
+impl_structured_type!(f32, Float);
+impl_structured_type!(f64, Double);
+impl_structured_type!(String, String);
+impl_structured_type!(bool, Bool);
+impl_structured_type!(Vec<u8>, VecBytes);
+
+impl StructuredType for Value {
+    fn into_structured_value(self) -> Value {
+        self
+    }
+
+    fn from_structured_value(value: Value) -> Result<Self> {
+        Ok(value)
+    }
+}
+
+impl<T: StructuredType> StructuredType for Option<T> {
+    fn into_structured_value(self) -> Value {
+        Value::Option(self.map(|v| Box::new(v.into_structured_value())))
+    }
+
+    fn from_structured_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Option(v) => v.map(|v| T::from_structured_value(*v)).transpose(),
+            other => bail!("expected an optional value but got {:?}", other),
+        }
+    }
+}
+
+impl<T: StructuredType> StructuredType for BTreeMap<String, T> {
+    fn into_structured_value(self) -> Value {
+        Value::Map(
+            self.into_iter()
+                .map(|(k, v)| (k, v.into_structured_value()))
+                .collect(),
+        )
+    }
+
+    fn from_structured_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Map(entries) => entries
+                .into_iter()
+                .map(|(k, v)| Ok((k, T::from_structured_value(v)?)))
+                .collect(),
+            other => bail!("expected a map but got {:?}", other),
+        }
+    }
+}
+
+/// Wraps a [`StructuredType`], such as a struct of your own, so that it can
+/// be used as a parameter or return type of a guest or host function.
+///
+/// `Option<T>` and `BTreeMap<String, T>` can be used directly, without
+/// this wrapper.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Structured<T>(pub T);
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    use super::*;
+
+    fn nested() -> Value {
+        let mut map = BTreeMap::new();
+        map.insert("name".to_string(), Value::String("hyperlight".to_string()));
+        map.insert("missing".to_string(), Value::Option(None));
+        map.insert(
+            "point".to_string(),
+            Value::Struct(vec![Value::Int(-1), Value::Double(2.5)]),
+        );
+        Value::Struct(vec![
+            Value::UInt(7),
+            Value::Long(i64::MIN),
+            Value::ULong(u64::MAX),
+            Value::Float(1.5),
+            Value::Bool(true),
+            Value::VecBytes(vec![1, 2, 3]),
+            Value::Option(Some(Box::new(Value::Map(map)))),
+        ])
+    }
+
+    #[test]
+    fn roundtrip() {
+        let value = nested();
+        let encoded = value.encode();
+        assert_eq!(encoded.len(), value.encoded_len());
+        assert_eq!(Value::decode(&encoded).unwrap(), value);
+
+        let mut map = BTreeMap::new();
+        map.insert("a".to_string(), Some(1u64));
+        map.insert("b".to_string(), None);
+        let converted = BTreeMap::<String, Option<u64>>::from_structured_value(
+            map.clone().into_structured_value(),
+        )
+        .unwrap();
+        assert_eq!(converted, map);
+        assert!(Option::<i32>::from_structured_value(Value::Int(1)).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_values() {
+        let encoded = nested().encode();
+        // Every strict prefix is truncated
+        for len in 0..encoded.len() {
+            assert!(Value::decode(&encoded[..len]).is_err());
+        }
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert!(Value::decode(&trailing).is_err());
+
+        assert!(Value::decode(&[42]).is_err());
+        assert!(Value::decode(&[TAG_BOOL, 2]).is_err());
+        assert!(Value::decode(&[TAG_STRING, 2, 0, 0, 0, 0xff, 0xfe]).is_err());
+        // A huge element count is rejected without allocating
+        assert!(Value::decode(&[TAG_STRUCT, 0xff, 0xff, 0xff, 0xff]).is_err());
+        let duplicate_keys = [
+            TAG_MAP, 2, 0, 0, 0, 1, 0, 0, 0, b'a', TAG_NONE, 1, 0, 0, 0, b'a', TAG_NONE,
+        ];
+        assert!(Value::decode(&duplicate_keys).is_err());
+
+        let mut deep = Value::Option(None);
+        for _ in 0..Value::MAX_DEPTH {
+            deep = Value::Option(Some(Box::new(deep)));
+        }
+        assert!(Value::decode(&deep.encode()).is_err());
+    }
+}
@@ -19,13 +19,13 @@ pub const ENUM_MIN_ERROR_CODE: u64 = 0;
     since = "2.0.0",
     note = "Use associated constants instead. This will no longer be generated in 2021."
 )]
-pub const ENUM_MAX_ERROR_CODE: u64 = 17;
+pub const ENUM_MAX_ERROR_CODE: u64 = 24;
 #[deprecated(
     since = "2.0.0",
     note = "Use associated constants instead. This will no longer be generated in 2021."
 )]
 #[allow(non_camel_case_types)]
-pub const ENUM_VALUES_ERROR_CODE: [ErrorCode; 16] = [
+pub const ENUM_VALUES_ERROR_CODE: [ErrorCode; 23] = [
     ErrorCode::NoError,
     ErrorCode::UnsupportedParameterType,
     ErrorCode::GuestFunctionNameNotProvided,
@@ -42,6 +42,13 @@ pub const ENUM_VALUES_ERROR_CODE: [ErrorCode; 16] = [
     ErrorCode::GuestError,
     ErrorCode::ArrayLengthParamIsMissing,
     ErrorCode::HostError,
+    ErrorCode::HostFunctionPermissionDenied,
+    ErrorCode::HostFunctionThrottled,
+    ErrorCode::AbiMismatch,
+    ErrorCode::HostFunctionTimedOut,
+    ErrorCode::HostFunctionPanicked,
+    ErrorCode::GuestPanicked,
+    ErrorCode::UnsupportedCallEncoding,
 ];
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
@@ -65,9 +72,16 @@ impl ErrorCode {
     pub const GuestError: Self = Self(15);
     pub const ArrayLengthParamIsMissing: Self = Self(16);
     pub const HostError: Self = Self(17);
+    pub const HostFunctionPermissionDenied: Self = Self(18);
+    pub const HostFunctionThrottled: Self = Self(19);
+    pub const AbiMismatch: Self = Self(20);
+    pub const HostFunctionTimedOut: Self = Self(21);
+    pub const HostFunctionPanicked: Self = Self(22);
+    pub const GuestPanicked: Self = Self(23);
+    pub const UnsupportedCallEncoding: Self = Self(24);
 
     pub const ENUM_MIN: u64 = 0;
-    pub const ENUM_MAX: u64 = 17;
+    pub const ENUM_MAX: u64 = 24;
     pub const ENUM_VALUES: &'static [Self] = &[
         Self::NoError,
         Self::UnsupportedParameterType,
@@ -85,6 +99,13 @@ impl ErrorCode {
         Self::GuestError,
         Self::ArrayLengthParamIsMissing,
         Self::HostError,
+        Self::HostFunctionPermissionDenied,
+        Self::HostFunctionThrottled,
+        Self::AbiMismatch,
+        Self::HostFunctionTimedOut,
+        Self::HostFunctionPanicked,
+        Self::GuestPanicked,
+        Self::UnsupportedCallEncoding,
     ];
     /// Returns the variant's name or "" if unknown.
     pub fn variant_name(self) -> Option<&'static str> {
@@ -107,6 +128,13 @@ impl ErrorCode {
             Self::GuestError => Some("GuestError"),
             Self::ArrayLengthParamIsMissing => Some("ArrayLengthParamIsMissing"),
             Self::HostError => Some("HostError"),
+            Self::HostFunctionPermissionDenied => Some("HostFunctionPermissionDenied"),
+            Self::HostFunctionThrottled => Some("HostFunctionThrottled"),
+            Self::AbiMismatch => Some("AbiMismatch"),
+            Self::HostFunctionTimedOut => Some("HostFunctionTimedOut"),
+            Self::HostFunctionPanicked => Some("HostFunctionPanicked"),
+            Self::GuestPanicked => Some("GuestPanicked"),
+            Self::UnsupportedCallEncoding => Some("UnsupportedCallEncoding"),
             _ => None,
         }
     }
@@ -30,6 +30,7 @@ impl<'a> flatbuffers::Follow<'a> for GuestError<'a> {
 impl<'a> GuestError<'a> {
     pub const VT_CODE: flatbuffers::VOffsetT = 4;
     pub const VT_MESSAGE: flatbuffers::VOffsetT = 6;
+    pub const VT_BACKTRACE: flatbuffers::VOffsetT = 8;
 
     #[inline]
     pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
@@ -42,6 +43,9 @@ impl<'a> GuestError<'a> {
     ) -> flatbuffers::WIPOffset<GuestError<'bldr>> {
         let mut builder = GuestErrorBuilder::new(_fbb);
         builder.add_code(args.code);
+        if let Some(x) = args.backtrace {
+            builder.add_backtrace(x);
+        }
         if let Some(x) = args.message {
             builder.add_message(x);
         }
@@ -69,6 +73,19 @@ impl<'a> GuestError<'a> {
                 .get::<flatbuffers::ForwardsUOffset<&str>>(GuestError::VT_MESSAGE, None)
         }
     }
+    #[inline]
+    pub fn backtrace(&self) -> Option<flatbuffers::Vector<'a, u64>> {
+        // Safety:
+        // Created from valid Table for this object
+        // which contains a valid value in this slot
+        unsafe {
+            self._tab
+                .get::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'a, u64>>>(
+                    GuestError::VT_BACKTRACE,
+                    None,
+                )
+        }
+    }
 }
 
 impl flatbuffers::Verifiable for GuestError<'_> {
@@ -81,6 +98,11 @@ impl flatbuffers::Verifiable for GuestError<'_> {
         v.visit_table(pos)?
             .visit_field::<ErrorCode>("code", Self::VT_CODE, false)?
             .visit_field::<flatbuffers::ForwardsUOffset<&str>>("message", Self::VT_MESSAGE, false)?
+            .visit_field::<flatbuffers::ForwardsUOffset<flatbuffers::Vector<'_, u64>>>(
+                "backtrace",
+                Self::VT_BACKTRACE,
+                false,
+            )?
             .finish();
         Ok(())
     }
@@ -88,6 +110,7 @@ impl flatbuffers::Verifiable for GuestError<'_> {
 pub struct GuestErrorArgs<'a> {
     pub code: ErrorCode,
     pub message: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub backtrace: Option<flatbuffers::WIPOffset<flatbuffers::Vector<'a, u64>>>,
 }
 impl<'a> Default for GuestErrorArgs<'a> {
     #[inline]
@@ -95,6 +118,7 @@ impl<'a> Default for GuestErrorArgs<'a> {
         GuestErrorArgs {
             code: ErrorCode::NoError,
             message: None,
+            backtrace: None,
         }
     }
 }
@@ -115,6 +139,14 @@ impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> GuestErrorBuilder<'a, 'b, A> {
             .push_slot_always::<flatbuffers::WIPOffset<_>>(GuestError::VT_MESSAGE, message);
     }
     #[inline]
+    pub fn add_backtrace(
+        &mut self,
+        backtrace: flatbuffers::WIPOffset<flatbuffers::Vector<'b, u64>>,
+    ) {
+        self.fbb_
+            .push_slot_always::<flatbuffers::WIPOffset<_>>(GuestError::VT_BACKTRACE, backtrace);
+    }
+    #[inline]
     pub fn new(
         _fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
     ) -> GuestErrorBuilder<'a, 'b, A> {
@@ -136,6 +168,7 @@ impl core::fmt::Debug for GuestError<'_> {
         let mut ds = f.debug_struct("GuestError");
         ds.field("code", &self.code());
         ds.field("message", &self.message());
+        ds.field("backtrace", &self.backtrace());
         ds.finish()
     }
 }
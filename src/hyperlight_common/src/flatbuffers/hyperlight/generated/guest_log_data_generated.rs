@@ -34,6 +34,8 @@ impl<'a> GuestLogData<'a> {
     pub const VT_CALLER: flatbuffers::VOffsetT = 10;
     pub const VT_SOURCE_FILE: flatbuffers::VOffsetT = 12;
     pub const VT_LINE: flatbuffers::VOffsetT = 14;
+    pub const VT_TARGET: flatbuffers::VOffsetT = 16;
+    pub const VT_FIELDS: flatbuffers::VOffsetT = 18;
 
     #[inline]
     pub unsafe fn init_from_table(table: flatbuffers::Table<'a>) -> Self {
@@ -45,6 +47,12 @@ impl<'a> GuestLogData<'a> {
         args: &'args GuestLogDataArgs<'args>,
     ) -> flatbuffers::WIPOffset<GuestLogData<'bldr>> {
         let mut builder = GuestLogDataBuilder::new(_fbb);
+        if let Some(x) = args.fields {
+            builder.add_fields(x);
+        }
+        if let Some(x) = args.target {
+            builder.add_target(x);
+        }
         builder.add_line(args.line);
         if let Some(x) = args.source_file {
             builder.add_source_file(x);
@@ -124,6 +132,29 @@ impl<'a> GuestLogData<'a> {
                 .unwrap()
         }
     }
+    #[inline]
+    pub fn target(&self) -> Option<&'a str> {
+        // Safety:
+        // Created from valid Table for this object
+        // which contains a valid value in this slot
+        unsafe {
+            self._tab
+                .get::<flatbuffers::ForwardsUOffset<&str>>(GuestLogData::VT_TARGET, None)
+        }
+    }
+    #[inline]
+    pub fn fields(
+        &self,
+    ) -> Option<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<KeyValue<'a>>>> {
+        // Safety:
+        // Created from valid Table for this object
+        // which contains a valid value in this slot
+        unsafe {
+            self._tab.get::<flatbuffers::ForwardsUOffset<
+                flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<KeyValue>>,
+            >>(GuestLogData::VT_FIELDS, None)
+        }
+    }
 }
 
 impl flatbuffers::Verifiable for GuestLogData<'_> {
@@ -144,6 +175,10 @@ impl flatbuffers::Verifiable for GuestLogData<'_> {
                 false,
             )?
             .visit_field::<u32>("line", Self::VT_LINE, false)?
+            .visit_field::<flatbuffers::ForwardsUOffset<&str>>("target", Self::VT_TARGET, false)?
+            .visit_field::<flatbuffers::ForwardsUOffset<
+                flatbuffers::Vector<'_, flatbuffers::ForwardsUOffset<KeyValue>>,
+            >>("fields", Self::VT_FIELDS, false)?
             .finish();
         Ok(())
     }
@@ -155,6 +190,10 @@ pub struct GuestLogDataArgs<'a> {
     pub caller: Option<flatbuffers::WIPOffset<&'a str>>,
     pub source_file: Option<flatbuffers::WIPOffset<&'a str>>,
     pub line: u32,
+    pub target: Option<flatbuffers::WIPOffset<&'a str>>,
+    pub fields: Option<
+        flatbuffers::WIPOffset<flatbuffers::Vector<'a, flatbuffers::ForwardsUOffset<KeyValue<'a>>>>,
+    >,
 }
 impl<'a> Default for GuestLogDataArgs<'a> {
     #[inline]
@@ -166,6 +205,8 @@ impl<'a> Default for GuestLogDataArgs<'a> {
             caller: None,
             source_file: None,
             line: 0,
+            target: None,
+            fields: None,
         }
     }
 }
@@ -207,6 +248,21 @@ impl<'a: 'b, 'b, A: flatbuffers::Allocator + 'a> GuestLogDataBuilder<'a, 'b, A>
         self.fbb_.push_slot::<u32>(GuestLogData::VT_LINE, line, 0);
     }
     #[inline]
+    pub fn add_target(&mut self, target: flatbuffers::WIPOffset<&'b str>) {
+        self.fbb_
+            .push_slot_always::<flatbuffers::WIPOffset<_>>(GuestLogData::VT_TARGET, target);
+    }
+    #[inline]
+    pub fn add_fields(
+        &mut self,
+        fields: flatbuffers::WIPOffset<
+            flatbuffers::Vector<'b, flatbuffers::ForwardsUOffset<KeyValue<'b>>>,
+        >,
+    ) {
+        self.fbb_
+            .push_slot_always::<flatbuffers::WIPOffset<_>>(GuestLogData::VT_FIELDS, fields);
+    }
+    #[inline]
     pub fn new(
         _fbb: &'b mut flatbuffers::FlatBufferBuilder<'a, A>,
     ) -> GuestLogDataBuilder<'a, 'b, A> {
@@ -232,6 +288,8 @@ impl core::fmt::Debug for GuestLogData<'_> {
         ds.field("caller", &self.caller());
         ds.field("source_file", &self.source_file());
         ds.field("line", &self.line());
+        ds.field("target", &self.target());
+        ds.field("fields", &self.fields());
         ds.finish()
     }
 }
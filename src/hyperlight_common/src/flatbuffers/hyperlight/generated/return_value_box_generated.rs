@@ -212,6 +212,20 @@ impl<'a> ReturnValueBox<'a> {
             None
         }
     }
+
+    #[inline]
+    #[allow(non_snake_case)]
+    pub fn value_as_hlvalue(&self) -> Option<hlvalue<'a>> {
+        if self.value_type() == ReturnValue::hlvalue {
+            let u = self.value();
+            // Safety:
+            // Created from a valid Table for this object
+            // Which contains a valid union in this slot
+            Some(unsafe { hlvalue::init_from_table(u) })
+        } else {
+            None
+        }
+    }
 }
 
 impl flatbuffers::Verifiable for ReturnValueBox<'_> {
@@ -279,6 +293,11 @@ impl flatbuffers::Verifiable for ReturnValueBox<'_> {
                             "ReturnValue::hlsizeprefixedbuffer",
                             pos,
                         ),
+                    ReturnValue::hlvalue => v
+                        .verify_union_variant::<flatbuffers::ForwardsUOffset<hlvalue>>(
+                            "ReturnValue::hlvalue",
+                            pos,
+                        ),
                     _ => Ok(()),
                 },
             )?
@@ -441,6 +460,16 @@ impl core::fmt::Debug for ReturnValueBox<'_> {
                     )
                 }
             }
+            ReturnValue::hlvalue => {
+                if let Some(x) = self.value_as_hlvalue() {
+                    ds.field("value", &x)
+                } else {
+                    ds.field(
+                        "value",
+                        &"InvalidFlatbuffer: Union discriminant does not match value.",
+                    )
+                }
+            }
             _ => {
                 let x: Option<()> = None;
                 ds.field("value", &x)
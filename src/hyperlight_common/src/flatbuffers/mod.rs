@@ -40,6 +40,12 @@ pub mod hyperlight {
         pub use self::hlbool_generated::*;
         mod hlvecbytes_generated;
         pub use self::hlvecbytes_generated::*;
+        mod hlvalue_generated;
+        pub use self::hlvalue_generated::*;
+        mod hlvecfloat_generated;
+        pub use self::hlvecfloat_generated::*;
+        mod hlvecdouble_generated;
+        pub use self::hlvecdouble_generated::*;
         mod hlsizeprefixedbuffer_generated;
         pub use self::hlsizeprefixedbuffer_generated::*;
         mod hlvoid_generated;
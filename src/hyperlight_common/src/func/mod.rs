@@ -36,7 +36,7 @@ pub(crate) mod ret_type;
 pub use error::Error;
 /// Re-export for `HostFunction` trait
 pub use functions::Function;
-pub use param_type::{ParameterTuple, SupportedParameterType};
+pub use param_type::{IntoParameterRef, ParameterRefTuple, ParameterTuple, SupportedParameterType};
 pub use ret_type::{ResultType, SupportedReturnType};
 
 /// Re-export for `ParameterValue` enum
@@ -45,5 +45,7 @@ pub use crate::flatbuffer_wrappers::function_types::ParameterValue;
 pub use crate::flatbuffer_wrappers::function_types::ReturnType;
 /// Re-export for `ReturnType` enum
 pub use crate::flatbuffer_wrappers::function_types::ReturnValue;
+/// Re-export for structured values: structs, optionals and maps
+pub use crate::flatbuffer_wrappers::value::{Structured, StructuredType, Value};
 
 mod utils;
@@ -14,13 +14,16 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use alloc::collections::BTreeMap;
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 
 use super::error::Error;
 use super::utils::for_each_tuple;
+use crate::flatbuffer_wrappers::function_call::ParameterRef;
 use crate::flatbuffer_wrappers::function_types::{ParameterType, ParameterValue};
+use crate::flatbuffer_wrappers::value::{Structured, StructuredType, Value};
 
 /// This is a marker trait that is used to indicate that a type is a
 /// valid Hyperlight parameter type.
@@ -50,6 +53,9 @@ macro_rules! for_each_param_type {
         $macro!(f64, Double);
         $macro!(bool, Bool);
         $macro!(Vec<u8>, VecBytes);
+        $macro!(Value, Value);
+        $macro!(Vec<f32>, VecFloat);
+        $macro!(Vec<f64>, VecDouble);
     };
 }
 
@@ -77,6 +83,66 @@ macro_rules! impl_supported_param_type {
 
 for_each_param_type!(impl_supported_param_type);
 
+fn structured_from_value<T: StructuredType>(value: ParameterValue) -> Result<T, Error> {
+    match value {
+        ParameterValue::Value(v) => T::from_structured_value(v.clone()).map_err(|_| {
+            Error::ParameterValueConversionFailure(
+                ParameterValue::Value(v),
+                core::any::type_name::<T>(),
+            )
+        }),
+        other => Err(Error::ParameterValueConversionFailure(
+            other,
+            core::any::type_name::<T>(),
+        )),
+    }
+}
+
+impl<T> SupportedParameterType for Option<T>
+where
+    T: StructuredType + Clone + Send + Sync + 'static,
+{
+    const TYPE: ParameterType = ParameterType::Value;
+
+    fn into_value(self) -> ParameterValue {
+        ParameterValue::Value(self.into_structured_value())
+    }
+
+    fn from_value(value: ParameterValue) -> Result<Self, Error> {
+        structured_from_value(value)
+    }
+}
+
+impl<T> SupportedParameterType for BTreeMap<String, T>
+where
+    T: StructuredType + Clone + Send + Sync + 'static,
+{
+    const TYPE: ParameterType = ParameterType::Value;
+
+    fn into_value(self) -> ParameterValue {
+        ParameterValue::Value(self.into_structured_value())
+    }
+
+    fn from_value(value: ParameterValue) -> Result<Self, Error> {
+        structured_from_value(value)
+    }
+}
+
+impl<T> SupportedParameterType for Structured<T>
+where
+    T: StructuredType + Clone + Send + Sync + 'static,
+{
+    const TYPE: ParameterType = ParameterType::Value;
+
+    fn into_value(self) -> ParameterValue {
+        ParameterValue::Value(self.0.into_structured_value())
+    }
+
+    fn from_value(value: ParameterValue) -> Result<Self, Error> {
+        structured_from_value(value).map(Structured)
+    }
+}
+
 /// A trait to describe the tuple of parameters that a host function can take.
 pub trait ParameterTuple: Sized + Clone + Send + Sync + 'static {
     /// The number of parameters in the tuple
@@ -135,3 +201,50 @@ macro_rules! impl_param_tuple {
 }
 
 for_each_tuple!(impl_param_tuple);
+
+/// A parameter of a guest function call that may borrow from the caller.
+///
+/// This is implemented for every [`SupportedParameterType`], and for
+/// `&[u8]`, which the guest receives as a `Vec<u8>`.
+pub trait IntoParameterRef<'a> {
+    /// Get the parameter, borrowing from `self` where possible
+    fn into_parameter_ref(self) -> ParameterRef<'a>;
+}
+
+impl<T: SupportedParameterType> IntoParameterRef<'_> for T {
+    fn into_parameter_ref(self) -> ParameterRef<'static> {
+        ParameterRef::Value(self.into_value())
+    }
+}
+
+impl<'a> IntoParameterRef<'a> for &'a [u8] {
+    fn into_parameter_ref(self) -> ParameterRef<'a> {
+        ParameterRef::Bytes(self)
+    }
+}
+
+/// A tuple of parameters of a guest function call, any of which may
+/// borrow from the caller. See [`IntoParameterRef`].
+pub trait ParameterRefTuple<'a> {
+    /// Get the parameters, borrowing from `self` where possible
+    fn into_parameter_refs(self) -> Vec<ParameterRef<'a>>;
+}
+
+impl<'a, T: IntoParameterRef<'a>> ParameterRefTuple<'a> for T {
+    fn into_parameter_refs(self) -> Vec<ParameterRef<'a>> {
+        vec![self.into_parameter_ref()]
+    }
+}
+
+macro_rules! impl_param_ref_tuple {
+    ([$N:expr] ($($name:ident: $param:ident),*)) => {
+        impl<'a, $($param: IntoParameterRef<'a>),*> ParameterRefTuple<'a> for ($($param,)*) {
+            fn into_parameter_refs(self) -> Vec<ParameterRef<'a>> {
+                let ($($name,)*) = self;
+                vec![$($name.into_parameter_ref()),*]
+            }
+        }
+    };
+}
+
+for_each_tuple!(impl_param_ref_tuple);
@@ -14,11 +14,13 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use alloc::collections::BTreeMap;
 use alloc::string::String;
 use alloc::vec::Vec;
 
 use super::error::Error;
 use crate::flatbuffer_wrappers::function_types::{ReturnType, ReturnValue};
+use crate::flatbuffer_wrappers::value::{Structured, StructuredType};
 
 /// This is a marker trait that is used to indicate that a type is a valid Hyperlight return type.
 pub trait SupportedReturnType: Sized + Clone + Send + Sync + 'static {
@@ -46,6 +48,7 @@ macro_rules! for_each_return_type {
         $macro!(f64, Double);
         $macro!(bool, Bool);
         $macro!(Vec<u8>, VecBytes);
+        $macro!($crate::flatbuffer_wrappers::value::Value, Value);
     };
 }
 
@@ -105,3 +108,60 @@ where
 }
 
 for_each_return_type!(impl_supported_return_type);
+
+fn structured_from_value<T: StructuredType>(value: ReturnValue) -> Result<T, Error> {
+    match value {
+        ReturnValue::Value(v) => T::from_structured_value(v.clone()).map_err(|_| {
+            Error::ReturnValueConversionFailure(ReturnValue::Value(v), core::any::type_name::<T>())
+        }),
+        other => Err(Error::ReturnValueConversionFailure(
+            other,
+            core::any::type_name::<T>(),
+        )),
+    }
+}
+
+impl<T> SupportedReturnType for Option<T>
+where
+    T: StructuredType + Clone + Send + Sync + 'static,
+{
+    const TYPE: ReturnType = ReturnType::Value;
+
+    fn into_value(self) -> ReturnValue {
+        ReturnValue::Value(self.into_structured_value())
+    }
+
+    fn from_value(value: ReturnValue) -> Result<Self, Error> {
+        structured_from_value(value)
+    }
+}
+
+impl<T> SupportedReturnType for BTreeMap<String, T>
+where
+    T: StructuredType + Clone + Send + Sync + 'static,
+{
+    const TYPE: ReturnType = ReturnType::Value;
+
+    fn into_value(self) -> ReturnValue {
+        ReturnValue::Value(self.into_structured_value())
+    }
+
+    fn from_value(value: ReturnValue) -> Result<Self, Error> {
+        structured_from_value(value)
+    }
+}
+
+impl<T> SupportedReturnType for Structured<T>
+where
+    T: StructuredType + Clone + Send + Sync + 'static,
+{
+    const TYPE: ReturnType = ReturnType::Value;
+
+    fn into_value(self) -> ReturnValue {
+        ReturnValue::Value(self.0.into_structured_value())
+    }
+
+    fn from_value(value: ReturnValue) -> Result<Self, Error> {
+        structured_from_value(value).map(Structured)
+    }
+}
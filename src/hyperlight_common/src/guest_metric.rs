@@ -0,0 +1,111 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Metrics a guest sends to the host, which the host records with its
+//! `metrics` recorder.
+//!
+//! A metric is encoded as its kind (1 byte), its value (8 bytes, little
+//! endian) and its name as UTF-8.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// The longest name a guest metric may have
+pub const MAX_GUEST_METRIC_NAME_LEN: usize = 64;
+
+const KIND_COUNTER: u8 = 0;
+const KIND_HISTOGRAM: u8 = 1;
+
+/// A measurement a guest sends to the host
+#[derive(Debug, Clone, PartialEq)]
+pub enum GuestMetric {
+    /// Increment the counter `name` by a value
+    Counter(String, u64),
+    /// Record a value in the histogram `name`
+    Histogram(String, f64),
+}
+
+impl GuestMetric {
+    /// The name of the metric
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Counter(name, _) | Self::Histogram(name, _) => name,
+        }
+    }
+
+    /// Returns whether `name` can be the name of a guest metric: it is
+    /// not empty, at most [`MAX_GUEST_METRIC_NAME_LEN`] bytes long, and
+    /// made of ASCII letters, digits and underscores
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name.len() <= MAX_GUEST_METRIC_NAME_LEN
+            && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+    }
+
+    /// Encode the metric to send it to the host
+    pub fn encode(&self) -> Vec<u8> {
+        let (kind, value) = match self {
+            Self::Counter(_, value) => (KIND_COUNTER, *value),
+            Self::Histogram(_, value) => (KIND_HISTOGRAM, value.to_bits()),
+        };
+        let name = self.name().as_bytes();
+        let mut buf = Vec::with_capacity(9 + name.len());
+        buf.push(kind);
+        buf.extend_from_slice(&value.to_le_bytes());
+        buf.extend_from_slice(name);
+        buf
+    }
+
+    /// Decode a metric encoded by [`encode()`](Self::encode), or `None`
+    /// if `buf` is not one
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        let (&kind, rest) = buf.split_first()?;
+        let value = u64::from_le_bytes(rest.get(..8)?.try_into().ok()?);
+        let name = String::from(core::str::from_utf8(rest.get(8..)?).ok()?);
+        match kind {
+            KIND_COUNTER => Some(Self::Counter(name, value)),
+            KIND_HISTOGRAM => Some(Self::Histogram(name, f64::from_bits(value))),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::GuestMetric;
+
+    #[test]
+    fn metrics_roundtrip() {
+        for metric in [
+            GuestMetric::Counter("requests_total".to_string(), 3),
+            GuestMetric::Histogram("parse_seconds".to_string(), 0.25),
+        ] {
+            assert_eq!(GuestMetric::decode(&metric.encode()), Some(metric));
+        }
+        assert_eq!(GuestMetric::decode(&[7; 12]), None);
+        assert_eq!(GuestMetric::decode(&[0; 4]), None);
+    }
+
+    #[test]
+    fn names_are_checked() {
+        assert!(GuestMetric::is_valid_name("cache_hits_2"));
+        assert!(!GuestMetric::is_valid_name(""));
+        assert!(!GuestMetric::is_valid_name("cache.hits"));
+        assert!(!GuestMetric::is_valid_name(&"a".repeat(65)));
+    }
+}
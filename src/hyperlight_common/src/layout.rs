@@ -38,17 +38,67 @@ pub const SCRATCH_TOP_SIZE_OFFSET: u64 = 0x08;
 pub const SCRATCH_TOP_ALLOCATOR_OFFSET: u64 = 0x10;
 pub const SCRATCH_TOP_SNAPSHOT_PT_GPA_BASE_OFFSET: u64 = 0x18;
 pub const SCRATCH_TOP_SNAPSHOT_GENERATION_OFFSET: u64 = 0x20;
+/// The stack policy for the next guest call, written by the host before
+/// each call. See [`STACK_POLICY_ZERO_STACK`] for the encoding.
+pub const SCRATCH_TOP_STACK_POLICY_OFFSET: u64 = 0x28;
 pub const SCRATCH_TOP_EXN_STACK_OFFSET: u64 = 0x30;
 
 /// Offset from the top of scratch memory for a shared host-guest u64 counter.
 ///
-/// This is placed at 0x1008 (rather than the next sequential 0x28) so that the
+/// This is placed at 0x1008 (rather than next to the other values above) so that the
 /// counter falls in scratch page 0xffffe000 instead of the very last page
 /// 0xfffff000, which on i686 guests would require frame 0xfffff — exceeding the
 /// maximum representable frame number.
 #[cfg(feature = "guest-counter")]
 pub const SCRATCH_TOP_GUEST_COUNTER_OFFSET: u64 = 0x1008;
 
+/// Offset from the top of scratch memory of the control page, a small
+/// block of `u64` slots that the host and the guest access only with
+/// atomic operations, so that they can signal each other without a VM
+/// exit. The slots are at the `CONTROL_PAGE_*` offsets from the start
+/// of the block. Like the guest counter, the block is kept out of the
+/// very last page of scratch memory.
+pub const SCRATCH_TOP_CONTROL_PAGE_OFFSET: u64 = 0x1040;
+/// A progress counter, written by the guest and read by the host
+pub const CONTROL_PAGE_PROGRESS: u64 = 0x00;
+/// Non-zero when the host has asked the guest to stop what it is doing
+/// at the next convenient point. Written by the host and read by the
+/// guest.
+pub const CONTROL_PAGE_SOFT_CANCEL: u64 = 0x08;
+/// A counter the guest increments to show that it is still making
+/// progress, read by the host
+pub const CONTROL_PAGE_HEARTBEAT: u64 = 0x10;
+/// The size of the control page's block of slots
+pub const CONTROL_PAGE_SIZE: u64 = 0x18;
+
+/// Offset from the top of scratch memory of the W3C trace context of the
+/// current guest call, which the host writes before each call, encoded
+/// as by [`TraceParent::encode()`](crate::trace_context::TraceParent::encode)
+/// in [`TRACE_CONTEXT_SIZE`](crate::trace_context::TRACE_CONTEXT_SIZE)
+/// bytes. Like the control page, it is kept out of the very last page of
+/// scratch memory.
+pub const SCRATCH_TOP_TRACE_CONTEXT_OFFSET: u64 = 0x1080;
+
+/// Offset from the top of scratch memory of the wall-clock time, in
+/// nanoseconds since the Unix epoch, at which the guest's paravirtual
+/// clock read zero, which the host writes when the sandbox is created and
+/// restored. Zero when the host does not let the guest read the time from
+/// the clock. Like the control page, it is kept out of the very last page
+/// of scratch memory.
+pub const SCRATCH_TOP_WALL_CLOCK_OFFSET: u64 = 0x1088;
+
+/// Offset from the top of scratch memory of the page the guest asks the
+/// hypervisor to keep its paravirtual clock in (see
+/// [`clock`](crate::clock)). Hyper-V overlays the whole page, so it is a
+/// page of its own, below the two pages of bookkeeping above.
+pub const SCRATCH_TOP_CLOCK_PAGE_OFFSET: u64 = 0x3000;
+
+/// Bit in the stack policy word requesting that the guest zero the
+/// stack pages it used once a call has returned. The remaining bits
+/// (which must be page aligned) hold the maximum size of the main stack
+/// in bytes, or 0 for no limit beyond the available scratch memory.
+pub const STACK_POLICY_ZERO_STACK: u64 = 1;
+
 pub fn scratch_base_gpa(size: usize) -> u64 {
     (MAX_GPA - size + 1) as u64
 }
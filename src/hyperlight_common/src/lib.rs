@@ -22,6 +22,10 @@ limitations under the License.
 
 extern crate alloc;
 
+/// cbindgen:ignore
+pub mod call_encoding;
+/// The paravirtual clocks the hypervisors give guests.
+pub mod clock;
 pub mod flatbuffer_wrappers;
 /// cbindgen:ignore
 /// FlatBuffers-related utilities and (mostly) generated code
@@ -30,6 +34,9 @@ mod flatbuffers;
 // cbindgen:ignore
 pub mod layout;
 
+/// Metrics a guest sends to the host.
+pub mod guest_metric;
+
 // cbindgen:ignore
 pub mod log_level;
 
@@ -48,5 +55,8 @@ pub mod func;
 // cbindgen:ignore
 pub mod vmem;
 
+/// The W3C trace context the host passes to each guest call.
+pub mod trace_context;
+
 /// ELF note types for embedding hyperlight version metadata in guest binaries.
 pub mod version_note;
@@ -65,13 +65,46 @@ impl Default for FileMappingInfo {
     }
 }
 
+/// The version of the ABI between the host and the guest: the layout of
+/// the [`HyperlightPEB`] and of the input and output buffers, and the
+/// protocol used to initialise the guest and call functions.
+///
+/// The host writes its version into [`HyperlightPEB::abi_version`], and
+/// the guest checks it before using anything else the host set up. A guest
+/// built for a different version aborts with
+/// [`ErrorCode::AbiMismatch`](crate::flatbuffer_wrappers::guest_error::ErrorCode::AbiMismatch),
+/// giving its own version in decimal as the abort message.
+///
+/// New fields are appended to the end of the [`HyperlightPEB`], and any
+/// change to the layout of the PEB or of the scratch region, or to how
+/// messages are laid out in the input and output buffers, bumps this
+/// version.
+pub const ABI_VERSION: u32 = 8;
+
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct HyperlightPEB {
+    /// The [`ABI_VERSION`] of the host. This is always the first field, so
+    /// that it can be read whatever the layout of the rest of the PEB.
+    pub abi_version: u64,
     pub input_stack: GuestMemoryRegion,
     pub output_stack: GuestMemoryRegion,
     pub init_data: GuestMemoryRegion,
     pub guest_heap: GuestMemoryRegion,
+    /// Opaque configuration handed to the guest when the sandbox is
+    /// created. Stored read-only immediately after the PEB (and the
+    /// file mappings array); `size` is 0 if there is none.
+    pub startup_config: GuestMemoryRegion,
+    /// Guest address of the function to run instead of `hyperlight_main`
+    /// when the guest is initialised, or 0 to run `hyperlight_main`.
+    pub init_entry: u64,
+    /// Arguments handed to the guest when the sandbox is created, stored
+    /// read-only after the startup config.
+    /// **Note:** `size` holds the **argument count** (`argc`), NOT a byte
+    /// size. `ptr` holds the guest address of a C-style `argv` array of
+    /// `argc` pointers to NUL-terminated UTF-8 strings, followed by a null
+    /// pointer.
+    pub args: GuestMemoryRegion,
     /// File mappings array descriptor.
     /// **Note:** `size` holds the **entry count** (number of valid
     /// [`FileMappingInfo`] entries), NOT a byte size. `ptr` holds the
@@ -79,4 +112,7 @@ pub struct HyperlightPEB {
     /// PEB struct).
     #[cfg(feature = "nanvix-unstable")]
     pub file_mappings: GuestMemoryRegion,
+    /// The [`CallEncoding`](crate::call_encoding::CallEncoding) the host
+    /// chose for function calls, by its identifier
+    pub call_encoding: u64,
 }
@@ -94,6 +94,13 @@ impl TryFrom<u8> for Exception {
 /// - TraceBatch: reports a batch of spans and events from the guest
 /// - TraceMemoryAlloc: records memory allocation events
 /// - TraceMemoryFree: records memory deallocation events
+/// - StreamChunk: sends one chunk of a streamed return value to the host
+/// - WriteOutput: sends one chunk of the guest's stdout (1) or stderr (2),
+///   as given by the value written to the port
+/// - ReadInput: asks the host for up to as many bytes of the guest's stdin
+///   as the value written to the port
+/// - Metric: sends a metric for the host to record, encoded as a
+///   `GuestMetric`
 pub enum OutBAction {
     Log = 99,
     CallFunction = 101,
@@ -105,6 +112,10 @@ pub enum OutBAction {
     TraceMemoryAlloc = 105,
     #[cfg(feature = "mem_profile")]
     TraceMemoryFree = 106,
+    StreamChunk = 109,
+    WriteOutput = 110,
+    ReadInput = 111,
+    Metric = 112,
 }
 
 /// IO-port actions intercepted at the hypervisor level (in `run_vcpu`)
@@ -137,6 +148,10 @@ impl TryFrom<u16> for OutBAction {
             105 => Ok(OutBAction::TraceMemoryAlloc),
             #[cfg(feature = "mem_profile")]
             106 => Ok(OutBAction::TraceMemoryFree),
+            109 => Ok(OutBAction::StreamChunk),
+            110 => Ok(OutBAction::WriteOutput),
+            111 => Ok(OutBAction::ReadInput),
+            112 => Ok(OutBAction::Metric),
             _ => Err(anyhow::anyhow!("Invalid OutBAction value: {}", val)),
         }
     }
@@ -0,0 +1,130 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The W3C trace context of a guest call, which the host writes to
+//! scratch memory before each call so the guest can tell which
+//! distributed trace its work is part of.
+
+use core::fmt;
+
+/// The size of an encoded [`TraceParent`] in scratch memory
+pub const TRACE_CONTEXT_SIZE: usize = 32;
+
+/// The `sampled` bit of the trace flags
+const FLAG_SAMPLED: u8 = 0x01;
+
+/// The trace and span a guest call is made in, as carried in a W3C
+/// `traceparent` header.
+///
+/// It is [`Display`](fmt::Display)ed in the `traceparent` header format,
+/// `00-<trace id>-<span id>-<flags>`, so the guest can pass it on to
+/// anything it calls out to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceParent {
+    /// The id of the trace
+    pub trace_id: [u8; 16],
+    /// The id of the host span the call is made in
+    pub span_id: [u8; 8],
+    /// The trace flags
+    pub flags: u8,
+}
+
+impl TraceParent {
+    /// Returns whether the trace is sampled, so work done for it should
+    /// be recorded
+    pub fn is_sampled(&self) -> bool {
+        self.flags & FLAG_SAMPLED != 0
+    }
+
+    /// Encode the trace context, or its absence, as it is laid out in
+    /// scratch memory: a presence byte, the flags, six bytes of padding,
+    /// the trace id and the span id.
+    pub fn encode(parent: Option<&Self>) -> [u8; TRACE_CONTEXT_SIZE] {
+        let mut bytes = [0; TRACE_CONTEXT_SIZE];
+        if let Some(parent) = parent {
+            bytes[0] = 1;
+            bytes[1] = parent.flags;
+            bytes[8..24].copy_from_slice(&parent.trace_id);
+            bytes[24..32].copy_from_slice(&parent.span_id);
+        }
+        bytes
+    }
+
+    /// Decode a trace context encoded with [`encode()`](Self::encode)
+    pub fn decode(bytes: &[u8; TRACE_CONTEXT_SIZE]) -> Option<Self> {
+        if bytes[0] == 0 {
+            return None;
+        }
+        let mut parent = Self {
+            trace_id: [0; 16],
+            span_id: [0; 8],
+            flags: bytes[1],
+        };
+        parent.trace_id.copy_from_slice(&bytes[8..24]);
+        parent.span_id.copy_from_slice(&bytes[24..32]);
+        Some(parent)
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("00-")?;
+        for b in self.trace_id {
+            write!(f, "{b:02x}")?;
+        }
+        f.write_str("-")?;
+        for b in self.span_id {
+            write!(f, "{b:02x}")?;
+        }
+        write!(f, "-{:02x}", self.flags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::TraceParent;
+
+    #[test]
+    fn round_trips_through_scratch_encoding() {
+        let parent = TraceParent {
+            trace_id: [0xab; 16],
+            span_id: [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08],
+            flags: 1,
+        };
+        let bytes = TraceParent::encode(Some(&parent));
+        assert_eq!(TraceParent::decode(&bytes), Some(parent));
+        assert_eq!(TraceParent::decode(&TraceParent::encode(None)), None);
+    }
+
+    #[test]
+    fn displays_as_traceparent_header() {
+        let parent = TraceParent {
+            trace_id: [
+                0x4b, 0xf9, 0x2f, 0x35, 0x77, 0xb3, 0x4d, 0xa6, 0xa3, 0xce, 0x92, 0x9d, 0x0e, 0x0e,
+                0x47, 0x36,
+            ],
+            span_id: [0x00, 0xf0, 0x67, 0xaa, 0x0b, 0xa9, 0x02, 0xb7],
+            flags: 1,
+        };
+        assert_eq!(
+            parent.to_string(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+        assert!(parent.is_sampled());
+    }
+}
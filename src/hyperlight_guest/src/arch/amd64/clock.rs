@@ -0,0 +1,206 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use core::arch::x86_64::{__cpuid, _mm_lfence, _rdtsc};
+use core::sync::atomic::{AtomicU8, Ordering, fence};
+
+use hyperlight_common::clock::{
+    HV_REFERENCE_TIME_TICK_NS, HV_X64_MSR_REFERENCE_TSC, HV_X64_MSR_TIME_REF_COUNT,
+    HvReferenceTscPage, MSR_KVM_SYSTEM_TIME_NEW, PvclockVcpuTimeInfo,
+};
+
+use crate::layout::{clock_page_gpa, clock_page_gva};
+
+/// The signature in CPUID leaf 0x40000000 under KVM
+const KVM_SIGNATURE: [u8; 12] = *b"KVMKVMKVM\0\0\0";
+/// The signature in CPUID leaf 0x40000000 under Hyper-V
+const HYPER_V_SIGNATURE: [u8; 12] = *b"Microsoft Hv";
+/// `KVM_FEATURE_CLOCKSOURCE2`, in EAX of CPUID leaf 0x40000001
+const KVM_FEATURE_CLOCKSOURCE2: u32 = 1 << 3;
+/// `AccessPartitionReferenceCounter`, in EAX of CPUID leaf 0x40000003
+const HV_ACCESS_REFERENCE_COUNTER: u32 = 1 << 1;
+/// `AccessPartitionReferenceTsc`, in EAX of CPUID leaf 0x40000003
+const HV_ACCESS_REFERENCE_TSC: u32 = 1 << 9;
+
+/// The paravirtual clock the hypervisor gives the guest
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Source {
+    Unknown,
+    None,
+    Kvm,
+    /// Hyper-V, with the reference TSC page
+    HyperVTsc,
+    /// Hyper-V, with only the reference time MSR
+    HyperVCounter,
+}
+
+/// The clock the guest has, found on first use, as CPUID exits the VM
+static SOURCE: AtomicU8 = AtomicU8::new(Source::Unknown as u8);
+
+pub(super) fn nanos() -> Option<u64> {
+    match source() {
+        Source::Kvm => kvm_nanos(),
+        Source::HyperVTsc => Some(hyper_v_nanos()),
+        Source::HyperVCounter => Some(reference_counter_nanos()),
+        Source::None | Source::Unknown => None,
+    }
+}
+
+fn source() -> Source {
+    match SOURCE.load(Ordering::Relaxed) {
+        s if s == Source::None as u8 => Source::None,
+        s if s == Source::Kvm as u8 => Source::Kvm,
+        s if s == Source::HyperVTsc as u8 => Source::HyperVTsc,
+        s if s == Source::HyperVCounter as u8 => Source::HyperVCounter,
+        _ => {
+            let source = detect();
+            SOURCE.store(source as u8, Ordering::Relaxed);
+            source
+        }
+    }
+}
+
+fn detect() -> Source {
+    // SAFETY: CPUID is always available on x86_64
+    let leaf = unsafe { __cpuid(0x4000_0000) };
+    let mut signature = [0; 12];
+    signature[0..4].copy_from_slice(&leaf.ebx.to_le_bytes());
+    signature[4..8].copy_from_slice(&leaf.ecx.to_le_bytes());
+    signature[8..12].copy_from_slice(&leaf.edx.to_le_bytes());
+    match signature {
+        KVM_SIGNATURE => {
+            let features = unsafe { __cpuid(0x4000_0001) }.eax;
+            if features & KVM_FEATURE_CLOCKSOURCE2 != 0 {
+                Source::Kvm
+            } else {
+                Source::None
+            }
+        }
+        HYPER_V_SIGNATURE if leaf.eax >= 0x4000_0003 => {
+            let features = unsafe { __cpuid(0x4000_0003) }.eax;
+            if features & HV_ACCESS_REFERENCE_TSC != 0 {
+                Source::HyperVTsc
+            } else if features & HV_ACCESS_REFERENCE_COUNTER != 0 {
+                Source::HyperVCounter
+            } else {
+                Source::None
+            }
+        }
+        _ => Source::None,
+    }
+}
+
+/// The TSC, read once the loads before it have completed
+fn rdtsc() -> u64 {
+    // SAFETY: LFENCE and RDTSC are always available on x86_64
+    unsafe {
+        _mm_lfence();
+        _rdtsc()
+    }
+}
+
+/// # Safety
+/// `msr` must be an MSR the hypervisor lets the guest read
+unsafe fn rdmsr(msr: u32) -> u64 {
+    let (lo, hi): (u32, u32);
+    unsafe {
+        core::arch::asm!(
+            "rdmsr",
+            in("ecx") msr,
+            out("eax") lo,
+            out("edx") hi,
+            options(nomem, nostack, preserves_flags)
+        );
+    }
+    ((hi as u64) << 32) | lo as u64
+}
+
+/// # Safety
+/// `msr` must be an MSR the hypervisor lets the guest write `value` to
+unsafe fn wrmsr(msr: u32, value: u64) {
+    unsafe {
+        core::arch::asm!(
+            "wrmsr",
+            in("ecx") msr,
+            in("eax") value as u32,
+            in("edx") (value >> 32) as u32,
+            options(nostack, preserves_flags)
+        );
+    }
+}
+
+fn kvm_nanos() -> Option<u64> {
+    let info = clock_page_gva() as *const PvclockVcpuTimeInfo;
+    // The clock page is always mapped, and only written by KVM, which
+    // makes `version` odd while it does.
+    unsafe {
+        // The host clears scratch memory when the sandbox is restored, so
+        // the guest asks again for KVM to keep the clock page up to date
+        // whenever it finds it empty
+        if !core::ptr::read_volatile(info).is_valid() {
+            wrmsr(MSR_KVM_SYSTEM_TIME_NEW, clock_page_gpa() | 1);
+        }
+        loop {
+            let version = core::ptr::read_volatile(&raw const (*info).version);
+            if version & 1 != 0 {
+                core::hint::spin_loop();
+                continue;
+            }
+            fence(Ordering::Acquire);
+            let time = core::ptr::read_volatile(info);
+            let tsc = rdtsc();
+            fence(Ordering::Acquire);
+            if core::ptr::read_volatile(&raw const (*info).version) == version {
+                return time.is_valid().then(|| time.nanos(tsc));
+            }
+        }
+    }
+}
+
+fn hyper_v_nanos() -> u64 {
+    let page = clock_page_gva() as *const HvReferenceTscPage;
+    // The clock page is always mapped, and overlaid by Hyper-V, which
+    // changes `tsc_sequence` whenever it updates it.
+    unsafe {
+        // Like under KVM, the overlay may be gone once the sandbox has
+        // been restored
+        if !core::ptr::read_volatile(page).is_valid() {
+            wrmsr(HV_X64_MSR_REFERENCE_TSC, clock_page_gpa() | 1);
+        }
+        loop {
+            let sequence = core::ptr::read_volatile(&raw const (*page).tsc_sequence);
+            fence(Ordering::Acquire);
+            let reference = core::ptr::read_volatile(page);
+            if !reference.is_valid() {
+                // Hyper-V can't give a reference TSC page on this host
+                return reference_counter_nanos();
+            }
+            let tsc = rdtsc();
+            fence(Ordering::Acquire);
+            if core::ptr::read_volatile(&raw const (*page).tsc_sequence) == sequence {
+                return reference.nanos(tsc);
+            }
+        }
+    }
+}
+
+/// The Hyper-V reference time, read from its MSR, which exits to the
+/// hypervisor, but not to the host
+fn reference_counter_nanos() -> u64 {
+    // SAFETY: the hypervisor has said that the guest can read the MSR
+    unsafe { rdmsr(HV_X64_MSR_TIME_REF_COUNT) * HV_REFERENCE_TIME_TICK_NS }
+}
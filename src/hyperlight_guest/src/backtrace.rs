@@ -0,0 +1,70 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Capturing the return addresses on the guest's stack, which the host
+//! resolves against the guest's symbols.
+
+use alloc::vec::Vec;
+
+/// The most frames a backtrace holds
+const MAX_FRAMES: usize = 64;
+
+/// The return addresses of the frames on the main stack, innermost first,
+/// found by following the chain of saved frame pointers.
+///
+/// The chain is only complete if the guest was built with frame pointers
+/// (`-C force-frame-pointers=yes`). Otherwise the backtrace may be empty,
+/// cut short, or include addresses that are not return addresses, but
+/// nothing outside the part of the stack in use is ever read.
+#[inline(never)]
+pub fn capture() -> Vec<u64> {
+    #[allow(unused_mut)]
+    let mut frames = Vec::new();
+    #[cfg(target_arch = "x86_64")]
+    {
+        use crate::layout::MAIN_STACK_TOP_GVA;
+
+        let (mut fp, sp): (u64, u64);
+        unsafe {
+            core::arch::asm!(
+                "mov {fp}, rbp",
+                "mov {sp}, rsp",
+                fp = out(reg) fp,
+                sp = out(reg) sp,
+                options(nomem, nostack, preserves_flags),
+            );
+        }
+        // Each frame holds the caller's frame pointer followed by the
+        // return address, and callers' frames are higher up the stack
+        let mut lowest = sp;
+        while frames.len() < MAX_FRAMES
+            && fp % 8 == 0
+            && fp >= lowest
+            && fp
+                .checked_add(16)
+                .is_some_and(|end| end <= MAIN_STACK_TOP_GVA)
+        {
+            let (next, ret) = unsafe { (*(fp as *const u64), *((fp + 8) as *const u64)) };
+            if ret == 0 {
+                break;
+            }
+            frames.push(ret);
+            lowest = fp + 16;
+            fp = next;
+        }
+    }
+    frames
+}
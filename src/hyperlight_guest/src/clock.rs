@@ -0,0 +1,59 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The guest's clocks, read from the paravirtual clock the hypervisor
+//! keeps up to date in scratch memory, so that reading the time exits
+//! neither to the host nor, once the clock is set up, to the hypervisor.
+
+use core::time::Duration;
+
+#[cfg_attr(target_arch = "x86_64", path = "arch/amd64/clock.rs")]
+#[cfg_attr(target_arch = "x86", path = "arch/i686/clock.rs")]
+#[cfg_attr(target_arch = "aarch64", path = "arch/aarch64/clock.rs")]
+mod arch;
+
+/// The time since the guest's clock started, which was when the sandbox
+/// was created, or `None` if the guest has no clock.
+///
+/// The guest has no clock when the hypervisor does not give it one, or
+/// when the host does not let it read the time, such as while its
+/// execution is being recorded or replayed.
+pub fn monotonic() -> Option<Duration> {
+    wall_clock_base()?;
+    arch::nanos().map(Duration::from_nanos)
+}
+
+/// The wall-clock time, as the time since the Unix epoch, or `None` if
+/// the guest has no clock (see [`monotonic()`]).
+///
+/// The host works out when the guest's clock started from its own clock
+/// when the sandbox is created and restored, so changes to the host's
+/// clock in between are not seen by the guest.
+pub fn wall_clock() -> Option<Duration> {
+    let base = wall_clock_base()?;
+    let nanos = base.checked_add(arch::nanos()?)?;
+    Some(Duration::from_nanos(nanos))
+}
+
+/// The wall-clock time the guest's clock started at, in nanoseconds since
+/// the Unix epoch, or `None` if the host has not let the guest read the
+/// time
+fn wall_clock_base() -> Option<u64> {
+    // The slot is always mapped, and the host only writes it between
+    // calls.
+    let base = unsafe { core::ptr::read_volatile(crate::layout::wall_clock_gva()) };
+    (base != 0).then_some(base)
+}
@@ -0,0 +1,58 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The control page shared with the host, through which the guest can
+//! report its progress and learn that the host wants it to stop,
+//! without exiting the VM.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use hyperlight_common::layout::{
+    CONTROL_PAGE_HEARTBEAT, CONTROL_PAGE_PROGRESS, CONTROL_PAGE_SOFT_CANCEL,
+};
+
+fn slot(offset: u64) -> &'static AtomicU64 {
+    // The control page is always mapped, aligned, and only ever accessed
+    // atomically, by both the host and the guest.
+    unsafe {
+        AtomicU64::from_ptr(
+            (crate::layout::control_page_gva() as *mut u8).add(offset as usize) as *mut u64,
+        )
+    }
+}
+
+/// Set the progress counter the host can read
+pub fn set_progress(value: u64) {
+    slot(CONTROL_PAGE_PROGRESS).store(value, Ordering::Release);
+}
+
+/// Add `delta` to the progress counter the host can read
+pub fn add_progress(delta: u64) {
+    slot(CONTROL_PAGE_PROGRESS).fetch_add(delta, Ordering::Release);
+}
+
+/// Show the host that the guest is still making progress
+pub fn heartbeat() {
+    slot(CONTROL_PAGE_HEARTBEAT).fetch_add(1, Ordering::Release);
+}
+
+/// Returns whether the host has asked the guest to stop what it is doing.
+///
+/// Unlike killing the call, this is only a request: the guest decides
+/// when and how to stop, and can return a result as usual.
+pub fn soft_cancel_requested() -> bool {
+    slot(CONTROL_PAGE_SOFT_CANCEL).load(Ordering::Acquire) != 0
+}
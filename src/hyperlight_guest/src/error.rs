@@ -16,40 +16,44 @@ limitations under the License.
 
 use alloc::format;
 use alloc::string::{String, ToString as _};
+use alloc::vec::Vec;
 
 pub use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
 use hyperlight_common::func::Error as FuncError;
 use {anyhow, serde_json};
 
+use crate::backtrace;
+
 pub type Result<T> = core::result::Result<T, HyperlightGuestError>;
 
 #[derive(Debug)]
 pub struct HyperlightGuestError {
     pub kind: ErrorCode,
     pub message: String,
+    /// The return addresses on the stack where the error was created,
+    /// innermost first, which are sent to the host with the error
+    pub backtrace: Vec<u64>,
 }
 
 impl HyperlightGuestError {
     pub fn new(kind: ErrorCode, message: String) -> Self {
-        Self { kind, message }
+        Self {
+            kind,
+            message,
+            backtrace: backtrace::capture(),
+        }
     }
 }
 
 impl From<anyhow::Error> for HyperlightGuestError {
     fn from(error: anyhow::Error) -> Self {
-        Self {
-            kind: ErrorCode::GuestError,
-            message: format!("Error: {:?}", error),
-        }
+        Self::new(ErrorCode::GuestError, format!("Error: {:?}", error))
     }
 }
 
 impl From<serde_json::Error> for HyperlightGuestError {
     fn from(error: serde_json::Error) -> Self {
-        Self {
-            kind: ErrorCode::GuestError,
-            message: format!("Error: {:?}", error),
-        }
+        Self::new(ErrorCode::GuestError, format!("Error: {:?}", error))
     }
 }
 
@@ -14,8 +14,14 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use alloc::format;
+
+use hyperlight_common::call_encoding::CallEncoding;
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
 use hyperlight_common::mem::HyperlightPEB;
 
+use crate::error::{HyperlightGuestError, Result};
+
 /// A guest handle holds the `HyperlightPEB` and enables the guest to perform
 /// operations like:
 /// - calling host functions,
@@ -45,4 +51,18 @@ impl GuestHandle {
     pub fn peb(&self) -> Option<*mut HyperlightPEB> {
         self.peb
     }
+
+    /// Returns the encoding the host chose for function calls, or an
+    /// [`ErrorCode::UnsupportedCallEncoding`] error if this guest was built
+    /// without support for it
+    pub fn call_encoding(&self) -> Result<CallEncoding> {
+        let peb_ptr = self.peb().unwrap();
+        let id = unsafe { (*peb_ptr).call_encoding };
+        CallEncoding::from_id(id).ok_or_else(|| {
+            HyperlightGuestError::new(
+                ErrorCode::UnsupportedCallEncoding,
+                format!("The guest does not support call encoding {id}"),
+            )
+        })
+    }
 }
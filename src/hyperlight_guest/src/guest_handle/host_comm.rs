@@ -17,16 +17,17 @@ limitations under the License.
 use alloc::format;
 use alloc::string::ToString;
 use alloc::vec::Vec;
+use core::ffi::{CStr, c_char};
 
-use flatbuffers::FlatBufferBuilder;
-use hyperlight_common::flatbuffer_wrappers::function_call::{FunctionCall, FunctionCallType};
+use hyperlight_common::call_encoding::WireFormat;
+use hyperlight_common::flatbuffer_wrappers::function_call::{FunctionCallType, ParameterRef};
 use hyperlight_common::flatbuffer_wrappers::function_types::{
-    FunctionCallResult, ParameterValue, ReturnType, ReturnValue,
+    ParameterValue, ReturnType, ReturnValue,
 };
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
 use hyperlight_common::flatbuffer_wrappers::guest_log_data::GuestLogData;
 use hyperlight_common::flatbuffer_wrappers::guest_log_level::LogLevel;
-use hyperlight_common::flatbuffer_wrappers::util::estimate_flatbuffer_capacity;
+use hyperlight_common::guest_metric::GuestMetric;
 use hyperlight_common::outb::OutBAction;
 use tracing::instrument;
 
@@ -59,6 +60,35 @@ impl GuestHandle {
         }
     }
 
+    /// Get the startup configuration the host passed to the sandbox.
+    ///
+    /// Returns an empty slice if the host did not pass one. The
+    /// configuration lives in read-only memory for the whole lifetime of
+    /// the sandbox.
+    pub fn startup_config(&self) -> &'static [u8] {
+        let peb_ptr = self.peb().unwrap();
+        let config = unsafe { (*peb_ptr).startup_config };
+        if config.size == 0 {
+            return &[];
+        }
+        unsafe { core::slice::from_raw_parts(config.ptr as *const u8, config.size as usize) }
+    }
+
+    /// Get the command-line style arguments the host passed to the sandbox.
+    ///
+    /// The arguments live in read-only memory for the whole lifetime of
+    /// the sandbox.
+    pub fn args(&self) -> impl ExactSizeIterator<Item = &'static str> + use<> {
+        let peb_ptr = self.peb().unwrap();
+        let args = unsafe { (*peb_ptr).args };
+        let argv = args.ptr as *const *const c_char;
+        (0..args.size as usize).map(move |i| {
+            let arg = unsafe { CStr::from_ptr(*argv.add(i)) };
+            // The host only passes valid UTF-8 strings
+            arg.to_str().unwrap_or_default()
+        })
+    }
+
     /// Get a return value from a host function call.
     /// This usually requires a host function to be called first using
     /// `call_host_function_internal`.
@@ -67,38 +97,26 @@ impl GuestHandle {
     /// internally to get the return value.
     #[instrument(skip_all, level = "Trace")]
     pub fn get_host_return_value<T: TryFrom<ReturnValue>>(&self) -> Result<T> {
-        let inner = self
-            .try_pop_shared_input_data_into::<FunctionCallResult>()
-            .expect("Unable to deserialize a return value from host")
-            .into_inner();
-
-        match inner {
-            Ok(ret) => T::try_from(ret).map_err(|_| {
-                let expected = core::any::type_name::<T>();
-                HyperlightGuestError::new(
-                    ErrorCode::UnsupportedParameterType,
-                    format!("Host return value could not be converted to expected {expected}",),
-                )
-            }),
-            Err(e) => Err(HyperlightGuestError {
-                kind: e.code,
-                message: e.message,
-            }),
-        }
+        let ret = self.get_host_return_raw()?;
+        T::try_from(ret).map_err(|_| {
+            let expected = core::any::type_name::<T>();
+            HyperlightGuestError::new(
+                ErrorCode::UnsupportedParameterType,
+                format!("Host return value could not be converted to expected {expected}",),
+            )
+        })
     }
 
     pub fn get_host_return_raw(&self) -> Result<ReturnValue> {
+        let encoding = self.call_encoding()?;
         let inner = self
-            .try_pop_shared_input_data_into::<FunctionCallResult>()
+            .try_pop_shared_input_data_with(|data| encoding.decode_function_call_result(data))
             .expect("Unable to deserialize a return value from host")
             .into_inner();
 
         match inner {
             Ok(ret) => Ok(ret),
-            Err(e) => Err(HyperlightGuestError {
-                kind: e.code,
-                message: e.message,
-            }),
+            Err(e) => Err(HyperlightGuestError::new(e.code, e.message)),
         }
     }
 
@@ -114,20 +132,20 @@ impl GuestHandle {
         parameters: Option<Vec<ParameterValue>>,
         return_type: ReturnType,
     ) -> Result<()> {
-        let estimated_capacity =
-            estimate_flatbuffer_capacity(function_name, parameters.as_deref().unwrap_or(&[]));
-
-        let host_function_call = FunctionCall::new(
-            function_name.to_string(),
-            parameters,
-            FunctionCallType::Host,
-            return_type,
-        );
-
-        let mut builder = FlatBufferBuilder::with_capacity(estimated_capacity);
-
-        let host_function_call_buffer = host_function_call.encode(&mut builder);
-        self.push_shared_output_data(host_function_call_buffer)?;
+        let parameters: Vec<ParameterRef> = parameters
+            .into_iter()
+            .flatten()
+            .map(ParameterRef::Value)
+            .collect();
+        let host_function_call_buffer = self
+            .call_encoding()?
+            .encode_function_call(
+                function_name,
+                &parameters,
+                FunctionCallType::Host,
+                return_type,
+            )?;
+        self.push_shared_output_data(&host_function_call_buffer)?;
 
         unsafe {
             out32(OutBAction::CallFunction as u16, 0);
@@ -152,6 +170,99 @@ impl GuestHandle {
         self.get_host_return_value::<T>()
     }
 
+    /// Send `data` to the host as part of the streamed return value of the
+    /// current guest function call.
+    ///
+    /// Data larger than the output buffer is split into several chunks.
+    /// The host receives each chunk as soon as it is written, so a guest
+    /// function can return far more data than fits in the output buffer.
+    #[instrument(skip_all, level = "Trace")]
+    pub fn write_stream_chunk(&self, data: &[u8]) -> Result<()> {
+        self.write_chunks(data, OutBAction::StreamChunk as u16, 0)
+    }
+
+    /// Write `data` to the guest's stdout (`fd` 1) or stderr (`fd` 2),
+    /// which the host sends to the sink it set for the stream.
+    #[instrument(skip_all, level = "Trace")]
+    pub fn write_output(&self, fd: u32, data: &[u8]) -> Result<()> {
+        self.write_chunks(data, OutBAction::WriteOutput as u16, fd)
+    }
+
+    /// Send `metric` to the host, which records it with its `metrics`
+    /// recorder along with the labels of the sandbox.
+    ///
+    /// Fails if the name of the metric is not valid, as described by
+    /// [`GuestMetric::is_valid_name()`].
+    #[instrument(skip_all, level = "Trace")]
+    pub fn emit_metric(&self, metric: &GuestMetric) -> Result<()> {
+        if !GuestMetric::is_valid_name(metric.name()) {
+            return Err(HyperlightGuestError::new(
+                ErrorCode::GuestError,
+                format!("Invalid metric name: {:?}", metric.name()),
+            ));
+        }
+        self.write_chunks(&metric.encode(), OutBAction::Metric as u16, 0)
+    }
+
+    /// Read the next bytes of the guest's stdin from the host into `buf`,
+    /// returning how many were read. This is 0 only at the end of the
+    /// input, or if `buf` is empty.
+    ///
+    /// The host may return fewer bytes than `buf` can hold, such as when
+    /// no more input is available yet, or it does not fit in the input
+    /// buffer.
+    #[instrument(skip_all, level = "Trace")]
+    pub fn read_input(&self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        unsafe {
+            out32(
+                OutBAction::ReadInput as u16,
+                buf.len().min(u32::MAX as usize) as u32,
+            );
+        }
+        // The host sends the bytes with a size prefix
+        self.try_pop_shared_input_data_with(|data| {
+            let len = u32::from_le_bytes(data.get(..4).ok_or(())?.try_into().map_err(|_| ())?);
+            let input = data.get(4..4 + len as usize).ok_or(())?;
+            buf.get_mut(..input.len()).ok_or(())?.copy_from_slice(input);
+            Ok::<_, ()>(input.len())
+        })
+    }
+
+    /// Send `data` to the host in chunks that fit in the output buffer,
+    /// writing `value` to `port` after each one
+    fn write_chunks(&self, data: &[u8], port: u16, value: u32) -> Result<()> {
+        let peb_ptr = self.peb().unwrap();
+        let output_stack_size = unsafe { (*peb_ptr).output_stack.size as usize };
+        // Leave room for the buffer's stack pointer, the back-pointer to
+        // the chunk and the chunk's size prefix
+        let max_chunk_size = output_stack_size.saturating_sub(8 + 8 + 4) & !7;
+        if max_chunk_size == 0 {
+            return Err(HyperlightGuestError::new(
+                ErrorCode::GuestError,
+                "Shared output buffer too small for chunks".to_string(),
+            ));
+        }
+
+        for chunk in data.chunks(max_chunk_size) {
+            // The host reads chunks as size-prefixed buffers of at least 8
+            // bytes, so short chunks are padded
+            let mut buffer = Vec::with_capacity((chunk.len() + 4).max(8));
+            buffer.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            buffer.extend_from_slice(chunk);
+            buffer.resize(buffer.len().max(8), 0);
+            self.push_shared_output_data(&buffer)?;
+
+            unsafe {
+                out32(port, value);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Log a message with the specified log level, source, caller, source file, and line number.
     pub fn log_message(
         &self,
@@ -162,46 +273,50 @@ impl GuestHandle {
         source_file: &str,
         line: u32,
     ) {
-        // Closure to send log message to host
-        let _send_to_host = || {
-            let guest_log_data = GuestLogData::new(
-                message.to_string(),
-                source.to_string(),
-                log_level,
-                caller.to_string(),
-                source_file.to_string(),
-                line,
-            );
-
-            let bytes: Vec<u8> = guest_log_data
-                .try_into()
-                .expect("Failed to convert GuestLogData to bytes");
-
-            self.push_shared_output_data(&bytes)
-                .expect("Unable to push log data to shared output data");
-
-            unsafe {
-                out32(OutBAction::Log as u16, 0);
-            }
-        };
+        self.log_record(GuestLogData::new(
+            message.to_string(),
+            source.to_string(),
+            log_level,
+            caller.to_string(),
+            source_file.to_string(),
+            line,
+        ));
+    }
 
+    /// Log a record, which may have a target and structured fields that
+    /// the host turns into the target and fields of a `tracing` event.
+    pub fn log_record(&self, record: GuestLogData) {
         #[cfg(all(feature = "trace_guest", target_arch = "x86_64"))]
         if hyperlight_guest_tracing::is_trace_enabled() {
             // If the "trace_guest" feature is enabled and tracing is initialized, log using tracing
+            let fields = record
+                .fields
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>()
+                .join(" ");
             tracing::trace!(
-                event = message,
-                level = ?log_level,
-                code.filepath = source,
-                caller = caller,
-                source_file = source_file,
-                code.lineno = line,
+                event = record.message.as_str(),
+                level = ?record.level,
+                code.filepath = record.source.as_str(),
+                caller = record.caller.as_str(),
+                source_file = record.source_file.as_str(),
+                code.lineno = record.line,
+                target = record.target.as_str(),
+                fields = fields.as_str(),
             );
-        } else {
-            _send_to_host();
+            return;
         }
-        #[cfg(not(all(feature = "trace_guest", target_arch = "x86_64")))]
-        {
-            _send_to_host();
+
+        let bytes: Vec<u8> = record
+            .try_into()
+            .expect("Failed to convert GuestLogData to bytes");
+
+        self.push_shared_output_data(&bytes)
+            .expect("Unable to push log data to shared output data");
+
+        unsafe {
+            out32(OutBAction::Log as u16, 0);
         }
     }
 }
@@ -32,6 +32,16 @@ impl GuestHandle {
     where
         T: for<'a> TryFrom<&'a [u8]>,
     {
+        self.try_pop_shared_input_data_with(|buffer| T::try_from(buffer).map_err(|_| ()))
+    }
+
+    /// Pops the top element from the shared input data buffer and decodes
+    /// it into a T with `decode`
+    #[instrument(skip_all, level = "Trace")]
+    pub fn try_pop_shared_input_data_with<T, E>(
+        &self,
+        decode: impl FnOnce(&[u8]) -> core::result::Result<T, E>,
+    ) -> Result<T> {
         let peb_ptr = self.peb().unwrap();
         let input_stack_size = unsafe { (*peb_ptr).input_stack.size as usize };
         let input_stack_ptr = unsafe { (*peb_ptr).input_stack.ptr as *mut u8 };
@@ -69,7 +79,7 @@ impl GuestHandle {
         let buffer = &idb[last_element_offset_rel as usize..];
 
         // convert the buffer to T
-        let type_t = match T::try_from(buffer) {
+        let type_t = match decode(buffer) {
             Ok(t) => Ok(t),
             Err(_e) => {
                 return Err(HyperlightGuestError::new(
@@ -88,6 +98,19 @@ impl GuestHandle {
         type_t
     }
 
+    /// Whether the shared input data buffer has anything left to pop
+    pub fn has_shared_input_data(&self) -> bool {
+        let peb_ptr = self.peb().unwrap();
+        let input_stack_size = unsafe { (*peb_ptr).input_stack.size as usize };
+        let input_stack_ptr = unsafe { (*peb_ptr).input_stack.ptr as *const u8 };
+        if input_stack_size < 8 {
+            return false;
+        }
+        let stack_ptr_rel = u64::from_le_bytes(unsafe { *(input_stack_ptr as *const [u8; 8]) });
+        // An empty buffer's stack pointer is 8
+        stack_ptr_rel > 8
+    }
+
     /// Pushes the given data onto the shared output data buffer.
     pub fn push_shared_output_data(&self, data: &[u8]) -> Result<()> {
         let peb_ptr = self.peb().unwrap();
@@ -120,8 +143,10 @@ impl GuestHandle {
             ));
         }
 
-        // check if there is enough space in the buffer
-        let size_required = data.len() + 8; // the data plus the pointer pointing to the data
+        // check if there is enough space in the buffer. The data is padded to
+        // a multiple of 8 bytes, so that every element starts 8 byte aligned
+        let padded_len = data.len().next_multiple_of(8);
+        let size_required = padded_len + 8; // the data plus the pointer pointing to the data
         let size_available = output_stack_size - stack_ptr_rel as usize;
         if size_required > size_available {
             return Err(HyperlightGuestError::new(
@@ -133,16 +158,17 @@ impl GuestHandle {
             ));
         }
 
-        // write the actual data
-        odb[stack_ptr_rel as usize..stack_ptr_rel as usize + data.len()].copy_from_slice(data);
+        // write the actual data, followed by its padding
+        let start = stack_ptr_rel as usize;
+        odb[start..start + data.len()].copy_from_slice(data);
+        odb[start + data.len()..start + padded_len].fill(0);
 
         // write the offset to the newly written data, to the top of the stack
         let bytes: [u8; 8] = stack_ptr_rel.to_le_bytes();
-        odb[stack_ptr_rel as usize + data.len()..stack_ptr_rel as usize + data.len() + 8]
-            .copy_from_slice(&bytes);
+        odb[start + padded_len..start + padded_len + 8].copy_from_slice(&bytes);
 
         // update stack pointer to point to next free address
-        let new_stack_ptr_rel: u64 = (stack_ptr_rel as usize + data.len() + 8) as u64;
+        let new_stack_ptr_rel: u64 = (start + padded_len + 8) as u64;
         odb[0..8].copy_from_slice(&(new_stack_ptr_rel).to_le_bytes());
 
         Ok(())
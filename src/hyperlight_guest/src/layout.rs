@@ -36,6 +36,32 @@ pub fn snapshot_generation_gva() -> *mut u64 {
     use hyperlight_common::layout::{MAX_GVA, SCRATCH_TOP_SNAPSHOT_GENERATION_OFFSET};
     (MAX_GVA as u64 - SCRATCH_TOP_SNAPSHOT_GENERATION_OFFSET + 1) as *mut u64
 }
+pub fn stack_policy_gva() -> *mut u64 {
+    use hyperlight_common::layout::{MAX_GVA, SCRATCH_TOP_STACK_POLICY_OFFSET};
+    (MAX_GVA as u64 - SCRATCH_TOP_STACK_POLICY_OFFSET + 1) as *mut u64
+}
+pub fn control_page_gva() -> *mut u64 {
+    use hyperlight_common::layout::{MAX_GVA, SCRATCH_TOP_CONTROL_PAGE_OFFSET};
+    (MAX_GVA as u64 - SCRATCH_TOP_CONTROL_PAGE_OFFSET + 1) as *mut u64
+}
+pub fn trace_context_gva() -> *const u8 {
+    use hyperlight_common::layout::{MAX_GVA, SCRATCH_TOP_TRACE_CONTEXT_OFFSET};
+    (MAX_GVA as u64 - SCRATCH_TOP_TRACE_CONTEXT_OFFSET + 1) as *const u8
+}
+pub fn wall_clock_gva() -> *const u64 {
+    use hyperlight_common::layout::{MAX_GVA, SCRATCH_TOP_WALL_CLOCK_OFFSET};
+    (MAX_GVA as u64 - SCRATCH_TOP_WALL_CLOCK_OFFSET + 1) as *const u64
+}
+pub fn clock_page_gva() -> *const u8 {
+    use hyperlight_common::layout::{MAX_GVA, SCRATCH_TOP_CLOCK_PAGE_OFFSET};
+    (MAX_GVA as u64 - SCRATCH_TOP_CLOCK_PAGE_OFFSET + 1) as *const u8
+}
+/// The GPA of the clock page, which the hypervisor is told to keep the
+/// paravirtual clock in
+pub fn clock_page_gpa() -> u64 {
+    use hyperlight_common::layout::{MAX_GPA, SCRATCH_TOP_CLOCK_PAGE_OFFSET};
+    MAX_GPA as u64 - SCRATCH_TOP_CLOCK_PAGE_OFFSET + 1
+}
 pub use arch::{scratch_base_gpa, scratch_base_gva};
 
 /// Returns a pointer to the guest counter u64 in scratch memory.
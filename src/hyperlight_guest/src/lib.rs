@@ -21,10 +21,14 @@ compile_error!("trace_guest feature is only supported on x86_64 architecture");
 extern crate alloc;
 
 // Modules
+pub mod backtrace;
+pub mod clock;
+pub mod control;
 pub mod error;
 pub mod exit;
 pub mod layout;
 pub mod prim_alloc;
+pub mod trace_context;
 pub mod types;
 
 pub mod guest_handle {
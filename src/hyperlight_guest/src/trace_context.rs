@@ -0,0 +1,37 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The distributed trace the current guest call is part of, as passed in
+//! by the host.
+
+pub use hyperlight_common::trace_context::TraceParent;
+use hyperlight_common::trace_context::TRACE_CONTEXT_SIZE;
+
+/// The W3C trace context of the current guest call, or `None` if the host
+/// did not make the call inside a trace.
+///
+/// Its `Display` form is a `traceparent` header value, which the guest can
+/// pass on to host functions so that the work they do joins the trace.
+pub fn current() -> Option<TraceParent> {
+    // The trace context is always mapped, and the host only writes it
+    // between calls.
+    let bytes = unsafe {
+        core::ptr::read_volatile(
+            crate::layout::trace_context_gva() as *const [u8; TRACE_CONTEXT_SIZE],
+        )
+    };
+    TraceParent::decode(&bytes)
+}
@@ -25,6 +25,10 @@ pub mod dispatch {
     }
 }
 
+pub(crate) mod stack {
+    pub(crate) fn zero_unused_stack() {}
+}
+
 /// The entrypoint for the guest binary — called by the hypervisor.
 ///
 /// On aarch64 this is a stub that will be implemented when the
@@ -21,10 +21,11 @@ use hyperlight_common::vmem::{
     BasicMapping, CowMapping, MappingKind, PAGE_SIZE, PhysAddr, VirtAddr,
 };
 use hyperlight_guest::exit::write_abort;
-use hyperlight_guest::layout::{MAIN_STACK_LIMIT_GVA, MAIN_STACK_TOP_GVA};
+use hyperlight_guest::layout::MAIN_STACK_TOP_GVA;
 
 use super::super::context::Context;
 use super::super::machine::ExceptionInfo;
+use super::super::stack::{note_stack_page, stack_limit_gva};
 use crate::{ErrorCode, HyperlightAbortWriter};
 
 /// Array of installed exception handlers for vectors 0-30.
@@ -83,6 +84,7 @@ fn handle_stack_pagefault(gva: u64) {
         // after [`iret`], which is a serializing instruction, so
         // that's already handled as well.
     }
+    note_stack_page(gva & !0xfff);
 }
 
 fn handle_cow_pagefault(_phys: PhysAddr, virt: VirtAddr, perms: CowMapping) {
@@ -134,8 +136,10 @@ fn try_handle_internal_pagefault(
     let present = (error_code & (1 << 0)) != 0; // bit 0 is P
     if !present {
         // If the fault was caused by a not-present page, check if we
-        // should populate it with a stack page
-        if (MAIN_STACK_LIMIT_GVA..MAIN_STACK_TOP_GVA).contains(&gva) {
+        // should populate it with a stack page. Faults below the
+        // stack limit set by the host for this call are left
+        // unhandled, and so abort the call.
+        if (stack_limit_gva()..MAIN_STACK_TOP_GVA).contains(&gva) {
             handle_stack_pagefault(gva);
             return true;
         }
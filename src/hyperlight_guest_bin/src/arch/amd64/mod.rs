@@ -20,3 +20,4 @@ pub mod exception;
 mod init;
 mod layout;
 pub(crate) mod machine;
+pub(crate) mod stack;
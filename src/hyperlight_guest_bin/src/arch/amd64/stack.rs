@@ -0,0 +1,74 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+ */
+
+//! Management of the main guest stack, according to the stack policy
+//! set by the host for each call.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use hyperlight_common::layout::STACK_POLICY_ZERO_STACK;
+use hyperlight_common::vmem::PAGE_SIZE;
+use hyperlight_guest::layout::{MAIN_STACK_LIMIT_GVA, MAIN_STACK_TOP_GVA, stack_policy_gva};
+
+/// The base of the lowest page of the main stack that has been mapped
+/// so far. The top page is mapped during initialisation.
+static LOWEST_STACK_PAGE: AtomicU64 = AtomicU64::new((MAIN_STACK_TOP_GVA - 1) & !0xfff);
+
+fn stack_policy() -> u64 {
+    unsafe { stack_policy_gva().read_volatile() }
+}
+
+/// The lowest address the main stack may grow down to during the
+/// current call
+pub(crate) fn stack_limit_gva() -> u64 {
+    let max_size = stack_policy() & !(PAGE_SIZE as u64 - 1);
+    if max_size == 0 {
+        MAIN_STACK_LIMIT_GVA
+    } else {
+        MAIN_STACK_TOP_GVA
+            .saturating_sub(max_size)
+            .max(MAIN_STACK_LIMIT_GVA)
+    }
+}
+
+/// Record that the stack page starting at `page_base` has been mapped
+pub(crate) fn note_stack_page(page_base: u64) {
+    LOWEST_STACK_PAGE.fetch_min(page_base, Ordering::Relaxed);
+}
+
+/// If the host asked for it, zero every mapped stack page below the
+/// current stack pointer, so that nothing a call left on the stack is
+/// visible to the next one.
+pub(crate) fn zero_unused_stack() {
+    if stack_policy() & STACK_POLICY_ZERO_STACK == 0 {
+        return;
+    }
+    let lowest = LOWEST_STACK_PAGE.load(Ordering::Relaxed);
+    // The zeroing must not itself touch the part of the stack that is
+    // being zeroed, so it is done with a single `rep stosb` rather than
+    // by calling a function.
+    unsafe {
+        core::arch::asm!(
+            "mov rcx, rsp",
+            "sub rcx, rdi",
+            "rep stosb",
+            inout("rdi") lowest => _,
+            out("rcx") _,
+            in("al") 0u8,
+            options(nostack),
+        );
+    }
+}
@@ -0,0 +1,27 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! The runtime hooks of SanitizerCoverage's inline 8-bit edge counters.
+//!
+//! An instrumented guest increments its counters in place, and the host
+//! reads them from the guest's `__sancov_cntrs` section, so the hooks the
+//! instrumentation calls when the guest starts have nothing to do.
+
+#[unsafe(no_mangle)]
+extern "C" fn __sanitizer_cov_8bit_counters_init(_start: *mut u8, _stop: *mut u8) {}
+
+#[unsafe(no_mangle)]
+extern "C" fn __sanitizer_cov_pcs_init(_pcs_beg: *const usize, _pcs_end: *const usize) {}
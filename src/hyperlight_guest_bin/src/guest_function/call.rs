@@ -18,11 +18,16 @@ use alloc::format;
 use alloc::vec::Vec;
 
 use flatbuffers::FlatBufferBuilder;
-use hyperlight_common::flatbuffer_wrappers::function_call::{FunctionCall, FunctionCallType};
+use hyperlight_common::call_encoding::{ResultDescriptor, WireFormat};
+use hyperlight_common::flatbuffer_wrappers::function_call::{
+    FunctionCall, FunctionCallType, GUEST_FUNCTIONS_FUNCTION_NAME,
+};
 use hyperlight_common::flatbuffer_wrappers::function_types::{FunctionCallResult, ParameterType};
 use hyperlight_common::flatbuffer_wrappers::guest_error::{ErrorCode, GuestError};
+use hyperlight_common::flatbuffer_wrappers::util::get_flatbuffer_result;
 use hyperlight_guest::bail;
 use hyperlight_guest::error::{HyperlightGuestError, Result};
+use hyperlight_guest::guest_handle::handle::GuestHandle;
 use tracing::instrument;
 
 use crate::{GUEST_HANDLE, REGISTERED_GUEST_FUNCTIONS};
@@ -52,6 +57,10 @@ pub(crate) fn call_guest_function(function_call: FunctionCall) -> Result<Vec<u8>
         ));
     }
 
+    if function_call.function_name == GUEST_FUNCTIONS_FUNCTION_NAME {
+        return describe_guest_functions();
+    }
+
     // Find the function definition for the function call.
     // Use &raw const to get an immutable reference to the static HashMap
     // this is to avoid the clippy warning "shared reference to mutable static"
@@ -85,6 +94,38 @@ pub(crate) fn call_guest_function(function_call: FunctionCall) -> Result<Vec<u8>
     }
 }
 
+/// Tell the host which guest functions are registered
+fn describe_guest_functions() -> Result<Vec<u8>> {
+    #[allow(clippy::deref_addrof)]
+    let details = unsafe { (*(&raw const REGISTERED_GUEST_FUNCTIONS)).details() };
+    let bytes = Vec::<u8>::try_from(&details)?;
+    Ok(get_flatbuffer_result(bytes.as_slice()))
+}
+
+/// Results that did not fit in the output buffer, which are kept until the
+/// next guest call so that the host can read them from guest memory
+static mut LARGE_RESULTS: Vec<Vec<u8>> = Vec::new();
+
+/// Send the result of a guest function call to the host, through the
+/// output buffer if it fits, or otherwise as a descriptor of where the host
+/// can find it in guest memory
+fn push_result(handle: GuestHandle, bytes: Vec<u8>) {
+    if handle.push_shared_output_data(bytes.as_slice()).is_ok() {
+        return;
+    }
+    let descriptor = ResultDescriptor {
+        gva: bytes.as_ptr() as u64,
+        len: bytes.len() as u64,
+    };
+    handle
+        .push_shared_output_data(&descriptor.encode())
+        .expect("Failed to serialize function call result");
+    #[allow(clippy::deref_addrof)]
+    unsafe {
+        (*(&raw mut LARGE_RESULTS)).push(bytes)
+    };
+}
+
 pub(crate) fn internal_dispatch_function() {
     // Read the current TSC to report it to the host with the spans/events
     // This helps calculating the timestamps relative to the guest call
@@ -100,29 +141,43 @@ pub(crate) fn internal_dispatch_function() {
 
     let handle = unsafe { GUEST_HANDLE };
 
-    let function_call = handle
-        .try_pop_shared_input_data_into::<FunctionCall>()
-        .expect("Function call deserialization failed");
-
-    let res = call_guest_function(function_call);
-
-    match res {
-        Ok(bytes) => {
-            handle
-                .push_shared_output_data(bytes.as_slice())
-                .expect("Failed to serialize function call result");
+    // The host has read the previous call's results by now
+    unsafe { LARGE_RESULTS = Vec::new() };
+
+    // The host may pass a batch of calls, which are made in turn, each
+    // pushing its result before the next is popped
+    // The encoding was checked when the guest was initialised
+    let encoding = handle
+        .call_encoding()
+        .expect("Unsupported call encoding");
+    loop {
+        let function_call = handle
+            .try_pop_shared_input_data_with(|data| encoding.decode_function_call(data))
+            .expect("Function call deserialization failed");
+
+        let res = call_guest_function(function_call);
+
+        match res {
+            Ok(bytes) => push_result(handle, bytes),
+            Err(err) => {
+                let guest_error =
+                    Err(GuestError::new(err.kind, err.message).with_backtrace(err.backtrace));
+                let fcr = FunctionCallResult::new(guest_error);
+                let mut builder = FlatBufferBuilder::new();
+                let data = fcr.encode(&mut builder);
+                handle
+                    .push_shared_output_data(data)
+                    .expect("Failed to serialize function call result");
+            }
         }
-        Err(err) => {
-            let guest_error = Err(GuestError::new(err.kind, err.message));
-            let fcr = FunctionCallResult::new(guest_error);
-            let mut builder = FlatBufferBuilder::new();
-            let data = fcr.encode(&mut builder);
-            handle
-                .push_shared_output_data(data)
-                .expect("Failed to serialize function call result");
+
+        if !handle.has_shared_input_data() {
+            break;
         }
     }
 
+    crate::arch::stack::zero_unused_stack();
+
     // All this tracing logic shall be done right before the call to `hlt` which is done after this
     // function returns
     #[cfg(all(feature = "trace_guest", target_arch = "x86_64"))]
@@ -17,6 +17,9 @@ limitations under the License.
 use alloc::collections::BTreeMap;
 use alloc::string::String;
 
+use hyperlight_common::flatbuffer_wrappers::function_call::SHUTDOWN_FUNCTION_NAME;
+use hyperlight_common::flatbuffer_wrappers::host_function_definition::HostFunctionDefinition;
+use hyperlight_common::flatbuffer_wrappers::host_function_details::HostFunctionDetails;
 use hyperlight_common::func::{ParameterTuple, SupportedReturnType};
 
 use super::definition::{GuestFunc, GuestFunctionDefinition};
@@ -62,6 +65,24 @@ impl<F: Copy> GuestFunctionRegister<F> {
     pub fn get(&self, function_name: &str) -> Option<&GuestFunctionDefinition<F>> {
         self.guest_functions.get(function_name)
     }
+
+    /// Describes the registered functions, for the host
+    pub fn details(&self) -> HostFunctionDetails {
+        let functions = self
+            .guest_functions
+            .values()
+            .map(|f| {
+                HostFunctionDefinition::new(
+                    f.function_name.clone(),
+                    Some(f.parameter_types.clone()),
+                    f.return_type,
+                )
+            })
+            .collect();
+        HostFunctionDetails {
+            host_functions: Some(functions),
+        }
+    }
 }
 
 impl GuestFunctionRegister<GuestFunc> {
@@ -103,3 +124,11 @@ pub fn register_fn<Output, Args>(
         gfd.register_fn(name, f);
     }
 }
+
+/// Register a handler that runs when the host asks the guest to shut down
+/// gracefully, for example to release resources held through host
+/// functions. The host waits a limited time for the handler to return
+/// before killing the guest.
+pub fn register_shutdown_handler(handler: impl AsGuestFunctionDefinition<(), ()>) {
+    register_fn(SHUTDOWN_FUNCTION_NAME, handler);
+}
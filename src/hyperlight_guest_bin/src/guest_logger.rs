@@ -15,8 +15,12 @@ limitations under the License.
 */
 
 use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
+use hyperlight_common::flatbuffer_wrappers::guest_log_data::GuestLogData;
 use hyperlight_common::flatbuffer_wrappers::guest_log_level::LogLevel;
+use log::kv::{self, Key, Value, VisitSource};
 use log::{LevelFilter, Metadata, Record};
 
 use crate::GUEST_HANDLE;
@@ -42,13 +46,20 @@ impl log::Log for GuestLogger {
     fn log(&self, record: &Record) {
         let handle = unsafe { GUEST_HANDLE };
         if self.enabled(record.metadata()) {
-            handle.log_message(
-                record.level().into(),
-                format!("{}", record.args()).as_str(),
-                record.module_path().unwrap_or("Unknown"),
-                record.target(),
-                record.file().unwrap_or("Unknown"),
-                record.line().unwrap_or(0),
+            let mut fields = FieldCollector(Vec::new());
+            // Collecting into a `Vec` cannot fail
+            let _ = record.key_values().visit(&mut fields);
+            handle.log_record(
+                GuestLogData::new(
+                    format!("{}", record.args()),
+                    record.module_path().unwrap_or("Unknown").to_string(),
+                    record.level().into(),
+                    record.target().to_string(),
+                    record.file().unwrap_or("Unknown").to_string(),
+                    record.line().unwrap_or(0),
+                )
+                .with_target(record.target().to_string())
+                .with_fields(fields.0),
             );
         }
     }
@@ -56,6 +67,17 @@ impl log::Log for GuestLogger {
     fn flush(&self) {}
 }
 
+/// Collects the key/value pairs of a `log` record, such as those of
+/// `log::info!(status = 200; "served")`, as strings
+struct FieldCollector(Vec<(String, String)>);
+
+impl<'kvs> VisitSource<'kvs> for FieldCollector {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
 pub fn log_message(
     level: LogLevel,
     message: &str,
@@ -67,3 +89,33 @@ pub fn log_message(
     let handle = unsafe { GUEST_HANDLE };
     handle.log_message(level, message, module_path, target, file, line);
 }
+
+/// Log a message with a target and structured fields, which the host
+/// turns into the target and fields of a `tracing` event
+pub fn log_structured(
+    level: LogLevel,
+    message: &str,
+    target: &str,
+    fields: &[(&str, &str)],
+    file: &str,
+    line: u32,
+) {
+    let handle = unsafe { GUEST_HANDLE };
+    handle.log_record(
+        GuestLogData::new(
+            message.to_string(),
+            target.to_string(),
+            level,
+            target.to_string(),
+            file.to_string(),
+            line,
+        )
+        .with_target(target.to_string())
+        .with_fields(
+            fields
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        ),
+    );
+}
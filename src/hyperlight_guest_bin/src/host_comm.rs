@@ -24,6 +24,7 @@ use hyperlight_common::flatbuffer_wrappers::function_types::{
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
 use hyperlight_common::flatbuffer_wrappers::util::get_flatbuffer_result;
 use hyperlight_common::func::{ParameterTuple, SupportedReturnType};
+use hyperlight_common::guest_metric::GuestMetric;
 use hyperlight_guest::error::{HyperlightGuestError, Result};
 
 use crate::GUEST_HANDLE;
@@ -71,6 +72,71 @@ pub fn read_n_bytes_from_user_memory(num: u64) -> Result<Vec<u8>> {
     handle.read_n_bytes_from_user_memory(num)
 }
 
+/// Get the startup configuration passed to the sandbox with
+/// `GuestEnvironment::with_startup_config`, or an empty slice if there is
+/// none. It is available from `hyperlight_main` onwards.
+pub fn startup_config() -> &'static [u8] {
+    let handle = unsafe { GUEST_HANDLE };
+    handle.startup_config()
+}
+
+/// Get the command-line style arguments passed to the sandbox with
+/// `GuestEnvironment::with_args`. By convention the first argument is the
+/// program name. They are available from `hyperlight_main` onwards.
+pub fn args() -> impl ExactSizeIterator<Item = &'static str> {
+    let handle = unsafe { GUEST_HANDLE };
+    handle.args()
+}
+
+/// Send `data` to the host as part of the streamed return value of the
+/// current guest function call, which the host receives with
+/// `MultiUseSandbox::call_streaming`.
+pub fn write_stream_chunk(data: &[u8]) -> Result<()> {
+    let handle = unsafe { GUEST_HANDLE };
+    handle.write_stream_chunk(data)
+}
+
+/// Write `data` to the guest's stdout, which the host sends to the sink it
+/// set with `UninitializedSandbox::set_guest_stdout`, or otherwise prints
+/// with its print function.
+pub fn write_stdout(data: &[u8]) -> Result<()> {
+    let handle = unsafe { GUEST_HANDLE };
+    handle.write_output(1, data)
+}
+
+/// Write `data` to the guest's stderr, which the host sends to the sink it
+/// set with `UninitializedSandbox::set_guest_stderr`, or otherwise writes
+/// to its own stderr.
+pub fn write_stderr(data: &[u8]) -> Result<()> {
+    let handle = unsafe { GUEST_HANDLE };
+    handle.write_output(2, data)
+}
+
+/// Read the next bytes of the guest's stdin into `buf`, returning how many
+/// were read, or 0 at the end of the input. The host reads them from the
+/// source it set with `UninitializedSandbox::set_guest_stdin`, and without
+/// one the input is empty.
+pub fn read_stdin(buf: &mut [u8]) -> Result<usize> {
+    let handle = unsafe { GUEST_HANDLE };
+    handle.read_input(buf)
+}
+
+/// Increment the counter `name` by `value`. The host records it with its
+/// `metrics` recorder, prefixed with `guest_custom_` and labelled with the
+/// sandbox's labels.
+pub fn increment_counter(name: &str, value: u64) -> Result<()> {
+    let handle = unsafe { GUEST_HANDLE };
+    handle.emit_metric(&GuestMetric::Counter(name.to_string(), value))
+}
+
+/// Record `value` in the histogram `name`. The host records it with its
+/// `metrics` recorder, prefixed with `guest_custom_` and labelled with the
+/// sandbox's labels.
+pub fn record_histogram(name: &str, value: f64) -> Result<()> {
+    let handle = unsafe { GUEST_HANDLE };
+    handle.emit_metric(&GuestMetric::Histogram(name.to_string(), value))
+}
+
 /// Print a message using the host's print function.
 ///
 /// This function requires memory to be setup to be used. In particular, the
@@ -24,9 +24,10 @@ use arch::dispatch::dispatch_function;
 use buddy_system_allocator::LockedHeap;
 use guest_function::register::GuestFunctionRegister;
 use guest_logger::init_logger;
+use hyperlight_common::call_encoding::CallEncoding;
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
 use hyperlight_common::log_level::GuestLogFilter;
-use hyperlight_common::mem::HyperlightPEB;
+use hyperlight_common::mem::{ABI_VERSION, HyperlightPEB};
 #[cfg(feature = "mem_profile")]
 use hyperlight_common::outb::OutBAction;
 use hyperlight_guest::exit::write_abort;
@@ -47,6 +48,10 @@ pub mod guest_function {
     pub mod register;
 }
 
+/// Hooks for guests instrumented for coverage-guided fuzzing.
+/// cbindgen:ignore
+#[cfg(feature = "coverage")]
+mod coverage;
 pub mod error;
 pub mod guest_logger;
 pub mod host_comm;
@@ -186,9 +191,22 @@ fn _panic_handler(info: &core::panic::PanicInfo) -> ! {
     let mut w = HyperlightAbortWriter;
 
     // begin abort sequence by writing the error code
-    write_abort(&[ErrorCode::UnknownError as u8]);
+    write_abort(&[ErrorCode::GuestPanicked as u8]);
+
+    // The location goes first, on a line of its own, so that the host
+    // still gets it if the message is too long for the abort buffer
+    if let Some(location) = info.location() {
+        let _ = write!(
+            w,
+            "{}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        );
+    }
+    write_abort(b"\n");
 
-    let write_res = write!(w, "{}", info);
+    let write_res = write!(w, "{}", info.message());
     if write_res.is_err() {
         write_abort("panic: message format failed".as_bytes());
     }
@@ -199,6 +217,32 @@ fn _panic_handler(info: &core::panic::PanicInfo) -> ! {
     unreachable!();
 }
 
+/// Abort initialisation if the host implements a different ABI version,
+/// giving the guest's version as the abort message. This runs before the
+/// heap is set up, so it must not allocate.
+fn check_abi_version(host_abi_version: u64) {
+    if host_abi_version == u64::from(ABI_VERSION) {
+        return;
+    }
+    write_abort(&[ErrorCode::AbiMismatch as u8]);
+    let _ = write!(HyperlightAbortWriter, "{}", ABI_VERSION);
+    write_abort(&[0xFF]);
+    unreachable!();
+}
+
+/// Abort initialisation if the host chose a call encoding that this guest
+/// was built without support for, giving the encoding's identifier as the
+/// abort message. Like [`check_abi_version`], this must not allocate.
+fn check_call_encoding(call_encoding: u64) {
+    if CallEncoding::from_id(call_encoding).is_some() {
+        return;
+    }
+    write_abort(&[ErrorCode::UnsupportedCallEncoding as u8]);
+    let _ = write!(HyperlightAbortWriter, "{call_encoding}");
+    write_abort(&[0xFF]);
+    unreachable!();
+}
+
 // === Entrypoint ===
 
 unsafe extern "C" {
@@ -226,11 +270,16 @@ pub(crate) extern "C" fn generic_init(
     ops: u64,
     max_log_level: u64,
 ) -> u64 {
-    unsafe {
+    let peb_ptr = unsafe {
         GUEST_HANDLE = GuestHandle::init(peb_address as *mut HyperlightPEB);
         #[allow(static_mut_refs)]
         let peb_ptr = GUEST_HANDLE.peb().unwrap();
 
+        // Nothing else in the PEB can be trusted if the host uses another
+        // ABI version
+        check_abi_version((*peb_ptr).abi_version);
+        check_call_encoding((*peb_ptr).call_encoding);
+
         let heap_start = (*peb_ptr).guest_heap.ptr as usize;
         let heap_size = (*peb_ptr).guest_heap.size as usize;
         #[cfg(not(all(feature = "mem_profile", target_arch = "x86_64")))]
@@ -281,8 +330,16 @@ pub(crate) extern "C" fn generic_init(
         registration();
     }
 
+    // The host may have selected another exported function to run in
+    // place of `hyperlight_main`
     unsafe {
-        hyperlight_main();
+        match (*peb_ptr).init_entry {
+            0 => hyperlight_main(),
+            addr => {
+                let init_entry: extern "C" fn() = core::mem::transmute(addr as usize);
+                init_entry();
+            }
+        }
     }
 
     // All this tracing logic shall be done right before the call to `hlt` which is done after this
@@ -306,6 +363,7 @@ pub(crate) extern "C" fn generic_init(
 #[cfg(feature = "macros")]
 #[doc(hidden)]
 pub mod __private {
+    pub use alloc::borrow::ToOwned;
     pub use alloc::vec::Vec;
 
     pub use hyperlight_common::flatbuffer_wrappers::function_call::FunctionCall;
@@ -321,9 +379,11 @@ pub mod __private {
         fn from_result(res: Result<Self::Output, HyperlightGuestError>) -> Self;
     }
 
+    use alloc::collections::BTreeMap;
     use alloc::string::String;
 
     use hyperlight_common::for_each_return_type;
+    use hyperlight_common::func::{Structured, StructuredType};
 
     macro_rules! impl_maybe_unwrap {
         ($ty:ty, $enum:ident) => {
@@ -346,6 +406,28 @@ pub mod __private {
     }
 
     for_each_return_type!(impl_maybe_unwrap);
+
+    macro_rules! impl_maybe_unwrap_structured {
+        ($ty:ty) => {
+            impl<T: StructuredType> FromResult for $ty {
+                type Output = Self;
+                fn from_result(res: Result<Self::Output, HyperlightGuestError>) -> Self {
+                    res.unwrap()
+                }
+            }
+
+            impl<T: StructuredType> FromResult for Result<$ty, HyperlightGuestError> {
+                type Output = $ty;
+                fn from_result(res: Result<Self::Output, HyperlightGuestError>) -> Self {
+                    res
+                }
+            }
+        };
+    }
+
+    impl_maybe_unwrap_structured!(Option<T>);
+    impl_maybe_unwrap_structured!(BTreeMap<String, T>);
+    impl_maybe_unwrap_structured!(Structured<T>);
 }
 
 #[cfg(feature = "macros")]
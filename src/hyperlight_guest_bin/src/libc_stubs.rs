@@ -14,13 +14,9 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use alloc::string::String;
-use alloc::vec;
 use core::sync::atomic::{AtomicU64, Ordering};
 
-use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnType};
-
-use crate::host_comm::call_host_function;
+use crate::host_comm::{read_stdin, write_stderr, write_stdout};
 use crate::libc::{
     CLOCK_MONOTONIC, CLOCK_REALTIME, EBADF, EINVAL, EIO, ENOSYS, c_int, c_long, c_void, clockid_t,
     errno, timespec,
@@ -35,7 +31,7 @@ fn set_errno(val: u32) {
 static CURRENT_TIME: AtomicU64 = AtomicU64::new(0);
 
 /// Returns a synthetic monotonically-increasing time starting at Unix epoch
-/// increasing 1s each call.
+/// increasing 1s each call, for guests that have no clock.
 fn current_time() -> (u64, u64) {
     let call_count = CURRENT_TIME.fetch_add(1, Ordering::Relaxed) + 1;
     (call_count, 0)
@@ -53,7 +49,18 @@ extern "C" fn read(fd: c_int, buf: *mut c_void, count: usize) -> isize {
         return -1;
     }
 
-    0
+    if count == 0 {
+        return 0;
+    }
+
+    let slice = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, count) };
+    match read_stdin(slice) {
+        Ok(n) => n as isize,
+        Err(_) => {
+            set_errno(EIO);
+            -1
+        }
+    }
 }
 
 #[unsafe(no_mangle)]
@@ -69,13 +76,12 @@ extern "C" fn write(fd: c_int, buf: *const c_void, count: usize) -> isize {
     }
 
     let slice = unsafe { core::slice::from_raw_parts(buf as *const u8, count) };
-    let s = String::from_utf8_lossy(slice);
-    match call_host_function::<i32>(
-        "HostPrint",
-        Some(vec![ParameterValue::String(s.into_owned())]),
-        ReturnType::Int,
-    ) {
-        Ok(_) => count as isize,
+    let res = match fd {
+        1 => write_stdout(slice),
+        _ => write_stderr(slice),
+    };
+    match res {
+        Ok(()) => count as isize,
         Err(_) => {
             set_errno(EIO);
             -1
@@ -97,7 +103,14 @@ extern "C" fn clock_gettime(clk_id: clockid_t, tp: *mut timespec) -> c_int {
 
     match clk_id {
         CLOCK_ID_REALTIME | CLOCK_ID_MONOTONIC => {
-            let (secs, nanos) = current_time();
+            let clock = match clk_id {
+                CLOCK_ID_REALTIME => hyperlight_guest::clock::wall_clock(),
+                _ => hyperlight_guest::clock::monotonic(),
+            };
+            // Without a clock, fall back to the synthetic time
+            let (secs, nanos) = clock
+                .map(|time| (time.as_secs(), time.subsec_nanos() as u64))
+                .unwrap_or_else(current_time);
             unsafe {
                 (*tp).tv_sec = secs as c_long;
                 (*tp).tv_nsec = nanos as c_long;
@@ -0,0 +1,35 @@
+/*
+Copyright 2025 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use core::ffi::c_char;
+
+use hyperlight_guest_bin::GUEST_HANDLE;
+
+/// Returns the number of arguments the host passed to the sandbox
+#[unsafe(no_mangle)]
+pub extern "C" fn hl_get_argc() -> i32 {
+    let peb_ptr = unsafe { GUEST_HANDLE }.peb().unwrap();
+    unsafe { (*peb_ptr).args.size as i32 }
+}
+
+/// Returns the arguments the host passed to the sandbox, as a
+/// null-terminated array of `hl_get_argc()` strings that lives for the whole
+/// lifetime of the sandbox, or NULL if there are none
+#[unsafe(no_mangle)]
+pub extern "C" fn hl_get_argv() -> *const *const c_char {
+    let peb_ptr = unsafe { GUEST_HANDLE }.peb().unwrap();
+    unsafe { (*peb_ptr).args.ptr as *const *const c_char }
+}
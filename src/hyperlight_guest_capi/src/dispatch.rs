@@ -24,7 +24,7 @@ use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterType, Retu
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
 use hyperlight_guest::error::{HyperlightGuestError, Result};
 use hyperlight_guest_bin::guest_function::definition::GuestFunctionDefinition;
-use hyperlight_guest_bin::guest_function::register::GuestFunctionRegister;
+use hyperlight_guest_bin::guest_function::register::{GuestFunctionRegister, register_function};
 use hyperlight_guest_bin::host_comm::call_host_function_without_returning_result;
 
 use crate::types::{FfiFunctionCall, FfiVec};
@@ -91,6 +91,15 @@ pub extern "C" fn hl_register_function_definition(
 
     let func_params = unsafe { slice::from_raw_parts(params_type, param_no).to_vec() };
 
+    // Also register the function with the Rust guest functions, so that it
+    // is described to the host along with them
+    register_function(GuestFunctionDefinition::new(
+        func_name.clone(),
+        func_params.clone(),
+        return_type,
+        guest_dispatch_function,
+    ));
+
     let func_def = GuestFunctionDefinition::new(func_name, func_params, return_type, func_ptr);
 
     // Use &raw mut to get a mutable raw pointer, then dereference it
@@ -19,6 +19,7 @@ limitations under the License.
 
 extern crate alloc;
 
+pub mod args;
 pub mod dispatch;
 pub mod error;
 pub mod flatbuffer;
@@ -15,10 +15,12 @@ limitations under the License.
 */
 
 use alloc::ffi::CString;
+use alloc::string::ToString;
 use core::ffi::{CStr, c_char};
 
 use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterType, ParameterValue};
-use hyperlight_guest::error::Result;
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_guest::error::{HyperlightGuestError, Result};
 
 use crate::types::FfiVec;
 
@@ -71,6 +73,18 @@ impl FfiParameter {
                     FfiParameterValue { VecBytes: leaked },
                 )
             }
+            ParameterValue::Value(_) => {
+                return Err(HyperlightGuestError::new(
+                    ErrorCode::GuestFunctionParameterTypeMismatch,
+                    "Structured values are not supported by the C API".to_string(),
+                ));
+            }
+            ParameterValue::VecFloat(_) | ParameterValue::VecDouble(_) => {
+                return Err(HyperlightGuestError::new(
+                    ErrorCode::GuestFunctionParameterTypeMismatch,
+                    "Float vectors are not supported by the C API".to_string(),
+                ));
+            }
         };
         Ok(FfiParameter { tag, value: union })
     }
@@ -95,6 +109,12 @@ impl FfiParameter {
             ParameterType::VecBytes => {
                 ParameterValue::VecBytes(unsafe { self.value.VecBytes.copy_to_vec() })
             }
+            ParameterType::Value => {
+                unreachable!("from_parameter_value never creates structured values")
+            }
+            ParameterType::VecFloat | ParameterType::VecDouble => {
+                unreachable!("from_parameter_value never creates float vectors")
+            }
         }
     }
 }
@@ -259,9 +259,12 @@ pub fn dispatch(_attr: TokenStream, item: TokenStream) -> TokenStream {
 /// If a name is provided as an argument, that name will be used to call the host function.
 /// Otherwise, the function's identifier will be used.
 ///
-/// The function arguments must be supported parameter types, and the return type must be
-/// a supported return type or a `Result<T, HyperlightGuestError>` with T being a supported
-/// return type.
+/// The function arguments must be supported parameter types, `&[u8]` or `&str`, and the
+/// return type must be a supported return type or a `Result<T, HyperlightGuestError>` with
+/// T being a supported return type. Borrowed arguments are copied to be sent to the host.
+///
+/// On the host side, the function can be implemented with `#[hyperlight_host_fn]` from
+/// `hyperlight-host`.
 ///
 /// # Panic
 /// If the return type is not a Result, the generated function will panic if the host function
@@ -288,6 +291,13 @@ pub fn dispatch(_attr: TokenStream, item: TokenStream) -> TokenStream {
 /// #[host_function]
 /// fn my_host_function(arg1: i32, arg2: String) -> Result<i32, HyperlightGuestError>;
 /// ```
+///
+/// or with borrowed arguments:
+/// ```ignore
+/// use hyperlight_guest_bin::host_function;
+/// #[host_function]
+/// fn checksum(data: &[u8]) -> u32;
+/// ```
 #[proc_macro_attribute]
 pub fn host_function(attr: TokenStream, item: TokenStream) -> TokenStream {
     // Obtain the crate name for hyperlight-guest-bin
@@ -404,7 +414,13 @@ pub fn host_function(attr: TokenStream, item: TokenStream) -> TokenStream {
                 let ident = pat.ident.clone();
 
                 // All checks passed, add the identifier to the argument list.
-                args.push(quote! { #ident });
+                // Borrowed arguments, such as `&[u8]` or `&str`, are sent as
+                // their owned counterpart.
+                if matches!(*arg.ty, syn::Type::Reference(_)) {
+                    args.push(quote! { #crate_name::__private::ToOwned::to_owned(#ident) });
+                } else {
+                    args.push(quote! { #ident });
+                }
             }
         }
     }
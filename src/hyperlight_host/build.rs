@@ -103,6 +103,11 @@ fn main() -> Result<()> {
         kvm: { all(feature = "kvm", target_os = "linux") },
         mshv3: { all(feature = "mshv3", target_os = "linux") },
         crashdump: { all(feature = "crashdump", target_arch = "x86_64") },
+        seccomp: { all(feature = "seccomp", target_os = "linux") },
+        landlock: { all(feature = "landlock", target_os = "linux") },
+        usdt: { all(feature = "usdt", target_os = "linux", target_arch = "x86_64") },
+        etw: { all(feature = "etw", target_os = "windows") },
+        perfmon: { all(feature = "perfmon", target_os = "windows") },
         // print_debug feature is aliased with debug_assertions to make it only available in debug-builds.
         print_debug: { all(feature = "print_debug", debug_assertions) },
         // the nanvix-unstable and gdb features both (only
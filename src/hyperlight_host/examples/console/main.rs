@@ -0,0 +1,40 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! An interactive console for a guest binary.
+//!
+//! ```text
+//! cargo run --example console --features console -- [path/to/guest]
+//! ```
+//!
+//! The simple guest from `hyperlight-testing` is loaded when no guest is
+//! given. Type `help` at the prompt to list the commands.
+
+use hyperlight_host::sandbox::SandboxConsole;
+use hyperlight_host::{GuestBinary, MultiUseSandbox, UninitializedSandbox};
+
+fn main() -> hyperlight_host::Result<()> {
+    let guest = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => hyperlight_testing::simple_guest_as_string().unwrap(),
+    };
+    let sandbox: MultiUseSandbox =
+        UninitializedSandbox::new(GuestBinary::FilePath(guest.clone()), None)?.evolve()?;
+
+    println!("Loaded {guest}. Type `help` for the commands.");
+    let mut console = SandboxConsole::new(sandbox);
+    console.run(std::io::stdin().lock(), std::io::stdout())
+}
@@ -25,7 +25,7 @@ fn get_sandbox_cfg() -> Option<SandboxConfiguration> {
     #[cfg(gdb)]
     {
         let mut cfg = SandboxConfiguration::default();
-        let debug_info = DebugInfo { port: 8080 };
+        let debug_info = DebugInfo::new(8080);
         cfg.set_guest_debug_info(debug_info);
 
         Some(cfg)
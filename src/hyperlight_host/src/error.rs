@@ -21,7 +21,7 @@ use std::error::Error;
 use std::num::TryFromIntError;
 use std::string::FromUtf8Error;
 use std::sync::{MutexGuard, PoisonError};
-use std::time::SystemTimeError;
+use std::time::{Duration, SystemTimeError};
 
 #[cfg(target_os = "windows")]
 use crossbeam_channel::{RecvError, SendError};
@@ -30,15 +30,29 @@ use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, Ret
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
 use thiserror::Error;
 
+use crate::hypervisor::disassembly::{FaultingInstruction, fault_suffix};
 use crate::hypervisor::hyperlight_vm::HyperlightVmError;
 #[cfg(target_os = "windows")]
 use crate::hypervisor::wrappers::HandleWrapper;
 use crate::mem::memory_region::MemoryRegionFlags;
 use crate::mem::ptr::RawPtr;
+use crate::sandbox::{Capabilities, GuestBacktrace, SandboxLabels};
 
 /// The error type for Hyperlight operations
 #[derive(Error, Debug)]
 pub enum HyperlightError {
+    /// The guest was built for a different version of the ABI between the
+    /// host and the guest than the host implements, so it refused to run.
+    /// See [`hyperlight_common::mem::ABI_VERSION`].
+    #[error("Guest was built for ABI version {guest}, but the host implements ABI version {host}")]
+    AbiMismatch {
+        /// The ABI version of the host
+        host: u32,
+        /// The ABI version the guest was built for, or 0 if it did not
+        /// report a valid version
+        guest: u32,
+    },
+
     /// Anyhow error
     #[error("Anyhow Error was returned: {0}")]
     AnyhowError(#[from] anyhow::Error),
@@ -46,6 +60,11 @@ pub enum HyperlightError {
     #[error("Offset: {0} out of bounds, Max is: {1}")]
     BoundsCheckFailed(u64, usize),
 
+    /// A call into a [`crate::sandbox::SharedSandbox`] was rejected because
+    /// the given number of callers were already queued
+    #[error("The call queue is full ({0} callers waiting)")]
+    CallQueueFull(usize),
+
     /// Checked Add Overflow
     #[error("Couldn't add offset to base address. Offset: {0}, Base Address: {1}")]
     CheckedAddOverflow(u64, u64),
@@ -88,9 +107,27 @@ pub enum HyperlightError {
     #[error("Guest aborted: {0} {1}")]
     GuestAborted(u8, String),
 
+    /// The guest binary could not be verified against its signature, or
+    /// an unverified guest was used when signed guests are required
+    #[error("Guest binary verification failed: {0}")]
+    GuestBinaryVerificationFailed(String),
+
+    /// A guest call did not complete within the timeout given to
+    /// [`crate::MultiUseSandbox::call_with_timeout()`] and was cancelled
+    #[error("Guest call timed out after {0:?}")]
+    GuestCallTimedOut(Duration),
+
     /// Guest call resulted in error in guest
-    #[error("Guest error occurred {0:?}: {1}")]
-    GuestError(ErrorCode, String),
+    #[error("Guest error occurred {code:?}: {message}")]
+    GuestError {
+        /// The kind of error the guest returned
+        code: ErrorCode,
+        /// The message the guest returned with the error
+        message: String,
+        /// The guest's stack where the error was returned, resolved against
+        /// the guest binary's symbols
+        guest_backtrace: GuestBacktrace,
+    },
 
     /// An attempt to cancel guest execution failed because it is hanging on a host function call
     #[error("Guest execution hung on the execution of a host function call")]
@@ -100,10 +137,26 @@ pub enum HyperlightError {
     #[error("Guest call is already in progress")]
     GuestFunctionCallAlreadyInProgress(),
 
+    /// The guest does not implement the functions it was required to have
+    /// with [`crate::UninitializedSandbox::require_guest_function()`]. Each
+    /// entry describes one missing or mismatched function.
+    #[error("Guest does not implement the required interface: {}", .0.join("; "))]
+    GuestInterfaceMismatch(Vec<String>),
+
     /// The given type is not supported by the guest interface.
     #[error("Unsupported type: {0}")]
     GuestInterfaceUnsupportedType(String),
 
+    /// A Rust guest panicked
+    #[error("Guest panicked at {}: {message}", location.as_deref().unwrap_or("an unknown location"))]
+    GuestPanicked {
+        /// The message the guest panicked with
+        message: String,
+        /// Where in the guest's source the panic happened, as
+        /// `file:line:column`, if known
+        location: Option<String>,
+    },
+
     /// The guest binary was built with a different hyperlight-guest-bin version than the host expects.
     /// Hyperlight currently provides no backwards compatibility guarantees for guest binaries,
     /// so the guest and host versions must match exactly. This might change in the future.
@@ -118,10 +171,35 @@ pub enum HyperlightError {
         host_version: String,
     },
 
+    /// A Host function was called by the guest, but it is in a namespace
+    /// that is disabled by the sandbox's configuration.
+    #[error("HostFunction {0} is in the disabled namespace {1}")]
+    HostFunctionNamespaceDisabled(String, String),
+
     /// A Host function was called by the guest but it was not registered.
     #[error("HostFunction {0} was not found")]
     HostFunctionNotFound(String),
 
+    /// A Host function called by the guest panicked. The panic is caught,
+    /// so the sandbox can still be used.
+    #[error("HostFunction {0} panicked: {1}")]
+    HostFunctionPanicked(String, String),
+
+    /// A Host function was called by the guest, but the sandbox was not
+    /// granted all of the capabilities it requires.
+    #[error("HostFunction {0} requires capabilities {1:?} that were not granted")]
+    HostFunctionPermissionDenied(String, Capabilities),
+
+    /// A Host function was called by the guest more often than a rate
+    /// limit set on the function or the sandbox allows.
+    #[error("HostFunction {0} was throttled: {1}")]
+    HostFunctionThrottled(String, String),
+
+    /// A Host function called by the guest did not return within the
+    /// timeout set on it.
+    #[error("HostFunction {0} did not return within {1:?}")]
+    HostFunctionTimedOut(String, Duration),
+
     /// Hyperlight VM error.
     ///
     /// **Note:** This error variant is considered internal and its structure is not stable.
@@ -138,6 +216,10 @@ pub enum HyperlightError {
     #[error("Failed To Convert Size to usize")]
     IntConversionFailure(#[from] TryFromIntError),
 
+    /// A sandbox configuration failed validation
+    #[error("Invalid sandbox configuration: {0}")]
+    InvalidConfiguration(String),
+
     /// The flatbuffer is invalid
     #[error("The flatbuffer is invalid")]
     InvalidFlatBuffer(#[from] InvalidFlatbuffer),
@@ -146,13 +228,32 @@ pub enum HyperlightError {
     #[error("Conversion of str data to json failed")]
     JsonConversionFailure(#[from] serde_json::Error),
 
+    /// A guest call on a sandbox with labels failed with `error`. See
+    /// [`labels()`](Self::labels) and [`unlabelled()`](Self::unlabelled).
+    #[error("{error} (sandbox {labels})")]
+    Labelled {
+        /// The labels of the sandbox
+        labels: SandboxLabels,
+        /// The error the call failed with
+        error: Box<HyperlightError>,
+    },
+
     /// An attempt to get a lock from a Mutex failed.
     #[error("Unable to lock resource")]
     LockAttemptFailed(String),
 
-    /// Memory Access Violation at the given address. The access type and memory region flags are provided.
-    #[error("Memory Access Violation at address {0:#x} of type {1}, but memory is marked as {2}")]
-    MemoryAccessViolation(u64, MemoryRegionFlags, MemoryRegionFlags),
+    /// Memory Access Violation at the given address. The access type and memory region flags are provided,
+    /// along with the guest instruction that made the access when it could be decoded.
+    #[error(
+        "Memory Access Violation at address {0:#x} of type {1}, but memory is marked as {2}{suffix}",
+        suffix = fault_suffix(.3)
+    )]
+    MemoryAccessViolation(
+        u64,
+        MemoryRegionFlags,
+        MemoryRegionFlags,
+        Option<FaultingInstruction>,
+    ),
 
     /// Memory Allocation Failed.
     #[error("Memory Allocation Failed with OS Error {0:?}.")]
@@ -236,14 +337,35 @@ pub enum HyperlightError {
     #[error("RefCell mut borrow failed")]
     RefCellMutBorrowFailed(#[from] BorrowMutError),
 
+    /// A sandbox replaying an
+    /// [`ExecutionRecording`](crate::sandbox::ExecutionRecording) did
+    /// something other than what was recorded
+    #[error("Replay diverged from the recording: {0}")]
+    ReplayDiverged(String),
+
     /// Failed to get value from return value
     #[error("Failed To Convert Return Value {0:?} to {1:?}")]
     ReturnValueConversionFailure(ReturnValue, &'static str),
 
+    /// A non-blocking call into a [`crate::sandbox::SharedSandbox`] was
+    /// rejected because the sandbox was in use
+    #[error("The sandbox is busy")]
+    SandboxBusy,
+
     /// Tried to restore snapshot to a sandbox that is not the same as the one the snapshot was taken from
     #[error("Snapshot was taken from a different sandbox")]
     SnapshotSandboxMismatch,
 
+    /// The surrogate process providing the sandbox's memory to the
+    /// hypervisor exited, so the sandbox can't run. The pool replaces the
+    /// process for the sandboxes created afterwards.
+    #[cfg(target_os = "windows")]
+    #[error("The sandbox's surrogate process exited with code {exit_code:#x}")]
+    SurrogateDied {
+        /// The exit code of the surrogate process
+        exit_code: u32,
+    },
+
     /// SystemTimeError
     #[error("SystemTimeError {0:?}")]
     SystemTimeError(#[from] SystemTimeError),
@@ -264,6 +386,12 @@ pub enum HyperlightError {
     #[error("The return value type is unexpected got {0:?} expected {1:?}")]
     UnexpectedReturnValueType(ReturnValue, String),
 
+    /// The guest was built without support for the call encoding the host
+    /// chose, given by its identifier, so it refused to run. See
+    /// [`SandboxConfiguration::set_call_encoding()`](crate::sandbox::SandboxConfiguration::set_call_encoding).
+    #[error("Guest does not support call encoding {0}")]
+    UnsupportedCallEncoding(u64),
+
     /// Slice conversion to UTF8 failed
     #[error("String Conversion of UTF8 data to str failed")]
     UTF8StringConversionFailure(#[from] FromUtf8Error),
@@ -311,6 +439,49 @@ impl<T> From<PoisonError<MutexGuard<'_, T>>> for HyperlightError {
 }
 
 impl HyperlightError {
+    /// The error for a guest that aborted with `code` and `message`. The
+    /// message of a guest panic is split into the panic's location, on its
+    /// first line, and the message the guest panicked with.
+    pub(crate) fn from_guest_abort(code: u8, message: String) -> Self {
+        if code != ErrorCode::GuestPanicked as u8 {
+            return HyperlightError::GuestAborted(code, message);
+        }
+        let (location, message) = message.split_once('\n').unwrap_or(("", &message));
+        HyperlightError::GuestPanicked {
+            message: message.to_string(),
+            location: (!location.is_empty()).then(|| location.to_string()),
+        }
+    }
+
+    /// The labels of the sandbox a guest call failed on, if it has any
+    pub fn labels(&self) -> Option<&SandboxLabels> {
+        match self {
+            HyperlightError::Labelled { labels, .. } => Some(labels),
+            _ => None,
+        }
+    }
+
+    /// The error without the labels of the sandbox it was returned from
+    pub fn unlabelled(&self) -> &HyperlightError {
+        match self {
+            HyperlightError::Labelled { error, .. } => error,
+            error => error,
+        }
+    }
+
+    /// Attach `labels` to the error, unless there are none or the error
+    /// already has them
+    pub(crate) fn with_labels(self, labels: &SandboxLabels) -> Self {
+        match self {
+            error @ HyperlightError::Labelled { .. } => error,
+            error if labels.is_empty() => error,
+            error => HyperlightError::Labelled {
+                labels: labels.clone(),
+                error: Box::new(error),
+            },
+        }
+    }
+
     /// Internal helper to determines if the given error has potential to poison the sandbox.
     ///
     /// Errors that poison the sandbox are those that can leave the sandbox in an inconsistent
@@ -327,10 +498,12 @@ impl HyperlightError {
             // These errors poison the sandbox because they can leave it in an inconsistent state due
             // to the guest not running to completion.
             HyperlightError::GuestAborted(_, _)
+            | HyperlightError::GuestPanicked { .. }
             | HyperlightError::ExecutionCanceledByHost()
+            | HyperlightError::GuestCallTimedOut(_)
             | HyperlightError::PoisonedSandbox
             | HyperlightError::ExecutionAccessViolation(_)
-            | HyperlightError::MemoryAccessViolation(_, _, _)
+            | HyperlightError::MemoryAccessViolation(_, _, _, _)
             | HyperlightError::MemoryRegionSizeMismatch(_, _, _)
             // HyperlightVmError::Restore is already handled manually in restore(), but we mark it
             // as poisoning here too for defense in depth.
@@ -342,31 +515,43 @@ impl HyperlightError {
             HyperlightError::HyperlightVmError(HyperlightVmError::UpdateRegion(_))
             | HyperlightError::HyperlightVmError(HyperlightVmError::AccessPageTable(_)) => true,
 
+            HyperlightError::Labelled { error, .. } => error.is_poison_error(),
+
             // HyperlightVmError::DispatchGuestCall may poison the sandbox
             HyperlightError::HyperlightVmError(HyperlightVmError::DispatchGuestCall(e)) => {
                 e.is_poison_error()
             }
 
             // All other errors do not poison the sandbox.
-            HyperlightError::AnyhowError(_)
+            HyperlightError::AbiMismatch { .. }
+            | HyperlightError::AnyhowError(_)
             | HyperlightError::BoundsCheckFailed(_, _)
+            | HyperlightError::CallQueueFull(_)
             | HyperlightError::CheckedAddOverflow(_, _)
             | HyperlightError::CStringConversionError(_)
             | HyperlightError::Error(_)
             | HyperlightError::FailedToGetValueFromParameter()
             | HyperlightError::FieldIsMissingInGuestLogData(_)
+            | HyperlightError::GuestBinaryVerificationFailed(_)
             | HyperlightError::GuestBinVersionMismatch { .. }
-            | HyperlightError::GuestError(_, _)
+            | HyperlightError::GuestError { .. }
             | HyperlightError::GuestExecutionHungOnHostFunctionCall()
             | HyperlightError::GuestFunctionCallAlreadyInProgress()
+            | HyperlightError::GuestInterfaceMismatch(_)
             | HyperlightError::GuestInterfaceUnsupportedType(_)
+            | HyperlightError::HostFunctionNamespaceDisabled(_, _)
             | HyperlightError::HostFunctionNotFound(_)
+            | HyperlightError::HostFunctionPanicked(_, _)
+            | HyperlightError::HostFunctionPermissionDenied(_, _)
+            | HyperlightError::HostFunctionThrottled(_, _)
+            | HyperlightError::HostFunctionTimedOut(_, _)
             | HyperlightError::HyperlightVmError(HyperlightVmError::Create(_))
             | HyperlightError::HyperlightVmError(HyperlightVmError::Initialize(_))
             | HyperlightError::HyperlightVmError(HyperlightVmError::MapRegion(_))
             | HyperlightError::HyperlightVmError(HyperlightVmError::UnmapRegion(_))
             | HyperlightError::IOError(_)
             | HyperlightError::IntConversionFailure(_)
+            | HyperlightError::InvalidConfiguration(_)
             | HyperlightError::InvalidFlatBuffer(_)
             | HyperlightError::JsonConversionFailure(_)
             | HyperlightError::LockAttemptFailed(_)
@@ -384,13 +569,16 @@ impl HyperlightError {
             | HyperlightError::RawPointerLessThanBaseAddress(_, _)
             | HyperlightError::RefCellBorrowFailed(_)
             | HyperlightError::RefCellMutBorrowFailed(_)
+            | HyperlightError::ReplayDiverged(_)
             | HyperlightError::ReturnValueConversionFailure(_, _)
+            | HyperlightError::SandboxBusy
             | HyperlightError::SnapshotSandboxMismatch
             | HyperlightError::SystemTimeError(_)
             | HyperlightError::TryFromSliceError(_)
             | HyperlightError::UnexpectedNoOfArguments(_, _)
             | HyperlightError::UnexpectedParameterValueType(_, _)
             | HyperlightError::UnexpectedReturnValueType(_, _)
+            | HyperlightError::UnsupportedCallEncoding(_)
             | HyperlightError::UTF8StringConversionFailure(_)
             | HyperlightError::VectorCapacityIncorrect(_, _, _) => false,
 
@@ -399,6 +587,8 @@ impl HyperlightError {
             #[cfg(target_os = "windows")]
             HyperlightError::CrossBeamSendError(_) => false,
             #[cfg(target_os = "windows")]
+            HyperlightError::SurrogateDied { .. } => true,
+            #[cfg(target_os = "windows")]
             HyperlightError::WindowsAPIError(_) => false,
             #[cfg(target_os = "linux")]
             HyperlightError::VmmSysError(_) => false,
@@ -431,6 +621,35 @@ mod tests {
     };
     use crate::sandbox::outb::HandleOutbError;
 
+    #[test]
+    fn is_poison_error_classifies_errors() {
+        let cases = [
+            (HyperlightError::GuestAborted(1, "abort".to_string()), true),
+            (HyperlightError::ExecutionCanceledByHost(), true),
+            (HyperlightError::PoisonedSandbox, true),
+            (HyperlightError::Error("error".to_string()), false),
+            (
+                HyperlightError::HostFunctionNotFound("f".to_string()),
+                false,
+            ),
+            (
+                HyperlightError::Labelled {
+                    labels: SandboxLabels::default(),
+                    error: Box::new(HyperlightError::PoisonedSandbox),
+                },
+                true,
+            ),
+            // Verification fails before any guest state is touched
+            (
+                HyperlightError::GuestBinaryVerificationFailed("unsigned".to_string()),
+                false,
+            ),
+        ];
+        for (error, poisons) in cases {
+            assert_eq!(error.is_poison_error(), poisons, "{error:?}");
+        }
+    }
+
     /// Test that ExecutionCancelledByHost promotes to HyperlightError::ExecutionCanceledByHost
     #[test]
     fn test_promote_execution_cancelled_by_host() {
@@ -448,6 +667,40 @@ mod tests {
         );
     }
 
+    /// Test that a guest panic promotes to HyperlightError::GuestPanicked with its location
+    /// split from its message
+    #[test]
+    fn test_promote_guest_panicked() {
+        let err = DispatchGuestCallError::Run(RunVmError::HandleIo(HandleIoError::Outb(
+            HandleOutbError::GuestAborted {
+                code: ErrorCode::GuestPanicked as u8,
+                message: "src/main.rs:10:5\nit broke\nbadly".to_string(),
+            },
+        )));
+        let (promoted, should_poison) = err.promote();
+
+        assert!(should_poison, "GuestPanicked should poison the sandbox");
+        match promoted {
+            HyperlightError::GuestPanicked { message, location } => {
+                assert_eq!(message, "it broke\nbadly");
+                assert_eq!(location.as_deref(), Some("src/main.rs:10:5"));
+            }
+            _ => panic!(
+                "Expected HyperlightError::GuestPanicked, got {:?}",
+                promoted
+            ),
+        }
+
+        let promoted = HyperlightError::from_guest_abort(
+            ErrorCode::GuestPanicked as u8,
+            "\nno location".to_string(),
+        );
+        assert!(matches!(
+            promoted,
+            HyperlightError::GuestPanicked { message, location: None } if message == "no location"
+        ));
+    }
+
     /// Test that GuestAborted promotes to HyperlightError::GuestAborted with correct values
     #[test]
     fn test_promote_guest_aborted() {
@@ -476,6 +729,7 @@ mod tests {
             addr: 0xDEADBEEF,
             access_type: MemoryRegionFlags::WRITE,
             region_flags: MemoryRegionFlags::READ,
+            instruction: None,
         });
         let (promoted, should_poison) = err.promote();
 
@@ -484,10 +738,16 @@ mod tests {
             "MemoryAccessViolation should poison the sandbox"
         );
         match promoted {
-            HyperlightError::MemoryAccessViolation(addr, access_type, region_flags) => {
+            HyperlightError::MemoryAccessViolation(
+                addr,
+                access_type,
+                region_flags,
+                instruction,
+            ) => {
                 assert_eq!(addr, 0xDEADBEEF);
                 assert_eq!(access_type, MemoryRegionFlags::WRITE);
                 assert_eq!(region_flags, MemoryRegionFlags::READ);
+                assert_eq!(instruction, None);
             }
             _ => panic!(
                 "Expected HyperlightError::MemoryAccessViolation, got {:?}",
@@ -499,7 +759,7 @@ mod tests {
     /// Test that non-promoted Run errors are wrapped in HyperlightVmError
     #[test]
     fn test_promote_other_run_errors_wrapped() {
-        let err = DispatchGuestCallError::Run(RunVmError::MmioReadUnmapped(0x1000));
+        let err = DispatchGuestCallError::Run(RunVmError::MmioReadUnmapped(0x1000, None));
         let (promoted, should_poison) = err.promote();
 
         assert!(should_poison, "Run errors should poison the sandbox");
@@ -0,0 +1,321 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! An ETW provider, `Hyperlight`, whose TraceLogging events WPA and
+//! PerfView decode without a manifest:
+//!
+//! | Event            | Opcode | Keyword | Fields                        |
+//! |------------------|--------|---------|-------------------------------|
+//! | `SandboxCreate`  | Start  | `0x1`   | `SandboxId`                   |
+//! | `SandboxDestroy` | Stop   | `0x1`   | `SandboxId`                   |
+//! | `GuestCall`      | Start  | `0x2`   | `SandboxId`, `Function`       |
+//! | `GuestCall`      | Stop   | `0x2`   | `SandboxId`, `Failed`         |
+//! | `HostCall`       | Start  | `0x4`   | `Function`                    |
+//! | `HostCall`       | Stop   | `0x4`   | `Function`, `ErrorCode`       |
+//! | `Kill`           | Info   | `0x8`   | `VcpuRunning`                 |
+//!
+//! The provider's GUID is derived from its name, as tools do for
+//! `*Hyperlight`. All events are at the informational level, and an event
+//! is only encoded if a session is listening for it. Without the `etw`
+//! feature, or on other platforms than Windows, events compile to nothing.
+
+/// Sandboxes being created and dropped
+const KEYWORD_SANDBOX: u64 = 0x1;
+/// Calls into the guest
+const KEYWORD_GUEST_CALL: u64 = 0x2;
+/// Calls the guest makes to host functions
+const KEYWORD_HOST_CALL: u64 = 0x4;
+/// Sandboxes being killed
+#[cfg(target_os = "windows")]
+const KEYWORD_KILL: u64 = 0x8;
+
+#[cfg(target_os = "windows")]
+const OPCODE_INFO: u8 = 0;
+const OPCODE_START: u8 = 1;
+const OPCODE_STOP: u8 = 2;
+
+// TraceLogging field types
+const IN_UINT64: u8 = 10;
+const IN_BOOL32: u8 = 13;
+const IN_COUNTED_ANSI_STRING: u8 = 23;
+const OUT_UTF8: u8 = 35;
+/// Set on an input type that is followed by an output type
+const HAS_OUT_TYPE: u8 = 0x80;
+
+/// A field of an event
+enum Field<'a> {
+    U64(&'static str, u64),
+    Bool(&'static str, bool),
+    Str(&'static str, &'a str),
+}
+
+/// Prefix TraceLogging metadata with its size, which includes the prefix
+#[cfg_attr(not(etw), allow(dead_code))]
+fn with_size(metadata: Vec<u8>) -> Vec<u8> {
+    let size = (metadata.len() + 2) as u16;
+    size.to_le_bytes().into_iter().chain(metadata).collect()
+}
+
+/// Encode the event `name` as TraceLogging event metadata, giving the
+/// event's name, without tags, and the name and type of each field, and
+/// the fields' values
+#[cfg_attr(not(etw), allow(dead_code))]
+fn encode(name: &str, fields: &[Field<'_>]) -> (Vec<u8>, Vec<u8>) {
+    let mut metadata = vec![0];
+    metadata.extend(name.bytes().chain([0]));
+    let mut data = Vec::new();
+    for field in fields {
+        match field {
+            Field::U64(name, value) => {
+                metadata.extend(name.bytes().chain([0, IN_UINT64]));
+                data.extend(value.to_le_bytes());
+            }
+            Field::Bool(name, value) => {
+                metadata.extend(name.bytes().chain([0, IN_BOOL32]));
+                data.extend((*value as u32).to_le_bytes());
+            }
+            Field::Str(name, value) => {
+                metadata.extend(name.bytes().chain([
+                    0,
+                    IN_COUNTED_ANSI_STRING | HAS_OUT_TYPE,
+                    OUT_UTF8,
+                ]));
+                let len = value.len().min(u16::MAX as usize);
+                data.extend((len as u16).to_le_bytes());
+                data.extend(&value.as_bytes()[..len]);
+            }
+        }
+    }
+    (with_size(metadata), data)
+}
+
+/// A sandbox was created
+pub(crate) fn sandbox_create(id: u64) {
+    write(
+        "SandboxCreate",
+        KEYWORD_SANDBOX,
+        OPCODE_START,
+        &[Field::U64("SandboxId", id)],
+    );
+}
+
+/// A sandbox was dropped
+pub(crate) fn sandbox_destroy(id: u64) {
+    write(
+        "SandboxDestroy",
+        KEYWORD_SANDBOX,
+        OPCODE_STOP,
+        &[Field::U64("SandboxId", id)],
+    );
+}
+
+/// A call to the guest function `function` is starting
+pub(crate) fn guest_call_start(id: u64, function: &str) {
+    write(
+        "GuestCall",
+        KEYWORD_GUEST_CALL,
+        OPCODE_START,
+        &[
+            Field::U64("SandboxId", id),
+            Field::Str("Function", function),
+        ],
+    );
+}
+
+/// A call to the guest returned
+pub(crate) fn guest_call_stop(id: u64, failed: bool) {
+    write(
+        "GuestCall",
+        KEYWORD_GUEST_CALL,
+        OPCODE_STOP,
+        &[Field::U64("SandboxId", id), Field::Bool("Failed", failed)],
+    );
+}
+
+/// The guest is calling the host function `function`
+pub(crate) fn host_call_start(function: &str) {
+    write(
+        "HostCall",
+        KEYWORD_HOST_CALL,
+        OPCODE_START,
+        &[Field::Str("Function", function)],
+    );
+}
+
+/// The host function `function` returned, with the error code it failed
+/// with, or 0
+pub(crate) fn host_call_stop(function: &str, error_code: u64) {
+    write(
+        "HostCall",
+        KEYWORD_HOST_CALL,
+        OPCODE_STOP,
+        &[
+            Field::Str("Function", function),
+            Field::U64("ErrorCode", error_code),
+        ],
+    );
+}
+
+/// A sandbox was killed, while its vCPU was running or not
+#[cfg(target_os = "windows")]
+pub(crate) fn kill(vcpu_running: bool) {
+    write(
+        "Kill",
+        KEYWORD_KILL,
+        OPCODE_INFO,
+        &[Field::Bool("VcpuRunning", vcpu_running)],
+    );
+}
+
+#[cfg(not(etw))]
+#[inline(always)]
+fn write(_name: &str, _keyword: u64, _opcode: u8, _fields: &[Field<'_>]) {}
+
+#[cfg(etw)]
+use provider::write;
+
+#[cfg(etw)]
+mod provider {
+    use std::sync::OnceLock;
+
+    use windows::Win32::System::Diagnostics::Etw::{
+        EVENT_DATA_DESCRIPTOR, EVENT_DATA_DESCRIPTOR_0, EVENT_DATA_DESCRIPTOR_TYPE_EVENT_METADATA,
+        EVENT_DATA_DESCRIPTOR_TYPE_NONE, EVENT_DATA_DESCRIPTOR_TYPE_PROVIDER_METADATA,
+        EVENT_DESCRIPTOR, EventProviderEnabled, EventProviderSetTraits, EventRegister,
+        EventSetInformation, EventWriteTransfer, REGHANDLE,
+    };
+    use windows::core::GUID;
+
+    use super::{Field, encode, with_size};
+
+    const PROVIDER_NAME: &str = "Hyperlight";
+    /// The GUID derived from the provider's name, the way EventSource and
+    /// TraceLogging tools do
+    const PROVIDER_ID: GUID = GUID::from_u128(0x7e54c20f_9c10_57c0_3090_eae811463b52);
+
+    /// The channel of TraceLogging events
+    const CHANNEL_TRACELOGGING: u8 = 11;
+    const LEVEL_INFORMATIONAL: u8 = 4;
+
+    struct Provider {
+        handle: REGHANDLE,
+        metadata: Vec<u8>,
+    }
+
+    /// The provider, registered by the first event written, or `None` if
+    /// it can't be
+    fn provider() -> Option<&'static Provider> {
+        static PROVIDER: OnceLock<Option<Provider>> = OnceLock::new();
+        PROVIDER
+            .get_or_init(|| {
+                let mut handle = REGHANDLE::default();
+                // SAFETY: the GUID and handle outlive the call, and the
+                // provider is never unregistered, so the handle stays valid
+                let status = unsafe { EventRegister(&PROVIDER_ID, None, None, &mut handle) };
+                if status != 0 {
+                    tracing::warn!("Failed to register the ETW provider: {:#x}", status);
+                    return None;
+                }
+                let metadata = with_size(PROVIDER_NAME.bytes().chain([0]).collect());
+                // Decoders also find the name in each event, so failing to
+                // set it here is not an error
+                // SAFETY: the metadata is valid for its length
+                let _ = unsafe {
+                    EventSetInformation(
+                        handle,
+                        EventProviderSetTraits,
+                        metadata.as_ptr().cast(),
+                        metadata.len() as u32,
+                    )
+                };
+                Some(Provider { handle, metadata })
+            })
+            .as_ref()
+    }
+
+    fn descriptor(data: &[u8], kind: u32) -> EVENT_DATA_DESCRIPTOR {
+        EVENT_DATA_DESCRIPTOR {
+            Ptr: data.as_ptr() as u64,
+            Size: data.len() as u32,
+            Anonymous: EVENT_DATA_DESCRIPTOR_0 { Reserved: kind },
+        }
+    }
+
+    pub(super) fn write(name: &str, keyword: u64, opcode: u8, fields: &[Field<'_>]) {
+        let Some(provider) = provider() else {
+            return;
+        };
+        // SAFETY: the handle is registered
+        if !unsafe { EventProviderEnabled(provider.handle, LEVEL_INFORMATIONAL, keyword) } {
+            return;
+        }
+
+        let (metadata, data) = encode(name, fields);
+
+        let event = EVENT_DESCRIPTOR {
+            Channel: CHANNEL_TRACELOGGING,
+            Level: LEVEL_INFORMATIONAL,
+            Opcode: opcode,
+            Keyword: keyword,
+            ..Default::default()
+        };
+        let user_data = [
+            descriptor(
+                &provider.metadata,
+                EVENT_DATA_DESCRIPTOR_TYPE_PROVIDER_METADATA,
+            ),
+            descriptor(&metadata, EVENT_DATA_DESCRIPTOR_TYPE_EVENT_METADATA),
+            descriptor(&data, EVENT_DATA_DESCRIPTOR_TYPE_NONE),
+        ];
+        // SAFETY: the descriptors point to buffers that outlive the call.
+        // An event that can't be written is dropped, as ETW does when its
+        // buffers are full.
+        let _ =
+            unsafe { EventWriteTransfer(provider.handle, &event, None, None, Some(&user_data)) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Field, encode};
+
+    #[test]
+    fn events_are_encoded_as_tracelogging() {
+        let (metadata, data) = encode(
+            "GuestCall",
+            &[
+                Field::U64("SandboxId", 7),
+                Field::Str("Function", "Echo"),
+                Field::Bool("Failed", true),
+            ],
+        );
+        let expected: Vec<u8> = [&[0][..], b"GuestCall\0"]
+            .concat()
+            .into_iter()
+            .chain(*b"SandboxId\0\x0a")
+            .chain(*b"Function\0\x97\x23")
+            .chain(*b"Failed\0\x0d")
+            .collect();
+        assert_eq!(metadata[..2], ((expected.len() + 2) as u16).to_le_bytes());
+        assert_eq!(metadata[2..], expected);
+
+        let mut expected = 7u64.to_le_bytes().to_vec();
+        expected.extend(4u16.to_le_bytes());
+        expected.extend(b"Echo");
+        expected.extend(1u32.to_le_bytes());
+        assert_eq!(data, expected);
+    }
+}
@@ -14,15 +14,22 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::pin;
 use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
 
 use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnValue};
 use hyperlight_common::for_each_tuple;
 use hyperlight_common::func::{Error as FuncError, Function, ResultType};
 
 use super::{ParameterTuple, SupportedReturnType};
-use crate::sandbox::UninitializedSandbox;
+#[cfg(seccomp)]
+use crate::sandbox::SyscallFilter;
 use crate::sandbox::host_funcs::FunctionEntry;
+use crate::sandbox::{Capabilities, UninitializedSandbox};
 use crate::{HyperlightError, Result, new_error};
 
 /// A sandbox on which (primitive) host functions can be registered
@@ -50,6 +57,11 @@ impl Registerable for UninitializedSandbox {
             function: hf.into().into(),
             parameter_types: Args::TYPE,
             return_type: Output::TYPE,
+            required_capabilities: Capabilities::empty(),
+            rate_limit: None,
+            timeout: None,
+            #[cfg(seccomp)]
+            seccomp_filter: SyscallFilter::default().compile()?,
         };
 
         (*hfs).register_host_function(name.to_string(), entry)
@@ -90,6 +102,11 @@ impl Registerable for crate::MultiUseSandbox {
             function: hf.into().into(),
             parameter_types: Args::TYPE,
             return_type: Output::TYPE,
+            required_capabilities: Capabilities::empty(),
+            rate_limit: None,
+            timeout: None,
+            #[cfg(seccomp)]
+            seccomp_filter: SyscallFilter::default().compile()?,
         };
 
         (*hfs).register_host_function(name.to_string(), entry)
@@ -131,8 +148,187 @@ where
     func: Arc<dyn Function<Output, Args, HyperlightError> + Send + Sync + 'static>,
 }
 
+/// A host function implemented as an `async fn`, or as a closure returning a
+/// future.
+///
+/// When the guest calls an async host function, the guest is parked: its
+/// vCPU stays exited while the future is pending, and the guest resumes
+/// once the future resolves. The future is polled on the thread that is
+/// running the guest call, which sleeps between polls instead of spinning,
+/// so it must be woken by something outside that thread, such as another
+/// thread or an async runtime. Futures that rely on a particular runtime
+/// being active, such as those doing tokio I/O, are run inside that
+/// runtime once it is set as the sandbox's [`AsyncContext`].
+///
+/// Async host functions are registered with
+/// [`UninitializedSandbox::register_async()`]. An `AsyncHostFunction` also
+/// converts into a [`HostFunction`], so it can be registered anywhere a
+/// host function can.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// # async fn fetch_price(item: String) -> hyperlight_host::Result<f64> { Ok(1.0) }
+/// let mut sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// sandbox.register_async("FetchPrice", fetch_price)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AsyncHostFunction<Output, Args>
+where
+    Args: ParameterTuple,
+    Output: SupportedReturnType,
+{
+    func: HostFunction<Output, Args>,
+}
+
+impl<Args, Output> From<AsyncHostFunction<Output, Args>> for HostFunction<Output, Args>
+where
+    Args: ParameterTuple,
+    Output: SupportedReturnType,
+{
+    fn from(func: AsyncHostFunction<Output, Args>) -> Self {
+        func.func
+    }
+}
+
+thread_local! {
+    /// The context async host functions are run in on this thread
+    static CURRENT_CONTEXT: RefCell<Option<AsyncContext>> = const { RefCell::new(None) };
+}
+
+/// The context the futures of async host functions are created and polled
+/// in, such as an async runtime.
+///
+/// A future that relies on a particular runtime being active, such as one
+/// doing tokio I/O or using tokio timers, panics when it is run outside of
+/// that runtime. Once a context is set with
+/// [`UninitializedSandbox::set_async_context()`], every
+/// [`AsyncHostFunction`] the guest calls, and every read from an async
+/// [`InputSource`](crate::sandbox::InputSource), runs inside it.
+///
+/// The thread running the guest call only parks while the future is
+/// pending, so the runtime must drive its I/O and timers on threads of its
+/// own, as a multi-threaded tokio runtime does.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::func::AsyncContext;
+/// # fn example(runtime: &tokio::runtime::Runtime) -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// let handle = runtime.handle().clone();
+/// sandbox.set_async_context(AsyncContext::new(move |run| {
+///     let _guard = handle.enter();
+///     run()
+/// }))?;
+/// sandbox.register_async("Fetch", |addr: String| async move {
+///     let stream = tokio::net::TcpStream::connect(addr).await?;
+///     Ok(stream.peer_addr()?.to_string())
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AsyncContext {
+    enter: Arc<EnterFn>,
+}
+
+type EnterFn = dyn Fn(&mut dyn FnMut()) + Send + Sync;
+
+impl AsyncContext {
+    /// A context that is set up by `enter`, which is called with a function
+    /// that creates the future of an async host function and waits for it,
+    /// and must call that function once the context is set up
+    pub fn new(enter: impl Fn(&mut dyn FnMut()) + Send + Sync + 'static) -> Self {
+        Self {
+            enter: Arc::new(enter),
+        }
+    }
+
+    /// Run `f` with `context` as the context async host functions are run
+    /// in on the calling thread
+    pub(crate) fn scope<T>(context: Option<&Self>, f: impl FnOnce() -> T) -> T {
+        struct Restore(Option<AsyncContext>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let previous = self.0.take();
+                CURRENT_CONTEXT.with(|current| *current.borrow_mut() = previous);
+            }
+        }
+        let _restore = Restore(CURRENT_CONTEXT.with(|current| current.replace(context.cloned())));
+        f()
+    }
+}
+
+impl std::fmt::Debug for AsyncContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncContext").finish_non_exhaustive()
+    }
+}
+
+/// Create a future with `make` and run it to completion on the current
+/// thread, like [`block_on`], inside the thread's [`AsyncContext`] if it
+/// has one
+pub(crate) fn run_async<F: Future>(make: impl FnOnce() -> F) -> F::Output {
+    let Some(context) = CURRENT_CONTEXT.with(|current| current.borrow().clone()) else {
+        return block_on(make());
+    };
+    let mut make = Some(make);
+    let mut output = None;
+    (context.enter)(&mut || {
+        if let Some(make) = make.take() {
+            output = Some(block_on(make()));
+        }
+    });
+    match (output, make) {
+        (Some(output), _) => output,
+        // The context did not run the future, so it is run outside of it
+        (None, Some(make)) => block_on(make()),
+        (None, None) => unreachable!("the future is only taken to be run"),
+    }
+}
+
+/// Wakes a thread parked in [`block_on`]
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Run `future` to completion on the current thread, parking the thread
+/// whenever the future is pending
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        thread::park();
+    }
+}
+
+#[derive(Clone)]
 pub(crate) struct TypeErasedHostFunction {
-    func: Box<dyn Fn(Vec<ParameterValue>) -> Result<ReturnValue> + Send + Sync + 'static>,
+    func: Arc<dyn Fn(Vec<ParameterValue>) -> Result<ReturnValue> + Send + Sync + 'static>,
 }
 
 impl<Args, Output> HostFunction<Output, Args>
@@ -150,6 +346,25 @@ impl TypeErasedHostFunction {
     pub(crate) fn call(&self, args: Vec<ParameterValue>) -> Result<ReturnValue> {
         (self.func)(args)
     }
+
+    /// A function that handles calls to any function in a namespace. The
+    /// registry passes the name the function was called by as its first
+    /// argument, ahead of the guest's arguments.
+    pub(crate) fn wildcard(
+        func: impl Fn(&str, Vec<ParameterValue>) -> Result<ReturnValue> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            func: Arc::new(move |args: Vec<ParameterValue>| {
+                let mut args = args.into_iter();
+                match args.next() {
+                    Some(ParameterValue::String(name)) => func(&name, args.collect()),
+                    _ => Err(new_error!(
+                        "wildcard host function called without the name it was called by"
+                    )),
+                }
+            }),
+        }
+    }
 }
 
 impl From<FuncError> for HyperlightError {
@@ -181,7 +396,7 @@ where
 {
     fn from(func: HostFunction<Output, Args>) -> TypeErasedHostFunction {
         TypeErasedHostFunction {
-            func: Box::new(move |args: Vec<ParameterValue>| {
+            func: Arc::new(move |args: Vec<ParameterValue>| {
                 let args = Args::from_value(args)?;
                 Ok(func.call(args)?.into_value())
             }),
@@ -207,7 +422,9 @@ macro_rules! impl_host_function {
             fn from(func: F) -> HostFunction<R::ReturnType, ($($P,)*)> {
                 let func = Mutex::new(func);
                 let func = move |$($p: $P,)*| {
-                    let mut func = func.lock().map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+                    // A panic in an earlier call has already been reported
+                    // as an error, so the function can still be called
+                    let mut func = func.lock().unwrap_or_else(|e| e.into_inner());
                     (func)($($p),*).into_result()
                 };
                 let func = Arc::new(func);
@@ -219,10 +436,41 @@ macro_rules! impl_host_function {
 
 for_each_tuple!(impl_host_function);
 
+macro_rules! impl_async_host_function {
+    ([$N:expr] ($($p:ident: $P:ident),*)) => {
+        impl<F, Fut, $($P),*> From<F> for AsyncHostFunction<<Fut::Output as ResultType<HyperlightError>>::ReturnType, ($($P,)*)>
+        where
+            F: FnMut($($P),*) -> Fut + Send + 'static,
+            Fut: Future,
+            Fut::Output: ResultType<HyperlightError>,
+            ($($P,)*): ParameterTuple,
+        {
+            fn from(func: F) -> Self {
+                let func = Mutex::new(func);
+                let func = move |$($p: $P,)*| {
+                    // Only hold the lock while creating the future, not
+                    // while waiting for it
+                    run_async(|| {
+                        let mut func = func.lock().unwrap_or_else(|e| e.into_inner());
+                        (func)($($p),*)
+                    })
+                    .into_result()
+                };
+                let func = Arc::new(func);
+                AsyncHostFunction { func: HostFunction { func } }
+            }
+        }
+    };
+}
+
+for_each_tuple!(impl_async_host_function);
+
 pub(crate) fn register_host_function<Args: ParameterTuple, Output: SupportedReturnType>(
     func: impl Into<HostFunction<Output, Args>>,
     sandbox: &mut UninitializedSandbox,
     name: &str,
+    required_capabilities: Capabilities,
+    #[cfg(seccomp)] syscall_filter: &SyscallFilter,
 ) -> Result<()> {
     let func = func.into().into();
 
@@ -230,6 +478,11 @@ pub(crate) fn register_host_function<Args: ParameterTuple, Output: SupportedRetu
         function: func,
         parameter_types: Args::TYPE,
         return_type: Output::TYPE,
+        required_capabilities,
+        rate_limit: None,
+        timeout: None,
+        #[cfg(seccomp)]
+        seccomp_filter: syscall_filter.compile()?,
     };
 
     sandbox
@@ -240,3 +493,88 @@ pub(crate) fn register_host_function<Args: ParameterTuple, Output: SupportedRetu
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnValue};
+
+    use super::{AsyncHostFunction, HostFunction, Registerable, TypeErasedHostFunction};
+    use crate::func::{ParameterTuple, SupportedReturnType};
+    use crate::{Result, hyperlight_host_fn, new_error};
+
+    #[derive(Default)]
+    struct Registry(HashMap<String, TypeErasedHostFunction>);
+
+    impl Registerable for Registry {
+        fn register_host_function<Args: ParameterTuple, Output: SupportedReturnType>(
+            &mut self,
+            name: &str,
+            hf: impl Into<HostFunction<Output, Args>>,
+        ) -> Result<()> {
+            self.0.insert(name.to_string(), hf.into().into());
+            Ok(())
+        }
+    }
+
+    #[hyperlight_host_fn]
+    fn checksum(data: &[u8], seed: u32) -> u32 {
+        data.iter().fold(seed, |sum, b| sum + *b as u32)
+    }
+
+    #[hyperlight_host_fn("Greet")]
+    fn greet(name: &str) -> Result<String> {
+        if name.is_empty() {
+            return Err(new_error!("no name given"));
+        }
+        Ok(format!("Hello, {name}!"))
+    }
+
+    #[test]
+    fn host_fn_macro_registers_function() {
+        let mut registry = Registry::default();
+        register_checksum(&mut registry).unwrap();
+        register_greet(&mut registry).unwrap();
+
+        let res = registry.0["checksum"]
+            .call(vec![
+                ParameterValue::VecBytes(vec![1, 2, 3]),
+                ParameterValue::UInt(10),
+            ])
+            .unwrap();
+        assert_eq!(res, ReturnValue::UInt(16));
+
+        let res = registry.0["Greet"]
+            .call(vec![ParameterValue::String("world".to_string())])
+            .unwrap();
+        assert_eq!(res, ReturnValue::String("Hello, world!".to_string()));
+        assert!(
+            registry.0["Greet"]
+                .call(vec![ParameterValue::String(String::new())])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn async_host_function_waits_for_future() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let handle = rt.handle().clone();
+        let func: HostFunction<i32, (i32,)> = AsyncHostFunction::from(move |x: i32| {
+            let task = handle.spawn(async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                x + 1
+            });
+            async move { task.await.map_err(|e| new_error!("task failed: {}", e)) }
+        })
+        .into();
+        assert_eq!(func.call((41,)).unwrap(), 42);
+
+        async fn fails(msg: String) -> crate::Result<()> {
+            Err(new_error!("{}", msg))
+        }
+        let func: HostFunction<(), (String,)> = AsyncHostFunction::from(fails).into();
+        assert!(func.call(("oops".to_string(),)).is_err());
+    }
+}
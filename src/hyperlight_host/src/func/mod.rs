@@ -28,7 +28,7 @@ limitations under the License.
 pub(crate) mod host_functions;
 
 /// Re-export for `HostFunction` trait
-pub use host_functions::{HostFunction, Registerable};
+pub use host_functions::{AsyncContext, AsyncHostFunction, HostFunction, Registerable};
 /// Re-export for `ParameterType` enum
 pub use hyperlight_common::flatbuffer_wrappers::function_types::ParameterType;
 /// Re-export for `ParameterValue` enum
@@ -41,6 +41,9 @@ pub use hyperlight_common::flatbuffer_wrappers::function_types::ReturnValue;
 pub use hyperlight_common::flatbuffer_wrappers::host_function_definition::HostFunctionDefinition;
 /// Re-export for `HostFunctionDetails`
 pub use hyperlight_common::flatbuffer_wrappers::host_function_details::HostFunctionDetails;
+/// Re-export for structured values: structs, optionals and maps
+pub use hyperlight_common::flatbuffer_wrappers::value::{Structured, StructuredType, Value};
 pub use hyperlight_common::func::{
-    ParameterTuple, ResultType, SupportedParameterType, SupportedReturnType,
+    IntoParameterRef, ParameterRefTuple, ParameterTuple, ResultType, SupportedParameterType,
+    SupportedReturnType,
 };
@@ -0,0 +1,129 @@
+/*
+Copyright 2025 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::path::PathBuf;
+use std::sync::mpsc::{Sender, channel};
+use std::sync::{Arc, Mutex};
+
+use crate::hypervisor::InterruptHandleImpl;
+use crate::{Result, new_error};
+
+/// A core dump requested through a [`CoreDumpHandle`], waiting for the
+/// vcpu thread to take it
+#[derive(Debug)]
+pub(crate) struct LiveDumpRequest {
+    /// The file the dump is written to
+    pub(crate) path: PathBuf,
+    /// Where the outcome of the dump is sent
+    pub(crate) reply: Sender<Result<()>>,
+}
+
+/// The request a sandbox's [`CoreDumpHandle`]s and its vcpu thread share
+pub(crate) type LiveDumpSlot = Arc<Mutex<Option<LiveDumpRequest>>>;
+
+/// A handle for taking core dumps of a sandbox while it runs a guest
+/// function, such as one stuck in an infinite loop, without killing it.
+///
+/// Taking a dump pauses the guest's vCPU, writes its registers and memory
+/// in the same format as crash dumps, and lets the guest carry on. The
+/// sandbox's [`CrashDumpPolicy`](crate::sandbox::CrashDumpPolicy) applies
+/// to the dump.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+/// # use std::thread;
+/// # use std::time::Duration;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?.evolve()?;
+///
+/// let dumper = sandbox.core_dump_handle();
+/// thread::spawn(move || {
+///     thread::sleep(Duration::from_secs(5));
+///     dumper.dump_core("/tmp/stuck.elf").unwrap();
+/// });
+///
+/// let result = sandbox.call_guest_function_by_name::<i32>("LongRunningFunction", ());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CoreDumpHandle {
+    interrupt_handle: Arc<dyn InterruptHandleImpl>,
+    pending: LiveDumpSlot,
+}
+
+impl CoreDumpHandle {
+    pub(crate) fn new(
+        interrupt_handle: Arc<dyn InterruptHandleImpl>,
+        pending: LiveDumpSlot,
+    ) -> Self {
+        Self {
+            interrupt_handle,
+            pending,
+        }
+    }
+
+    /// Write a core dump of the running guest to `path`, blocking until
+    /// it has been written.
+    ///
+    /// Returns an error if the guest is not running on its vCPU, such as
+    /// between guest calls or while it waits for a host function, in which
+    /// case [`MultiUseSandbox::dump_core()`](crate::MultiUseSandbox::dump_core)
+    /// can be used instead.
+    pub fn dump_core(&self, path: impl Into<PathBuf>) -> Result<()> {
+        let (reply, done) = channel();
+        {
+            let mut pending = self
+                .pending
+                .lock()
+                .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+            if pending.is_some() {
+                return Err(new_error!(
+                    "A core dump of the sandbox is already being taken"
+                ));
+            }
+            *pending = Some(LiveDumpRequest {
+                path: path.into(),
+                reply,
+            });
+        }
+
+        if !self.interrupt_handle.request_core_dump() {
+            // The vcpu is not running, so withdraw the request unless the
+            // vcpu thread has already taken it
+            let withdrawn = self
+                .pending
+                .lock()
+                .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+                .take()
+                .is_some();
+            if withdrawn {
+                self.interrupt_handle.clear_core_dump_request();
+                return Err(new_error!(
+                    "Cannot take a live core dump because the guest is not running"
+                ));
+            }
+        }
+
+        done.recv()
+            .map_err(|_| new_error!("The sandbox was dropped before the core dump was taken"))?
+    }
+}
@@ -0,0 +1,487 @@
+/*
+Copyright 2025 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Writes a [`CrashDumpContext`] as a Windows minidump (`MDMP`), the format
+//! WinDbg and Visual Studio open.
+//!
+//! The dump holds four streams: the system info, a single thread with its
+//! AMD64 `CONTEXT`, a module list naming the guest binary, and a
+//! `Memory64List` describing every guest memory region. The memory itself
+//! follows the streams, at the end of the file, so the regions are copied
+//! straight from guest memory into the writer without being buffered.
+
+use std::io::Write;
+
+use super::CrashDumpContext;
+use crate::mem::memory_region::{CrashDumpRegion, MemoryRegionType};
+use crate::{Result, new_error};
+
+/// `"MDMP"` read as a little-endian `u32`
+const MINIDUMP_SIGNATURE: u32 = 0x504d_444d;
+/// The version of the minidump format written by `dbghelp.dll`
+const MINIDUMP_VERSION: u32 = 0xa793;
+/// `MiniDumpWithFullMemory`: the dump contains all of the guest's memory
+const MINIDUMP_WITH_FULL_MEMORY: u64 = 0x2;
+
+/// `ThreadListStream`
+const THREAD_LIST_STREAM: u32 = 3;
+/// `ModuleListStream`
+const MODULE_LIST_STREAM: u32 = 4;
+/// `SystemInfoStream`
+const SYSTEM_INFO_STREAM: u32 = 7;
+/// `Memory64ListStream`
+const MEMORY64_LIST_STREAM: u32 = 9;
+/// The number of streams in the directory
+const STREAM_COUNT: usize = 4;
+
+/// `PROCESSOR_ARCHITECTURE_AMD64`
+const PROCESSOR_ARCHITECTURE_AMD64: u16 = 9;
+/// `VER_PLATFORM_WIN32_NT`
+const VER_PLATFORM_WIN32_NT: u32 = 2;
+
+/// `CONTEXT_AMD64` with `CONTEXT_CONTROL`, `CONTEXT_INTEGER`,
+/// `CONTEXT_SEGMENTS` and `CONTEXT_FLOATING_POINT` set
+const CONTEXT_FLAGS: u32 = 0x0010_000f;
+/// The size of an AMD64 `CONTEXT`
+const CONTEXT_SIZE: usize = 0x4d0;
+/// The size of the legacy `FXSAVE` area at the start of the XSAVE state,
+/// which is what `CONTEXT::FltSave` holds
+const FXSAVE_SIZE: usize = 512;
+/// The offset of `MXCSR` within the `FXSAVE` area
+const FXSAVE_MXCSR_OFFSET: usize = 24;
+
+/// The size of `MINIDUMP_HEADER`
+const HEADER_SIZE: usize = 32;
+/// The size of `MINIDUMP_DIRECTORY`
+const DIRECTORY_SIZE: usize = 12;
+/// The size of `MINIDUMP_MEMORY_DESCRIPTOR64`
+const MEMORY_DESCRIPTOR64_SIZE: usize = 16;
+/// The size of `VS_FIXEDFILEINFO`
+const FIXED_FILE_INFO_SIZE: usize = 52;
+
+/// Where each register of [`CrashDumpContext::regs`] goes in an AMD64
+/// `CONTEXT`, with its width in bytes. The registers are in the order of
+/// the Linux `user_regs_struct` used for the ELF core dump.
+const CONTEXT_REGISTERS: [(usize, usize); 27] = [
+    (0xf0, 8), // r15
+    (0xe8, 8), // r14
+    (0xe0, 8), // r13
+    (0xd8, 8), // r12
+    (0xa0, 8), // rbp
+    (0x90, 8), // rbx
+    (0xd0, 8), // r11
+    (0xc8, 8), // r10
+    (0xc0, 8), // r9
+    (0xb8, 8), // r8
+    (0x78, 8), // rax
+    (0x80, 8), // rcx
+    (0x88, 8), // rdx
+    (0xa8, 8), // rsi
+    (0xb0, 8), // rdi
+    (0, 0),    // orig_rax has no place in a CONTEXT
+    (0xf8, 8), // rip
+    (0x38, 2), // cs
+    (0x44, 4), // eflags
+    (0x98, 8), // rsp
+    (0x42, 2), // ss
+    (0, 0),    // fs_base is not part of a CONTEXT
+    (0, 0),    // gs_base is not part of a CONTEXT
+    (0x3a, 2), // ds
+    (0x3c, 2), // es
+    (0x3e, 2), // fs
+    (0x40, 2), // gs
+];
+/// The index of `rsp` in [`CrashDumpContext::regs`]
+const RSP: usize = 19;
+
+/// The bytes of a minidump before the memory of the guest, built up stream
+/// by stream
+struct Streams {
+    buf: Vec<u8>,
+    directory: Vec<(u32, u32, u32)>,
+}
+
+impl Streams {
+    fn new() -> Self {
+        Self {
+            buf: vec![0; HEADER_SIZE + STREAM_COUNT * DIRECTORY_SIZE],
+            directory: Vec::with_capacity(STREAM_COUNT),
+        }
+    }
+
+    /// The RVA of the next byte written
+    fn rva(&self) -> Result<u32> {
+        u32::try_from(self.buf.len()).map_err(|_| new_error!("Minidump streams exceed 4GiB"))
+    }
+
+    fn align(&mut self, alignment: usize) {
+        self.buf
+            .resize(self.buf.len().next_multiple_of(alignment), 0);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    /// Write a `MINIDUMP_STRING` and return its RVA
+    fn string(&mut self, s: &str) -> Result<u32> {
+        self.align(4);
+        let rva = self.rva()?;
+        let utf16: Vec<u16> = s.encode_utf16().collect();
+        self.u32((utf16.len() * 2) as u32);
+        for c in utf16 {
+            self.u16(c);
+        }
+        // The length excludes the terminating null, which is still written
+        self.u16(0);
+        Ok(rva)
+    }
+
+    /// Write a stream with `write` and add it to the directory
+    fn stream(
+        &mut self,
+        stream_type: u32,
+        write: impl FnOnce(&mut Self) -> Result<()>,
+    ) -> Result<()> {
+        self.align(8);
+        let rva = self.rva()?;
+        write(self)?;
+        let size = self.rva()? - rva;
+        self.directory.push((stream_type, size, rva));
+        Ok(())
+    }
+
+    /// Fill in the header and the stream directory
+    fn finish(mut self, timestamp: u32) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_SIZE + STREAM_COUNT * DIRECTORY_SIZE);
+        header.extend_from_slice(&MINIDUMP_SIGNATURE.to_le_bytes());
+        header.extend_from_slice(&MINIDUMP_VERSION.to_le_bytes());
+        header.extend_from_slice(&(self.directory.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes()); // CheckSum
+        header.extend_from_slice(&timestamp.to_le_bytes());
+        header.extend_from_slice(&MINIDUMP_WITH_FULL_MEMORY.to_le_bytes());
+        for (stream_type, size, rva) in &self.directory {
+            header.extend_from_slice(&stream_type.to_le_bytes());
+            header.extend_from_slice(&size.to_le_bytes());
+            header.extend_from_slice(&rva.to_le_bytes());
+        }
+        self.buf[..header.len()].copy_from_slice(&header);
+        self.buf
+    }
+}
+
+/// The guest memory of a region that can be copied into the dump
+fn region_data(region: &CrashDumpRegion) -> &[u8] {
+    let len = region.guest_region.len().min(region.host_region.len());
+    // SAFETY: the host region is mapped for as long as the sandbox that
+    // produced the crash dump context is alive, which it is while the
+    // dump is written
+    unsafe { std::slice::from_raw_parts(region.host_region.start as *const u8, len) }
+}
+
+/// Build an AMD64 `CONTEXT` from the registers of the crash dump context
+fn thread_context(ctx: &CrashDumpContext) -> Vec<u8> {
+    let mut context = vec![0u8; CONTEXT_SIZE];
+    context[0x30..0x34].copy_from_slice(&CONTEXT_FLAGS.to_le_bytes());
+    for (&value, &(offset, width)) in ctx.regs.iter().zip(CONTEXT_REGISTERS.iter()) {
+        context[offset..offset + width].copy_from_slice(&value.to_le_bytes()[..width]);
+    }
+    // The FXSAVE area is the first part of the XSAVE state
+    if ctx.xsave.len() >= FXSAVE_SIZE {
+        context[0x100..0x100 + FXSAVE_SIZE].copy_from_slice(&ctx.xsave[..FXSAVE_SIZE]);
+        context[0x34..0x38]
+            .copy_from_slice(&ctx.xsave[FXSAVE_MXCSR_OFFSET..FXSAVE_MXCSR_OFFSET + 4]);
+    }
+    context
+}
+
+/// Write the crash dump context to `writer` as a minidump.
+///
+/// Returns the number of bytes written.
+pub(super) fn write_minidump(ctx: &CrashDumpContext, mut writer: impl Write) -> Result<usize> {
+    let regions: Vec<&CrashDumpRegion> = ctx
+        .regions
+        .iter()
+        .filter(|r| !region_data(r).is_empty())
+        .collect();
+    if regions.is_empty() {
+        return Err(new_error!("No memory regions to write to the minidump"));
+    }
+
+    let mut streams = Streams::new();
+
+    streams.stream(SYSTEM_INFO_STREAM, |s| {
+        s.u16(PROCESSOR_ARCHITECTURE_AMD64);
+        s.u16(0); // ProcessorLevel
+        s.u16(0); // ProcessorRevision
+        s.buf.push(1); // NumberOfProcessors
+        s.buf.push(0); // ProductType
+        s.u32(0); // MajorVersion
+        s.u32(0); // MinorVersion
+        s.u32(0); // BuildNumber
+        s.u32(VER_PLATFORM_WIN32_NT);
+        let csd_version = s.buf.len();
+        s.u32(0); // CSDVersionRva, filled in below
+        s.u16(0); // SuiteMask
+        s.u16(0); // Reserved2
+        s.buf.extend_from_slice(&[0; 24]); // CPU_INFORMATION
+        let rva = s.string("")?;
+        s.buf[csd_version..csd_version + 4].copy_from_slice(&rva.to_le_bytes());
+        Ok(())
+    })?;
+
+    // The memory follows the streams, so where each region ends up is only
+    // known once the size of the streams is. The thread's stack is given
+    // as the part of the region holding `rsp` above it, which is patched
+    // into the thread once the memory list is written.
+    let rsp = ctx.regs[RSP] as usize;
+    let mut stack_descriptor = 0;
+    let mut context_rva_offset = 0;
+
+    streams.stream(THREAD_LIST_STREAM, |s| {
+        s.u32(1); // NumberOfThreads
+        s.u32(1); // ThreadId
+        s.u32(0); // SuspendCount
+        s.u32(0); // PriorityClass
+        s.u32(0); // Priority
+        s.u64(0); // Teb
+        stack_descriptor = s.buf.len();
+        s.u64(rsp as u64); // Stack.StartOfMemoryRange
+        s.u32(0); // Stack.Memory.DataSize, filled in below
+        s.u32(0); // Stack.Memory.Rva, filled in below
+        s.u32(CONTEXT_SIZE as u32); // ThreadContext.DataSize
+        context_rva_offset = s.buf.len();
+        s.u32(0); // ThreadContext.Rva, filled in below
+        Ok(())
+    })?;
+
+    streams.align(16);
+    let context_rva = streams.rva()?;
+    streams.buf.extend_from_slice(&thread_context(ctx));
+    streams.buf[context_rva_offset..context_rva_offset + 4]
+        .copy_from_slice(&context_rva.to_le_bytes());
+
+    // The guest binary is the region holding its entry point, and its name
+    // is the path it was loaded from
+    let entry = ctx.entry as usize;
+    let module = ctx
+        .regions
+        .iter()
+        .find(|r| r.guest_region.contains(&entry))
+        .or_else(|| {
+            ctx.regions
+                .iter()
+                .find(|r| r.region_type == MemoryRegionType::Code && !r.guest_region.is_empty())
+        });
+    let module_name = ctx
+        .binary
+        .as_deref()
+        .or(ctx.filename.as_deref())
+        .unwrap_or("<unknown>");
+    let module_name_rva = match module {
+        Some(_) => Some(streams.string(module_name)?),
+        None => None,
+    };
+
+    streams.stream(MODULE_LIST_STREAM, |s| {
+        match (module, module_name_rva) {
+            (Some(module), Some(name_rva)) => {
+                s.u32(1); // NumberOfModules
+                s.u64(module.guest_region.start as u64); // BaseOfImage
+                s.u32(u32::try_from(module.guest_region.len()).unwrap_or(u32::MAX)); // SizeOfImage
+                s.u32(0); // CheckSum
+                s.u32(0); // TimeDateStamp
+                s.u32(name_rva); // ModuleNameRva
+                s.buf.extend_from_slice(&[0; FIXED_FILE_INFO_SIZE]); // VersionInfo
+                s.u64(0); // CvRecord
+                s.u64(0); // MiscRecord
+                s.u64(0); // Reserved0
+                s.u64(0); // Reserved1
+            }
+            _ => s.u32(0),
+        }
+        Ok(())
+    })?;
+
+    streams.stream(MEMORY64_LIST_STREAM, |s| {
+        let base_rva = s.buf.len() + 2 * 8 + regions.len() * MEMORY_DESCRIPTOR64_SIZE;
+        s.u64(regions.len() as u64); // NumberOfMemoryRanges
+        s.u64(base_rva as u64); // BaseRva
+
+        let mut rva = base_rva;
+        for region in &regions {
+            let start = region.guest_region.start;
+            let len = region_data(region).len();
+            s.u64(start as u64);
+            s.u64(len as u64);
+
+            if (start..start + len).contains(&rsp) {
+                let stack_rva = rva + (rsp - start);
+                if let (Ok(size), Ok(stack_rva)) =
+                    (u32::try_from(start + len - rsp), u32::try_from(stack_rva))
+                {
+                    s.buf[stack_descriptor + 8..stack_descriptor + 12]
+                        .copy_from_slice(&size.to_le_bytes());
+                    s.buf[stack_descriptor + 12..stack_descriptor + 16]
+                        .copy_from_slice(&stack_rva.to_le_bytes());
+                }
+            }
+            rva += len;
+        }
+        Ok(())
+    })?;
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as u32);
+    let streams = streams.finish(timestamp);
+
+    let write_err = |e| new_error!("Failed to write minidump: {:?}", e);
+    writer.write_all(&streams).map_err(write_err)?;
+    let mut nbytes = streams.len();
+    for region in &regions {
+        let data = region_data(region);
+        writer.write_all(data).map_err(write_err)?;
+        nbytes += data.len();
+    }
+    writer.flush().map_err(write_err)?;
+
+    Ok(nbytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem::memory_region::MemoryRegionFlags;
+
+    fn read_u32(buf: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn read_u64(buf: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+    }
+
+    /// Find a stream in the directory, returning its size and RVA
+    fn find_stream(dump: &[u8], stream_type: u32) -> (usize, usize) {
+        let count = read_u32(dump, 8) as usize;
+        let directory = read_u32(dump, 12) as usize;
+        (0..count)
+            .map(|i| directory + i * DIRECTORY_SIZE)
+            .find(|&d| read_u32(dump, d) == stream_type)
+            .map(|d| {
+                (
+                    read_u32(dump, d + 4) as usize,
+                    read_u32(dump, d + 8) as usize,
+                )
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn minidump_has_thread_module_and_memory() {
+        let code = vec![0xccu8; 0x1000];
+        let stack: Vec<u8> = (0..0x2000).map(|i| i as u8).collect();
+        let code_ptr = code.as_ptr() as usize;
+        let stack_ptr = stack.as_ptr() as usize;
+        let regions = vec![
+            CrashDumpRegion {
+                guest_region: 0x1000..0x2000,
+                host_region: code_ptr..code_ptr + code.len(),
+                flags: MemoryRegionFlags::READ | MemoryRegionFlags::EXECUTE,
+                region_type: MemoryRegionType::Code,
+            },
+            CrashDumpRegion {
+                guest_region: 0x10000..0x12000,
+                host_region: stack_ptr..stack_ptr + stack.len(),
+                flags: MemoryRegionFlags::READ | MemoryRegionFlags::WRITE,
+                region_type: MemoryRegionType::Scratch,
+            },
+        ];
+        let mut regs = [0; 27];
+        regs[10] = 0xaaaa; // rax
+        regs[16] = 0x1234; // rip
+        regs[RSP] = 0x11f00;
+        let ctx = CrashDumpContext::new(
+            regions,
+            regs,
+            vec![],
+            0x1400,
+            Some("guest.exe".to_string()),
+            None,
+        );
+
+        let mut dump = Vec::new();
+        let nbytes = write_minidump(&ctx, &mut dump).unwrap();
+        assert_eq!(nbytes, dump.len());
+        assert_eq!(read_u32(&dump, 0), MINIDUMP_SIGNATURE);
+        assert_eq!(read_u32(&dump, 8), STREAM_COUNT as u32);
+
+        // The thread's context holds the registers, and its stack is the
+        // memory above rsp
+        let (_, threads) = find_stream(&dump, THREAD_LIST_STREAM);
+        assert_eq!(read_u32(&dump, threads), 1);
+        let thread = threads + 4;
+        assert_eq!(read_u64(&dump, thread + 24), 0x11f00);
+        let stack_size = read_u32(&dump, thread + 32) as usize;
+        let stack_rva = read_u32(&dump, thread + 36) as usize;
+        assert_eq!(stack_size, 0x100);
+        assert_eq!(&dump[stack_rva..stack_rva + stack_size], &stack[0x1f00..]);
+        let context = read_u32(&dump, thread + 44) as usize;
+        assert_eq!(read_u32(&dump, context + 0x30), CONTEXT_FLAGS);
+        assert_eq!(read_u64(&dump, context + 0x78), 0xaaaa);
+        assert_eq!(read_u64(&dump, context + 0xf8), 0x1234);
+
+        // The guest binary is the only module
+        let (_, modules) = find_stream(&dump, MODULE_LIST_STREAM);
+        assert_eq!(read_u32(&dump, modules), 1);
+        assert_eq!(read_u64(&dump, modules + 4), 0x1000);
+        assert_eq!(read_u32(&dump, modules + 12), 0x1000);
+        let name = read_u32(&dump, modules + 24) as usize;
+        let name_len = read_u32(&dump, name) as usize;
+        let name: Vec<u16> = dump[name + 4..name + 4 + name_len]
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        assert_eq!(String::from_utf16(&name).unwrap(), "guest.exe");
+
+        // Both regions are in the memory list, with their data at the end
+        let (_, memory) = find_stream(&dump, MEMORY64_LIST_STREAM);
+        assert_eq!(read_u64(&dump, memory), 2);
+        let base = read_u64(&dump, memory + 8) as usize;
+        assert_eq!(read_u64(&dump, memory + 16), 0x1000);
+        assert_eq!(read_u64(&dump, memory + 24), 0x1000);
+        assert_eq!(read_u64(&dump, memory + 32), 0x10000);
+        assert_eq!(read_u64(&dump, memory + 40), 0x2000);
+        assert_eq!(&dump[base..base + 0x1000], &code[..]);
+        assert_eq!(&dump[base + 0x1000..], &stack[..]);
+    }
+
+    #[test]
+    fn minidump_without_regions_fails() {
+        let ctx = CrashDumpContext::new(vec![], [0; 27], vec![], 0, None, None);
+        assert!(write_minidump(&ctx, std::io::sink()).is_err());
+    }
+}
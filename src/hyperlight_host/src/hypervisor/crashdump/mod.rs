@@ -27,8 +27,27 @@ use crate::hypervisor::hyperlight_vm::HyperlightVm;
 use crate::mem::memory_region::{CrashDumpRegion, MemoryRegionFlags};
 use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::shared_mem::HostSharedMemory;
+use crate::sandbox::SandboxLabels;
 use crate::{Result, new_error};
 
+mod live;
+mod minidump;
+mod policy;
+mod report;
+mod sink;
+#[cfg(target_os = "windows")]
+mod wer;
+
+pub use live::CoreDumpHandle;
+pub(crate) use live::LiveDumpSlot;
+pub use policy::{CrashDumpArea, CrashDumpPolicy};
+pub(crate) use report::RecentOutcalls;
+pub use report::{CrashReport, CrashReportRegion, RecentOutcall};
+use sink::PathCrashDumpSink;
+pub use sink::{CrashDumpInfo, CrashDumpReason, CrashDumpSink, FileCrashDumpSink};
+#[cfg(target_os = "windows")]
+pub use wer::WerCrashDumpSink;
+
 /// This constant is used to identify the XSAVE state in the core dump
 const NT_X86_XSTATE: u32 = 0x202;
 /// This constant identifies the entry point of the program in an Auxiliary Vector
@@ -42,6 +61,32 @@ const CORE_DUMP_PID: i32 = 1;
 /// The page size of the core dump
 const CORE_DUMP_PAGE_SIZE: usize = 0x1000;
 
+/// The file format a core dump is written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoreDumpFormat {
+    /// An ELF core file, opened with gdb or lldb
+    Elf,
+    /// A Windows minidump, opened with WinDbg or Visual Studio
+    Minidump,
+}
+
+impl CoreDumpFormat {
+    /// The format the debuggers of the host platform open
+    const NATIVE: Self = if cfg!(windows) {
+        Self::Minidump
+    } else {
+        Self::Elf
+    };
+
+    /// The extension of core dump files in this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Elf => "elf",
+            Self::Minidump => "dmp",
+        }
+    }
+}
+
 /// Structure to hold the crash dump context
 /// This structure contains the information needed to create a core dump
 #[derive(Debug)]
@@ -52,6 +97,8 @@ pub(crate) struct CrashDumpContext {
     entry: u64,
     binary: Option<String>,
     filename: Option<String>,
+    /// The zeros that redacted regions are read from
+    redacted: Box<[u8]>,
 }
 
 impl CrashDumpContext {
@@ -70,6 +117,7 @@ impl CrashDumpContext {
             entry,
             binary,
             filename,
+            redacted: Box::default(),
         }
     }
 }
@@ -251,80 +299,140 @@ impl ReadProcessMemory for GuestMemReader {
     }
 }
 
-/// Create core dump file from the hypervisor information if the sandbox is configured
-/// to allow core dumps.
+/// Create core dump from the hypervisor information if the sandbox is configured
+/// to allow core dumps, and pass it to the sandbox's [`CrashDumpSink`].
 ///
-/// This function generates an ELF core dump file capturing the hypervisor's state,
-/// which can be used for debugging when crashes occur.
+/// This function generates a core dump capturing the hypervisor's state,
+/// which can be used for debugging when crashes occur. The dump is an ELF core
+/// file, or a minidump on Windows.
 ///
-/// If `override_dir` is `Some`, the core dump is placed there. Otherwise, the
-/// location is determined by the `HYPERLIGHT_CORE_DUMP_DIR` environment variable.
-/// If neither is set, it defaults to the system's temporary directory.
+/// If `sink` is `Some`, the dump is passed to it instead of the sandbox's
+/// sink. If the sandbox has no sink either, the dump is written to a file by
+/// a [`FileCrashDumpSink`].
 ///
 /// # Arguments
 /// * `hv`: Reference to the hypervisor implementation
 /// * `mem_mgr`: Mutable reference to the sandbox memory manager
-/// * `override_dir`: Optional directory path that takes priority over the environment variable
+/// * `reason`: Why the dump is being taken
+/// * `sink`: Optional sink that takes priority over the sandbox's sink
 ///
 /// # Returns
 /// * `Result<()>`: Success or error
 pub(crate) fn generate_crashdump(
     hv: &HyperlightVm,
     mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
-    override_dir: Option<String>,
+    reason: CrashDumpReason,
+    sink: Option<&dyn CrashDumpSink>,
 ) -> Result<()> {
-    // Get crash context from hypervisor
-    let ctx = hv
-        .crashdump_context(mem_mgr)
-        .map_err(|e| new_error!("Failed to get crashdump context: {:?}", e))?;
+    let ctx = crashdump_context(hv, mem_mgr)?;
 
-    // Prefer the explicit override, then the env var, then the system temp dir
-    let core_dump_dir = override_dir.or_else(|| std::env::var("HYPERLIGHT_CORE_DUMP_DIR").ok());
+    // Prefer the explicit sink, then the sandbox's sink, then a file
+    let default_sink = FileCrashDumpSink::new();
+    let sink = sink
+        .or(hv.rt_cfg.crashdump_sink.as_deref())
+        .unwrap_or(&default_sink);
 
-    // Compute file path on the filesystem
-    let file_path = core_dump_file_path(core_dump_dir);
+    if let Err(e) = write_to_sink(hv, ctx, reason, sink) {
+        tracing::error!("Failed to create core dump: {:?}", e);
+    }
 
-    let create_dump_file = || {
-        // Create the file
-        Ok(Box::new(
-            std::fs::File::create(&file_path)
-                .map_err(|e| new_error!("Failed to create core dump file: {:?}", e))?,
-        ) as Box<dyn Write>)
-    };
+    Ok(())
+}
 
-    if let Ok(nbytes) = checked_core_dump(ctx, create_dump_file) {
-        if nbytes > 0 {
-            println!("Core dump created successfully: {}", file_path);
-            tracing::error!("Core dump file: {}", file_path);
-        }
-    } else {
-        tracing::error!("Failed to create core dump file");
+/// Write a core dump of the current state of the guest to the file at
+/// `path`, bypassing the sandbox's sink.
+///
+/// Unlike [`generate_crashdump`], a failure to write the dump is returned,
+/// as is the sandbox not being configured to allow core dumps.
+pub(crate) fn dump_core(
+    hv: &HyperlightVm,
+    mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+    path: &std::path::Path,
+) -> Result<()> {
+    let ctx = crashdump_context(hv, mem_mgr)?;
+    if ctx.is_none() {
+        return Err(new_error!("Core dumps are disabled for this sandbox"));
     }
-
+    let sink = PathCrashDumpSink(path.to_path_buf());
+    write_to_sink(hv, ctx, CrashDumpReason::Requested, &sink)?;
+    tracing::info!("Core dump created: {}", path.display());
     Ok(())
 }
 
-/// Computes the file path for the core dump file.
+/// Get the crash dump context of the guest from the hypervisor, with the
+/// sandbox's [`CrashDumpPolicy`] applied
+fn crashdump_context(
+    hv: &HyperlightVm,
+    mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+) -> Result<Option<CrashDumpContext>> {
+    let mut ctx = hv
+        .crashdump_context(mem_mgr)
+        .map_err(|e| new_error!("Failed to get crashdump context: {:?}", e))?;
+    if let Some(ctx) = &mut ctx {
+        hv.rt_cfg.crashdump_policy.apply(ctx, &mem_mgr.layout);
+    }
+    Ok(ctx)
+}
+
+/// Write the core dump in `ctx` to `sink`, and tell the sink once it has
+/// been written
+fn write_to_sink(
+    hv: &HyperlightVm,
+    ctx: Option<CrashDumpContext>,
+    reason: CrashDumpReason,
+    sink: &dyn CrashDumpSink,
+) -> Result<usize> {
+    let format = CoreDumpFormat::NATIVE;
+    let location = ctx
+        .as_ref()
+        .map(|ctx| hv.rt_cfg.guest_symbolizer.symbolize(ctx.regs[16]));
+    if let (CrashDumpReason::GuestCrash(_), Some(location)) = (&reason, &location) {
+        tracing::error!("Guest crashed at {}", location);
+    }
+    let report = ctx.as_ref().map(|ctx| {
+        CrashReport::new(
+            ctx,
+            reason.clone(),
+            location.clone(),
+            hv.rt_cfg.labels.clone(),
+            hv.rt_cfg.binary_path.clone(),
+            hv.rt_cfg.guest_symbolizer.build_id(),
+            &hv.rt_cfg.recent_outcalls,
+        )
+    });
+    let info = CrashDumpInfo {
+        labels: hv.rt_cfg.labels.clone(),
+        binary_path: hv.rt_cfg.binary_path.clone(),
+        reason,
+        location,
+        format,
+        file_name: core_dump_file_name(&hv.rt_cfg.labels, format),
+    };
+
+    let nbytes = checked_core_dump(ctx, format, || sink.open(&info))?;
+    if let Some(report) = &report
+        && let Err(e) = sink.report(&info, report)
+    {
+        tracing::error!("Crash dump sink failed to write crash report: {:?}", e);
+    }
+    if let Err(e) = sink.finished(&info, nbytes) {
+        tracing::error!("Crash dump sink failed to finish core dump: {:?}", e);
+    }
+    Ok(nbytes)
+}
+
+/// Computes the directory core dump files are written to.
 ///
-/// The file path is generated based on the current timestamp and an
-/// output directory.
-/// If the directory does not exist, it falls back to the system's temp directory.
-/// If the variable is not set, it defaults to the system's temporary directory.
-/// The filename is formatted as `hl_core_<timestamp>.elf`.
+/// If `dump_dir` is set and the directory exists, it is used. Otherwise,
+/// the system's temporary directory is used.
 ///
 /// Arguments:
-/// * `dump_dir`: The environment variable value to check for the output directory.
+/// * `dump_dir`: The requested output directory, if any.
 ///
 /// Returns:
-/// * `String`: The file path for the core dump file.
-fn core_dump_file_path(dump_dir: Option<String>) -> String {
-    // Generate timestamp string for the filename using chrono
-    let timestamp = chrono::Local::now()
-        .format("%Y%m%d_T%H%M%S%.3f")
-        .to_string();
-
-    // Determine the output directory based on environment variable
-    let output_dir = if let Some(dump_dir) = dump_dir {
+/// * `PathBuf`: The directory for the core dump file.
+fn core_dump_dir(dump_dir: Option<String>) -> std::path::PathBuf {
+    if let Some(dump_dir) = dump_dir {
         // Check if the directory exists
         // If it doesn't exist, fall back to the system temp directory
         // This is to ensure that the core dump can be created even if the directory is not set
@@ -340,13 +448,37 @@ fn core_dump_file_path(dump_dir: Option<String>) -> String {
     } else {
         // Fall back to the system temp directory
         std::env::temp_dir()
-    };
+    }
+}
 
-    // Create the filename with timestamp
-    let filename = format!("hl_core_{}.elf", timestamp);
-    let file_path = output_dir.join(filename);
+/// Computes the name of a core dump.
+///
+/// The name is formatted as `hl_core_<timestamp>.<ext>`, or as
+/// `hl_core_<labels>_<timestamp>.<ext>` if the sandbox has labels, where the
+/// extension depends on the format.
+///
+/// Arguments:
+/// * `labels`: The labels of the sandbox being dumped.
+/// * `format`: The format the core dump is written in.
+///
+/// Returns:
+/// * `String`: The name of the core dump.
+fn core_dump_file_name(labels: &SandboxLabels, format: CoreDumpFormat) -> String {
+    // Generate timestamp string for the filename using chrono
+    let timestamp = chrono::Local::now()
+        .format("%Y%m%d_T%H%M%S%.3f")
+        .to_string();
 
-    file_path.to_string_lossy().to_string()
+    if labels.is_empty() {
+        format!("hl_core_{}.{}", timestamp, format.extension())
+    } else {
+        format!(
+            "hl_core_{}_{}.{}",
+            labels.to_file_name_part(),
+            timestamp,
+            format.extension()
+        )
+    }
 }
 
 /// Create core dump from Hypervisor context if the sandbox is configured to allow core dumps.
@@ -354,12 +486,14 @@ fn core_dump_file_path(dump_dir: Option<String>) -> String {
 /// Arguments:
 /// * `ctx`: Optional crash dump context from the hypervisor. This contains the information
 ///   needed to create the core dump. If `None`, no core dump will be created.
+/// * `format`: The format to write the core dump in.
 /// * `get_writer`: Closure that returns a writer to the output destination.
 ///
 /// Returns:
 /// * `Result<usize>`: The number of bytes written to the core dump file.
 fn checked_core_dump(
     ctx: Option<CrashDumpContext>,
+    format: CoreDumpFormat,
     get_writer: impl FnOnce() -> Result<Box<dyn Write>>,
 ) -> Result<usize> {
     let mut nbytes = 0;
@@ -368,6 +502,10 @@ fn checked_core_dump(
     if let Some(ctx) = ctx {
         tracing::info!("Creating core dump file...");
 
+        if format == CoreDumpFormat::Minidump {
+            return minidump::write_minidump(&ctx, get_writer()?);
+        }
+
         // Set up data sources for the core dump
         let guest_view = GuestView::new(&ctx);
         let memory_reader = GuestMemReader::new(&ctx);
@@ -390,55 +528,66 @@ fn checked_core_dump(
 mod test {
     use super::*;
 
-    /// Test the core_dump_file_path function when the environment variable is set to an existing
+    /// Test the core_dump_dir function when the environment variable is set to an existing
     /// directory
     #[test]
     fn test_crashdump_file_path_valid() {
         // Get CWD
-        let valid_dir = std::env::current_dir()
-            .unwrap()
-            .to_string_lossy()
-            .to_string();
+        let valid_dir = std::env::current_dir().unwrap();
 
         // Call the function
-        let path = core_dump_file_path(Some(valid_dir.clone()));
+        let path = core_dump_dir(Some(valid_dir.to_string_lossy().to_string()));
 
         // Check if the path is correct
-        assert!(path.contains(&valid_dir));
+        assert_eq!(path, valid_dir);
     }
 
-    /// Test the core_dump_file_path function when the environment variable is set to an invalid
+    /// Test the core_dump_dir function when the environment variable is set to an invalid
     /// directory
     #[test]
     fn test_crashdump_file_path_invalid() {
         // Call the function
-        let path = core_dump_file_path(Some("/tmp/not_existing_dir".to_string()));
-
-        // Get the temp directory
-        let temp_dir = std::env::temp_dir().to_string_lossy().to_string();
+        let path = core_dump_dir(Some("/tmp/not_existing_dir".to_string()));
 
         // Check if the path is correct
-        assert!(path.contains(&temp_dir));
+        assert_eq!(path, std::env::temp_dir());
     }
 
-    /// Test the core_dump_file_path function when the environment is not set
+    /// Test the core_dump_dir function when the environment is not set
     /// Check against the default temp directory by using the env::temp_dir() function
     #[test]
     fn test_crashdump_file_path_default() {
         // Call the function
-        let path = core_dump_file_path(None);
-
-        let temp_dir = std::env::temp_dir().to_string_lossy().to_string();
+        let path = core_dump_dir(None);
 
         // Check if the path is correct
-        assert!(path.starts_with(&temp_dir));
+        assert_eq!(path, std::env::temp_dir());
+    }
+
+    /// Test that the sandbox labels are included in the file name
+    #[test]
+    fn test_crashdump_file_path_labels() {
+        let mut labels = SandboxLabels::default();
+        labels.set("tenant", "contoso/eu").unwrap();
+        let name = core_dump_file_name(&labels, CoreDumpFormat::Elf);
+
+        assert!(name.starts_with("hl_core_tenant-contoso_eu_"));
+    }
+
+    /// Test that minidumps get the extension Windows debuggers expect
+    #[test]
+    fn test_crashdump_file_path_minidump() {
+        let name = core_dump_file_name(&SandboxLabels::default(), CoreDumpFormat::Minidump);
+
+        assert!(name.ends_with(".dmp"));
     }
 
     /// Test core is not created when the context is None
     #[test]
     fn test_crashdump_not_created_when_context_is_none() {
         // Call the function with None context
-        let result = checked_core_dump(None, || Ok(Box::new(std::io::empty())));
+        let result =
+            checked_core_dump(None, CoreDumpFormat::Elf, || Ok(Box::new(std::io::empty())));
 
         // Check if the result is ok and the number of bytes is 0
         assert!(result.is_ok());
@@ -461,7 +610,7 @@ mod test {
         let get_writer = || Ok(Box::new(std::io::empty()) as Box<dyn Write>);
 
         // Call the function
-        let result = checked_core_dump(Some(ctx), get_writer);
+        let result = checked_core_dump(Some(ctx), CoreDumpFormat::Elf, get_writer);
 
         // Check if the result is an error
         // This should fail because there are no regions
@@ -493,7 +642,7 @@ mod test {
         let get_writer = || Ok(Box::new(std::io::empty()) as Box<dyn Write>);
 
         // Call the function
-        let result = checked_core_dump(Some(ctx), get_writer);
+        let result = checked_core_dump(Some(ctx), CoreDumpFormat::Elf, get_writer);
 
         // Check if the result is ok and the number of bytes is 0
         assert!(result.is_ok());
@@ -0,0 +1,343 @@
+/*
+Copyright 2025 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::ops::Range;
+
+use super::CrashDumpContext;
+use crate::mem::layout::SandboxMemoryLayout;
+use crate::mem::memory_region::CrashDumpRegion;
+
+/// The granularity that a [`CrashDumpPolicy::with_max_size`] cap is
+/// applied at, so that truncated regions stay page aligned
+const CAP_GRANULARITY: usize = 0x1000;
+
+/// A part of guest memory that a [`CrashDumpPolicy`] can leave out of, or
+/// redact in, crash dumps
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrashDumpArea {
+    /// The guest heap
+    Heap,
+    /// The buffer the host writes guest function calls into
+    InputBuffer,
+    /// The buffer the guest writes function results and host function
+    /// calls into
+    OutputBuffer,
+    /// A range of guest virtual addresses
+    GuestRange {
+        /// The first address of the range
+        start: u64,
+        /// The length of the range in bytes
+        len: u64,
+    },
+}
+
+impl CrashDumpArea {
+    /// The guest virtual addresses covered by the area
+    fn guest_range(self, layout: &SandboxMemoryLayout) -> Range<usize> {
+        match self {
+            Self::Heap => layout.get_heap_gva_range(),
+            Self::InputBuffer => layout.get_input_data_buffer_gva_range(),
+            Self::OutputBuffer => layout.get_output_data_buffer_gva_range(),
+            Self::GuestRange { start, len } => start as usize..start.saturating_add(len) as usize,
+        }
+    }
+}
+
+/// What the crash dumps of a sandbox contain.
+///
+/// By default a crash dump holds all of the guest's memory, which can
+/// include data that should not leave the host, such as the secrets of a
+/// tenant. A policy can leave parts of guest memory out of the dump
+/// entirely, [redact](Self::redact) them so that they are present but
+/// zeroed, and cap the amount of memory a dump holds. Leaving an area out
+/// wins over redacting it.
+///
+/// The policy is set with
+/// [`UninitializedSandbox::set_crashdump_policy()`](crate::UninitializedSandbox::set_crashdump_policy),
+/// and applies to both crash dumps taken when the guest crashes and to
+/// those requested with `generate_crashdump`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::{CrashDumpArea, CrashDumpPolicy};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut u_sbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// u_sbox.set_crashdump_policy(
+///     CrashDumpPolicy::new()
+///         .exclude(CrashDumpArea::InputBuffer)
+///         .exclude(CrashDumpArea::OutputBuffer)
+///         .redact(CrashDumpArea::Heap)
+///         .with_max_size(64 * 1024 * 1024),
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CrashDumpPolicy {
+    excluded: Vec<CrashDumpArea>,
+    redacted: Vec<CrashDumpArea>,
+    max_size: Option<usize>,
+}
+
+impl CrashDumpPolicy {
+    /// A policy that dumps all of guest memory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leave `area` out of crash dumps
+    pub fn exclude(mut self, area: CrashDumpArea) -> Self {
+        self.excluded.push(area);
+        self
+    }
+
+    /// Keep `area` in crash dumps, but replace its contents with zeros
+    pub fn redact(mut self, area: CrashDumpArea) -> Self {
+        self.redacted.push(area);
+        self
+    }
+
+    /// Dump at most `max_size` bytes of guest memory, rounded down to a
+    /// whole number of pages. The memory around the instruction and stack
+    /// pointers is kept first, followed by the rest of memory in order of
+    /// address.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Apply the policy to the regions of `ctx`
+    pub(super) fn apply(&self, ctx: &mut CrashDumpContext, layout: &SandboxMemoryLayout) {
+        let excluded: Vec<_> = self
+            .excluded
+            .iter()
+            .map(|a| a.guest_range(layout))
+            .collect();
+        let redacted: Vec<_> = self
+            .redacted
+            .iter()
+            .map(|a| a.guest_range(layout))
+            .collect();
+        ctx.filter_regions(&excluded, &redacted, self.max_size);
+    }
+}
+
+/// What happens to a piece of a region
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Treatment {
+    Keep,
+    Redact,
+    Exclude,
+}
+
+/// Split `region` into the pieces that the excluded and redacted ranges
+/// cut it into, dropping the excluded ones
+fn split_region(
+    region: &CrashDumpRegion,
+    excluded: &[Range<usize>],
+    redacted: &[Range<usize>],
+) -> Vec<(Range<usize>, Treatment)> {
+    let guest = region.guest_region.clone();
+    let mut cuts = vec![guest.start, guest.end];
+    for range in excluded.iter().chain(redacted) {
+        for cut in [range.start, range.end] {
+            if guest.contains(&cut) {
+                cuts.push(cut);
+            }
+        }
+    }
+    cuts.sort_unstable();
+    cuts.dedup();
+
+    let mut pieces: Vec<(Range<usize>, Treatment)> = Vec::new();
+    for piece in cuts.windows(2).map(|w| w[0]..w[1]) {
+        let treatment = if excluded.iter().any(|r| r.contains(&piece.start)) {
+            Treatment::Exclude
+        } else if redacted.iter().any(|r| r.contains(&piece.start)) {
+            Treatment::Redact
+        } else {
+            Treatment::Keep
+        };
+        match pieces.last_mut() {
+            Some((last, t)) if *t == treatment => last.end = piece.end,
+            _ => pieces.push((piece, treatment)),
+        }
+    }
+    pieces.retain(|(_, t)| *t != Treatment::Exclude);
+    pieces
+}
+
+impl CrashDumpContext {
+    /// Leave the `excluded` guest ranges out of the dump, zero the
+    /// `redacted` ones, and keep at most `max_size` bytes of memory
+    pub(super) fn filter_regions(
+        &mut self,
+        excluded: &[Range<usize>],
+        redacted: &[Range<usize>],
+        max_size: Option<usize>,
+    ) {
+        let pieces: Vec<_> = self
+            .regions
+            .iter()
+            .filter(|r| !r.guest_region.is_empty())
+            .flat_map(|r| {
+                split_region(r, excluded, redacted)
+                    .into_iter()
+                    .map(move |(piece, treatment)| (r, piece, treatment))
+            })
+            .collect();
+
+        // Redacted pieces are backed by a buffer of zeros owned by the
+        // context, so the writers need not know about redaction
+        let zeros_len = pieces
+            .iter()
+            .filter(|(_, _, t)| *t == Treatment::Redact)
+            .map(|(_, piece, _)| piece.len())
+            .max()
+            .unwrap_or(0);
+        self.redacted = vec![0; zeros_len].into();
+        let zeros = self.redacted.as_ptr() as usize;
+
+        let mut regions: Vec<CrashDumpRegion> = pieces
+            .into_iter()
+            .map(|(region, piece, treatment)| {
+                let host_region = match treatment {
+                    Treatment::Redact => zeros..zeros + piece.len(),
+                    _ => {
+                        let host = &region.host_region;
+                        let offset = piece.start - region.guest_region.start;
+                        let start = (host.start + offset).min(host.end);
+                        start..(start + piece.len()).min(host.end)
+                    }
+                };
+                CrashDumpRegion {
+                    guest_region: piece,
+                    host_region,
+                    flags: region.flags,
+                    region_type: region.region_type,
+                }
+            })
+            .collect();
+
+        if let Some(max_size) = max_size {
+            let rip = self.regs[16] as usize;
+            let rsp = self.regs[19] as usize;
+            let mut remaining = max_size - max_size % CAP_GRANULARITY;
+            // The stable sort keeps the other regions in address order
+            regions
+                .sort_by_key(|r| !(r.guest_region.contains(&rip) || r.guest_region.contains(&rsp)));
+            regions.retain_mut(|r| {
+                if remaining == 0 {
+                    return false;
+                }
+                let len = r.guest_region.len().min(remaining);
+                // Truncating the region around rsp keeps the top of the stack
+                let start = if r.guest_region.contains(&rsp) {
+                    (rsp - rsp % CAP_GRANULARITY)
+                        .min(r.guest_region.end - len)
+                        .max(r.guest_region.start)
+                } else {
+                    r.guest_region.start
+                };
+                let offset = start - r.guest_region.start;
+                r.host_region = (r.host_region.start + offset).min(r.host_region.end)
+                    ..(r.host_region.start + offset + len).min(r.host_region.end);
+                r.guest_region = start..start + len;
+                remaining -= len;
+                true
+            });
+            regions.sort_by_key(|r| r.guest_region.start);
+        }
+
+        self.regions = regions;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem::memory_region::{MemoryRegionFlags, MemoryRegionType};
+
+    fn context(memory: &[u8], rsp: u64) -> CrashDumpContext {
+        let host = memory.as_ptr() as usize;
+        let regions = vec![CrashDumpRegion {
+            guest_region: 0x10000..0x10000 + memory.len(),
+            host_region: host..host + memory.len(),
+            flags: MemoryRegionFlags::READ | MemoryRegionFlags::WRITE,
+            region_type: MemoryRegionType::Snapshot,
+        }];
+        let mut regs = [0; 27];
+        regs[19] = rsp;
+        CrashDumpContext::new(regions, regs, vec![], 0, None, None)
+    }
+
+    fn contents(region: &CrashDumpRegion) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(
+                region.host_region.start as *const u8,
+                region.host_region.len(),
+            )
+        }
+    }
+
+    #[test]
+    fn excluded_ranges_are_left_out_and_redacted_ranges_zeroed() {
+        let memory = vec![0xaa; 0x4000];
+        let mut ctx = context(&memory, 0);
+
+        let excluded = 0x11000..0x12000;
+        let redacted = 0x11800..0x13000;
+        ctx.filter_regions(
+            std::slice::from_ref(&excluded),
+            std::slice::from_ref(&redacted),
+            None,
+        );
+
+        let guest: Vec<_> = ctx.regions.iter().map(|r| r.guest_region.clone()).collect();
+        assert_eq!(
+            guest,
+            vec![0x10000..0x11000, 0x12000..0x13000, 0x13000..0x14000]
+        );
+        assert!(contents(&ctx.regions[0]).iter().all(|&b| b == 0xaa));
+        assert!(contents(&ctx.regions[1]).iter().all(|&b| b == 0));
+        assert_eq!(contents(&ctx.regions[1]).len(), 0x1000);
+        assert!(contents(&ctx.regions[2]).iter().all(|&b| b == 0xaa));
+        assert_eq!(
+            contents(&ctx.regions[2]).as_ptr() as usize,
+            memory.as_ptr() as usize + 0x3000
+        );
+    }
+
+    #[test]
+    fn max_size_keeps_the_top_of_the_stack() {
+        let memory = vec![0xaa; 0x8000];
+        let mut ctx = context(&memory, 0x16800);
+
+        ctx.filter_regions(&[], &[], Some(0x2fff));
+
+        assert_eq!(ctx.regions.len(), 1);
+        assert_eq!(ctx.regions[0].guest_region, 0x16000..0x18000);
+        assert_eq!(
+            ctx.regions[0].host_region.start,
+            memory.as_ptr() as usize + 0x6000
+        );
+    }
+}
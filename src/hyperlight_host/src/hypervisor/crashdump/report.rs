@@ -0,0 +1,274 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+use super::{CrashDumpContext, CrashDumpReason};
+use crate::mem::memory_region::{MemoryRegionFlags, MemoryRegionType};
+use crate::sandbox::{GuestFrame, SandboxLabels};
+
+/// The names of the registers in a [`CrashDumpContext`], in the order of
+/// the `user_regs_struct` of an ELF core dump
+const REGISTER_NAMES: [&str; 27] = [
+    "r15", "r14", "r13", "r12", "rbp", "rbx", "r11", "r10", "r9", "r8", "rax", "rcx", "rdx", "rsi",
+    "rdi", "orig_rax", "rip", "cs", "rflags", "rsp", "ss", "fs_base", "gs_base", "ds", "es", "fs",
+    "gs",
+];
+
+/// How many of the guest's last outcalls a crash report lists
+const MAX_RECENT_OUTCALLS: usize = 16;
+
+/// A machine-readable summary of a crash, which a [`CrashDumpSink`]
+/// receives alongside the dump, so incident tooling can triage the crash
+/// without parsing the core file.
+///
+/// [`FileCrashDumpSink`] writes the report, as returned by
+/// [`to_json()`](Self::to_json), to a `.json` file next to the dump.
+///
+/// [`CrashDumpSink`]: super::CrashDumpSink
+/// [`FileCrashDumpSink`]: super::FileCrashDumpSink
+#[derive(Clone, Debug)]
+pub struct CrashReport {
+    /// When the dump was taken, in RFC 3339 format
+    pub timestamp: String,
+    /// Why the dump was taken, which for a crash includes how the guest
+    /// exited
+    pub reason: CrashDumpReason,
+    /// The instruction the guest was at
+    pub location: Option<GuestFrame>,
+    /// The labels of the sandbox
+    pub labels: SandboxLabels,
+    /// The path of the guest binary, if it was loaded from a file
+    pub binary_path: Option<String>,
+    /// The GNU build id of the guest binary, in hex, if it has one
+    pub build_id: Option<String>,
+    /// The guest's general purpose and segment registers, by name
+    pub registers: Vec<(&'static str, u64)>,
+    /// The regions of guest memory in the dump
+    pub regions: Vec<CrashReportRegion>,
+    /// The last host functions the guest called, oldest first
+    pub recent_outcalls: Vec<RecentOutcall>,
+}
+
+/// A region of guest memory in a [`CrashReport`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CrashReportRegion {
+    /// The first guest address of the region
+    pub start: u64,
+    /// The guest address just past the end of the region
+    pub end: u64,
+    /// How the guest may access the region
+    pub flags: MemoryRegionFlags,
+    /// What the region holds
+    pub region_type: MemoryRegionType,
+}
+
+/// A host function call in a [`CrashReport`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecentOutcall {
+    /// The name of the host function
+    pub function: String,
+    /// When the call was made, in RFC 3339 format
+    pub timestamp: String,
+    /// How long the call took
+    pub duration: Duration,
+    /// The error the call failed with, if it failed
+    pub error: Option<String>,
+}
+
+impl CrashReport {
+    /// Describe the crash in `ctx`
+    pub(super) fn new(
+        ctx: &CrashDumpContext,
+        reason: CrashDumpReason,
+        location: Option<GuestFrame>,
+        labels: SandboxLabels,
+        binary_path: Option<String>,
+        build_id: Option<&[u8]>,
+        recent_outcalls: &RecentOutcalls,
+    ) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            reason,
+            location,
+            labels,
+            binary_path,
+            build_id: build_id.map(|id| id.iter().map(|b| format!("{b:02x}")).collect()),
+            registers: REGISTER_NAMES
+                .iter()
+                .zip(ctx.regs)
+                .filter(|(name, _)| **name != "orig_rax")
+                .map(|(name, value)| (*name, value))
+                .collect(),
+            regions: ctx
+                .regions
+                .iter()
+                .filter(|r| !r.guest_region.is_empty())
+                .map(|r| CrashReportRegion {
+                    start: r.guest_region.start as u64,
+                    end: r.guest_region.end as u64,
+                    flags: r.flags,
+                    region_type: r.region_type,
+                })
+                .collect(),
+            recent_outcalls: recent_outcalls.to_vec(),
+        }
+    }
+
+    /// The report as a JSON object. Addresses and register values are
+    /// hex strings, as they do not all fit in a JSON number.
+    pub fn to_json(&self) -> String {
+        let (reason, message) = match &self.reason {
+            CrashDumpReason::GuestCrash(message) => ("guest_crash", Some(message)),
+            CrashDumpReason::Requested => ("requested", None),
+        };
+        let registers: serde_json::Map<String, Value> = self
+            .registers
+            .iter()
+            .map(|(name, value)| (name.to_string(), json!(format!("{value:#x}"))))
+            .collect();
+        let report = json!({
+            "timestamp": self.timestamp,
+            "reason": reason,
+            "message": message,
+            "location": self.location.as_ref().map(|frame| json!({
+                "address": format!("{:#x}", frame.address),
+                "symbol": frame.symbol,
+                "offset": format!("{:#x}", frame.offset),
+                "file": frame.file,
+                "line": frame.line,
+            })),
+            "labels": self
+                .labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), json!(value)))
+                .collect::<serde_json::Map<_, _>>(),
+            "binary_path": self.binary_path,
+            "build_id": self.build_id,
+            "registers": registers,
+            "regions": self.regions.iter().map(|region| json!({
+                "start": format!("{:#x}", region.start),
+                "end": format!("{:#x}", region.end),
+                "flags": region.flags.to_string(),
+                "type": format!("{:?}", region.region_type),
+            })).collect::<Vec<_>>(),
+            "recent_outcalls": self.recent_outcalls.iter().map(|outcall| json!({
+                "function": outcall.function,
+                "timestamp": outcall.timestamp,
+                "duration_us": outcall.duration.as_micros() as u64,
+                "error": outcall.error,
+            })).collect::<Vec<_>>(),
+        });
+        // A `Value` always serializes
+        serde_json::to_string_pretty(&report).unwrap_or_default()
+    }
+}
+
+/// The last host functions a guest called, shared by the sandbox's
+/// function registry, which records them, and its crash dumps
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RecentOutcalls(Arc<Mutex<VecDeque<RecentOutcall>>>);
+
+impl RecentOutcalls {
+    /// Record a call to `function`, dropping the oldest call if there are
+    /// too many
+    pub(crate) fn record(&self, function: &str, duration: Duration, error: Option<String>) {
+        let Ok(mut outcalls) = self.0.lock() else {
+            return;
+        };
+        if outcalls.len() == MAX_RECENT_OUTCALLS {
+            outcalls.pop_front();
+        }
+        outcalls.push_back(RecentOutcall {
+            function: function.to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            duration,
+            error,
+        });
+    }
+
+    fn to_vec(&self) -> Vec<RecentOutcall> {
+        self.0
+            .lock()
+            .map(|outcalls| outcalls.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{CrashReport, RecentOutcalls};
+    use crate::hypervisor::crashdump::{CrashDumpContext, CrashDumpReason};
+    use crate::mem::memory_region::{CrashDumpRegion, MemoryRegionFlags, MemoryRegionType};
+    use crate::sandbox::SandboxLabels;
+
+    #[test]
+    fn only_the_last_outcalls_are_kept() {
+        let outcalls = RecentOutcalls::default();
+        for i in 0..20 {
+            outcalls.record(&format!("f{i}"), Duration::from_micros(i), None);
+        }
+        let names: Vec<_> = outcalls.to_vec().into_iter().map(|o| o.function).collect();
+        assert_eq!(names.len(), 16);
+        assert_eq!(names.first().unwrap(), "f4");
+        assert_eq!(names.last().unwrap(), "f19");
+    }
+
+    #[test]
+    fn report_is_json() {
+        let mut regs = [0; 27];
+        regs[16] = 0x1234;
+        let region = CrashDumpRegion {
+            guest_region: 0x1000..0x2000,
+            host_region: 0..0x1000,
+            flags: MemoryRegionFlags::READ | MemoryRegionFlags::EXECUTE,
+            region_type: MemoryRegionType::Code,
+        };
+        let ctx = CrashDumpContext::new(vec![region], regs, vec![], 0, None, None);
+        let outcalls = RecentOutcalls::default();
+        outcalls.record("Open", Duration::from_micros(5), Some("denied".to_string()));
+        let mut labels = SandboxLabels::default();
+        labels.set("tenant", "contoso").unwrap();
+
+        let report = CrashReport::new(
+            &ctx,
+            CrashDumpReason::GuestCrash("page fault".to_string()),
+            None,
+            labels,
+            Some("guest.bin".to_string()),
+            Some(&[0xab, 0x01]),
+            &outcalls,
+        );
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["reason"], "guest_crash");
+        assert_eq!(json["message"], "page fault");
+        assert_eq!(json["build_id"], "ab01");
+        assert_eq!(json["labels"]["tenant"], "contoso");
+        assert_eq!(json["registers"]["rip"], "0x1234");
+        assert!(json["registers"].get("orig_rax").is_none());
+        assert_eq!(json["regions"][0]["start"], "0x1000");
+        assert_eq!(json["regions"][0]["flags"], "READ | EXECUTE");
+        assert_eq!(json["regions"][0]["type"], "Code");
+        assert_eq!(json["recent_outcalls"][0]["function"], "Open");
+        assert_eq!(json["recent_outcalls"][0]["error"], "denied");
+    }
+}
@@ -0,0 +1,220 @@
+/*
+Copyright 2025 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt::Debug;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::{CoreDumpFormat, CrashReport, core_dump_dir};
+use crate::sandbox::{GuestFrame, SandboxLabels};
+use crate::{Result, new_error};
+
+/// Why a crash dump was taken
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CrashDumpReason {
+    /// The guest crashed, for example with an unhandled exception or an
+    /// unexpected VM exit, described by the contained message
+    GuestCrash(String),
+    /// The dump was requested with `generate_crashdump`
+    Requested,
+}
+
+/// What a [`CrashDumpSink`] is told about a crash dump it receives
+#[derive(Clone, Debug)]
+pub struct CrashDumpInfo {
+    /// The labels of the sandbox the dump was taken of
+    pub labels: SandboxLabels,
+    /// The path of the guest binary the sandbox was created from, if it was
+    /// loaded from a file
+    pub binary_path: Option<String>,
+    /// Why the dump was taken
+    pub reason: CrashDumpReason,
+    /// The instruction the guest was at when the dump was taken, resolved
+    /// to its function and source location where the guest binary has
+    /// symbols and debug information
+    pub location: Option<GuestFrame>,
+    /// The format the dump is written in
+    pub format: CoreDumpFormat,
+    /// A unique name for the dump, such as
+    /// `hl_core_tenant-contoso_20250101_T120000.000.elf`, which the default
+    /// sink uses as the file name
+    pub file_name: String,
+}
+
+/// Where the crash dumps of a sandbox are written.
+///
+/// By default crash dumps are written as files to a directory, see
+/// [`FileCrashDumpSink`]. An embedder can set a sink of its own with
+/// [`UninitializedSandbox::set_crashdump_sink()`](crate::UninitializedSandbox::set_crashdump_sink)
+/// to send dumps elsewhere, such as to object storage or a crash reporting
+/// service, and to be told about each dump that is taken.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::io::Write;
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::{CrashDumpInfo, CrashDumpSink};
+/// #[derive(Debug)]
+/// struct Upload;
+///
+/// impl CrashDumpSink for Upload {
+///     fn open(&self, info: &CrashDumpInfo) -> hyperlight_host::Result<Box<dyn Write>> {
+///         println!("dumping {:?} because of {:?}", info.labels, info.reason);
+///         Ok(Box::new(std::io::sink()))
+///     }
+/// }
+///
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut u_sbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// u_sbox.set_crashdump_sink(Upload);
+/// # Ok(())
+/// # }
+/// ```
+pub trait CrashDumpSink: Debug + Send + Sync {
+    /// Open the writer that the dump described by `info` is written to
+    fn open(&self, info: &CrashDumpInfo) -> Result<Box<dyn Write>>;
+
+    /// Called once the dump described by `info` has been written in full,
+    /// with its size in bytes
+    fn finished(&self, _info: &CrashDumpInfo, _nbytes: usize) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called with the machine-readable report of the crash the dump
+    /// described by `info` was taken of, once the dump has been written.
+    /// The default ignores the report.
+    fn report(&self, _info: &CrashDumpInfo, _report: &CrashReport) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The default [`CrashDumpSink`], which writes each dump to a file named
+/// after [`CrashDumpInfo::file_name`], and its [`CrashReport`] to a file
+/// with the same name and a `.json` extension.
+///
+/// The file is placed in the directory the sink was created with, or
+/// otherwise in the directory named by the `HYPERLIGHT_CORE_DUMP_DIR`
+/// environment variable. If neither is set, or the directory does not
+/// exist, the system's temporary directory is used.
+#[derive(Clone, Debug, Default)]
+pub struct FileCrashDumpSink {
+    dir: Option<String>,
+}
+
+impl FileCrashDumpSink {
+    /// A sink that writes to the directory in `HYPERLIGHT_CORE_DUMP_DIR`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A sink that writes to `dir`, ignoring `HYPERLIGHT_CORE_DUMP_DIR`
+    pub fn in_dir(dir: impl Into<String>) -> Self {
+        Self {
+            dir: Some(dir.into()),
+        }
+    }
+
+    /// The path of the file the dump described by `info` is written to
+    fn path(&self, info: &CrashDumpInfo) -> PathBuf {
+        let dir = self
+            .dir
+            .clone()
+            .or_else(|| std::env::var("HYPERLIGHT_CORE_DUMP_DIR").ok());
+        core_dump_dir(dir).join(&info.file_name)
+    }
+}
+
+impl CrashDumpSink for FileCrashDumpSink {
+    fn open(&self, info: &CrashDumpInfo) -> Result<Box<dyn Write>> {
+        let file = std::fs::File::create(self.path(info))
+            .map_err(|e| new_error!("Failed to create core dump file: {:?}", e))?;
+        Ok(Box::new(file))
+    }
+
+    fn report(&self, info: &CrashDumpInfo, report: &CrashReport) -> Result<()> {
+        write_report(&self.path(info), report)
+    }
+
+    fn finished(&self, info: &CrashDumpInfo, nbytes: usize) -> Result<()> {
+        if nbytes > 0 {
+            let path = self.path(info);
+            println!("Core dump created successfully: {}", path.display());
+            tracing::error!("Core dump file: {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+/// A sink that writes a dump to a given file, used for dumps taken with
+/// `dump_core`
+#[derive(Debug)]
+pub(super) struct PathCrashDumpSink(pub(super) PathBuf);
+
+impl CrashDumpSink for PathCrashDumpSink {
+    fn open(&self, _info: &CrashDumpInfo) -> Result<Box<dyn Write>> {
+        let file = std::fs::File::create(&self.0)
+            .map_err(|e| new_error!("Failed to create core dump file: {:?}", e))?;
+        Ok(Box::new(file))
+    }
+
+    fn report(&self, _info: &CrashDumpInfo, report: &CrashReport) -> Result<()> {
+        write_report(&self.0, report)
+    }
+}
+
+/// Write `report` as JSON next to the dump at `dump_path`, in a file with
+/// the same name and a `.json` extension
+pub(super) fn write_report(dump_path: &Path, report: &CrashReport) -> Result<()> {
+    std::fs::write(dump_path.with_extension("json"), report.to_json())
+        .map_err(|e| new_error!("Failed to write crash report: {:?}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    /// Test that the file sink writes the dump to the file named in the
+    /// dump's info, in the sink's directory
+    #[test]
+    fn file_sink_writes_to_named_file() {
+        let dir = std::env::temp_dir().join(format!("hl_sink_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sink = FileCrashDumpSink::in_dir(dir.to_string_lossy());
+        let info = CrashDumpInfo {
+            labels: SandboxLabels::default(),
+            binary_path: None,
+            reason: CrashDumpReason::Requested,
+            location: None,
+            format: CoreDumpFormat::Elf,
+            file_name: "hl_core_test.elf".to_string(),
+        };
+
+        let mut writer = sink.open(&info).unwrap();
+        writer.write_all(b"core").unwrap();
+        drop(writer);
+        sink.finished(&info, 4).unwrap();
+
+        let path = dir.join("hl_core_test.elf");
+        assert_eq!(std::fs::read(&path).unwrap(), b"core");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,236 @@
+/*
+Copyright 2025 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use windows::Win32::System::ErrorReporting::{
+    WER_FILE, WER_SUBMIT_OUTOFPROCESS, WER_SUBMIT_QUEUE, WER_SUBMIT_RESULT, WerConsentNotAsked,
+    WerFileTypeMinidump, WerFileTypeOther, WerReportAddFile, WerReportCloseHandle, WerReportCreate,
+    WerReportNonCritical, WerReportSetParameter, WerReportSubmit,
+};
+use windows::core::HSTRING;
+
+use super::sink::write_report;
+use super::{CoreDumpFormat, CrashDumpInfo, CrashDumpReason, CrashDumpSink, CrashReport};
+use crate::{Result, new_error};
+
+/// The event type of the reports queued for guest crashes
+const WER_EVENT_TYPE: &str = "HyperlightGuestCrash";
+/// The longest value WER keeps for a report parameter
+const WER_MAX_PARAM_LEN: usize = 255;
+
+/// A [`CrashDumpSink`] for hosts whose crash pipeline is built on Windows
+/// Error Reporting.
+///
+/// Each dump is written as a minidump, with its [`CrashReport`] as JSON
+/// next to it, to the directory WER's `LocalDumps` writes process dumps
+/// to by default, `%LOCALAPPDATA%\CrashDumps`, or to the directory the
+/// sink was created with. Unless
+/// [`with_wer_report(false)`](Self::with_wer_report) is set, a
+/// `HyperlightGuestCrash` report carrying both files is then queued with
+/// WER, whose parameters are the guest binary, its build id, why the dump
+/// was taken, where the guest was, and the sandbox's labels. The report
+/// shows up in Reliability Monitor and is collected by WER's upload
+/// policies like any other queued report. The files are not anonymous,
+/// as the dump holds guest memory.
+#[derive(Clone, Debug)]
+pub struct WerCrashDumpSink {
+    dir: Option<PathBuf>,
+    wer_report: bool,
+}
+
+impl Default for WerCrashDumpSink {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            wer_report: true,
+        }
+    }
+}
+
+impl WerCrashDumpSink {
+    /// A sink that writes to `%LOCALAPPDATA%\CrashDumps` and queues a WER
+    /// report for each dump
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A sink that writes to `dir` instead
+    pub fn in_dir(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Some(dir.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Whether to queue a WER report for each dump, or only write the
+    /// files for a pipeline that collects them from the directory
+    pub fn with_wer_report(mut self, wer_report: bool) -> Self {
+        self.wer_report = wer_report;
+        self
+    }
+
+    /// The directory dumps are written to
+    fn dir(&self) -> PathBuf {
+        self.dir.clone().unwrap_or_else(|| {
+            std::env::var_os("LOCALAPPDATA")
+                .map(PathBuf::from)
+                .unwrap_or_else(std::env::temp_dir)
+                .join("CrashDumps")
+        })
+    }
+
+    /// The path of the file the dump described by `info` is written to
+    fn path(&self, info: &CrashDumpInfo) -> PathBuf {
+        self.dir().join(&info.file_name)
+    }
+}
+
+impl CrashDumpSink for WerCrashDumpSink {
+    fn open(&self, info: &CrashDumpInfo) -> Result<Box<dyn Write>> {
+        let dir = self.dir();
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| new_error!("Failed to create crash dump directory: {:?}", e))?;
+        let file = std::fs::File::create(dir.join(&info.file_name))
+            .map_err(|e| new_error!("Failed to create core dump file: {:?}", e))?;
+        Ok(Box::new(file))
+    }
+
+    fn report(&self, info: &CrashDumpInfo, report: &CrashReport) -> Result<()> {
+        let path = self.path(info);
+        write_report(&path, report)?;
+        if self.wer_report {
+            submit(&path, info.format, report)?;
+        }
+        Ok(())
+    }
+
+    fn finished(&self, info: &CrashDumpInfo, nbytes: usize) -> Result<()> {
+        if nbytes > 0 {
+            tracing::error!("Core dump file: {}", self.path(info).display());
+        }
+        Ok(())
+    }
+}
+
+/// The parameters of the WER report of `report`, by name
+fn parameters(report: &CrashReport) -> [(&'static str, String); 5] {
+    let binary = report
+        .binary_path
+        .as_deref()
+        .and_then(|path| Path::new(path).file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "<buffer>".to_string());
+    let reason = match &report.reason {
+        CrashDumpReason::GuestCrash(message) => format!("GuestCrash: {message}"),
+        CrashDumpReason::Requested => "Requested".to_string(),
+    };
+    let location = report
+        .location
+        .as_ref()
+        .map(ToString::to_string)
+        .unwrap_or_default();
+    [
+        ("GuestBinary", binary),
+        ("BuildId", report.build_id.clone().unwrap_or_default()),
+        ("Reason", reason),
+        ("Location", location),
+        ("Labels", report.labels.to_string()),
+    ]
+    .map(|(name, value)| (name, truncate(value)))
+}
+
+/// `value` cut to the longest value WER keeps, on a character boundary
+fn truncate(mut value: String) -> String {
+    if value.len() > WER_MAX_PARAM_LEN {
+        let mut end = WER_MAX_PARAM_LEN;
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        value.truncate(end);
+    }
+    value
+}
+
+/// Queue a WER report of the crash in `report`, with the dump at
+/// `dump_path` and its JSON report attached
+fn submit(dump_path: &Path, format: CoreDumpFormat, report: &CrashReport) -> Result<()> {
+    let dump_type = match format {
+        CoreDumpFormat::Minidump => WerFileTypeMinidump,
+        CoreDumpFormat::Elf => WerFileTypeOther,
+    };
+    let files = [
+        (dump_path.to_path_buf(), dump_type),
+        (dump_path.with_extension("json"), WerFileTypeOther),
+    ];
+
+    // SAFETY: the strings outlive the calls, and the handle is closed once
+    // the report has been queued or has failed
+    unsafe {
+        let handle = WerReportCreate(&HSTRING::from(WER_EVENT_TYPE), WerReportNonCritical, None)
+            .map_err(|e| new_error!("Failed to create WER report: {:?}", e))?;
+        let queued = (|| {
+            for (id, (name, value)) in parameters(report).iter().enumerate() {
+                WerReportSetParameter(
+                    handle,
+                    id as u32,
+                    &HSTRING::from(*name),
+                    &HSTRING::from(value.as_str()),
+                )?;
+            }
+            for (path, file_type) in &files {
+                let path = HSTRING::from(path.to_string_lossy().as_ref());
+                WerReportAddFile(handle, &path, *file_type, WER_FILE(0))?;
+            }
+            let mut result = WER_SUBMIT_RESULT::default();
+            WerReportSubmit(
+                handle,
+                WerConsentNotAsked,
+                WER_SUBMIT_QUEUE | WER_SUBMIT_OUTOFPROCESS,
+                Some(&mut result),
+            )
+        })();
+        let _ = WerReportCloseHandle(handle);
+        queued.map_err(|e| new_error!("Failed to queue WER report: {:?}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sandbox::SandboxLabels;
+
+    #[test]
+    fn wer_parameters_describe_the_crash() {
+        let report = CrashReport {
+            timestamp: String::new(),
+            reason: CrashDumpReason::GuestCrash("x".repeat(300)),
+            location: None,
+            labels: SandboxLabels::default(),
+            binary_path: Some(r"C:\guests\simpleguest".to_string()),
+            build_id: Some("0badc0de".to_string()),
+            registers: Vec::new(),
+            regions: Vec::new(),
+            recent_outcalls: Vec::new(),
+        };
+        let parameters = parameters(&report);
+        assert_eq!(parameters[0], ("GuestBinary", "simpleguest".to_string()));
+        assert_eq!(parameters[1], ("BuildId", "0badc0de".to_string()));
+        assert_eq!(parameters[2].1.len(), WER_MAX_PARAM_LEN);
+        assert!(parameters[2].1.starts_with("GuestCrash: x"));
+    }
+}
@@ -0,0 +1,198 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt;
+
+#[cfg(target_arch = "x86_64")]
+use iced_x86::{Decoder, DecoderOptions, Formatter, IntelFormatter, OpKind, Register};
+
+#[cfg(target_arch = "x86_64")]
+use crate::hypervisor::regs::CommonRegisters;
+
+/// The longest an x86 instruction can be
+#[cfg(target_arch = "x86_64")]
+pub(crate) const MAX_INSTRUCTION_LEN: usize = 15;
+
+/// The guest instruction that made a memory access the host rejected,
+/// decoded from the guest's memory at its instruction pointer, with the
+/// values of the registers it uses when it faulted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FaultingInstruction {
+    rip: u64,
+    bytes: Vec<u8>,
+    text: String,
+    registers: Vec<(String, u64)>,
+}
+
+impl FaultingInstruction {
+    /// The address of the instruction
+    pub fn rip(&self) -> u64 {
+        self.rip
+    }
+
+    /// The encoded instruction
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The instruction in Intel syntax, such as `mov [rax+8],rcx`
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The general purpose registers the instruction uses as operands or
+    /// to address memory, and their values when it faulted
+    pub fn registers(&self) -> &[(String, u64)] {
+        &self.registers
+    }
+
+    /// Decode the instruction at the start of `code`, which was read from
+    /// the guest's memory at `regs.rip`
+    #[cfg(target_arch = "x86_64")]
+    pub(crate) fn decode(code: &[u8], regs: &CommonRegisters) -> Option<Self> {
+        let instruction = Decoder::with_ip(64, code, regs.rip, DecoderOptions::NONE).decode();
+        if instruction.is_invalid() {
+            return None;
+        }
+        let mut text = String::new();
+        IntelFormatter::new().format(&instruction, &mut text);
+
+        let mut used = Vec::new();
+        for i in 0..instruction.op_count() {
+            match instruction.op_kind(i) {
+                OpKind::Register => used.push(instruction.op_register(i)),
+                OpKind::Memory => {
+                    used.extend([instruction.memory_base(), instruction.memory_index()])
+                }
+                OpKind::MemorySegRSI => used.push(Register::RSI),
+                OpKind::MemorySegRDI | OpKind::MemoryESRDI => used.push(Register::RDI),
+                _ => {}
+            }
+        }
+        let mut registers: Vec<(String, u64)> = Vec::new();
+        for register in used {
+            let register = register.full_register();
+            let name = format!("{register:?}").to_lowercase();
+            if let Some(value) = register_value(register, regs)
+                && !registers.iter().any(|(used, _)| *used == name)
+            {
+                registers.push((name, value));
+            }
+        }
+
+        Some(Self {
+            rip: regs.rip,
+            bytes: code[..instruction.len()].to_vec(),
+            text,
+            registers,
+        })
+    }
+}
+
+/// The value of the general purpose register `register` in `regs`
+#[cfg(target_arch = "x86_64")]
+fn register_value(register: Register, regs: &CommonRegisters) -> Option<u64> {
+    Some(match register {
+        Register::RAX => regs.rax,
+        Register::RBX => regs.rbx,
+        Register::RCX => regs.rcx,
+        Register::RDX => regs.rdx,
+        Register::RSI => regs.rsi,
+        Register::RDI => regs.rdi,
+        Register::RSP => regs.rsp,
+        Register::RBP => regs.rbp,
+        Register::R8 => regs.r8,
+        Register::R9 => regs.r9,
+        Register::R10 => regs.r10,
+        Register::R11 => regs.r11,
+        Register::R12 => regs.r12,
+        Register::R13 => regs.r13,
+        Register::R14 => regs.r14,
+        Register::R15 => regs.r15,
+        Register::RIP => regs.rip,
+        _ => return None,
+    })
+}
+
+impl fmt::Display for FaultingInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` at {:#x}", self.text, self.rip)?;
+        for (i, (name, value)) in self.registers.iter().enumerate() {
+            let separator = if i == 0 { " with " } else { ", " };
+            write!(f, "{separator}{name}={value:#x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Describes the instruction that faulted, if it is known, for the end of
+/// an error message
+pub(crate) fn fault_suffix(instruction: &Option<FaultingInstruction>) -> String {
+    instruction
+        .as_ref()
+        .map(|instruction| format!(" (faulting instruction: {instruction})"))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+#[cfg(target_arch = "x86_64")]
+mod tests {
+    use super::{FaultingInstruction, fault_suffix};
+    use crate::hypervisor::regs::CommonRegisters;
+
+    #[test]
+    fn decodes_the_instruction_and_its_registers() {
+        let regs = CommonRegisters {
+            rip: 0x1000,
+            rax: 0xdead_0000,
+            rcx: 0x2a,
+            ..Default::default()
+        };
+        // mov [rax+8],rcx followed by an unrelated instruction
+        let code = [0x48, 0x89, 0x48, 0x08, 0x90];
+        let instruction = FaultingInstruction::decode(&code, &regs).unwrap();
+        assert_eq!(instruction.bytes(), &code[..4]);
+        assert_eq!(instruction.text(), "mov [rax+8],rcx");
+        assert_eq!(
+            instruction.registers(),
+            [("rax".to_string(), 0xdead_0000), ("rcx".to_string(), 0x2a)]
+        );
+        assert_eq!(
+            fault_suffix(&Some(instruction)),
+            " (faulting instruction: `mov [rax+8],rcx` at 0x1000 with rax=0xdead0000, rcx=0x2a)"
+        );
+    }
+
+    #[test]
+    fn sub_registers_are_shown_as_their_full_register() {
+        let regs = CommonRegisters {
+            rip: 0x2000,
+            rdx: 0xffff_ffff_0000_0010,
+            ..Default::default()
+        };
+        // mov eax,[rdx]
+        let instruction = FaultingInstruction::decode(&[0x8b, 0x02], &regs).unwrap();
+        assert_eq!(
+            instruction.registers(),
+            [
+                ("rax".to_string(), 0),
+                ("rdx".to_string(), 0xffff_ffff_0000_0010)
+            ]
+        );
+        assert!(FaultingInstruction::decode(&[0x0f], &regs).is_none());
+        assert_eq!(fault_suffix(&None), "");
+    }
+}
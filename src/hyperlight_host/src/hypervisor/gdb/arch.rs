@@ -16,7 +16,7 @@ limitations under the License.
 
 //! This file contains architecture specific code for the x86_64
 
-use super::{DebugError, DebuggableVm, VcpuStopReason};
+use super::{DebugError, DebuggableVm, VcpuStopReason, WatchpointKind};
 use crate::hypervisor::regs::CommonRegisters;
 use crate::hypervisor::virtual_machine::RegisterError;
 
@@ -27,6 +27,8 @@ pub enum VcpuStopReasonError {
     GetRegs(#[from] RegisterError),
     #[error("Failed to remove hardware breakpoint: {0}")]
     RemoveHwBreakpoint(#[from] DebugError),
+    #[error("Failed to read hardware breakpoints: {0}")]
+    ReadHwBreakpoints(DebugError),
 }
 
 // Described in Table 6-1. Exceptions and Interrupts at Page 6-13 Vol. 1
@@ -58,6 +60,168 @@ pub(crate) const DR6_HW_BP_FLAGS_POS: usize = 0;
 /// Bit mask of HW breakpoints status in DR6 debug register
 pub(crate) const DR6_HW_BP_FLAGS_MASK: u64 = 0x0F << DR6_HW_BP_FLAGS_POS;
 
+/// Check page 19-5 Vol. 3B of Intel 64 and IA-32
+/// Architectures Software Developer's Manual
+/// Bit position of the R/W0 field in DR7, followed by LEN0, then the
+/// fields of the other slots, four bits per slot
+pub(crate) const DR7_SLOT_CONFIG_POS: usize = 16;
+/// Bit mask of the R/W and LEN fields of a slot in DR7
+pub(crate) const DR7_SLOT_CONFIG_MASK: u64 = 0x0F;
+
+/// The hardware breakpoint slots DR0–DR3, and the DR7 bits that enable
+/// them and set what they trigger on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct HwBreakpointSlots {
+    pub(crate) addrs: [u64; MAX_NO_OF_HW_BP],
+    pub(crate) dr7: u64,
+}
+
+impl HwBreakpointSlots {
+    /// The R/W and LEN fields of slot `i`
+    fn config(&self, i: usize) -> u64 {
+        (self.dr7 >> (DR7_SLOT_CONFIG_POS + i * 4)) & DR7_SLOT_CONFIG_MASK
+    }
+
+    fn is_enabled(&self, i: usize) -> bool {
+        self.dr7 & (1 << (i * 2)) != 0
+    }
+
+    /// The enabled slot at `addr` triggering on `config`
+    fn find(&self, addr: u64, config: u64) -> Option<usize> {
+        (0..MAX_NO_OF_HW_BP)
+            .find(|&i| self.is_enabled(i) && self.addrs[i] == addr && self.config(i) == config)
+    }
+
+    fn free_slots(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..MAX_NO_OF_HW_BP).filter(|&i| !self.is_enabled(i))
+    }
+
+    fn enable(&mut self, i: usize, addr: u64, config: u64) {
+        self.addrs[i] = addr;
+        let shift = DR7_SLOT_CONFIG_POS + i * 4;
+        self.dr7 = (self.dr7 & !(DR7_SLOT_CONFIG_MASK << shift)) | config << shift;
+        // Enable LOCAL bit
+        self.dr7 |= 1 << (i * 2);
+    }
+
+    fn disable(&mut self, i: usize) {
+        self.addrs[i] = 0;
+        self.dr7 &= !(DR7_SLOT_CONFIG_MASK << (DR7_SLOT_CONFIG_POS + i * 4));
+        // Disable LOCAL bit
+        self.dr7 &= !(1 << (i * 2));
+    }
+
+    /// Add an instruction breakpoint at `addr`, unless there already is one
+    pub(crate) fn add_breakpoint(&mut self, addr: u64) -> Result<(), DebugError> {
+        if self.find(addr, 0).is_some() {
+            return Ok(());
+        }
+        let i = self
+            .free_slots()
+            .next()
+            .ok_or(DebugError::TooManyHwBreakpoints(MAX_NO_OF_HW_BP))?;
+        self.enable(i, addr, 0);
+        Ok(())
+    }
+
+    /// Remove the instruction breakpoint at `addr`
+    pub(crate) fn remove_breakpoint(&mut self, addr: u64) -> Result<(), DebugError> {
+        let i = self
+            .find(addr, 0)
+            .ok_or(DebugError::HwBreakpointNotFound(addr))?;
+        self.disable(i);
+        Ok(())
+    }
+
+    /// Add a watchpoint on the `len` bytes at `addr`, using as many slots
+    /// as it takes to cover them with naturally aligned ranges
+    pub(crate) fn add_watchpoint(
+        &mut self,
+        addr: u64,
+        len: u64,
+        kind: WatchpointKind,
+    ) -> Result<(), DebugError> {
+        let ranges = watchpoint_ranges(addr, len)?;
+        if ranges
+            .iter()
+            .all(|&(addr, len)| self.find(addr, watchpoint_config(len, kind)).is_some())
+        {
+            return Ok(());
+        }
+        let slots: Vec<usize> = self.free_slots().take(ranges.len()).collect();
+        if slots.len() < ranges.len() {
+            return Err(DebugError::TooManyHwBreakpoints(MAX_NO_OF_HW_BP));
+        }
+        for (i, (addr, len)) in slots.into_iter().zip(ranges) {
+            self.enable(i, addr, watchpoint_config(len, kind));
+        }
+        Ok(())
+    }
+
+    /// Remove the watchpoint on the `len` bytes at `addr`
+    pub(crate) fn remove_watchpoint(
+        &mut self,
+        addr: u64,
+        len: u64,
+        kind: WatchpointKind,
+    ) -> Result<(), DebugError> {
+        let slots = watchpoint_ranges(addr, len)?
+            .into_iter()
+            .map(|(addr, len)| self.find(addr, watchpoint_config(len, kind)))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(DebugError::HwWatchpointNotFound(addr))?;
+        slots.into_iter().for_each(|i| self.disable(i));
+        Ok(())
+    }
+
+    /// The address of the watchpoint slot that triggered the debug
+    /// exception with the status `dr6`, if one did
+    pub(crate) fn hit_watchpoint(&self, dr6: u64) -> Option<u64> {
+        (0..MAX_NO_OF_HW_BP)
+            .find(|&i| dr6 & (1 << (DR6_HW_BP_FLAGS_POS + i)) != 0 && self.config(i) != 0)
+            .map(|i| self.addrs[i])
+    }
+}
+
+/// The R/W and LEN fields of a slot watching `len` bytes, which is 1, 2, 4
+/// or 8, for accesses of `kind`. x86 cannot break on reads alone, so read
+/// watchpoints also trigger on writes.
+fn watchpoint_config(len: u64, kind: WatchpointKind) -> u64 {
+    let rw = match kind {
+        WatchpointKind::Write => 0b01,
+        WatchpointKind::Read | WatchpointKind::ReadWrite => 0b11,
+    };
+    let len = match len {
+        1 => 0b00,
+        2 => 0b01,
+        8 => 0b10,
+        _ => 0b11,
+    };
+    len << 2 | rw
+}
+
+/// Split the `len` bytes at `addr` into the naturally aligned ranges of
+/// at most 8 bytes that watchpoint slots can cover
+fn watchpoint_ranges(mut addr: u64, len: u64) -> Result<Vec<(u64, u64)>, DebugError> {
+    let end = addr
+        .checked_add(len)
+        .filter(|_| len != 0)
+        .ok_or(DebugError::InvalidWatchpoint(addr, len))?;
+    let mut ranges = Vec::new();
+    while addr < end {
+        let len = [8, 4, 2]
+            .into_iter()
+            .find(|&size| addr % size == 0 && addr + size <= end)
+            .unwrap_or(1);
+        ranges.push((addr, len));
+        addr += len;
+        if ranges.len() > MAX_NO_OF_HW_BP {
+            return Err(DebugError::TooManyHwBreakpoints(MAX_NO_OF_HW_BP));
+        }
+    }
+    Ok(ranges)
+}
+
 /// Determine the reason the vCPU stopped
 /// This is done by checking the DR6 register and the exception id
 pub(crate) fn vcpu_stop_reason(
@@ -81,6 +245,12 @@ pub(crate) fn vcpu_stop_reason(
         // Check page 19-4 Vol. 3B of Intel 64 and IA-32
         // Architectures Software Developer's Manual
         if DR6_HW_BP_FLAGS_MASK & dr6 != 0 {
+            let slots = vm
+                .hw_breakpoint_slots()
+                .map_err(VcpuStopReasonError::ReadHwBreakpoints)?;
+            if let Some(addr) = slots.hit_watchpoint(dr6) {
+                return Ok(VcpuStopReason::HwWatchpoint(addr));
+            }
             if rip == entrypoint {
                 vm.remove_hw_breakpoint(entrypoint)?;
                 return Ok(VcpuStopReason::EntryPointBp);
@@ -109,3 +279,60 @@ pub(crate) fn vcpu_stop_reason(
 
     Ok(VcpuStopReason::Unknown)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{HwBreakpointSlots, watchpoint_ranges};
+    use crate::hypervisor::gdb::{DebugError, WatchpointKind};
+
+    #[test]
+    fn watchpoints_are_split_into_aligned_ranges() {
+        assert_eq!(watchpoint_ranges(0x1000, 8).unwrap(), [(0x1000, 8)]);
+        assert_eq!(
+            watchpoint_ranges(0x1001, 7).unwrap(),
+            [(0x1001, 1), (0x1002, 2), (0x1004, 4)]
+        );
+        assert!(matches!(
+            watchpoint_ranges(0x1000, 0),
+            Err(DebugError::InvalidWatchpoint(0x1000, 0))
+        ));
+        assert!(matches!(
+            watchpoint_ranges(0x1000, 40),
+            Err(DebugError::TooManyHwBreakpoints(_))
+        ));
+    }
+
+    #[test]
+    fn breakpoints_and_watchpoints_share_slots() {
+        let mut slots = HwBreakpointSlots::default();
+        slots.add_breakpoint(0x2000).unwrap();
+        slots
+            .add_watchpoint(0x2000, 4, WatchpointKind::Write)
+            .unwrap();
+        // Slot 1 is a 4 byte write watchpoint
+        assert_eq!(slots.addrs, [0x2000, 0x2000, 0, 0]);
+        assert_eq!(slots.dr7, 0b0101 | 0b1101 << 20);
+
+        // Adding the same watchpoint again is a no-op
+        slots
+            .add_watchpoint(0x2000, 4, WatchpointKind::Write)
+            .unwrap();
+        assert!(matches!(
+            slots.add_watchpoint(0x3001, 4, WatchpointKind::ReadWrite),
+            Err(DebugError::TooManyHwBreakpoints(_))
+        ));
+
+        assert_eq!(slots.hit_watchpoint(0b0001), None);
+        assert_eq!(slots.hit_watchpoint(0b0010), Some(0x2000));
+
+        slots.remove_breakpoint(0x2000).unwrap();
+        assert!(matches!(
+            slots.remove_watchpoint(0x2000, 4, WatchpointKind::ReadWrite),
+            Err(DebugError::HwWatchpointNotFound(0x2000))
+        ));
+        slots
+            .remove_watchpoint(0x2000, 4, WatchpointKind::Write)
+            .unwrap();
+        assert_eq!(slots, HwBreakpointSlots::default());
+    }
+}
@@ -0,0 +1,715 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A Debug Adapter Protocol (DAP) server, so that editors such as VS Code
+//! can attach to a guest directly.
+//!
+//! The server drives the vCPU through the same [`DebugMsg`] /
+//! [`DebugResponse`] channel as the GDB stub. Guests have no source-level
+//! debug information on the host side, so debugging is at the instruction
+//! level: breakpoints are set with `setInstructionBreakpoints`, stepping
+//! moves by one instruction, and the only variables are the registers.
+
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::Arc;
+use std::thread;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use crossbeam_channel::{Receiver, Sender, select};
+use serde_json::{Value, json};
+
+use super::{
+    DebugCommChannel, DebugMsg, DebugResponse, DebugStream, GdbTargetError, VcpuStopReason,
+};
+use crate::hypervisor::InterruptHandle;
+use crate::hypervisor::regs::CommonRegisters;
+
+/// The id of the only thread reported to the client: the sandbox's vCPU
+const THREAD_ID: u64 = 1;
+/// The variables reference of the registers scope
+const REGISTERS_REF: u64 = 1;
+/// The maximum number of frames reported in a stack trace
+const MAX_STACK_FRAMES: usize = 64;
+
+/// Why a request from the client could not be carried out
+enum RequestError {
+    /// The request failed, and the client is told why
+    Failed(String),
+    /// The connection to the hypervisor was lost, which ends the session
+    Fatal(GdbTargetError),
+}
+
+impl From<GdbTargetError> for RequestError {
+    fn from(err: GdbTargetError) -> Self {
+        Self::Fatal(err)
+    }
+}
+
+/// A debugging session with a single DAP client
+struct DapSession<W: Write> {
+    /// Where messages to the client are written
+    writer: W,
+    /// The sequence number of the last message sent to the client
+    seq: u64,
+    /// Hypervisor communication channels
+    hyp_conn: DebugCommChannel<DebugMsg, DebugResponse>,
+    /// Interrupt handle for the vCPU thread
+    interrupt_handle: Option<Arc<dyn InterruptHandle>>,
+    /// The addresses of the instruction breakpoints set by the client
+    breakpoints: BTreeSet<u64>,
+    /// Whether the vCPU is running, as opposed to stopped and waiting for
+    /// requests
+    running: bool,
+    /// A stop to report once the response to the current request is sent
+    pending_stop: Option<VcpuStopReason>,
+    /// Why the vCPU was stopped when the client connected, which is
+    /// reported once the client is configured
+    initial_stop: Option<VcpuStopReason>,
+}
+
+impl<W: Write> DapSession<W> {
+    fn new(writer: W, hyp_conn: DebugCommChannel<DebugMsg, DebugResponse>) -> Self {
+        Self {
+            writer,
+            seq: 0,
+            hyp_conn,
+            interrupt_handle: None,
+            breakpoints: BTreeSet::new(),
+            running: false,
+            pending_stop: None,
+            initial_stop: None,
+        }
+    }
+
+    /// Serve requests from the client until it disconnects or the guest
+    /// finishes executing
+    fn run(mut self, requests: Receiver<Value>) -> Result<(), GdbTargetError> {
+        let vcpu = self.hyp_conn.rx.clone();
+        loop {
+            select! {
+                recv(requests) -> request => {
+                    let Ok(request) = request else {
+                        tracing::info!("DAP client disconnected");
+                        return self.detach();
+                    };
+                    if self.handle(&request)? {
+                        return Ok(());
+                    }
+                }
+                recv(vcpu) -> msg => match msg {
+                    Ok(DebugResponse::VcpuStopped(reason)) => {
+                        tracing::debug!("VcpuStopped with reason {:?}", reason);
+                        self.running = false;
+                        self.stopped(reason)?;
+                    }
+                    Ok(msg) => {
+                        tracing::error!("Unexpected message received {:?}", msg);
+                    }
+                    Err(_) => {
+                        tracing::info!("Guest finalized execution and disconnected");
+                        self.event("terminated", Value::Null)?;
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Handle a single request. Returns whether the session is over.
+    fn handle(&mut self, request: &Value) -> Result<bool, GdbTargetError> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let args = &request["arguments"];
+        tracing::debug!("DAP request {}", command);
+
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsReadMemoryRequest": true,
+                "supportsWriteMemoryRequest": true,
+                "supportsSteppingGranularity": true,
+            })),
+            "attach" | "launch" | "setExceptionBreakpoints" => Ok(Value::Null),
+            "configurationDone" => {
+                // The vCPU has been stopped since the client connected
+                self.pending_stop = self.initial_stop.take();
+                Ok(Value::Null)
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "vCPU" }] })),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Ok(json!({
+                "scopes": [{
+                    "name": "Registers",
+                    "presentationHint": "registers",
+                    "variablesReference": REGISTERS_REF,
+                    "expensive": false,
+                }]
+            })),
+            "variables" => self.variables(args),
+            "setBreakpoints" => Ok(unsupported_source_breakpoints(args)),
+            "setInstructionBreakpoints" => self.set_instruction_breakpoints(args),
+            "continue" => self
+                .resume()
+                .map(|()| json!({ "allThreadsContinued": true })),
+            "next" | "stepIn" => self.step().map(|reason| {
+                self.pending_stop = Some(reason);
+                Value::Null
+            }),
+            "pause" => self.pause().map(|()| Value::Null),
+            "readMemory" => self.read_memory_request(args),
+            "writeMemory" => self.write_memory_request(args),
+            "disconnect" => {
+                self.respond(request, Ok(Value::Null))?;
+                self.detach()?;
+                return Ok(true);
+            }
+            _ => Err(RequestError::Failed(format!(
+                "unsupported request '{command}'"
+            ))),
+        };
+
+        let result = match result {
+            Err(RequestError::Fatal(e)) => return Err(e),
+            Err(RequestError::Failed(message)) => Err(message),
+            Ok(body) => Ok(body),
+        };
+        self.respond(request, result)?;
+
+        if command == "initialize" {
+            self.event("initialized", Value::Null)?;
+        }
+        if let Some(reason) = self.pending_stop.take() {
+            self.stopped(reason)?;
+        }
+        Ok(false)
+    }
+
+    fn stack_trace(&mut self) -> Result<Value, RequestError> {
+        let regs = self.registers()?;
+        let mut pcs = vec![regs.rip];
+        let mut rbp = regs.rbp;
+
+        // Follow the chain of frame pointers: each frame saves the caller's
+        // rbp at [rbp] and the return address right above it
+        while rbp != 0 && pcs.len() < MAX_STACK_FRAMES {
+            let Ok(frame) = self.read_memory(rbp, 16) else {
+                break;
+            };
+            let (saved_rbp, return_address) = frame.split_at(8);
+            let saved_rbp = u64::from_le_bytes(saved_rbp.try_into().unwrap_or_default());
+            let return_address = u64::from_le_bytes(return_address.try_into().unwrap_or_default());
+            if return_address == 0 {
+                break;
+            }
+            pcs.push(return_address);
+            if saved_rbp <= rbp {
+                break;
+            }
+            rbp = saved_rbp;
+        }
+
+        let frames: Vec<Value> = pcs
+            .iter()
+            .enumerate()
+            .map(|(id, pc)| {
+                json!({
+                    "id": id,
+                    "name": format!("{pc:#x}"),
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": format!("{pc:#x}"),
+                })
+            })
+            .collect();
+        Ok(json!({ "totalFrames": frames.len(), "stackFrames": frames }))
+    }
+
+    fn variables(&mut self, args: &Value) -> Result<Value, RequestError> {
+        if args["variablesReference"].as_u64() != Some(REGISTERS_REF) {
+            return Ok(json!({ "variables": [] }));
+        }
+
+        let regs = self.registers()?;
+        let variables: Vec<Value> = [
+            ("rax", regs.rax),
+            ("rbx", regs.rbx),
+            ("rcx", regs.rcx),
+            ("rdx", regs.rdx),
+            ("rsi", regs.rsi),
+            ("rdi", regs.rdi),
+            ("rbp", regs.rbp),
+            ("rsp", regs.rsp),
+            ("r8", regs.r8),
+            ("r9", regs.r9),
+            ("r10", regs.r10),
+            ("r11", regs.r11),
+            ("r12", regs.r12),
+            ("r13", regs.r13),
+            ("r14", regs.r14),
+            ("r15", regs.r15),
+            ("rip", regs.rip),
+            ("rflags", regs.rflags),
+        ]
+        .into_iter()
+        .map(|(name, value)| {
+            json!({
+                "name": name,
+                "value": format!("{value:#018x}"),
+                "memoryReference": format!("{value:#x}"),
+                "variablesReference": 0,
+            })
+        })
+        .collect();
+        Ok(json!({ "variables": variables }))
+    }
+
+    /// Replace the instruction breakpoints with the ones in `args`
+    fn set_instruction_breakpoints(&mut self, args: &Value) -> Result<Value, RequestError> {
+        let requested: Vec<Option<u64>> = args["breakpoints"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .map(|bp| {
+                let address = bp["instructionReference"]
+                    .as_str()
+                    .and_then(parse_address)?;
+                address.checked_add_signed(bp["offset"].as_i64().unwrap_or(0))
+            })
+            .collect();
+        let wanted: BTreeSet<u64> = requested.iter().flatten().copied().collect();
+
+        let removed: Vec<u64> = self.breakpoints.difference(&wanted).copied().collect();
+        for addr in removed {
+            match self.command(DebugMsg::RemoveSwBreakpoint(addr))? {
+                DebugResponse::RemoveSwBreakpoint(_) => (),
+                msg => return Err(unexpected(msg)),
+            }
+        }
+        self.breakpoints.retain(|addr| wanted.contains(addr));
+        for &addr in &wanted {
+            if !self.breakpoints.contains(&addr) && self.add_breakpoint(addr)? {
+                self.breakpoints.insert(addr);
+            }
+        }
+
+        let breakpoints: Vec<Value> = requested
+            .into_iter()
+            .map(|addr| match addr {
+                Some(addr) if self.breakpoints.contains(&addr) => json!({
+                    "verified": true,
+                    "instructionReference": format!("{addr:#x}"),
+                }),
+                _ => json!({ "verified": false, "message": "cannot set a breakpoint here" }),
+            })
+            .collect();
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    fn add_breakpoint(&mut self, addr: u64) -> Result<bool, RequestError> {
+        match self.command(DebugMsg::AddSwBreakpoint(addr))? {
+            DebugResponse::AddSwBreakpoint(added) => Ok(added),
+            msg => Err(unexpected(msg)),
+        }
+    }
+
+    /// Resume the vCPU until it stops again
+    fn resume(&mut self) -> Result<(), RequestError> {
+        // The breakpoint the vCPU is stopped at would stop it again straight
+        // away, so step past it first
+        let rip = self.registers()?.rip;
+        if self.breakpoints.contains(&rip) {
+            self.step()?;
+        }
+        match self.command(DebugMsg::Continue)? {
+            DebugResponse::Continue => {
+                self.running = true;
+                Ok(())
+            }
+            msg => Err(unexpected(msg)),
+        }
+    }
+
+    /// Step the vCPU by one instruction, and wait for it to stop again
+    fn step(&mut self) -> Result<VcpuStopReason, RequestError> {
+        let rip = self.registers()?.rip;
+        let at_breakpoint = self.breakpoints.contains(&rip);
+        if at_breakpoint {
+            match self.command(DebugMsg::RemoveSwBreakpoint(rip))? {
+                DebugResponse::RemoveSwBreakpoint(_) => (),
+                msg => return Err(unexpected(msg)),
+            }
+        }
+
+        match self.command(DebugMsg::Step)? {
+            DebugResponse::Step => (),
+            msg => return Err(unexpected(msg)),
+        }
+        let reason = match self.hyp_conn.recv()? {
+            DebugResponse::VcpuStopped(reason) => reason,
+            msg => return Err(unexpected(msg)),
+        };
+
+        if at_breakpoint && !self.add_breakpoint(rip)? {
+            self.breakpoints.remove(&rip);
+        }
+        Ok(reason)
+    }
+
+    fn pause(&mut self) -> Result<(), RequestError> {
+        let interrupted = self
+            .interrupt_handle
+            .as_ref()
+            .is_some_and(|handle| handle.kill_from_debugger());
+        if interrupted {
+            Ok(())
+        } else {
+            Err(RequestError::Failed(
+                "cannot interrupt the vCPU".to_string(),
+            ))
+        }
+    }
+
+    fn read_memory_request(&mut self, args: &Value) -> Result<Value, RequestError> {
+        let addr = memory_address(args)?;
+        let count = args["count"].as_u64().unwrap_or(0) as usize;
+        let data = self.read_memory(addr, count)?;
+        Ok(json!({ "address": format!("{addr:#x}"), "data": BASE64.encode(data) }))
+    }
+
+    fn write_memory_request(&mut self, args: &Value) -> Result<Value, RequestError> {
+        let addr = memory_address(args)?;
+        let data = BASE64
+            .decode(args["data"].as_str().unwrap_or_default())
+            .map_err(|e| RequestError::Failed(format!("invalid data: {e}")))?;
+        let len = data.len();
+        match self.command(DebugMsg::WriteAddr(addr, data))? {
+            DebugResponse::WriteAddr => Ok(json!({ "bytesWritten": len })),
+            msg => Err(unexpected(msg)),
+        }
+    }
+
+    fn read_memory(&mut self, addr: u64, len: usize) -> Result<Vec<u8>, RequestError> {
+        match self.command(DebugMsg::ReadAddr(addr, len))? {
+            DebugResponse::ReadAddr(data) => Ok(data),
+            msg => Err(unexpected(msg)),
+        }
+    }
+
+    fn registers(&mut self) -> Result<CommonRegisters, RequestError> {
+        match self.command(DebugMsg::ReadRegisters)? {
+            DebugResponse::ReadRegisters(regs) => Ok(regs.0),
+            msg => Err(unexpected(msg)),
+        }
+    }
+
+    /// Sends a command to the hypervisor and waits for its response
+    fn command(&mut self, cmd: DebugMsg) -> Result<DebugResponse, RequestError> {
+        if self.running {
+            return Err(RequestError::Failed(
+                "the guest is running, pause it first".to_string(),
+            ));
+        }
+        self.hyp_conn.send(cmd)?;
+        match self.hyp_conn.recv()? {
+            DebugResponse::ErrorOccurred => Err(RequestError::Failed(
+                "the hypervisor failed to carry out the request".to_string(),
+            )),
+            DebugResponse::NotAllowed => Err(RequestError::Failed(
+                "not allowed after the guest has crashed".to_string(),
+            )),
+            msg => Ok(msg),
+        }
+    }
+
+    /// Stop debugging and let the guest run to completion
+    fn detach(&mut self) -> Result<(), GdbTargetError> {
+        if self.running {
+            // Requests are only processed while the vCPU is stopped
+            let _ = self.pause();
+            loop {
+                if let DebugResponse::VcpuStopped(_) = self.hyp_conn.recv()? {
+                    break;
+                }
+            }
+            self.running = false;
+        }
+        for addr in std::mem::take(&mut self.breakpoints) {
+            let _ = self.command(DebugMsg::RemoveSwBreakpoint(addr));
+        }
+        match self.command(DebugMsg::DisableDebug) {
+            Ok(DebugResponse::DisableDebug) => Ok(()),
+            Ok(msg) => {
+                tracing::error!("Unexpected message received: {:?}", msg);
+                Err(GdbTargetError::UnexpectedMessage)
+            }
+            Err(RequestError::Fatal(e)) => Err(e),
+            Err(RequestError::Failed(message)) => {
+                tracing::error!("Cannot disable debugging: {}", message);
+                Err(GdbTargetError::UnexpectedError)
+            }
+        }
+    }
+
+    fn stopped(&mut self, reason: VcpuStopReason) -> Result<(), GdbTargetError> {
+        let reason = match reason {
+            VcpuStopReason::DoneStep => "step",
+            VcpuStopReason::EntryPointBp => "entry",
+            VcpuStopReason::SwBp | VcpuStopReason::HwBp => "instruction breakpoint",
+            VcpuStopReason::HwWatchpoint(_) => "data breakpoint",
+            VcpuStopReason::Interrupt => "pause",
+            VcpuStopReason::Crash => "exception",
+            VcpuStopReason::Unknown => {
+                tracing::warn!("Unknown stop reason received");
+                "breakpoint"
+            }
+        };
+        self.event(
+            "stopped",
+            json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+        )
+    }
+
+    fn respond(
+        &mut self,
+        request: &Value,
+        result: Result<Value, String>,
+    ) -> Result<(), GdbTargetError> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => (),
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = Value::String(message),
+        }
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> Result<(), GdbTargetError> {
+        let mut msg = json!({ "type": "event", "event": event });
+        if !body.is_null() {
+            msg["body"] = body;
+        }
+        self.send(msg)
+    }
+
+    fn send(&mut self, mut msg: Value) -> Result<(), GdbTargetError> {
+        self.seq += 1;
+        msg["seq"] = self.seq.into();
+        write_message(&mut self.writer, &msg)?;
+        Ok(())
+    }
+}
+
+/// Source breakpoints need debug information the host does not have, so
+/// they are all reported as unverified
+fn unsupported_source_breakpoints(args: &Value) -> Value {
+    let count = args["breakpoints"].as_array().map_or(0, Vec::len);
+    let breakpoint = json!({
+        "verified": false,
+        "message": "source breakpoints are not supported, use instruction breakpoints instead",
+    });
+    json!({ "breakpoints": vec![breakpoint; count] })
+}
+
+fn unexpected(msg: DebugResponse) -> RequestError {
+    tracing::error!("Unexpected message received: {:?}", msg);
+    RequestError::Fatal(GdbTargetError::UnexpectedMessage)
+}
+
+/// The address a `readMemory` or `writeMemory` request refers to
+fn memory_address(args: &Value) -> Result<u64, RequestError> {
+    args["memoryReference"]
+        .as_str()
+        .and_then(parse_address)
+        .and_then(|addr| addr.checked_add_signed(args["offset"].as_i64().unwrap_or(0)))
+        .ok_or_else(|| RequestError::Failed("invalid memory reference".to_string()))
+}
+
+/// Parses an address given as hexadecimal with a `0x` prefix, or as decimal
+fn parse_address(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Reads one message from the client, or `None` if it closed the connection
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            content_length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let len = content_length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn write_message(writer: &mut impl Write, msg: &Value) -> io::Result<()> {
+    let body = msg.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// Forwards the messages read from the client to `requests`, until the
+/// connection is closed
+fn read_requests(reader: impl Read, requests: Sender<Value>) {
+    let mut reader = BufReader::new(reader);
+    loop {
+        match read_message(&mut reader) {
+            Ok(Some(request)) => {
+                if requests.send(request).is_err() {
+                    return;
+                }
+            }
+            Ok(None) => return,
+            Err(e) => {
+                tracing::error!("Failed to read DAP message: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+/// Serves a DAP client until it disconnects or the guest finishes
+/// executing. The vCPU is stopped for `stop_reason` when this is called.
+pub(super) fn serve_dap(
+    conn: DebugStream,
+    hyp_conn: DebugCommChannel<DebugMsg, DebugResponse>,
+    interrupt_handle: Arc<dyn InterruptHandle>,
+    stop_reason: VcpuStopReason,
+) -> Result<(), GdbTargetError> {
+    let reader = conn.try_clone()?;
+    let mut session = DapSession::new(conn, hyp_conn);
+    session.interrupt_handle = Some(interrupt_handle);
+    session.initial_stop = Some(stop_reason);
+
+    let (requests_tx, requests_rx) = crossbeam_channel::unbounded();
+    thread::Builder::new()
+        .name("DAP reader".to_string())
+        .spawn(move || read_requests(reader, requests_tx))?;
+
+    session.run(requests_rx).inspect_err(|e| {
+        tracing::error!("fatal error encountered: {e:?}");
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(seq: u64, command: &str, arguments: Value) -> Value {
+        json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments })
+    }
+
+    /// Parses all the messages the session wrote to the client
+    fn sent_messages(written: &[u8]) -> Vec<Value> {
+        let mut reader = written;
+        std::iter::from_fn(|| read_message(&mut reader).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_dap_message_framing() {
+        let msg = request(1, "initialize", json!({ "adapterID": "hyperlight" }));
+        let mut written = Vec::new();
+        write_message(&mut written, &msg).unwrap();
+        write_message(&mut written, &msg).unwrap();
+
+        assert!(written.starts_with(b"Content-Length: "));
+        assert_eq!(sent_messages(&written), vec![msg.clone(), msg]);
+        assert!(read_message(&mut &b"\r\n{}"[..]).is_err());
+    }
+
+    #[test]
+    fn test_dap_session_requests() {
+        let (gdb_conn, hyp_conn) = DebugCommChannel::unbounded();
+        let mut session = DapSession::new(Vec::new(), hyp_conn);
+
+        assert!(
+            !session
+                .handle(&request(1, "initialize", json!({})))
+                .unwrap()
+        );
+
+        // Send the responses first to not be blocked by the session
+        gdb_conn
+            .send(DebugResponse::ReadAddr(vec![0xde, 0xad]))
+            .unwrap();
+        let args = json!({ "memoryReference": "0x1000", "offset": 2, "count": 2 });
+        session.handle(&request(2, "readMemory", args)).unwrap();
+        assert!(matches!(
+            gdb_conn.recv().unwrap(),
+            DebugMsg::ReadAddr(0x1002, 2)
+        ));
+
+        gdb_conn.send(DebugResponse::AddSwBreakpoint(true)).unwrap();
+        let args = json!({ "breakpoints": [{ "instructionReference": "0x2000" }, {}] });
+        session
+            .handle(&request(3, "setInstructionBreakpoints", args))
+            .unwrap();
+        assert!(matches!(
+            gdb_conn.recv().unwrap(),
+            DebugMsg::AddSwBreakpoint(0x2000)
+        ));
+
+        session
+            .handle(&request(4, "frobnicate", json!({})))
+            .unwrap();
+
+        let sent = sent_messages(&session.writer);
+        let seqs: Vec<_> = sent
+            .iter()
+            .map(|msg| msg["seq"].as_u64().unwrap())
+            .collect();
+        assert_eq!(seqs, vec![1, 2, 3, 4, 5]);
+
+        assert_eq!(sent[0]["command"], "initialize");
+        assert_eq!(sent[0]["body"]["supportsInstructionBreakpoints"], true);
+        assert_eq!(sent[1]["event"], "initialized");
+
+        assert_eq!(sent[2]["request_seq"], 2);
+        assert_eq!(sent[2]["body"]["address"], "0x1002");
+        assert_eq!(sent[2]["body"]["data"], "3q0=");
+
+        let breakpoints = &sent[3]["body"]["breakpoints"];
+        assert_eq!(breakpoints[0]["verified"], true);
+        assert_eq!(breakpoints[1]["verified"], false);
+
+        assert_eq!(sent[4]["success"], false);
+    }
+}
@@ -62,6 +62,14 @@ impl run_blocking::BlockingEventLoop for GdbBlockingEventLoop {
                         VcpuStopReason::EntryPointBp => BaseStopReason::HwBreak(()),
                         VcpuStopReason::SwBp => BaseStopReason::SwBreak(()),
                         VcpuStopReason::HwBp => BaseStopReason::HwBreak(()),
+                        VcpuStopReason::HwWatchpoint(addr) => {
+                            let (kind, addr) = target.hit_watchpoint(addr);
+                            BaseStopReason::Watch {
+                                tid: (),
+                                kind,
+                                addr,
+                            }
+                        }
                         // This is a consequence of the GDB client sending an interrupt signal
                         // to the target thread
                         VcpuStopReason::Interrupt => BaseStopReason::SignalWithThread {
@@ -15,14 +15,21 @@ limitations under the License.
 */
 
 pub(crate) mod arch;
+mod dap;
 mod event_loop;
 mod x86_64_target;
 
-use std::io::{self, ErrorKind};
-use std::net::TcpListener;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::thread;
 
+use arch::HwBreakpointSlots;
 use crossbeam_channel::{Receiver, Sender, TryRecvError};
 use event_loop::event_loop_thread;
 use gdbstub::conn::ConnectionExt;
@@ -40,6 +47,7 @@ use crate::mem::layout::BaseGpaRegion;
 use crate::mem::memory_region::MemoryRegion;
 use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::shared_mem::HostSharedMemory;
+use crate::sandbox::config::{DebugInfo, DebugProtocol};
 
 #[derive(Debug, Error)]
 pub enum GdbTargetError {
@@ -100,6 +108,35 @@ pub enum DebugMemoryAccessError {
 }
 
 impl DebugMemoryAccess {
+    /// Translates a range of the guest's virtual address space by walking
+    /// the guest's page tables
+    ///
+    /// # Arguments
+    /// * `gva` - Guest virtual address of the start of the range
+    /// * `len` - Length of the range in bytes
+    /// * `root_pt` - Guest physical address of the root page table
+    /// # Returns
+    /// * `Result<Vec<(u64, usize)>, DebugMemoryAccessError>` - The guest
+    ///   physical address and length of each run of the range, none of
+    ///   which cross a page boundary
+    pub(crate) fn translate(
+        &self,
+        gva: u64,
+        len: usize,
+        root_pt: u64,
+    ) -> std::result::Result<Vec<(u64, usize)>, DebugMemoryAccessError> {
+        let mut mgr = self
+            .dbg_mem_access_fn
+            .try_lock()
+            .map_err(|e| DebugMemoryAccessError::LockFailed(file!(), line!(), e.to_string()))?;
+
+        let runs = mgr.translate_gva_range(gva, len, root_pt).map_err(|e| {
+            tracing::debug!("Failed to translate {:#x}: {}", gva, e);
+            DebugMemoryAccessError::TranslateGuestAddress(gva)
+        })?;
+        Ok(runs.into_iter().map(|(gpa, len, _)| (gpa, len)).collect())
+    }
+
     /// Reads memory from the guest's address space with a maximum length of a PAGE_SIZE
     ///
     /// # Arguments
@@ -176,15 +213,29 @@ pub enum VcpuStopReason {
     /// the entry point code before the debugger is connected
     EntryPointBp,
     HwBp,
+    /// A hardware watchpoint was triggered by an access to the range
+    /// starting at the given address, which is one of the naturally
+    /// aligned ranges the watchpoint was split into
+    HwWatchpoint(u64),
     SwBp,
     Interrupt,
     Unknown,
 }
 
+/// The accesses a hardware watchpoint stops the vCPU on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchpointKind {
+    Write,
+    /// Reads, which on x86 also stops on writes
+    Read,
+    ReadWrite,
+}
+
 /// Enumerates the possible actions that a debugger can ask from a Hypervisor
 #[derive(Debug)]
 pub(crate) enum DebugMsg {
     AddHwBreakpoint(u64),
+    AddHwWatchpoint(u64, u64, WatchpointKind),
     AddSwBreakpoint(u64),
     Continue,
     DisableDebug,
@@ -192,6 +243,7 @@ pub(crate) enum DebugMsg {
     ReadAddr(u64, usize),
     ReadRegisters,
     RemoveHwBreakpoint(u64),
+    RemoveHwWatchpoint(u64, u64, WatchpointKind),
     RemoveSwBreakpoint(u64),
     Step,
     WriteAddr(u64, Vec<u8>),
@@ -202,6 +254,7 @@ pub(crate) enum DebugMsg {
 #[derive(Debug)]
 pub(crate) enum DebugResponse {
     AddHwBreakpoint(bool),
+    AddHwWatchpoint(bool),
     AddSwBreakpoint(bool),
     Continue,
     DisableDebug,
@@ -212,6 +265,7 @@ pub(crate) enum DebugResponse {
     ReadAddr(Vec<u8>),
     ReadRegisters(Box<(CommonRegisters, CommonFpu)>),
     RemoveHwBreakpoint(bool),
+    RemoveHwWatchpoint(bool),
     RemoveSwBreakpoint(bool),
     Step,
     VcpuStopped(VcpuStopReason),
@@ -224,37 +278,78 @@ pub(crate) enum DebugResponse {
 pub enum DebugError {
     #[error("Hardware breakpoint not found at address {0:#x}")]
     HwBreakpointNotFound(u64),
+    #[error("Hardware watchpoint not found at address {0:#x}")]
+    HwWatchpointNotFound(u64),
     #[error("Failed to enable/disable intercept: {enable}, {inner}")]
     Intercept {
         enable: bool,
         inner: HypervisorError,
     },
+    #[error("Cannot watch {1} bytes at address {0:#x}")]
+    InvalidWatchpoint(u64, u64),
     #[error("Register operation failed: {0}")]
     Register(#[from] RegisterError),
     #[error("Maximum hardware breakpoints ({0}) exceeded")]
     TooManyHwBreakpoints(usize),
-    #[error("Translation of guest virtual address failed: {0}")]
-    TranslateGva(u64),
 }
 
 /// Trait for VMs that support debugging capabilities.
 /// This extends the base VirtualMachine trait with GDB-specific functionality.
 pub(crate) trait DebuggableVm: VirtualMachine {
-    /// Translates a guest virtual address to a guest physical address
-    fn translate_gva(&self, gva: u64) -> std::result::Result<u64, DebugError>;
-
     /// Enable/disable debugging
     fn set_debug(&mut self, enable: bool) -> std::result::Result<(), DebugError>;
 
     /// Enable/disable single stepping
     fn set_single_step(&mut self, enable: bool) -> std::result::Result<(), DebugError>;
 
+    /// The hardware breakpoints and watchpoints the debugger has set
+    fn hw_breakpoint_slots(&self) -> std::result::Result<HwBreakpointSlots, DebugError>;
+
+    /// Set the hardware breakpoints and watchpoints of the debugger
+    fn set_hw_breakpoint_slots(
+        &mut self,
+        slots: &HwBreakpointSlots,
+    ) -> std::result::Result<(), DebugError>;
+
     /// Add a hardware breakpoint at the given address.
     /// Must be idempotent.
-    fn add_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError>;
+    fn add_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
+        let mut slots = self.hw_breakpoint_slots()?;
+        slots.add_breakpoint(addr)?;
+        self.set_hw_breakpoint_slots(&slots)
+    }
 
     /// Remove a hardware breakpoint at the given address
-    fn remove_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError>;
+    fn remove_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
+        let mut slots = self.hw_breakpoint_slots()?;
+        slots.remove_breakpoint(addr)?;
+        self.set_hw_breakpoint_slots(&slots)
+    }
+
+    /// Add a hardware watchpoint on the `len` bytes at the given address.
+    /// Must be idempotent.
+    fn add_hw_watchpoint(
+        &mut self,
+        addr: u64,
+        len: u64,
+        kind: WatchpointKind,
+    ) -> std::result::Result<(), DebugError> {
+        let mut slots = self.hw_breakpoint_slots()?;
+        slots.add_watchpoint(addr, len, kind)?;
+        self.set_hw_breakpoint_slots(&slots)
+    }
+
+    /// Remove a hardware watchpoint on the `len` bytes at the given address
+    fn remove_hw_watchpoint(
+        &mut self,
+        addr: u64,
+        len: u64,
+        kind: WatchpointKind,
+    ) -> std::result::Result<(), DebugError> {
+        let mut slots = self.hw_breakpoint_slots()?;
+        slots.remove_watchpoint(addr, len, kind)?;
+        self.set_hw_breakpoint_slots(&slots)
+    }
 }
 
 /// Debug communication channel that is used for sending a request type and
@@ -300,51 +395,242 @@ impl<T, U> DebugCommChannel<T, U> {
     }
 }
 
-/// Creates a thread that handles gdb protocol
-pub(crate) fn create_gdb_thread(
-    port: u16,
+/// Where a debug thread waits for a debugger to connect
+enum DebugListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl DebugListener {
+    /// Listen on the unix domain socket at `socket_path` if it is given, or
+    /// on the address and port in `debug_info` otherwise
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn bind(debug_info: &DebugInfo, socket_path: Option<&Path>) -> io::Result<Self> {
+        #[cfg(unix)]
+        if let Some(path) = socket_path {
+            // A socket left behind by an earlier process would make binding fail
+            if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+                std::fs::remove_file(path)?;
+            }
+            tracing::info!("Listening on {:?}", path);
+            return Ok(Self::Unix(UnixListener::bind(path)?));
+        }
+
+        let addr = SocketAddr::new(debug_info.address, debug_info.port);
+        tracing::info!("Listening on {:?}", addr);
+        Ok(Self::Tcp(TcpListener::bind(addr)?))
+    }
+
+    fn accept(&self) -> io::Result<DebugStream> {
+        match self {
+            Self::Tcp(listener) => Ok(DebugStream::Tcp(listener.accept()?.0)),
+            #[cfg(unix)]
+            Self::Unix(listener) => Ok(DebugStream::Unix(listener.accept()?.0)),
+        }
+    }
+}
+
+/// A connection from a debugger
+pub(crate) enum DebugStream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl DebugStream {
+    pub(crate) fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Self::Tcp(stream) => Ok(Self::Tcp(stream.try_clone()?)),
+            #[cfg(unix)]
+            Self::Unix(stream) => Ok(Self::Unix(stream.try_clone()?)),
+        }
+    }
+
+    fn into_gdb_connection(self) -> Box<dyn ConnectionExt<Error = io::Error>> {
+        match self {
+            Self::Tcp(stream) => Box::new(stream),
+            #[cfg(unix)]
+            Self::Unix(stream) => Box::new(stream),
+        }
+    }
+}
+
+impl Read for DebugStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => Read::read(stream, buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => Read::read(stream, buf),
+        }
+    }
+}
+
+impl Write for DebugStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Creates a thread that lets a debugger attach to the guest, speaking the
+/// protocol in `debug_info`
+pub(crate) fn create_debug_thread(
+    debug_info: &DebugInfo,
+    socket_path: Option<&Path>,
 ) -> Result<DebugCommChannel<DebugResponse, DebugMsg>, GdbTargetError> {
-    let (gdb_conn, hyp_conn) = DebugCommChannel::unbounded();
-    let socket = format!("localhost:{}", port);
+    let listener = DebugListener::bind(debug_info, socket_path)?;
+
+    match debug_info.protocol {
+        DebugProtocol::Gdb => spawn_debug_thread(
+            "GDB handler",
+            listener,
+            debug_info.wait_for_attach,
+            serve_gdb,
+        ),
+        DebugProtocol::Dap => spawn_debug_thread(
+            "DAP handler",
+            listener,
+            debug_info.wait_for_attach,
+            dap::serve_dap,
+        ),
+    }
+}
 
-    tracing::info!("Listening on {:?}", socket);
-    let listener = TcpListener::bind(socket)?;
+/// Serves a gdb client until it disconnects
+fn serve_gdb(
+    conn: DebugStream,
+    hyp_conn: DebugCommChannel<DebugMsg, DebugResponse>,
+    interrupt_handle: Arc<dyn InterruptHandle>,
+    _stop_reason: VcpuStopReason,
+) -> Result<(), GdbTargetError> {
+    let debugger = GdbStub::new(conn.into_gdb_connection());
 
-    tracing::info!("Starting GDB thread");
-    let _handle = thread::Builder::new()
-        .name("GDB handler".to_string())
-        .spawn(move || -> Result<(), GdbTargetError> {
-            tracing::info!("Waiting for GDB connection ... ");
-            let (conn, _) = listener.accept()?;
+    let mut target = HyperlightSandboxTarget::new(hyp_conn);
+    target.set_interrupt_handle(interrupt_handle);
+    event_loop_thread(debugger, &mut target);
 
-            let conn: Box<dyn ConnectionExt<Error = io::Error>> = Box::new(conn);
-            let debugger = GdbStub::new(conn);
+    Ok(())
+}
 
-            let mut target = HyperlightSandboxTarget::new(hyp_conn);
+/// Spawns a thread that waits for a debugger to connect over `listener`,
+/// then hands the connection to `serve` once the vCPU has stopped.
+///
+/// The vCPU stops at the entry point of the guest. If `wait_for_attach` is
+/// set, it waits there for the debugger to connect. Otherwise the guest runs
+/// without a debugger, and is interrupted when one connects.
+fn spawn_debug_thread<F>(
+    name: &str,
+    listener: DebugListener,
+    wait_for_attach: bool,
+    serve: F,
+) -> Result<DebugCommChannel<DebugResponse, DebugMsg>, GdbTargetError>
+where
+    F: FnOnce(
+            DebugStream,
+            DebugCommChannel<DebugMsg, DebugResponse>,
+            Arc<dyn InterruptHandle>,
+            VcpuStopReason,
+        ) -> Result<(), GdbTargetError>
+        + Send
+        + 'static,
+{
+    let (gdb_conn, hyp_conn) = DebugCommChannel::unbounded();
 
-            // Waits for vCPU to stop at entrypoint breakpoint
-            let msg = target.recv()?;
-            if let DebugResponse::InterruptHandle(handle) = msg {
-                tracing::info!("Received interrupt handle: {:?}", handle);
-                target.set_interrupt_handle(handle);
-            } else {
+    tracing::info!("Starting {}", name);
+    let _handle = thread::Builder::new().name(name.to_string()).spawn(
+        move || -> Result<(), GdbTargetError> {
+            // The vCPU thread sends its interrupt handle before it starts
+            let DebugResponse::InterruptHandle(handle) = hyp_conn.recv()? else {
                 return Err(GdbTargetError::UnexpectedMessage);
-            }
+            };
+            tracing::info!("Received interrupt handle: {:?}", handle);
+
+            let (conn, stop_reason) = if wait_for_attach {
+                tracing::info!("Waiting for debugger connection ... ");
+                let conn = listener.accept()?;
 
-            // Waits for vCPU to stop at entrypoint breakpoint
-            let msg = target.recv()?;
-            if let DebugResponse::VcpuStopped(_) = msg {
-                event_loop_thread(debugger, &mut target);
+                // Waits for vCPU to stop at entrypoint breakpoint
+                (conn, wait_for_stop(&hyp_conn)?)
             } else {
-                return Err(GdbTargetError::UnexpectedMessage);
-            }
+                let Some(conn) = accept_in_background(listener, &hyp_conn)? else {
+                    return Ok(());
+                };
 
-            Ok(())
-        });
+                // Stops the vCPU, which happens the next time the guest runs
+                if !handle.kill_from_debugger() {
+                    tracing::debug!("vCPU not running, it will stop when it next runs");
+                }
+                (conn, wait_for_stop(&hyp_conn)?)
+            };
+
+            serve(conn, hyp_conn, handle, stop_reason)
+        },
+    );
 
     Ok(gdb_conn)
 }
 
+/// Waits for a debugger to connect over `listener`, letting the vCPU run
+/// whenever it stops in the meantime. Returns `None` if the sandbox is
+/// dropped before a debugger connects.
+fn accept_in_background(
+    listener: DebugListener,
+    hyp_conn: &DebugCommChannel<DebugMsg, DebugResponse>,
+) -> Result<Option<DebugStream>, GdbTargetError> {
+    let (conn_tx, conn_rx) = crossbeam_channel::bounded(1);
+    thread::Builder::new()
+        .name("Debug listener".to_string())
+        .spawn(move || {
+            tracing::info!("Listening for debugger connection in the background");
+            let _ = conn_tx.send(listener.accept());
+        })?;
+
+    loop {
+        crossbeam_channel::select! {
+            recv(conn_rx) -> conn => {
+                let conn = conn.map_err(|_| GdbTargetError::ListenerError)??;
+                return Ok(Some(conn));
+            }
+            recv(hyp_conn.rx) -> msg => match msg {
+                Ok(DebugResponse::VcpuStopped(VcpuStopReason::Crash)) => {
+                    // Nobody can inspect the crash, so let the vCPU thread
+                    // report it as usual
+                    hyp_conn.send(DebugMsg::DisableDebug)?;
+                }
+                Ok(DebugResponse::VcpuStopped(_)) => hyp_conn.send(DebugMsg::Continue)?,
+                // Responses to the requests above
+                Ok(_) => (),
+                Err(_) => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Waits for the vCPU to stop
+fn wait_for_stop(
+    hyp_conn: &DebugCommChannel<DebugMsg, DebugResponse>,
+) -> Result<VcpuStopReason, GdbTargetError> {
+    loop {
+        match hyp_conn.recv()? {
+            DebugResponse::VcpuStopped(reason) => return Ok(reason),
+            // Responses to requests made before a debugger connected
+            msg => tracing::debug!("Ignoring {:?} while waiting for the vCPU to stop", msg),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -373,6 +659,48 @@ mod tests {
         assert!(res.is_ok());
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_debug_listener_unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("debug.sock");
+        let debug_info = DebugInfo::new(0);
+
+        // Binding again replaces the socket left behind by the first listener
+        let listener = DebugListener::bind(&debug_info, Some(&path)).unwrap();
+        drop(listener);
+        let listener = DebugListener::bind(&debug_info, Some(&path)).unwrap();
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        let mut conn = listener.accept().unwrap();
+        client.write_all(b"$?#3f").unwrap();
+        let mut buf = [0u8; 5];
+        conn.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"$?#3f");
+    }
+
+    #[test]
+    fn test_accept_in_background_lets_vcpu_run() {
+        let (debugger_conn, vcpu_conn) = DebugCommChannel::<DebugMsg, DebugResponse>::unbounded();
+        let listener = DebugListener::bind(&DebugInfo::new(0), None).unwrap();
+        let DebugListener::Tcp(tcp) = &listener else {
+            panic!("expected a TCP listener");
+        };
+        let addr = tcp.local_addr().unwrap();
+
+        let debugger = thread::spawn(move || accept_in_background(listener, &debugger_conn));
+
+        // The vCPU is let go from its entry point while nobody is attached
+        vcpu_conn
+            .send(DebugResponse::VcpuStopped(VcpuStopReason::EntryPointBp))
+            .unwrap();
+        assert!(matches!(vcpu_conn.recv().unwrap(), DebugMsg::Continue));
+        vcpu_conn.send(DebugResponse::Continue).unwrap();
+
+        let _client = TcpStream::connect(addr).unwrap();
+        assert!(debugger.join().unwrap().unwrap().is_some());
+    }
+
     #[cfg(target_os = "linux")]
     mod mem_access_tests {
         use std::os::fd::AsRawFd;
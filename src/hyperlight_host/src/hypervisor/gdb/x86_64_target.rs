@@ -25,13 +25,14 @@ use gdbstub::target::ext::base::singlethread::{
     SingleThreadSingleStepOps,
 };
 use gdbstub::target::ext::breakpoints::{
-    Breakpoints, BreakpointsOps, HwBreakpoint, HwBreakpointOps, SwBreakpoint, SwBreakpointOps,
+    Breakpoints, BreakpointsOps, HwBreakpoint, HwBreakpointOps, HwWatchpoint, HwWatchpointOps,
+    SwBreakpoint, SwBreakpointOps, WatchKind,
 };
 use gdbstub::target::ext::section_offsets::{Offsets, SectionOffsets};
 use gdbstub::target::{Target, TargetError, TargetResult};
 use gdbstub_arch::x86::X86_64_SSE as GdbTargetArch;
 
-use super::{DebugCommChannel, DebugMsg, DebugResponse, GdbTargetError};
+use super::{DebugCommChannel, DebugMsg, DebugResponse, GdbTargetError, WatchpointKind};
 use crate::hypervisor::InterruptHandle;
 use crate::hypervisor::regs::{CommonFpu, CommonRegisters};
 
@@ -41,6 +42,8 @@ pub(crate) struct HyperlightSandboxTarget {
     hyp_conn: DebugCommChannel<DebugMsg, DebugResponse>,
     /// Interrupt handle for the vCPU thread
     interrupt_handle: Option<Arc<dyn InterruptHandle>>,
+    /// The address, length and kind of each hardware watchpoint gdb set
+    watchpoints: Vec<(u64, u64, WatchKind)>,
 }
 
 impl HyperlightSandboxTarget {
@@ -48,9 +51,22 @@ impl HyperlightSandboxTarget {
         HyperlightSandboxTarget {
             hyp_conn,
             interrupt_handle: None,
+            watchpoints: Vec::new(),
         }
     }
 
+    /// The watchpoint gdb set that covers `addr`, the start of the range
+    /// the hypervisor reported was accessed, as the kind gdb asked for and
+    /// its start address
+    pub(crate) fn hit_watchpoint(&self, addr: u64) -> (WatchKind, u64) {
+        self.watchpoints
+            .iter()
+            .find(|&&(start, len, _)| (start..start + len).contains(&addr))
+            .map_or((WatchKind::ReadWrite, addr), |&(start, _, kind)| {
+                (kind, start)
+            })
+    }
+
     /// Sends a command over the communication channel and waits for response
     fn send_command(&self, cmd: DebugMsg) -> Result<DebugResponse, GdbTargetError> {
         self.send(cmd)?;
@@ -337,11 +353,91 @@ impl Breakpoints for HyperlightSandboxTarget {
     fn support_hw_breakpoint(&mut self) -> Option<HwBreakpointOps<'_, Self>> {
         Some(self)
     }
+    fn support_hw_watchpoint(&mut self) -> Option<HwWatchpointOps<'_, Self>> {
+        Some(self)
+    }
+
     fn support_sw_breakpoint(&mut self) -> Option<SwBreakpointOps<'_, Self>> {
         Some(self)
     }
 }
 
+fn watchpoint_kind(kind: WatchKind) -> WatchpointKind {
+    match kind {
+        WatchKind::Write => WatchpointKind::Write,
+        WatchKind::Read => WatchpointKind::Read,
+        WatchKind::ReadWrite => WatchpointKind::ReadWrite,
+    }
+}
+
+impl HwWatchpoint for HyperlightSandboxTarget {
+    fn add_hw_watchpoint(
+        &mut self,
+        addr: <Self::Arch as Arch>::Usize,
+        len: <Self::Arch as Arch>::Usize,
+        kind: WatchKind,
+    ) -> TargetResult<bool, Self> {
+        tracing::debug!("Add hw watchpoint at address {:X} len: {:X}", addr, len);
+        match self.send_command(DebugMsg::AddHwWatchpoint(addr, len, watchpoint_kind(kind)))? {
+            DebugResponse::AddHwWatchpoint(rsp) => {
+                if rsp {
+                    self.watchpoints.push((addr, len, kind));
+                }
+                Ok(rsp)
+            }
+            DebugResponse::NotAllowed => {
+                tracing::error!("Action not allowed at this time, crash might have occurred");
+                // This is a consequence of the target crashing or being in an invalid state
+                // we cannot continue execution, but we can still read registers and memory
+                Err(TargetError::NonFatal)
+            }
+            DebugResponse::ErrorOccurred => {
+                tracing::error!("Error occurred");
+                Err(TargetError::NonFatal)
+            }
+            msg => {
+                tracing::error!("Unexpected message received: {:?}", msg);
+                Err(TargetError::Fatal(GdbTargetError::UnexpectedMessage))
+            }
+        }
+    }
+
+    fn remove_hw_watchpoint(
+        &mut self,
+        addr: <Self::Arch as Arch>::Usize,
+        len: <Self::Arch as Arch>::Usize,
+        kind: WatchKind,
+    ) -> TargetResult<bool, Self> {
+        tracing::debug!("Remove hw watchpoint at address {:X} len: {:X}", addr, len);
+        match self.send_command(DebugMsg::RemoveHwWatchpoint(
+            addr,
+            len,
+            watchpoint_kind(kind),
+        ))? {
+            DebugResponse::RemoveHwWatchpoint(rsp) => {
+                if rsp {
+                    self.watchpoints.retain(|&w| w != (addr, len, kind));
+                }
+                Ok(rsp)
+            }
+            DebugResponse::NotAllowed => {
+                tracing::error!("Action not allowed at this time, crash might have occurred");
+                // This is a consequence of the target crashing or being in an invalid state
+                // we cannot continue execution, but we can still read registers and memory
+                Err(TargetError::NonFatal)
+            }
+            DebugResponse::ErrorOccurred => {
+                tracing::error!("Error occurred");
+                Err(TargetError::NonFatal)
+            }
+            msg => {
+                tracing::error!("Unexpected message received: {:?}", msg);
+                Err(TargetError::Fatal(GdbTargetError::UnexpectedMessage))
+            }
+        }
+    }
+}
+
 impl HwBreakpoint for HyperlightSandboxTarget {
     fn add_hw_breakpoint(
         &mut self,
@@ -528,4 +624,35 @@ mod tests {
             expected to fail"
         );
     }
+
+    #[test]
+    fn test_gdb_target_watchpoints() {
+        let (gdb_conn, hyp_conn) = DebugCommChannel::unbounded();
+        let mut target = HyperlightSandboxTarget::new(hyp_conn);
+
+        gdb_conn.send(DebugResponse::AddHwWatchpoint(true)).unwrap();
+        assert!(matches!(
+            target.add_hw_watchpoint(0x1001, 7, WatchKind::Read),
+            Ok(true)
+        ));
+        assert!(matches!(
+            gdb_conn.recv().unwrap(),
+            DebugMsg::AddHwWatchpoint(0x1001, 7, WatchpointKind::Read)
+        ));
+
+        // The hypervisor reports the aligned range that was accessed
+        assert_eq!(target.hit_watchpoint(0x1004), (WatchKind::Read, 0x1001));
+
+        gdb_conn
+            .send(DebugResponse::RemoveHwWatchpoint(true))
+            .unwrap();
+        assert!(matches!(
+            target.remove_hw_watchpoint(0x1001, 7, WatchKind::Read),
+            Ok(true)
+        ));
+        assert_eq!(
+            target.hit_watchpoint(0x1004),
+            (WatchKind::ReadWrite, 0x1004)
+        );
+    }
 }
@@ -19,8 +19,8 @@ limitations under the License.
 use std::sync::Arc;
 
 use super::{
-    AccessPageTableError, CreateHyperlightVmError, DispatchGuestCallError, HyperlightVm,
-    InitializeError,
+    AccessPageTableError, CreateHyperlightVmError, DispatchGuestCallError, FaultingInstruction,
+    HyperlightVm, InitializeError,
 };
 #[cfg(gdb)]
 use crate::hypervisor::gdb::{DebugCommChannel, DebugMsg, DebugResponse};
@@ -79,6 +79,20 @@ impl HyperlightVm {
         unimplemented!("dispatch_call_from_host")
     }
 
+    pub(super) fn sample_stack(
+        &self,
+        _mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+    ) -> Option<Vec<u64>> {
+        None
+    }
+
+    pub(super) fn faulting_instruction(
+        &self,
+        _mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+    ) -> Option<FaultingInstruction> {
+        None
+    }
+
     pub(crate) fn get_root_pt(&self) -> Result<u64, AccessPageTableError> {
         unimplemented!("get_root_pt")
     }
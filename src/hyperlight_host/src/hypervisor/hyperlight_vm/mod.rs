@@ -23,13 +23,19 @@ mod aarch64;
 use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 #[cfg(target_arch = "aarch64")]
 pub(crate) use aarch64::*;
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
 use hyperlight_common::log_level::GuestLogFilter;
+use hyperlight_common::mem::ABI_VERSION;
+#[cfg(target_os = "windows")]
+use hyperlight_common::outb::OutBAction;
 use tracing_core::LevelFilter;
 
 use crate::HyperlightError;
+use crate::hypervisor::disassembly::{FaultingInstruction, fault_suffix};
 #[cfg(gdb)]
 use crate::hypervisor::gdb::DebuggableVm;
 #[cfg(gdb)]
@@ -49,7 +55,8 @@ use crate::hypervisor::{InterruptHandle, InterruptHandleImpl};
 use crate::mem::memory_region::{MemoryRegion, MemoryRegionFlags, MemoryRegionType};
 use crate::mem::mgr::{SandboxMemoryManager, SnapshotSharedMemory};
 use crate::mem::shared_mem::{GuestSharedMemory, HostSharedMemory, SharedMemory};
-use crate::metrics::{METRIC_ERRONEOUS_VCPU_KICKS, METRIC_GUEST_CANCELLATION};
+use crate::metrics::METRIC_ERRONEOUS_VCPU_KICKS;
+use crate::sandbox::exit_stats::{ExitStats, VmExitKind};
 use crate::sandbox::host_funcs::FunctionRegistry;
 use crate::sandbox::outb::{HandleOutbError, handle_outb};
 use crate::sandbox::snapshot::NextAction;
@@ -157,13 +164,25 @@ impl DispatchGuestCallError {
 
             DispatchGuestCallError::Run(RunVmError::HandleIo(HandleIoError::Outb(
                 HandleOutbError::GuestAborted { code, message },
-            ))) => HyperlightError::GuestAborted(code, message),
+            ))) => HyperlightError::from_guest_abort(code, message),
+
+            DispatchGuestCallError::Run(RunVmError::HandleIo(HandleIoError::Outb(
+                HandleOutbError::ReplayDiverged(message),
+            ))) => HyperlightError::ReplayDiverged(message),
 
             DispatchGuestCallError::Run(RunVmError::MemoryAccessViolation {
                 addr,
                 access_type,
                 region_flags,
-            }) => HyperlightError::MemoryAccessViolation(addr, access_type, region_flags),
+                instruction,
+            }) => {
+                HyperlightError::MemoryAccessViolation(addr, access_type, region_flags, instruction)
+            }
+
+            #[cfg(target_os = "windows")]
+            DispatchGuestCallError::Run(RunVmError::RunVcpu(RunVcpuError::SurrogateDied(
+                exit_code,
+            ))) => HyperlightError::SurrogateDied { exit_code },
 
             // Leave others as is
             other => HyperlightVmError::DispatchGuestCall(other).into(),
@@ -185,6 +204,35 @@ pub enum InitializeError {
     InvalidStackPointer(u64),
 }
 
+impl InitializeError {
+    /// Converts an `InitializeError` to a `HyperlightError`, reporting a
+    /// guest that aborted because it was built for a different ABI version
+    /// as [`HyperlightError::AbiMismatch`], and one that aborted because it
+    /// does not support the call encoding as
+    /// [`HyperlightError::UnsupportedCallEncoding`]
+    pub(crate) fn promote(self) -> HyperlightError {
+        match self {
+            InitializeError::Run(RunVmError::HandleIo(HandleIoError::Outb(
+                HandleOutbError::GuestAborted { code, message },
+            ))) if code == ErrorCode::AbiMismatch as u8 => HyperlightError::AbiMismatch {
+                host: ABI_VERSION,
+                guest: message.trim().parse().unwrap_or_default(),
+            },
+            InitializeError::Run(RunVmError::HandleIo(HandleIoError::Outb(
+                HandleOutbError::GuestAborted { code, message },
+            ))) if code == ErrorCode::UnsupportedCallEncoding as u8 => {
+                HyperlightError::UnsupportedCallEncoding(message.trim().parse().unwrap_or_default())
+            }
+            InitializeError::Run(RunVmError::HandleIo(HandleIoError::Outb(
+                HandleOutbError::GuestAborted { code, message },
+            ))) if code == ErrorCode::GuestPanicked as u8 => {
+                HyperlightError::from_guest_abort(code, message)
+            }
+            other => HyperlightVmError::Initialize(other).into(),
+        }
+    }
+}
+
 /// Errors that can occur during VM execution in the run loop
 #[derive(Debug, thiserror::Error)]
 pub enum RunVmError {
@@ -204,17 +252,19 @@ pub enum RunVmError {
     #[error("IO handling error: {0}")]
     HandleIo(#[from] HandleIoError),
     #[error(
-        "Memory access violation at address {addr:#x}: {access_type} access, but memory is marked as {region_flags}"
+        "Memory access violation at address {addr:#x}: {access_type} access, but memory is marked as {region_flags}{suffix}",
+        suffix = fault_suffix(instruction)
     )]
     MemoryAccessViolation {
         addr: u64,
         access_type: MemoryRegionFlags,
         region_flags: MemoryRegionFlags,
+        instruction: Option<FaultingInstruction>,
     },
-    #[error("MMIO READ access to unmapped address {0:#x}")]
-    MmioReadUnmapped(u64),
-    #[error("MMIO WRITE access to unmapped address {0:#x}")]
-    MmioWriteUnmapped(u64),
+    #[error("MMIO READ access to unmapped address {0:#x}{suffix}", suffix = fault_suffix(.1))]
+    MmioReadUnmapped(u64, Option<FaultingInstruction>),
+    #[error("MMIO WRITE access to unmapped address {0:#x}{suffix}", suffix = fault_suffix(.1))]
+    MmioWriteUnmapped(u64, Option<FaultingInstruction>),
     #[error("vCPU run failed: {0}")]
     RunVcpu(#[from] RunVcpuError),
     #[error("Unexpected VM exit: {0}")]
@@ -393,6 +443,25 @@ pub(crate) struct HyperlightVm {
     pub(super) trace_info: MemTraceInfo,
     #[cfg(crashdump)]
     pub(super) rt_cfg: SandboxRuntimeConfig,
+    /// A live core dump requested through a `CoreDumpHandle`
+    #[cfg(crashdump)]
+    pub(super) live_dump: crate::hypervisor::crashdump::LiveDumpSlot,
+    /// The profile being taken by a `SamplingProfiler`, if any
+    pub(super) profile: crate::sandbox::profiler::ProfileSlot,
+    /// The trace timeline being recorded, if any
+    #[cfg(feature = "trace_guest")]
+    pub(super) timeline: crate::sandbox::trace::TimelineSlot,
+    /// The exits the vcpu made
+    pub(super) exit_stats: ExitStats,
+    /// The Intel PT capture of guest calls, if enabled
+    #[cfg(all(kvm, target_arch = "x86_64"))]
+    pub(super) intel_pt: Option<crate::hypervisor::virtual_machine::kvm::intel_pt::IntelPtCapture>,
+    /// Whether the guest's clocks are stopped while it is not running
+    #[cfg(target_os = "windows")]
+    pub(super) freeze_guest_time: bool,
+    /// Whether the guest's clocks are stopped now
+    #[cfg(target_os = "windows")]
+    pub(super) guest_time_frozen: bool,
 }
 
 impl HyperlightVm {
@@ -502,6 +571,12 @@ impl HyperlightVm {
         self.rsp_gva = gva;
     }
 
+    /// The path of the guest binary, used for core dumps
+    #[cfg(crashdump)]
+    pub(crate) fn binary_path(&self) -> Option<String> {
+        self.rt_cfg.binary_path.clone()
+    }
+
     /// Get the current entrypoint action
     pub(crate) fn get_entrypoint(&self) -> NextAction {
         self.entrypoint
@@ -520,6 +595,117 @@ impl HyperlightVm {
         self.interrupt_handle.clear_cancel();
     }
 
+    /// Get a handle for taking core dumps while the guest runs
+    #[cfg(crashdump)]
+    pub(crate) fn core_dump_handle(&self) -> crate::hypervisor::crashdump::CoreDumpHandle {
+        crate::hypervisor::crashdump::CoreDumpHandle::new(
+            self.interrupt_handle.clone(),
+            self.live_dump.clone(),
+        )
+    }
+
+    /// Take the core dump requested through a `CoreDumpHandle`, if any,
+    /// and send its outcome back to the requester
+    #[cfg(crashdump)]
+    fn take_requested_core_dump(&self, mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>) {
+        self.interrupt_handle.clear_core_dump_request();
+        let request = match self.live_dump.lock() {
+            Ok(mut pending) => pending.take(),
+            Err(e) => {
+                tracing::error!("Failed to lock the core dump request: {}", e);
+                None
+            }
+        };
+        if let Some(request) = request {
+            let result = crate::hypervisor::crashdump::dump_core(self, mem_mgr, &request.path);
+            // The requester may have given up waiting
+            let _ = request.reply.send(result);
+        }
+    }
+
+    /// Start sampling the guest's stack into `profile`
+    pub(crate) fn start_profiler(
+        &self,
+        profile: crate::sandbox::GuestProfile,
+    ) -> crate::Result<crate::sandbox::profiler::SamplingProfiler> {
+        crate::sandbox::profiler::SamplingProfiler::start(
+            self.interrupt_handle.clone(),
+            self.profile.clone(),
+            profile,
+        )
+    }
+
+    /// Start watching a guest call to `function_name` under `policy`,
+    /// which samples the guest's stack through the profile slot
+    pub(crate) fn start_slow_call_watchdog(
+        &self,
+        policy: &crate::sandbox::SlowCallPolicy,
+        function_name: Option<&str>,
+        labels: &crate::sandbox::SandboxLabels,
+        started: std::time::Instant,
+        symbolizer: Arc<crate::sandbox::GuestSymbolizer>,
+    ) -> Option<crate::sandbox::slow_call::SlowCallWatchdog> {
+        crate::sandbox::slow_call::SlowCallWatchdog::start(
+            policy,
+            function_name,
+            labels,
+            started,
+            self.interrupt_handle.clone(),
+            self.profile.clone(),
+            symbolizer,
+        )
+    }
+
+    /// The slot the trace timeline of the sandbox is recorded into
+    #[cfg(feature = "trace_guest")]
+    pub(crate) fn timeline(&self) -> crate::sandbox::trace::TimelineSlot {
+        self.timeline.clone()
+    }
+
+    /// The exits the vcpu made
+    pub(crate) fn exit_stats(&self) -> &ExitStats {
+        &self.exit_stats
+    }
+
+    /// The exits the vcpu made, to reset them or change what is recorded
+    pub(crate) fn exit_stats_mut(&mut self) -> &mut ExitStats {
+        &mut self.exit_stats
+    }
+
+    /// The wall-clock time the guest's paravirtual clock started at, in
+    /// nanoseconds since the Unix epoch, or 0 if the guest has no clock
+    pub(crate) fn wall_clock_base(&self) -> u64 {
+        let clock = match self.vm.guest_clock() {
+            Ok(Some(clock)) => clock,
+            Ok(None) => return 0,
+            Err(e) => {
+                tracing::warn!("Failed to read the guest's clock: {}", e);
+                return 0;
+            }
+        };
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| (now.as_nanos() as u64).saturating_sub(clock))
+    }
+
+    /// Add a sample of the guest's stack to the profile being taken, if
+    /// any, as requested by its `SamplingProfiler`
+    fn take_requested_sample(&self, mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>) {
+        self.interrupt_handle.clear_sample_request();
+        let mut profile = match self.profile.lock() {
+            Ok(profile) => profile,
+            Err(e) => {
+                tracing::error!("Failed to lock the profile: {}", e);
+                return;
+            }
+        };
+        if let Some(profile) = profile.as_mut()
+            && let Some(stack) = self.sample_stack(mem_mgr)
+        {
+            profile.add_sample(stack);
+        }
+    }
+
     pub(super) fn run(
         &mut self,
         mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
@@ -529,7 +715,11 @@ impl HyperlightVm {
         // Keeps the trace context and open spans
         #[cfg(feature = "trace_guest")]
         let mut tc = crate::sandbox::trace::TraceContext::new();
+        #[cfg(feature = "trace_guest")]
+        tc.record_timeline(self.timeline.clone());
 
+        #[cfg(target_os = "windows")]
+        self.set_guest_time_frozen(false);
         let result = loop {
             // ===== KILL() TIMING POINT 2: Before set_tid() =====
             // If kill() is called and ran to completion BEFORE this line executes:
@@ -541,18 +731,28 @@ impl HyperlightVm {
             // NOTE: `set_running()`` must be called before checking `is_cancelled()`
             // otherwise we risk missing a call to `kill()` because the vcpu would not be marked as running yet so signals won't be sent
 
+            #[cfg(crashdump)]
+            let core_dump_requested = self.interrupt_handle.is_core_dump_requested();
+            #[cfg(not(crashdump))]
+            let core_dump_requested = false;
+
+            let mut latency = None;
             let exit_reason = if self.interrupt_handle.is_cancelled()
                 || self.interrupt_handle.is_debug_interrupted()
+                || core_dump_requested
+                || self.interrupt_handle.is_sample_requested()
             {
                 Ok(VmExit::Cancelled())
             } else {
                 // ==== KILL() TIMING POINT 3: Before calling run() ====
                 // If kill() is called and ran to completion BEFORE this line executes:
                 //    - Will still do a VM entry, but signals will be sent until VM exits
+                let entered = self.exit_stats.records_latency().then(Instant::now);
                 let result = self.vm.run_vcpu(
                     #[cfg(feature = "trace_guest")]
                     &mut tc,
                 );
+                latency = entered.map(|entered| entered.elapsed());
 
                 // End current host trace by closing the current span that captures traces
                 // happening when a guest exits and re-enters.
@@ -578,6 +778,16 @@ impl HyperlightVm {
                 result
             };
 
+            let exit_kind = VmExitKind::of(&exit_reason);
+            self.exit_stats.record(exit_kind, latency);
+
+            #[cfg(feature = "trace_guest")]
+            if let Ok(mut timeline) = self.timeline.lock()
+                && let Some(timeline) = timeline.as_mut()
+            {
+                timeline.vm_exit(exit_kind.name());
+            }
+
             // ===== KILL() TIMING POINT 4: Before clear_running() =====
             // If kill() is called and ran to completion BEFORE this line executes:
             //    - CANCEL_BIT will be set. Cancellation is deferred to the next iteration.
@@ -590,6 +800,9 @@ impl HyperlightVm {
             //    - Signals will not be sent
             let cancel_requested = self.interrupt_handle.is_cancelled();
             let debug_interrupted = self.interrupt_handle.is_debug_interrupted();
+            #[cfg(crashdump)]
+            let core_dump_requested = self.interrupt_handle.is_core_dump_requested();
+            let sample_requested = self.interrupt_handle.is_sample_requested();
 
             // ===== KILL() TIMING POINT 6: Before checking exit_reason =====
             // If kill() is called and ran to completion BEFORE this line executes:
@@ -632,10 +845,14 @@ impl HyperlightVm {
                                 addr,
                                 access_type: MemoryRegionFlags::READ,
                                 region_flags,
+                                instruction: self.faulting_instruction(mem_mgr),
                             });
                         }
                         None => {
-                            break Err(RunVmError::MmioReadUnmapped(addr));
+                            break Err(RunVmError::MmioReadUnmapped(
+                                addr,
+                                self.faulting_instruction(mem_mgr),
+                            ));
                         }
                     }
                 }
@@ -651,10 +868,14 @@ impl HyperlightVm {
                                 addr,
                                 access_type: MemoryRegionFlags::WRITE,
                                 region_flags,
+                                instruction: self.faulting_instruction(mem_mgr),
                             });
                         }
                         None => {
-                            break Err(RunVmError::MmioWriteUnmapped(addr));
+                            break Err(RunVmError::MmioWriteUnmapped(
+                                addr,
+                                self.faulting_instruction(mem_mgr),
+                            ));
                         }
                     }
                 }
@@ -663,25 +884,50 @@ impl HyperlightVm {
                     // the vcpu was interrupted by a stale cancellation. This can occur when:
                     // - Linux: A signal from a previous call arrives late
                     // - Windows: WHvCancelRunVirtualProcessor called right after vcpu exits but RUNNING_BIT is still true
-                    if !cancel_requested && !debug_interrupted {
+                    if !cancel_requested
+                        && !debug_interrupted
+                        && !core_dump_requested
+                        && !sample_requested
+                    {
                         // Track that an erroneous vCPU kick occurred
                         metrics::counter!(METRIC_ERRONEOUS_VCPU_KICKS).increment(1);
                         // treat this the same as a VmExit::Retry, the cancel was not meant for this call
                         continue;
                     }
 
+                    // Neither does a profiler sample
+                    if sample_requested {
+                        self.take_requested_sample(mem_mgr);
+                        if !cancel_requested && !debug_interrupted && !core_dump_requested {
+                            continue;
+                        }
+                    }
+
+                    // A live core dump only pauses the guest while it is taken
+                    #[cfg(crashdump)]
+                    if core_dump_requested {
+                        self.take_requested_core_dump(mem_mgr);
+                        if !cancel_requested && !debug_interrupted {
+                            continue;
+                        }
+                    }
+
                     // If the vcpu was interrupted by a debugger, we need to handle it
                     #[cfg(gdb)]
-                    {
+                    if debug_interrupted {
                         self.interrupt_handle.clear_debug_interrupt();
                         if let Err(e) =
                             self.handle_debug(dbg_mem_access_fn.clone(), VcpuStopReason::Interrupt)
                         {
                             break Err(e.into());
                         }
+                        // The debugger only paused the guest, so carry on with
+                        // the call unless it was also cancelled
+                        if !cancel_requested {
+                            continue;
+                        }
                     }
 
-                    metrics::counter!(METRIC_GUEST_CANCELLATION).increment(1);
                     break Err(RunVmError::ExecutionCancelledByHost);
                 }
                 Ok(VmExit::Unknown(reason)) => {
@@ -693,6 +939,22 @@ impl HyperlightVm {
                 }
             }
         };
+        #[cfg(target_os = "windows")]
+        {
+            self.set_guest_time_frozen(true);
+            self.update_hypervisor_counters();
+        }
+
+        // A core dump requested as the guest call ended is taken of the
+        // state the guest ended in
+        #[cfg(crashdump)]
+        if self.interrupt_handle.is_core_dump_requested() {
+            self.take_requested_core_dump(mem_mgr);
+        }
+
+        // A sample requested as the guest call ended is not of the guest
+        // running, so it is dropped
+        self.interrupt_handle.clear_sample_request();
 
         match result {
             Ok(_) => Ok(()),
@@ -703,8 +965,13 @@ impl HyperlightVm {
             Err(e) => {
                 #[cfg(crashdump)]
                 if self.rt_cfg.guest_core_dump {
-                    crate::hypervisor::crashdump::generate_crashdump(self, mem_mgr, None)
-                        .map_err(|e| RunVmError::CrashdumpGeneration(Box::new(e)))?;
+                    crate::hypervisor::crashdump::generate_crashdump(
+                        self,
+                        mem_mgr,
+                        crate::hypervisor::crashdump::CrashDumpReason::GuestCrash(e.to_string()),
+                        None,
+                    )
+                    .map_err(|e| RunVmError::CrashdumpGeneration(Box::new(e)))?;
                 }
 
                 // If GDB is enabled, we handle the debug memory access
@@ -738,19 +1005,190 @@ impl HyperlightVm {
             data.get(3).copied().unwrap_or(0),
         ]);
 
+        // The guest waits while a host function runs, so no time passes
+        // for it
+        #[cfg(target_os = "windows")]
+        let host_call = port == OutBAction::CallFunction as u16;
+        #[cfg(target_os = "windows")]
+        if host_call {
+            self.set_guest_time_frozen(true);
+        }
+
         #[cfg(feature = "mem_profile")]
         {
             let regs = self.vm.regs().map_err(HandleIoError::GetRegs)?;
-            handle_outb(mem_mgr, host_funcs, port, val, &regs, &mut self.trace_info)?;
+            handle_outb(
+                mem_mgr,
+                host_funcs,
+                &self.interrupt_handle,
+                port,
+                val,
+                &self.timeline,
+                &regs,
+                &mut self.trace_info,
+            )?;
         }
 
         #[cfg(not(feature = "mem_profile"))]
         {
-            handle_outb(mem_mgr, host_funcs, port, val)?;
+            handle_outb(
+                mem_mgr,
+                host_funcs,
+                &self.interrupt_handle,
+                port,
+                val,
+                #[cfg(feature = "trace_guest")]
+                &self.timeline,
+            )?;
+        }
+
+        #[cfg(target_os = "windows")]
+        if host_call {
+            self.set_guest_time_frozen(false);
         }
 
         Ok(())
     }
+
+    /// Stop or restart the guest's clocks, if the sandbox stops them while
+    /// the guest is not running. Failing to is logged rather than
+    /// returned, as the guest can still run.
+    #[cfg(target_os = "windows")]
+    pub(super) fn set_guest_time_frozen(&mut self, frozen: bool) {
+        use windows::Win32::System::Hypervisor::{WHvResumePartitionTime, WHvSuspendPartitionTime};
+
+        if !self.freeze_guest_time || self.guest_time_frozen == frozen {
+            return;
+        }
+        let partition = self.vm.partition_handle();
+        // SAFETY: the partition lives as long as the VM
+        let res = unsafe {
+            if frozen {
+                WHvSuspendPartitionTime(partition)
+            } else {
+                WHvResumePartitionTime(partition)
+            }
+        };
+        match res {
+            Ok(()) => self.guest_time_frozen = frozen,
+            Err(e) => tracing::warn!(
+                "Failed to {} the guest's time: {}",
+                if frozen { "suspend" } else { "resume" },
+                e
+            ),
+        }
+    }
+
+    /// Read the counters WHP keeps of the vCPU and partition into the exit
+    /// stats. Failing to is logged rather than returned, as the guest call
+    /// has still run.
+    #[cfg(target_os = "windows")]
+    fn update_hypervisor_counters(&mut self) {
+        use std::ffi::c_void;
+        use std::time::Duration;
+
+        use windows::Win32::System::Hypervisor::{
+            WHV_PARTITION_MEMORY_COUNTERS, WHV_PROCESSOR_EVENT_COUNTERS,
+            WHV_PROCESSOR_INTERCEPT_COUNTERS, WHV_PROCESSOR_RUNTIME_COUNTERS,
+            WHV_PROCESSOR_SYNTHETIC_FEATURES_COUNTERS, WHvGetPartitionCounters,
+            WHvGetVirtualProcessorCounters, WHvPartitionCounterSetMemory,
+            WHvProcessorCounterSetEvents, WHvProcessorCounterSetIntercepts,
+            WHvProcessorCounterSetRuntime, WHvProcessorCounterSetSyntheticFeatures,
+        };
+
+        use crate::sandbox::exit_stats::HypervisorCounters;
+
+        /// Read a counter set into a `T` with `get`, which is given the
+        /// buffer and its size
+        fn read<T: Default>(
+            get: impl FnOnce(*mut c_void, u32) -> windows::core::Result<()>,
+        ) -> windows::core::Result<T> {
+            let mut counters = T::default();
+            get((&mut counters as *mut T).cast(), size_of::<T>() as u32)?;
+            Ok(counters)
+        }
+
+        let partition = self.vm.partition_handle();
+        // SAFETY: each buffer is the struct of its counter set, and the
+        // partition lives as long as the VM
+        let counters = unsafe {
+            (|| {
+                let runtime: WHV_PROCESSOR_RUNTIME_COUNTERS = read(|buf, size| {
+                    WHvGetVirtualProcessorCounters(
+                        partition,
+                        0,
+                        WHvProcessorCounterSetRuntime,
+                        buf,
+                        size,
+                        None,
+                    )
+                })?;
+                let intercepts: WHV_PROCESSOR_INTERCEPT_COUNTERS = read(|buf, size| {
+                    WHvGetVirtualProcessorCounters(
+                        partition,
+                        0,
+                        WHvProcessorCounterSetIntercepts,
+                        buf,
+                        size,
+                        None,
+                    )
+                })?;
+                let events: WHV_PROCESSOR_EVENT_COUNTERS = read(|buf, size| {
+                    WHvGetVirtualProcessorCounters(
+                        partition,
+                        0,
+                        WHvProcessorCounterSetEvents,
+                        buf,
+                        size,
+                        None,
+                    )
+                })?;
+                // Older versions of Windows don't have the synthetic
+                // features counters
+                let synthetic: WHV_PROCESSOR_SYNTHETIC_FEATURES_COUNTERS = read(|buf, size| {
+                    WHvGetVirtualProcessorCounters(
+                        partition,
+                        0,
+                        WHvProcessorCounterSetSyntheticFeatures,
+                        buf,
+                        size,
+                        None,
+                    )
+                })
+                .unwrap_or_default();
+                let memory: WHV_PARTITION_MEMORY_COUNTERS = read(|buf, size| {
+                    WHvGetPartitionCounters(
+                        partition,
+                        WHvPartitionCounterSetMemory,
+                        buf,
+                        size,
+                        None,
+                    )
+                })?;
+                windows::core::Result::Ok(HypervisorCounters {
+                    vcpu_runtime: Duration::from_nanos(runtime.TotalRuntime100ns * 100),
+                    hypervisor_runtime: Duration::from_nanos(runtime.HypervisorRuntime100ns * 100),
+                    io_exits: intercepts.IoInstructions.Count,
+                    halt_exits: intercepts.HaltInstructions.Count,
+                    cpuid_exits: intercepts.CpuidInstructions.Count,
+                    msr_exits: intercepts.MsrAccesses.Count,
+                    page_fault_exits: intercepts.PageFaultIntercepts.Count
+                        + intercepts.NestedPageFaultIntercepts.Count,
+                    emulated_instructions: intercepts.EmulatedInstructions.Count,
+                    tlb_flushes: intercepts.PageInvalidations.Count
+                        + synthetic.VirtualMmuHypercallsCount,
+                    hypercalls: intercepts.Hypercalls.Count,
+                    interrupts: events.InterruptCount,
+                    mapped_4k_pages: memory.Mapped4KPageCount,
+                    mapped_2m_pages: memory.Mapped2MPageCount,
+                })
+            })()
+        };
+        match counters {
+            Ok(counters) => self.exit_stats.update_hypervisor(counters),
+            Err(e) => tracing::debug!("Failed to read the hypervisor's counters: {}", e),
+        }
+    }
 }
 
 impl Drop for HyperlightVm {
@@ -25,6 +25,7 @@ use std::sync::atomic::AtomicU8;
 use std::sync::atomic::AtomicU64;
 use std::sync::{Arc, Mutex};
 
+use hyperlight_common::mem::PAGE_SIZE_USIZE;
 use tracing::{Span, instrument};
 use tracing_core::LevelFilter;
 
@@ -34,12 +35,13 @@ use crate::hypervisor::InterruptHandleImpl;
 use crate::hypervisor::LinuxInterruptHandle;
 #[cfg(crashdump)]
 use crate::hypervisor::crashdump;
+use crate::hypervisor::disassembly::MAX_INSTRUCTION_LEN;
+#[cfg(gdb)]
+use crate::hypervisor::gdb::DebugMemoryAccessError;
 #[cfg(gdb)]
 use crate::hypervisor::gdb::{
     DebugCommChannel, DebugMsg, DebugResponse, DebuggableVm, VcpuStopReason,
 };
-#[cfg(gdb)]
-use crate::hypervisor::gdb::{DebugError, DebugMemoryAccessError};
 use crate::hypervisor::regs::{
     CommonDebugRegs, CommonFpu, CommonRegisters, CommonSpecialRegisters,
 };
@@ -47,6 +49,8 @@ use crate::hypervisor::regs::{
 use crate::hypervisor::virtual_machine::VirtualMachine;
 #[cfg(kvm)]
 use crate::hypervisor::virtual_machine::kvm::KvmVm;
+#[cfg(kvm)]
+use crate::hypervisor::virtual_machine::kvm::intel_pt::{IntelPtCapture, IntelPtTrace};
 #[cfg(mshv3)]
 use crate::hypervisor::virtual_machine::mshv::MshvVm;
 #[cfg(target_os = "windows")]
@@ -96,7 +100,9 @@ impl HyperlightVm {
             #[cfg(mshv3)]
             Some(HypervisorType::Mshv) => Box::new(MshvVm::new().map_err(VmError::CreateVm)?),
             #[cfg(target_os = "windows")]
-            Some(HypervisorType::Whp) => Box::new(WhpVm::new().map_err(VmError::CreateVm)?),
+            Some(HypervisorType::Whp) => {
+                Box::new(WhpVm::new(config.get_surrogate_isolation()).map_err(VmError::CreateVm)?)
+            }
             None => return Err(CreateHyperlightVmError::NoHypervisorFound),
         };
 
@@ -131,6 +137,7 @@ impl HyperlightVm {
             retry_delay: config.get_interrupt_retry_delay(),
             sig_rt_min_offset: config.get_interrupt_vcpu_sigrtmin_offset(),
             dropped: AtomicBool::new(false),
+            last_interrupt: std::sync::Mutex::new(None),
         });
 
         #[cfg(target_os = "windows")]
@@ -172,7 +179,22 @@ impl HyperlightVm {
             trace_info,
             #[cfg(crashdump)]
             rt_cfg,
+            #[cfg(crashdump)]
+            live_dump: Default::default(),
+            profile: Default::default(),
+            #[cfg(feature = "trace_guest")]
+            timeline: Default::default(),
+            exit_stats: Default::default(),
+            #[cfg(kvm)]
+            intel_pt: None,
+            #[cfg(target_os = "windows")]
+            freeze_guest_time: config.get_freeze_guest_time(),
+            #[cfg(target_os = "windows")]
+            guest_time_frozen: false,
         };
+        // The guest's clocks start when it first runs
+        #[cfg(target_os = "windows")]
+        ret.set_guest_time_frozen(true);
 
         ret.update_snapshot_mapping(snapshot_mem)?;
         ret.update_scratch_mapping(scratch_mem)?;
@@ -252,6 +274,64 @@ impl HyperlightVm {
         Ok(())
     }
 
+    /// The guest's stack for a profiler sample: the address it is at
+    /// followed by the return addresses found by following its frame
+    /// pointers
+    pub(super) fn sample_stack(
+        &self,
+        mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+    ) -> Option<Vec<u64>> {
+        let regs = self.vm.regs().ok()?;
+        let root_pt = self.get_root_pt().ok()?;
+        let mut stack = vec![regs.rip];
+        let mut rbp = regs.rbp;
+
+        // Each frame saves the caller's rbp at [rbp] and the return address
+        // right above it
+        while rbp != 0 && stack.len() < crate::sandbox::profiler::MAX_SAMPLE_FRAMES {
+            let Ok(frame) = mem_mgr.read_guest_memory_by_gva(rbp, 16, root_pt) else {
+                break;
+            };
+            let (saved_rbp, return_address) = frame.split_at(8);
+            let saved_rbp = u64::from_le_bytes(saved_rbp.try_into().unwrap_or_default());
+            let return_address = u64::from_le_bytes(return_address.try_into().unwrap_or_default());
+            if return_address == 0 {
+                break;
+            }
+            stack.push(return_address);
+            if saved_rbp <= rbp {
+                break;
+            }
+            rbp = saved_rbp;
+        }
+        Some(stack)
+    }
+
+    /// The instruction the guest is at, for errors about a memory access
+    /// it made that the host rejected
+    pub(super) fn faulting_instruction(
+        &self,
+        mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+    ) -> Option<FaultingInstruction> {
+        let regs = self.vm.regs().ok()?;
+        let root_pt = self.get_root_pt().ok()?;
+        // The instruction may be shorter than the longest one and end just
+        // before an unmapped page
+        let to_page_end =
+            (PAGE_SIZE_USIZE - (regs.rip as usize % PAGE_SIZE_USIZE)).min(MAX_INSTRUCTION_LEN);
+        let code = mem_mgr
+            .read_guest_memory_by_gva(regs.rip, MAX_INSTRUCTION_LEN, root_pt)
+            .or_else(|_| mem_mgr.read_guest_memory_by_gva(regs.rip, to_page_end, root_pt))
+            .ok()?;
+        FaultingInstruction::decode(&code, &regs)
+    }
+
+    /// The vcpu's general purpose registers
+    #[cfg(feature = "console")]
+    pub(crate) fn registers(&self) -> Result<CommonRegisters, RegisterError> {
+        self.vm.regs()
+    }
+
     /// Get the current base page table physical address from CR3.
     pub(crate) fn get_root_pt(&self) -> Result<u64, AccessPageTableError> {
         let sregs = self.vm.sregs()?;
@@ -303,14 +383,17 @@ impl HyperlightVm {
             rflags,
             ..Default::default()
         };
+        // and reset fpu
         self.vm
-            .set_regs(&regs)
+            .set_regs_and_fpu(&regs, &CommonFpu::default())
             .map_err(DispatchGuestCallError::SetupRegs)?;
 
-        // reset fpu
-        self.vm
-            .set_fpu(&CommonFpu::default())
-            .map_err(DispatchGuestCallError::SetupRegs)?;
+        #[cfg(kvm)]
+        if let Some(intel_pt) = &mut self.intel_pt
+            && let Err(e) = intel_pt.start()
+        {
+            tracing::warn!("Failed to start the Intel PT capture: {}", e);
+        }
 
         let result = self
             .run(
@@ -321,6 +404,13 @@ impl HyperlightVm {
             )
             .map_err(DispatchGuestCallError::Run);
 
+        #[cfg(kvm)]
+        if let Some(intel_pt) = &mut self.intel_pt
+            && let Err(e) = intel_pt.stop()
+        {
+            tracing::warn!("Failed to stop the Intel PT capture: {}", e);
+        }
+
         // Clear the TLB flush flag only after run() returns. The guest
         // may have been cancelled before it executed the flush.
         self.pending_tlb_flush = false;
@@ -328,6 +418,32 @@ impl HyperlightVm {
         result
     }
 
+    /// Capture an Intel PT trace of each guest call, of up to
+    /// `buffer_size` bytes
+    #[cfg(kvm)]
+    pub(crate) fn enable_intel_pt(&mut self, buffer_size: usize) -> crate::Result<()> {
+        if get_available_hypervisor() != &Some(HypervisorType::Kvm) {
+            return Err(crate::new_error!(
+                "Intel PT capture is only supported on KVM"
+            ));
+        }
+        self.intel_pt = Some(IntelPtCapture::new(buffer_size)?);
+        Ok(())
+    }
+
+    /// Stop capturing Intel PT traces of guest calls
+    #[cfg(kvm)]
+    pub(crate) fn disable_intel_pt(&mut self) {
+        self.intel_pt = None;
+    }
+
+    /// The Intel PT trace of the last guest call, if one was captured
+    /// and not taken already
+    #[cfg(kvm)]
+    pub(crate) fn take_intel_pt_trace(&mut self) -> Option<IntelPtTrace> {
+        self.intel_pt.as_mut()?.take_trace()
+    }
+
     /// Resets the following vCPU state:
     /// - General purpose registers
     /// - Debug registers
@@ -411,8 +527,10 @@ impl HyperlightVm {
                         }
                         // Do not allow adding/removing breakpoints and writing to memory or registers
                         DebugMsg::AddHwBreakpoint(_)
+                        | DebugMsg::AddHwWatchpoint(..)
                         | DebugMsg::AddSwBreakpoint(_)
                         | DebugMsg::RemoveHwBreakpoint(_)
+                        | DebugMsg::RemoveHwWatchpoint(..)
                         | DebugMsg::RemoveSwBreakpoint(_)
                         | DebugMsg::WriteAddr(_, _)
                         | DebugMsg::WriteRegisters(_) => DebugResponse::NotAllowed,
@@ -426,9 +544,9 @@ impl HyperlightVm {
                                 Err(ProcessDebugRequestError::ReadMemory(
                                     DebugMemoryAccessError::TranslateGuestAddress(_),
                                 ))
-                                | Err(ProcessDebugRequestError::Debug(DebugError::TranslateGva(
-                                    _,
-                                ))) => DebugResponse::ErrorOccurred,
+                                | Err(ProcessDebugRequestError::WriteMemory(
+                                    DebugMemoryAccessError::TranslateGuestAddress(_),
+                                )) => DebugResponse::ErrorOccurred,
                                 Err(e) => {
                                     tracing::error!("Error processing debug request: {:?}", e);
                                     return Err(HandleDebugError::ProcessRequest(e));
@@ -473,9 +591,9 @@ impl HyperlightVm {
                         Err(ProcessDebugRequestError::ReadMemory(
                             DebugMemoryAccessError::TranslateGuestAddress(_),
                         ))
-                        | Err(ProcessDebugRequestError::Debug(DebugError::TranslateGva(_))) => {
-                            DebugResponse::ErrorOccurred
-                        }
+                        | Err(ProcessDebugRequestError::WriteMemory(
+                            DebugMemoryAccessError::TranslateGuestAddress(_),
+                        )) => DebugResponse::ErrorOccurred,
                         Err(e) => {
                             return Err(HandleDebugError::ProcessRequest(e));
                         }
@@ -583,13 +701,12 @@ impl HyperlightVm {
 
 #[cfg(gdb)]
 pub(super) mod debug {
-    use hyperlight_common::mem::PAGE_SIZE;
-
     use super::HyperlightVm;
     use crate::hypervisor::gdb::arch::{SW_BP, SW_BP_SIZE};
     use crate::hypervisor::gdb::{
         DebugError, DebugMemoryAccess, DebugMemoryAccessError, DebugMsg, DebugResponse,
     };
+    use crate::hypervisor::hyperlight_vm::AccessPageTableError;
     use crate::hypervisor::virtual_machine::VmError;
 
     /// Errors that can occur during GDB debug request processing
@@ -599,6 +716,8 @@ pub(super) mod debug {
         DebugNotEnabled,
         #[error("Failed to acquire lock at {0}:{1}")]
         TryLockError(&'static str, u32),
+        #[error("Failed to get the root page table: {0}")]
+        AccessPageTable(#[from] AccessPageTableError),
         #[error("VM operation error: {0}")]
         Vm(#[from] VmError),
         #[error("Debug operation error: {0}")]
@@ -629,6 +748,18 @@ pub(super) mod debug {
                             })
                             .is_ok(),
                     )),
+                    DebugMsg::AddHwWatchpoint(addr, len, kind) => {
+                        Ok(DebugResponse::AddHwWatchpoint(
+                            self.vm
+                                .add_hw_watchpoint(addr, len, kind)
+                                .map_err(|e| {
+                                    tracing::error!("Failed to add hw watchpoint: {:?}", e);
+
+                                    e
+                                })
+                                .is_ok(),
+                        ))
+                    }
                     DebugMsg::AddSwBreakpoint(addr) => Ok(DebugResponse::AddSwBreakpoint(
                         self.add_sw_breakpoint(addr, mem_access)
                             .map_err(|e| {
@@ -691,6 +822,18 @@ pub(super) mod debug {
                             })
                             .is_ok(),
                     )),
+                    DebugMsg::RemoveHwWatchpoint(addr, len, kind) => {
+                        Ok(DebugResponse::RemoveHwWatchpoint(
+                            self.vm
+                                .remove_hw_watchpoint(addr, len, kind)
+                                .map_err(|e| {
+                                    tracing::error!("Failed to remove hw watchpoint: {:?}", e);
+
+                                    e
+                                })
+                                .is_ok(),
+                        ))
+                    }
                     DebugMsg::RemoveSwBreakpoint(addr) => Ok(DebugResponse::RemoveSwBreakpoint(
                         self.remove_sw_breakpoint(addr, mem_access)
                             .map_err(|e| {
@@ -720,8 +863,9 @@ pub(super) mod debug {
                     }
                     DebugMsg::WriteRegisters(boxed_regs) => {
                         let (regs, fpu) = boxed_regs.as_ref();
-                        self.vm.set_regs(regs).map_err(VmError::Register)?;
-                        self.vm.set_fpu(fpu).map_err(VmError::Register)?;
+                        self.vm
+                            .set_regs_and_fpu(regs, fpu)
+                            .map_err(VmError::Register)?;
 
                         Ok(DebugResponse::WriteRegisters)
                     }
@@ -762,25 +906,18 @@ pub(super) mod debug {
 
         fn read_addrs(
             &mut self,
-            mut gva: u64,
+            gva: u64,
             mut data: &mut [u8],
             mem_access: &DebugMemoryAccess,
         ) -> std::result::Result<(), ProcessDebugRequestError> {
             let data_len = data.len();
             tracing::debug!("Read addr: {:X} len: {:X}", gva, data_len);
 
-            while !data.is_empty() {
-                let gpa = self.vm.translate_gva(gva)?;
-
-                let read_len = std::cmp::min(
-                    data.len(),
-                    (PAGE_SIZE - (gpa & (PAGE_SIZE - 1))).try_into().unwrap(),
-                );
-
+            let root_pt = self.get_root_pt()?;
+            for (gpa, read_len) in mem_access.translate(gva, data_len, root_pt)? {
                 mem_access.read(&mut data[..read_len], gpa)?;
 
                 data = &mut data[read_len..];
-                gva += read_len as u64;
             }
 
             Ok(())
@@ -790,28 +927,26 @@ pub(super) mod debug {
         /// The address is checked to be a valid guest address
         fn write_addrs(
             &mut self,
-            mut gva: u64,
+            gva: u64,
             mut data: &[u8],
             mem_access: &DebugMemoryAccess,
         ) -> std::result::Result<(), ProcessDebugRequestError> {
             let data_len = data.len();
             tracing::debug!("Write addr: {:X} len: {:X}", gva, data_len);
 
-            while !data.is_empty() {
-                let gpa = self.vm.translate_gva(gva)?;
-
-                let write_len = std::cmp::min(
-                    data.len(),
-                    (PAGE_SIZE - (gpa & (PAGE_SIZE - 1))).try_into().unwrap(),
-                );
-
+            // Whether the guest maps the pages writable is not checked, so
+            // that software breakpoints can be written to its code
+            let root_pt = self.get_root_pt()?;
+            let runs = mem_access
+                .translate(gva, data_len, root_pt)
+                .map_err(ProcessDebugRequestError::WriteMemory)?;
+            for (gpa, write_len) in runs {
                 // Use the memory access to write to guest memory
                 mem_access
                     .write(&data[..write_len], gpa)
                     .map_err(ProcessDebugRequestError::WriteMemory)?;
 
                 data = &data[write_len..];
-                gva += write_len as u64;
             }
 
             Ok(())
@@ -861,6 +996,7 @@ pub(super) mod debug {
 #[allow(clippy::needless_range_loop)]
 mod tests {
     use std::sync::{Arc, Mutex};
+    use std::time::{SystemTime, UNIX_EPOCH};
 
     use hyperlight_common::vmem::{self, BasicMapping, Mapping, MappingKind};
     use rand::RngExt;
@@ -1422,7 +1558,7 @@ mod tests {
         #[cfg(any(crashdump, gdb))]
         let rt_cfg: SandboxRuntimeConfig = Default::default();
 
-        let mut layout = SandboxMemoryLayout::new(config, code.len(), 4096, None).unwrap();
+        let mut layout = SandboxMemoryLayout::new(config, code.len(), 4096, None, 0, 0).unwrap();
 
         let pt_base_gpa = layout.get_pt_base_gpa();
         let pt_buf = GuestPageTableBuffer::new(pt_base_gpa as usize);
@@ -1543,6 +1679,43 @@ mod tests {
     // Tests
     // ==========================================================================
 
+    #[cfg_attr(feature = "hw-interrupts", ignore)]
+    #[test]
+    fn set_regs_and_fpu_sets_both() {
+        const CODE: [u8; 2] = [0x50, 0xf4];
+        let hyperlight_vm = hyperlight_vm(&CODE);
+
+        let regs = dirty_regs();
+        let fpu = dirty_fpu();
+        hyperlight_vm.vm.set_regs_and_fpu(&regs, &fpu).unwrap();
+
+        assert_eq!(hyperlight_vm.vm.regs().unwrap(), regs);
+        let mut got_fpu = hyperlight_vm.vm.fpu().unwrap();
+        let mut expected_fpu = fpu;
+        normalize_fpu_mxcsr_for_kvm(&mut got_fpu, fpu.mxcsr);
+        for i in 0..8 {
+            expected_fpu.fpr[i][10..16].copy_from_slice(&got_fpu.fpr[i][10..16]);
+        }
+        assert_eq!(got_fpu, expected_fpu);
+    }
+
+    #[test]
+    fn wall_clock_base_is_before_now() {
+        const CODE: [u8; 2] = [0x50, 0xf4];
+        let hyperlight_vm = hyperlight_vm(&CODE);
+
+        let base = hyperlight_vm.wall_clock_base();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64;
+        // The clock started when the VM was created, moments ago
+        assert!(base <= now);
+        assert!(now - base < 60_000_000_000);
+        let clock = hyperlight_vm.vm.guest_clock().unwrap().unwrap();
+        assert!(hyperlight_vm.vm.guest_clock().unwrap().unwrap() >= clock);
+    }
+
     #[cfg_attr(feature = "hw-interrupts", ignore)]
     #[test]
     fn reset_vcpu_simple() {
@@ -2118,7 +2291,7 @@ mod tests {
             // These are in the output_data region which starts at a known offset.
             // We use a default SandboxConfiguration to get the same layout as create_test_vm_context.
             let config: SandboxConfiguration = Default::default();
-            let layout = SandboxMemoryLayout::new(config, 512, 4096, None).unwrap();
+            let layout = SandboxMemoryLayout::new(config, 512, 4096, None, 0, 0).unwrap();
             let fxsave_offset = layout.get_output_data_buffer_scratch_host_offset();
             let fxsave_gva = layout.get_output_data_buffer_gva();
             let flag_gva = fxsave_gva + 512;
@@ -18,17 +18,29 @@ limitations under the License.
 #[cfg(gdb)]
 pub(crate) mod gdb;
 
+/// Disassembly of the guest instructions that fault
+pub mod disassembly;
+
 /// Abstracts over different hypervisor register representations
 pub(crate) mod regs;
 
 pub(crate) mod virtual_machine;
 
+/// Checks of whether this host can run sandboxes
+pub mod preflight;
+
 #[cfg(target_os = "windows")]
 /// Hyperlight Surrogate Process
 pub(crate) mod surrogate_process;
 #[cfg(target_os = "windows")]
 /// Hyperlight Surrogate Process
 pub(crate) mod surrogate_process_manager;
+/// Re-export for `SurrogatePoolConfig` type
+#[cfg(target_os = "windows")]
+pub use surrogate_process_manager::{
+    SurrogateIsolation, SurrogatePoolConfig, SurrogatePoolStats, check_surrogate_pool_health,
+    init_surrogate_pool, resize_surrogate_pool, surrogate_pool_stats, trim_surrogate_pool,
+};
 /// Safe wrappers around windows types like `PSTR`
 #[cfg(target_os = "windows")]
 pub mod wrappers;
@@ -40,11 +52,19 @@ pub(crate) mod hyperlight_vm;
 
 use std::fmt::Debug;
 #[cfg(any(kvm, mshv3))]
+use std::sync::Mutex;
+#[cfg(any(kvm, mshv3))]
 use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
 #[cfg(target_os = "windows")]
 use std::sync::atomic::{AtomicU8, Ordering};
 #[cfg(any(kvm, mshv3))]
-use std::time::Duration;
+use std::time::Instant;
+use std::time::{Duration, SystemTime};
+
+/// How long the vcpu may take to stop after being signalled before a
+/// warning is logged
+#[cfg(any(kvm, mshv3))]
+const SLOW_INTERRUPT_THRESHOLD: Duration = Duration::from_millis(100);
 
 /// A trait for platform-specific interrupt handle implementation details
 pub(crate) trait InterruptHandleImpl: InterruptHandle {
@@ -73,6 +93,31 @@ pub(crate) trait InterruptHandleImpl: InterruptHandle {
     // Clear the debug interrupt request flag
     #[cfg(gdb)]
     fn clear_debug_interrupt(&self);
+
+    /// Interrupt the vcpu so that it takes a core dump and carries on
+    /// running. Returns `false` if the vcpu is not running, in which case
+    /// the dump is taken the next time it is scheduled.
+    #[cfg(crashdump)]
+    fn request_core_dump(&self) -> bool;
+
+    /// Check if a core dump was requested
+    #[cfg(crashdump)]
+    fn is_core_dump_requested(&self) -> bool;
+
+    /// Clear the core dump request flag
+    #[cfg(crashdump)]
+    fn clear_core_dump_request(&self);
+
+    /// Interrupt the vcpu so that the profiler can sample its stack, after
+    /// which it carries on running. Returns `false` if the vcpu is not
+    /// running.
+    fn request_sample(&self) -> bool;
+
+    /// Check if a profiler sample was requested
+    fn is_sample_requested(&self) -> bool;
+
+    /// Clear the profiler sample request flag
+    fn clear_sample_request(&self);
 }
 
 /// A trait for handling interrupts to a sandbox's vcpu
@@ -100,6 +145,38 @@ pub trait InterruptHandle: Send + Sync + Debug {
 
     /// Returns true if the corresponding sandbox has been dropped
     fn dropped(&self) -> bool;
+
+    /// Returns how the last [`kill()`](Self::kill) interrupted the vcpu,
+    /// or `None` if the sandbox has not been killed or the platform does
+    /// not record it. Only Linux records it, as interrupting the vcpu there
+    /// can take several signals.
+    fn last_interrupt(&self) -> Option<InterruptTrace> {
+        None
+    }
+}
+
+/// How a [`kill()`](InterruptHandle::kill) interrupted the vcpu, as
+/// returned by [`InterruptHandle::last_interrupt()`], for finding out why
+/// a kill was slow or appeared to fail.
+///
+/// On Linux, the vcpu thread is signalled until the vcpu stops running,
+/// every [`SandboxConfiguration::set_interrupt_retry_delay()`](crate::sandbox::SandboxConfiguration::set_interrupt_retry_delay),
+/// and a structured warning is logged when it takes more than 100ms.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InterruptTrace {
+    /// When the kill was requested
+    pub requested_at: SystemTime,
+    /// Whether the vcpu was running when the kill was requested. If it
+    /// was not, it is cancelled the next time it would run.
+    pub vcpu_running: bool,
+    /// How many signals were sent to the vcpu thread
+    pub signals_sent: u32,
+    /// The ids of the threads that were signalled, in order. There is more
+    /// than one if the vcpu moved to another thread while being signalled.
+    pub threads: Vec<u64>,
+    /// How long the vcpu took to stop running, or for the kill to be
+    /// superseded by a new guest call, if it was running
+    pub time_to_exit: Option<Duration>,
 }
 
 #[cfg(any(kvm, mshv3))]
@@ -108,6 +185,8 @@ pub(super) struct LinuxInterruptHandle {
     /// Atomic value packing vcpu execution state.
     ///
     /// Bit layout:
+    /// - Bit 4: SAMPLE_BIT - set when the profiler requests a sample
+    /// - Bit 3: CORE_DUMP_BIT - set when a live core dump is requested
     /// - Bit 2: DEBUG_INTERRUPT_BIT - set when debugger interrupt is requested
     /// - Bit 1: RUNNING_BIT - set when vcpu is actively running
     /// - Bit 0: CANCEL_BIT - set when cancellation has been requested
@@ -130,6 +209,9 @@ pub(super) struct LinuxInterruptHandle {
 
     /// Offset from SIGRTMIN for the signal used to interrupt the vcpu thread.
     sig_rt_min_offset: u8,
+
+    /// How the last kill() interrupted the vcpu
+    pub(super) last_interrupt: Mutex<Option<InterruptTrace>>,
 }
 
 #[cfg(any(kvm, mshv3))]
@@ -138,6 +220,9 @@ impl LinuxInterruptHandle {
     const CANCEL_BIT: u8 = 1 << 0;
     #[cfg(gdb)]
     const DEBUG_INTERRUPT_BIT: u8 = 1 << 2;
+    #[cfg(crashdump)]
+    const CORE_DUMP_BIT: u8 = 1 << 3;
+    const SAMPLE_BIT: u8 = 1 << 4;
 
     /// Get the running, cancel and debug flags atomically.
     ///
@@ -152,15 +237,37 @@ impl LinuxInterruptHandle {
         let debug = state & Self::DEBUG_INTERRUPT_BIT != 0;
         #[cfg(not(gdb))]
         let debug = false;
+        // A core dump request interrupts the vcpu the same way as a debugger
+        #[cfg(crashdump)]
+        let debug = debug || state & Self::CORE_DUMP_BIT != 0;
+        // And so does a profiler sample
+        let debug = debug || state & Self::SAMPLE_BIT != 0;
         (running, cancel, debug)
     }
 
     fn send_signal(&self) -> bool {
+        self.signal_vcpu().signals_sent > 0
+    }
+
+    /// Signal the vcpu thread until the vcpu stops running or the request
+    /// is withdrawn, and describe how it went
+    fn signal_vcpu(&self) -> InterruptTrace {
         let signal_number = libc::SIGRTMIN() + self.sig_rt_min_offset as libc::c_int;
-        let mut sent_signal = false;
+        let start = Instant::now();
+        let mut trace = InterruptTrace {
+            requested_at: SystemTime::now(),
+            vcpu_running: false,
+            signals_sent: 0,
+            threads: Vec::new(),
+            time_to_exit: None,
+        };
+        let mut warned = false;
 
         loop {
             let (running, cancel, debug) = self.get_running_cancel_debug();
+            if trace.signals_sent == 0 {
+                trace.vcpu_running = running;
+            }
 
             // Check if we should continue sending signals
             // Exit if not running OR if neither cancel nor debug_interrupt is set
@@ -171,16 +278,34 @@ impl LinuxInterruptHandle {
             }
 
             tracing::info!("Sending signal to kill vcpu thread...");
-            sent_signal = true;
             // Acquire ordering to synchronize with the Release store in set_tid()
             // This ensures we see the correct tid value for the currently running vcpu
+            let tid = self.tid.load(Ordering::Acquire);
+            if trace.threads.last() != Some(&tid) {
+                trace.threads.push(tid);
+            }
             unsafe {
-                libc::pthread_kill(self.tid.load(Ordering::Acquire) as _, signal_number);
+                libc::pthread_kill(tid as _, signal_number);
+            }
+            trace.signals_sent += 1;
+
+            let elapsed = start.elapsed();
+            if !warned && elapsed >= SLOW_INTERRUPT_THRESHOLD {
+                warned = true;
+                tracing::warn!(
+                    signals_sent = trace.signals_sent,
+                    elapsed_ms = elapsed.as_millis() as u64,
+                    tid,
+                    "The vcpu is slow to stop after being interrupted"
+                );
             }
             std::thread::sleep(self.retry_delay);
         }
 
-        sent_signal
+        if trace.vcpu_running {
+            trace.time_to_exit = Some(start.elapsed());
+        }
+        trace
     }
 }
 
@@ -236,6 +361,36 @@ impl InterruptHandleImpl for LinuxInterruptHandle {
             .fetch_and(!Self::DEBUG_INTERRUPT_BIT, Ordering::Release);
     }
 
+    #[cfg(crashdump)]
+    fn request_core_dump(&self) -> bool {
+        self.state.fetch_or(Self::CORE_DUMP_BIT, Ordering::Release);
+        self.send_signal()
+    }
+
+    #[cfg(crashdump)]
+    fn is_core_dump_requested(&self) -> bool {
+        self.state.load(Ordering::Acquire) & Self::CORE_DUMP_BIT != 0
+    }
+
+    #[cfg(crashdump)]
+    fn clear_core_dump_request(&self) {
+        self.state
+            .fetch_and(!Self::CORE_DUMP_BIT, Ordering::Release);
+    }
+
+    fn request_sample(&self) -> bool {
+        self.state.fetch_or(Self::SAMPLE_BIT, Ordering::Release);
+        self.send_signal()
+    }
+
+    fn is_sample_requested(&self) -> bool {
+        self.state.load(Ordering::Acquire) & Self::SAMPLE_BIT != 0
+    }
+
+    fn clear_sample_request(&self) {
+        self.state.fetch_and(!Self::SAMPLE_BIT, Ordering::Release);
+    }
+
     fn set_dropped(&self) {
         // Release ordering to ensure all VM cleanup operations are visible
         // to any thread that checks dropped() via Acquire
@@ -251,7 +406,14 @@ impl InterruptHandle for LinuxInterruptHandle {
         self.state.fetch_or(Self::CANCEL_BIT, Ordering::Release);
 
         // Send signals to interrupt the vcpu if it's currently running
-        self.send_signal()
+        let trace = self.signal_vcpu();
+        let interrupted = trace.signals_sent > 0;
+        crate::usdt::probe!("kill", interrupted);
+        *self
+            .last_interrupt
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(trace);
+        interrupted
     }
 
     #[cfg(gdb)]
@@ -265,6 +427,13 @@ impl InterruptHandle for LinuxInterruptHandle {
         // This ensures we see all VM cleanup operations that happened before drop
         self.dropped.load(Ordering::Acquire)
     }
+
+    fn last_interrupt(&self) -> Option<InterruptTrace> {
+        self.last_interrupt
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
 }
 
 #[cfg(target_os = "windows")]
@@ -273,6 +442,8 @@ pub(super) struct WindowsInterruptHandle {
     /// Atomic value packing vcpu execution state.
     ///
     /// Bit layout:
+    /// - Bit 4: SAMPLE_BIT - set when the profiler requests a sample
+    /// - Bit 3: CORE_DUMP_BIT - set when a live core dump is requested
     /// - Bit 2: DEBUG_INTERRUPT_BIT - set when debugger interrupt is requested
     /// - Bit 1: RUNNING_BIT - set when vcpu is actively running
     /// - Bit 0: CANCEL_BIT - set when cancellation has been requested
@@ -320,6 +491,9 @@ impl WindowsInterruptHandle {
     const CANCEL_BIT: u8 = 1 << 0;
     #[cfg(gdb)]
     const DEBUG_INTERRUPT_BIT: u8 = 1 << 2;
+    #[cfg(crashdump)]
+    const CORE_DUMP_BIT: u8 = 1 << 3;
+    const SAMPLE_BIT: u8 = 1 << 4;
 }
 
 #[cfg(target_os = "windows")]
@@ -364,6 +538,80 @@ impl InterruptHandleImpl for WindowsInterruptHandle {
             .fetch_and(!Self::DEBUG_INTERRUPT_BIT, Ordering::Release);
     }
 
+    #[cfg(crashdump)]
+    fn request_core_dump(&self) -> bool {
+        use windows::Win32::System::Hypervisor::WHvCancelRunVirtualProcessor;
+
+        self.state.fetch_or(Self::CORE_DUMP_BIT, Ordering::Release);
+
+        // Acquire ordering to synchronize with the Release in set_running()
+        let state = self.state.load(Ordering::Acquire);
+        if state & Self::RUNNING_BIT == 0 {
+            return false;
+        }
+
+        // Take read lock to prevent race with WHvDeletePartition in set_dropped()
+        let guard = match self.partition_state.read() {
+            Ok(guard) => guard,
+            Err(e) => {
+                tracing::error!("Failed to acquire partition_state read lock: {}", e);
+                return false;
+            }
+        };
+
+        if guard.dropped {
+            return false;
+        }
+
+        unsafe { WHvCancelRunVirtualProcessor(guard.handle, 0, 0).is_ok() }
+    }
+
+    #[cfg(crashdump)]
+    fn is_core_dump_requested(&self) -> bool {
+        self.state.load(Ordering::Acquire) & Self::CORE_DUMP_BIT != 0
+    }
+
+    #[cfg(crashdump)]
+    fn clear_core_dump_request(&self) {
+        self.state
+            .fetch_and(!Self::CORE_DUMP_BIT, Ordering::Release);
+    }
+
+    fn request_sample(&self) -> bool {
+        use windows::Win32::System::Hypervisor::WHvCancelRunVirtualProcessor;
+
+        self.state.fetch_or(Self::SAMPLE_BIT, Ordering::Release);
+
+        // Acquire ordering to synchronize with the Release in set_running()
+        let state = self.state.load(Ordering::Acquire);
+        if state & Self::RUNNING_BIT == 0 {
+            return false;
+        }
+
+        // Take read lock to prevent race with WHvDeletePartition in set_dropped()
+        let guard = match self.partition_state.read() {
+            Ok(guard) => guard,
+            Err(e) => {
+                tracing::error!("Failed to acquire partition_state read lock: {}", e);
+                return false;
+            }
+        };
+
+        if guard.dropped {
+            return false;
+        }
+
+        unsafe { WHvCancelRunVirtualProcessor(guard.handle, 0, 0).is_ok() }
+    }
+
+    fn is_sample_requested(&self) -> bool {
+        self.state.load(Ordering::Acquire) & Self::SAMPLE_BIT != 0
+    }
+
+    fn clear_sample_request(&self) {
+        self.state.fetch_and(!Self::SAMPLE_BIT, Ordering::Release);
+    }
+
     fn set_dropped(&self) {
         // Take write lock to:
         // 1. Wait for any in-flight kill() calls (holding read locks) to complete
@@ -394,7 +642,9 @@ impl InterruptHandle for WindowsInterruptHandle {
         // Acquire ordering to synchronize with the Release in set_running()
         // This ensures we see the running state set by the vcpu thread
         let state = self.state.load(Ordering::Acquire);
-        if state & Self::RUNNING_BIT == 0 {
+        let running = state & Self::RUNNING_BIT != 0;
+        crate::etw::kill(running);
+        if !running {
             return false;
         }
 
@@ -526,3 +776,33 @@ pub(crate) mod tests {
         Ok(())
     }
 }
+
+#[cfg(all(test, any(kvm, mshv3)))]
+mod interrupt_tests {
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64};
+    use std::time::Duration;
+
+    use super::{InterruptHandle, LinuxInterruptHandle};
+
+    #[test]
+    fn kill_records_an_interrupt_trace() {
+        let handle = LinuxInterruptHandle {
+            state: AtomicU8::new(0),
+            tid: AtomicU64::new(0),
+            dropped: AtomicBool::new(false),
+            retry_delay: Duration::from_micros(500),
+            sig_rt_min_offset: 0,
+            last_interrupt: Mutex::new(None),
+        };
+        assert!(handle.last_interrupt().is_none());
+
+        // A vcpu that isn't running is not signalled
+        assert!(!handle.kill());
+        let trace = handle.last_interrupt().unwrap();
+        assert!(!trace.vcpu_running);
+        assert_eq!(trace.signals_sent, 0);
+        assert!(trace.threads.is_empty());
+        assert_eq!(trace.time_to_exit, None);
+    }
+}
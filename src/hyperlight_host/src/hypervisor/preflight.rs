@@ -0,0 +1,415 @@
+/*
+Copyright 2025 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Checks of whether this host can run sandboxes, and if not, why.
+//!
+//! [`preflight`] runs the checks the hypervisor backends make before
+//! creating a sandbox, one at a time, and reports each of them with what
+//! it found. It is meant for hosts where sandbox creation fails with
+//! [`NoHypervisorFound`](crate::HyperlightError::NoHypervisorFound), most
+//! often a VM that does not expose virtualization extensions to its
+//! guests, or a user that can't open the hypervisor's device.
+
+use std::fmt;
+
+use super::virtual_machine::{HypervisorType, get_available_hypervisor};
+
+/// How a [`PreflightCheck`] turned out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CheckStatus {
+    /// The check passed
+    Passed,
+    /// The check failed, but does not keep sandboxes from being created
+    Warning,
+    /// The check failed, and sandboxes can't be created until it passes
+    Failed,
+}
+
+/// One of the checks of a [`PreflightReport`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PreflightCheck {
+    /// What was checked, e.g. `KVM_CAP_USER_MEMORY`
+    pub name: &'static str,
+    /// How the check turned out
+    pub status: CheckStatus,
+    /// What was found, and for a failed check, how to fix it
+    pub detail: String,
+}
+
+impl PreflightCheck {
+    fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// The checks [`preflight`] made, in the order they were made
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PreflightReport {
+    checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    /// All the checks
+    pub fn checks(&self) -> &[PreflightCheck] {
+        &self.checks
+    }
+
+    /// The checks that keep sandboxes from being created
+    pub fn failures(&self) -> impl Iterator<Item = &PreflightCheck> {
+        self.checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Failed)
+    }
+
+    /// Whether sandboxes can be created on this host
+    pub fn is_ok(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    fn push(&mut self, check: PreflightCheck) {
+        self.checks.push(check);
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let status = match check.status {
+                CheckStatus::Passed => "ok",
+                CheckStatus::Warning => "warn",
+                CheckStatus::Failed => "FAIL",
+            };
+            writeln!(f, "[{:>4}] {}: {}", status, check.name, check.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check whether this host can run sandboxes.
+///
+/// When a hypervisor is available, only the checks of its backend are
+/// made. When none is, the checks of every backend this build supports
+/// are, so that the report shows why each of them can't be used.
+pub fn preflight() -> PreflightReport {
+    let available = *get_available_hypervisor();
+    let mut report = PreflightReport::default();
+
+    #[cfg(target_arch = "aarch64")]
+    report.push(PreflightCheck::new(
+        "Architecture",
+        CheckStatus::Failed,
+        "the hypervisor backends are not implemented on aarch64 yet",
+    ));
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    report.push(cpu_check(CpuFeatures::detect(), available.is_some()));
+
+    #[cfg(kvm)]
+    if available.is_none() || available == Some(HypervisorType::Kvm) {
+        kvm_checks(&mut report);
+    }
+    #[cfg(mshv3)]
+    if available.is_none() || available == Some(HypervisorType::Mshv) {
+        mshv_checks(&mut report);
+    }
+    #[cfg(target_os = "windows")]
+    whp_checks(&mut report);
+
+    report.push(match available {
+        Some(hypervisor) => PreflightCheck::new(
+            "Hypervisor",
+            CheckStatus::Passed,
+            format!("sandboxes will use {}", name(hypervisor)),
+        ),
+        None => PreflightCheck::new(
+            "Hypervisor",
+            CheckStatus::Failed,
+            "no hypervisor is available, so creating a sandbox fails with NoHypervisorFound",
+        ),
+    });
+    report
+}
+
+/// The name a hypervisor is known by
+fn name(hypervisor: HypervisorType) -> &'static str {
+    match hypervisor {
+        #[cfg(kvm)]
+        HypervisorType::Kvm => "KVM",
+        #[cfg(mshv3)]
+        HypervisorType::Mshv => "MSHV",
+        #[cfg(target_os = "windows")]
+        HypervisorType::Whp => "the Windows Hypervisor Platform",
+    }
+}
+
+/// The CPUID bits that tell whether the CPU can run VMs, and whether this
+/// host is itself one
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[derive(Clone, Copy, Debug)]
+struct CpuFeatures {
+    /// VT-x or AMD-V is exposed
+    virtualization: bool,
+    /// The host runs under a hypervisor
+    hypervisor: bool,
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+impl CpuFeatures {
+    fn detect() -> Self {
+        use std::arch::x86_64::__cpuid;
+
+        // SAFETY: CPUID is available on all x86_64 CPUs
+        let (leaf1, extended) = unsafe { (__cpuid(1), __cpuid(0x8000_0000)) };
+        // SAFETY: as above, and the leaf was checked to be supported
+        let svm =
+            extended.eax >= 0x8000_0001 && unsafe { __cpuid(0x8000_0001) }.ecx & (1 << 2) != 0;
+        Self {
+            virtualization: leaf1.ecx & (1 << 5) != 0 || svm,
+            hypervisor: leaf1.ecx & (1 << 31) != 0,
+        }
+    }
+}
+
+/// Whether the CPU lets this host run VMs. In the root partition of the
+/// Microsoft Hypervisor the extensions are hidden, but MSHV works, so
+/// missing extensions only fail the check if no hypervisor is available.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn cpu_check(cpu: CpuFeatures, available: bool) -> PreflightCheck {
+    const NAME: &str = "Virtualization extensions";
+    match cpu {
+        CpuFeatures {
+            virtualization: true,
+            hypervisor: true,
+        } => PreflightCheck::new(
+            NAME,
+            CheckStatus::Warning,
+            "running in a VM whose host exposes VT-x or AMD-V to it; sandboxes work, but VM \
+             exits are slower under nested virtualization",
+        ),
+        CpuFeatures {
+            virtualization: true,
+            hypervisor: false,
+        } => PreflightCheck::new(NAME, CheckStatus::Passed, "the CPU exposes VT-x or AMD-V"),
+        CpuFeatures { .. } if available => PreflightCheck::new(
+            NAME,
+            CheckStatus::Passed,
+            "VT-x and AMD-V are hidden by the hypervisor this host runs under, which runs VMs for it",
+        ),
+        CpuFeatures {
+            hypervisor: true, ..
+        } => PreflightCheck::new(
+            NAME,
+            CheckStatus::Failed,
+            "running in a VM whose host does not expose VT-x or AMD-V to it; enable nested \
+             virtualization on the host, e.g. with `Set-VMProcessor -ExposeVirtualizationExtensions \
+             $true` for Hyper-V, or the `nested=1` parameter of kvm_intel or kvm_amd and a \
+             `host-passthrough` CPU for KVM",
+        ),
+        CpuFeatures { .. } => PreflightCheck::new(
+            NAME,
+            CheckStatus::Failed,
+            "the CPU does not expose VT-x or AMD-V; enable virtualization in the firmware settings",
+        ),
+    }
+}
+
+/// Check that `path` can be opened for reading and writing, the way the
+/// backends open it, and explain why not if it can't
+#[cfg(any(kvm, mshv3))]
+fn device_check(name: &'static str, path: &str, missing: &str) -> PreflightCheck {
+    use std::io::ErrorKind;
+
+    match std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+    {
+        Ok(_) => PreflightCheck::new(name, CheckStatus::Passed, format!("{} can be opened", path)),
+        Err(e) if e.kind() == ErrorKind::NotFound => PreflightCheck::new(
+            name,
+            CheckStatus::Failed,
+            format!("{} does not exist; {}", path, missing),
+        ),
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => PreflightCheck::new(
+            name,
+            CheckStatus::Failed,
+            format!(
+                "the current user can't open {}; give them read and write access to it, e.g. \
+                 by adding them to the group that owns it",
+                path
+            ),
+        ),
+        Err(e) => PreflightCheck::new(
+            name,
+            CheckStatus::Failed,
+            format!("{} can't be opened: {}", path, e),
+        ),
+    }
+}
+
+#[cfg(kvm)]
+fn kvm_checks(report: &mut PreflightReport) {
+    use kvm_ioctls::Cap::UserMemory;
+    use kvm_ioctls::Kvm;
+
+    let device = device_check(
+        "/dev/kvm",
+        "/dev/kvm",
+        "load the kvm_intel or kvm_amd module, which needs VT-x or AMD-V",
+    );
+    let opened = device.status == CheckStatus::Passed;
+    report.push(device);
+    if !opened {
+        return;
+    }
+
+    let kvm = match Kvm::new() {
+        Ok(kvm) => kvm,
+        Err(e) => {
+            report.push(PreflightCheck::new(
+                "KVM",
+                CheckStatus::Failed,
+                format!("KVM can't be used: {}", e),
+            ));
+            return;
+        }
+    };
+    report.push(match kvm.get_api_version() {
+        12 => PreflightCheck::new("KVM API version", CheckStatus::Passed, "version 12"),
+        version => PreflightCheck::new(
+            "KVM API version",
+            CheckStatus::Failed,
+            format!("version {}, expected 12", version),
+        ),
+    });
+    report.push(if kvm.check_extension(UserMemory) {
+        PreflightCheck::new("KVM_CAP_USER_MEMORY", CheckStatus::Passed, "supported")
+    } else {
+        PreflightCheck::new(
+            "KVM_CAP_USER_MEMORY",
+            CheckStatus::Failed,
+            "not supported, so guest memory can't be mapped from the host",
+        )
+    });
+}
+
+#[cfg(mshv3)]
+fn mshv_checks(report: &mut PreflightReport) {
+    use mshv_ioctls::Mshv;
+
+    let device = device_check(
+        "/dev/mshv",
+        "/dev/mshv",
+        "it is only present in the root partition of a host running the Microsoft Hypervisor, \
+         with the mshv driver loaded",
+    );
+    let opened = device.status == CheckStatus::Passed;
+    report.push(device);
+    if !opened {
+        return;
+    }
+
+    report.push(match Mshv::new() {
+        Ok(_) => PreflightCheck::new("MSHV", CheckStatus::Passed, "the driver is usable"),
+        Err(e) => PreflightCheck::new(
+            "MSHV",
+            CheckStatus::Failed,
+            format!("MSHV can't be used: {}", e),
+        ),
+    });
+}
+
+#[cfg(target_os = "windows")]
+fn whp_checks(report: &mut PreflightReport) {
+    use std::ffi::c_void;
+
+    use windows::Win32::System::Hypervisor::{
+        WHV_CAPABILITY, WHvCapabilityCodeHypervisorPresent, WHvGetCapability,
+    };
+
+    const NAME: &str = "Windows Hypervisor Platform";
+    let mut capability = WHV_CAPABILITY::default();
+    // SAFETY: the capability is valid for its size
+    let result = unsafe {
+        WHvGetCapability(
+            WHvCapabilityCodeHypervisorPresent,
+            &mut capability as *mut _ as *mut c_void,
+            size_of::<WHV_CAPABILITY>() as u32,
+            None,
+        )
+    };
+    report.push(match result {
+        // SAFETY: the call filled in the capability it was asked for
+        Ok(()) if unsafe { capability.HypervisorPresent.as_bool() } => {
+            PreflightCheck::new(NAME, CheckStatus::Passed, "the hypervisor is running")
+        }
+        Ok(()) => PreflightCheck::new(
+            NAME,
+            CheckStatus::Failed,
+            "the hypervisor is not running; enable virtualization in the firmware settings, or \
+             in a Hyper-V VM, run `Set-VMProcessor -ExposeVirtualizationExtensions $true` on \
+             its host",
+        ),
+        Err(e) => PreflightCheck::new(
+            NAME,
+            CheckStatus::Failed,
+            format!(
+                "the platform can't be used ({}); enable it with `Enable-WindowsOptionalFeature \
+                 -Online -FeatureName HypervisorPlatform` and restart",
+                e
+            ),
+        ),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_agrees_with_is_hypervisor_present() {
+        let report = preflight();
+        assert_eq!(report.is_ok(), crate::is_hypervisor_present(), "{}", report);
+        let last = report.checks().last().unwrap();
+        assert_eq!(last.name, "Hypervisor");
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn missing_extensions_only_fail_without_a_hypervisor() {
+        let nested = CpuFeatures {
+            virtualization: false,
+            hypervisor: true,
+        };
+        assert_eq!(cpu_check(nested, true).status, CheckStatus::Passed);
+        let check = cpu_check(nested, false);
+        assert_eq!(check.status, CheckStatus::Failed);
+        assert!(check.detail.contains("nested"));
+
+        let bare_metal = CpuFeatures {
+            virtualization: false,
+            hypervisor: false,
+        };
+        let check = cpu_check(bare_metal, false);
+        assert_eq!(check.status, CheckStatus::Failed);
+        assert!(check.detail.contains("firmware"));
+    }
+}
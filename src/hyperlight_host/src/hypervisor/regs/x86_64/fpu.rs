@@ -20,7 +20,16 @@ use std::collections::HashSet;
 #[cfg(kvm)]
 use kvm_bindings::kvm_fpu;
 #[cfg(mshv3)]
-use mshv_bindings::FloatingPointUnit;
+use mshv_bindings::{
+    FloatingPointUnit, hv_register_assoc, hv_register_name,
+    hv_register_name_HV_X64_REGISTER_FP_CONTROL_STATUS, hv_register_name_HV_X64_REGISTER_FP_MMX0,
+    hv_register_name_HV_X64_REGISTER_XMM_CONTROL_STATUS, hv_register_name_HV_X64_REGISTER_XMM0,
+    hv_register_value, hv_u128, hv_x64_fp_control_status_register,
+    hv_x64_fp_control_status_register__bindgen_ty_1,
+    hv_x64_fp_control_status_register__bindgen_ty_1__bindgen_ty_1, hv_x64_fp_register,
+    hv_x64_xmm_control_status_register, hv_x64_xmm_control_status_register__bindgen_ty_1,
+    hv_x64_xmm_control_status_register__bindgen_ty_1__bindgen_ty_1,
+};
 
 #[cfg(target_os = "windows")]
 use super::Align16;
@@ -97,6 +106,83 @@ impl From<&CommonFpu> for FloatingPointUnit {
     }
 }
 
+#[cfg(mshv3)]
+pub(crate) const MSHV_FPU_NAMES_LEN: usize = 26;
+
+/// The FPU registers as one batch for `HVCALL_SET_VP_REGISTERS`, laid out
+/// as `VcpuFd::set_fpu` lays them out
+#[cfg(mshv3)]
+impl From<&CommonFpu> for [hv_register_assoc; MSHV_FPU_NAMES_LEN] {
+    fn from(fpu: &CommonFpu) -> Self {
+        let reg128 = |bytes: &[u8; 16]| {
+            let value = u128::from_le_bytes(*bytes);
+            hv_u128 {
+                low_part: value as u64,
+                high_part: (value >> 64) as u64,
+            }
+        };
+        let assoc = |name: hv_register_name, value| hv_register_assoc {
+            name,
+            value,
+            ..Default::default()
+        };
+
+        let mut regs = [hv_register_assoc::default(); MSHV_FPU_NAMES_LEN];
+        for (i, xmm) in fpu.xmm.iter().enumerate() {
+            regs[i] = assoc(
+                hv_register_name_HV_X64_REGISTER_XMM0 + i as u32,
+                hv_register_value {
+                    reg128: reg128(xmm),
+                },
+            );
+        }
+        for (i, fpr) in fpu.fpr.iter().enumerate() {
+            regs[16 + i] = assoc(
+                hv_register_name_HV_X64_REGISTER_FP_MMX0 + i as u32,
+                hv_register_value {
+                    fp: hv_x64_fp_register {
+                        as_uint128: reg128(fpr),
+                    },
+                },
+            );
+        }
+        regs[24] = assoc(
+            hv_register_name_HV_X64_REGISTER_FP_CONTROL_STATUS,
+            hv_register_value {
+                fp_control_status: hv_x64_fp_control_status_register {
+                    __bindgen_anon_1: hv_x64_fp_control_status_register__bindgen_ty_1 {
+                        fp_control: fpu.fcw,
+                        fp_status: fpu.fsw,
+                        fp_tag: fpu.ftwx,
+                        reserved: 0,
+                        last_fp_op: fpu.last_opcode,
+                        __bindgen_anon_1:
+                            hv_x64_fp_control_status_register__bindgen_ty_1__bindgen_ty_1 {
+                                last_fp_rip: fpu.last_ip,
+                            },
+                    },
+                },
+            },
+        );
+        regs[25] = assoc(
+            hv_register_name_HV_X64_REGISTER_XMM_CONTROL_STATUS,
+            hv_register_value {
+                xmm_control_status: hv_x64_xmm_control_status_register {
+                    __bindgen_anon_1: hv_x64_xmm_control_status_register__bindgen_ty_1 {
+                        xmm_status_control: fpu.mxcsr,
+                        xmm_status_control_mask: 0,
+                        __bindgen_anon_1:
+                            hv_x64_xmm_control_status_register__bindgen_ty_1__bindgen_ty_1 {
+                                last_fp_rdp: fpu.last_dp,
+                            },
+                    },
+                },
+            },
+        );
+        regs
+    }
+}
+
 #[cfg(kvm)]
 impl From<&kvm_fpu> for CommonFpu {
     fn from(kvm_fpu: &kvm_fpu) -> Self {
@@ -382,6 +468,41 @@ mod tests {
         assert_eq!(original, round_tripped);
     }
 
+    #[cfg(mshv3)]
+    #[test]
+    fn mshv_fpu_batch() {
+        let original = sample_common_fpu();
+        let assocs: [hv_register_assoc; MSHV_FPU_NAMES_LEN] = (&original).into();
+
+        assert_eq!(
+            { assocs[1].name },
+            hv_register_name_HV_X64_REGISTER_XMM0 + 1
+        );
+        // SAFETY: the values were written as the unions read here
+        let (xmm1, mmx7, fp_control, mxcsr) = unsafe {
+            let xmm1 = assocs[1].value.reg128;
+            let mmx7 = assocs[23].value.fp.as_uint128;
+            let fp_control = assocs[24]
+                .value
+                .fp_control_status
+                .__bindgen_anon_1
+                .fp_control;
+            let mxcsr = assocs[25]
+                .value
+                .xmm_control_status
+                .__bindgen_anon_1
+                .xmm_status_control;
+            (xmm1, mmx7, fp_control, mxcsr)
+        };
+        let bytes = |value: hv_u128| {
+            (value.low_part as u128 | (value.high_part as u128) << 64).to_le_bytes()
+        };
+        assert_eq!(bytes(xmm1), original.xmm[1]);
+        assert_eq!(bytes(mmx7), original.fpr[7]);
+        assert_eq!(fp_control, original.fcw);
+        assert_eq!(mxcsr, original.mxcsr);
+    }
+
     #[cfg(target_os = "windows")]
     #[test]
     fn round_trip_windows_fpu() {
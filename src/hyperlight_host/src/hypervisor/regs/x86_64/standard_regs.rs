@@ -17,7 +17,18 @@ limitations under the License.
 #[cfg(kvm)]
 use kvm_bindings::kvm_regs;
 #[cfg(mshv3)]
-use mshv_bindings::StandardRegisters;
+use mshv_bindings::{
+    StandardRegisters, hv_register_assoc, hv_register_name_HV_X64_REGISTER_R8,
+    hv_register_name_HV_X64_REGISTER_R9, hv_register_name_HV_X64_REGISTER_R10,
+    hv_register_name_HV_X64_REGISTER_R11, hv_register_name_HV_X64_REGISTER_R12,
+    hv_register_name_HV_X64_REGISTER_R13, hv_register_name_HV_X64_REGISTER_R14,
+    hv_register_name_HV_X64_REGISTER_R15, hv_register_name_HV_X64_REGISTER_RAX,
+    hv_register_name_HV_X64_REGISTER_RBP, hv_register_name_HV_X64_REGISTER_RBX,
+    hv_register_name_HV_X64_REGISTER_RCX, hv_register_name_HV_X64_REGISTER_RDI,
+    hv_register_name_HV_X64_REGISTER_RDX, hv_register_name_HV_X64_REGISTER_RFLAGS,
+    hv_register_name_HV_X64_REGISTER_RIP, hv_register_name_HV_X64_REGISTER_RSI,
+    hv_register_name_HV_X64_REGISTER_RSP, hv_register_value,
+};
 
 #[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub(crate) struct CommonRegisters {
@@ -148,6 +159,41 @@ impl From<&CommonRegisters> for StandardRegisters {
     }
 }
 
+#[cfg(mshv3)]
+pub(crate) const MSHV_REGS_NAMES_LEN: usize = 18;
+
+/// The registers as one batch for `HVCALL_SET_VP_REGISTERS`
+#[cfg(mshv3)]
+impl From<&CommonRegisters> for [hv_register_assoc; MSHV_REGS_NAMES_LEN] {
+    fn from(regs: &CommonRegisters) -> Self {
+        [
+            (hv_register_name_HV_X64_REGISTER_RAX, regs.rax),
+            (hv_register_name_HV_X64_REGISTER_RBX, regs.rbx),
+            (hv_register_name_HV_X64_REGISTER_RCX, regs.rcx),
+            (hv_register_name_HV_X64_REGISTER_RDX, regs.rdx),
+            (hv_register_name_HV_X64_REGISTER_RSI, regs.rsi),
+            (hv_register_name_HV_X64_REGISTER_RDI, regs.rdi),
+            (hv_register_name_HV_X64_REGISTER_RSP, regs.rsp),
+            (hv_register_name_HV_X64_REGISTER_RBP, regs.rbp),
+            (hv_register_name_HV_X64_REGISTER_R8, regs.r8),
+            (hv_register_name_HV_X64_REGISTER_R9, regs.r9),
+            (hv_register_name_HV_X64_REGISTER_R10, regs.r10),
+            (hv_register_name_HV_X64_REGISTER_R11, regs.r11),
+            (hv_register_name_HV_X64_REGISTER_R12, regs.r12),
+            (hv_register_name_HV_X64_REGISTER_R13, regs.r13),
+            (hv_register_name_HV_X64_REGISTER_R14, regs.r14),
+            (hv_register_name_HV_X64_REGISTER_R15, regs.r15),
+            (hv_register_name_HV_X64_REGISTER_RIP, regs.rip),
+            (hv_register_name_HV_X64_REGISTER_RFLAGS, regs.rflags),
+        ]
+        .map(|(name, reg64)| hv_register_assoc {
+            name,
+            value: hv_register_value { reg64 },
+            ..Default::default()
+        })
+    }
+}
+
 #[cfg(target_os = "windows")]
 use windows::Win32::System::Hypervisor::*;
 
@@ -379,6 +425,23 @@ mod tests {
         assert_eq!(original, converted);
     }
 
+    #[cfg(mshv3)]
+    #[test]
+    fn mshv_register_batch() {
+        let assocs: [hv_register_assoc; MSHV_REGS_NAMES_LEN] = (&common_regs()).into();
+        let names: std::collections::HashSet<u32> = assocs.iter().map(|assoc| assoc.name).collect();
+        assert_eq!(names.len(), MSHV_REGS_NAMES_LEN);
+        let value = |name| {
+            let assoc = assocs.iter().find(|assoc| { assoc.name } == name).unwrap();
+            // SAFETY: all the registers are 64-bit
+            unsafe { assoc.value.reg64 }
+        };
+        assert_eq!(value(hv_register_name_HV_X64_REGISTER_RAX), 1);
+        assert_eq!(value(hv_register_name_HV_X64_REGISTER_R15), 16);
+        assert_eq!(value(hv_register_name_HV_X64_REGISTER_RIP), 17);
+        assert_eq!(value(hv_register_name_HV_X64_REGISTER_RFLAGS), 18);
+    }
+
     #[cfg(target_os = "windows")]
     #[test]
     fn round_trip_whp_regs() {
@@ -27,7 +27,9 @@ use windows::Win32::System::Memory::{
 };
 use windows::Win32::System::SystemServices::NUMA_NO_PREFERRED_NODE;
 
-use super::surrogate_process_manager::get_surrogate_process_manager;
+use super::surrogate_process_manager::{
+    SurrogateIsolation, get_surrogate_process_manager, surrogate_exit_code,
+};
 use super::wrappers::HandleWrapper;
 use crate::HyperlightError::WindowsAPIError;
 use crate::mem::memory_region::SurrogateMapping;
@@ -50,6 +52,8 @@ pub(super) struct SurrogateProcess {
     pub(crate) mappings: HashMap<usize, HandleMapping>,
     /// The handle to the surrogate process.
     pub(crate) process_handle: HandleWrapper,
+    /// How the process is restricted, which decides whether it is pooled
+    isolation: SurrogateIsolation,
 }
 
 impl SurrogateProcess {
@@ -58,9 +62,22 @@ impl SurrogateProcess {
         Self {
             mappings: HashMap::new(),
             process_handle: HandleWrapper::from(process_handle),
+            isolation: SurrogateIsolation::Standard,
         }
     }
 
+    /// Marks the process as started restricted by `isolation`
+    pub(super) fn with_isolation(mut self, isolation: SurrogateIsolation) -> Self {
+        self.isolation = isolation;
+        self
+    }
+
+    /// The exit code of the surrogate process if it has exited, or `None`
+    /// if it is still running
+    pub(super) fn exit_code(&self) -> Option<u32> {
+        surrogate_exit_code(self.process_handle.into())
+    }
+
     /// Maps a file mapping handle into the surrogate process.
     ///
     /// The `mapping` parameter controls the page protection and guard page
@@ -251,15 +268,17 @@ impl Drop for SurrogateProcess {
         // do because we are in the process of dropping ourselves
         // anyway.
         match get_surrogate_process_manager() {
-            Ok(manager) => match manager.return_surrogate_process(self.process_handle) {
-                Ok(_) => (),
-                Err(e) => {
-                    tracing::error!(
-                        "Failed to return surrogate process to surrogate process manager when dropping : {:?}",
-                        e
-                    );
+            Ok(manager) => {
+                match manager.return_surrogate_process(self.process_handle, self.isolation) {
+                    Ok(_) => manager.publish_usage(),
+                    Err(e) => {
+                        tracing::error!(
+                            "Failed to return surrogate process to surrogate process manager when dropping : {:?}",
+                            e
+                        );
+                    }
                 }
-            },
+            }
             Err(e) => {
                 tracing::error!(
                     "Failed to get surrogate process manager when dropping SurrogateProcess: {:?}",
@@ -19,27 +19,44 @@ use std::fs::File;
 use std::io::Write;
 use std::mem::size_of;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
-use crossbeam_channel::{Receiver, Sender, TryRecvError, unbounded};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TryRecvError, unbounded};
 use rust_embed::RustEmbed;
 use tracing::{Span, error, info, instrument, warn};
-use windows::Win32::Foundation::HANDLE;
-use windows::Win32::Security::SECURITY_ATTRIBUTES;
+use windows::Win32::Foundation::{CloseHandle, HANDLE, STILL_ACTIVE};
+use windows::Win32::Security::{
+    CreateRestrictedToken, CreateWellKnownSid, DISABLE_MAX_PRIVILEGE, GetLengthSid, PSID,
+    SECURITY_ATTRIBUTES, SECURITY_MAX_SID_SIZE, SID_AND_ATTRIBUTES, SetTokenInformation,
+    TOKEN_ADJUST_DEFAULT, TOKEN_ASSIGN_PRIMARY, TOKEN_DUPLICATE, TOKEN_MANDATORY_LABEL,
+    TOKEN_QUERY, TokenIntegrityLevel, WinLowLabelSid,
+};
 use windows::Win32::System::JobObjects::{
-    AssignProcessToJobObject, CreateJobObjectA, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
-    JOBOBJECT_BASIC_LIMIT_INFORMATION, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+    AssignProcessToJobObject, CreateJobObjectA, JOB_OBJECT_CPU_RATE_CONTROL_ENABLE,
+    JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP, JOB_OBJECT_LIMIT_DIE_ON_UNHANDLED_EXCEPTION,
+    JOB_OBJECT_LIMIT_JOB_MEMORY, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    JOB_OBJECT_LIMIT_PROCESS_MEMORY, JOBOBJECT_BASIC_LIMIT_INFORMATION,
+    JOBOBJECT_CPU_RATE_CONTROL_INFORMATION, JOBOBJECT_CPU_RATE_CONTROL_INFORMATION_0,
+    JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JobObjectCpuRateControlInformation,
     JobObjectExtendedLimitInformation, SetInformationJobObject, TerminateJobObject,
 };
+use windows::Win32::System::SystemServices::SE_GROUP_INTEGRITY;
 use windows::Win32::System::Threading::{
-    CREATE_SUSPENDED, CreateProcessA, PROCESS_INFORMATION, STARTUPINFOA,
+    CREATE_SUSPENDED, CreateProcessA, CreateProcessAsUserA, GetCurrentProcess, GetExitCodeProcess,
+    OpenProcessToken, PROCESS_INFORMATION, STARTUPINFOA, TerminateProcess,
 };
 use windows::core::PCSTR;
 
 use super::surrogate_process::SurrogateProcess;
 use super::wrappers::{HandleWrapper, PSTRWrapper};
 use crate::HyperlightError::WindowsAPIError;
-use crate::{Result, log_then_return, new_error};
+use crate::metrics::{
+    METRIC_SURROGATE_DEATHS, METRIC_SURROGATE_POOL_EXHAUSTED, METRIC_SURROGATE_POOL_WAIT_DURATION,
+    METRIC_SURROGATE_PROCESSES,
+};
+use crate::{Result, log_then_return, new_error, perfmon};
 
 // Use the rust-embed crate to embed the hyperlights_surrogate.exe
 // binary in the hyperlight-host library to make dependency management easier.
@@ -68,6 +85,127 @@ const INITIAL_SURROGATES_ENV_VAR: &str = "HYPERLIGHT_INITIAL_SURROGATES";
 /// (512). Defaults to 512 if unset.
 const MAX_SURROGATES_ENV_VAR: &str = "HYPERLIGHT_MAX_SURROGATES";
 
+/// How often a caller waiting for a surrogate process checks whether the
+/// pool has been allowed to grow
+const EXHAUSTED_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How restricted the surrogate process of a sandbox is, set with
+/// [`SandboxConfiguration::set_surrogate_isolation()`](crate::sandbox::SandboxConfiguration::set_surrogate_isolation).
+///
+/// A surrogate process never runs code, as it is kept suspended, but it
+/// holds the sandbox's memory, so restricting it limits what code that
+/// somehow escaped into it could do.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SurrogateIsolation {
+    /// A surrogate from the pool, running as the host process's user
+    #[default]
+    Standard,
+    /// A surrogate of its own, started for the sandbox with a restricted
+    /// token: without the user's privileges, and at low integrity, so it
+    /// can't write to most of the user's files, registry keys and
+    /// processes. Starting it adds the time to start a process to creating
+    /// the sandbox.
+    LowIntegrity,
+}
+
+/// The sizing of the pool of surrogate processes that lets a Windows host
+/// run many sandboxes. See [`init_surrogate_pool()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SurrogatePoolConfig {
+    initial: usize,
+    max: usize,
+    process_memory_limit: Option<usize>,
+    job_memory_limit: Option<usize>,
+    cpu_rate_limit: Option<u32>,
+}
+
+impl Default for SurrogatePoolConfig {
+    /// Pre-create the most surrogate processes a process can use, 512
+    fn default() -> Self {
+        Self {
+            initial: HARD_MAX_SURROGATE_PROCESSES,
+            max: HARD_MAX_SURROGATE_PROCESSES,
+            process_memory_limit: None,
+            job_memory_limit: None,
+            cpu_rate_limit: None,
+        }
+    }
+}
+
+impl SurrogatePoolConfig {
+    /// Create surrogate processes on demand up to `max`, which is clamped
+    /// to `1..=512`, after pre-creating all of them
+    pub fn new(max: usize) -> Self {
+        let (initial, max) = compute_surrogate_counts(None, Some(max));
+        Self {
+            initial,
+            max,
+            ..Default::default()
+        }
+    }
+
+    /// Pre-create only `initial` surrogate processes when the pool starts,
+    /// clamped to `1..=max`
+    pub fn with_initial(mut self, initial: usize) -> Self {
+        (self.initial, self.max) = compute_surrogate_counts(Some(initial), Some(self.max));
+        self
+    }
+
+    /// Limit the memory each surrogate process can commit to `bytes`.
+    /// The memory of a sandbox is mapped into its surrogate process from
+    /// the host, so this only stops a surrogate from allocating memory of
+    /// its own.
+    pub fn with_process_memory_limit(mut self, bytes: usize) -> Self {
+        self.process_memory_limit = Some(bytes);
+        self
+    }
+
+    /// Limit the memory all the surrogate processes together can commit
+    /// to `bytes`
+    pub fn with_job_memory_limit(mut self, bytes: usize) -> Self {
+        self.job_memory_limit = Some(bytes);
+        self
+    }
+
+    /// Cap the CPU time all the surrogate processes together can use to
+    /// `percent` of the host's CPU time, clamped to `1..=100`. Surrogate
+    /// processes never run code of their own, so this only limits a
+    /// surrogate that was somehow made to.
+    pub fn with_cpu_rate_limit(mut self, percent: u32) -> Self {
+        self.cpu_rate_limit = Some(percent.clamp(1, 100));
+        self
+    }
+
+    /// How many surrogate processes are pre-created
+    pub fn initial(&self) -> usize {
+        self.initial
+    }
+
+    /// The most surrogate processes there can be
+    pub fn max(&self) -> usize {
+        self.max
+    }
+}
+
+/// The state of the pool of surrogate processes, as returned by
+/// [`surrogate_pool_stats()`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SurrogatePoolStats {
+    /// The surrogate processes that exist, in use or not
+    pub created: usize,
+    /// The surrogate processes waiting in the pool for a sandbox
+    pub idle: usize,
+    /// The most surrogate processes there can be
+    pub max: usize,
+    /// How many times a sandbox had to wait for a surrogate process to be
+    /// returned, because the pool was empty and at its maximum size
+    pub exhausted: u64,
+    /// How many surrogate processes were found to have exited, and were
+    /// replaced
+    pub died: u64,
+}
+
 /// Returns the on-disk filename for the surrogate binary, incorporating the
 /// first 8 hex characters of the BLAKE3 hash of the embedded binary so that
 /// different hyperlight versions produce different filenames and can coexist
@@ -132,6 +270,17 @@ fn surrogate_process_counts() -> (usize, usize) {
     (initial, max)
 }
 
+/// The pool sizing given by `HYPERLIGHT_INITIAL_SURROGATES` and
+/// `HYPERLIGHT_MAX_SURROGATES`
+fn surrogate_pool_config_from_env() -> SurrogatePoolConfig {
+    let (initial, max) = surrogate_process_counts();
+    SurrogatePoolConfig {
+        initial,
+        max,
+        ..Default::default()
+    }
+}
+
 /// `SurrogateProcessManager` manages hyperlight_surrogate processes. These
 /// processes are required to allow multiple WHP Partitions to be created in a
 /// single process.
@@ -162,10 +311,12 @@ fn surrogate_process_counts() -> (usize, usize) {
 ///
 /// There is a limit of 512 partitions per process. By default 512 processes
 /// are pre-created at startup, but this can be reduced via
-/// `HYPERLIGHT_INITIAL_SURROGATES`. Additional processes are created on
-/// demand up to the limit set by `HYPERLIGHT_MAX_SURROGATES` (also
-/// defaulting to 512). If the pool is empty and the max has been reached,
-/// callers will block until a process is returned.
+/// `HYPERLIGHT_INITIAL_SURROGATES` or [`init_surrogate_pool()`]. Additional
+/// processes are created on demand up to the limit set by
+/// `HYPERLIGHT_MAX_SURROGATES` (also defaulting to 512), which
+/// [`resize_surrogate_pool()`] changes at runtime. If the pool is empty and
+/// the max has been reached, callers will block until a process is returned
+/// or the max is raised.
 ///
 /// This class is `Send + Sync`, and internally manages the pool of
 /// surrogate processes in a concurrency-safe way.
@@ -190,65 +341,216 @@ pub(crate) struct SurrogateProcessManager {
     process_sender: Sender<HandleWrapper>,
     /// Path to the on-disk surrogate binary (hash-stamped).
     surrogate_process_path: PathBuf,
-    /// Maximum number of surrogate processes allowed to exist.
-    max_processes: usize,
+    /// Maximum number of surrogate processes allowed to exist. Lowering it
+    /// terminates processes as they are returned, until there are no more
+    /// than the maximum.
+    max_processes: AtomicUsize,
     /// Number of surrogate processes that have been created so far.
     /// Used to decide whether we can spawn more on demand.
     created_count: AtomicUsize,
+    /// Number of times a caller had to wait for a process to be returned
+    exhausted_count: AtomicU64,
+    /// Number of processes found to have exited, and discarded
+    died_count: AtomicU64,
 }
 
 impl SurrogateProcessManager {
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
-    fn new() -> Result<Self> {
+    fn new(config: SurrogatePoolConfig) -> Result<Self> {
         let binary_name = surrogate_binary_name()?;
         ensure_surrogate_process_exe(&binary_name)?;
         let surrogate_process_path = get_surrogate_process_dir()?.join(&binary_name);
 
-        let (initial, max) = surrogate_process_counts();
-
         let (sender, receiver) = unbounded();
-        let job_handle = create_job_object()?;
+        let job_handle = create_job_object(&config)?;
         let surrogate_process_manager = SurrogateProcessManager {
             job_handle,
             process_receiver: receiver,
             process_sender: sender,
             surrogate_process_path,
-            max_processes: max,
+            max_processes: AtomicUsize::new(config.max),
             created_count: AtomicUsize::new(0),
+            exhausted_count: AtomicU64::new(0),
+            died_count: AtomicU64::new(0),
         };
 
-        surrogate_process_manager.create_initial_surrogate_processes(initial)?;
+        surrogate_process_manager.create_initial_surrogate_processes(config.initial)?;
 
         Ok(surrogate_process_manager)
     }
     /// Gets a surrogate process from the pool. If the pool is empty and
     /// fewer than `max_processes` have been created, a new process is
     /// spawned on demand. If the pool is empty and the maximum has been
-    /// reached, this call blocks until a process is returned.
+    /// reached, this call blocks until a process is returned, or the
+    /// maximum is raised. Pooled processes that have exited are discarded,
+    /// which frees their slot for a replacement.
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
     pub(super) fn get_surrogate_process(&self) -> Result<SurrogateProcess> {
         // Fast path: try to grab an already-pooled process.
-        match self.process_receiver.try_recv() {
-            Ok(handle) => {
-                let surrogate_process_handle: HANDLE = handle.into();
-                return Ok(SurrogateProcess::new(surrogate_process_handle));
+        loop {
+            match self.process_receiver.try_recv() {
+                Ok(handle) => {
+                    if let Some(process) = self.take_if_alive(handle) {
+                        return Ok(process);
+                    }
+                }
+                Err(TryRecvError::Empty) => {
+                    // Pool is empty — try to grow on demand below.
+                    break;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    return Err(new_error!("surrogate process channel disconnected"));
+                }
             }
-            Err(TryRecvError::Empty) => {
-                // Pool is empty — try to grow on demand below.
+        }
+
+        if let Some(process) = self.create_surrogate_process_on_demand()? {
+            return Ok(process);
+        }
+
+        // Maximum reached — block until a process is returned to the pool,
+        // or the maximum is raised.
+        self.exhausted_count.fetch_add(1, Ordering::Relaxed);
+        metrics::counter!(METRIC_SURROGATE_POOL_EXHAUSTED).increment(1);
+        let waiting_since = Instant::now();
+        let process = loop {
+            match self.process_receiver.recv_timeout(EXHAUSTED_POLL_INTERVAL) {
+                Ok(handle) => {
+                    if let Some(process) = self.take_if_alive(handle) {
+                        break process;
+                    }
+                    if let Some(process) = self.create_surrogate_process_on_demand()? {
+                        break process;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if let Some(process) = self.create_surrogate_process_on_demand()? {
+                        break process;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(new_error!("surrogate process channel disconnected"));
+                }
             }
-            Err(TryRecvError::Disconnected) => {
-                return Err(new_error!("surrogate process channel disconnected"));
+        };
+        metrics::histogram!(METRIC_SURROGATE_POOL_WAIT_DURATION)
+            .record(waiting_since.elapsed().as_secs_f64());
+        Ok(process)
+    }
+
+    /// Gets a surrogate process restricted by `isolation`. Restricted
+    /// processes are not pooled, so they are started for each sandbox and
+    /// terminated once it is dropped, while counting against the maximum
+    /// like the pooled ones.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(super) fn get_isolated_surrogate_process(
+        &self,
+        isolation: SurrogateIsolation,
+    ) -> Result<SurrogateProcess> {
+        let process = if isolation == SurrogateIsolation::Standard {
+            self.get_surrogate_process()
+        } else {
+            self.create_isolated_surrogate_process(isolation)
+        };
+        self.publish_usage();
+        process
+    }
+
+    /// Starts a surrogate process restricted by `isolation` in a free
+    /// slot, freeing one from an idle pooled process if there is none
+    fn create_isolated_surrogate_process(
+        &self,
+        isolation: SurrogateIsolation,
+    ) -> Result<SurrogateProcess> {
+        // An idle pooled process only holds a slot, so it gives it up when
+        // the maximum has been reached
+        while !self.claim_slot() {
+            match self.process_receiver.recv_timeout(EXHAUSTED_POLL_INTERVAL) {
+                Ok(handle) => {
+                    terminate_surrogate_process(handle);
+                    break;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(new_error!("surrogate process channel disconnected"));
+                }
             }
         }
+        let handle = match create_surrogate_process(
+            &self.surrogate_process_path,
+            self.job_handle,
+            isolation,
+        ) {
+            Ok(h) => h,
+            Err(e) => {
+                self.created_count.fetch_sub(1, Ordering::AcqRel);
+                return Err(e);
+            }
+        };
+        metrics::gauge!(METRIC_SURROGATE_PROCESSES).increment(1.0);
+        Ok(SurrogateProcess::new(handle.into()).with_isolation(isolation))
+    }
+
+    /// Claims a slot for a new process if fewer than `max_processes` have
+    /// been created, returning whether one was claimed
+    fn claim_slot(&self) -> bool {
+        // A CAS loop so that concurrent callers don't overshoot the maximum
+        loop {
+            let current = self.created_count.load(Ordering::Acquire);
+            if current >= self.max_processes.load(Ordering::Acquire) {
+                return false;
+            }
+            if self
+                .created_count
+                .compare_exchange(current, current + 1, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                return true;
+            }
+        }
+    }
+
+    /// Returns the pooled process `handle` if it is still running, or
+    /// discards it and returns `None` if it has exited
+    fn take_if_alive(&self, handle: HandleWrapper) -> Option<SurrogateProcess> {
+        match surrogate_exit_code(handle.into()) {
+            None => Some(SurrogateProcess::new(handle.into())),
+            Some(exit_code) => {
+                self.discard_dead_surrogate_process(handle, exit_code);
+                None
+            }
+        }
+    }
+
+    /// Closes the handle of a surrogate process that has exited, freeing
+    /// its slot so that a replacement can be created
+    fn discard_dead_surrogate_process(&self, handle: HandleWrapper, exit_code: u32) {
+        warn!(
+            "surrogate process exited with code {:#x}, replacing it",
+            exit_code
+        );
+        let handle: HANDLE = handle.into();
+        // SAFETY: the process has exited, and its handle is not used again
+        if let Err(e) = unsafe { CloseHandle(handle) } {
+            error!("failed to close surrogate process handle: {:?}", e);
+        }
+        self.created_count.fetch_sub(1, Ordering::AcqRel);
+        self.died_count.fetch_add(1, Ordering::Relaxed);
+        metrics::gauge!(METRIC_SURROGATE_PROCESSES).decrement(1.0);
+        metrics::counter!(METRIC_SURROGATE_DEATHS).increment(1);
+    }
 
+    /// Creates a new surrogate process if fewer than `max_processes` have
+    /// been created, or returns `None` if the maximum has been reached.
+    fn create_surrogate_process_on_demand(&self) -> Result<Option<SurrogateProcess>> {
         // On-demand growth: atomically claim a slot if one is available.
         // We use a CAS loop so that concurrent callers don't overshoot
         // the maximum.
         loop {
             let current = self.created_count.load(Ordering::Acquire);
-            if current >= self.max_processes {
-                // At the limit — fall through to the blocking recv below.
-                break;
+            let max = self.max_processes.load(Ordering::Acquire);
+            if current >= max {
+                return Ok(None);
             }
             if self
                 .created_count
@@ -258,54 +560,161 @@ impl SurrogateProcessManager {
                 info!(
                     "on-demand surrogate process creation ({}/{})",
                     current + 1,
-                    self.max_processes
+                    max
                 );
-                let handle =
-                    match create_surrogate_process(&self.surrogate_process_path, self.job_handle) {
-                        Ok(h) => h,
-                        Err(e) => {
-                            // Rollback the slot claim so capacity isn't
-                            // permanently lost on transient failures.
-                            self.created_count.fetch_sub(1, Ordering::AcqRel);
-                            return Err(e);
-                        }
-                    };
+                let handle = match create_surrogate_process(
+                    &self.surrogate_process_path,
+                    self.job_handle,
+                    SurrogateIsolation::Standard,
+                ) {
+                    Ok(h) => h,
+                    Err(e) => {
+                        // Rollback the slot claim so capacity isn't
+                        // permanently lost on transient failures.
+                        self.created_count.fetch_sub(1, Ordering::AcqRel);
+                        return Err(e);
+                    }
+                };
+                metrics::gauge!(METRIC_SURROGATE_PROCESSES).increment(1.0);
                 let surrogate_process_handle: HANDLE = handle.into();
-                return Ok(SurrogateProcess::new(surrogate_process_handle));
+                return Ok(Some(SurrogateProcess::new(surrogate_process_handle)));
             }
             // CAS failed — another thread beat us; retry.
         }
-
-        // Maximum reached — block until a process is returned to the pool.
-        let surrogate_process_handle: HANDLE = self.process_receiver.recv()?.into();
-        Ok(SurrogateProcess::new(surrogate_process_handle))
     }
 
     /// Returns a surrogate process to the pool of surrogate processes.
     /// This should be called from within a surrogate process's drop
     /// implementation, after process resources have been freed.
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
-    pub(super) fn return_surrogate_process(&self, proc_handle: HandleWrapper) -> Result<()> {
+    pub(super) fn return_surrogate_process(
+        &self,
+        proc_handle: HandleWrapper,
+        isolation: SurrogateIsolation,
+    ) -> Result<()> {
+        if let Some(exit_code) = surrogate_exit_code(proc_handle.into()) {
+            self.discard_dead_surrogate_process(proc_handle, exit_code);
+            return Ok(());
+        }
+        // Restricted processes are only used by the sandbox they were
+        // started for
+        if isolation != SurrogateIsolation::Standard {
+            self.created_count.fetch_sub(1, Ordering::AcqRel);
+            terminate_surrogate_process(proc_handle);
+            return Ok(());
+        }
+        // Processes over a lowered maximum are terminated rather than
+        // pooled
+        loop {
+            let current = self.created_count.load(Ordering::Acquire);
+            if current <= self.max_processes.load(Ordering::Acquire) {
+                break;
+            }
+            if self
+                .created_count
+                .compare_exchange(current, current - 1, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                terminate_surrogate_process(proc_handle);
+                return Ok(());
+            }
+        }
         Ok(self.process_sender.send(proc_handle)?)
     }
 
+    /// Changes the maximum number of surrogate processes, terminating
+    /// pooled processes over a lowered maximum. Processes in use over it
+    /// are terminated when they are returned.
+    fn resize(&self, max: usize) {
+        self.max_processes.store(max, Ordering::Release);
+        while self.created_count.load(Ordering::Acquire) > max {
+            let Ok(handle) = self.process_receiver.try_recv() else {
+                break;
+            };
+            self.created_count.fetch_sub(1, Ordering::AcqRel);
+            terminate_surrogate_process(handle);
+        }
+        self.publish_usage();
+    }
+
+    /// Terminates pooled processes until at most `keep_idle` are left,
+    /// returning how many were terminated
+    fn trim(&self, keep_idle: usize) -> usize {
+        let mut terminated = 0;
+        while self.process_receiver.len() > keep_idle {
+            let Ok(handle) = self.process_receiver.try_recv() else {
+                break;
+            };
+            self.created_count.fetch_sub(1, Ordering::AcqRel);
+            terminate_surrogate_process(handle);
+            terminated += 1;
+        }
+        self.publish_usage();
+        terminated
+    }
+
+    /// Checks every pooled process, replacing those that have exited with
+    /// new ones, and returns how many were replaced
+    fn check_health(&self) -> Result<usize> {
+        let mut replaced = 0;
+        // Live processes go back at the end of the queue, so each pooled
+        // process is checked once
+        for _ in 0..self.process_receiver.len() {
+            let Ok(handle) = self.process_receiver.try_recv() else {
+                break;
+            };
+            let Some(exit_code) = surrogate_exit_code(handle.into()) else {
+                self.process_sender.send(handle)?;
+                continue;
+            };
+            self.discard_dead_surrogate_process(handle, exit_code);
+            replaced += 1;
+            if let Some(process) = self.create_surrogate_process_on_demand()? {
+                // Dropping the process returns it to the pool
+                drop(process);
+            }
+        }
+        self.publish_usage();
+        Ok(replaced)
+    }
+
+    /// Updates the PerfMon counters of how many processes there are, and
+    /// how many are idle
+    pub(super) fn publish_usage(&self) {
+        perfmon::surrogate_pool(
+            self.created_count.load(Ordering::Acquire),
+            self.process_receiver.len(),
+        );
+    }
+
+    fn stats(&self) -> SurrogatePoolStats {
+        SurrogatePoolStats {
+            created: self.created_count.load(Ordering::Acquire),
+            idle: self.process_receiver.len(),
+            max: self.max_processes.load(Ordering::Acquire),
+            exhausted: self.exhausted_count.load(Ordering::Relaxed),
+            died: self.died_count.load(Ordering::Relaxed),
+        }
+    }
+
     /// Pre-creates the initial batch of surrogate processes at startup.
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
     fn create_initial_surrogate_processes(&self, initial_count: usize) -> Result<()> {
         info!(
-            "pre-creating {} surrogate processes ({}={:?}, {}={:?})",
+            "pre-creating {} surrogate processes (max {})",
             initial_count,
-            INITIAL_SURROGATES_ENV_VAR,
-            std::env::var(INITIAL_SURROGATES_ENV_VAR).ok(),
-            MAX_SURROGATES_ENV_VAR,
-            std::env::var(MAX_SURROGATES_ENV_VAR).ok(),
+            self.max_processes.load(Ordering::Acquire),
         );
         for _ in 0..initial_count {
-            let surrogate_process =
-                create_surrogate_process(&self.surrogate_process_path, self.job_handle)?;
+            let surrogate_process = create_surrogate_process(
+                &self.surrogate_process_path,
+                self.job_handle,
+                SurrogateIsolation::Standard,
+            )?;
             self.process_sender.send(surrogate_process)?;
             self.created_count.fetch_add(1, Ordering::AcqRel);
         }
+        metrics::gauge!(METRIC_SURROGATE_PROCESSES).set(initial_count as f64);
 
         Ok(())
     }
@@ -328,43 +737,171 @@ impl Drop for SurrogateProcessManager {
     }
 }
 
-lazy_static::lazy_static! {
-    // see the large comment inside `SurrogateProcessManager` describing
-    // our reasoning behind using `lazy_static`.
-    static ref SURROGATE_PROCESSES_MANAGER: std::result::Result<SurrogateProcessManager, &'static str> =
-        match SurrogateProcessManager::new() {
-            Ok(manager) => Ok(manager),
-            Err(e) => {
-                error!("Failed to create SurrogateProcessManager: {:?}", e);
-                Err("Failed to create SurrogateProcessManager")
-            }
-        };
+/// The singleton `SurrogateProcessManager`, started by the first sandbox
+/// or by `init_surrogate_pool()`
+static SURROGATE_PROCESSES_MANAGER: OnceLock<std::result::Result<SurrogateProcessManager, String>> =
+    OnceLock::new();
+
+/// Gets the singleton SurrogateProcessManager, starting it with the sizing
+/// returned by `config` if it has not been started
+fn get_or_start_surrogate_process_manager(
+    config: impl FnOnce() -> SurrogatePoolConfig,
+) -> Result<&'static SurrogateProcessManager> {
+    let manager = SURROGATE_PROCESSES_MANAGER.get_or_init(|| {
+        SurrogateProcessManager::new(config()).map_err(|e| {
+            error!("Failed to create SurrogateProcessManager: {:?}", e);
+            e.to_string()
+        })
+    });
+    match manager {
+        Ok(manager) => Ok(manager),
+        Err(e) => {
+            error!("Failed to get SurrogateProcessManager: {:?}", e);
+            Err(new_error!("Failed to get SurrogateProcessManager {}", e))
+        }
+    }
 }
 
 /// Gets the singleton SurrogateProcessManager. This should be called when a new HyperV on Windows Driver is created.
 #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
 pub(crate) fn get_surrogate_process_manager() -> Result<&'static SurrogateProcessManager> {
-    match &*SURROGATE_PROCESSES_MANAGER {
-        Ok(manager) => Ok(manager),
-        Err(e) => {
-            error!("Failed to get SurrogateProcessManager: {:?}", e);
-            Err(new_error!("Failed to get SurrogateProcessManager {}", e))
+    get_or_start_surrogate_process_manager(surrogate_pool_config_from_env)
+}
+
+/// Start the pool of surrogate processes with `config`, rather than with
+/// the sizing of the `HYPERLIGHT_INITIAL_SURROGATES` and
+/// `HYPERLIGHT_MAX_SURROGATES` environment variables.
+///
+/// The pool is started when the first sandbox is created, so this must be
+/// called before then, and returns an error if the pool has already been
+/// started. Starting the pool pre-creates `config.initial()` processes,
+/// which can take a while, so calling this early at startup also keeps that
+/// time off the first sandbox.
+///
+/// # Examples
+///
+/// ```no_run
+/// use hyperlight_host::hypervisor::{SurrogatePoolConfig, init_surrogate_pool};
+///
+/// # fn example() -> hyperlight_host::Result<()> {
+/// // Start with 32 processes, growing to 256 as more sandboxes are created
+/// init_surrogate_pool(SurrogatePoolConfig::new(256).with_initial(32))?;
+/// # Ok(())
+/// # }
+/// ```
+#[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+pub fn init_surrogate_pool(config: SurrogatePoolConfig) -> Result<()> {
+    let mut config = Some(config);
+    get_or_start_surrogate_process_manager(|| config.take().unwrap_or_default())?;
+    if config.is_some() {
+        return Err(new_error!(
+            "The surrogate process pool has already been started"
+        ));
+    }
+    Ok(())
+}
+
+/// Change the most surrogate processes there can be to `max`, clamped to
+/// `1..=512`, starting the pool if it has not been started.
+///
+/// Raising the maximum lets sandboxes waiting for a process create one.
+/// Lowering it terminates the idle processes over the new maximum, and the
+/// processes in use over it once their sandboxes are dropped.
+#[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+pub fn resize_surrogate_pool(max: usize) -> Result<()> {
+    let (_, clamped) = compute_surrogate_counts(None, Some(max));
+    if clamped != max {
+        warn!("surrogate pool size {max} was clamped to {clamped}");
+    }
+    get_surrogate_process_manager()?.resize(clamped);
+    Ok(())
+}
+
+/// Terminate idle surrogate processes until at most `keep_idle` are left
+/// in the pool, returning how many were terminated. Processes are created
+/// again on demand, up to the pool's maximum.
+#[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+pub fn trim_surrogate_pool(keep_idle: usize) -> Result<usize> {
+    Ok(get_surrogate_process_manager()?.trim(keep_idle))
+}
+
+/// Check that the idle surrogate processes in the pool are still running,
+/// replacing those that have exited, and return how many were replaced.
+///
+/// Surrogate processes are also checked as they are handed to sandboxes
+/// and returned by them, so this only needs calling to find processes
+/// that died while idle before a sandbox needs them.
+#[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+pub fn check_surrogate_pool_health() -> Result<usize> {
+    get_surrogate_process_manager()?.check_health()
+}
+
+/// The state of the pool of surrogate processes, or `None` if it has not
+/// been started
+pub fn surrogate_pool_stats() -> Option<SurrogatePoolStats> {
+    match SURROGATE_PROCESSES_MANAGER.get() {
+        Some(Ok(manager)) => Some(manager.stats()),
+        _ => None,
+    }
+}
+
+/// Returns the exit code of the surrogate process `handle` if it has
+/// exited, or `None` if it is still running
+pub(super) fn surrogate_exit_code(handle: HANDLE) -> Option<u32> {
+    let mut exit_code = 0u32;
+    // SAFETY: the handle is of a surrogate process, opened with
+    // full access by CreateProcessA
+    if let Err(e) = unsafe { GetExitCodeProcess(handle, &mut exit_code) } {
+        error!("failed to get surrogate process exit code: {:?}", e);
+        return None;
+    }
+    // A process that exits with STILL_ACTIVE is taken to be running, which
+    // the surrogate never does
+    (exit_code != STILL_ACTIVE.0 as u32).then_some(exit_code)
+}
+
+/// Terminates a surrogate process that is no longer needed and closes its
+/// handle
+fn terminate_surrogate_process(handle: HandleWrapper) {
+    let handle: HANDLE = handle.into();
+    // SAFETY: the handle is of a surrogate process that is not in use,
+    // and is not used again
+    unsafe {
+        if let Err(e) = TerminateProcess(handle, 0) {
+            error!("failed to terminate surrogate process: {:?}", e);
+        }
+        if let Err(e) = CloseHandle(handle) {
+            error!("failed to close surrogate process handle: {:?}", e);
         }
     }
+    metrics::gauge!(METRIC_SURROGATE_PROCESSES).decrement(1.0);
 }
 
-// Creates a job object that will terminate all the surrogate processes when the struct instance is dropped.
+// Creates a job object that will terminate all the surrogate processes when the struct instance is dropped,
+// or when the host process exits and the last handle to the job is closed, with the limits in `config`.
 #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
-fn create_job_object() -> Result<HandleWrapper> {
+fn create_job_object(config: &SurrogatePoolConfig) -> Result<HandleWrapper> {
     let security_attributes: SECURITY_ATTRIBUTES = Default::default();
 
     let job_object = unsafe { CreateJobObjectA(Some(&security_attributes), PCSTR::null())? };
 
+    // A surrogate that crashes dies instead of waiting on a debugger or
+    // an error dialog
+    let mut limit_flags =
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE | JOB_OBJECT_LIMIT_DIE_ON_UNHANDLED_EXCEPTION;
+    if config.process_memory_limit.is_some() {
+        limit_flags |= JOB_OBJECT_LIMIT_PROCESS_MEMORY;
+    }
+    if config.job_memory_limit.is_some() {
+        limit_flags |= JOB_OBJECT_LIMIT_JOB_MEMORY;
+    }
     let mut job_object_information = JOBOBJECT_EXTENDED_LIMIT_INFORMATION {
         BasicLimitInformation: JOBOBJECT_BASIC_LIMIT_INFORMATION {
-            LimitFlags: JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+            LimitFlags: limit_flags,
             ..Default::default()
         },
+        ProcessMemoryLimit: config.process_memory_limit.unwrap_or_default(),
+        JobMemoryLimit: config.job_memory_limit.unwrap_or_default(),
         ..Default::default()
     };
     let job_object_information_ptr: *mut c_void =
@@ -380,6 +917,26 @@ fn create_job_object() -> Result<HandleWrapper> {
         log_then_return!(WindowsAPIError(e.clone()));
     }
 
+    if let Some(percent) = config.cpu_rate_limit {
+        // The rate is in hundredths of a percent of the host's CPU time
+        let cpu_rate_information = JOBOBJECT_CPU_RATE_CONTROL_INFORMATION {
+            ControlFlags: JOB_OBJECT_CPU_RATE_CONTROL_ENABLE | JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP,
+            Anonymous: JOBOBJECT_CPU_RATE_CONTROL_INFORMATION_0 {
+                CpuRate: percent * 100,
+            },
+        };
+        if let Err(e) = unsafe {
+            SetInformationJobObject(
+                job_object,
+                JobObjectCpuRateControlInformation,
+                &cpu_rate_information as *const _ as *const c_void,
+                size_of::<JOBOBJECT_CPU_RATE_CONTROL_INFORMATION>() as u32,
+            )
+        } {
+            log_then_return!(WindowsAPIError(e.clone()));
+        }
+    }
+
     Ok(job_object.into())
 }
 
@@ -445,6 +1002,57 @@ fn ensure_surrogate_process_exe(binary_name: &str) -> Result<()> {
     Ok(())
 }
 
+/// Creates a primary token for a [`SurrogateIsolation::LowIntegrity`]
+/// surrogate from the host process's token, with all privileges but
+/// `SeChangeNotifyPrivilege` removed and a low integrity level
+fn create_low_integrity_token() -> Result<HandleWrapper> {
+    let mut process_token = HANDLE::default();
+    let mut token = HANDLE::default();
+    // SAFETY: the handles are closed before returning, but for the new
+    // token on success, which the caller closes
+    unsafe {
+        OpenProcessToken(
+            GetCurrentProcess(),
+            TOKEN_DUPLICATE | TOKEN_QUERY | TOKEN_ASSIGN_PRIMARY | TOKEN_ADJUST_DEFAULT,
+            &mut process_token,
+        )?;
+        let restricted = CreateRestrictedToken(
+            process_token,
+            DISABLE_MAX_PRIVILEGE,
+            None,
+            None,
+            None,
+            &mut token,
+        );
+        let _ = CloseHandle(process_token);
+        restricted?;
+
+        let mut sid = [0u8; SECURITY_MAX_SID_SIZE as usize];
+        let mut sid_size = sid.len() as u32;
+        let sid = PSID(sid.as_mut_ptr().cast());
+        let labelled =
+            CreateWellKnownSid(WinLowLabelSid, None, Some(sid), &mut sid_size).and_then(|()| {
+                let label = TOKEN_MANDATORY_LABEL {
+                    Label: SID_AND_ATTRIBUTES {
+                        Sid: sid,
+                        Attributes: SE_GROUP_INTEGRITY as u32,
+                    },
+                };
+                SetTokenInformation(
+                    token,
+                    TokenIntegrityLevel,
+                    (&label as *const TOKEN_MANDATORY_LABEL).cast(),
+                    size_of::<TOKEN_MANDATORY_LABEL>() as u32 + GetLengthSid(sid),
+                )
+            });
+        if let Err(e) = labelled {
+            let _ = CloseHandle(token);
+            return Err(e.into());
+        }
+    }
+    Ok(token.into())
+}
+
 /// Creates a surrogate process and adds it to the job object.
 /// Process is created suspended, its only used as a host for memory
 /// the memory is allocated and freed when the process is returned to the pool.
@@ -456,6 +1064,7 @@ fn ensure_surrogate_process_exe(binary_name: &str) -> Result<()> {
 fn create_surrogate_process(
     surrogate_process_path: &Path,
     job_handle: HandleWrapper,
+    isolation: SurrogateIsolation,
 ) -> Result<HandleWrapper> {
     let mut process_information: PROCESS_INFORMATION = unsafe { std::mem::zeroed() };
     let mut startup_info: STARTUPINFOA = unsafe { std::mem::zeroed() };
@@ -468,27 +1077,57 @@ fn create_surrogate_process(
     ))?;
     let p_cmd_line = &PSTRWrapper::try_from(cmd_line)?;
 
-    if let Err(e) = unsafe {
-        CreateProcessA(
-            PCSTR::null(),
-            Some(p_cmd_line.into()),
-            Some(&process_attributes),
-            Some(&thread_attributes),
-            false,
-            CREATE_SUSPENDED,
-            None,
-            None,
-            &startup_info,
-            &mut process_information,
-        )
-    } {
+    let created = match isolation {
+        SurrogateIsolation::Standard => unsafe {
+            CreateProcessA(
+                PCSTR::null(),
+                Some(p_cmd_line.into()),
+                Some(&process_attributes),
+                Some(&thread_attributes),
+                false,
+                CREATE_SUSPENDED,
+                None,
+                None,
+                &startup_info,
+                &mut process_information,
+            )
+        },
+        SurrogateIsolation::LowIntegrity => {
+            let token = create_low_integrity_token()?;
+            let token: HANDLE = token.into();
+            let created = unsafe {
+                CreateProcessAsUserA(
+                    Some(token),
+                    PCSTR::null(),
+                    Some(p_cmd_line.into()),
+                    Some(&process_attributes),
+                    Some(&thread_attributes),
+                    false,
+                    CREATE_SUSPENDED,
+                    None,
+                    PCSTR::null(),
+                    &startup_info,
+                    &mut process_information,
+                )
+            };
+            // SAFETY: the token is not used again
+            let _ = unsafe { CloseHandle(token) };
+            created
+        }
+    };
+    if let Err(e) = created {
         log_then_return!(WindowsAPIError(e.clone()));
     }
 
     let job_handle: HANDLE = job_handle.into();
     let process_handle: HANDLE = process_information.hProcess;
     unsafe {
+        // The process has not run yet, and is not left behind outside of
+        // the job, where it would outlive the host
         if let Err(e) = AssignProcessToJobObject(job_handle, process_handle) {
+            let _ = TerminateProcess(process_handle, 1);
+            let _ = CloseHandle(process_information.hThread);
+            let _ = CloseHandle(process_handle);
             log_then_return!(WindowsAPIError(e.clone()));
         }
     }
@@ -502,7 +1141,7 @@ mod tests {
     use std::time::{Duration, Instant};
 
     use rand::{RngExt, rng};
-    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::Foundation::{DUPLICATE_SAME_ACCESS, DuplicateHandle, HANDLE};
     use windows::Win32::System::Diagnostics::ToolHelp::{
         CreateToolhelp32Snapshot, PROCESSENTRY32, Process32First, Process32Next, TH32CS_SNAPPROCESS,
     };
@@ -518,7 +1157,9 @@ mod tests {
         // the manager can handle multiple threads requesting processes at the
         // same time when there are not enough processes available.
         let surrogate_process_manager = get_surrogate_process_manager().unwrap();
-        let max_processes = surrogate_process_manager.max_processes;
+        let max_processes = surrogate_process_manager
+            .max_processes
+            .load(Ordering::Acquire);
         for t in 0..max_processes * 2 {
             let thread_handle = thread::spawn(move || -> Result<()> {
                 let surrogate_process_manager_res = get_surrogate_process_manager();
@@ -570,6 +1211,60 @@ mod tests {
         assert_number_of_surrogate_processes(max_processes);
     }
 
+    #[test]
+    fn dead_surrogate_processes_are_replaced() {
+        let manager = get_surrogate_process_manager().unwrap();
+        let died = manager.stats().died;
+        let process = manager.get_surrogate_process().unwrap();
+        assert_eq!(process.exit_code(), None);
+
+        let handle: HANDLE = process.process_handle.into();
+        unsafe { TerminateProcess(handle, 0x2a).unwrap() };
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while process.exit_code().is_none() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(process.exit_code(), Some(0x2a));
+
+        // The dead process is discarded rather than pooled, and its slot
+        // is free for a replacement
+        drop(process);
+        assert_eq!(manager.stats().died, died + 1);
+        if let Some(replacement) = manager.create_surrogate_process_on_demand().unwrap() {
+            assert_eq!(replacement.exit_code(), None);
+        }
+    }
+
+    #[test]
+    fn low_integrity_surrogates_are_not_pooled() {
+        let manager = get_surrogate_process_manager().unwrap();
+        let created = manager.stats().created;
+        let process = manager
+            .get_isolated_surrogate_process(SurrogateIsolation::LowIntegrity)
+            .unwrap();
+        assert_eq!(process.exit_code(), None);
+
+        // The process is terminated rather than returned to the pool
+        let handle: HANDLE = process.process_handle.into();
+        let mut duplicate = HANDLE::default();
+        unsafe {
+            DuplicateHandle(
+                GetCurrentProcess(),
+                handle,
+                GetCurrentProcess(),
+                &mut duplicate,
+                0,
+                false,
+                DUPLICATE_SAME_ACCESS,
+            )
+            .unwrap()
+        };
+        drop(process);
+        assert!(surrogate_exit_code(duplicate).is_some());
+        assert_eq!(manager.stats().created, created);
+        unsafe { CloseHandle(duplicate).unwrap() };
+    }
+
     #[track_caller]
     fn assert_number_of_surrogate_processes(expected_count: usize) {
         const MAX_RETRIES: u32 = 30;
@@ -849,7 +1544,7 @@ mod tests {
     ///
     /// This exercises the pure validation/clamping function directly,
     /// avoiding process-global env var mutation which previously caused
-    /// a race with the `lazy_static` singleton initialisation in
+    /// a race with the singleton initialisation in
     /// parallel test runs.
     #[test]
     fn test_compute_surrogate_counts() {
@@ -935,6 +1630,36 @@ mod tests {
         assert_eq!(max, HARD_MAX_SURROGATE_PROCESSES);
     }
 
+    #[test]
+    fn test_surrogate_pool_config() {
+        let config = SurrogatePoolConfig::default();
+        assert_eq!(config.initial(), HARD_MAX_SURROGATE_PROCESSES);
+        assert_eq!(config.max(), HARD_MAX_SURROGATE_PROCESSES);
+
+        let config = SurrogatePoolConfig::new(64).with_initial(8);
+        assert_eq!((config.initial(), config.max()), (8, 64));
+
+        // The initial count never exceeds the maximum, which never
+        // exceeds the hard limit
+        let config = SurrogatePoolConfig::new(9999).with_initial(9999);
+        assert_eq!(config.initial(), HARD_MAX_SURROGATE_PROCESSES);
+        assert_eq!(config.max(), HARD_MAX_SURROGATE_PROCESSES);
+        assert_eq!(SurrogatePoolConfig::new(16).with_initial(32).initial(), 16);
+
+        let config = SurrogatePoolConfig::default()
+            .with_process_memory_limit(64 << 20)
+            .with_cpu_rate_limit(250);
+        assert_eq!(config.process_memory_limit, Some(64 << 20));
+        assert_eq!(config.job_memory_limit, None);
+        assert_eq!(config.cpu_rate_limit, Some(100));
+        assert_eq!(
+            SurrogatePoolConfig::default()
+                .with_cpu_rate_limit(0)
+                .cpu_rate_limit,
+            Some(1)
+        );
+    }
+
     /// Smoke-tests `surrogate_process_counts()` with the default test
     /// environment (neither env var set). This does NOT mutate env vars —
     /// it just verifies the env-reading wrapper returns the expected
@@ -0,0 +1,407 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{Result, new_error};
+
+/// Where the kernel publishes the type of the Intel PT PMU
+const INTEL_PT_TYPE_PATH: &str = "/sys/bus/event_source/devices/intel_pt/type";
+
+/// `sizeof(struct perf_event_attr)` for `PERF_ATTR_SIZE_VER5`
+const PERF_ATTR_SIZE: u32 = 112;
+/// `perf_event_attr` flags
+const ATTR_DISABLED: u64 = 1 << 0;
+const ATTR_EXCLUDE_HOST: u64 = 1 << 19;
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 1 << 3;
+const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+const PERF_EVENT_IOC_DISABLE: libc::c_ulong = 0x2401;
+
+/// Offsets of the data and AUX area fields of `struct perf_event_mmap_page`
+const DATA_HEAD: usize = 1024;
+const DATA_TAIL: usize = 1032;
+const DATA_OFFSET: usize = 1040;
+const DATA_SIZE: usize = 1048;
+const AUX_HEAD: usize = 1056;
+const AUX_TAIL: usize = 1064;
+const AUX_OFFSET: usize = 1072;
+const AUX_SIZE: usize = 1080;
+/// The pages of the perf ring buffer the AUX area is described in, which
+/// are its header page and a power of two number of data pages. The data
+/// pages hold the records the kernel writes about the AUX area.
+const RING_PAGES: usize = 1 + 1;
+/// `PERF_RECORD_AUX`, written when data is added to the AUX area
+const PERF_RECORD_AUX: u32 = 11;
+/// Set in a `PERF_RECORD_AUX` when the AUX area filled up, and tracing
+/// stopped before the event was disabled
+const PERF_AUX_FLAG_TRUNCATED: u64 = 0x01;
+
+/// The fields of `struct perf_event_attr` up to `PERF_ATTR_SIZE_VER5`
+#[repr(C)]
+#[derive(Default)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+    config2: u64,
+    branch_sample_type: u64,
+    sample_regs_user: u64,
+    sample_stack_user: u32,
+    clockid: i32,
+    sample_regs_intr: u64,
+    aux_watermark: u32,
+    sample_max_stack: u16,
+    reserved: u16,
+}
+
+/// The raw Intel Processor Trace of a guest call, as captured by
+/// [`MultiUseSandbox::enable_intel_pt()`](crate::MultiUseSandbox::enable_intel_pt).
+///
+/// The trace is a stream of Intel PT packets for the guest's execution
+/// only, which can be decoded with `libipt` or `perf`'s decoder together
+/// with the guest binary.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IntelPtTrace {
+    data: Vec<u8>,
+    truncated: bool,
+}
+
+impl IntelPtTrace {
+    /// The Intel PT packets
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Whether the call traced more than the capture buffer holds, so
+    /// tracing stopped when the buffer filled up, and the end of the call
+    /// is missing from the trace
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Consume the trace and return its packets
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// A mapping of a perf event's ring buffer or AUX area
+struct Mapping {
+    addr: NonNull<libc::c_void>,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(fd: &OwnedFd, len: usize, offset: usize) -> Result<Self> {
+        // SAFETY: a new shared mapping of the perf event, which is only
+        // unmapped when dropped
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd.as_raw_fd(),
+                offset as libc::off_t,
+            )
+        };
+        if addr == libc::MAP_FAILED {
+            return Err(new_error!(
+                "Failed to map the Intel PT buffer: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        let addr = NonNull::new(addr).ok_or_else(|| new_error!("mmap returned null"))?;
+        Ok(Self { addr, len })
+    }
+
+    /// The bytes `len` bytes long at `offset`, or none if they are not
+    /// all in the mapping
+    fn range(&self, offset: usize, len: usize) -> &[u8] {
+        self.bytes()
+            .get(offset..offset.saturating_add(len))
+            .unwrap_or_default()
+    }
+
+    /// The 8-byte field of the header page at `offset`
+    fn field(&self, offset: usize) -> &AtomicU64 {
+        debug_assert!(offset + 8 <= self.len);
+        // SAFETY: the field is aligned and inside the mapping, and the
+        // kernel only accesses it atomically
+        unsafe { &*self.addr.as_ptr().byte_add(offset).cast::<AtomicU64>() }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        // SAFETY: the mapping is `len` bytes, and lives as long as `self`
+        unsafe { std::slice::from_raw_parts(self.addr.as_ptr().cast(), self.len) }
+    }
+}
+
+// SAFETY: the mapping is owned, and only read through `&self`
+unsafe impl Send for Mapping {}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: the mapping was made by `Mapping::new()`
+        unsafe { libc::munmap(self.addr.as_ptr(), self.len) };
+    }
+}
+
+/// An Intel PT perf event tracing the guest's execution on the thread
+/// that runs the vCPU, and the trace of the last guest call
+pub(crate) struct IntelPtCapture {
+    buffer_size: usize,
+    /// The event, which only traces the thread that opened it, and that
+    /// thread's id
+    event: Option<(PtEvent, libc::pid_t)>,
+    last_trace: Option<IntelPtTrace>,
+}
+
+struct PtEvent {
+    // The mappings are dropped before the event is closed
+    aux: Mapping,
+    ring: Mapping,
+    fd: OwnedFd,
+}
+
+impl IntelPtCapture {
+    /// Prepare to capture up to `buffer_size` bytes of trace per guest
+    /// call, which is rounded up to a power of two number of pages
+    pub(crate) fn new(buffer_size: usize) -> Result<Self> {
+        let page_size = page_size::get();
+        let pages = buffer_size.div_ceil(page_size).max(2).next_power_of_two();
+        let capture = Self {
+            buffer_size: pages * page_size,
+            event: None,
+            last_trace: None,
+        };
+        // Fail now if Intel PT is not available, rather than on each call
+        pmu_type()?;
+        Ok(capture)
+    }
+
+    /// Start tracing a guest call on the current thread
+    pub(crate) fn start(&mut self) -> Result<()> {
+        // SAFETY: gettid has no preconditions
+        let tid = unsafe { libc::gettid() };
+        if !matches!(&self.event, Some((_, event_tid)) if *event_tid == tid) {
+            self.event = Some((PtEvent::open(self.buffer_size)?, tid));
+        }
+        let Some((event, _)) = &self.event else {
+            return Ok(());
+        };
+        event.ioctl(PERF_EVENT_IOC_ENABLE)
+    }
+
+    /// Stop tracing the guest call and keep its trace
+    pub(crate) fn stop(&mut self) -> Result<()> {
+        let Some((event, _)) = &self.event else {
+            return Ok(());
+        };
+        event.ioctl(PERF_EVENT_IOC_DISABLE)?;
+        self.last_trace = Some(event.take_trace());
+        Ok(())
+    }
+
+    /// The trace of the last guest call, if it was not taken already
+    pub(crate) fn take_trace(&mut self) -> Option<IntelPtTrace> {
+        self.last_trace.take()
+    }
+}
+
+impl PtEvent {
+    fn open(aux_size: usize) -> Result<Self> {
+        let attr = PerfEventAttr {
+            type_: pmu_type()?,
+            size: PERF_ATTR_SIZE,
+            // Only the guest is traced, and the event is enabled for each call
+            flags: ATTR_DISABLED | ATTR_EXCLUDE_HOST,
+            ..Default::default()
+        };
+        // SAFETY: `attr` is a valid `perf_event_attr` of the size it gives
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                &attr as *const PerfEventAttr,
+                0,  // this thread
+                -1, // on any CPU
+                -1, // in no group
+                PERF_FLAG_FD_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return Err(new_error!(
+                "Failed to open an Intel PT event: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        // SAFETY: the syscall returned a new file descriptor
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+        let page_size = page_size::get();
+        // Both mappings are writable, so the kernel does not overwrite data
+        // that has not been read. It stops tracing when the AUX area is
+        // full instead, and says so in a `PERF_RECORD_AUX`.
+        let ring = Mapping::new(&fd, RING_PAGES * page_size, 0)?;
+        let aux_offset = RING_PAGES * page_size;
+        ring.field(AUX_OFFSET)
+            .store(aux_offset as u64, Ordering::Relaxed);
+        ring.field(AUX_SIZE)
+            .store(aux_size as u64, Ordering::Relaxed);
+        let aux = Mapping::new(&fd, aux_size, aux_offset)?;
+        Ok(Self { aux, ring, fd })
+    }
+
+    fn ioctl(&self, request: libc::c_ulong) -> Result<()> {
+        // SAFETY: the perf event ioctls take no argument
+        if unsafe { libc::ioctl(self.fd.as_raw_fd(), request, 0) } < 0 {
+            return Err(new_error!(
+                "Failed to control the Intel PT event: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        Ok(())
+    }
+
+    /// The trace written to the AUX area since it was last taken
+    fn take_trace(&self) -> IntelPtTrace {
+        let head = self.ring.field(AUX_HEAD).load(Ordering::Acquire);
+        let tail = self.ring.field(AUX_TAIL).load(Ordering::Relaxed);
+        let data = read_ring(self.aux.bytes(), head, tail);
+        self.ring.field(AUX_TAIL).store(head, Ordering::Release);
+
+        let records = self.ring.range(
+            self.ring.field(DATA_OFFSET).load(Ordering::Relaxed) as usize,
+            self.ring.field(DATA_SIZE).load(Ordering::Relaxed) as usize,
+        );
+        let head = self.ring.field(DATA_HEAD).load(Ordering::Acquire);
+        let tail = self.ring.field(DATA_TAIL).load(Ordering::Relaxed);
+        let truncated = aux_truncated(&read_ring(records, head, tail));
+        self.ring.field(DATA_TAIL).store(head, Ordering::Release);
+
+        IntelPtTrace { data, truncated }
+    }
+}
+
+/// The data written to `ring` between `tail` and `head`, which are
+/// positions in the stream written to it. The writer never gets more than
+/// `ring.len()` bytes ahead of `tail`.
+fn read_ring(ring: &[u8], head: u64, tail: u64) -> Vec<u8> {
+    let size = ring.len() as u64;
+    let start = tail.max(head.saturating_sub(size));
+    let mut data = Vec::with_capacity(head.saturating_sub(start) as usize);
+    let mut pos = start;
+    while pos < head {
+        let offset = (pos % size) as usize;
+        let len = ((head - pos) as usize).min(ring.len() - offset);
+        data.extend_from_slice(&ring[offset..offset + len]);
+        pos += len as u64;
+    }
+    data
+}
+
+/// Whether any of the perf `records` is a `PERF_RECORD_AUX` flagged
+/// `PERF_AUX_FLAG_TRUNCATED`
+fn aux_truncated(records: &[u8]) -> bool {
+    let mut rest = records;
+    // Each record starts with a `struct perf_event_header`
+    while let Some(&[t0, t1, t2, t3, _, _, s0, s1]) = rest.first_chunk::<8>() {
+        let size = u16::from_ne_bytes([s0, s1]) as usize;
+        if size < 8 || size > rest.len() {
+            break;
+        }
+        // `struct perf_record_aux` has the flags after the header and the
+        // offset and size of the new data
+        if u32::from_ne_bytes([t0, t1, t2, t3]) == PERF_RECORD_AUX
+            && let Some(flags) = rest.get(24..32).and_then(|f| f.try_into().ok())
+            && u64::from_ne_bytes(flags) & PERF_AUX_FLAG_TRUNCATED != 0
+        {
+            return true;
+        }
+        rest = &rest[size..];
+    }
+    false
+}
+
+/// The perf event type of the Intel PT PMU
+fn pmu_type() -> Result<u32> {
+    std::fs::read_to_string(INTEL_PT_TYPE_PATH)
+        .map_err(|e| new_error!("Intel PT is not available on this host: {}", e))?
+        .trim()
+        .parse()
+        .map_err(|e| new_error!("Invalid Intel PT PMU type: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        PERF_ATTR_SIZE, PERF_AUX_FLAG_TRUNCATED, PERF_RECORD_AUX, PerfEventAttr, aux_truncated,
+        read_ring,
+    };
+
+    #[test]
+    fn attr_has_the_kernel_layout() {
+        assert_eq!(size_of::<PerfEventAttr>(), PERF_ATTR_SIZE as usize);
+    }
+
+    #[test]
+    fn ring_is_read_from_tail_to_head() {
+        let ring = *b"efghabcd";
+        assert_eq!(read_ring(&ring, 12, 4), b"abcdefgh");
+        assert_eq!(read_ring(&ring, 14, 9), b"fghab");
+        assert!(read_ring(&ring, 9, 9).is_empty());
+    }
+
+    /// A perf record of `type_`, with a header and `body`
+    fn record(type_: u32, body: &[u64]) -> Vec<u8> {
+        let size = (8 + body.len() * 8) as u16;
+        let mut record = type_.to_ne_bytes().to_vec();
+        record.extend(0u16.to_ne_bytes());
+        record.extend(size.to_ne_bytes());
+        for field in body {
+            record.extend(field.to_ne_bytes());
+        }
+        record
+    }
+
+    #[test]
+    fn truncation_is_read_from_aux_records() {
+        // PERF_RECORD_ITRACE_START, then an AUX record without flags
+        let mut records = record(12, &[1]);
+        records.extend(record(PERF_RECORD_AUX, &[0, 4096, 0]));
+        assert!(!aux_truncated(&records));
+
+        records.extend(record(
+            PERF_RECORD_AUX,
+            &[4096, 4096, PERF_AUX_FLAG_TRUNCATED],
+        ));
+        assert!(aux_truncated(&records));
+
+        // A record cut short is ignored
+        let truncated = record(PERF_RECORD_AUX, &[0, 4096, PERF_AUX_FLAG_TRUNCATED]);
+        assert!(!aux_truncated(&truncated[..20]));
+        assert!(!aux_truncated(&[]));
+    }
+}
@@ -30,6 +30,8 @@ use tracing_opentelemetry::OpenTelemetrySpanExt;
 #[cfg(feature = "hw-interrupts")]
 use vmm_sys_util::eventfd::EventFd;
 
+#[cfg(gdb)]
+use crate::hypervisor::gdb::arch::HwBreakpointSlots;
 #[cfg(gdb)]
 use crate::hypervisor::gdb::{DebugError, DebuggableVm};
 use crate::hypervisor::regs::{
@@ -41,8 +43,8 @@ use crate::hypervisor::virtual_machine::XSAVE_BUFFER_SIZE;
 #[cfg(feature = "hw-interrupts")]
 use crate::hypervisor::virtual_machine::x86_64::hw_interrupts::TimerThread;
 use crate::hypervisor::virtual_machine::{
-    CreateVmError, MapMemoryError, RegisterError, RunVcpuError, UnmapMemoryError, VirtualMachine,
-    VmExit,
+    CreateVmError, HypervisorError, MapMemoryError, RegisterError, RunVcpuError, UnmapMemoryError,
+    VirtualMachine, VmExit,
 };
 use crate::mem::memory_region::MemoryRegion;
 #[cfg(feature = "trace_guest")]
@@ -445,6 +447,10 @@ impl VirtualMachine for KvmVm {
         Ok(())
     }
 
+    fn guest_clock(&self) -> std::result::Result<Option<u64>, HypervisorError> {
+        Ok(Some(self.vm_fd.get_clock()?.clock))
+    }
+
     #[cfg(test)]
     #[cfg(not(feature = "i686-guest"))]
     fn set_xsave(&self, xsave: &[u32]) -> std::result::Result<(), RegisterError> {
@@ -472,18 +478,6 @@ impl VirtualMachine for KvmVm {
 
 #[cfg(gdb)]
 impl DebuggableVm for KvmVm {
-    fn translate_gva(&self, gva: u64) -> std::result::Result<u64, DebugError> {
-        let gpa = self
-            .vcpu_fd
-            .translate_gva(gva)
-            .map_err(|_| DebugError::TranslateGva(gva))?;
-        if gpa.valid == 0 {
-            Err(DebugError::TranslateGva(gva))
-        } else {
-            Ok(gpa.physical_address)
-        }
-    }
-
     fn set_debug(&mut self, enable: bool) -> std::result::Result<(), DebugError> {
         use kvm_bindings::{KVM_GUESTDBG_ENABLE, KVM_GUESTDBG_USE_HW_BP, KVM_GUESTDBG_USE_SW_BP};
 
@@ -525,43 +519,20 @@ impl DebuggableVm for KvmVm {
         Ok(())
     }
 
-    fn add_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
-        use crate::hypervisor::gdb::arch::MAX_NO_OF_HW_BP;
-
-        // Check if breakpoint already exists
-        if self.debug_regs.arch.debugreg[..4].contains(&addr) {
-            return Ok(());
-        }
-
-        // Find the first available LOCAL (L0–L3) slot
-        let i = (0..MAX_NO_OF_HW_BP)
-            .position(|i| self.debug_regs.arch.debugreg[7] & (1 << (i * 2)) == 0)
-            .ok_or(DebugError::TooManyHwBreakpoints(MAX_NO_OF_HW_BP))?;
-
-        // Assign to corresponding debug register
-        self.debug_regs.arch.debugreg[i] = addr;
-
-        // Enable LOCAL bit
-        self.debug_regs.arch.debugreg[7] |= 1 << (i * 2);
-
-        self.vcpu_fd
-            .set_guest_debug(&self.debug_regs)
-            .map_err(|e| RegisterError::SetDebugRegs(e.into()))?;
-        Ok(())
+    fn hw_breakpoint_slots(&self) -> std::result::Result<HwBreakpointSlots, DebugError> {
+        let debugreg = &self.debug_regs.arch.debugreg;
+        Ok(HwBreakpointSlots {
+            addrs: [debugreg[0], debugreg[1], debugreg[2], debugreg[3]],
+            dr7: debugreg[7],
+        })
     }
 
-    fn remove_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
-        // Find the index of the breakpoint
-        let index = self.debug_regs.arch.debugreg[..4]
-            .iter()
-            .position(|&a| a == addr)
-            .ok_or(DebugError::HwBreakpointNotFound(addr))?;
-
-        // Clear the address
-        self.debug_regs.arch.debugreg[index] = 0;
-
-        // Disable LOCAL bit
-        self.debug_regs.arch.debugreg[7] &= !(1 << (index * 2));
+    fn set_hw_breakpoint_slots(
+        &mut self,
+        slots: &HwBreakpointSlots,
+    ) -> std::result::Result<(), DebugError> {
+        self.debug_regs.arch.debugreg[..4].copy_from_slice(&slots.addrs);
+        self.debug_regs.arch.debugreg[7] = slots.dr7;
 
         self.vcpu_fd
             .set_guest_debug(&self.debug_regs)
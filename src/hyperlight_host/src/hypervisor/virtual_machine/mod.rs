@@ -202,6 +202,9 @@ pub enum RunVcpuError {
     IncrementRip(HypervisorError),
     #[error("Parse GPA access info failed")]
     ParseGpaAccessInfo,
+    #[cfg(target_os = "windows")]
+    #[error("Surrogate process exited with code {0:#x}")]
+    SurrogateDied(u32),
     #[error("Unknown error: {0}")]
     Unknown(HypervisorError),
 }
@@ -267,6 +270,9 @@ pub enum MapMemoryError {
     #[error("Operation not supported: {0}")]
     NotSupported(String),
     #[cfg(target_os = "windows")]
+    #[error("Surrogate process exited with code {0:#x}")]
+    SurrogateDied(u32),
+    #[cfg(target_os = "windows")]
     #[error("Surrogate process creation failed: {0}")]
     SurrogateProcess(String),
 }
@@ -332,6 +338,16 @@ pub(crate) trait VirtualMachine: Debug + Send {
     fn fpu(&self) -> std::result::Result<CommonFpu, RegisterError>;
     /// Set fpu regs
     fn set_fpu(&self, fpu: &CommonFpu) -> std::result::Result<(), RegisterError>;
+    /// Set regs and fpu regs, which every guest call does, in as few calls
+    /// to the hypervisor as it allows
+    fn set_regs_and_fpu(
+        &self,
+        regs: &CommonRegisters,
+        fpu: &CommonFpu,
+    ) -> std::result::Result<(), RegisterError> {
+        self.set_regs(regs)?;
+        self.set_fpu(fpu)
+    }
     /// Get special regs
     #[allow(dead_code)]
     fn sregs(&self) -> std::result::Result<CommonSpecialRegisters, RegisterError>;
@@ -353,6 +369,12 @@ pub(crate) trait VirtualMachine: Debug + Send {
     #[cfg(not(feature = "i686-guest"))]
     fn set_xsave(&self, xsave: &[u32]) -> std::result::Result<(), RegisterError>;
 
+    /// The guest's paravirtual clock, in nanoseconds since the VM was
+    /// created, or `None` if the hypervisor doesn't give guests one
+    fn guest_clock(&self) -> std::result::Result<Option<u64>, HypervisorError> {
+        Ok(None)
+    }
+
     /// Get partition handle
     #[cfg(target_os = "windows")]
     fn partition_handle(&self) -> windows::Win32::System::Hypervisor::WHV_PARTITION_HANDLE;
@@ -20,19 +20,21 @@ use std::fmt::Debug;
 use std::sync::Arc;
 use std::sync::LazyLock;
 
+use hyperlight_common::clock::HV_REFERENCE_TIME_TICK_NS;
 use hyperlight_common::outb::VmAction;
 #[cfg(feature = "hw-interrupts")]
 use mshv_bindings::LapicState;
 #[cfg(gdb)]
 use mshv_bindings::{DebugRegisters, hv_message_type_HVMSG_X64_EXCEPTION_INTERCEPT};
 use mshv_bindings::{
-    FloatingPointUnit, SpecialRegisters, StandardRegisters, XSave, hv_message_type,
-    hv_message_type_HVMSG_GPA_INTERCEPT, hv_message_type_HVMSG_UNMAPPED_GPA,
+    FloatingPointUnit, HV_X64_REGISTER_CLASS_IP, SpecialRegisters, StandardRegisters, XSave,
+    hv_message_type, hv_message_type_HVMSG_GPA_INTERCEPT, hv_message_type_HVMSG_UNMAPPED_GPA,
     hv_message_type_HVMSG_X64_HALT, hv_message_type_HVMSG_X64_IO_PORT_INTERCEPT,
+    hv_partition_property_code_HV_PARTITION_PROPERTY_REFERENCE_TIME,
     hv_partition_property_code_HV_PARTITION_PROPERTY_SYNTHETIC_PROC_FEATURES,
     hv_partition_synthetic_processor_features, hv_register_assoc,
-    hv_register_name_HV_X64_REGISTER_RIP, hv_register_value, mshv_create_partition_v2,
-    mshv_user_mem_region,
+    hv_register_name_HV_X64_REGISTER_RIP, hv_register_value, hv_vp_register_page,
+    mshv_create_partition_v2, mshv_user_mem_region,
 };
 #[cfg(feature = "hw-interrupts")]
 use mshv_bindings::{
@@ -40,24 +42,26 @@ use mshv_bindings::{
 };
 #[cfg(feature = "hw-interrupts")]
 use mshv_ioctls::InterruptRequest;
-use mshv_ioctls::{Mshv, VcpuFd, VmFd};
+use mshv_ioctls::{Mshv, MshvError, VcpuFd, VmFd};
 use tracing::{Span, instrument};
 #[cfg(feature = "trace_guest")]
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
+#[cfg(gdb)]
+use crate::hypervisor::gdb::arch::HwBreakpointSlots;
 #[cfg(gdb)]
 use crate::hypervisor::gdb::{DebugError, DebuggableVm};
 use crate::hypervisor::regs::{
     CommonDebugRegs, CommonFpu, CommonRegisters, CommonSpecialRegisters, FP_CONTROL_WORD_DEFAULT,
-    MXCSR_DEFAULT,
+    MSHV_FPU_NAMES_LEN, MSHV_REGS_NAMES_LEN, MXCSR_DEFAULT,
 };
 #[cfg(all(test, not(feature = "i686-guest")))]
 use crate::hypervisor::virtual_machine::XSAVE_BUFFER_SIZE;
 #[cfg(feature = "hw-interrupts")]
 use crate::hypervisor::virtual_machine::x86_64::hw_interrupts::TimerThread;
 use crate::hypervisor::virtual_machine::{
-    CreateVmError, MapMemoryError, RegisterError, RunVcpuError, UnmapMemoryError, VirtualMachine,
-    VmExit, XSAVE_MIN_SIZE,
+    CreateVmError, HypervisorError, MapMemoryError, RegisterError, RunVcpuError, UnmapMemoryError,
+    VirtualMachine, VmExit, XSAVE_MIN_SIZE,
 };
 use crate::mem::memory_region::{MemoryRegion, MemoryRegionFlags};
 #[cfg(feature = "trace_guest")]
@@ -116,7 +120,17 @@ impl MshvVm {
             .map_err(|e| CreateVmError::CreateVmFd(e.into()))?;
 
         let vcpu_fd = {
-            let features: hv_partition_synthetic_processor_features = Default::default();
+            // Only the Hyper-V reference time is given to the guest, through
+            // the reference TSC page or its MSR, as its paravirtual clock
+            let mut features: hv_partition_synthetic_processor_features = Default::default();
+            // SAFETY: the features are plain bits
+            unsafe {
+                let bits = &mut features.__bindgen_anon_1;
+                bits.set_hypervisor_present(1);
+                bits.set_hv1(1);
+                bits.set_access_partition_reference_counter(1);
+                bits.set_access_partition_reference_tsc(1);
+            }
 
             vm_fd
                 .set_partition_property(
@@ -150,6 +164,33 @@ impl MshvVm {
             timer: None,
         })
     }
+
+    /// The VP register page the driver maps for the vCPU, if it maps one
+    /// and the hypervisor filled it in when the vCPU last exited
+    fn vp_register_page(&self) -> Option<*mut hv_vp_register_page> {
+        let page = self.vcpu_fd.get_vp_reg_page()?.0;
+        // SAFETY: the page stays mapped for as long as the vCPU fd is open
+        (unsafe { (*page).isvalid } != 0).then_some(page)
+    }
+
+    /// Set RIP. Through the VP register page, the hypervisor picks the new
+    /// value up on the next run, which saves a hypercall on every IO exit.
+    fn set_rip(&self, rip: u64) -> std::result::Result<(), MshvError> {
+        if let Some(page) = self.vp_register_page() {
+            // SAFETY: the page is valid, and the vCPU is not running, so
+            // nothing else writes to it
+            unsafe {
+                (*page).__bindgen_anon_1.__bindgen_anon_1.rip = rip;
+                (*page).dirty |= 1 << HV_X64_REGISTER_CLASS_IP;
+            }
+            return Ok(());
+        }
+        self.vcpu_fd.set_reg(&[hv_register_assoc {
+            name: hv_register_name_HV_X64_REGISTER_RIP,
+            value: hv_register_value { reg64: rip },
+            ..Default::default()
+        }])
+    }
 }
 
 impl VirtualMachine for MshvVm {
@@ -220,14 +261,7 @@ impl VirtualMachine for MshvVm {
                             let is_write = io_message.header.intercept_access_type != 0;
 
                             // mshv, unlike kvm, does not automatically increment RIP
-                            self.vcpu_fd
-                                .set_reg(&[hv_register_assoc {
-                                    name: hv_register_name_HV_X64_REGISTER_RIP,
-                                    value: hv_register_value {
-                                        reg64: rip + instruction_length,
-                                    },
-                                    ..Default::default()
-                                }])
+                            self.set_rip(rip + instruction_length)
                                 .map_err(|e| RunVcpuError::IncrementRip(e.into()))?;
 
                             // VmAction::Halt always means "I'm done", regardless
@@ -353,6 +387,24 @@ impl VirtualMachine for MshvVm {
         Ok(())
     }
 
+    fn set_regs_and_fpu(
+        &self,
+        regs: &CommonRegisters,
+        fpu: &CommonFpu,
+    ) -> std::result::Result<(), RegisterError> {
+        // With a VP register page, the general purpose registers are set
+        // without a hypercall, so there is nothing to batch them with
+        if self.vp_register_page().is_some() {
+            self.set_regs(regs)?;
+            return self.set_fpu(fpu);
+        }
+        let regs: [hv_register_assoc; MSHV_REGS_NAMES_LEN] = regs.into();
+        let fpu: [hv_register_assoc; MSHV_FPU_NAMES_LEN] = fpu.into();
+        self.vcpu_fd
+            .set_reg(&[&regs[..], &fpu[..]].concat())
+            .map_err(|e| RegisterError::SetRegs(e.into()))
+    }
+
     fn fpu(&self) -> std::result::Result<CommonFpu, RegisterError> {
         let mshv_fpu = self
             .vcpu_fd
@@ -444,6 +496,13 @@ impl VirtualMachine for MshvVm {
         Ok(())
     }
 
+    fn guest_clock(&self) -> std::result::Result<Option<u64>, HypervisorError> {
+        let ticks = self.vm_fd.get_partition_property(
+            hv_partition_property_code_HV_PARTITION_PROPERTY_REFERENCE_TIME,
+        )?;
+        Ok(Some(ticks * HV_REFERENCE_TIME_TICK_NS))
+    }
+
     #[cfg(test)]
     #[cfg(not(feature = "i686-guest"))]
     fn set_xsave(&self, xsave: &[u32]) -> std::result::Result<(), RegisterError> {
@@ -473,21 +532,6 @@ impl VirtualMachine for MshvVm {
 
 #[cfg(gdb)]
 impl DebuggableVm for MshvVm {
-    fn translate_gva(&self, gva: u64) -> std::result::Result<u64, DebugError> {
-        use mshv_bindings::HV_TRANSLATE_GVA_VALIDATE_READ;
-
-        // Do not use HV_TRANSLATE_GVA_VALIDATE_WRITE, since many
-        // things that are interesting to debug are not in fact
-        // writable from the guest's point of view.
-        let flags = HV_TRANSLATE_GVA_VALIDATE_READ as u64;
-        let (addr, _) = self
-            .vcpu_fd
-            .translate_gva(gva, flags)
-            .map_err(|_| DebugError::TranslateGva(gva))?;
-
-        Ok(addr)
-    }
-
     fn set_debug(&mut self, enabled: bool) -> std::result::Result<(), DebugError> {
         use mshv_bindings::{
             HV_INTERCEPT_ACCESS_MASK_EXECUTE, HV_INTERCEPT_ACCESS_MASK_NONE,
@@ -531,52 +575,25 @@ impl DebuggableVm for MshvVm {
         Ok(())
     }
 
-    fn add_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
-        use crate::hypervisor::gdb::arch::MAX_NO_OF_HW_BP;
+    fn hw_breakpoint_slots(&self) -> std::result::Result<HwBreakpointSlots, DebugError> {
+        let regs = self.debug_regs()?;
+        Ok(HwBreakpointSlots {
+            addrs: [regs.dr0, regs.dr1, regs.dr2, regs.dr3],
+            dr7: regs.dr7,
+        })
+    }
 
+    fn set_hw_breakpoint_slots(
+        &mut self,
+        slots: &HwBreakpointSlots,
+    ) -> std::result::Result<(), DebugError> {
         let mut regs = self.debug_regs()?;
-
-        // Check if breakpoint already exists
-        if [regs.dr0, regs.dr1, regs.dr2, regs.dr3].contains(&addr) {
-            return Ok(());
-        }
-
-        // Find the first available LOCAL (L0–L3) slot
-        let i = (0..MAX_NO_OF_HW_BP)
-            .position(|i| regs.dr7 & (1 << (i * 2)) == 0)
-            .ok_or(DebugError::TooManyHwBreakpoints(MAX_NO_OF_HW_BP))?;
-
-        // Assign to corresponding debug register
-        *[&mut regs.dr0, &mut regs.dr1, &mut regs.dr2, &mut regs.dr3][i] = addr;
-
-        // Enable LOCAL bit
-        regs.dr7 |= 1 << (i * 2);
+        [regs.dr0, regs.dr1, regs.dr2, regs.dr3] = slots.addrs;
+        regs.dr7 = slots.dr7;
 
         self.set_debug_regs(&regs)?;
         Ok(())
     }
-
-    fn remove_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
-        let mut debug_regs = self.debug_regs()?;
-
-        let regs = [
-            &mut debug_regs.dr0,
-            &mut debug_regs.dr1,
-            &mut debug_regs.dr2,
-            &mut debug_regs.dr3,
-        ];
-
-        if let Some(i) = regs.iter().position(|&&mut reg| reg == addr) {
-            // Clear the address
-            *regs[i] = 0;
-            // Disable LOCAL bit
-            debug_regs.dr7 &= !(1 << (i * 2));
-            self.set_debug_regs(&debug_regs)?;
-            Ok(())
-        } else {
-            Err(DebugError::HwBreakpointNotFound(addr))
-        }
-    }
 }
 
 /// Cast MSHV `LapicState.regs` (`[c_char; 1024]`) to a `&[u8]` slice
@@ -16,6 +16,7 @@ limitations under the License.
 
 use std::os::raw::c_void;
 
+use hyperlight_common::clock::HV_REFERENCE_TIME_TICK_NS;
 use hyperlight_common::outb::VmAction;
 #[cfg(feature = "trace_guest")]
 use tracing::Span;
@@ -28,6 +29,8 @@ use windows::Win32::System::Memory::{MEMORY_MAPPED_VIEW_ADDRESS, UnmapViewOfFile
 use windows::core::s;
 use windows_result::HRESULT;
 
+#[cfg(gdb)]
+use crate::hypervisor::gdb::arch::HwBreakpointSlots;
 #[cfg(gdb)]
 use crate::hypervisor::gdb::{DebugError, DebuggableVm};
 use crate::hypervisor::regs::{
@@ -37,7 +40,9 @@ use crate::hypervisor::regs::{
     WHP_SREGS_NAMES_LEN,
 };
 use crate::hypervisor::surrogate_process::SurrogateProcess;
-use crate::hypervisor::surrogate_process_manager::get_surrogate_process_manager;
+use crate::hypervisor::surrogate_process_manager::{
+    SurrogateIsolation, get_surrogate_process_manager,
+};
 #[cfg(feature = "hw-interrupts")]
 use crate::hypervisor::virtual_machine::x86_64::hw_interrupts::TimerThread;
 use crate::hypervisor::virtual_machine::{
@@ -111,7 +116,9 @@ pub(crate) struct WhpVm {
 unsafe impl Send for WhpVm {}
 
 impl WhpVm {
-    pub(crate) fn new() -> Result<Self, CreateVmError> {
+    /// Creates a partition, backed by a surrogate process restricted by
+    /// `isolation`
+    pub(crate) fn new(isolation: SurrogateIsolation) -> Result<Self, CreateVmError> {
         const NUM_CPU: u32 = 1;
 
         let partition = unsafe {
@@ -130,6 +137,8 @@ impl WhpVm {
             #[cfg(feature = "hw-interrupts")]
             Self::enable_lapic_emulation(p)?;
 
+            Self::enable_reference_time(p)?;
+
             WHvSetupPartition(p).map_err(|e| CreateVmError::InitializeVm(e.into()))?;
             WHvCreateVirtualProcessor(p, 0, 0)
                 .map_err(|e| CreateVmError::CreateVcpuFd(e.into()))?;
@@ -146,7 +155,7 @@ impl WhpVm {
         let mgr = get_surrogate_process_manager()
             .map_err(|e| CreateVmError::SurrogateProcess(e.to_string()))?;
         let surrogate_process = mgr
-            .get_surrogate_process()
+            .get_isolated_surrogate_process(isolation)
             .map_err(|e| CreateVmError::SurrogateProcess(e.to_string()))?;
 
         Ok(WhpVm {
@@ -192,7 +201,10 @@ impl VirtualMachine for WhpVm {
                 region.host_region.start.handle_size,
                 &region.region_type.surrogate_mapping(),
             )
-            .map_err(|e| MapMemoryError::SurrogateProcess(e.to_string()))?;
+            .map_err(|e| match self.surrogate_process.exit_code() {
+                Some(exit_code) => MapMemoryError::SurrogateDied(exit_code),
+                None => MapMemoryError::SurrogateProcess(e.to_string()),
+            })?;
         let surrogate_addr = surrogate_base.wrapping_add(region.host_region.start.offset);
 
         let flags = region
@@ -302,7 +314,10 @@ impl VirtualMachine for WhpVm {
                     &mut exit_context as *mut _ as *mut c_void,
                     std::mem::size_of::<WHV_RUN_VP_EXIT_CONTEXT>() as u32,
                 )
-                .map_err(|e| RunVcpuError::Unknown(e.into()))?;
+                .map_err(|e| match self.surrogate_process.exit_code() {
+                    Some(exit_code) => RunVcpuError::SurrogateDied(exit_code),
+                    None => RunVcpuError::Unknown(e.into()),
+                })?;
             }
 
             match exit_context.ExitReason {
@@ -379,6 +394,11 @@ impl VirtualMachine for WhpVm {
                     return Ok(VmExit::Halt());
                 }
                 WHvRunVpExitReasonMemoryAccess => {
+                    // The guest's memory is unmapped when the surrogate
+                    // process exits, so its accesses all fault
+                    if let Some(exit_code) = self.surrogate_process.exit_code() {
+                        return Err(RunVcpuError::SurrogateDied(exit_code));
+                    }
                     let gpa = unsafe { exit_context.Anonymous.MemoryAccess.Gpa };
                     let access_info = unsafe {
                         WHV_MEMORY_ACCESS_TYPE(
@@ -531,6 +551,20 @@ impl VirtualMachine for WhpVm {
         Ok(())
     }
 
+    fn set_regs_and_fpu(
+        &self,
+        regs: &CommonRegisters,
+        fpu: &CommonFpu,
+    ) -> std::result::Result<(), RegisterError> {
+        let whp_regs: [(WHV_REGISTER_NAME, Align16<WHV_REGISTER_VALUE>); WHP_REGS_NAMES_LEN] =
+            regs.into();
+        let whp_fpu: [(WHV_REGISTER_NAME, Align16<WHV_REGISTER_VALUE>); WHP_FPU_NAMES_LEN] =
+            fpu.into();
+        self.set_registers(&[&whp_regs[..], &whp_fpu[..]].concat())
+            .map_err(|e| RegisterError::SetRegs(e.into()))?;
+        Ok(())
+    }
+
     fn sregs(&self) -> std::result::Result<CommonSpecialRegisters, RegisterError> {
         let mut whp_sregs_values: [Align16<WHV_REGISTER_VALUE>; WHP_SREGS_NAMES_LEN] =
             unsafe { std::mem::zeroed() };
@@ -753,6 +787,22 @@ impl VirtualMachine for WhpVm {
         Ok(())
     }
 
+    fn guest_clock(&self) -> std::result::Result<Option<u64>, HypervisorError> {
+        let mut property = WHV_PARTITION_PROPERTY::default();
+        // SAFETY: the property outlives the call, and is as large as it
+        // says it is
+        unsafe {
+            WHvGetPartitionProperty(
+                self.partition,
+                WHvPartitionPropertyCodeReferenceTime,
+                &mut property as *mut _ as *mut c_void,
+                std::mem::size_of::<WHV_PARTITION_PROPERTY>() as u32,
+                None,
+            )?;
+            Ok(Some(property.ReferenceTime * HV_REFERENCE_TIME_TICK_NS))
+        }
+    }
+
     #[cfg(test)]
     #[cfg(not(feature = "i686-guest"))]
     fn set_xsave(&self, xsave: &[u32]) -> std::result::Result<(), RegisterError> {
@@ -807,29 +857,6 @@ impl VirtualMachine for WhpVm {
 
 #[cfg(gdb)]
 impl DebuggableVm for WhpVm {
-    fn translate_gva(&self, gva: u64) -> std::result::Result<u64, DebugError> {
-        let mut gpa = 0;
-        let mut result = WHV_TRANSLATE_GVA_RESULT::default();
-
-        // Only validate read access because the write access is handled through the
-        // host memory mapping
-        let translateflags = WHvTranslateGvaFlagValidateRead;
-
-        unsafe {
-            WHvTranslateGva(
-                self.partition,
-                0,
-                gva,
-                translateflags,
-                &mut result,
-                &mut gpa,
-            )
-            .map_err(|_| DebugError::TranslateGva(gva))?;
-        }
-
-        Ok(gpa)
-    }
-
     fn set_debug(&mut self, enable: bool) -> std::result::Result<(), DebugError> {
         let extended_vm_exits = if enable { 1 << 2 } else { 0 };
         let exception_exit_bitmap = if enable {
@@ -884,53 +911,78 @@ impl DebuggableVm for WhpVm {
         Ok(())
     }
 
-    fn add_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
-        use crate::hypervisor::gdb::arch::MAX_NO_OF_HW_BP;
+    fn hw_breakpoint_slots(&self) -> std::result::Result<HwBreakpointSlots, DebugError> {
+        let regs = self.debug_regs()?;
+        Ok(HwBreakpointSlots {
+            addrs: [regs.dr0, regs.dr1, regs.dr2, regs.dr3],
+            dr7: regs.dr7,
+        })
+    }
 
-        // Get current debug registers
+    fn set_hw_breakpoint_slots(
+        &mut self,
+        slots: &HwBreakpointSlots,
+    ) -> std::result::Result<(), DebugError> {
         let mut regs = self.debug_regs()?;
-
-        // Check if breakpoint already exists
-        if [regs.dr0, regs.dr1, regs.dr2, regs.dr3].contains(&addr) {
-            return Ok(());
-        }
-
-        // Find the first available LOCAL (L0–L3) slot
-        let i = (0..MAX_NO_OF_HW_BP)
-            .position(|i| regs.dr7 & (1 << (i * 2)) == 0)
-            .ok_or(DebugError::TooManyHwBreakpoints(MAX_NO_OF_HW_BP))?;
-
-        // Assign to corresponding debug register
-        *[&mut regs.dr0, &mut regs.dr1, &mut regs.dr2, &mut regs.dr3][i] = addr;
-
-        // Enable LOCAL bit
-        regs.dr7 |= 1 << (i * 2);
+        [regs.dr0, regs.dr1, regs.dr2, regs.dr3] = slots.addrs;
+        regs.dr7 = slots.dr7;
 
         self.set_debug_regs(&regs)?;
         Ok(())
     }
+}
 
-    fn remove_hw_breakpoint(&mut self, addr: u64) -> std::result::Result<(), DebugError> {
-        // Get current debug registers
-        let mut debug_regs = self.debug_regs()?;
-
-        let regs = [
-            &mut debug_regs.dr0,
-            &mut debug_regs.dr1,
-            &mut debug_regs.dr2,
-            &mut debug_regs.dr3,
-        ];
+impl WhpVm {
+    /// Give the guest the Hyper-V reference time, through the reference
+    /// TSC page or its MSR, as its paravirtual clock. Hosts that can't are
+    /// left without it, as the guest looks for it in CPUID.
+    fn enable_reference_time(partition: WHV_PARTITION_HANDLE) -> Result<(), CreateVmError> {
+        // HypervisorPresent, Hv1, AccessPartitionReferenceCounter and
+        // AccessPartitionReferenceTsc
+        const HYPERVISOR_PRESENT_AND_HV1: u64 = (1 << 0) | (1 << 1);
+        const REFERENCE_TIME: u64 = HYPERVISOR_PRESENT_AND_HV1 | (1 << 3) | (1 << 9);
 
-        if let Some(i) = regs.iter().position(|&&mut reg| reg == addr) {
-            // Clear the address
-            *regs[i] = 0;
-            // Disable LOCAL bit
-            debug_regs.dr7 &= !(1 << (i * 2));
+        let mut capability: WHV_CAPABILITY = Default::default();
+        // SAFETY: the capability outlives the call, and is as large as it
+        // says it is
+        let supported = unsafe {
+            match WHvGetCapability(
+                WHvCapabilityCodeSyntheticProcessorFeaturesBanks,
+                &mut capability as *mut _ as *mut c_void,
+                std::mem::size_of::<WHV_CAPABILITY>() as u32,
+                None,
+            ) {
+                Ok(()) => {
+                    capability
+                        .SyntheticProcessorFeaturesBanks
+                        .Anonymous
+                        .AsUINT64[0]
+                }
+                Err(_) => 0,
+            }
+        };
+        let features = supported & REFERENCE_TIME;
+        if features & HYPERVISOR_PRESENT_AND_HV1 != HYPERVISOR_PRESENT_AND_HV1 {
+            return Ok(());
+        }
 
-            self.set_debug_regs(&debug_regs)?;
-            Ok(())
-        } else {
-            Err(DebugError::HwBreakpointNotFound(addr))
+        let property = WHV_SYNTHETIC_PROCESSOR_FEATURES_BANKS {
+            BanksCount: WHV_SYNTHETIC_PROCESSOR_FEATURES_BANKS_COUNT,
+            Reserved0: 0,
+            Anonymous: WHV_SYNTHETIC_PROCESSOR_FEATURES_BANKS_0 {
+                AsUINT64: [features],
+            },
+        };
+        // SAFETY: the property outlives the call, and is as large as it
+        // says it is
+        unsafe {
+            WHvSetPartitionProperty(
+                partition,
+                WHvPartitionPropertyCodeSyntheticProcessorFeaturesBanks,
+                &property as *const _ as *const c_void,
+                std::mem::size_of_val(&property) as u32,
+            )
+            .map_err(|e| CreateVmError::SetPartitionProperty(e.into()))
         }
     }
 }
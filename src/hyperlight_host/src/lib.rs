@@ -42,6 +42,9 @@ limitations under the License.
 #[cfg(feature = "build-metadata")]
 use std::sync::Once;
 
+// Lets code generated by `hyperlight_host_fn` refer to this crate by name
+extern crate self as hyperlight_host;
+
 #[cfg(feature = "build-metadata")]
 /// The `built` crate is used to generate a `built.rs` file that contains
 /// information about the build environment. This information is used to
@@ -51,6 +54,8 @@ pub(crate) mod built_info {
 }
 /// Dealing with errors, including errors across VM boundaries
 pub mod error;
+/// ETW events for tracing sandboxes on Windows
+pub(crate) mod etw;
 /// Wrappers for host and guest functions.
 pub mod func;
 /// Wrappers for hypervisor implementations
@@ -71,6 +76,11 @@ pub mod hypervisor;
 pub mod mem;
 /// Metric definitions and helpers
 pub mod metrics;
+/// OpenTelemetry exporters for the crate's metrics and traces
+#[cfg(feature = "otel")]
+pub mod otel;
+/// PerfMon counters for monitoring sandboxes on Windows
+pub(crate) mod perfmon;
 /// The main sandbox implementations. Do not use this module directly in code
 /// outside this file. Types from this module needed for public consumption are
 /// re-exported below.
@@ -81,9 +91,15 @@ pub(crate) mod signal_handlers;
 /// Utilities for testing including interacting with `simpleguest` testing guest binary
 #[cfg(test)]
 pub(crate) mod testing;
+/// USDT probes for tracing sandboxes with eBPF
+pub(crate) mod usdt;
 
 /// The re-export for the `HyperlightError` type
 pub use error::HyperlightError;
+/// The re-export for the `hyperlight_host_fn` macro
+pub use hyperlight_host_macro::hyperlight_host_fn;
+/// The re-export for the `preflight` function
+pub use hypervisor::preflight::preflight;
 /// The re-export for the `is_hypervisor_present` type
 pub use hypervisor::virtual_machine::is_hypervisor_present;
 /// A sandbox that can call be used to make multiple calls to guest functions,
@@ -14,7 +14,6 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-#[cfg(feature = "mem_profile")]
 use std::sync::Arc;
 
 #[cfg(target_arch = "aarch64")]
@@ -28,6 +27,8 @@ use goblin::elf32::program_header::PT_LOAD;
 use goblin::elf64::program_header::PT_LOAD;
 
 use super::exe::LoadInfo;
+use crate::sandbox::coverage::COUNTERS_SECTION;
+use crate::sandbox::symbolizer::GuestSymbolizer;
 use crate::{Result, log_then_return, new_error};
 
 #[cfg(feature = "mem_profile")]
@@ -171,6 +172,30 @@ impl ElfInfo {
         self.entry
     }
 
+    /// Returns the virtual address of the function symbol `name`, looking
+    /// in both the static and the dynamic symbol tables
+    pub(crate) fn function_symbol_va(&self, name: &str) -> Option<u64> {
+        let elf = Elf::parse(&self.payload).ok()?;
+        let syms = elf.syms.iter().map(|sym| (sym, &elf.strtab));
+        let dynsyms = elf.dynsyms.iter().map(|sym| (sym, &elf.dynstrtab));
+        syms.chain(dynsyms)
+            .find(|(sym, strtab)| {
+                sym.is_function() && sym.st_value != 0 && strtab.get_at(sym.st_name) == Some(name)
+            })
+            .map(|(sym, _)| sym.st_value)
+    }
+
+    /// Returns the virtual addresses of the SanitizerCoverage edge
+    /// counters, if the binary is instrumented for coverage
+    pub(crate) fn coverage_counters_va(&self) -> Option<std::ops::Range<u64>> {
+        let elf = Elf::parse(&self.payload).ok()?;
+        elf.section_headers
+            .iter()
+            .find(|sh| elf.shdr_strtab.get_at(sh.sh_name) == Some(COUNTERS_SECTION))
+            .filter(|sh| sh.sh_size != 0)
+            .map(|sh| sh.sh_addr..sh.sh_addr + sh.sh_size)
+    }
+
     /// Returns the hyperlight version string embedded in the guest binary, if
     /// present. Used to detect version/ABI mismatches between guest and host.
     pub(crate) fn guest_bin_version(&self) -> Option<&str> {
@@ -236,6 +261,14 @@ impl ElfInfo {
                 }
             }
         }
+        // A binary that cannot be symbolized is still loaded, its guest
+        // addresses are just not resolved
+        let symbolizer =
+            Arc::new(GuestSymbolizer::new(&self.payload, load_addr as u64).unwrap_or_default());
+        let coverage_counters = self.coverage_counters_va().map(|vas| {
+            let base_va = self.get_base_va();
+            load_addr as u64 + (vas.start - base_va)..load_addr as u64 + (vas.end - base_va)
+        });
         cfg_if::cfg_if! {
             if #[cfg(feature = "mem_profile")] {
                 let va_size = self.get_va_size() as u64;
@@ -247,10 +280,15 @@ impl ElfInfo {
                         va_size,
                         base_svma,
                         shdrs: self.shdrs,
-                    })
+                    }),
+                    symbolizer,
+                    coverage_counters,
                 })
             } else {
-                Ok(LoadInfo {})
+                Ok(LoadInfo {
+                    symbolizer,
+                    coverage_counters,
+                })
             }
         }
     }
@@ -16,16 +16,24 @@ limitations under the License.
 
 use std::fs::File;
 use std::io::Read;
-#[cfg(feature = "mem_profile")]
 use std::sync::Arc;
 use std::vec::Vec;
 
 use super::elf::ElfInfo;
 use super::ptr_offset::Offset;
-use crate::Result;
+use crate::sandbox::symbolizer::GuestSymbolizer;
+use crate::{Result, log_then_return};
 
 pub enum ExeInfo {
     Elf(ElfInfo),
+    Raw(RawBlobInfo),
+}
+
+/// A flat, position-independent binary with no headers, which is loaded
+/// as-is at the start of the code region
+pub(crate) struct RawBlobInfo {
+    payload: Vec<u8>,
+    entry_offset: u64,
 }
 
 #[cfg(feature = "mem_profile")]
@@ -62,6 +70,12 @@ impl<A> framehop::ModuleSectionInfo<A> for &DummyUnwindInfo {
 pub(crate) struct LoadInfo {
     #[cfg(feature = "mem_profile")]
     pub(crate) info: Arc<dyn UnwindInfo>,
+    /// The symbolizer of the binary, used to resolve guest backtraces and
+    /// crash sites
+    pub(crate) symbolizer: Arc<GuestSymbolizer>,
+    /// Where the binary's SanitizerCoverage edge counters are loaded, if
+    /// it is instrumented for coverage
+    pub(crate) coverage_counters: Option<std::ops::Range<u64>>,
 }
 
 impl LoadInfo {
@@ -69,6 +83,8 @@ impl LoadInfo {
         LoadInfo {
             #[cfg(feature = "mem_profile")]
             info: Arc::new(DummyUnwindInfo {}),
+            symbolizer: Arc::default(),
+            coverage_counters: None,
         }
     }
 }
@@ -83,20 +99,48 @@ impl ExeInfo {
     pub fn from_buf(buf: &[u8]) -> Result<Self> {
         ElfInfo::new(buf).map(ExeInfo::Elf)
     }
+    /// A raw blob whose entrypoint is `entry_offset` bytes from its start
+    pub fn from_raw(buf: &[u8], entry_offset: u64) -> Result<Self> {
+        if buf.is_empty() {
+            log_then_return!("raw guest blob must not be empty");
+        }
+        if entry_offset >= buf.len() as u64 {
+            log_then_return!(
+                "raw guest blob entry offset {:#x} is outside the {:#x} byte blob",
+                entry_offset,
+                buf.len()
+            );
+        }
+        Ok(ExeInfo::Raw(RawBlobInfo {
+            payload: buf.to_vec(),
+            entry_offset,
+        }))
+    }
     pub fn entrypoint(&self) -> Offset {
         match self {
             ExeInfo::Elf(elf) => Offset::from(elf.entrypoint_va()),
+            ExeInfo::Raw(raw) => Offset::from(raw.entry_offset),
+        }
+    }
+    /// Returns the address of the exported function `name`, if the binary
+    /// has one
+    pub fn function_symbol(&self, name: &str) -> Option<Offset> {
+        match self {
+            ExeInfo::Elf(elf) => elf.function_symbol_va(name).map(Offset::from),
+            ExeInfo::Raw(_) => None,
         }
     }
     /// Returns the base virtual address of the loaded binary (lowest PT_LOAD p_vaddr).
     pub fn base_va(&self) -> u64 {
         match self {
             ExeInfo::Elf(elf) => elf.get_base_va(),
+            ExeInfo::Raw(_) => 0,
         }
     }
     pub fn loaded_size(&self) -> usize {
         match self {
             ExeInfo::Elf(elf) => elf.get_va_size(),
+            ExeInfo::Raw(raw) => raw.payload.len(),
         }
     }
 
@@ -106,6 +150,7 @@ impl ExeInfo {
     pub fn guest_bin_version(&self) -> Option<&str> {
         match self {
             ExeInfo::Elf(elf) => elf.guest_bin_version(),
+            ExeInfo::Raw(_) => None,
         }
     }
     // todo: this doesn't morally need to be &mut self, since we're
@@ -115,6 +160,10 @@ impl ExeInfo {
     pub fn load(self, load_addr: usize, target: &mut [u8]) -> Result<LoadInfo> {
         match self {
             ExeInfo::Elf(elf) => elf.load_at(load_addr, target),
+            ExeInfo::Raw(raw) => {
+                target[..raw.payload.len()].copy_from_slice(&raw.payload);
+                Ok(LoadInfo::dummy())
+            }
         }
     }
 }
@@ -62,8 +62,9 @@ limitations under the License.
 
 use std::fmt::Debug;
 use std::mem::{offset_of, size_of};
+use std::ops::Range;
 
-use hyperlight_common::mem::{HyperlightPEB, PAGE_SIZE_USIZE};
+use hyperlight_common::mem::{ABI_VERSION, HyperlightPEB, PAGE_SIZE_USIZE};
 use tracing::{Span, instrument};
 
 use super::memory_region::MemoryRegionType::{Code, Heap, InitData, Peb};
@@ -219,17 +220,26 @@ pub(crate) struct SandboxMemoryLayout {
     /// The heap size of this sandbox.
     pub(super) heap_size: usize,
     init_data_size: usize,
+    startup_config_size: usize,
+    args_size: usize,
 
     /// The following fields are offsets to the actual PEB struct fields.
     /// They are used when writing the PEB struct itself
     peb_offset: usize,
+    peb_abi_version_offset: usize,
     peb_input_data_offset: usize,
     peb_output_data_offset: usize,
     peb_init_data_offset: usize,
     peb_heap_data_offset: usize,
+    peb_startup_config_offset: usize,
+    peb_init_entry_offset: usize,
+    peb_call_encoding_offset: usize,
+    peb_args_offset: usize,
     #[cfg(feature = "nanvix-unstable")]
     peb_file_mappings_offset: usize,
 
+    startup_config_offset: usize,
+    args_offset: usize,
     guest_heap_buffer_offset: usize,
     init_data_offset: usize,
     pt_size: Option<usize>,
@@ -282,13 +292,39 @@ impl Debug for SandboxMemoryLayout {
         .field(
             "Guest Heap Offset",
             &format_args!("{:#x}", self.peb_heap_data_offset),
-        );
+        )
+        .field(
+            "Startup Config Offset",
+            &format_args!("{:#x}", self.peb_startup_config_offset),
+        )
+        .field(
+            "Init Entry Offset",
+            &format_args!("{:#x}", self.peb_init_entry_offset),
+        )
+        .field(
+            "Call Encoding Offset",
+            &format_args!("{:#x}", self.peb_call_encoding_offset),
+        )
+        .field("Args Offset", &format_args!("{:#x}", self.peb_args_offset));
         #[cfg(feature = "nanvix-unstable")]
         ff.field(
             "File Mappings Offset",
             &format_args!("{:#x}", self.peb_file_mappings_offset),
         );
         ff.field(
+            "Startup Config Buffer Offset",
+            &format_args!("{:#x}", self.startup_config_offset),
+        )
+        .field(
+            "Startup Config Size",
+            &format_args!("{:#x}", self.startup_config_size),
+        )
+        .field(
+            "Args Buffer Offset",
+            &format_args!("{:#x}", self.args_offset),
+        )
+        .field("Args Size", &format_args!("{:#x}", self.args_size))
+        .field(
             "Guest Heap Buffer Offset",
             &format_args!("{:#x}", self.guest_heap_buffer_offset),
         )
@@ -315,7 +351,7 @@ impl SandboxMemoryLayout {
     /// Both the scratch region and the snapshot region are bounded by
     /// this size. The value is arbitrary but chosen to be large enough
     /// for most workloads while preventing accidental resource exhaustion.
-    const MAX_MEMORY_SIZE: usize = (16 * 1024 * 1024 * 1024) - Self::BASE_ADDRESS; // 16 GiB - BASE_ADDRESS
+    pub(crate) const MAX_MEMORY_SIZE: usize = (16 * 1024 * 1024 * 1024) - Self::BASE_ADDRESS; // 16 GiB - BASE_ADDRESS
 
     /// The base address of the sandbox's memory.
     pub(crate) const BASE_ADDRESS: usize = 0x1000;
@@ -331,6 +367,8 @@ impl SandboxMemoryLayout {
         code_size: usize,
         init_data_size: usize,
         init_data_permissions: Option<MemoryRegionFlags>,
+        startup_config_size: usize,
+        args_size: usize,
     ) -> Result<Self> {
         let heap_size = usize::try_from(cfg.get_heap_size())?;
         let scratch_size = cfg.get_scratch_size();
@@ -348,10 +386,15 @@ impl SandboxMemoryLayout {
         let guest_code_offset = 0;
         // The following offsets are to the fields of the PEB struct itself!
         let peb_offset = code_size.next_multiple_of(PAGE_SIZE_USIZE);
+        let peb_abi_version_offset = peb_offset + offset_of!(HyperlightPEB, abi_version);
         let peb_input_data_offset = peb_offset + offset_of!(HyperlightPEB, input_stack);
         let peb_output_data_offset = peb_offset + offset_of!(HyperlightPEB, output_stack);
         let peb_init_data_offset = peb_offset + offset_of!(HyperlightPEB, init_data);
         let peb_heap_data_offset = peb_offset + offset_of!(HyperlightPEB, guest_heap);
+        let peb_startup_config_offset = peb_offset + offset_of!(HyperlightPEB, startup_config);
+        let peb_init_entry_offset = peb_offset + offset_of!(HyperlightPEB, init_entry);
+        let peb_call_encoding_offset = peb_offset + offset_of!(HyperlightPEB, call_encoding);
+        let peb_args_offset = peb_offset + offset_of!(HyperlightPEB, args);
         #[cfg(feature = "nanvix-unstable")]
         let peb_file_mappings_offset = peb_offset + offset_of!(HyperlightPEB, file_mappings);
 
@@ -373,10 +416,14 @@ impl SandboxMemoryLayout {
             + hyperlight_common::mem::MAX_FILE_MAPPINGS
                 * size_of::<hyperlight_common::mem::FileMappingInfo>();
         #[cfg(feature = "nanvix-unstable")]
-        let guest_heap_buffer_offset = file_mappings_array_end.next_multiple_of(PAGE_SIZE_USIZE);
+        let startup_config_offset = file_mappings_array_end;
         #[cfg(not(feature = "nanvix-unstable"))]
-        let guest_heap_buffer_offset =
-            (peb_offset + size_of::<HyperlightPEB>()).next_multiple_of(PAGE_SIZE_USIZE);
+        let startup_config_offset = peb_offset + size_of::<HyperlightPEB>();
+        // The startup config is stored immediately after the PEB (and the
+        // FileMappingInfo array), followed by the pointer-aligned guest
+        // arguments, in the same read-only region.
+        let args_offset = (startup_config_offset + startup_config_size).next_multiple_of(8);
+        let guest_heap_buffer_offset = (args_offset + args_size).next_multiple_of(PAGE_SIZE_USIZE);
 
         // make sure init data starts at 4K boundary
         let init_data_offset =
@@ -384,10 +431,15 @@ impl SandboxMemoryLayout {
         let mut ret = Self {
             peb_offset,
             heap_size,
+            peb_abi_version_offset,
             peb_input_data_offset,
             peb_output_data_offset,
             peb_init_data_offset,
             peb_heap_data_offset,
+            peb_startup_config_offset,
+            peb_init_entry_offset,
+            peb_call_encoding_offset,
+            peb_args_offset,
             #[cfg(feature = "nanvix-unstable")]
             peb_file_mappings_offset,
             sandbox_memory_config: cfg,
@@ -398,6 +450,10 @@ impl SandboxMemoryLayout {
             init_data_offset,
             init_data_size,
             init_data_permissions,
+            startup_config_offset,
+            startup_config_size,
+            args_offset,
+            args_size,
             pt_size: None,
             scratch_size,
             snapshot_size: 0,
@@ -476,6 +532,27 @@ impl SandboxMemoryLayout {
         hyperlight_common::layout::scratch_base_gva(self.scratch_size)
     }
 
+    /// Get the guest virtual addresses of the input data buffer
+    #[cfg(crashdump)]
+    pub(crate) fn get_input_data_buffer_gva_range(&self) -> std::ops::Range<usize> {
+        let start = self.get_input_data_buffer_gva() as usize;
+        start..start + self.sandbox_memory_config.get_input_data_size()
+    }
+
+    /// Get the guest virtual addresses of the output data buffer
+    #[cfg(crashdump)]
+    pub(crate) fn get_output_data_buffer_gva_range(&self) -> std::ops::Range<usize> {
+        let start = self.get_output_data_buffer_gva() as usize;
+        start..start + self.sandbox_memory_config.get_output_data_size()
+    }
+
+    /// Get the guest virtual addresses of the heap
+    #[cfg(crashdump)]
+    pub(crate) fn get_heap_gva_range(&self) -> std::ops::Range<usize> {
+        let start = Self::BASE_ADDRESS + self.guest_heap_buffer_offset;
+        start..start + self.heap_size
+    }
+
     /// Get the offset into the host scratch buffer of the start of
     /// the input data
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
@@ -513,6 +590,20 @@ impl SandboxMemoryLayout {
         self.peb_heap_data_offset
     }
 
+    /// Get the offset in guest memory to the startup config size
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    fn get_startup_config_size_offset(&self) -> usize {
+        self.peb_startup_config_offset
+    }
+
+    /// Get the offset in guest memory to the startup config pointer
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    fn get_startup_config_pointer_offset(&self) -> usize {
+        // The startup config pointer is immediately after the startup
+        // config size field, which is a `u64`.
+        self.get_startup_config_size_offset() + size_of::<u64>()
+    }
+
     /// Get the offset in guest memory to the file_mappings count field
     /// (the `size` field of the `GuestMemoryRegion` in the PEB).
     #[cfg(feature = "nanvix-unstable")]
@@ -561,6 +652,12 @@ impl SandboxMemoryLayout {
         self.guest_code_offset
     }
 
+    /// Get the size of the code section in the sandbox
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_code_size(&self) -> usize {
+        self.code_size
+    }
+
     /// Get the guest address of the code section in the sandbox
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn get_guest_code_address(&self) -> usize {
@@ -642,21 +739,19 @@ impl SandboxMemoryLayout {
             ));
         }
 
-        // PEB + preallocated FileMappingInfo array
+        // PEB + preallocated FileMappingInfo array + startup config + args
         #[cfg(feature = "nanvix-unstable")]
-        let heap_offset = {
-            let peb_and_array_size = size_of::<HyperlightPEB>()
-                + hyperlight_common::mem::MAX_FILE_MAPPINGS
-                    * size_of::<hyperlight_common::mem::FileMappingInfo>();
-            builder.push_page_aligned(
-                peb_and_array_size,
-                MemoryRegionFlags::READ | MemoryRegionFlags::WRITE,
-                Peb,
-            )
-        };
+        let heap_offset = builder.push_page_aligned(
+            self.args_offset - self.peb_offset + self.args_size,
+            MemoryRegionFlags::READ | MemoryRegionFlags::WRITE,
+            Peb,
+        );
         #[cfg(not(feature = "nanvix-unstable"))]
-        let heap_offset =
-            builder.push_page_aligned(size_of::<HyperlightPEB>(), MemoryRegionFlags::READ, Peb);
+        let heap_offset = builder.push_page_aligned(
+            self.args_offset - self.peb_offset + self.args_size,
+            MemoryRegionFlags::READ,
+            Peb,
+        );
 
         let expected_heap_offset = TryInto::<usize>::try_into(self.guest_heap_buffer_offset)?;
 
@@ -724,6 +819,81 @@ impl SandboxMemoryLayout {
         Ok(())
     }
 
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn write_startup_config(&self, out: &mut [u8], bytes: &[u8]) -> Result<()> {
+        out[self.startup_config_offset..self.startup_config_offset + self.startup_config_size]
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// The number of bytes needed to store `args` in guest memory, as an
+    /// `argv` array followed by the NUL-terminated strings
+    pub(crate) fn args_size(args: &[String]) -> usize {
+        if args.is_empty() {
+            return 0;
+        }
+        (args.len() + 1) * size_of::<u64>() + args.iter().map(|arg| arg.len() + 1).sum::<usize>()
+    }
+
+    /// Write `args` to guest memory and describe them in the PEB. The
+    /// layout must have been created with [`Self::args_size`] of the same
+    /// arguments.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn write_args(&self, out: &mut [u8], args: &[String]) -> Result<()> {
+        if Self::args_size(args) != self.args_size {
+            return Err(new_error!(
+                "guest arguments need {} bytes but the layout has {}",
+                Self::args_size(args),
+                self.args_size
+            ));
+        }
+        if args.is_empty() {
+            return Ok(());
+        }
+        let argv_address = u64::try_from(Self::BASE_ADDRESS + self.args_offset)?;
+        let mut pointer_offset = self.args_offset;
+        let mut string_offset = self.args_offset + (args.len() + 1) * size_of::<u64>();
+        for arg in args {
+            let string_address = u64::try_from(Self::BASE_ADDRESS + string_offset)?;
+            out[pointer_offset..pointer_offset + size_of::<u64>()]
+                .copy_from_slice(&string_address.to_ne_bytes());
+            out[string_offset..string_offset + arg.len()].copy_from_slice(arg.as_bytes());
+            out[string_offset + arg.len()] = 0;
+            pointer_offset += size_of::<u64>();
+            string_offset += arg.len() + 1;
+        }
+        // The argv array is terminated by a null pointer
+        out[pointer_offset..pointer_offset + size_of::<u64>()].fill(0);
+
+        let argc = u64::try_from(args.len())?;
+        out[self.peb_args_offset..self.peb_args_offset + size_of::<u64>()]
+            .copy_from_slice(&argc.to_ne_bytes());
+        out[self.peb_args_offset + size_of::<u64>()..self.peb_args_offset + 2 * size_of::<u64>()]
+            .copy_from_slice(&argv_address.to_ne_bytes());
+        Ok(())
+    }
+
+    /// The ranges of guest memory, as offsets, holding the inputs passed
+    /// to the guest through the PEB: the init entry point, the init data,
+    /// the startup configuration and the guest arguments, in that order
+    pub(crate) fn get_guest_input_ranges(&self) -> [Range<usize>; 4] {
+        [
+            self.peb_init_entry_offset..self.peb_init_entry_offset + size_of::<u64>(),
+            self.init_data_offset..self.init_data_offset + self.init_data_size,
+            self.startup_config_offset..self.startup_config_offset + self.startup_config_size,
+            self.args_offset..self.args_offset + self.args_size,
+        ]
+    }
+
+    /// Write the guest address of the function to run instead of
+    /// `hyperlight_main` during initialisation into the PEB
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn write_init_entry(&self, out: &mut [u8], addr: u64) -> Result<()> {
+        out[self.peb_init_entry_offset..self.peb_init_entry_offset + size_of::<u64>()]
+            .copy_from_slice(&addr.to_ne_bytes());
+        Ok(())
+    }
+
     /// Write the finished memory layout to `mem` and return `Ok` if
     /// successful.
     ///
@@ -753,6 +923,9 @@ impl SandboxMemoryLayout {
 
         // Start of setting up the PEB. The following are in the order of the PEB fields
 
+        // Set up the ABI version the guest checks before anything else
+        write_u64(mem, self.peb_abi_version_offset, ABI_VERSION.into())?;
+
         // Set up input buffer pointer
         write_u64(
             mem,
@@ -795,6 +968,22 @@ impl SandboxMemoryLayout {
         write_u64(mem, self.get_heap_size_offset(), self.heap_size.try_into()?)?;
         write_u64(mem, self.get_heap_pointer_offset(), addr)?;
 
+        // Set up startup config pointer
+        let addr = get_address!(startup_config_offset);
+        write_u64(
+            mem,
+            self.get_startup_config_size_offset(),
+            self.startup_config_size.try_into()?,
+        )?;
+        write_u64(mem, self.get_startup_config_pointer_offset(), addr)?;
+
+        // Set up the encoding used for function calls
+        write_u64(
+            mem,
+            self.peb_call_encoding_offset,
+            self.sandbox_memory_config.get_call_encoding().id(),
+        )?;
+
         // Set up the file_mappings descriptor in the PEB.
         // - The `size` field holds the number of valid FileMappingInfo
         //   entries currently written (initially 0 — entries are added
@@ -871,7 +1060,9 @@ mod tests {
                 * size_of::<hyperlight_common::mem::FileMappingInfo>();
         #[cfg(not(feature = "nanvix-unstable"))]
         let peb_and_array = size_of::<HyperlightPEB>();
-        expected_size += peb_and_array.next_multiple_of(PAGE_SIZE_USIZE);
+        expected_size += ((peb_and_array + layout.startup_config_size).next_multiple_of(8)
+            + layout.args_size)
+            .next_multiple_of(PAGE_SIZE_USIZE);
 
         expected_size += layout.heap_size.next_multiple_of(PAGE_SIZE_USIZE);
 
@@ -881,11 +1072,54 @@ mod tests {
     #[test]
     fn test_get_memory_size() {
         let sbox_cfg = SandboxConfiguration::default();
-        let sbox_mem_layout = SandboxMemoryLayout::new(sbox_cfg, 4096, 0, None).unwrap();
+        let sbox_mem_layout = SandboxMemoryLayout::new(sbox_cfg, 4096, 0, None, 0, 0).unwrap();
+        assert_eq!(
+            sbox_mem_layout.get_memory_size().unwrap(),
+            get_expected_memory_size(&sbox_mem_layout)
+        );
+
+        // A startup config that does not fit in the PEB page
+        let sbox_mem_layout = SandboxMemoryLayout::new(sbox_cfg, 4096, 0, None, 5000, 0).unwrap();
         assert_eq!(
             sbox_mem_layout.get_memory_size().unwrap(),
             get_expected_memory_size(&sbox_mem_layout)
         );
+
+        // Arguments after a startup config that is not pointer-aligned
+        let sbox_mem_layout = SandboxMemoryLayout::new(sbox_cfg, 4096, 0, None, 4093, 200).unwrap();
+        assert_eq!(
+            sbox_mem_layout.get_memory_size().unwrap(),
+            get_expected_memory_size(&sbox_mem_layout)
+        );
+    }
+
+    #[test]
+    fn test_write_args() {
+        let args = vec!["prog".to_string(), "--verbose".to_string()];
+        let args_size = SandboxMemoryLayout::args_size(&args);
+        assert_eq!(args_size, 3 * 8 + 5 + 10);
+
+        let layout =
+            SandboxMemoryLayout::new(SandboxConfiguration::default(), 4096, 0, None, 3, args_size)
+                .unwrap();
+        let mut mem = vec![0; layout.get_memory_size().unwrap()];
+        layout.write_args(&mut mem, &args).unwrap();
+
+        let read_u64 =
+            |offset: usize| u64::from_ne_bytes(mem[offset..offset + 8].try_into().unwrap());
+        let to_offset = |address: u64| address as usize - SandboxMemoryLayout::BASE_ADDRESS;
+        assert_eq!(read_u64(layout.peb_args_offset), 2);
+        let argv = to_offset(read_u64(layout.peb_args_offset + 8));
+        assert_eq!(argv % 8, 0);
+        for (i, arg) in args.iter().enumerate() {
+            let start = to_offset(read_u64(argv + i * 8));
+            assert_eq!(&mem[start..start + arg.len()], arg.as_bytes());
+            assert_eq!(mem[start + arg.len()], 0);
+        }
+        assert_eq!(read_u64(argv + 2 * 8), 0);
+
+        // The layout must have room for exactly these arguments
+        assert!(layout.write_args(&mut mem, &["other".to_string()]).is_err());
     }
 
     #[test]
@@ -894,7 +1128,7 @@ mod tests {
         // scratch_size exceeds 16 GiB limit
         cfg.set_scratch_size(17 * 1024 * 1024 * 1024);
         cfg.set_input_data_size(16 * 1024 * 1024 * 1024);
-        let layout = SandboxMemoryLayout::new(cfg, 4096, 4096, None);
+        let layout = SandboxMemoryLayout::new(cfg, 4096, 4096, None, 0, 0);
         assert!(matches!(layout.unwrap_err(), MemoryRequestTooBig(..)));
     }
 }
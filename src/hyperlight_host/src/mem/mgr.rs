@@ -16,14 +16,16 @@ limitations under the License.
 #[cfg(feature = "nanvix-unstable")]
 use std::mem::offset_of;
 
-use flatbuffers::FlatBufferBuilder;
+use hyperlight_common::call_encoding::{
+    CallEncoding, EncodedMessage, ResultDescriptor, WireFormat,
+};
 use hyperlight_common::flatbuffer_wrappers::function_call::{
     FunctionCall, validate_guest_function_call_buffer,
 };
 use hyperlight_common::flatbuffer_wrappers::function_types::FunctionCallResult;
 use hyperlight_common::flatbuffer_wrappers::guest_log_data::GuestLogData;
+use hyperlight_common::trace_context::TraceParent;
 use hyperlight_common::vmem::{self, PAGE_TABLE_SIZE};
-#[cfg(all(feature = "crashdump", not(feature = "i686-guest")))]
 use hyperlight_common::vmem::{BasicMapping, MappingKind};
 use tracing::{Span, instrument};
 
@@ -439,21 +441,30 @@ impl SandboxMemoryManager<HostSharedMemory> {
     /// Reads a host function call from memory
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn get_host_function_call(&mut self) -> Result<FunctionCall> {
-        self.scratch_mem.try_pop_buffer_into::<FunctionCall>(
+        let encoding = self.layout.sandbox_memory_config.get_call_encoding();
+        self.scratch_mem.try_pop_buffer_with(
             self.layout.get_output_data_buffer_scratch_host_offset(),
             self.layout.sandbox_memory_config.get_output_data_size(),
+            |data| encoding.decode_function_call(data),
         )
     }
 
-    /// Writes a host function call result to memory
-    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
-    pub(crate) fn write_response_from_host_function_call(
-        &mut self,
+    /// Encodes a host function call result, to be written to memory with
+    /// [`write_response_from_host_function_call`](Self::write_response_from_host_function_call)
+    pub(crate) fn encode_host_function_response(
+        &self,
         res: &FunctionCallResult,
-    ) -> Result<()> {
-        let mut builder = FlatBufferBuilder::new();
-        let data = res.encode(&mut builder);
+    ) -> Result<EncodedMessage> {
+        Ok(self
+            .layout
+            .sandbox_memory_config
+            .get_call_encoding()
+            .encode_function_call_result(res)?)
+    }
 
+    /// Writes an encoded host function call result to memory
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn write_response_from_host_function_call(&mut self, data: &[u8]) -> Result<()> {
         self.scratch_mem.push_buffer(
             self.layout.get_input_data_buffer_scratch_host_offset(),
             self.layout.sandbox_memory_config.get_input_data_size(),
@@ -464,12 +475,14 @@ impl SandboxMemoryManager<HostSharedMemory> {
     /// Writes a guest function call to memory
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn write_guest_function_call(&mut self, buffer: &[u8]) -> Result<()> {
-        validate_guest_function_call_buffer(buffer).map_err(|e| {
-            new_error!(
-                "Guest function call buffer validation failed: {}",
-                e.to_string()
-            )
-        })?;
+        if self.layout.sandbox_memory_config.get_call_encoding() == CallEncoding::FlatBuffers {
+            validate_guest_function_call_buffer(buffer).map_err(|e| {
+                new_error!(
+                    "Guest function call buffer validation failed: {}",
+                    e.to_string()
+                )
+            })?;
+        }
 
         self.scratch_mem.push_buffer(
             self.layout.get_input_data_buffer_scratch_host_offset(),
@@ -481,11 +494,92 @@ impl SandboxMemoryManager<HostSharedMemory> {
 
     /// Reads a function call result from memory.
     /// A function call result can be either an error or a successful return value.
+    ///
+    /// A result too large for the output buffer is read from guest memory,
+    /// which requires the root page table address from `root_pt`.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_guest_function_call_result(
+        &mut self,
+        root_pt: impl FnOnce() -> Result<u64>,
+    ) -> Result<FunctionCallResult> {
+        let mut data = self.scratch_mem.try_pop_buffer_into::<Vec<u8>>(
+            self.layout.get_output_data_buffer_scratch_host_offset(),
+            self.layout.sandbox_memory_config.get_output_data_size(),
+        )?;
+        if let Some(descriptor) = ResultDescriptor::decode(&data) {
+            data = self.read_result_descriptor(descriptor, root_pt()?)?;
+        }
+        FunctionCallResult::try_from(data.as_slice()).map_err(|e| {
+            new_error!(
+                "pop_buffer_into: failed to convert buffer to {}: {}",
+                std::any::type_name::<FunctionCallResult>(),
+                e
+            )
+        })
+    }
+
+    /// Read a function call result the guest left in its own memory,
+    /// checking that the descriptor points within guest memory
+    fn read_result_descriptor(
+        &mut self,
+        descriptor: ResultDescriptor,
+        root_pt: u64,
+    ) -> Result<Vec<u8>> {
+        let guest_memory_size = self.shared_mem.mem_size() + self.scratch_mem.mem_size();
+        let len = usize::try_from(descriptor.len)
+            .ok()
+            .filter(|&len| len <= guest_memory_size && len >= 8)
+            .ok_or_else(|| {
+                new_error!(
+                    "Guest function call result size {} is invalid",
+                    descriptor.len
+                )
+            })?;
+        if descriptor.gva.checked_add(descriptor.len).is_none() {
+            return Err(new_error!(
+                "Guest function call result at {:#x} overflows the address space",
+                descriptor.gva
+            ));
+        }
+        self.read_guest_memory_by_gva(descriptor.gva, len, root_pt)
+    }
+
+    /// Read a chunk of a streamed return value from the output buffer
     #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
-    pub(crate) fn get_guest_function_call_result(&mut self) -> Result<FunctionCallResult> {
-        self.scratch_mem.try_pop_buffer_into::<FunctionCallResult>(
+    pub(crate) fn read_stream_chunk(&mut self) -> Result<Vec<u8>> {
+        let mut chunk = self.scratch_mem.try_pop_buffer_into::<Vec<u8>>(
             self.layout.get_output_data_buffer_scratch_host_offset(),
             self.layout.sandbox_memory_config.get_output_data_size(),
+        )?;
+        // Strip the size prefix
+        chunk.drain(..4);
+        Ok(chunk)
+    }
+
+    /// The most bytes of input that can be written to the input buffer with
+    /// [`Self::write_input_chunk`]
+    pub(crate) fn input_chunk_capacity(&mut self) -> Result<usize> {
+        let offset = self.layout.get_input_data_buffer_scratch_host_offset();
+        let used = self.scratch_mem.read::<u64>(offset)? as usize;
+        // Leave room for the chunk's back-pointer and size prefix
+        Ok(self
+            .layout
+            .sandbox_memory_config
+            .get_input_data_size()
+            .saturating_sub(used + 8 + 4))
+    }
+
+    /// Write a chunk of the guest's stdin to the input buffer, with a size
+    /// prefix
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn write_input_chunk(&mut self, data: &[u8]) -> Result<()> {
+        let mut buffer = Vec::with_capacity(data.len() + 4);
+        buffer.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        buffer.extend_from_slice(data);
+        self.scratch_mem.push_buffer(
+            self.layout.get_input_data_buffer_scratch_host_offset(),
+            self.layout.sandbox_memory_config.get_input_data_size(),
+            &buffer,
         )
     }
 
@@ -569,6 +663,37 @@ impl SandboxMemoryManager<HostSharedMemory> {
         Ok((gsnapshot, gscratch))
     }
 
+    /// Set the stack policy the guest applies during the next guest call
+    pub(crate) fn write_stack_policy(
+        &mut self,
+        max_stack_size: usize,
+        zero_stack: bool,
+    ) -> Result<()> {
+        use hyperlight_common::layout::{SCRATCH_TOP_STACK_POLICY_OFFSET, STACK_POLICY_ZERO_STACK};
+        let mut policy = (max_stack_size as u64).next_multiple_of(vmem::PAGE_SIZE as u64);
+        if zero_stack {
+            policy |= STACK_POLICY_ZERO_STACK;
+        }
+        self.update_scratch_bookkeeping_item(SCRATCH_TOP_STACK_POLICY_OFFSET, policy)
+    }
+
+    /// Set the W3C trace context the guest sees during the next guest
+    /// call, or clear it if the call is not made inside a trace
+    pub(crate) fn write_trace_context(&mut self, parent: Option<&TraceParent>) -> Result<()> {
+        use hyperlight_common::layout::SCRATCH_TOP_TRACE_CONTEXT_OFFSET;
+        let offset = self.scratch_mem.mem_size() - SCRATCH_TOP_TRACE_CONTEXT_OFFSET as usize;
+        self.scratch_mem
+            .copy_from_slice(&TraceParent::encode(parent), offset)
+    }
+
+    /// Set the wall-clock time the guest's paravirtual clock started at,
+    /// in nanoseconds since the Unix epoch, or 0 to not let the guest read
+    /// the time from it
+    pub(crate) fn write_wall_clock_base(&mut self, base: u64) -> Result<()> {
+        use hyperlight_common::layout::SCRATCH_TOP_WALL_CLOCK_OFFSET;
+        self.update_scratch_bookkeeping_item(SCRATCH_TOP_WALL_CLOCK_OFFSET, base)
+    }
+
     #[inline]
     fn update_scratch_bookkeeping_item(&mut self, offset: u64, value: u64) -> Result<()> {
         let scratch_size = self.scratch_mem.mem_size();
@@ -749,73 +874,97 @@ impl SandboxMemoryManager<HostSharedMemory> {
         Ok(regions)
     }
 
-    /// Read guest memory at a Guest Virtual Address (GVA) by walking the
-    /// page tables to translate GVA → GPA, then reading from the correct
-    /// backing memory (shared_mem or scratch_mem).
+    /// Translate the `len` bytes of guest memory at a Guest Virtual Address
+    /// (GVA) to the Guest Physical Addresses (GPAs) they are mapped to, by
+    /// walking the page tables at `root_pt`.
     ///
-    /// This is necessary because with Copy-on-Write (CoW) the guest's
-    /// virtual pages are backed by physical pages in the scratch
-    /// region rather than being identity-mapped.
+    /// Returns the part of the range in each page, in order, as its GPA,
+    /// its length and how the page is mapped, or an error if any of the
+    /// range is unmapped.
     ///
     /// # Arguments
-    /// * `gva` - The Guest Virtual Address to read from
-    /// * `len` - The number of bytes to read
+    /// * `gva` - The Guest Virtual Address to translate
+    /// * `len` - The number of bytes to translate
     /// * `root_pt` - The root page table physical address (CR3)
-    #[cfg(feature = "trace_guest")]
-    pub(crate) fn read_guest_memory_by_gva(
+    pub(crate) fn translate_gva_range(
         &mut self,
         gva: u64,
         len: usize,
         root_pt: u64,
-    ) -> Result<Vec<u8>> {
+    ) -> Result<Vec<(u64, usize, MappingKind)>> {
         use hyperlight_common::vmem::PAGE_SIZE;
 
-        use crate::sandbox::snapshot::{SharedMemoryPageTableBuffer, access_gpa};
+        use crate::sandbox::snapshot::SharedMemoryPageTableBuffer;
 
         self.shared_mem.with_contents(|snap| {
             self.scratch_mem.with_contents(|scratch| {
                 let pt_buf = SharedMemoryPageTableBuffer::new(snap, scratch, self.layout, root_pt);
-
-                // Walk page tables to get all mappings that cover the GVA range
-                let mappings: Vec<_> = unsafe {
-                    hyperlight_common::vmem::virt_to_phys(&pt_buf, gva, len as u64)
+                let mappings =
+                    unsafe { hyperlight_common::vmem::virt_to_phys(&pt_buf, gva, len as u64) };
+
+                let mut runs = Vec::new();
+                let mut translated = 0;
+                for mapping in mappings {
+                    // A gap in the mappings leaves the rest of the range
+                    // unmapped
+                    let current_gva = gva + translated as u64;
+                    if mapping.virt_base > current_gva {
+                        break;
+                    }
+                    let page_offset = (current_gva - mapping.virt_base) as usize;
+                    let run_len = (len - translated).min(PAGE_SIZE - page_offset);
+                    runs.push((
+                        mapping.phys_base + page_offset as u64,
+                        run_len,
+                        mapping.kind,
+                    ));
+                    translated += run_len;
+                    if translated == len {
+                        break;
+                    }
                 }
-                .collect();
 
-                if mappings.is_empty() {
+                if translated != len {
                     return Err(new_error!(
-                        "No page table mappings found for GVA {:#x} (len {})",
-                        gva,
+                        "Could not translate full GVA range: {} of {} bytes at {:#x} are mapped",
+                        translated,
                         len,
+                        gva
                     ));
                 }
+                Ok(runs)
+            })
+        })??
+    }
 
-                // Resulting vector of bytes to return
-                let mut result = Vec::with_capacity(len);
-                let mut current_gva = gva;
-
-                for mapping in &mappings {
-                    // The page table walker should only return valid mappings
-                    // that cover our current read position.
-                    if mapping.virt_base > current_gva {
-                        return Err(new_error!(
-                            "Page table walker returned mapping with virt_base {:#x} > current read position {:#x}",
-                            mapping.virt_base,
-                            current_gva,
-                        ));
-                    }
-
-                    // Calculate the offset within this page where to start copying
-                    let page_offset = (current_gva - mapping.virt_base) as usize;
-
-                    let bytes_remaining = len - result.len();
-                    let available_in_page = PAGE_SIZE - page_offset;
-                    let bytes_to_copy = bytes_remaining.min(available_in_page);
+    /// Read guest memory at a Guest Virtual Address (GVA) by walking the
+    /// page tables to translate GVA → GPA, then reading from the correct
+    /// backing memory (shared_mem or scratch_mem).
+    ///
+    /// This is necessary because with Copy-on-Write (CoW) the guest's
+    /// virtual pages are backed by physical pages in the scratch
+    /// region rather than being identity-mapped.
+    ///
+    /// # Arguments
+    /// * `gva` - The Guest Virtual Address to read from
+    /// * `len` - The number of bytes to read
+    /// * `root_pt` - The root page table physical address (CR3)
+    pub(crate) fn read_guest_memory_by_gva(
+        &mut self,
+        gva: u64,
+        len: usize,
+        root_pt: u64,
+    ) -> Result<Vec<u8>> {
+        use crate::sandbox::snapshot::access_gpa;
 
+        let runs = self.translate_gva_range(gva, len, root_pt)?;
+        self.shared_mem.with_contents(|snap| {
+            self.scratch_mem.with_contents(|scratch| {
+                let mut result = Vec::with_capacity(len);
+                for (gpa, run_len, _) in runs {
                     // Translate the GPA to host memory
-                    let gpa = mapping.phys_base + page_offset as u64;
-                    let (mem, offset) = access_gpa(snap, scratch, self.layout, gpa)
-                        .ok_or_else(|| {
+                    let (mem, offset) =
+                        access_gpa(snap, scratch, self.layout, gpa).ok_or_else(|| {
                             new_error!(
                                 "Failed to resolve GPA {:#x} to host memory (GVA {:#x})",
                                 gpa,
@@ -823,38 +972,92 @@ impl SandboxMemoryManager<HostSharedMemory> {
                             )
                         })?;
 
-                    let slice = mem
-                        .get(offset..offset + bytes_to_copy)
-                        .ok_or_else(|| {
-                            new_error!(
-                                "GPA {:#x} resolved to out-of-bounds host offset {} (need {} bytes)",
-                                gpa,
-                                offset,
-                                bytes_to_copy
-                            )
-                        })?;
-
+                    let slice = mem.get(offset..offset + run_len).ok_or_else(|| {
+                        new_error!(
+                            "GPA {:#x} resolved to out-of-bounds host offset {} (need {} bytes)",
+                            gpa,
+                            offset,
+                            run_len
+                        )
+                    })?;
                     result.extend_from_slice(slice);
-                    current_gva += bytes_to_copy as u64;
                 }
+                Ok(result)
+            })
+        })??
+    }
 
-                if result.len() != len {
-                    tracing::error!(
-                        "Page table walker returned mappings that don't cover the full requested length: got {}, expected {}",
-                        result.len(),
-                        len,
-                    );
+    /// Write `data` to guest memory at a Guest Virtual Address (GVA),
+    /// walking the page tables to translate GVA → GPA like
+    /// [`read_guest_memory_by_gva`](Self::read_guest_memory_by_gva).
+    ///
+    /// Every page written must be mapped writable, and be backed by memory
+    /// the host can write without affecting other sandboxes, which rules
+    /// out copy-on-write pages the guest has not written to yet. Nothing
+    /// is written unless all of the pages can be.
+    ///
+    /// # Arguments
+    /// * `gva` - The Guest Virtual Address to write to
+    /// * `data` - The bytes to write
+    /// * `root_pt` - The root page table physical address (CR3)
+    pub(crate) fn write_guest_memory_by_gva(
+        &mut self,
+        gva: u64,
+        data: &[u8],
+        root_pt: u64,
+    ) -> Result<()> {
+        use super::layout::BaseGpaRegion;
+
+        // Where each run of `data` goes: whether it is in the snapshot
+        // region rather than scratch, the offset there, and the run
+        let mut writes = Vec::new();
+        let mut written = 0;
+        for (gpa, len, kind) in self.translate_gva_range(gva, data.len(), root_pt)? {
+            let current_gva = gva + written as u64;
+            if !matches!(
+                kind,
+                MappingKind::Basic(BasicMapping { writable: true, .. })
+            ) {
+                return Err(new_error!(
+                    "GVA {:#x} is not mapped writable ({:?})",
+                    current_gva,
+                    kind
+                ));
+            }
+
+            let resolved = self.layout.resolve_gpa(gpa, &[]).ok_or_else(|| {
+                new_error!(
+                    "Failed to resolve GPA {:#x} to host memory (GVA {:#x})",
+                    gpa,
+                    current_gva
+                )
+            })?;
+            // The snapshot region may be shared with other sandboxes, so it
+            // can only be written when it is not
+            let in_snapshot = match resolved.base {
+                BaseGpaRegion::Scratch(()) => false,
+                #[cfg(unshared_snapshot_mem)]
+                BaseGpaRegion::Snapshot(()) => true,
+                _ => {
                     return Err(new_error!(
-                        "Could not read full GVA range: got {} of {} bytes {:?}",
-                        result.len(),
-                        len,
-                        mappings
+                        "GVA {:#x} is not backed by memory the host can write",
+                        current_gva
                     ));
                 }
+            };
+            writes.push((in_snapshot, resolved.offset, written..written + len));
+            written += len;
+        }
 
-                Ok(result)
-            })
-        })??
+        for (in_snapshot, offset, run) in writes {
+            if in_snapshot {
+                #[cfg(unshared_snapshot_mem)]
+                self.shared_mem.copy_from_slice(&data[run], offset)?;
+            } else {
+                self.scratch_mem.copy_from_slice(&data[run], offset)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -20,6 +20,7 @@ use std::io::Error;
 use std::mem::{align_of, size_of};
 #[cfg(target_os = "linux")]
 use std::ptr::null_mut;
+use std::sync::atomic::AtomicU64;
 use std::sync::{Arc, RwLock};
 
 use hyperlight_common::mem::PAGE_SIZE_USIZE;
@@ -931,6 +932,30 @@ impl HostSharedMemory {
         Ok(())
     }
 
+    /// Run `f` on the `u64` at `offset` as an atomic, for memory that
+    /// the guest only ever accesses atomically too. The offset must be
+    /// 8-byte aligned.
+    pub(crate) fn with_atomic_u64<T>(
+        &self,
+        offset: usize,
+        f: impl FnOnce(&AtomicU64) -> T,
+    ) -> Result<T> {
+        bounds_check!(offset, size_of::<u64>(), self.mem_size());
+        let ptr = self.base_ptr().wrapping_add(offset);
+        if !ptr.cast::<u64>().is_aligned() {
+            return Err(new_error!("Unaligned atomic access at offset {}", offset));
+        }
+        let guard = self
+            .lock
+            .try_read()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+        // Safety: the pointer is in bounds and aligned, and the mapping
+        // stays alive for as long as `self` does
+        let ret = f(unsafe { AtomicU64::from_ptr(ptr.cast()) });
+        drop(guard);
+        Ok(ret)
+    }
+
     /// Copy the contents of the slice into the sandbox at the
     /// specified offset
     pub fn copy_to_slice(&self, slice: &mut [u8], offset: usize) -> Result<()> {
@@ -1101,7 +1126,11 @@ impl HostSharedMemory {
             ));
         }
 
-        let size_required = data.len() + 8;
+        // Elements are padded to a multiple of 8 bytes, so that every
+        // element starts 8 byte aligned and the vectors of floats and
+        // doubles in a flatbuffer are aligned in the buffer
+        let padded_len = data.len().next_multiple_of(8);
+        let size_required = padded_len + 8;
         let size_available = buffer_size - stack_pointer_rel;
 
         if size_required > size_available {
@@ -1115,17 +1144,21 @@ impl HostSharedMemory {
         // get absolute
         let stack_pointer_abs = stack_pointer_rel + buffer_start_offset;
 
-        // write the actual data to the top of stack
+        // write the actual data to the top of stack, followed by its padding
         self.copy_from_slice(data, stack_pointer_abs)?;
+        self.copy_from_slice(
+            &[0; 8][..padded_len - data.len()],
+            stack_pointer_abs + data.len(),
+        )?;
 
         // write the offset to the newly written data, to the top of stack.
         // this is used when popping the stack, to know how far back to jump
-        self.write::<u64>(stack_pointer_abs + data.len(), stack_pointer_rel as u64)?;
+        self.write::<u64>(stack_pointer_abs + padded_len, stack_pointer_rel as u64)?;
 
         // update stack pointer to point to the next free address
         self.write::<u64>(
             buffer_start_offset,
-            (stack_pointer_rel + data.len() + 8) as u64,
+            (stack_pointer_rel + padded_len + 8) as u64,
         )?;
         Ok(())
     }
@@ -1141,6 +1174,21 @@ impl HostSharedMemory {
     where
         T: for<'b> TryFrom<&'b [u8]>,
     {
+        self.try_pop_buffer_with(buffer_start_offset, buffer_size, |data| {
+            T::try_from(data).map_err(|_| ())
+        })
+    }
+
+    /// Pops the given buffer and decodes it into a `T` with `decode`.
+    /// NOTE! the data must be prefixed with its size as a little-endian
+    /// `u32`, as size-prefixed flatbuffers are, and buffer_start_offset
+    /// must point to the beginning of the buffer
+    pub fn try_pop_buffer_with<T, E>(
+        &mut self,
+        buffer_start_offset: usize,
+        buffer_size: usize,
+        decode: impl FnOnce(&[u8]) -> std::result::Result<T, E>,
+    ) -> Result<T> {
         // get the stackpointer
         let stack_pointer_rel = self.read::<u64>(buffer_start_offset)? as usize;
 
@@ -1203,7 +1251,7 @@ impl HostSharedMemory {
         let mut result_buffer = vec![0; fb_buffer_size];
 
         self.copy_to_slice(&mut result_buffer, last_element_offset_abs)?;
-        let to_return = T::try_from(result_buffer.as_slice()).map_err(|_e| {
+        let to_return = decode(result_buffer.as_slice()).map_err(|_e| {
             new_error!(
                 "pop_buffer_into: failed to convert buffer to {}",
                 type_name::<T>()
@@ -1764,6 +1812,26 @@ mod tests {
             assert_eq!(result.0, data);
         }
 
+        #[test]
+        fn pushed_elements_are_8_byte_aligned() {
+            let mem_size = 4096;
+            let mut hshm = make_buffer(mem_size);
+
+            let first = [5u8, 0, 0, 0, 1, 2, 3, 4, 5];
+            let second = [4u8, 0, 0, 0, 6, 7, 8, 9];
+            hshm.push_buffer(0, mem_size, &first).unwrap();
+            hshm.push_buffer(0, mem_size, &second).unwrap();
+            // The first element is padded to 16 bytes and followed by its
+            // back-pointer, so the second one starts at 8 + 16 + 8
+            assert_eq!(hshm.read::<u64>(40).unwrap(), 32);
+            assert_eq!(hshm.read::<u64>(0).unwrap(), 48);
+
+            let result: RawBytes = hshm.try_pop_buffer_into(0, mem_size).unwrap();
+            assert_eq!(result.0, second);
+            let result: RawBytes = hshm.try_pop_buffer_into(0, mem_size).unwrap();
+            assert_eq!(result.0, first);
+        }
+
         #[test]
         fn malicious_flatbuffer_size_prefix() {
             let mem_size = 4096;
@@ -1781,7 +1849,7 @@ mod tests {
             let result: Result<RawBytes> = hshm.try_pop_buffer_into(0, mem_size);
             let err_msg = format!("{}", result.unwrap_err());
             assert!(
-                err_msg.contains("Corrupt buffer size prefix: flatbuffer claims 4294967295 bytes but the element slot is only 9 bytes"),
+                err_msg.contains("Corrupt buffer size prefix: flatbuffer claims 4294967295 bytes but the element slot is only 16 bytes"),
                 "Unexpected error message: {}",
                 err_msg
             );
@@ -2013,6 +2081,14 @@ pub struct ReadonlySharedMemory {
     #[cfg_attr(unshared_snapshot_mem, allow(dead_code))]
     guest_mapped_size: Option<usize>,
 }
+
+/// The size of a [`ReadonlySharedMemory`] whose contents have been
+/// written out to a file
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SwappedSharedMemory {
+    len: usize,
+    guest_mapped_size: Option<usize>,
+}
 // Safety: HostMapping is only non-Send/Sync (causing
 // ReadonlySharedMemory to not be automatically Send/Sync) because raw
 // pointers are not ("as a lint", as the Rust docs say). We don't want
@@ -2048,6 +2124,29 @@ impl ReadonlySharedMemory {
         })
     }
 
+    /// Write the contents to `file`, so that they can be read back with
+    /// [`Self::swap_in`] once this copy of them has been dropped
+    pub(crate) fn swap_out(&self, file: &mut std::fs::File) -> Result<SwappedSharedMemory> {
+        use std::io::Write;
+        file.write_all(self.as_slice())?;
+        Ok(SwappedSharedMemory {
+            len: self.mem_size(),
+            guest_mapped_size: self.guest_mapped_size,
+        })
+    }
+
+    /// Read back contents written by [`Self::swap_out`]
+    pub(crate) fn swap_in(file: &mut std::fs::File, swapped: SwappedSharedMemory) -> Result<Self> {
+        use std::io::{Read, Seek, SeekFrom};
+        let mut anon = ExclusiveSharedMemory::new(swapped.len)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(anon.as_mut_slice())?;
+        Ok(ReadonlySharedMemory {
+            region: anon.region,
+            guest_mapped_size: swapped.guest_mapped_size,
+        })
+    }
+
     /// The number of bytes that should be mapped into guest PA space.
     /// Returns `guest_mapped_size` if set, otherwise `mem_size()`.
     #[cfg(not(unshared_snapshot_mem))]
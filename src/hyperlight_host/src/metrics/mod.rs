@@ -14,6 +14,12 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::time::{Duration, Instant};
+
+use hyperlight_common::guest_metric::GuestMetric;
+
+use crate::sandbox::SandboxLabels;
+
 // Counter metric that counter number of times a guest error occurred
 pub(crate) static METRIC_GUEST_ERROR: &str = "guest_errors_total";
 pub(crate) static METRIC_GUEST_ERROR_LABEL_CODE: &str = "code";
@@ -21,12 +27,35 @@ pub(crate) static METRIC_GUEST_ERROR_LABEL_CODE: &str = "code";
 // Counter metric that counts the number of times a guest function was called due to timing out
 pub(crate) static METRIC_GUEST_CANCELLATION: &str = "guest_cancellations_total";
 
+// Label of the guest or host function a metric is about
+pub(crate) static METRIC_LABEL_FUNCTION_NAME: &str = "function_name";
+
+// Label of how a guest call ended: `ok`, `guest_error`, `cancelled` or `error`
+#[cfg(feature = "function_call_metrics")]
+pub(crate) static METRIC_LABEL_EXIT_REASON: &str = "exit_reason";
+
+// Counter metric that counts the guest calls that ran for longer than the sandbox's slow call
+// threshold
+pub(crate) static METRIC_SLOW_GUEST_CALLS: &str = "guest_slow_calls_total";
+
 // Counter metric that counts the number of times a vCPU was erroneously kicked by a stale cancellation
 // This can happen in two scenarios:
 // 1. Linux: A signal from a previous guest call arrives late and interrupts a new call
 // 2. Windows: WHvCancelRunVirtualProcessor is called right after vCPU exits but RUNNING_BIT is still true
 pub(crate) static METRIC_ERRONEOUS_VCPU_KICKS: &str = "erroneous_vcpu_kicks_total";
 
+// Counter metric that counts the number of times a vCPU exited to the host, by the kind of exit
+pub(crate) static METRIC_VM_EXITS: &str = "vm_exits_total";
+pub(crate) static METRIC_VM_EXITS_LABEL_TYPE: &str = "exit_type";
+
+// Counter metric that counts the guest log records dropped for being over the sandbox's
+// guest log rate limit
+pub(crate) static METRIC_GUEST_LOG_RECORDS_DROPPED: &str = "guest_log_records_dropped_total";
+
+// Histogram metric that measures how long a vCPU ran before exiting, by the kind of exit,
+// once recording exit latencies is enabled for a sandbox
+pub(crate) static METRIC_VM_EXIT_DURATION: &str = "vm_exit_latency_seconds";
+
 // Histogram metric that measures the duration of guest function calls
 #[cfg(feature = "function_call_metrics")]
 pub(crate) static METRIC_GUEST_FUNC_DURATION: &str = "guest_call_duration_seconds";
@@ -35,26 +64,128 @@ pub(crate) static METRIC_GUEST_FUNC_DURATION: &str = "guest_call_duration_second
 #[cfg(feature = "function_call_metrics")]
 pub(crate) static METRIC_HOST_FUNC_DURATION: &str = "host_call_duration_seconds";
 
+// Histogram metric that measures the time each call into the guest spends in each phase:
+// `total`, `guest`, `host` and `serialization`
+#[cfg(feature = "function_call_metrics")]
+pub(crate) static METRIC_GUEST_CALL_PHASE_DURATION: &str = "guest_call_phase_duration_seconds";
+#[cfg(feature = "function_call_metrics")]
+pub(crate) static METRIC_GUEST_CALL_PHASE_LABEL_PHASE: &str = "phase";
+
+// Counter metric that counts the times a sandbox had to wait for a Windows surrogate process,
+// because the pool was empty and at its maximum size
+#[cfg(target_os = "windows")]
+pub(crate) static METRIC_SURROGATE_POOL_EXHAUSTED: &str = "surrogate_pool_exhausted_total";
+
+// Histogram metric that measures how long sandboxes waited for a surrogate process
+#[cfg(target_os = "windows")]
+pub(crate) static METRIC_SURROGATE_POOL_WAIT_DURATION: &str =
+    "surrogate_pool_wait_duration_seconds";
+
+// Gauge metric of the number of surrogate processes that exist
+#[cfg(target_os = "windows")]
+pub(crate) static METRIC_SURROGATE_PROCESSES: &str = "surrogate_processes";
+
+// Counter metric that counts the surrogate processes found to have exited, and replaced
+#[cfg(target_os = "windows")]
+pub(crate) static METRIC_SURROGATE_DEATHS: &str = "surrogate_process_deaths_total";
+
+// Prefix of the counters and histograms guests emit, which keeps them apart from the
+// host's own metrics
+pub(crate) static METRIC_GUEST_CUSTOM_PREFIX: &str = "guest_custom_";
+
+/// Converts the labels of a sandbox into metric labels
+pub(crate) fn metric_labels(sandbox_labels: &SandboxLabels) -> Vec<metrics::Label> {
+    sandbox_labels
+        .iter()
+        .map(|(k, v)| metrics::Label::new(k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// The metric labels of a call to the guest function `function_name`, if
+/// it is known, in a sandbox with `sandbox_labels`, so the metric can be
+/// attributed to the tenant and the function
+pub(crate) fn call_metric_labels(
+    sandbox_labels: &SandboxLabels,
+    function_name: Option<&str>,
+) -> Vec<metrics::Label> {
+    let mut labels = metric_labels(sandbox_labels);
+    if let Some(name) = function_name {
+        labels.push(metrics::Label::new(
+            METRIC_LABEL_FUNCTION_NAME,
+            name.to_string(),
+        ));
+    }
+    labels
+}
+
+/// Record `metric`, which a guest in a sandbox with `sandbox_labels` sent,
+/// with its name prefixed so it can't be mistaken for the host's metrics
+pub(crate) fn emit_guest_metric(metric: &GuestMetric, sandbox_labels: &SandboxLabels) {
+    let labels = metric_labels(sandbox_labels);
+    let name = format!("{METRIC_GUEST_CUSTOM_PREFIX}{}", metric.name());
+    match metric {
+        GuestMetric::Counter(_, value) => metrics::counter!(name, labels).increment(*value),
+        GuestMetric::Histogram(_, value) => metrics::histogram!(name, labels).record(*value),
+    }
+}
+
+/// How a guest call that returned `result` ended, as the value of its
+/// `exit_reason` label
+#[cfg(feature = "function_call_metrics")]
+fn exit_reason<T>(result: &crate::Result<T>) -> &'static str {
+    match result {
+        Ok(_) => "ok",
+        Err(crate::HyperlightError::GuestError { .. }) => "guest_error",
+        Err(crate::HyperlightError::ExecutionCanceledByHost()) => "cancelled",
+        Err(_) => "error",
+    }
+}
+
+/// Record a guest call metric for a call to `name` that took `duration`
+/// and ended with `exit_reason`
+#[cfg(feature = "function_call_metrics")]
+fn emit_guest_call(
+    name: &str,
+    sandbox_labels: &SandboxLabels,
+    duration: Duration,
+    exit_reason: &'static str,
+) {
+    let mut labels = call_metric_labels(sandbox_labels, Some(name));
+    labels.push(metrics::Label::new(METRIC_LABEL_EXIT_REASON, exit_reason));
+    metrics::histogram!(METRIC_GUEST_FUNC_DURATION, labels).record(duration);
+}
+
 /// If the the `function_call_metrics` feature is enabled, this function measures
-/// the time it takes to execute the given closure, and will then emit a guest call metric
-/// with the given function name.
+/// the time it takes to execute the given closure, which makes calls to each of
+/// `names` with a single entry into the guest and returns their results in order,
+/// and will then emit a guest call metric for each call with its function name,
+/// the labels of the sandbox and how the call ended. The calls can't be timed
+/// one by one, so each is recorded as taking an equal share of the time they
+/// took together.
 ///
 /// If the feature is not enabled, the given closure is executed without any additional metrics being emitted,
 /// and the result of the closure is returned directly.
-pub(crate) fn maybe_time_and_emit_guest_call<T, F: FnOnce() -> T>(
-    #[allow(unused_variables)] name: &str,
+pub(crate) fn maybe_time_and_emit_guest_calls<T, F>(
+    #[allow(unused_variables)] names: &[&str],
+    #[allow(unused_variables)] sandbox_labels: &SandboxLabels,
     f: F,
-) -> T {
+) -> crate::Result<Vec<crate::Result<T>>>
+where
+    F: FnOnce() -> crate::Result<Vec<crate::Result<T>>>,
+{
     cfg_if::cfg_if! {
         if #[cfg(feature = "function_call_metrics")] {
-            use std::time::Instant;
-
             let start = Instant::now();
             let result = f();
-            let duration = start.elapsed();
-
-            static LABEL_GUEST_FUNC_NAME: &str = "function_name";
-            metrics::histogram!(METRIC_GUEST_FUNC_DURATION, LABEL_GUEST_FUNC_NAME => name.to_string()).record(duration);
+            let calls = u32::try_from(names.len()).unwrap_or(u32::MAX).max(1);
+            let duration = start.elapsed() / calls;
+            for (i, name) in names.iter().enumerate() {
+                let exit_reason = match &result {
+                    Ok(results) => results.get(i).map_or("error", exit_reason),
+                    Err(_) => exit_reason(&result),
+                };
+                emit_guest_call(name, sandbox_labels, duration, exit_reason);
+            }
             result
         } else {
             f()
@@ -62,6 +193,77 @@ pub(crate) fn maybe_time_and_emit_guest_call<T, F: FnOnce() -> T>(
     }
 }
 
+/// Times the phases of a call into the guest: serializing the calls and
+/// their results, running the guest, and running the host functions it
+/// calls, which are emitted as a guest call phase metric if the
+/// `function_call_metrics` feature is enabled
+#[cfg_attr(not(feature = "function_call_metrics"), allow(dead_code))]
+pub(crate) struct CallPhaseTimer {
+    started: Instant,
+    serialization: Duration,
+    dispatch: Duration,
+    host: Duration,
+}
+
+impl CallPhaseTimer {
+    /// Start timing a call
+    pub(crate) fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            serialization: Duration::ZERO,
+            dispatch: Duration::ZERO,
+            host: Duration::ZERO,
+        }
+    }
+
+    /// Add the serialization that started at `started` and just ended
+    pub(crate) fn serialized(&mut self, started: Instant) {
+        self.serialization += started.elapsed();
+    }
+
+    /// Record that the vCPU ran the call from `started` until now, of which
+    /// `host` was spent in host functions
+    pub(crate) fn dispatched(&mut self, started: Instant, host: Duration) {
+        self.dispatch = started.elapsed();
+        self.host = host;
+    }
+
+    /// Emit the time spent in each phase, with the labels of the sandbox,
+    /// for each of the calls to `function_names` that were made together.
+    /// Each call is given an equal share of the time, as the calls can't
+    /// be timed one by one.
+    pub(crate) fn emit<'a>(
+        &self,
+        #[allow(unused_variables)] sandbox_labels: &SandboxLabels,
+        #[allow(unused_variables)] function_names: impl ExactSizeIterator<Item = &'a str>,
+    ) {
+        #[cfg(feature = "function_call_metrics")]
+        {
+            let calls = u32::try_from(function_names.len())
+                .unwrap_or(u32::MAX)
+                .max(1);
+            let phases = [
+                ("total", self.started.elapsed()),
+                ("guest", self.dispatch.saturating_sub(self.host)),
+                ("host", self.host),
+                ("serialization", self.serialization),
+            ];
+            for function_name in function_names {
+                let labels = call_metric_labels(sandbox_labels, Some(function_name));
+                for (phase, duration) in phases {
+                    let mut labels = labels.clone();
+                    labels.push(metrics::Label::new(
+                        METRIC_GUEST_CALL_PHASE_LABEL_PHASE,
+                        phase,
+                    ));
+                    metrics::histogram!(METRIC_GUEST_CALL_PHASE_DURATION, labels)
+                        .record(duration / calls);
+                }
+            }
+        }
+    }
+}
+
 /// If the the `function_call_metrics` feature is enabled, this function measures
 /// the time it takes to execute the given closure, and will then emit a host call metric
 /// with the given function name.
@@ -80,8 +282,7 @@ pub(crate) fn maybe_time_and_emit_host_call<T, F: FnOnce() -> T>(
             let result = f();
             let duration = start.elapsed();
 
-            static LABEL_HOST_FUNC_NAME: &str = "function_name";
-            metrics::histogram!(METRIC_HOST_FUNC_DURATION, LABEL_HOST_FUNC_NAME => name.to_string()).record(duration);
+            metrics::histogram!(METRIC_HOST_FUNC_DURATION, METRIC_LABEL_FUNCTION_NAME => name.to_string()).record(duration);
             result
         } else {
             f()
@@ -132,12 +333,43 @@ mod tests {
         });
 
         // Convert snapshot into a hashmap for easier lookup
-        let snapshot = snapshot.into_hashmap();
+        let mut snapshot = snapshot.into_hashmap();
+
+        // Every call ends with the guest halting. The other VM exits depend
+        // on the guest, so they are not checked.
+        let halt_key = CompositeKey::new(
+            metrics_util::MetricKind::Counter,
+            Key::from_parts(
+                METRIC_VM_EXITS,
+                vec![metrics::Label::new(METRIC_VM_EXITS_LABEL_TYPE, "halt")],
+            ),
+        );
+        assert!(matches!(
+            snapshot.get(&halt_key).unwrap().2,
+            metrics_util::debugging::DebugValue::Counter(halts) if halts >= 1
+        ));
+        snapshot.retain(|key, _| key.key().name() != METRIC_VM_EXITS);
 
         cfg_if::cfg_if! {
             if #[cfg(feature = "function_call_metrics")] {
                 use metrics::Label;
 
+                // Every phase of every call is timed
+                for phase in ["total", "guest", "host", "serialization"] {
+                    let phase_key = CompositeKey::new(
+                        metrics_util::MetricKind::Histogram,
+                        Key::from_parts(
+                            METRIC_GUEST_CALL_PHASE_DURATION,
+                            vec![
+                                Label::new("function_name", "PrintOutput"),
+                                Label::new(METRIC_GUEST_CALL_PHASE_LABEL_PHASE, phase),
+                            ],
+                        ),
+                    );
+                    assert!(snapshot.contains_key(&phase_key), "no {phase} phase");
+                }
+                snapshot.retain(|key, _| key.key().name() != METRIC_GUEST_CALL_PHASE_DURATION);
+
                 let expected_num_metrics = 4;
 
                 // Verify that the histogram metrics are recorded correctly
@@ -148,7 +380,10 @@ mod tests {
                     metrics_util::MetricKind::Histogram,
                     Key::from_parts(
                         METRIC_GUEST_FUNC_DURATION,
-                        vec![Label::new("function_name", "PrintOutput")],
+                        vec![
+                            Label::new("function_name", "PrintOutput"),
+                            Label::new("exit_reason", "ok"),
+                        ],
                     ),
                 );
                 let histogram_value = &snapshot.get(&histogram_key).unwrap().2;
@@ -163,7 +398,10 @@ mod tests {
                 // 2. Guest cancellation
                 let counter_key = CompositeKey::new(
                     metrics_util::MetricKind::Counter,
-                    Key::from_name(METRIC_GUEST_CANCELLATION),
+                    Key::from_parts(
+                        METRIC_GUEST_CANCELLATION,
+                        vec![metrics::Label::new("function_name", "Spin")],
+                    ),
                 );
                 assert_eq!(
                     snapshot.get(&counter_key).unwrap().2,
@@ -175,7 +413,10 @@ mod tests {
                     metrics_util::MetricKind::Histogram,
                     Key::from_parts(
                         METRIC_GUEST_FUNC_DURATION,
-                        vec![Label::new("function_name", "Spin")],
+                        vec![
+                            Label::new("function_name", "Spin"),
+                            Label::new("exit_reason", "cancelled"),
+                        ],
                     ),
                 );
                 let histogram_value = &snapshot.get(&histogram_key).unwrap().2;
@@ -192,7 +433,10 @@ mod tests {
 
                 let counter_key = CompositeKey::new(
                     metrics_util::MetricKind::Counter,
-                    Key::from_name(METRIC_GUEST_CANCELLATION),
+                    Key::from_parts(
+                        METRIC_GUEST_CANCELLATION,
+                        vec![metrics::Label::new("function_name", "Spin")],
+                    ),
                 );
                 assert_eq!(
                     snapshot.get(&counter_key).unwrap().2,
@@ -201,4 +445,100 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    #[cfg(feature = "function_call_metrics")]
+    fn batched_calls_are_timed_one_by_one() {
+        use metrics::Label;
+
+        use crate::sandbox::GuestCall;
+
+        let recorder = metrics_util::debugging::DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let snapshot = with_local_recorder(&recorder, || {
+            let mut sbox = UninitializedSandbox::new(
+                GuestBinary::FilePath(simple_guest_as_string().unwrap()),
+                None,
+            )
+            .unwrap()
+            .evolve()
+            .unwrap();
+            sbox.call_guest_functions_batch(&[
+                GuestCall::new::<String>("Echo", "hello".to_string()),
+                GuestCall::new::<i32>("NoSuchFunction", ()),
+            ])
+            .unwrap();
+            snapshotter.snapshot()
+        })
+        .into_hashmap();
+
+        for (function_name, exit_reason) in [("Echo", "ok"), ("NoSuchFunction", "guest_error")] {
+            let duration_key = CompositeKey::new(
+                metrics_util::MetricKind::Histogram,
+                Key::from_parts(
+                    METRIC_GUEST_FUNC_DURATION,
+                    vec![
+                        Label::new("function_name", function_name),
+                        Label::new("exit_reason", exit_reason),
+                    ],
+                ),
+            );
+            assert!(
+                snapshot.contains_key(&duration_key),
+                "{function_name} not timed"
+            );
+
+            let phase_key = CompositeKey::new(
+                metrics_util::MetricKind::Histogram,
+                Key::from_parts(
+                    METRIC_GUEST_CALL_PHASE_DURATION,
+                    vec![
+                        Label::new("function_name", function_name),
+                        Label::new(METRIC_GUEST_CALL_PHASE_LABEL_PHASE, "total"),
+                    ],
+                ),
+            );
+            assert!(
+                snapshot.contains_key(&phase_key),
+                "{function_name} phases not timed"
+            );
+        }
+    }
+
+    #[test]
+    fn guest_metrics_are_prefixed_and_labelled() {
+        let recorder = metrics_util::debugging::DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let mut sandbox_labels = SandboxLabels::default();
+        sandbox_labels.set("tenant", "contoso").unwrap();
+        with_local_recorder(&recorder, || {
+            emit_guest_metric(
+                &GuestMetric::Counter("cache_hits".to_string(), 2),
+                &sandbox_labels,
+            );
+            emit_guest_metric(
+                &GuestMetric::Counter("cache_hits".to_string(), 3),
+                &sandbox_labels,
+            );
+            emit_guest_metric(
+                &GuestMetric::Histogram("parse_seconds".to_string(), 0.5),
+                &sandbox_labels,
+            );
+        });
+        let snapshot = snapshotter.snapshot().into_hashmap();
+        let labels = vec![metrics::Label::new("tenant", "contoso")];
+        let counter_key = CompositeKey::new(
+            metrics_util::MetricKind::Counter,
+            Key::from_parts("guest_custom_cache_hits", labels.clone()),
+        );
+        assert_eq!(
+            snapshot.get(&counter_key).unwrap().2,
+            metrics_util::debugging::DebugValue::Counter(5)
+        );
+        let histogram_key = CompositeKey::new(
+            metrics_util::MetricKind::Histogram,
+            Key::from_parts("guest_custom_parse_seconds", labels),
+        );
+        assert!(snapshot.contains_key(&histogram_key));
+    }
 }
@@ -0,0 +1,510 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! Pre-configured OpenTelemetry exporters for the crate's metrics and
+//! traces.
+//!
+//! The crate emits metrics through the [`metrics`] facade and spans and
+//! events through [`tracing`]. [`OtelConfig::install()`] sends both to an
+//! OTLP collector over HTTP: it installs a [`metrics`] recorder that
+//! forwards every metric to an OpenTelemetry meter, and returns an
+//! [`OtelGuard`] whose [`tracing_layer()`](OtelGuard::tracing_layer) is
+//! added to the embedder's `tracing` subscriber.
+//!
+//! Both signals carry the same resource attributes: the service name and
+//! version given, `hyperlight.version`, and any set with
+//! `OTEL_RESOURCE_ATTRIBUTES`.
+//!
+//! Each guest call is traced as a span named `guest_call <function>`, with
+//! the attributes in [`conventions`], and an error status if it failed.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use hyperlight_host::otel::OtelConfig;
+//! # use tracing_subscriber::layer::SubscriberExt;
+//! # use tracing_subscriber::util::SubscriberInitExt;
+//! # fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let otel = OtelConfig::new("my-service")
+//!     .with_endpoint("http://collector:4318")
+//!     .with_attribute("deployment.environment", "production")
+//!     .install()?;
+//! tracing_subscriber::registry()
+//!     .with(otel.tracing_layer())
+//!     .init();
+//! // ... run sandboxes ...
+//! otel.shutdown()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, SharedString, Unit};
+use opentelemetry::metrics::{Meter, MeterProvider as _};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{KeyValue, global};
+use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::sandbox::SandboxLabels;
+use crate::{Result, new_error};
+
+/// The name the crate's meter and tracer are created with
+const INSTRUMENTATION_SCOPE: &str = "hyperlight_host";
+
+/// The names of the attributes of guest call spans
+pub mod conventions {
+    /// The RPC system of a guest call, always `hyperlight`
+    pub const RPC_SYSTEM: &str = "rpc.system";
+    /// The name of the guest function called
+    pub const RPC_METHOD: &str = "rpc.method";
+    /// The labels of the sandbox the guest function was called in, as
+    /// `key=value` pairs separated by commas
+    pub const SANDBOX_LABELS: &str = "hyperlight.sandbox.labels";
+    /// The error a failed guest call failed with
+    pub const ERROR_TYPE: &str = "error.type";
+    /// The version of the crate, as a resource attribute
+    pub const HYPERLIGHT_VERSION: &str = "hyperlight.version";
+}
+
+/// Where and how the crate's metrics and traces are exported
+#[derive(Clone, Debug)]
+pub struct OtelConfig {
+    service_name: String,
+    service_version: Option<String>,
+    endpoint: Option<String>,
+    attributes: Vec<KeyValue>,
+    export_interval: Duration,
+}
+
+impl OtelConfig {
+    /// Export the metrics and traces of the service `service_name` to the
+    /// collector in `OTEL_EXPORTER_OTLP_ENDPOINT`, or to
+    /// `http://localhost:4318` if it is not set
+    pub fn new(service_name: impl Into<String>) -> Self {
+        Self {
+            service_name: service_name.into(),
+            service_version: None,
+            endpoint: None,
+            attributes: Vec::new(),
+            export_interval: Duration::from_secs(60),
+        }
+    }
+
+    /// Export to the OTLP/HTTP collector at `endpoint`, such as
+    /// `http://collector:4318`, to which `/v1/traces` and `/v1/metrics`
+    /// are appended
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Set the `service.version` resource attribute
+    pub fn with_service_version(mut self, version: impl Into<String>) -> Self {
+        self.service_version = Some(version.into());
+        self
+    }
+
+    /// Add the resource attribute `key` with `value` to all metrics and
+    /// traces
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes
+            .push(KeyValue::new(key.into(), value.into()));
+        self
+    }
+
+    /// Export metrics every `interval`. The default is a minute.
+    pub fn with_export_interval(mut self, interval: Duration) -> Self {
+        self.export_interval = interval;
+        self
+    }
+
+    /// Create the exporters, install the [`metrics`] recorder that
+    /// forwards to them, and set the global OpenTelemetry tracer and meter
+    /// providers.
+    ///
+    /// Fails if an exporter cannot be created, or a [`metrics`] recorder
+    /// is already installed.
+    pub fn install(self) -> Result<OtelGuard> {
+        let resource = self.resource();
+        let endpoint = |path: &str| {
+            self.endpoint
+                .as_ref()
+                .map(|endpoint| format!("{}/{path}", endpoint.trim_end_matches('/')))
+        };
+
+        let mut spans = SpanExporter::builder().with_http();
+        if let Some(endpoint) = endpoint("v1/traces") {
+            spans = spans.with_endpoint(endpoint);
+        }
+        let spans = spans
+            .build()
+            .map_err(|e| new_error!("Failed to create the OTLP span exporter: {}", e))?;
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_resource(resource.clone())
+            .with_batch_exporter(spans)
+            .build();
+
+        let mut metrics = MetricExporter::builder().with_http();
+        if let Some(endpoint) = endpoint("v1/metrics") {
+            metrics = metrics.with_endpoint(endpoint);
+        }
+        let metrics = metrics
+            .build()
+            .map_err(|e| new_error!("Failed to create the OTLP metric exporter: {}", e))?;
+        let reader = PeriodicReader::builder(metrics)
+            .with_interval(self.export_interval)
+            .build();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_resource(resource)
+            .with_reader(reader)
+            .build();
+
+        let recorder = OtelRecorder::new(meter_provider.meter(INSTRUMENTATION_SCOPE));
+        global::set_tracer_provider(tracer_provider.clone());
+        global::set_meter_provider(meter_provider.clone());
+        metrics::set_global_recorder(recorder)
+            .map_err(|e| new_error!("Failed to install the metrics recorder: {}", e))?;
+
+        Ok(OtelGuard {
+            providers: Some((tracer_provider, meter_provider)),
+        })
+    }
+
+    /// The resource attributes of the metrics and traces
+    fn resource(&self) -> Resource {
+        let mut builder = Resource::builder()
+            .with_service_name(self.service_name.clone())
+            .with_attribute(KeyValue::new(
+                conventions::HYPERLIGHT_VERSION,
+                env!("CARGO_PKG_VERSION"),
+            ))
+            .with_attributes(self.attributes.clone());
+        if let Some(version) = &self.service_version {
+            builder = builder.with_attribute(KeyValue::new("service.version", version.clone()));
+        }
+        builder.build()
+    }
+}
+
+/// The exporters installed by [`OtelConfig::install()`], which are flushed
+/// and shut down when it is dropped
+#[derive(Debug)]
+pub struct OtelGuard {
+    providers: Option<(SdkTracerProvider, SdkMeterProvider)>,
+}
+
+impl OtelGuard {
+    /// A `tracing` layer that exports spans, and the events in them, to
+    /// the collector
+    pub fn tracing_layer<S>(&self) -> OpenTelemetryLayer<S, SdkTracer>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let tracer = match &self.providers {
+            Some((tracer_provider, _)) => tracer_provider.tracer(INSTRUMENTATION_SCOPE),
+            None => SdkTracerProvider::default().tracer(INSTRUMENTATION_SCOPE),
+        };
+        tracing_opentelemetry::layer().with_tracer(tracer)
+    }
+
+    /// Export everything recorded so far, and stop exporting
+    pub fn shutdown(mut self) -> Result<()> {
+        self.shutdown_providers()
+    }
+
+    fn shutdown_providers(&mut self) -> Result<()> {
+        let Some((tracer_provider, meter_provider)) = self.providers.take() else {
+            return Ok(());
+        };
+        let traces = tracer_provider.shutdown();
+        let metrics = meter_provider.shutdown();
+        traces.map_err(|e| new_error!("Failed to shut down the span exporter: {}", e))?;
+        metrics.map_err(|e| new_error!("Failed to shut down the metric exporter: {}", e))
+    }
+}
+
+impl Drop for OtelGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.shutdown_providers() {
+            tracing::error!("{}", e);
+        }
+    }
+}
+
+/// The span a guest call to `function` in a sandbox with `labels` is
+/// traced in
+pub(crate) fn guest_call_span(function: &str, labels: &SandboxLabels) -> tracing::Span {
+    tracing::info_span!(
+        "guest_call",
+        otel.name = format!("guest_call {function}"),
+        otel.kind = "internal",
+        otel.status_code = tracing::field::Empty,
+        rpc.system = "hyperlight",
+        rpc.method = function,
+        hyperlight.sandbox.labels = %labels,
+        error.type = tracing::field::Empty,
+    )
+}
+
+/// Give `span`, made by [`guest_call_span()`], the status of the guest
+/// call that ended with `result`
+pub(crate) fn record_guest_call_result<T>(span: &tracing::Span, result: &Result<T>) {
+    match result {
+        Ok(_) => span.record("otel.status_code", "ok"),
+        Err(e) => span
+            .record("otel.status_code", "error")
+            .record("error.type", e.to_string()),
+    };
+}
+
+/// A [`metrics`] recorder that forwards every metric to an OpenTelemetry
+/// meter, with the metric's labels as attributes
+struct OtelRecorder {
+    meter: Meter,
+    descriptions: Mutex<HashMap<String, String>>,
+    counters: Mutex<HashMap<Key, Counter>>,
+    gauges: Mutex<HashMap<Key, Gauge>>,
+    histograms: Mutex<HashMap<Key, Histogram>>,
+}
+
+impl OtelRecorder {
+    fn new(meter: Meter) -> Self {
+        Self {
+            meter,
+            descriptions: Default::default(),
+            counters: Default::default(),
+            gauges: Default::default(),
+            histograms: Default::default(),
+        }
+    }
+
+    fn describe(&self, key: KeyName, description: SharedString) {
+        if let Ok(mut descriptions) = self.descriptions.lock() {
+            descriptions.insert(key.as_str().to_string(), description.into_owned());
+        }
+    }
+
+    fn description(&self, key: &Key) -> String {
+        self.descriptions
+            .lock()
+            .ok()
+            .and_then(|descriptions| descriptions.get(key.name()).cloned())
+            .unwrap_or_default()
+    }
+
+    /// The handle registered for `key` in `handles`, which is made with
+    /// `make` the first time the key is registered
+    fn handle<H: Clone>(
+        handles: &Mutex<HashMap<Key, H>>,
+        key: &Key,
+        make: impl FnOnce() -> H,
+    ) -> H {
+        match handles.lock() {
+            Ok(mut handles) => handles.entry(key.clone()).or_insert_with(make).clone(),
+            Err(_) => make(),
+        }
+    }
+}
+
+/// The labels of `key` as OpenTelemetry attributes
+fn attributes(key: &Key) -> Vec<KeyValue> {
+    key.labels()
+        .map(|label| KeyValue::new(label.key().to_string(), label.value().to_string()))
+        .collect()
+}
+
+impl metrics::Recorder for OtelRecorder {
+    fn describe_counter(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        self.describe(key, description);
+    }
+
+    fn describe_gauge(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        self.describe(key, description);
+    }
+
+    fn describe_histogram(&self, key: KeyName, _unit: Option<Unit>, description: SharedString) {
+        self.describe(key, description);
+    }
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+        Self::handle(&self.counters, key, || {
+            let counter = self
+                .meter
+                .u64_counter(key.name().to_string())
+                .with_description(self.description(key))
+                .build();
+            Counter::from_arc(Arc::new(OtelCounter {
+                counter,
+                attributes: attributes(key),
+                total: Default::default(),
+            }))
+        })
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+        Self::handle(&self.gauges, key, || {
+            let gauge = self
+                .meter
+                .f64_gauge(key.name().to_string())
+                .with_description(self.description(key))
+                .build();
+            Gauge::from_arc(Arc::new(OtelGauge {
+                gauge,
+                attributes: attributes(key),
+                value: Mutex::new(0.0),
+            }))
+        })
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+        Self::handle(&self.histograms, key, || {
+            let histogram = self
+                .meter
+                .f64_histogram(key.name().to_string())
+                .with_description(self.description(key))
+                .build();
+            Histogram::from_arc(Arc::new(OtelHistogram {
+                histogram,
+                attributes: attributes(key),
+            }))
+        })
+    }
+}
+
+struct OtelCounter {
+    counter: opentelemetry::metrics::Counter<u64>,
+    attributes: Vec<KeyValue>,
+    /// The total so far, which absolute values are added to the counter
+    /// relative to
+    total: std::sync::atomic::AtomicU64,
+}
+
+impl metrics::CounterFn for OtelCounter {
+    fn increment(&self, value: u64) {
+        self.total
+            .fetch_add(value, std::sync::atomic::Ordering::Relaxed);
+        self.counter.add(value, &self.attributes);
+    }
+
+    fn absolute(&self, value: u64) {
+        let total = self
+            .total
+            .fetch_max(value, std::sync::atomic::Ordering::Relaxed);
+        if value > total {
+            self.counter.add(value - total, &self.attributes);
+        }
+    }
+}
+
+struct OtelGauge {
+    gauge: opentelemetry::metrics::Gauge<f64>,
+    attributes: Vec<KeyValue>,
+    /// The value the gauge was last set to, which increments and
+    /// decrements are relative to
+    value: Mutex<f64>,
+}
+
+impl OtelGauge {
+    fn update(&self, f: impl FnOnce(f64) -> f64) {
+        if let Ok(mut value) = self.value.lock() {
+            *value = f(*value);
+            self.gauge.record(*value, &self.attributes);
+        }
+    }
+}
+
+impl metrics::GaugeFn for OtelGauge {
+    fn increment(&self, value: f64) {
+        self.update(|current| current + value);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.update(|current| current - value);
+    }
+
+    fn set(&self, value: f64) {
+        self.update(|_| value);
+    }
+}
+
+struct OtelHistogram {
+    histogram: opentelemetry::metrics::Histogram<f64>,
+    attributes: Vec<KeyValue>,
+}
+
+impl metrics::HistogramFn for OtelHistogram {
+    fn record(&self, value: f64) {
+        self.histogram.record(value, &self.attributes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::metrics::MeterProvider as _;
+    use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+    use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+
+    use super::OtelRecorder;
+
+    #[test]
+    fn metrics_are_forwarded_with_their_labels() {
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        let recorder = OtelRecorder::new(provider.meter("test"));
+
+        metrics::with_local_recorder(&recorder, || {
+            metrics::counter!("guest_errors_total", "code" => "1").increment(2);
+            metrics::counter!("guest_errors_total", "code" => "1").increment(3);
+            metrics::histogram!("guest_call_duration_seconds").record(0.5);
+        });
+        provider.force_flush().unwrap();
+
+        let exported = exporter.get_finished_metrics().unwrap();
+        let metrics: Vec<_> = exported
+            .iter()
+            .flat_map(|rm| rm.scope_metrics())
+            .flat_map(|sm| sm.metrics())
+            .collect();
+        let errors = metrics
+            .iter()
+            .find(|m| m.name() == "guest_errors_total")
+            .unwrap();
+        let AggregatedMetrics::U64(MetricData::Sum(sum)) = errors.data() else {
+            panic!("guest_errors_total is not a u64 sum");
+        };
+        let point = sum.data_points().next().unwrap();
+        assert_eq!(point.value(), 5);
+        let code = point.attributes().find(|kv| kv.key.as_str() == "code");
+        assert_eq!(code.unwrap().value.as_str(), "1");
+        assert!(
+            metrics
+                .iter()
+                .any(|m| m.name() == "guest_call_duration_seconds")
+        );
+        drop(recorder);
+    }
+}
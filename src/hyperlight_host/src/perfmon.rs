@@ -0,0 +1,248 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! PerfMon counters, in the counter set `Hyperlight`, with an instance for
+//! each host process:
+//!
+//! | Id | Counter                    | Type                          |
+//! |----|----------------------------|-------------------------------|
+//! | 1  | `Active Sandboxes`         | `perf_counter_large_rawcount` |
+//! | 2  | `Guest Calls/sec`          | `perf_counter_bulk_count`     |
+//! | 3  | `Guest Cancellations`      | `perf_counter_large_rawcount` |
+//! | 4  | `Surrogate Processes`      | `perf_counter_large_rawcount` |
+//! | 5  | `Idle Surrogate Processes` | `perf_counter_large_rawcount` |
+//!
+//! The counters are read by reference, so updating one is a single atomic
+//! operation. The counter set must be installed with `lodctr /m` for
+//! PerfMon to find it. Without the `perfmon` feature, or on other
+//! platforms than Windows, counters compile to nothing.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A sandbox was created
+pub(crate) fn sandbox_created() {
+    update(|counters| counters.active_sandboxes.fetch_add(1, Ordering::Relaxed));
+}
+
+/// A sandbox was dropped
+pub(crate) fn sandbox_dropped() {
+    update(|counters| counters.active_sandboxes.fetch_sub(1, Ordering::Relaxed));
+}
+
+/// A call into the guest is starting
+pub(crate) fn guest_call() {
+    update(|counters| counters.guest_calls.fetch_add(1, Ordering::Relaxed));
+}
+
+/// A call into the guest was cancelled
+pub(crate) fn guest_cancellation() {
+    update(|counters| counters.guest_cancellations.fetch_add(1, Ordering::Relaxed));
+}
+
+/// The surrogate pool has `created` processes, of which `idle` are not
+/// used by a sandbox
+#[cfg(target_os = "windows")]
+pub(crate) fn surrogate_pool(created: usize, idle: usize) {
+    update(|counters| {
+        counters
+            .surrogate_processes
+            .store(created as u64, Ordering::Relaxed);
+        counters
+            .idle_surrogate_processes
+            .store(idle as u64, Ordering::Relaxed);
+    });
+}
+
+/// The values of the counters, in the order of their ids
+#[derive(Debug)]
+#[cfg_attr(not(perfmon), allow(dead_code))]
+struct Counters {
+    active_sandboxes: AtomicU64,
+    guest_calls: AtomicU64,
+    guest_cancellations: AtomicU64,
+    surrogate_processes: AtomicU64,
+    idle_surrogate_processes: AtomicU64,
+}
+
+#[cfg(not(perfmon))]
+#[inline(always)]
+fn update<T>(_f: impl FnOnce(&Counters) -> T) {}
+
+#[cfg(perfmon)]
+use provider::update;
+
+#[cfg(perfmon)]
+mod provider {
+    use std::ffi::c_void;
+    use std::sync::OnceLock;
+    use std::sync::atomic::AtomicU64;
+
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::System::Performance::{
+        PERF_ATTRIB_BY_REFERENCE, PERF_COUNTER_INFO, PERF_COUNTERSET_INFO,
+        PERF_COUNTERSET_MULTI_INSTANCES, PERF_DETAIL_NOVICE, PerfCreateInstance,
+        PerfSetCounterRefValue, PerfSetCounterSetInfo, PerfStartProviderEx,
+    };
+    use windows::core::{GUID, HSTRING};
+
+    use super::Counters;
+
+    /// The GUIDs the manifest installed with `lodctr` gives the provider
+    /// and its counter set
+    const PROVIDER_ID: GUID = GUID::from_u128(0x258555c0_b223_40cf_b3b6_91679d76d8c4);
+    const COUNTER_SET_ID: GUID = GUID::from_u128(0xc33f28e4_8a95_478c_a999_53bd20ce9966);
+
+    // Counter types, from winperf.h
+    const PERF_COUNTER_LARGE_RAWCOUNT: u32 = 0x0001_0100;
+    const PERF_COUNTER_BULK_COUNT: u32 = 0x1041_0500;
+
+    const NUM_COUNTERS: usize = 5;
+
+    /// The layout `PerfSetCounterSetInfo` expects: the counter set,
+    /// followed by its counters
+    #[repr(C)]
+    pub(super) struct Template {
+        pub(super) info: PERF_COUNTERSET_INFO,
+        pub(super) counters: [PERF_COUNTER_INFO; NUM_COUNTERS],
+    }
+
+    pub(super) fn template() -> Template {
+        let types = [
+            PERF_COUNTER_LARGE_RAWCOUNT,
+            PERF_COUNTER_BULK_COUNT,
+            PERF_COUNTER_LARGE_RAWCOUNT,
+            PERF_COUNTER_LARGE_RAWCOUNT,
+            PERF_COUNTER_LARGE_RAWCOUNT,
+        ];
+        Template {
+            info: PERF_COUNTERSET_INFO {
+                CounterSetGuid: COUNTER_SET_ID,
+                ProviderGuid: PROVIDER_ID,
+                NumCounters: NUM_COUNTERS as u32,
+                InstanceType: PERF_COUNTERSET_MULTI_INSTANCES,
+            },
+            counters: std::array::from_fn(|i| PERF_COUNTER_INFO {
+                CounterId: i as u32 + 1,
+                Type: types[i],
+                Attrib: PERF_ATTRIB_BY_REFERENCE,
+                Size: size_of::<u64>() as u32,
+                DetailLevel: PERF_DETAIL_NOVICE.0,
+                Scale: 0,
+                // By reference, a counter's data is a pointer to its value
+                Offset: (i * size_of::<*const u64>()) as u32,
+            }),
+        }
+    }
+
+    /// The counters, registered with their instance by the first update,
+    /// or `None` if they can't be
+    fn counters() -> Option<&'static Counters> {
+        static COUNTERS: Counters = Counters {
+            active_sandboxes: AtomicU64::new(0),
+            guest_calls: AtomicU64::new(0),
+            guest_cancellations: AtomicU64::new(0),
+            surrogate_processes: AtomicU64::new(0),
+            idle_surrogate_processes: AtomicU64::new(0),
+        };
+        static REGISTERED: OnceLock<bool> = OnceLock::new();
+        let registered = *REGISTERED.get_or_init(|| match register(&COUNTERS) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("Failed to register the PerfMon counters: {}", e);
+                false
+            }
+        });
+        registered.then_some(&COUNTERS)
+    }
+
+    /// Registers the provider, and an instance of the counter set named
+    /// after the process whose counters are `counters`. The provider is
+    /// never stopped, so the counters stay valid until the process exits.
+    fn register(counters: &'static Counters) -> Result<(), String> {
+        let mut provider = HANDLE::default();
+        // SAFETY: the GUID and handle outlive the call
+        let status = unsafe { PerfStartProviderEx(&PROVIDER_ID, None, &mut provider) };
+        if status != 0 {
+            return Err(format!("PerfStartProviderEx failed: {:#x}", status));
+        }
+        let mut template = template();
+        // SAFETY: the template is laid out as the call expects, and valid
+        // for its size
+        let status = unsafe {
+            PerfSetCounterSetInfo(
+                provider,
+                (&mut template as *mut Template).cast::<PERF_COUNTERSET_INFO>(),
+                size_of::<Template>() as u32,
+            )
+        };
+        if status != 0 {
+            return Err(format!("PerfSetCounterSetInfo failed: {:#x}", status));
+        }
+
+        let process = std::env::current_exe()
+            .ok()
+            .and_then(|exe| Some(exe.file_stem()?.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "hyperlight".to_string());
+        let name = HSTRING::from(format!("{}_{}", process, std::process::id()));
+        // SAFETY: the GUID and name outlive the call
+        let instance = unsafe { PerfCreateInstance(provider, &COUNTER_SET_ID, &name, 0) };
+        if instance.is_null() {
+            return Err("PerfCreateInstance failed".to_string());
+        }
+
+        let values = [
+            &counters.active_sandboxes,
+            &counters.guest_calls,
+            &counters.guest_cancellations,
+            &counters.surrogate_processes,
+            &counters.idle_surrogate_processes,
+        ];
+        for (i, value) in values.into_iter().enumerate() {
+            // SAFETY: the instance is valid, and the value is static
+            let status = unsafe {
+                PerfSetCounterRefValue(
+                    provider,
+                    instance,
+                    i as u32 + 1,
+                    value.as_ptr() as *const c_void,
+                )
+            };
+            if status != 0 {
+                return Err(format!("PerfSetCounterRefValue failed: {:#x}", status));
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn update<T>(f: impl FnOnce(&Counters) -> T) {
+        if let Some(counters) = counters() {
+            f(counters);
+        }
+    }
+}
+
+#[cfg(all(test, perfmon))]
+mod tests {
+    #[test]
+    fn counters_are_laid_out_by_reference() {
+        let template = super::provider::template();
+        assert_eq!(template.info.NumCounters as usize, template.counters.len());
+        for (i, counter) in template.counters.iter().enumerate() {
+            assert_eq!(counter.CounterId as usize, i + 1);
+            assert_eq!(counter.Offset as usize, i * 8);
+        }
+    }
+}
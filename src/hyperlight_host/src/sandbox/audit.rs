@@ -0,0 +1,201 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use serde_json::json;
+
+use super::labels::SandboxLabels;
+use crate::{Result, new_error};
+
+/// A host function a guest invoked, as given to an [`AuditSink`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// When the host function returned, in RFC 3339 format
+    pub timestamp: String,
+    /// The name of the host function
+    pub function: String,
+    /// The labels of the sandbox whose guest invoked the function
+    pub sandbox: SandboxLabels,
+    /// The error code the call failed with, or `None` if it succeeded
+    pub error: Option<ErrorCode>,
+}
+
+impl AuditRecord {
+    /// A record of a call to `function` that just returned, failing with
+    /// `error` if it failed
+    pub(crate) fn new(function: &str, sandbox: &SandboxLabels, error: Option<ErrorCode>) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            function: function.to_string(),
+            sandbox: sandbox.clone(),
+            error,
+        }
+    }
+
+    /// The record as a single line of JSON, without a trailing newline.
+    /// The result is `"ok"` or the name of the error code.
+    pub fn to_json(&self) -> String {
+        json!({
+            "timestamp": self.timestamp,
+            "function": self.function,
+            "sandbox": self
+                .sandbox
+                .iter()
+                .map(|(key, value)| (key.to_string(), json!(value)))
+                .collect::<serde_json::Map<_, _>>(),
+            "result": self
+                .error
+                .map_or_else(|| "ok".to_string(), |code| format!("{code:?}")),
+        })
+        .to_string()
+    }
+}
+
+/// Receives a record of every host function the guests of a sandbox
+/// invoke, so that running third-party code can be audited.
+///
+/// A sink is set with
+/// [`UninitializedSandbox::set_audit_sink()`](crate::UninitializedSandbox::set_audit_sink)
+/// or [`MultiUseSandbox::set_audit_sink()`](crate::MultiUseSandbox::set_audit_sink).
+/// Each call is recorded once it returns, in the order the guest made
+/// them, including calls that failed because they were denied, throttled
+/// or timed out. Calls answered from a replayed
+/// [`ExecutionRecording`](crate::sandbox::ExecutionRecording) do not
+/// invoke the host function, and are not recorded.
+///
+/// Records are delivered synchronously, on the thread running the guest,
+/// so a sink that is slow to write holds up the guest. Closures taking an
+/// `&AuditRecord` are sinks, and [`FileAuditSink`] appends records to a
+/// file.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::{AuditRecord, FileAuditSink};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// sandbox.set_label("tenant", "contoso")?;
+/// sandbox.set_audit_sink(FileAuditSink::new("/var/log/hyperlight/audit.jsonl")?)?;
+///
+/// // Or handle the records directly
+/// sandbox.set_audit_sink(|record: &AuditRecord| println!("{}", record.to_json()))?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait AuditSink: Send + Sync {
+    /// Record a host function call. A sink that fails to keep the record
+    /// should report it itself, as the guest is not told.
+    fn record(&self, record: &AuditRecord);
+}
+
+impl<F: Fn(&AuditRecord) + Send + Sync> AuditSink for F {
+    fn record(&self, record: &AuditRecord) {
+        self(record)
+    }
+}
+
+/// An [`AuditSink`] that appends each record to a file, as a line of
+/// JSON in the format of [`AuditRecord::to_json()`]. Each line is written
+/// with a single write to a file opened for appending, so several sinks,
+/// even in different processes, can share a file.
+#[derive(Debug)]
+pub struct FileAuditSink {
+    file: Mutex<File>,
+}
+
+impl FileAuditSink {
+    /// Append records to the file at `path`, creating it if it does not
+    /// exist
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| new_error!("Failed to open audit log {}: {}", path.display(), e))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl AuditSink for FileAuditSink {
+    fn record(&self, record: &AuditRecord) {
+        let mut line = record.to_json();
+        line.push('\n');
+        let res = match self.file.lock() {
+            Ok(mut file) => file.write_all(line.as_bytes()),
+            Err(e) => Err(std::io::Error::other(e.to_string())),
+        };
+        if let Err(e) = res {
+            tracing::error!("Failed to write to the audit log: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+
+    use super::{AuditRecord, AuditSink, FileAuditSink};
+    use crate::sandbox::SandboxLabels;
+
+    #[test]
+    fn records_are_json() {
+        let mut labels = SandboxLabels::default();
+        labels.set("tenant", "contoso").unwrap();
+        let record = AuditRecord::new(
+            "Delete",
+            &labels,
+            Some(ErrorCode::HostFunctionPermissionDenied),
+        );
+        let json: serde_json::Value = serde_json::from_str(&record.to_json()).unwrap();
+        assert_eq!(json["function"], "Delete");
+        assert_eq!(json["sandbox"]["tenant"], "contoso");
+        assert_eq!(json["result"], "HostFunctionPermissionDenied");
+
+        let record = AuditRecord::new("Open", &labels, None);
+        let json: serde_json::Value = serde_json::from_str(&record.to_json()).unwrap();
+        assert_eq!(json["result"], "ok");
+    }
+
+    #[test]
+    fn file_sink_appends_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        std::fs::write(&path, "earlier\n").unwrap();
+        let sink = FileAuditSink::new(&path).unwrap();
+        let labels = SandboxLabels::default();
+        sink.record(&AuditRecord::new("Open", &labels, None));
+        sink.record(&AuditRecord::new("Read", &labels, None));
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "earlier");
+        let json: serde_json::Value = serde_json::from_str(lines[2]).unwrap();
+        assert_eq!(json["function"], "Read");
+    }
+}
@@ -0,0 +1,86 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt;
+
+/// The stack of the guest at the point it returned an error.
+///
+/// A backtrace is part of every
+/// [`HyperlightError::GuestError`](crate::HyperlightError::GuestError),
+/// and lists the return addresses on the guest's stack where the error was
+/// created, innermost first, each resolved by the sandbox's
+/// [`GuestSymbolizer`](super::GuestSymbolizer) to the guest function it is
+/// in where the guest binary has symbols, and to its source file and line
+/// where the binary has debug information.
+///
+/// Frames are found by following the guest's frame pointers, so the
+/// backtrace is only complete for guests built with
+/// `-C force-frame-pointers=yes`, and is empty for guests that do not
+/// report one, such as those written in C.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GuestBacktrace {
+    /// The frames of the backtrace, innermost first
+    pub frames: Vec<GuestFrame>,
+}
+
+/// A frame of a [`GuestBacktrace`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GuestFrame {
+    /// The return address of the frame in the guest's address space
+    pub address: u64,
+    /// The demangled name of the guest function the address is in, if the
+    /// guest binary has a symbol for it
+    pub symbol: Option<String>,
+    /// How far the address is from the start of `symbol`
+    pub offset: u64,
+    /// The source file the address is in, if the guest binary has debug
+    /// information for it
+    pub file: Option<String>,
+    /// The line in `file` the address is on
+    pub line: Option<u32>,
+}
+
+impl GuestBacktrace {
+    /// Whether the backtrace has no frames
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+impl fmt::Display for GuestBacktrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, frame) in self.frames.iter().enumerate() {
+            writeln!(f, "{i:4}: {frame}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for GuestFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.address)?;
+        if let Some(symbol) = &self.symbol {
+            write!(f, " - {symbol}+{:#x}", self.offset)?;
+        }
+        if let Some(file) = &self.file {
+            write!(f, " at {file}")?;
+            if let Some(line) = self.line {
+                write!(f, ":{line}")?;
+            }
+        }
+        Ok(())
+    }
+}
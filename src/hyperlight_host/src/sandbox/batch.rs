@@ -0,0 +1,53 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use hyperlight_common::flatbuffer_wrappers::function_call::ParameterRef;
+use hyperlight_common::flatbuffer_wrappers::function_types::ReturnType;
+
+use crate::func::{ParameterTuple, SupportedReturnType};
+
+/// A guest function call to be made as part of a batch with
+/// [`MultiUseSandbox::call_guest_functions_batch()`](crate::MultiUseSandbox::call_guest_functions_batch).
+#[derive(Debug, Clone)]
+pub struct GuestCall {
+    pub(crate) function_name: String,
+    pub(crate) return_type: ReturnType,
+    pub(crate) args: Vec<ParameterRef<'static>>,
+}
+
+impl GuestCall {
+    /// A call to the guest function `function_name` with `args`, which
+    /// returns an `Output`
+    pub fn new<Output: SupportedReturnType>(
+        function_name: impl Into<String>,
+        args: impl ParameterTuple,
+    ) -> Self {
+        Self {
+            function_name: function_name.into(),
+            return_type: Output::TYPE,
+            args: args
+                .into_value()
+                .into_iter()
+                .map(ParameterRef::Value)
+                .collect(),
+        }
+    }
+
+    /// The name of the guest function that is called
+    pub fn function_name(&self) -> &str {
+        &self.function_name
+    }
+}
@@ -0,0 +1,275 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::hypervisor::InterruptHandle;
+
+/// A request-scoped handle for cancelling guest calls.
+///
+/// A `CancellationToken` is passed to
+/// [`MultiUseSandbox::call_with_cancellation()`](crate::MultiUseSandbox::call_with_cancellation)
+/// and may be cloned and handed to any other thread (for example, the task
+/// that owns the client connection of an async server). Calling
+/// [`cancel()`](Self::cancel) interrupts whichever guest call the token is
+/// currently attached to.
+///
+/// Unlike using [`InterruptHandle::kill()`] directly, a token is only ever
+/// attached to a sandbox for the duration of a single call, so cancelling it
+/// can never interrupt an unrelated call that happens to run on the same
+/// sandbox later. Each call is killed at most once, no matter how many times
+/// `cancel()` is called.
+///
+/// Cancellation is sticky: once a token has been cancelled, any further call
+/// made with it fails immediately with
+/// [`HyperlightError::ExecutionCanceledByHost`](crate::HyperlightError::ExecutionCanceledByHost).
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    inner: Arc<Mutex<TokenState>>,
+}
+
+#[derive(Debug, Default)]
+struct TokenState {
+    /// Whether [`CancellationToken::cancel`] has been called
+    cancelled: bool,
+    /// The interrupt handle of the sandbox running the call this token is
+    /// currently attached to, if any
+    attached: Option<Arc<dyn InterruptHandle>>,
+}
+
+impl CancellationToken {
+    /// Create a new, uncancelled token
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the guest call this token is attached to, and any future call
+    /// made with this token.
+    ///
+    /// This blocks for as long as it takes to interrupt the vCPU, in the same
+    /// way as [`InterruptHandle::kill()`].
+    pub fn cancel(&self) {
+        let mut state = self.lock();
+        if state.cancelled {
+            return;
+        }
+        state.cancelled = true;
+        // Taking the handle guarantees that the attached call is killed
+        // exactly once. The lock is held across `kill()` so that the call
+        // cannot detach and move on to its next guest call in the meantime.
+        if let Some(handle) = state.attached.take() {
+            handle.kill();
+        }
+    }
+
+    /// Returns whether [`cancel()`](Self::cancel) has been called
+    pub fn is_cancelled(&self) -> bool {
+        self.lock().cancelled
+    }
+
+    /// Attach the token to a guest call that is about to start. If the token
+    /// has already been cancelled, the call is killed immediately.
+    ///
+    /// This must be called after the sandbox has cleared any stale
+    /// cancellation request, or the kill may be lost.
+    pub(crate) fn attach(&self, handle: Arc<dyn InterruptHandle>) {
+        let mut state = self.lock();
+        if state.cancelled {
+            handle.kill();
+        } else {
+            state.attached = Some(handle);
+        }
+    }
+
+    /// Detach the token from the guest call that has just finished
+    pub(crate) fn detach(&self) {
+        self.lock().attached = None;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, TokenState> {
+        // The state is always left consistent, so a panic while holding the
+        // lock does not need to be propagated.
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+thread_local! {
+    /// The status of the guest call that the host function running on this
+    /// thread was called by, if any
+    static CURRENT_STATUS: RefCell<Option<CancellationStatus>> = const { RefCell::new(None) };
+}
+
+/// Whether the guest call that called a host function has been cancelled.
+///
+/// A host function that runs for a long time can get the status of the
+/// guest call it is serving with [`current()`](Self::current), and check it
+/// to stop early when the call is cancelled, whether by
+/// [`InterruptHandle::kill()`] or a [`CancellationToken`]. Otherwise a
+/// cancelled call only stops once the host function has returned. The status
+/// can be cloned and moved to other threads the host function hands its
+/// work to.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::CancellationStatus;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// sandbox.register("CountTo", |n: u64| {
+///     let status = CancellationStatus::current();
+///     let mut count = 0;
+///     while count < n && !status.is_cancelled() {
+///         count += 1;
+///     }
+///     Ok(count)
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct CancellationStatus {
+    is_cancelled: Option<Arc<dyn Fn() -> bool + Send + Sync>>,
+}
+
+impl CancellationStatus {
+    /// A status that reports whatever `is_cancelled` returns
+    pub(crate) fn new(is_cancelled: impl Fn() -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            is_cancelled: Some(Arc::new(is_cancelled)),
+        }
+    }
+
+    /// The status of the guest call that the host function running on this
+    /// thread was called by. Outside of a host function, this is a status
+    /// that is never cancelled.
+    pub fn current() -> Self {
+        CURRENT_STATUS.with(|current| current.borrow().clone().unwrap_or_default())
+    }
+
+    /// Returns whether the guest call has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.is_cancelled.as_ref().is_some_and(|f| f())
+    }
+
+    /// Run `f` with this as the [`current()`](Self::current) status of
+    /// the calling thread
+    pub(crate) fn scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        struct Restore(Option<CancellationStatus>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let previous = self.0.take();
+                CURRENT_STATUS.with(|current| *current.borrow_mut() = previous);
+            }
+        }
+        let _restore = Restore(CURRENT_STATUS.with(|current| current.replace(Some(self.clone()))));
+        f()
+    }
+}
+
+impl std::fmt::Debug for CancellationStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancellationStatus")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use super::{CancellationStatus, CancellationToken};
+    use crate::hypervisor::InterruptHandle;
+
+    #[derive(Debug, Default)]
+    struct CountingHandle {
+        kills: AtomicUsize,
+    }
+
+    impl InterruptHandle for CountingHandle {
+        fn kill(&self) -> bool {
+            self.kills.fetch_add(1, Ordering::Relaxed);
+            true
+        }
+
+        #[cfg(gdb)]
+        fn kill_from_debugger(&self) -> bool {
+            false
+        }
+
+        fn dropped(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn cancel_kills_attached_call_once() {
+        let handle = Arc::new(CountingHandle::default());
+        let token = CancellationToken::new();
+
+        token.attach(handle.clone());
+        token.cancel();
+        token.clone().cancel();
+        assert!(token.is_cancelled());
+        assert_eq!(handle.kills.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn cancel_after_detach_does_not_kill() {
+        let handle = Arc::new(CountingHandle::default());
+        let token = CancellationToken::new();
+
+        token.attach(handle.clone());
+        token.detach();
+        token.cancel();
+        assert_eq!(handle.kills.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn status_is_current_within_its_scope() {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let status = CancellationStatus::new({
+            let cancelled = cancelled.clone();
+            move || cancelled.load(Ordering::Relaxed)
+        });
+
+        status.scope(|| {
+            let current = CancellationStatus::current();
+            assert!(!current.is_cancelled());
+            cancelled.store(true, Ordering::Relaxed);
+            assert!(current.is_cancelled());
+        });
+        assert!(!CancellationStatus::current().is_cancelled());
+    }
+
+    #[test]
+    fn attach_after_cancel_kills_immediately() {
+        let handle = Arc::new(CountingHandle::default());
+        let token = CancellationToken::new();
+
+        token.cancel();
+        token.attach(handle.clone());
+        assert_eq!(handle.kills.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,62 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use bitflags::bitflags;
+
+bitflags! {
+    /// Capabilities a host function needs in order to be called.
+    ///
+    /// A host function registered with
+    /// [`UninitializedSandbox::register_with_capabilities()`](crate::UninitializedSandbox::register_with_capabilities)
+    /// can only be called by a guest running in a sandbox that has been
+    /// granted all of its capabilities with
+    /// [`UninitializedSandbox::grant_capabilities()`](crate::UninitializedSandbox::grant_capabilities).
+    /// Calls to the function from any other sandbox fail with
+    /// [`ErrorCode::HostFunctionPermissionDenied`](hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode::HostFunctionPermissionDenied)
+    /// in the guest, without the function being run.
+    ///
+    /// Functions registered without capabilities can be called by every
+    /// sandbox, and sandboxes are granted no capabilities by default.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+    /// # use hyperlight_host::sandbox::Capabilities;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?;
+    /// sandbox.register_with_capabilities(
+    ///     "Fetch",
+    ///     |url: String| Ok(format!("contents of {url}")),
+    ///     Capabilities::NETWORK,
+    /// )?;
+    /// sandbox.grant_capabilities(Capabilities::NETWORK);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct Capabilities: u32 {
+        /// Access to the network
+        const NETWORK = 1;
+        /// Access to the host filesystem
+        const FILESYSTEM = 2;
+        /// Access to secrets such as credentials and keys
+        const SECRETS = 4;
+    }
+}
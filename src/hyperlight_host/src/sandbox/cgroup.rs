@@ -0,0 +1,172 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::{Result, new_error};
+
+/// The mount point of the cgroup v2 hierarchy
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// A cgroup v2 group that the thread running a sandbox's vCPU is moved
+/// into while guest code is executing.
+///
+/// Hyperlight runs the vCPU on the thread that makes the guest call, so
+/// that thread is moved into the cgroup when the call starts and back to
+/// its original cgroup when the call returns. This lets the kernel enforce
+/// CPU and memory limits on hostile guests in addition to Hyperlight's own
+/// timeouts, on every hypervisor backend.
+///
+/// Since only a single thread is moved, the group must be a threaded
+/// cgroup (`cgroup.type` set to `threaded`) in the same threaded subtree
+/// as the calling process, and the process must be allowed to write to its
+/// `cgroup.threads` file. Creating and delegating the group is up to the
+/// caller. Note that `cpu.max` applies to the group itself, while the
+/// memory controller is not threaded and so `memory.high` applies to the
+/// threaded domain the group belongs to.
+#[derive(Clone, Debug)]
+pub struct VcpuCgroup {
+    path: PathBuf,
+}
+
+impl VcpuCgroup {
+    /// Use the existing cgroup v2 group at `path`
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        if !path.join("cgroup.threads").is_file() {
+            return Err(new_error!(
+                "{} is not a cgroup v2 group",
+                path.to_string_lossy()
+            ));
+        }
+        Ok(Self { path })
+    }
+
+    /// The path of the group
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Limit the group to `quota` of CPU time in every `period`, by writing
+    /// to its `cpu.max` file. A `quota` of `None` removes the limit.
+    pub fn set_cpu_max(&self, quota: Option<Duration>, period: Duration) -> Result<()> {
+        let quota = match quota {
+            Some(quota) => quota.as_micros().to_string(),
+            None => "max".to_string(),
+        };
+        self.write("cpu.max", &format!("{quota} {}", period.as_micros()))
+    }
+
+    /// Throttle the group once its memory usage exceeds `bytes`, by writing
+    /// to its `memory.high` file. `None` removes the limit.
+    pub fn set_memory_high(&self, bytes: Option<u64>) -> Result<()> {
+        match bytes {
+            Some(bytes) => self.write("memory.high", &bytes.to_string()),
+            None => self.write("memory.high", "max"),
+        }
+    }
+
+    /// Move the calling thread into the group until the returned guard is
+    /// dropped
+    pub(crate) fn enter(&self) -> Result<CgroupMembership> {
+        let previous = current_thread_cgroup()?;
+        move_current_thread(&self.path)?;
+        Ok(CgroupMembership { previous })
+    }
+
+    fn write(&self, file: &str, value: &str) -> Result<()> {
+        std::fs::write(self.path.join(file), value).map_err(|e| {
+            new_error!(
+                "failed to write {value:?} to {}: {e}",
+                self.path.join(file).to_string_lossy()
+            )
+        })
+    }
+}
+
+/// Membership of the calling thread in a [`VcpuCgroup`]. Dropping it moves
+/// the thread back to the cgroup it was in before.
+pub(crate) struct CgroupMembership {
+    previous: PathBuf,
+}
+
+impl Drop for CgroupMembership {
+    fn drop(&mut self) {
+        if let Err(e) = move_current_thread(&self.previous) {
+            tracing::error!("failed to restore vCPU thread cgroup: {e:?}");
+        }
+    }
+}
+
+/// The cgroup v2 group the calling thread is currently in
+fn current_thread_cgroup() -> Result<PathBuf> {
+    let contents = std::fs::read_to_string("/proc/thread-self/cgroup")?;
+    let relative = contents
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .ok_or_else(|| new_error!("the calling thread is not in a cgroup v2 hierarchy"))?;
+    Ok(Path::new(CGROUP_ROOT).join(relative.trim_start_matches('/')))
+}
+
+fn move_current_thread(group: &Path) -> Result<()> {
+    let tid = unsafe { libc::gettid() };
+    std::fs::write(group.join("cgroup.threads"), tid.to_string()).map_err(|e| {
+        new_error!(
+            "failed to move thread {tid} into cgroup {}: {e}",
+            group.to_string_lossy()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::VcpuCgroup;
+
+    #[test]
+    fn rejects_non_cgroup_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(VcpuCgroup::new(dir.path()).is_err());
+    }
+
+    #[test]
+    fn writes_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("cgroup.threads"), "").unwrap();
+        let cgroup = VcpuCgroup::new(dir.path()).unwrap();
+
+        cgroup
+            .set_cpu_max(Some(Duration::from_millis(20)), Duration::from_millis(100))
+            .unwrap();
+        let cpu_max = std::fs::read_to_string(dir.path().join("cpu.max")).unwrap();
+        assert_eq!(cpu_max, "20000 100000");
+
+        cgroup.set_memory_high(Some(64 * 1024 * 1024)).unwrap();
+        let memory_high = std::fs::read_to_string(dir.path().join("memory.high")).unwrap();
+        assert_eq!(memory_high, "67108864");
+
+        cgroup
+            .set_cpu_max(None, Duration::from_millis(100))
+            .unwrap();
+        cgroup.set_memory_high(None).unwrap();
+        let cpu_max = std::fs::read_to_string(dir.path().join("cpu.max")).unwrap();
+        let memory_high = std::fs::read_to_string(dir.path().join("memory.high")).unwrap();
+        assert_eq!(cpu_max, "max 100000");
+        assert_eq!(memory_high, "max");
+    }
+}
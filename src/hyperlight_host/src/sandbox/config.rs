@@ -15,18 +15,66 @@ limitations under the License.
 */
 
 use std::cmp::max;
+#[cfg(gdb)]
+use std::net::{IpAddr, Ipv4Addr};
 use std::time::Duration;
 
+use hyperlight_common::call_encoding::CallEncoding;
 #[cfg(target_os = "linux")]
 use libc::c_int;
 use tracing::{Span, instrument};
 
+use crate::HyperlightError;
+#[cfg(target_os = "windows")]
+use crate::hypervisor::SurrogateIsolation;
+use crate::mem::layout::SandboxMemoryLayout;
+
 /// Used for passing debug configuration to a sandbox
+///
+/// The sandbox listens for a debugger on `address` and `port`, or on a
+/// unix domain socket if one is set with
+/// [`UninitializedSandbox::set_guest_debug_socket()`](crate::UninitializedSandbox::set_guest_debug_socket).
 #[cfg(gdb)]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct DebugInfo {
     /// Guest debug port
     pub port: u16,
+    /// The address to listen on. Listening on an address other than
+    /// localhost, e.g. to debug a guest in a container, lets anyone who can
+    /// reach it read and write guest memory.
+    pub address: IpAddr,
+    /// The protocol the debugger speaks on the port
+    pub protocol: DebugProtocol,
+    /// Whether the guest waits at its entry point for a debugger to
+    /// connect. If not, the guest runs as usual until a debugger connects,
+    /// and is stopped the next time it runs after that.
+    pub wait_for_attach: bool,
+}
+
+#[cfg(gdb)]
+impl DebugInfo {
+    /// Listen for a gdb client on localhost at `port`, waiting for it to
+    /// connect before running the guest
+    pub fn new(port: u16) -> Self {
+        Self {
+            port,
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            protocol: DebugProtocol::Gdb,
+            wait_for_attach: true,
+        }
+    }
+}
+
+/// The protocol a debugger uses to attach to a guest
+#[cfg(gdb)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum DebugProtocol {
+    /// The GDB remote serial protocol, for `gdb` and `lldb`
+    #[default]
+    Gdb,
+    /// The Debug Adapter Protocol, for editors such as VS Code to attach
+    /// to the guest directly, without a `gdb` or `lldb` in between
+    Dap,
 }
 
 /// The complete set of configuration needed to create a Sandbox
@@ -74,6 +122,25 @@ pub struct SandboxConfiguration {
     interrupt_vcpu_sigrtmin_offset: u8,
     /// How much writable memory to offer the guest
     scratch_size: usize,
+    /// Whether [`crate::UninitializedSandbox::evolve`] should refuse guest
+    /// binaries that were not verified against a
+    /// [`GuestSignature`](crate::sandbox::GuestSignature)
+    require_signed_guests: bool,
+    /// The maximum size of the guest's main stack during a guest call, in
+    /// bytes. If set to 0, the stack may grow until scratch memory runs out.
+    max_stack_size: usize,
+    /// Whether the guest should zero the stack pages it used once each
+    /// guest call has returned
+    zero_stack_between_calls: bool,
+    /// The wire format used to pass function calls between the host and
+    /// the guest
+    call_encoding: CallEncoding,
+    /// Whether the guest's clocks are stopped while it is not running
+    #[cfg(target_os = "windows")]
+    freeze_guest_time: bool,
+    /// How the surrogate process holding the guest's memory is restricted
+    #[cfg(target_os = "windows")]
+    surrogate_isolation: SurrogateIsolation,
 }
 
 impl SandboxConfiguration {
@@ -114,6 +181,14 @@ impl SandboxConfiguration {
             scratch_size,
             interrupt_retry_delay,
             interrupt_vcpu_sigrtmin_offset,
+            require_signed_guests: false,
+            max_stack_size: 0,
+            zero_stack_between_calls: false,
+            call_encoding: CallEncoding::FlatBuffers,
+            #[cfg(target_os = "windows")]
+            freeze_guest_time: false,
+            #[cfg(target_os = "windows")]
+            surrogate_isolation: SurrogateIsolation::Standard,
             #[cfg(gdb)]
             guest_debug_info,
             #[cfg(crashdump)]
@@ -215,6 +290,110 @@ impl SandboxConfiguration {
         self.scratch_size = scratch_size;
     }
 
+    /// Require guest binaries to be verified against a
+    /// [`GuestSignature`](crate::sandbox::GuestSignature) when the sandbox is
+    /// created. If set, evolving a sandbox whose guest was not verified fails
+    /// with [`HyperlightError::GuestBinaryVerificationFailed`].
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_require_signed_guests(&mut self, require: bool) {
+        self.require_signed_guests = require;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_require_signed_guests(&self) -> bool {
+        self.require_signed_guests
+    }
+
+    /// Set the maximum size of the guest's main stack during a guest call.
+    /// The size is rounded up to a whole number of pages. A guest call that
+    /// overflows it is aborted. If set to 0 (the default), the stack may
+    /// grow until scratch memory runs out.
+    ///
+    /// This can be overridden for individual guest functions with
+    /// [`MultiUseSandbox::set_function_stack_size`](crate::MultiUseSandbox::set_function_stack_size).
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_max_stack_size(&mut self, max_stack_size: usize) {
+        self.max_stack_size = max_stack_size;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_max_stack_size(&self) -> usize {
+        self.max_stack_size
+    }
+
+    /// Have the guest zero the stack pages it used once each guest call has
+    /// returned, so that nothing one call leaves on the stack is visible to
+    /// the next.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_zero_stack_between_calls(&mut self, enable: bool) {
+        self.zero_stack_between_calls = enable;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_zero_stack_between_calls(&self) -> bool {
+        self.zero_stack_between_calls
+    }
+
+    /// Set the wire format used to pass function calls, and the results of
+    /// host function calls, between the host and the guest. The guest must
+    /// have been built with support for it, or it fails to initialise with
+    /// [`HyperlightError::UnsupportedCallEncoding`](crate::HyperlightError::UnsupportedCallEncoding).
+    /// The postcard encoding needs the `postcard` feature of both the
+    /// host and the guest.
+    ///
+    /// The results of guest function calls are always flatbuffers, whichever
+    /// encoding is set, as guest functions return them already encoded.
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_call_encoding(&mut self, call_encoding: CallEncoding) {
+        self.call_encoding = call_encoding;
+    }
+
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_call_encoding(&self) -> CallEncoding {
+        self.call_encoding
+    }
+
+    /// Stop the guest's clocks while it is not running: between guest
+    /// calls, and while it waits for a host function to return. The
+    /// guest's TSC and reference time then only advance while it runs, so
+    /// long host work doesn't make time leap forward for it.
+    ///
+    /// The clocks belong to the sandbox's VM. A sandbox that is suspended
+    /// while idle is resumed in a new VM, whose clocks do not carry on from
+    /// those of the old one, so the guest's time is only frozen across the
+    /// calls made between a suspension and the next.
+    ///
+    /// Guest time no longer follows the host's, so this should not be
+    /// used with guests that need the wall-clock time, or with the
+    /// `trace_guest` feature, whose timestamps come from the guest's TSC.
+    #[cfg(target_os = "windows")]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_freeze_guest_time(&mut self, enable: bool) {
+        self.freeze_guest_time = enable;
+    }
+
+    #[cfg(target_os = "windows")]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_freeze_guest_time(&self) -> bool {
+        self.freeze_guest_time
+    }
+
+    /// Set how the surrogate process that holds the guest's memory is
+    /// restricted. Restricted surrogates are started for the sandbox
+    /// instead of being taken from the pool, which makes creating the
+    /// sandbox slower. See [`SurrogateIsolation`].
+    #[cfg(target_os = "windows")]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub fn set_surrogate_isolation(&mut self, isolation: SurrogateIsolation) {
+        self.surrogate_isolation = isolation;
+    }
+
+    #[cfg(target_os = "windows")]
+    #[instrument(skip_all, parent = Span::current(), level= "Trace")]
+    pub(crate) fn get_surrogate_isolation(&self) -> SurrogateIsolation {
+        self.surrogate_isolation
+    }
+
     #[cfg(crashdump)]
     #[instrument(skip_all, parent = Span::current(), level= "Trace")]
     pub(crate) fn get_guest_core_dump(&self) -> bool {
@@ -259,9 +438,277 @@ impl Default for SandboxConfiguration {
     }
 }
 
+/// A builder for [`SandboxConfiguration`] that validates the configuration
+/// as a whole when [`build()`](Self::build) is called.
+///
+/// Unlike the setters on [`SandboxConfiguration`], which silently clamp
+/// out-of-range values, the builder rejects them with a
+/// [`HyperlightError::InvalidConfiguration`] describing what is wrong. Values
+/// that depend on each other (for example, the scratch region must be large
+/// enough to hold the input and output buffers) are checked together, so
+/// mistakes are reported here rather than deep inside sandbox creation.
+///
+/// # Examples
+///
+/// ```
+/// # use hyperlight_host::sandbox::SandboxConfiguration;
+/// let cfg = SandboxConfiguration::builder()
+///     .heap_size(0x40000)
+///     .input_data_size(0x8000)
+///     .output_data_size(0x8000)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct SandboxConfigurationBuilder {
+    input_data_size: usize,
+    output_data_size: usize,
+    heap_size: u64,
+    scratch_size: usize,
+    interrupt_retry_delay: Duration,
+    interrupt_vcpu_sigrtmin_offset: u8,
+    require_signed_guests: bool,
+    max_stack_size: usize,
+    zero_stack_between_calls: bool,
+    call_encoding: CallEncoding,
+    #[cfg(target_os = "windows")]
+    freeze_guest_time: bool,
+    #[cfg(target_os = "windows")]
+    surrogate_isolation: SurrogateIsolation,
+    #[cfg(gdb)]
+    guest_debug_info: Option<DebugInfo>,
+    #[cfg(crashdump)]
+    guest_core_dump: bool,
+}
+
+impl SandboxConfiguration {
+    /// Create a [`SandboxConfigurationBuilder`] initialised with the
+    /// default configuration values
+    pub fn builder() -> SandboxConfigurationBuilder {
+        SandboxConfigurationBuilder::default()
+    }
+}
+
+impl Default for SandboxConfigurationBuilder {
+    fn default() -> Self {
+        let cfg = SandboxConfiguration::default();
+        Self {
+            input_data_size: cfg.input_data_size,
+            output_data_size: cfg.output_data_size,
+            heap_size: cfg.heap_size_override,
+            scratch_size: cfg.scratch_size,
+            interrupt_retry_delay: cfg.interrupt_retry_delay,
+            interrupt_vcpu_sigrtmin_offset: cfg.interrupt_vcpu_sigrtmin_offset,
+            require_signed_guests: cfg.require_signed_guests,
+            max_stack_size: cfg.max_stack_size,
+            zero_stack_between_calls: cfg.zero_stack_between_calls,
+            call_encoding: cfg.call_encoding,
+            #[cfg(target_os = "windows")]
+            freeze_guest_time: cfg.freeze_guest_time,
+            #[cfg(target_os = "windows")]
+            surrogate_isolation: cfg.surrogate_isolation,
+            #[cfg(gdb)]
+            guest_debug_info: cfg.guest_debug_info,
+            #[cfg(crashdump)]
+            guest_core_dump: cfg.guest_core_dump,
+        }
+    }
+}
+
+impl SandboxConfigurationBuilder {
+    /// Set the size of the memory buffer that is made available for input
+    /// to the guest. Must be at least [`SandboxConfiguration::MIN_INPUT_SIZE`].
+    pub fn input_data_size(mut self, input_data_size: usize) -> Self {
+        self.input_data_size = input_data_size;
+        self
+    }
+
+    /// Set the size of the memory buffer that is made available for output
+    /// from the guest. Must be at least [`SandboxConfiguration::MIN_OUTPUT_SIZE`].
+    pub fn output_data_size(mut self, output_data_size: usize) -> Self {
+        self.output_data_size = output_data_size;
+        self
+    }
+
+    /// Set the heap size to use in the guest sandbox. If set to 0,
+    /// [`SandboxConfiguration::DEFAULT_HEAP_SIZE`] is used.
+    pub fn heap_size(mut self, heap_size: u64) -> Self {
+        self.heap_size = heap_size;
+        self
+    }
+
+    /// Set the size of the scratch region, which holds the guest stack,
+    /// page tables and the input and output buffers.
+    pub fn scratch_size(mut self, scratch_size: usize) -> Self {
+        self.scratch_size = scratch_size;
+        self
+    }
+
+    /// Set the delay between attempts to interrupt the vCPU thread. Must be
+    /// non-zero.
+    #[cfg(target_os = "linux")]
+    pub fn interrupt_retry_delay(mut self, delay: Duration) -> Self {
+        self.interrupt_retry_delay = delay;
+        self
+    }
+
+    /// Set the offset from `SIGRTMIN` of the real-time signal used to
+    /// interrupt the vCPU thread. `SIGRTMIN + offset` must not exceed
+    /// `SIGRTMAX`.
+    #[cfg(target_os = "linux")]
+    pub fn interrupt_vcpu_sigrtmin_offset(mut self, offset: u8) -> Self {
+        self.interrupt_vcpu_sigrtmin_offset = offset;
+        self
+    }
+
+    /// Require guest binaries to be verified against a
+    /// [`GuestSignature`](crate::sandbox::GuestSignature)
+    pub fn require_signed_guests(mut self, require: bool) -> Self {
+        self.require_signed_guests = require;
+        self
+    }
+
+    /// Set the maximum size of the guest's main stack during a guest call,
+    /// or 0 for no limit. Must not exceed the scratch size.
+    pub fn max_stack_size(mut self, max_stack_size: usize) -> Self {
+        self.max_stack_size = max_stack_size;
+        self
+    }
+
+    /// Have the guest zero the stack pages it used once each guest call
+    /// has returned
+    pub fn zero_stack_between_calls(mut self, enable: bool) -> Self {
+        self.zero_stack_between_calls = enable;
+        self
+    }
+
+    /// Set the wire format used to pass function calls between the host
+    /// and the guest
+    pub fn call_encoding(mut self, call_encoding: CallEncoding) -> Self {
+        self.call_encoding = call_encoding;
+        self
+    }
+
+    /// Stop the guest's clocks while it is not running
+    #[cfg(target_os = "windows")]
+    pub fn freeze_guest_time(mut self, enable: bool) -> Self {
+        self.freeze_guest_time = enable;
+        self
+    }
+
+    /// Set how the surrogate process holding the guest's memory is
+    /// restricted
+    #[cfg(target_os = "windows")]
+    pub fn surrogate_isolation(mut self, isolation: SurrogateIsolation) -> Self {
+        self.surrogate_isolation = isolation;
+        self
+    }
+
+    /// Enable or disable guest core dump generation
+    #[cfg(crashdump)]
+    pub fn guest_core_dump(mut self, enable: bool) -> Self {
+        self.guest_core_dump = enable;
+        self
+    }
+
+    /// Set the configuration for guest debugging
+    #[cfg(gdb)]
+    pub fn guest_debug_info(mut self, debug_info: DebugInfo) -> Self {
+        self.guest_debug_info = Some(debug_info);
+        self
+    }
+
+    /// Validate the configuration and build a [`SandboxConfiguration`]
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+    pub fn build(self) -> crate::Result<SandboxConfiguration> {
+        let invalid = |msg: String| Err(HyperlightError::InvalidConfiguration(msg));
+        let max_memory_size = SandboxMemoryLayout::MAX_MEMORY_SIZE;
+
+        if self.input_data_size < SandboxConfiguration::MIN_INPUT_SIZE {
+            return invalid(format!(
+                "input data size {:#x} is smaller than the minimum of {:#x}",
+                self.input_data_size,
+                SandboxConfiguration::MIN_INPUT_SIZE
+            ));
+        }
+        if self.output_data_size < SandboxConfiguration::MIN_OUTPUT_SIZE {
+            return invalid(format!(
+                "output data size {:#x} is smaller than the minimum of {:#x}",
+                self.output_data_size,
+                SandboxConfiguration::MIN_OUTPUT_SIZE
+            ));
+        }
+        if self.heap_size > max_memory_size as u64 {
+            return invalid(format!(
+                "heap size {:#x} exceeds the maximum sandbox memory size of {:#x}",
+                self.heap_size, max_memory_size
+            ));
+        }
+        if self.scratch_size > max_memory_size {
+            return invalid(format!(
+                "scratch size {:#x} exceeds the maximum sandbox memory size of {:#x}",
+                self.scratch_size, max_memory_size
+            ));
+        }
+        let min_scratch_size = hyperlight_common::layout::min_scratch_size(
+            self.input_data_size,
+            self.output_data_size,
+        );
+        if self.scratch_size < min_scratch_size {
+            return invalid(format!(
+                "scratch size {:#x} is too small to hold the input buffer ({:#x}) and output buffer ({:#x}); it must be at least {:#x}",
+                self.scratch_size, self.input_data_size, self.output_data_size, min_scratch_size
+            ));
+        }
+        if self.max_stack_size > self.scratch_size {
+            return invalid(format!(
+                "maximum stack size {:#x} exceeds the scratch size of {:#x}",
+                self.max_stack_size, self.scratch_size
+            ));
+        }
+        if self.interrupt_retry_delay.is_zero() {
+            return invalid("interrupt retry delay must be non-zero".to_string());
+        }
+        #[cfg(target_os = "linux")]
+        if libc::SIGRTMIN() + self.interrupt_vcpu_sigrtmin_offset as c_int > libc::SIGRTMAX() {
+            return invalid(format!(
+                "interrupt signal offset {} exceeds the maximum real-time signal number",
+                self.interrupt_vcpu_sigrtmin_offset
+            ));
+        }
+
+        let mut cfg = SandboxConfiguration::new(
+            self.input_data_size,
+            self.output_data_size,
+            Some(self.heap_size),
+            self.scratch_size,
+            self.interrupt_retry_delay,
+            self.interrupt_vcpu_sigrtmin_offset,
+            #[cfg(gdb)]
+            self.guest_debug_info,
+            #[cfg(crashdump)]
+            self.guest_core_dump,
+        );
+        cfg.require_signed_guests = self.require_signed_guests;
+        cfg.max_stack_size = self.max_stack_size;
+        cfg.zero_stack_between_calls = self.zero_stack_between_calls;
+        cfg.call_encoding = self.call_encoding;
+        #[cfg(target_os = "windows")]
+        {
+            cfg.freeze_guest_time = self.freeze_guest_time;
+            cfg.surrogate_isolation = self.surrogate_isolation;
+        }
+        Ok(cfg)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    #[cfg(target_os = "linux")]
+    use std::time::Duration;
+
     use super::SandboxConfiguration;
+    use crate::HyperlightError;
 
     #[test]
     fn overrides() {
@@ -320,6 +767,58 @@ mod tests {
         assert_eq!(SandboxConfiguration::MIN_OUTPUT_SIZE, cfg.output_data_size);
     }
 
+    #[test]
+    fn builder_defaults_match_default() {
+        let cfg = SandboxConfiguration::builder().build().unwrap();
+        assert_eq!(cfg, SandboxConfiguration::default());
+    }
+
+    #[test]
+    fn builder_sets_values() {
+        let cfg = SandboxConfiguration::builder()
+            .input_data_size(0x8000)
+            .output_data_size(0x9000)
+            .heap_size(0x50000)
+            .scratch_size(0x60000)
+            .require_signed_guests(true)
+            .build()
+            .unwrap();
+        assert_eq!(0x8000, cfg.get_input_data_size());
+        assert_eq!(0x9000, cfg.get_output_data_size());
+        assert_eq!(0x50000, cfg.get_heap_size());
+        assert_eq!(0x60000, cfg.get_scratch_size());
+        assert!(cfg.get_require_signed_guests());
+    }
+
+    #[test]
+    fn builder_rejects_invalid_values() {
+        let invalid = [
+            SandboxConfiguration::builder()
+                .input_data_size(SandboxConfiguration::MIN_INPUT_SIZE - 1),
+            SandboxConfiguration::builder()
+                .output_data_size(SandboxConfiguration::MIN_OUTPUT_SIZE - 1),
+            SandboxConfiguration::builder().heap_size(u64::MAX),
+            SandboxConfiguration::builder().scratch_size(usize::MAX),
+            // scratch must be able to hold the input and output buffers
+            SandboxConfiguration::builder()
+                .input_data_size(SandboxConfiguration::DEFAULT_SCRATCH_SIZE)
+                .output_data_size(SandboxConfiguration::DEFAULT_SCRATCH_SIZE),
+            SandboxConfiguration::builder()
+                .max_stack_size(SandboxConfiguration::DEFAULT_SCRATCH_SIZE + 1),
+            #[cfg(target_os = "linux")]
+            SandboxConfiguration::builder().interrupt_retry_delay(Duration::ZERO),
+            #[cfg(target_os = "linux")]
+            SandboxConfiguration::builder().interrupt_vcpu_sigrtmin_offset(u8::MAX),
+        ];
+        for builder in invalid {
+            let res = builder.clone().build();
+            assert!(
+                matches!(res, Err(HyperlightError::InvalidConfiguration(_))),
+                "{builder:?} should be rejected but got {res:?}"
+            );
+        }
+    }
+
     mod proptests {
         use proptest::prelude::*;
 
@@ -354,7 +853,7 @@ mod tests {
             #[cfg(gdb)]
             fn guest_debug_info(port in 9000..=u16::MAX) {
                 let mut cfg = SandboxConfiguration::default();
-                let debug_info = DebugInfo { port };
+                let debug_info = DebugInfo::new(port);
                 cfg.set_guest_debug_info(debug_info);
                 prop_assert_eq!(debug_info, *cfg.get_guest_debug_info().as_ref().unwrap());
             }
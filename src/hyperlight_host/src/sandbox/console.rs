@@ -0,0 +1,623 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+
+use hyperlight_common::flatbuffer_wrappers::function_types::{
+    ParameterType, ParameterValue, ReturnType, ReturnValue,
+};
+
+use super::MultiUseSandbox;
+use super::snapshot::Snapshot;
+use crate::{Result, new_error};
+
+/// The bytes of memory `mem` shows when no length is given
+const DEFAULT_DUMP_LEN: usize = 64;
+
+const HELP: &str = "\
+commands:
+  functions                          list the guest's functions
+  call <function> [args] [-> type]   call a guest function
+  mem <address> [len]                show the guest's memory at a virtual address
+  write <address> <bytes>            write to the guest's memory at a virtual address
+  regs                               show the vCPU's registers
+  snapshot                           take a snapshot of the sandbox
+  restore                            restore the last snapshot taken
+  stats                              show the vCPU's exits
+  dump <path>                        write an ELF core dump of the guest
+  help                               show this help
+  quit                               leave the console
+
+literals:
+  42  -7i64  0x10u32  42u64          integers, i32 unless suffixed
+  1.5  2.0f32                        floats, f64 unless suffixed
+  true  false                        bools
+  \"text\"                             strings
+  [1, 2, 0xff]  b\"bytes\"             byte vectors
+
+types: i32 u32 i64 u64 f32 f64 string bool bytes void";
+
+/// An interactive console for calling into a guest and inspecting it
+/// while developing it, without writing a host for each experiment.
+///
+/// Each line is a command, such as `call Echo "hello"` to call a guest
+/// function with literal arguments, `mem 0x200000 32` to show the guest's
+/// memory or `regs` to show its registers. `help` lists every command.
+/// Arguments are converted to the types of the function's parameters when
+/// the guest registered them, and given with suffixes such as `42u64`
+/// otherwise.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+/// # use hyperlight_host::sandbox::SandboxConsole;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let sandbox: MultiUseSandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?.evolve()?;
+///
+/// let mut console = SandboxConsole::new(sandbox);
+/// console.run(std::io::stdin().lock(), std::io::stdout())?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The `console` example runs a console for a guest binary given on the
+/// command line:
+///
+/// ```text
+/// cargo run --example console --features console -- path/to/guest
+/// ```
+pub struct SandboxConsole {
+    sandbox: MultiUseSandbox,
+    snapshot: Option<Arc<Snapshot>>,
+}
+
+impl SandboxConsole {
+    /// Create a console for `sandbox`
+    pub fn new(sandbox: MultiUseSandbox) -> Self {
+        Self {
+            sandbox,
+            snapshot: None,
+        }
+    }
+
+    /// The sandbox the console calls into
+    pub fn sandbox(&mut self) -> &mut MultiUseSandbox {
+        &mut self.sandbox
+    }
+
+    /// Consume the console and return its sandbox
+    pub fn into_sandbox(self) -> MultiUseSandbox {
+        self.sandbox
+    }
+
+    /// Read commands from `input` and write what they show to `output`
+    /// until `quit` is given or `input` ends. A command that fails shows
+    /// its error and the console carries on.
+    pub fn run(&mut self, mut input: impl BufRead, mut output: impl Write) -> Result<()> {
+        let mut line = String::new();
+        loop {
+            write!(output, "> ")?;
+            output.flush()?;
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            match self.execute(&line) {
+                Ok(Some(shown)) if shown.is_empty() => {}
+                Ok(Some(shown)) => writeln!(output, "{shown}")?,
+                Ok(None) => return Ok(()),
+                Err(e) => writeln!(output, "error: {e}")?,
+            }
+        }
+    }
+
+    /// Execute a single command and return what it shows, or `None` if
+    /// it is `quit`
+    pub fn execute(&mut self, line: &str) -> Result<Option<String>> {
+        let tokens = tokenize(line)?;
+        let Some((command, args)) = tokens.split_first() else {
+            return Ok(Some(String::new()));
+        };
+        let shown = match (command.as_str(), args) {
+            ("quit" | "exit", []) => return Ok(None),
+            ("help", []) => HELP.to_string(),
+            ("functions", []) => self.functions(),
+            ("call", [function, args @ ..]) => self.call(function, args)?,
+            ("mem", [address]) => self.mem(parse_u64(address)?, DEFAULT_DUMP_LEN)?,
+            ("mem", [address, len]) => self.mem(parse_u64(address)?, parse_u64(len)? as usize)?,
+            ("write", [address, bytes]) => {
+                let ParameterValue::VecBytes(bytes) = parse_literal(bytes, None)? else {
+                    return Err(new_error!("{bytes} is not a byte vector"));
+                };
+                self.sandbox
+                    .write_guest_memory(parse_u64(address)?, &bytes)?;
+                format!("wrote {} bytes", bytes.len())
+            }
+            ("regs", []) => self.regs()?,
+            ("snapshot", []) => {
+                self.snapshot = Some(self.sandbox.snapshot()?);
+                "snapshot taken".to_string()
+            }
+            ("restore", []) => {
+                let snapshot = self
+                    .snapshot
+                    .clone()
+                    .ok_or_else(|| new_error!("No snapshot has been taken"))?;
+                self.sandbox.restore(snapshot)?;
+                "restored".to_string()
+            }
+            ("stats", []) => self.sandbox.exit_stats().to_string(),
+            ("dump", [path]) => self.dump(path)?,
+            _ => {
+                return Err(new_error!(
+                    "Unknown command or wrong arguments: `{}`, see `help`",
+                    line.trim()
+                ));
+            }
+        };
+        Ok(Some(shown))
+    }
+
+    fn functions(&self) -> String {
+        let Some(functions) = self.sandbox.guest_functions() else {
+            return "the guest did not register its functions".to_string();
+        };
+        let mut shown = String::new();
+        for function in functions {
+            let params: Vec<_> = function
+                .parameter_types
+                .iter()
+                .map(parameter_type_name)
+                .collect();
+            let _ = writeln!(
+                shown,
+                "{}({}) -> {}",
+                function.name,
+                params.join(", "),
+                return_type_name(&function.return_type)
+            );
+        }
+        shown.trim_end().to_string()
+    }
+
+    fn call(&mut self, function: &str, args: &[String]) -> Result<String> {
+        let (args, return_type) = match args {
+            [args @ .., arrow, return_type] if arrow == "->" => {
+                (args, Some(parse_return_type(return_type)?))
+            }
+            args => (args, None),
+        };
+        let signature = self
+            .sandbox
+            .guest_functions()
+            .and_then(|functions| functions.iter().find(|f| f.name == function))
+            .cloned();
+        if let Some(signature) = &signature
+            && signature.parameter_types.len() != args.len()
+        {
+            return Err(new_error!(
+                "{function} takes {} arguments but {} were given",
+                signature.parameter_types.len(),
+                args.len()
+            ));
+        }
+        let args = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let param = signature.as_ref().map(|s| &s.parameter_types[i]);
+                parse_literal(arg, param)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let return_type = return_type
+            .or_else(|| signature.map(|s| s.return_type))
+            .ok_or_else(|| {
+                new_error!("The return type of {function} is not known, give it with `-> <type>`")
+            })?;
+        let value =
+            self.sandbox
+                .call_type_erased_guest_function_by_name(function, return_type, args)?;
+        Ok(show_return_value(&value))
+    }
+
+    fn mem(&mut self, address: u64, len: usize) -> Result<String> {
+        let bytes = self.sandbox.read_guest_memory(address, len)?;
+        Ok(hex_dump(address, &bytes))
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn regs(&self) -> Result<String> {
+        let regs = self.sandbox.vcpu_registers()?;
+        let named = [
+            ("rax", regs.rax),
+            ("rbx", regs.rbx),
+            ("rcx", regs.rcx),
+            ("rdx", regs.rdx),
+            ("rsi", regs.rsi),
+            ("rdi", regs.rdi),
+            ("rsp", regs.rsp),
+            ("rbp", regs.rbp),
+            ("r8", regs.r8),
+            ("r9", regs.r9),
+            ("r10", regs.r10),
+            ("r11", regs.r11),
+            ("r12", regs.r12),
+            ("r13", regs.r13),
+            ("r14", regs.r14),
+            ("r15", regs.r15),
+            ("rip", regs.rip),
+            ("rflags", regs.rflags),
+        ];
+        let mut shown = String::new();
+        for row in named.chunks(2) {
+            for (name, value) in row {
+                let _ = write!(shown, "{name:<6} {value:#018x}   ");
+            }
+            shown = shown.trim_end().to_string();
+            shown.push('\n');
+        }
+        Ok(shown.trim_end().to_string())
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    fn regs(&self) -> Result<String> {
+        Err(new_error!(
+            "Showing registers is not supported on this architecture"
+        ))
+    }
+
+    #[cfg(crashdump)]
+    fn dump(&mut self, path: &str) -> Result<String> {
+        self.sandbox.dump_core(path)?;
+        Ok(format!("core dump written to {path}"))
+    }
+
+    #[cfg(not(crashdump))]
+    fn dump(&mut self, _path: &str) -> Result<String> {
+        Err(new_error!(
+            "Core dumps need hyperlight-host's `crashdump` feature"
+        ))
+    }
+}
+
+impl std::fmt::Debug for SandboxConsole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SandboxConsole")
+            .field("sandbox", &self.sandbox)
+            .field("has_snapshot", &self.snapshot.is_some())
+            .finish()
+    }
+}
+
+/// Split a command line into words, keeping quoted strings and
+/// bracketed byte vectors whole
+fn tokenize(line: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut token = String::new();
+        let mut quoted = false;
+        let mut depth = 0;
+        while let Some(&c) = chars.peek() {
+            if !quoted && depth == 0 && c.is_whitespace() {
+                break;
+            }
+            chars.next();
+            token.push(c);
+            match c {
+                '\\' if quoted => {
+                    if let Some(escaped) = chars.next() {
+                        token.push(escaped);
+                    }
+                }
+                '"' => quoted = !quoted,
+                '[' if !quoted => depth += 1,
+                ']' if !quoted => depth -= 1,
+                _ => {}
+            }
+        }
+        if quoted || depth != 0 {
+            return Err(new_error!("Unterminated literal: {token}"));
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Parse a literal argument, as the type of `param` if it is known
+fn parse_literal(literal: &str, param: Option<&ParameterType>) -> Result<ParameterValue> {
+    let value = if let Some(quoted) = literal.strip_prefix("b\"") {
+        ParameterValue::VecBytes(unquote(&format!("\"{quoted}"))?.into_bytes())
+    } else if literal.starts_with('"') {
+        ParameterValue::String(unquote(literal)?)
+    } else if let Some(bytes) = literal
+        .strip_prefix('[')
+        .and_then(|bytes| bytes.strip_suffix(']'))
+    {
+        let bytes = bytes
+            .split(',')
+            .map(str::trim)
+            .filter(|byte| !byte.is_empty())
+            .map(|byte| {
+                u8::try_from(parse_u64(byte)?).map_err(|_| new_error!("{byte} is not a byte"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        ParameterValue::VecBytes(bytes)
+    } else if literal == "true" || literal == "false" {
+        ParameterValue::Bool(literal == "true")
+    } else {
+        parse_number(literal, param)?
+    };
+
+    match param {
+        Some(param) if *param != ParameterType::from(&value) => Err(new_error!(
+            "{literal} is not a {}",
+            parameter_type_name(param)
+        )),
+        _ => Ok(value),
+    }
+}
+
+fn parse_number(literal: &str, param: Option<&ParameterType>) -> Result<ParameterValue> {
+    const SUFFIXES: [(&str, ParameterType); 6] = [
+        ("i32", ParameterType::Int),
+        ("u32", ParameterType::UInt),
+        ("i64", ParameterType::Long),
+        ("u64", ParameterType::ULong),
+        ("f32", ParameterType::Float),
+        ("f64", ParameterType::Double),
+    ];
+    let is_hex = literal.trim_start_matches('-').starts_with("0x");
+    let (digits, suffixed) = SUFFIXES
+        .iter()
+        // A hex literal can end in digits that look like `f32` or `f64`
+        .filter(|(suffix, _)| !(is_hex && suffix.starts_with('f')))
+        .find_map(|(suffix, ty)| Some((literal.strip_suffix(suffix)?, Some(ty.clone()))))
+        .unwrap_or((literal, None));
+    let is_float = !is_hex && (digits.contains('.') || digits.contains('e'));
+    let ty = match (suffixed, param) {
+        (Some(ty), _) => ty,
+        (None, Some(param)) => param.clone(),
+        (None, None) if is_float => ParameterType::Double,
+        (None, None) => ParameterType::Int,
+    };
+
+    let invalid = || new_error!("{literal} is not a valid {}", parameter_type_name(&ty));
+    let integer = || -> Result<i128> {
+        let (negative, digits) = match digits.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, digits),
+        };
+        let magnitude = match digits.strip_prefix("0x") {
+            Some(hex) => i128::from_str_radix(hex, 16),
+            None => digits.parse(),
+        }
+        .map_err(|_| invalid())?;
+        Ok(if negative { -magnitude } else { magnitude })
+    };
+    Ok(match ty {
+        ParameterType::Int => ParameterValue::Int(integer()?.try_into().map_err(|_| invalid())?),
+        ParameterType::UInt => ParameterValue::UInt(integer()?.try_into().map_err(|_| invalid())?),
+        ParameterType::Long => ParameterValue::Long(integer()?.try_into().map_err(|_| invalid())?),
+        ParameterType::ULong => {
+            ParameterValue::ULong(integer()?.try_into().map_err(|_| invalid())?)
+        }
+        ParameterType::Float => ParameterValue::Float(digits.parse().map_err(|_| invalid())?),
+        ParameterType::Double => ParameterValue::Double(digits.parse().map_err(|_| invalid())?),
+        _ => return Err(invalid()),
+    })
+}
+
+/// The contents of a double-quoted string literal, with `\"`, `\\`, `\n`,
+/// `\t` and `\0` escapes
+fn unquote(literal: &str) -> Result<String> {
+    let inner = literal
+        .strip_prefix('"')
+        .and_then(|inner| inner.strip_suffix('"'))
+        .ok_or_else(|| new_error!("Unterminated string: {literal}"))?;
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unquoted.push(c);
+            continue;
+        }
+        unquoted.push(match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some(c @ ('"' | '\\')) => c,
+            _ => return Err(new_error!("Invalid escape in {literal}")),
+        });
+    }
+    Ok(unquoted)
+}
+
+fn parse_u64(literal: &str) -> Result<u64> {
+    match literal.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16),
+        None => literal.replace('_', "").parse(),
+    }
+    .map_err(|_| new_error!("{literal} is not a number"))
+}
+
+fn parse_return_type(name: &str) -> Result<ReturnType> {
+    Ok(match name {
+        "i32" => ReturnType::Int,
+        "u32" => ReturnType::UInt,
+        "i64" => ReturnType::Long,
+        "u64" => ReturnType::ULong,
+        "f32" => ReturnType::Float,
+        "f64" => ReturnType::Double,
+        "string" => ReturnType::String,
+        "bool" => ReturnType::Bool,
+        "bytes" => ReturnType::VecBytes,
+        "void" => ReturnType::Void,
+        _ => return Err(new_error!("Unknown return type {name}, see `help`")),
+    })
+}
+
+fn parameter_type_name(ty: &ParameterType) -> &'static str {
+    match ty {
+        ParameterType::Int => "i32",
+        ParameterType::UInt => "u32",
+        ParameterType::Long => "i64",
+        ParameterType::ULong => "u64",
+        ParameterType::Float => "f32",
+        ParameterType::Double => "f64",
+        ParameterType::String => "string",
+        ParameterType::Bool => "bool",
+        ParameterType::VecBytes => "bytes",
+        ParameterType::Value => "value",
+        ParameterType::VecFloat => "[f32]",
+        ParameterType::VecDouble => "[f64]",
+    }
+}
+
+fn return_type_name(ty: &ReturnType) -> &'static str {
+    match ty {
+        ReturnType::Int => "i32",
+        ReturnType::UInt => "u32",
+        ReturnType::Long => "i64",
+        ReturnType::ULong => "u64",
+        ReturnType::Float => "f32",
+        ReturnType::Double => "f64",
+        ReturnType::String => "string",
+        ReturnType::Bool => "bool",
+        ReturnType::Void => "void",
+        ReturnType::VecBytes => "bytes",
+        ReturnType::Value => "value",
+    }
+}
+
+fn show_return_value(value: &ReturnValue) -> String {
+    match value {
+        ReturnValue::Int(v) => v.to_string(),
+        ReturnValue::UInt(v) => format!("{v}u32"),
+        ReturnValue::Long(v) => format!("{v}i64"),
+        ReturnValue::ULong(v) => format!("{v}u64"),
+        ReturnValue::Float(v) => format!("{v:?}f32"),
+        ReturnValue::Double(v) => format!("{v:?}"),
+        ReturnValue::String(v) => format!("{v:?}"),
+        ReturnValue::Bool(v) => v.to_string(),
+        ReturnValue::Void(()) => String::new(),
+        ReturnValue::VecBytes(v) => format!("{v:?}"),
+        ReturnValue::Value(v) => format!("{v:?}"),
+    }
+}
+
+/// `bytes` read from `address`, as lines of 16 bytes in hex followed by
+/// their printable characters
+fn hex_dump(address: u64, bytes: &[u8]) -> String {
+    let mut shown = String::new();
+    for (i, line) in bytes.chunks(16).enumerate() {
+        let _ = write!(shown, "{:#018x} ", address + (i * 16) as u64);
+        for byte in line {
+            let _ = write!(shown, " {byte:02x}");
+        }
+        let padding = (16 - line.len()) * 3;
+        let text: String = line
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        let _ = writeln!(shown, "{:padding$}  |{text}|", "");
+    }
+    shown.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterType, ParameterValue};
+
+    use super::{hex_dump, parse_literal, tokenize};
+
+    #[test]
+    fn lines_are_split_into_words_and_literals() {
+        let tokens = tokenize(r#"  call Echo "hello \"world\"" [1, 2] b"x y" -> string "#).unwrap();
+        assert_eq!(
+            tokens,
+            [
+                "call",
+                "Echo",
+                r#""hello \"world\"""#,
+                "[1, 2]",
+                r#"b"x y""#,
+                "->",
+                "string"
+            ]
+        );
+        assert!(tokenize(r#"call Echo "hello"#).is_err());
+        assert!(tokenize("write 0x1000 [1, 2").is_err());
+    }
+
+    #[test]
+    fn literals_are_parsed_as_their_parameter_types() {
+        let parse = |literal| parse_literal(literal, None).unwrap();
+        assert_eq!(parse("42"), ParameterValue::Int(42));
+        assert_eq!(parse("-7i64"), ParameterValue::Long(-7));
+        assert_eq!(parse("0x10u32"), ParameterValue::UInt(16));
+        assert_eq!(parse("0xff"), ParameterValue::Int(255));
+        assert_eq!(parse("1.5"), ParameterValue::Double(1.5));
+        assert_eq!(parse("2.0f32"), ParameterValue::Float(2.0));
+        assert_eq!(parse("true"), ParameterValue::Bool(true));
+        assert_eq!(
+            parse(r#""a\n\"b\"""#),
+            ParameterValue::String("a\n\"b\"".to_string())
+        );
+        assert_eq!(parse("[1, 0x2,]"), ParameterValue::VecBytes(vec![1, 2]));
+        assert_eq!(parse(r#"b"hi""#), ParameterValue::VecBytes(b"hi".to_vec()));
+
+        // Unsuffixed numbers take the type of the parameter
+        assert_eq!(
+            parse_literal("42", Some(&ParameterType::ULong)).unwrap(),
+            ParameterValue::ULong(42)
+        );
+        assert_eq!(
+            parse_literal("3", Some(&ParameterType::Double)).unwrap(),
+            ParameterValue::Double(3.0)
+        );
+        assert!(parse_literal("-1", Some(&ParameterType::UInt)).is_err());
+        assert!(parse_literal("42u64", Some(&ParameterType::Int)).is_err());
+        assert!(parse_literal("\"42\"", Some(&ParameterType::Int)).is_err());
+        assert!(parse_literal("[256]", None).is_err());
+    }
+
+    #[test]
+    fn memory_is_dumped_with_its_text() {
+        let dump = hex_dump(0x1000, b"hello, world!\0\x01\x02xyz");
+        assert_eq!(
+            dump,
+            "0x0000000000001000  68 65 6c 6c 6f 2c 20 77 6f 72 6c 64 21 00 01 02  |hello, world!...|\n\
+             0x0000000000001010  78 79 7a                                         |xyz|"
+        );
+    }
+}
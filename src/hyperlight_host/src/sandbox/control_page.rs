@@ -0,0 +1,325 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use hyperlight_common::layout::{
+    CONTROL_PAGE_HEARTBEAT, CONTROL_PAGE_PROGRESS, CONTROL_PAGE_SOFT_CANCEL,
+    SCRATCH_TOP_CONTROL_PAGE_OFFSET,
+};
+
+use crate::Result;
+use crate::mem::shared_mem::{HostSharedMemory, SharedMemory};
+
+/// A handle to the control page of a sandbox: a few atomic slots in
+/// guest memory that the host and the guest use to signal each other
+/// without a VM exit.
+///
+/// The slots are:
+///
+/// - a *progress* counter that the guest sets
+///   (`hyperlight_guest::control::set_progress()`),
+/// - a *soft-cancel* flag that the host sets with
+///   [`request_soft_cancel()`](Self::request_soft_cancel) and the guest
+///   polls (`hyperlight_guest::control::soft_cancel_requested()`), and
+/// - a *heartbeat* counter that the guest increments
+///   (`hyperlight_guest::control::heartbeat()`).
+///
+/// [`call_progress()`](Self::call_progress) reads both counters along
+/// with how long the running call has gone without the guest beating, so
+/// a supervisor can tell a slow guest from a hung one before killing it.
+///
+/// A `ControlPage` is obtained from
+/// [`MultiUseSandbox::control_page()`](crate::MultiUseSandbox::control_page),
+/// and may be cloned and used from any thread, including while a guest
+/// call is running. The slots are not cleared between calls, only when
+/// the sandbox is restored from a snapshot. In particular, a soft
+/// cancellation stays requested until
+/// [`clear_soft_cancel()`](Self::clear_soft_cancel) is called.
+///
+/// The guest can write any value to any of the slots, so the values read
+/// from the control page are only suitable for reporting, not for
+/// decisions the host relies on.
+#[derive(Clone, Debug)]
+pub struct ControlPage {
+    scratch: Arc<Mutex<HostSharedMemory>>,
+    call: Arc<Mutex<CallState>>,
+}
+
+/// The progress of the running guest call, as seen by
+/// [`ControlPage::call_progress()`], which tells a guest that is slow but
+/// still beating apart from one that has hung.
+///
+/// The host only sees the guest's beats when it reads the control page,
+/// so [`since_last_beat`](Self::since_last_beat) is measured from the
+/// first read that saw the last beat, and is only as precise as the
+/// interval the control page is polled at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CallProgress {
+    /// The value of the progress counter last set by the guest
+    pub progress: u64,
+    /// The number of heartbeats the guest has sent
+    pub heartbeats: u64,
+    /// How long the call has been running, or `None` if no call is
+    pub elapsed: Option<Duration>,
+    /// How long ago the progress counter or the heartbeats were last seen
+    /// to change during the call, or since the call started if they have
+    /// not, or `None` if no call is running
+    pub since_last_beat: Option<Duration>,
+}
+
+impl CallProgress {
+    /// Returns whether a guest call is running
+    pub fn in_call(&self) -> bool {
+        self.elapsed.is_some()
+    }
+
+    /// Returns whether a guest call is running and has not beaten nor
+    /// made progress for at least `timeout`
+    pub fn is_stalled(&self, timeout: Duration) -> bool {
+        self.since_last_beat.is_some_and(|idle| idle >= timeout)
+    }
+}
+
+/// The running call, and the beats of the guest last seen during it
+#[derive(Debug, Default)]
+struct CallState {
+    started: Option<Instant>,
+    last_seen: (u64, u64),
+    last_beat: Option<Instant>,
+}
+
+impl ControlPage {
+    pub(crate) fn new(scratch: HostSharedMemory) -> Self {
+        Self {
+            scratch: Arc::new(Mutex::new(scratch)),
+            call: Arc::new(Mutex::new(CallState::default())),
+        }
+    }
+
+    /// Point the control page at the sandbox's new scratch memory, after
+    /// it has been replaced
+    pub(crate) fn set_scratch(&self, scratch: HostSharedMemory) {
+        *self.lock() = scratch;
+    }
+
+    /// The value of the progress counter last set by the guest
+    pub fn progress(&self) -> Result<u64> {
+        self.with_slot(CONTROL_PAGE_PROGRESS, |slot| slot.load(Ordering::Acquire))
+    }
+
+    /// The number of heartbeats the guest has sent
+    pub fn heartbeat(&self) -> Result<u64> {
+        self.with_slot(CONTROL_PAGE_HEARTBEAT, |slot| slot.load(Ordering::Acquire))
+    }
+
+    /// The progress of the running guest call, including how long ago the
+    /// guest last beat. See [`CallProgress`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use std::thread;
+    /// # use std::time::Duration;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// let control = sandbox.control_page();
+    /// let interrupt = sandbox.interrupt_handle();
+    /// thread::spawn(move || loop {
+    ///     thread::sleep(Duration::from_millis(100));
+    ///     // A guest that stops beating for a second has hung
+    ///     if control.call_progress().is_ok_and(|p| p.is_stalled(Duration::from_secs(1))) {
+    ///         interrupt.kill();
+    ///     }
+    /// });
+    ///
+    /// let result = sandbox.call::<u64>("LongRunningJob", ());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_progress(&self) -> Result<CallProgress> {
+        let progress = self.progress()?;
+        let heartbeats = self.heartbeat()?;
+        Ok(self.observe(progress, heartbeats, Instant::now()))
+    }
+
+    /// Note that a guest call is starting
+    pub(crate) fn call_started(&self) {
+        // Beats are only counted from the start of the call
+        let last_seen = (
+            self.progress().unwrap_or_default(),
+            self.heartbeat().unwrap_or_default(),
+        );
+        let now = Instant::now();
+        *self.call_state() = CallState {
+            started: Some(now),
+            last_seen,
+            last_beat: Some(now),
+        };
+    }
+
+    /// Note that the guest call has returned
+    pub(crate) fn call_ended(&self) {
+        *self.call_state() = CallState::default();
+    }
+
+    /// The progress of the call given the counters read at `now`
+    fn observe(&self, progress: u64, heartbeats: u64, now: Instant) -> CallProgress {
+        let mut call = self.call_state();
+        if call.started.is_some() && call.last_seen != (progress, heartbeats) {
+            call.last_seen = (progress, heartbeats);
+            call.last_beat = Some(now);
+        }
+        CallProgress {
+            progress,
+            heartbeats,
+            elapsed: call.started.map(|started| now.duration_since(started)),
+            since_last_beat: call.last_beat.map(|beat| now.duration_since(beat)),
+        }
+    }
+
+    fn call_state(&self) -> MutexGuard<'_, CallState> {
+        self.call
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Ask the guest to stop what it is doing.
+    ///
+    /// Unlike [`InterruptHandle::kill()`](crate::hypervisor::InterruptHandle::kill),
+    /// this does not interrupt the vCPU: a guest that never checks the
+    /// flag is not affected, and one that does can return a result as
+    /// usual.
+    pub fn request_soft_cancel(&self) -> Result<()> {
+        self.with_slot(CONTROL_PAGE_SOFT_CANCEL, |slot| {
+            slot.store(1, Ordering::Release)
+        })
+    }
+
+    /// Withdraw a request made with [`request_soft_cancel()`](Self::request_soft_cancel)
+    pub fn clear_soft_cancel(&self) -> Result<()> {
+        self.with_slot(CONTROL_PAGE_SOFT_CANCEL, |slot| {
+            slot.store(0, Ordering::Release)
+        })
+    }
+
+    /// Returns whether a soft cancellation is currently requested
+    pub fn soft_cancel_requested(&self) -> Result<bool> {
+        self.with_slot(CONTROL_PAGE_SOFT_CANCEL, |slot| {
+            slot.load(Ordering::Acquire) != 0
+        })
+    }
+
+    fn with_slot<T>(&self, slot: u64, f: impl FnOnce(&AtomicU64) -> T) -> Result<T> {
+        let scratch = self.lock();
+        let offset = scratch.mem_size() - SCRATCH_TOP_CONTROL_PAGE_OFFSET as usize + slot as usize;
+        scratch.with_atomic_u64(offset, f)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HostSharedMemory> {
+        // The memory is only ever replaced wholesale, so a panic while
+        // holding the lock does not need to be propagated.
+        self.scratch
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_common::layout::{
+        CONTROL_PAGE_HEARTBEAT, CONTROL_PAGE_PROGRESS, CONTROL_PAGE_SOFT_CANCEL,
+        SCRATCH_TOP_CONTROL_PAGE_OFFSET,
+    };
+
+    use std::time::{Duration, Instant};
+
+    use super::ControlPage;
+    use crate::mem::shared_mem::{ExclusiveSharedMemory, SharedMemory};
+
+    #[test]
+    fn slots_are_at_documented_offsets() {
+        let (scratch, _) = ExclusiveSharedMemory::new(0x4000).unwrap().build();
+        let base = scratch.mem_size() - SCRATCH_TOP_CONTROL_PAGE_OFFSET as usize;
+        let page = ControlPage::new(scratch.clone());
+
+        scratch
+            .write::<u64>(base + CONTROL_PAGE_PROGRESS as usize, 42)
+            .unwrap();
+        scratch
+            .write::<u64>(base + CONTROL_PAGE_HEARTBEAT as usize, 7)
+            .unwrap();
+        assert_eq!(page.progress().unwrap(), 42);
+        assert_eq!(page.heartbeat().unwrap(), 7);
+
+        assert!(!page.soft_cancel_requested().unwrap());
+        page.clone().request_soft_cancel().unwrap();
+        assert!(page.soft_cancel_requested().unwrap());
+        assert_eq!(
+            scratch
+                .read::<u64>(base + CONTROL_PAGE_SOFT_CANCEL as usize)
+                .unwrap(),
+            1
+        );
+        page.clear_soft_cancel().unwrap();
+        assert!(!page.soft_cancel_requested().unwrap());
+    }
+
+    #[test]
+    fn follows_replaced_scratch() {
+        let (old, _) = ExclusiveSharedMemory::new(0x4000).unwrap().build();
+        let (new, _) = ExclusiveSharedMemory::new(0x8000).unwrap().build();
+        let page = ControlPage::new(old);
+
+        page.request_soft_cancel().unwrap();
+        page.set_scratch(new);
+        assert!(!page.soft_cancel_requested().unwrap());
+    }
+
+    #[test]
+    fn beats_are_timed_during_calls() {
+        let (scratch, _) = ExclusiveSharedMemory::new(0x4000).unwrap().build();
+        let page = ControlPage::new(scratch);
+        let start = Instant::now();
+        assert!(!page.observe(0, 0, start).in_call());
+
+        page.call_started();
+        let later = Instant::now() + Duration::from_secs(2);
+        let progress = page.observe(0, 0, later);
+        assert!(progress.in_call());
+        assert!(progress.is_stalled(Duration::from_secs(1)));
+
+        // A beat resets how long the guest has been idle
+        let progress = page.observe(0, 1, later);
+        assert_eq!(progress.heartbeats, 1);
+        assert_eq!(progress.since_last_beat, Some(Duration::ZERO));
+        assert!(!progress.is_stalled(Duration::from_secs(1)));
+        let progress = page.observe(5, 1, later + Duration::from_secs(3));
+        assert!(!progress.is_stalled(Duration::from_secs(1)));
+
+        page.call_ended();
+        let progress = page.observe(5, 2, later);
+        assert!(!progress.in_call());
+        assert!(!progress.is_stalled(Duration::ZERO));
+    }
+}
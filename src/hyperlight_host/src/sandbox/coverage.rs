@@ -0,0 +1,175 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::ops::Range;
+
+/// The name of the ELF section SanitizerCoverage places the guest's edge
+/// counters in
+pub(crate) const COUNTERS_SECTION: &str = "__sancov_cntrs";
+
+/// The edges of the guest's code that were hit since coverage was last
+/// taken with
+/// [`MultiUseSandbox::take_coverage()`](crate::MultiUseSandbox::take_coverage),
+/// for coverage-guided fuzzing of guest functions.
+///
+/// Coverage is only collected from guests built with SanitizerCoverage's
+/// inline 8-bit edge counters, for example with
+///
+/// ```text
+/// RUSTFLAGS="-Cpasses=sancov-module \
+///     -Cllvm-args=-sanitizer-coverage-level=3 \
+///     -Cllvm-args=-sanitizer-coverage-inline-8bit-counters"
+/// ```
+///
+/// and the `coverage` feature of `hyperlight-guest-bin`, which provides
+/// the runtime hooks the instrumentation calls. The guest increments a
+/// counter in its memory for each edge it takes, and the host reads the
+/// counters without the guest's involvement.
+///
+/// Each edge has an 8-bit hit count, which wraps, so an edge taken a
+/// multiple of 256 times is not seen as hit.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GuestCoverage {
+    counts: Vec<u8>,
+}
+
+impl GuestCoverage {
+    /// The number of instrumented edges in the guest
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Whether the guest has no instrumented edges
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// The hit count of each edge, as a coverage map in the form fuzzers
+    /// such as libFuzzer and AFL use
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.counts
+    }
+
+    /// Whether `edge` was hit
+    pub fn is_hit(&self, edge: usize) -> bool {
+        self.counts.get(edge).is_some_and(|&count| count != 0)
+    }
+
+    /// The edges that were hit
+    pub fn hit_edges(&self) -> impl Iterator<Item = usize> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count != 0)
+            .map(|(edge, _)| edge)
+    }
+
+    /// The number of edges that were hit
+    pub fn edges_hit(&self) -> usize {
+        self.hit_edges().count()
+    }
+
+    /// Add the hits of `other` to this coverage, and return the number of
+    /// edges `other` hit that this coverage had not, such as to tell
+    /// whether a fuzzing input found new code
+    pub fn merge(&mut self, other: &GuestCoverage) -> usize {
+        if self.counts.len() < other.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        let mut new_edges = 0;
+        for (count, &other) in self.counts.iter_mut().zip(&other.counts) {
+            if *count == 0 && other != 0 {
+                new_edges += 1;
+            }
+            *count = count.saturating_add(other);
+        }
+        new_edges
+    }
+}
+
+/// The guest's edge counters, and their values when coverage was last
+/// taken
+#[derive(Clone, Debug)]
+pub(crate) struct CoverageCounters {
+    /// Where the counters are in the guest's address space
+    gvas: Range<u64>,
+    baseline: Vec<u8>,
+}
+
+impl CoverageCounters {
+    pub(crate) fn new(gvas: Range<u64>) -> Self {
+        Self {
+            gvas,
+            baseline: Vec::new(),
+        }
+    }
+
+    /// Where the counters are in the guest's address space
+    pub(crate) fn gvas(&self) -> Range<u64> {
+        self.gvas.clone()
+    }
+
+    /// The coverage from the last counters taken to `counters`, which
+    /// coverage is taken from next
+    pub(crate) fn take(&mut self, counters: Vec<u8>) -> GuestCoverage {
+        let counts = counters
+            .iter()
+            .enumerate()
+            .map(|(edge, &count)| count.wrapping_sub(self.baseline.get(edge).copied().unwrap_or(0)))
+            .collect();
+        self.baseline = counters;
+        GuestCoverage { counts }
+    }
+
+    /// Take coverage from `counters` next, such as after they were reset
+    /// by restoring a snapshot
+    pub(crate) fn rebase(&mut self, counters: Vec<u8>) {
+        self.baseline = counters;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CoverageCounters, GuestCoverage};
+
+    #[test]
+    fn coverage_is_the_counters_since_last_taken() {
+        let mut counters = CoverageCounters::new(0x1000..0x1004);
+        let first = counters.take(vec![0, 3, 0, 255]);
+        assert_eq!(first.as_bytes(), [0, 3, 0, 255]);
+
+        // The last counter wraps
+        let second = counters.take(vec![1, 3, 0, 1]);
+        assert_eq!(second.hit_edges().collect::<Vec<_>>(), [0, 3]);
+        assert_eq!(second.as_bytes(), [1, 0, 0, 2]);
+
+        counters.rebase(vec![0, 0, 0, 0]);
+        assert_eq!(counters.take(vec![0, 0, 2, 0]).edges_hit(), 1);
+    }
+
+    #[test]
+    fn merging_counts_new_edges() {
+        let mut total = GuestCoverage::default();
+        let coverage = |counts: &[u8]| GuestCoverage {
+            counts: counts.to_vec(),
+        };
+        assert_eq!(total.merge(&coverage(&[1, 0, 200])), 2);
+        assert_eq!(total.merge(&coverage(&[0, 0, 100])), 0);
+        assert_eq!(total.merge(&coverage(&[0, 1, 0])), 1);
+        assert_eq!(total.as_bytes(), [1, 1, 255]);
+        assert!(total.is_hit(1) && !total.is_hit(3));
+    }
+}
@@ -0,0 +1,251 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use super::host_funcs::FunctionRegistry;
+use super::labels::SandboxLabels;
+use crate::{HyperlightError, etw, perfmon, usdt};
+
+/// The subscribers added with [`add_global_event_subscriber()`]
+static GLOBAL_SUBSCRIBERS: RwLock<Vec<Arc<dyn EventSubscriber>>> = RwLock::new(Vec::new());
+
+/// Receives the lifecycle events of sandboxes, so that pooling, billing
+/// and audit systems can follow sandboxes without wrapping every call to
+/// them.
+///
+/// A subscriber is added to a single sandbox with
+/// [`UninitializedSandbox::add_event_subscriber()`](crate::UninitializedSandbox::add_event_subscriber)
+/// or [`MultiUseSandbox::add_event_subscriber()`](crate::MultiUseSandbox::add_event_subscriber),
+/// and to every sandbox with [`add_global_event_subscriber()`]. Each
+/// event is given the labels of the sandbox it is about. Every method does
+/// nothing by default, so a subscriber only implements the events it is
+/// interested in.
+///
+/// Events are delivered synchronously, on the thread that caused them, so
+/// subscribers should return quickly. A subscriber must not call back
+/// into the sandbox the event is about.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::{EventSubscriber, SandboxLabels};
+/// struct Billing;
+///
+/// impl EventSubscriber for Billing {
+///     fn on_call_end(
+///         &self,
+///         labels: &SandboxLabels,
+///         _function_name: Option<&str>,
+///         duration: Duration,
+///         _error: Option<&hyperlight_host::HyperlightError>,
+///     ) {
+///         println!("bill {:?} for {duration:?}", labels.get("tenant"));
+///     }
+/// }
+///
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// sandbox.set_label("tenant", "contoso")?;
+/// sandbox.add_event_subscriber(Billing)?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait EventSubscriber: Send + Sync {
+    /// A sandbox was created. Only global subscribers see this event, as
+    /// a sandbox's own subscribers are added after it is created.
+    fn on_created(&self, _labels: &SandboxLabels) {}
+
+    /// A sandbox was evolved and its guest initialised
+    fn on_initialised(&self, _labels: &SandboxLabels) {}
+
+    /// A call into the guest is starting. A batch of calls has no
+    /// function name.
+    fn on_call_start(&self, _labels: &SandboxLabels, _function_name: Option<&str>) {}
+
+    /// A call into the guest ended after `duration`, with the error it
+    /// failed with, if any
+    fn on_call_end(
+        &self,
+        _labels: &SandboxLabels,
+        _function_name: Option<&str>,
+        _duration: Duration,
+        _error: Option<&HyperlightError>,
+    ) {
+    }
+
+    /// A call into the guest was killed by the host, through the
+    /// sandbox's interrupt handle or a cancellation token
+    fn on_killed(&self, _labels: &SandboxLabels, _function_name: Option<&str>) {}
+
+    /// A call into the guest failed in a way that poisoned the sandbox,
+    /// such as the guest aborting or faulting
+    fn on_crashed(&self, _labels: &SandboxLabels, _error: &HyperlightError) {}
+
+    /// An initialised sandbox was dropped
+    fn on_dropped(&self, _labels: &SandboxLabels) {}
+}
+
+/// Deliver every sandbox's events to `subscriber`, in addition to the
+/// subscribers of each sandbox
+pub fn add_global_event_subscriber(subscriber: impl EventSubscriber + 'static) {
+    GLOBAL_SUBSCRIBERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Arc::new(subscriber));
+}
+
+/// Deliver an event to the subscribers of the sandbox with `host_funcs`,
+/// if any, and then to the global subscribers. The subscribers are
+/// called without any lock held.
+pub(crate) fn emit(
+    host_funcs: Option<&Arc<Mutex<FunctionRegistry>>>,
+    event: impl Fn(&dyn EventSubscriber),
+) {
+    let mut subscribers = host_funcs
+        .and_then(|host_funcs| host_funcs.try_lock().ok())
+        .map(|registry| registry.event_subscribers().to_vec())
+        .unwrap_or_default();
+    subscribers.extend(
+        GLOBAL_SUBSCRIBERS
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .cloned(),
+    );
+    for subscriber in &subscribers {
+        event(subscriber.as_ref());
+    }
+}
+
+/// Delivers the events of an initialised sandbox, and its
+/// [`on_dropped()`](EventSubscriber::on_dropped) event when it is dropped.
+///
+/// The events of a sandbox are only delivered once it is active, so that
+/// the calls made while initialising it, and the sandbox being taken apart
+/// to be suspended or replaced, are not seen as events.
+pub(crate) struct SandboxEvents {
+    host_funcs: Arc<Mutex<FunctionRegistry>>,
+    labels: SandboxLabels,
+    /// The id of the sandbox, for the `sandbox__destroy` probe
+    id: u64,
+    active: bool,
+}
+
+impl SandboxEvents {
+    pub(crate) fn new(
+        host_funcs: Arc<Mutex<FunctionRegistry>>,
+        labels: SandboxLabels,
+        id: u64,
+    ) -> Self {
+        Self {
+            host_funcs,
+            labels,
+            id,
+            active: false,
+        }
+    }
+
+    /// Start or stop delivering the sandbox's events
+    pub(crate) fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    /// Change the id of the sandbox, when it takes on the identity of a
+    /// suspended sandbox
+    pub(crate) fn set_id(&mut self, id: u64) {
+        self.id = id;
+    }
+
+    pub(crate) fn emit(&self, event: impl Fn(&dyn EventSubscriber, &SandboxLabels)) {
+        if self.active {
+            emit(Some(&self.host_funcs), |subscriber| {
+                event(subscriber, &self.labels)
+            });
+        }
+    }
+}
+
+impl Drop for SandboxEvents {
+    fn drop(&mut self) {
+        if self.active {
+            usdt::probe!("sandbox__destroy", self.id);
+            etw::sandbox_destroy(self.id);
+            perfmon::sandbox_dropped();
+        }
+        self.emit(|subscriber, labels| subscriber.on_dropped(labels));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{EventSubscriber, SandboxEvents};
+    use crate::sandbox::SandboxLabels;
+    use crate::sandbox::host_funcs::FunctionRegistry;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl EventSubscriber for Arc<Recorder> {
+        fn on_call_start(&self, labels: &SandboxLabels, function_name: Option<&str>) {
+            self.0.lock().unwrap().push(format!(
+                "start {} {}",
+                labels.get("tenant").unwrap_or_default(),
+                function_name.unwrap_or_default()
+            ));
+        }
+
+        fn on_dropped(&self, _labels: &SandboxLabels) {
+            self.0.lock().unwrap().push("dropped".to_string());
+        }
+    }
+
+    #[test]
+    fn only_active_sandboxes_deliver_events() {
+        let recorder = Arc::new(Recorder::default());
+        let host_funcs = Arc::new(Mutex::new(FunctionRegistry::default()));
+        host_funcs
+            .lock()
+            .unwrap()
+            .add_event_subscriber(Arc::new(recorder.clone()));
+        let mut labels = SandboxLabels::default();
+        labels.set("tenant", "contoso").unwrap();
+
+        let mut events = SandboxEvents::new(host_funcs.clone(), labels.clone(), 0);
+        events.emit(|s, labels| s.on_call_start(labels, Some("Init")));
+        events.set_active(true);
+        events.emit(|s, labels| s.on_call_start(labels, Some("Echo")));
+        drop(events);
+
+        let mut suspended = SandboxEvents::new(host_funcs, labels, 1);
+        suspended.set_active(true);
+        suspended.set_active(false);
+        drop(suspended);
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec!["start contoso Echo".to_string(), "dropped".to_string()]
+        );
+    }
+}
@@ -0,0 +1,498 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::hypervisor::virtual_machine::{RunVcpuError, VmExit};
+use crate::metrics::{METRIC_VM_EXIT_DURATION, METRIC_VM_EXITS, METRIC_VM_EXITS_LABEL_TYPE};
+
+/// The number of buckets of an [`ExitLatencyHistogram`]
+const BUCKETS: usize = 24;
+
+/// Why the guest's vCPU exited to the host
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum VmExitKind {
+    /// A debug event, such as a breakpoint, while a debugger is attached
+    Debug,
+    /// The guest halted at the end of a call
+    Halt,
+    /// The guest wrote to an I/O port, such as to call a host function
+    IoOut,
+    /// The guest read memory that is not mapped
+    MmioRead,
+    /// The guest wrote memory that is not mapped or is read-only
+    MmioWrite,
+    /// The vCPU was interrupted by the host
+    Cancelled,
+    /// An exit Hyperlight does not handle
+    Unknown,
+    /// The vCPU must be run again, such as when its run was interrupted
+    /// by a signal
+    Retry,
+    /// Running the vCPU failed
+    Error,
+}
+
+impl VmExitKind {
+    /// Every kind of exit
+    pub const ALL: [VmExitKind; 9] = [
+        VmExitKind::Debug,
+        VmExitKind::Halt,
+        VmExitKind::IoOut,
+        VmExitKind::MmioRead,
+        VmExitKind::MmioWrite,
+        VmExitKind::Cancelled,
+        VmExitKind::Unknown,
+        VmExitKind::Retry,
+        VmExitKind::Error,
+    ];
+
+    /// The name of the kind of exit, as used in metrics and traces
+    pub fn name(self) -> &'static str {
+        match self {
+            VmExitKind::Debug => "debug",
+            VmExitKind::Halt => "halt",
+            VmExitKind::IoOut => "io_out",
+            VmExitKind::MmioRead => "mmio_read",
+            VmExitKind::MmioWrite => "mmio_write",
+            VmExitKind::Cancelled => "cancelled",
+            VmExitKind::Unknown => "unknown",
+            VmExitKind::Retry => "retry",
+            VmExitKind::Error => "error",
+        }
+    }
+
+    pub(crate) fn of(exit: &Result<VmExit, RunVcpuError>) -> Self {
+        match exit {
+            #[cfg(gdb)]
+            Ok(VmExit::Debug { .. }) => VmExitKind::Debug,
+            Ok(VmExit::Halt()) => VmExitKind::Halt,
+            Ok(VmExit::IoOut(..)) => VmExitKind::IoOut,
+            Ok(VmExit::MmioRead(_)) => VmExitKind::MmioRead,
+            Ok(VmExit::MmioWrite(_)) => VmExitKind::MmioWrite,
+            Ok(VmExit::Cancelled()) => VmExitKind::Cancelled,
+            Ok(VmExit::Unknown(_)) => VmExitKind::Unknown,
+            Ok(VmExit::Retry()) => VmExitKind::Retry,
+            Err(_) => VmExitKind::Error,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for VmExitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How long the guest ran before exiting, in buckets that double in
+/// size: the first holds runs shorter than 1µs, the next shorter than
+/// 2µs, then 4µs and so on, with the last holding every longer run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExitLatencyHistogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl ExitLatencyHistogram {
+    /// The number of runs recorded
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// How long the guest ran in total
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// The longest run
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// The mean length of a run, or `None` if none were recorded
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count != 0).then(|| self.total / self.count as u32)
+    }
+
+    /// The upper bound of each bucket and the number of runs in it. The
+    /// last bucket has no upper bound, and is reported as `Duration::MAX`.
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(bucket, &count)| (Self::upper_bound(bucket), count))
+    }
+
+    /// The length no more than `quantile` of the runs were longer than,
+    /// to the bucket it is in, or `None` if no runs were recorded. For
+    /// example `quantile(0.99)` is the 99th percentile.
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(Self::upper_bound(bucket).min(self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros();
+        let bucket = if micros == 0 {
+            0
+        } else {
+            (micros.ilog2() as usize + 1).min(BUCKETS - 1)
+        };
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.total += latency;
+        self.max = self.max.max(latency);
+    }
+
+    fn upper_bound(bucket: usize) -> Duration {
+        if bucket == BUCKETS - 1 {
+            Duration::MAX
+        } else {
+            Duration::from_micros(1 << bucket)
+        }
+    }
+}
+
+/// The counters the hypervisor keeps of a sandbox's vCPU and partition.
+///
+/// Unlike the exits of [`ExitStats`], which are those that reach
+/// Hyperlight, these include the exits the hypervisor handles itself,
+/// such as CPUID and MSR accesses, and the time it spent handling them.
+/// Only the Windows Hypervisor Platform keeps them, so on other
+/// hypervisors [`ExitStats::hypervisor()`] is `None`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HypervisorCounters {
+    /// How long the vCPU ran, in the guest and in the hypervisor
+    pub vcpu_runtime: Duration,
+    /// How much of the vCPU's runtime was spent in the hypervisor
+    pub hypervisor_runtime: Duration,
+    /// Exits for port I/O, such as host function calls
+    pub io_exits: u64,
+    /// Exits for the `hlt` instruction
+    pub halt_exits: u64,
+    /// Exits for the `cpuid` instruction
+    pub cpuid_exits: u64,
+    /// Exits for reading or writing MSRs
+    pub msr_exits: u64,
+    /// Exits for page faults, in the guest's page tables or in the
+    /// hypervisor's mapping of guest memory
+    pub page_fault_exits: u64,
+    /// Instructions the hypervisor emulated, such as for MMIO
+    pub emulated_instructions: u64,
+    /// Flushes of the guest's TLB, by `invlpg` or by hypercall
+    pub tlb_flushes: u64,
+    /// Hypercalls the guest made
+    pub hypercalls: u64,
+    /// Interrupts delivered to the guest
+    pub interrupts: u64,
+    /// The 4 KiB pages of guest memory the hypervisor has mapped now
+    pub mapped_4k_pages: u64,
+    /// The 2 MiB pages of guest memory the hypervisor has mapped now
+    pub mapped_2m_pages: u64,
+}
+
+impl HypervisorCounters {
+    /// The counts since `baseline` was read. The mapped pages are not
+    /// counts, so they are the current values.
+    fn since(&self, baseline: &Self) -> Self {
+        Self {
+            vcpu_runtime: self.vcpu_runtime.saturating_sub(baseline.vcpu_runtime),
+            hypervisor_runtime: self
+                .hypervisor_runtime
+                .saturating_sub(baseline.hypervisor_runtime),
+            io_exits: self.io_exits.saturating_sub(baseline.io_exits),
+            halt_exits: self.halt_exits.saturating_sub(baseline.halt_exits),
+            cpuid_exits: self.cpuid_exits.saturating_sub(baseline.cpuid_exits),
+            msr_exits: self.msr_exits.saturating_sub(baseline.msr_exits),
+            page_fault_exits: self
+                .page_fault_exits
+                .saturating_sub(baseline.page_fault_exits),
+            emulated_instructions: self
+                .emulated_instructions
+                .saturating_sub(baseline.emulated_instructions),
+            tlb_flushes: self.tlb_flushes.saturating_sub(baseline.tlb_flushes),
+            hypercalls: self.hypercalls.saturating_sub(baseline.hypercalls),
+            interrupts: self.interrupts.saturating_sub(baseline.interrupts),
+            mapped_4k_pages: self.mapped_4k_pages,
+            mapped_2m_pages: self.mapped_2m_pages,
+        }
+    }
+}
+
+impl fmt::Display for HypervisorCounters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "vCPU ran {:?}, {:?} in the hypervisor",
+            self.vcpu_runtime, self.hypervisor_runtime
+        )?;
+        for (name, count) in [
+            ("io", self.io_exits),
+            ("halt", self.halt_exits),
+            ("cpuid", self.cpuid_exits),
+            ("msr", self.msr_exits),
+            ("page_fault", self.page_fault_exits),
+            ("emulated", self.emulated_instructions),
+            ("tlb_flush", self.tlb_flushes),
+            ("hypercall", self.hypercalls),
+            ("interrupt", self.interrupts),
+        ] {
+            write!(f, "\n  {name:<10} {count:>10}")?;
+        }
+        write!(
+            f,
+            "\n  mapped     {} 4K, {} 2M pages",
+            self.mapped_4k_pages, self.mapped_2m_pages
+        )
+    }
+}
+
+/// The number of times a sandbox's vCPU exited to the host, by why it
+/// exited, and optionally how long the guest ran before each exit, for
+/// finding out why a guest is slow.
+///
+/// Exits are counted from when the sandbox is created, across guest calls
+/// and snapshot restores, and are also emitted as the
+/// `vm_exits_total` counter metric, labelled with the kind of exit.
+/// Latencies are only recorded once enabled with
+/// [`MultiUseSandbox::record_exit_latencies()`](crate::MultiUseSandbox::record_exit_latencies),
+/// as timing each run of the vCPU has a cost, and are then also emitted
+/// as the `vm_exit_latency_seconds` histogram metric. On Windows, the
+/// hypervisor's own counters are read after each guest call, see
+/// [`hypervisor()`](Self::hypervisor).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+/// # use hyperlight_host::sandbox::VmExitKind;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?.evolve()?;
+///
+/// sandbox.record_exit_latencies(true);
+/// sandbox.call::<String>("Echo", "hello".to_string())?;
+///
+/// let stats = sandbox.exit_stats();
+/// println!("{} host calls and prints", stats.count(VmExitKind::IoOut));
+/// if let Some(latency) = stats.latency(VmExitKind::IoOut) {
+///     println!("p99 {:?}", latency.quantile(0.99));
+/// }
+/// println!("{stats}");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExitStats {
+    counts: [u64; VmExitKind::ALL.len()],
+    latencies: Option<Box<[ExitLatencyHistogram; VmExitKind::ALL.len()]>>,
+    /// The hypervisor's counters as last read, since the VM was created
+    hypervisor: Option<HypervisorCounters>,
+    /// The hypervisor's counters when the stats were last reset
+    hypervisor_baseline: HypervisorCounters,
+}
+
+impl ExitStats {
+    /// The number of exits of `kind`
+    pub fn count(&self, kind: VmExitKind) -> u64 {
+        self.counts[kind.index()]
+    }
+
+    /// The number of exits of every kind
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The kinds of exits that happened and how many times each did
+    pub fn iter(&self) -> impl Iterator<Item = (VmExitKind, u64)> + '_ {
+        VmExitKind::ALL
+            .into_iter()
+            .map(|kind| (kind, self.count(kind)))
+            .filter(|&(_, count)| count != 0)
+    }
+
+    /// How long the guest ran before each exit of `kind`, or `None` if
+    /// latencies are not being recorded
+    pub fn latency(&self, kind: VmExitKind) -> Option<&ExitLatencyHistogram> {
+        self.latencies
+            .as_ref()
+            .map(|latencies| &latencies[kind.index()])
+    }
+
+    /// The hypervisor's counters, as of the end of the last guest call, or
+    /// `None` if the hypervisor doesn't keep any
+    pub fn hypervisor(&self) -> Option<HypervisorCounters> {
+        self.hypervisor
+            .map(|counters| counters.since(&self.hypervisor_baseline))
+    }
+
+    /// Set the hypervisor's counters, as read since the VM was created
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    pub(crate) fn update_hypervisor(&mut self, counters: HypervisorCounters) {
+        self.hypervisor = Some(counters);
+    }
+
+    /// Whether latencies are being recorded
+    pub(crate) fn records_latency(&self) -> bool {
+        self.latencies.is_some()
+    }
+
+    /// Start or stop recording latencies. Stopping drops those recorded.
+    pub(crate) fn set_records_latency(&mut self, enabled: bool) {
+        match (enabled, self.latencies.is_some()) {
+            (true, false) => self.latencies = Some(Box::default()),
+            (false, true) => self.latencies = None,
+            _ => {}
+        }
+    }
+
+    /// Clear the counts and latencies recorded so far
+    pub(crate) fn reset(&mut self) {
+        let records_latency = self.records_latency();
+        let hypervisor = self.hypervisor;
+        *self = Self::default();
+        self.set_records_latency(records_latency);
+        // The hypervisor's counters can't be reset, so those read so far
+        // are subtracted from those read later
+        self.hypervisor = hypervisor;
+        self.hypervisor_baseline = hypervisor.unwrap_or_default();
+    }
+
+    /// Record an exit of `kind`, after the guest ran for `latency` if it
+    /// was measured
+    pub(crate) fn record(&mut self, kind: VmExitKind, latency: Option<Duration>) {
+        self.counts[kind.index()] += 1;
+        metrics::counter!(METRIC_VM_EXITS, METRIC_VM_EXITS_LABEL_TYPE => kind.name()).increment(1);
+        if let (Some(latency), Some(latencies)) = (latency, self.latencies.as_mut()) {
+            latencies[kind.index()].record(latency);
+            metrics::histogram!(METRIC_VM_EXIT_DURATION, METRIC_VM_EXITS_LABEL_TYPE => kind.name())
+                .record(latency);
+        }
+    }
+}
+
+impl fmt::Display for ExitStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} VM exits", self.total())?;
+        for (kind, count) in self.iter() {
+            write!(f, "\n  {kind:<10} {count:>10}")?;
+            if let Some(latency) = self.latency(kind)
+                && let Some(mean) = latency.mean()
+            {
+                write!(
+                    f,
+                    "  mean {mean:?}, p99 <= {:?}, max {:?}",
+                    latency.quantile(0.99).unwrap_or_default(),
+                    latency.max()
+                )?;
+            }
+        }
+        if let Some(hypervisor) = self.hypervisor() {
+            write!(f, "\n{hypervisor}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ExitLatencyHistogram, ExitStats, HypervisorCounters, VmExitKind};
+
+    #[test]
+    fn exits_are_counted_by_kind() {
+        let mut stats = ExitStats::default();
+        stats.record(VmExitKind::IoOut, None);
+        stats.record(VmExitKind::IoOut, Some(Duration::from_micros(3)));
+        stats.record(VmExitKind::Halt, None);
+        assert_eq!(stats.count(VmExitKind::IoOut), 2);
+        assert_eq!(stats.total(), 3);
+        assert_eq!(
+            stats.iter().collect::<Vec<_>>(),
+            [(VmExitKind::Halt, 1), (VmExitKind::IoOut, 2)]
+        );
+        assert!(stats.latency(VmExitKind::IoOut).is_none());
+
+        stats.set_records_latency(true);
+        stats.record(VmExitKind::IoOut, Some(Duration::from_micros(3)));
+        assert_eq!(stats.latency(VmExitKind::IoOut).unwrap().count(), 1);
+
+        stats.reset();
+        assert_eq!(stats.total(), 0);
+        assert!(stats.records_latency());
+        assert_eq!(stats.latency(VmExitKind::IoOut).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn hypervisor_counters_are_counted_from_the_last_reset() {
+        let mut stats = ExitStats::default();
+        assert_eq!(stats.hypervisor(), None);
+        stats.update_hypervisor(HypervisorCounters {
+            io_exits: 5,
+            mapped_4k_pages: 16,
+            ..Default::default()
+        });
+        stats.reset();
+        stats.update_hypervisor(HypervisorCounters {
+            io_exits: 8,
+            mapped_4k_pages: 20,
+            ..Default::default()
+        });
+        let hypervisor = stats.hypervisor().unwrap();
+        assert_eq!(hypervisor.io_exits, 3);
+        assert_eq!(hypervisor.mapped_4k_pages, 20);
+    }
+
+    #[test]
+    fn latencies_are_bucketed_by_powers_of_two() {
+        let mut histogram = ExitLatencyHistogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+        for micros in [0, 1, 3, 3, 100] {
+            histogram.record(Duration::from_micros(micros));
+        }
+        let buckets: Vec<_> = histogram.buckets().take(8).map(|(_, n)| n).collect();
+        assert_eq!(buckets, [1, 1, 2, 0, 0, 0, 0, 1]);
+        assert_eq!(histogram.mean(), Some(Duration::from_nanos(21_400)));
+        assert_eq!(histogram.quantile(0.5), Some(Duration::from_micros(4)));
+        assert_eq!(histogram.quantile(1.0), Some(Duration::from_micros(100)));
+        assert_eq!(
+            histogram.buckets().last(),
+            Some((Duration::MAX, 0)),
+            "the last bucket is unbounded"
+        );
+    }
+}
@@ -0,0 +1,470 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::HashSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arbitrary::Unstructured;
+use hyperlight_common::flatbuffer_wrappers::function_types::{
+    ParameterType, ParameterValue, ReturnType,
+};
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+use tracing::{Span, instrument};
+
+use super::coverage::GuestCoverage;
+use super::snapshot::Snapshot;
+use crate::{HyperlightError, MultiUseSandbox, Result};
+
+/// The longest input [`GuestFuzzer::fuzz()`] generates by default
+const DEFAULT_MAX_INPUT_LEN: usize = 4096;
+
+/// Bytes that are often at the edges of what guest code handles
+const INTERESTING_BYTES: [u8; 6] = [0x00, 0x01, 0x7f, 0x80, 0xfe, 0xff];
+
+/// Decode the arguments of a guest function with the parameter types
+/// `params` from the fuzzing input `data`
+pub(crate) fn decode_args(
+    params: &[ParameterType],
+    data: &[u8],
+) -> arbitrary::Result<Vec<ParameterValue>> {
+    let mut u = Unstructured::new(data);
+    params
+        .iter()
+        .map(|param| {
+            Ok(match param {
+                ParameterType::Int => ParameterValue::Int(u.arbitrary()?),
+                ParameterType::UInt => ParameterValue::UInt(u.arbitrary()?),
+                ParameterType::Long => ParameterValue::Long(u.arbitrary()?),
+                ParameterType::ULong => ParameterValue::ULong(u.arbitrary()?),
+                ParameterType::Float => ParameterValue::Float(u.arbitrary()?),
+                ParameterType::Double => ParameterValue::Double(u.arbitrary()?),
+                ParameterType::String => ParameterValue::String(u.arbitrary()?),
+                ParameterType::Bool => ParameterValue::Bool(u.arbitrary()?),
+                ParameterType::VecBytes => ParameterValue::VecBytes(u.arbitrary()?),
+                ParameterType::Value => ParameterValue::Value(u.arbitrary()?),
+                ParameterType::VecFloat => ParameterValue::VecFloat(u.arbitrary()?),
+                ParameterType::VecDouble => ParameterValue::VecDouble(u.arbitrary()?),
+            })
+        })
+        .collect()
+}
+
+/// Apply a few random byte-level mutations to `input`, keeping it no
+/// longer than `max_len`
+fn mutate(rng: &mut StdRng, input: &mut Vec<u8>, max_len: usize) {
+    for _ in 0..rng.random_range(1..=4) {
+        match rng.random_range(0..5) {
+            // Flip a bit
+            0 if !input.is_empty() => {
+                let i = rng.random_range(0..input.len());
+                input[i] ^= 1 << rng.random_range(0..8);
+            }
+            // Replace a byte with an interesting one
+            1 if !input.is_empty() => {
+                let i = rng.random_range(0..input.len());
+                input[i] = INTERESTING_BYTES[rng.random_range(0..INTERESTING_BYTES.len())];
+            }
+            // Insert a random byte
+            2 if input.len() < max_len => {
+                let i = rng.random_range(0..=input.len());
+                input.insert(i, rng.random());
+            }
+            // Remove a byte
+            3 if !input.is_empty() => {
+                let i = rng.random_range(0..input.len());
+                input.remove(i);
+            }
+            // Duplicate a run of bytes
+            4 if !input.is_empty() && input.len() < max_len => {
+                let start = rng.random_range(0..input.len());
+                let len = rng
+                    .random_range(1..=input.len() - start)
+                    .min(max_len - input.len());
+                let run = input[start..start + len].to_vec();
+                let at = rng.random_range(0..=input.len());
+                input.splice(at..at, run);
+            }
+            _ => input.push(rng.random()),
+        }
+    }
+    input.truncate(max_len);
+}
+
+/// A fuzzing input that made the guest crash
+#[derive(Debug)]
+pub struct FuzzCrash {
+    /// The raw input, which reproduces the crash when passed to
+    /// [`GuestFuzzer::run_input()`]
+    pub input: Vec<u8>,
+    /// The arguments the input decoded to
+    pub args: Vec<ParameterValue>,
+    /// The error the guest function call failed with
+    pub error: HyperlightError,
+    /// Where the input was written, if the fuzzer has a crash directory
+    pub input_path: Option<PathBuf>,
+    /// Where a core dump of the crashed guest was written, if the fuzzer
+    /// has a crash directory and the sandbox allows core dumps
+    pub dump_path: Option<PathBuf>,
+}
+
+/// What running one fuzzing input did
+#[derive(Debug)]
+pub enum FuzzOutcome {
+    /// The guest function returned, or failed without crashing the guest,
+    /// and hit `new_edges` edges of the guest's code no earlier input had
+    Passed {
+        /// The number of newly hit edges, which is always 0 when the guest
+        /// is not instrumented for coverage
+        new_edges: usize,
+    },
+    /// The guest crashed, leaving the sandbox poisoned until it was
+    /// restored
+    Crashed(FuzzCrash),
+    /// The input did not decode to arguments of the function
+    Rejected,
+}
+
+/// The results of a [`GuestFuzzer::fuzz()`] campaign
+#[derive(Debug, Default)]
+pub struct FuzzReport {
+    /// The number of inputs that were run
+    pub executions: usize,
+    /// The crashes found, one for each distinct error
+    pub crashes: Vec<FuzzCrash>,
+    /// The number of inputs in the corpus when the campaign ended
+    pub corpus_len: usize,
+    /// The number of edges of the guest's code hit by any input
+    pub edges_hit: usize,
+}
+
+/// Fuzzes a single guest function of a sandbox, generating and mutating
+/// its arguments from the function's parameter types, and restoring the
+/// sandbox to a snapshot between inputs so that every input runs against
+/// the same guest state.
+///
+/// A guest crash is any failed call that poisons the sandbox, such as a
+/// panic, abort or memory access violation. Errors the guest function
+/// returns normally are not crashes. When the guest is instrumented for
+/// coverage (see [`GuestCoverage`]), inputs that hit new edges are kept in
+/// the corpus to mutate further.
+///
+/// Calls are not timed out, so a guest function that never returns stalls
+/// the fuzzer; use the sandbox's
+/// [`InterruptHandle`](crate::hypervisor::InterruptHandle) to stop one.
+///
+/// The fuzzer can drive itself with [`fuzz()`](Self::fuzz), or be driven by
+/// `cargo fuzz` by passing each input to [`run_input()`](Self::run_input):
+///
+/// ```no_run
+/// # use hyperlight_host::func::{ParameterType, ReturnType};
+/// # use hyperlight_host::sandbox::fuzzing::{FuzzOutcome, GuestFuzzer};
+/// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let sandbox: MultiUseSandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?.evolve()?;
+///
+/// let mut fuzzer = GuestFuzzer::new(
+///     sandbox,
+///     "ParseRequest",
+///     vec![ParameterType::String, ParameterType::Int],
+///     ReturnType::Int,
+/// )?
+/// .with_crash_dir("crashes");
+///
+/// let report = fuzzer.fuzz(10_000)?;
+/// for crash in &report.crashes {
+///     println!("{:?} crashed the guest: {}", crash.args, crash.error);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct GuestFuzzer {
+    sandbox: MultiUseSandbox,
+    /// The state every input runs from
+    snapshot: Arc<Snapshot>,
+    function: String,
+    params: Vec<ParameterType>,
+    return_type: ReturnType,
+    rng: StdRng,
+    max_input_len: usize,
+    crash_dir: Option<PathBuf>,
+    /// The inputs that are mutated to make new ones
+    corpus: Vec<Vec<u8>>,
+    /// The coverage of every input run, if the guest is instrumented
+    coverage: Option<GuestCoverage>,
+    executions: usize,
+}
+
+impl std::fmt::Debug for GuestFuzzer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GuestFuzzer")
+            .field("function", &self.function)
+            .field("params", &self.params)
+            .field("return_type", &self.return_type)
+            .field("corpus_len", &self.corpus.len())
+            .field("executions", &self.executions)
+            .finish_non_exhaustive()
+    }
+}
+
+impl GuestFuzzer {
+    /// Create a fuzzer for the guest function `function` of `sandbox`,
+    /// which takes parameters of the types `params` and returns
+    /// `return_type`.
+    ///
+    /// Every input runs from a snapshot of the sandbox taken now.
+    pub fn new(
+        mut sandbox: MultiUseSandbox,
+        function: &str,
+        params: Vec<ParameterType>,
+        return_type: ReturnType,
+    ) -> Result<Self> {
+        let snapshot = sandbox.snapshot()?;
+        let coverage = sandbox.has_coverage().then(GuestCoverage::default);
+        Ok(Self {
+            sandbox,
+            snapshot,
+            function: function.to_string(),
+            params,
+            return_type,
+            rng: StdRng::from_rng(&mut rand::rng()),
+            max_input_len: DEFAULT_MAX_INPUT_LEN,
+            crash_dir: None,
+            corpus: Vec::new(),
+            coverage,
+            executions: 0,
+        })
+    }
+
+    /// Generate inputs from `seed`, so that campaigns can be repeated
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Generate inputs of at most `max_input_len` bytes
+    pub fn with_max_input_len(mut self, max_input_len: usize) -> Self {
+        self.max_input_len = max_input_len;
+        self
+    }
+
+    /// Write the input of each crash, and a core dump of the crashed guest
+    /// if the sandbox allows them, to `dir`
+    pub fn with_crash_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.crash_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Add `input` to the corpus that [`fuzz()`](Self::fuzz) mutates
+    pub fn add_seed(&mut self, input: impl Into<Vec<u8>>) {
+        self.corpus.push(input.into());
+    }
+
+    /// The number of inputs in the corpus
+    pub fn corpus_len(&self) -> usize {
+        self.corpus.len()
+    }
+
+    /// The number of inputs that have been run
+    pub fn executions(&self) -> usize {
+        self.executions
+    }
+
+    /// The coverage of every input run so far, if the guest is
+    /// instrumented for coverage
+    pub fn coverage(&self) -> Option<&GuestCoverage> {
+        self.coverage.as_ref()
+    }
+
+    /// The sandbox being fuzzed
+    pub fn sandbox(&mut self) -> &mut MultiUseSandbox {
+        &mut self.sandbox
+    }
+
+    /// Stop fuzzing and return the sandbox, restored to the snapshot the
+    /// fuzzer started from
+    pub fn into_sandbox(self) -> MultiUseSandbox {
+        self.sandbox
+    }
+
+    /// Run `iterations` inputs, each either generated at random or mutated
+    /// from one in the corpus.
+    ///
+    /// Crashes with an error already found are left out of the report.
+    /// Returns an error if the sandbox could not be restored after an
+    /// input.
+    #[instrument(err(Debug), skip(self), parent = Span::current())]
+    pub fn fuzz(&mut self, iterations: usize) -> Result<FuzzReport> {
+        let mut report = FuzzReport::default();
+        let mut errors = HashSet::new();
+        for _ in 0..iterations {
+            let input = self.next_input();
+            match self.run_input(&input)? {
+                FuzzOutcome::Passed { new_edges } if new_edges > 0 => self.corpus.push(input),
+                FuzzOutcome::Crashed(crash) => {
+                    if errors.insert(crash.error.to_string()) {
+                        report.crashes.push(crash);
+                    }
+                }
+                _ => {}
+            }
+            report.executions += 1;
+        }
+        report.corpus_len = self.corpus.len();
+        report.edges_hit = self.coverage.as_ref().map_or(0, GuestCoverage::edges_hit);
+        Ok(report)
+    }
+
+    /// Run the guest function with the arguments decoded from `input`,
+    /// then restore the sandbox.
+    ///
+    /// This is the entry point for external fuzzing engines such as
+    /// `cargo fuzz`, which supply and mutate the inputs themselves.
+    /// Returns an error if the sandbox could not be restored.
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn run_input(&mut self, input: &[u8]) -> Result<FuzzOutcome> {
+        let Ok(args) = decode_args(&self.params, input) else {
+            return Ok(FuzzOutcome::Rejected);
+        };
+        self.executions += 1;
+
+        let result = self.sandbox.call_type_erased_guest_function_by_name(
+            &self.function,
+            self.return_type,
+            args.clone(),
+        );
+        let new_edges = match self.coverage.as_mut() {
+            Some(total) => total.merge(&self.sandbox.take_coverage()?),
+            None => 0,
+        };
+        let outcome = match result {
+            Err(error) if self.sandbox.poisoned() => {
+                FuzzOutcome::Crashed(self.report_crash(input, args, error))
+            }
+            _ => FuzzOutcome::Passed { new_edges },
+        };
+
+        self.sandbox.restore(self.snapshot.clone())?;
+        Ok(outcome)
+    }
+
+    /// Pick the next input to run
+    fn next_input(&mut self) -> Vec<u8> {
+        if self.corpus.is_empty() || self.rng.random_ratio(1, 8) {
+            let len = self.rng.random_range(0..=self.max_input_len);
+            let mut input = vec![0; len];
+            self.rng.fill(&mut input[..]);
+            input
+        } else {
+            let mut input = self.corpus[self.rng.random_range(0..self.corpus.len())].clone();
+            mutate(&mut self.rng, &mut input, self.max_input_len);
+            input
+        }
+    }
+
+    /// Record a crash, writing its input and a core dump of the crashed
+    /// guest to the crash directory
+    fn report_crash(
+        &mut self,
+        input: &[u8],
+        args: Vec<ParameterValue>,
+        error: HyperlightError,
+    ) -> FuzzCrash {
+        let mut crash = FuzzCrash {
+            input: input.to_vec(),
+            args,
+            error,
+            input_path: None,
+            dump_path: None,
+        };
+        let Some(dir) = self.crash_dir.as_ref() else {
+            return crash;
+        };
+
+        let mut hasher = DefaultHasher::new();
+        input.hash(&mut hasher);
+        let name = format!("crash-{:016x}", hasher.finish());
+        let input_path = dir.join(&name);
+        match std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&input_path, input)) {
+            Ok(()) => crash.input_path = Some(input_path),
+            Err(e) => tracing::warn!("Failed to write crashing input to {}: {e}", dir.display()),
+        }
+
+        #[cfg(crashdump)]
+        {
+            let dump_path = dir.join(format!("{name}.core"));
+            match self.sandbox.dump_core(&dump_path) {
+                Ok(()) => crash.dump_path = Some(dump_path),
+                Err(e) => tracing::warn!("Failed to write core dump of crash {name}: {e}"),
+            }
+        }
+        crash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterType, ParameterValue};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::{decode_args, mutate};
+
+    #[test]
+    fn inputs_decode_to_the_parameter_types() {
+        let params = [
+            ParameterType::Int,
+            ParameterType::Bool,
+            ParameterType::String,
+            ParameterType::VecBytes,
+        ];
+        let args = decode_args(&params, &[1, 0, 0, 0, 1, 3, b'a', b'b']).unwrap();
+        assert_eq!(args.len(), params.len());
+        assert_eq!(args[0], ParameterValue::Int(1));
+        assert_eq!(args[1], ParameterValue::Bool(true));
+        assert!(matches!(args[2], ParameterValue::String(_)));
+        assert!(matches!(args[3], ParameterValue::VecBytes(_)));
+
+        // Short inputs decode to default values rather than being rejected
+        assert_eq!(
+            decode_args(&[ParameterType::ULong], &[]).unwrap(),
+            [ParameterValue::ULong(0)]
+        );
+    }
+
+    #[test]
+    fn mutation_respects_the_max_input_len() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut input = vec![0x41; 8];
+        let mut changed = false;
+        for _ in 0..1000 {
+            let before = input.clone();
+            mutate(&mut rng, &mut input, 16);
+            assert!(input.len() <= 16);
+            changed |= input != before;
+        }
+        assert!(changed);
+
+        let mut empty = Vec::new();
+        mutate(&mut rng, &mut empty, 0);
+        assert!(empty.is_empty());
+    }
+}
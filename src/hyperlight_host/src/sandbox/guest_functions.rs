@@ -0,0 +1,149 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt;
+
+use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterType, ReturnType};
+use hyperlight_common::flatbuffer_wrappers::host_function_definition::HostFunctionDefinition;
+
+use crate::{HyperlightError, Result};
+
+/// The name and signature of a function the guest has registered, as
+/// returned by
+/// [`MultiUseSandbox::guest_functions()`](crate::MultiUseSandbox::guest_functions).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestFunction {
+    /// The name the function is called by
+    pub name: String,
+    /// The types of the function's parameters
+    pub parameter_types: Vec<ParameterType>,
+    /// The type of the function's return value
+    pub return_type: ReturnType,
+}
+
+impl From<HostFunctionDefinition> for GuestFunction {
+    fn from(definition: HostFunctionDefinition) -> Self {
+        Self {
+            name: definition.function_name,
+            parameter_types: definition.parameter_types.unwrap_or_default(),
+            return_type: definition.return_type,
+        }
+    }
+}
+
+impl fmt::Display for GuestFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(", self.name)?;
+        for (i, ty) in self.parameter_types.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{ty:?}")?;
+        }
+        write!(f, ") -> {:?}", self.return_type)
+    }
+}
+
+/// Check that the guest registered each of the `required` functions,
+/// failing with a description of every one that it is missing or that has
+/// a different signature
+pub(super) fn check_interface(
+    required: &[GuestFunction],
+    registered: Option<&[GuestFunction]>,
+) -> Result<()> {
+    if required.is_empty() {
+        return Ok(());
+    }
+    let Some(registered) = registered else {
+        return Err(HyperlightError::GuestInterfaceMismatch(vec![
+            "the guest did not report its functions".to_string(),
+        ]));
+    };
+    let mismatches: Vec<String> = required
+        .iter()
+        .filter_map(|req| match registered.iter().find(|f| f.name == req.name) {
+            None => Some(format!("missing {req}")),
+            Some(found) if found != req => Some(format!("expected {req} but found {found}")),
+            Some(_) => None,
+        })
+        .collect();
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(HyperlightError::GuestInterfaceMismatch(mismatches))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterType, ReturnType};
+
+    use super::{GuestFunction, check_interface};
+    use crate::HyperlightError;
+
+    fn function(
+        name: &str,
+        parameter_types: &[ParameterType],
+        return_type: ReturnType,
+    ) -> GuestFunction {
+        GuestFunction {
+            name: name.to_string(),
+            parameter_types: parameter_types.to_vec(),
+            return_type,
+        }
+    }
+
+    #[test]
+    fn check_interface_lists_every_mismatch() {
+        let registered = [
+            function(
+                "Add",
+                &[ParameterType::Int, ParameterType::Int],
+                ReturnType::Int,
+            ),
+            function("Echo", &[ParameterType::String], ReturnType::String),
+        ];
+        let add = function(
+            "Add",
+            &[ParameterType::Int, ParameterType::Int],
+            ReturnType::Int,
+        );
+        let required = [
+            add.clone(),
+            function("Echo", &[ParameterType::VecBytes], ReturnType::String),
+            function("Missing", &[], ReturnType::Void),
+        ];
+        assert!(check_interface(&required[..1], Some(&registered)).is_ok());
+        assert!(check_interface(&[], None).is_ok());
+
+        let err = check_interface(&required, Some(&registered)).unwrap_err();
+        let HyperlightError::GuestInterfaceMismatch(mismatches) = err else {
+            panic!("unexpected error {err:?}");
+        };
+        assert_eq!(
+            mismatches,
+            [
+                "expected Echo(VecBytes) -> String but found Echo(String) -> String",
+                "missing Missing() -> Void",
+            ]
+        );
+
+        assert!(matches!(
+            check_interface(&[add], None),
+            Err(HyperlightError::GuestInterfaceMismatch(_))
+        ));
+    }
+}
@@ -0,0 +1,224 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex, OnceLock};
+
+use hyperlight_common::flatbuffer_wrappers::guest_log_data::GuestLogData;
+use tracing_core::callsite::{Callsite, Identifier};
+use tracing_core::field::{FieldSet, Value};
+use tracing_core::metadata::Kind;
+use tracing_core::subscriber::Interest;
+use tracing_core::{Event, Level, Metadata, dispatcher};
+
+/// The target of guest log records, which prefixes the target a record
+/// gives so that filters on it still match all guest logs
+pub(crate) const GUEST_LOG_TARGET: &str = "hyperlight_guest";
+
+/// The fields every event has before the record's own
+const RESERVED_FIELDS: [&str; 4] = ["message", "log.module_path", "log.file", "log.line"];
+
+/// How many distinct combinations of level, target and field names get a
+/// callsite. Callsites live for the rest of the process, so a guest that
+/// makes up new field names cannot make the host allocate without bound.
+const MAX_CALLSITES: usize = 1024;
+
+/// A callsite for the records of one level, target and set of field
+/// names, made when a guest first logs such a record
+struct GuestLogCallsite {
+    metadata: OnceLock<Metadata<'static>>,
+}
+
+impl Callsite for GuestLogCallsite {
+    fn set_interest(&self, _: Interest) {}
+
+    fn metadata(&self) -> &Metadata<'_> {
+        // The metadata is set before the callsite is registered, so the
+        // fallback is never used
+        self.metadata.get().unwrap_or_else(|| {
+            tracing::callsite!(
+                name: "guest log",
+                kind: Kind::EVENT,
+                target: GUEST_LOG_TARGET,
+                level: Level::INFO,
+                fields: message
+            )
+            .metadata()
+        })
+    }
+}
+
+type CallsiteKey = (Level, String, Vec<String>);
+
+static CALLSITES: LazyLock<Mutex<HashMap<CallsiteKey, &'static GuestLogCallsite>>> =
+    LazyLock::new(Default::default);
+
+fn new_callsite(level: Level, target: String, names: Vec<String>) -> &'static GuestLogCallsite {
+    let callsite: &'static GuestLogCallsite = Box::leak(Box::new(GuestLogCallsite {
+        metadata: OnceLock::new(),
+    }));
+    let names: Vec<&'static str> = RESERVED_FIELDS
+        .into_iter()
+        .chain(names.into_iter().map(|name| &*name.leak()))
+        .collect();
+    let _ = callsite.metadata.set(Metadata::new(
+        "guest log",
+        target.leak(),
+        level,
+        None,
+        None,
+        None,
+        FieldSet::new(names.leak(), Identifier(callsite)),
+        Kind::EVENT,
+    ));
+    tracing_core::callsite::register(callsite);
+    callsite
+}
+
+/// The callsite for `key`, or `None` if there are too many callsites
+fn callsite(key: CallsiteKey) -> Option<&'static GuestLogCallsite> {
+    let mut callsites = CALLSITES.lock().ok()?;
+    if let Some(callsite) = callsites.get(&key) {
+        return Some(*callsite);
+    }
+    if callsites.len() >= MAX_CALLSITES {
+        return None;
+    }
+    let callsite = new_callsite(key.0, key.1.clone(), key.2.clone());
+    callsites.insert(key, callsite);
+    Some(callsite)
+}
+
+/// The target of the event for `log_data`
+pub(crate) fn target(log_data: &GuestLogData) -> String {
+    if log_data.target.is_empty() {
+        GUEST_LOG_TARGET.to_string()
+    } else {
+        format!("{GUEST_LOG_TARGET}::{}", log_data.target)
+    }
+}
+
+/// The fields of `log_data` that become fields of its event: the first of
+/// each name, other than the names every event has
+fn event_fields(log_data: &GuestLogData) -> Vec<(&str, &str)> {
+    let mut fields: Vec<(&str, &str)> = Vec::with_capacity(log_data.fields.len());
+    for (key, value) in &log_data.fields {
+        if !RESERVED_FIELDS.contains(&key.as_str()) && !fields.iter().any(|(k, _)| k == key) {
+            fields.push((key, value));
+        }
+    }
+    fields
+}
+
+/// Dispatch `log_data` as a `tracing` event whose target is the record's
+/// target under [`GUEST_LOG_TARGET`], and whose fields include the
+/// record's fields.
+///
+/// Returns `false`, without dispatching anything, if there are too many
+/// distinct kinds of record to give this one its own callsite.
+pub(crate) fn dispatch(log_data: &GuestLogData, level: Level) -> bool {
+    let fields = event_fields(log_data);
+    let key = (
+        level,
+        target(log_data),
+        fields.iter().map(|(key, _)| key.to_string()).collect(),
+    );
+    let Some(callsite) = callsite(key) else {
+        return false;
+    };
+    let metadata = callsite.metadata();
+    if !dispatcher::get_default(|dispatch| dispatch.enabled(metadata)) {
+        return true;
+    }
+
+    let message = format_args!("{}", log_data.message);
+    let reserved: [&dyn Value; 4] = [
+        &message,
+        &log_data.source.as_str(),
+        &log_data.source_file.as_str(),
+        &log_data.line,
+    ];
+    let values: Vec<Option<&dyn Value>> = reserved
+        .into_iter()
+        .chain(fields.iter().map(|(_, value)| value as &dyn Value))
+        .map(Some)
+        .collect();
+    // The values are in the order of the callsite's field names
+    Event::dispatch(metadata, &metadata.fields().value_set_all(&values));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_common::flatbuffer_wrappers::guest_log_data::GuestLogData;
+    use hyperlight_common::flatbuffer_wrappers::guest_log_level::LogLevel;
+    use hyperlight_testing::tracing_subscriber::TracingSubscriber;
+    use tracing_core::Level;
+
+    use super::{dispatch, target};
+
+    fn log_data(target: &str, fields: &[(&str, &str)]) -> GuestLogData {
+        GuestLogData::new(
+            "request served".to_string(),
+            "guest::server".to_string(),
+            LogLevel::Information,
+            "guest::server".to_string(),
+            "server.rs".to_string(),
+            42,
+        )
+        .with_target(target.to_string())
+        .with_fields(
+            fields
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn targets_are_under_the_guest_target() {
+        assert_eq!(target(&log_data("", &[])), "hyperlight_guest");
+        assert_eq!(target(&log_data("http", &[])), "hyperlight_guest::http");
+    }
+
+    #[test]
+    fn fields_become_event_fields() {
+        let subscriber = TracingSubscriber::new(Level::TRACE);
+        tracing::subscriber::with_default(subscriber.clone(), || {
+            let data = log_data(
+                "http",
+                &[
+                    ("status", "200"),
+                    ("path", "/"),
+                    ("status", "500"),
+                    ("message", "x"),
+                ],
+            );
+            assert!(dispatch(&data, Level::INFO));
+        });
+
+        let events = subscriber.get_events();
+        assert_eq!(events.len(), 1);
+        let event = &events[0]["event"];
+        assert_eq!(event["metadata"]["target"], "hyperlight_guest::http");
+        assert_eq!(event["metadata"]["level"], "INFO");
+        assert_eq!(event["message"], "request served");
+        assert_eq!(event["status"], "200");
+        assert_eq!(event["path"], "/");
+        assert_eq!(event["log.file"], "server.rs");
+        assert_eq!(event["log.line"], 42);
+    }
+}
@@ -14,32 +14,107 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::collections::HashMap;
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
 use std::io::{IsTerminal, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
 
 use hyperlight_common::flatbuffer_wrappers::function_types::{
     ParameterType, ParameterValue, ReturnType, ReturnValue,
 };
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
 use hyperlight_common::flatbuffer_wrappers::host_function_definition::HostFunctionDefinition;
 use hyperlight_common::flatbuffer_wrappers::host_function_details::HostFunctionDetails;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 use tracing::{Span, instrument};
 
-use crate::HyperlightError::HostFunctionNotFound;
-use crate::Result;
-use crate::func::host_functions::TypeErasedHostFunction;
+use super::Capabilities;
+use super::audit::{AuditRecord, AuditSink};
+use super::cancellation::CancellationStatus;
+use super::events::EventSubscriber;
+use super::input::InputSource;
+use super::interceptor::{HostCall, Interceptor};
+use super::labels::SandboxLabels;
+#[cfg(landlock)]
+use super::landlock::FilesystemRestriction;
+use super::namespace::namespaces;
+use super::outcall_trace::OutcallTracer;
+use super::output::OutputSink;
+use super::rate_limit::{GuestLogRateLimit, RateLimit};
+use super::replay::ExecutionLog;
+use crate::HyperlightError::{
+    HostFunctionNamespaceDisabled, HostFunctionNotFound, HostFunctionPanicked,
+    HostFunctionPermissionDenied, HostFunctionThrottled, HostFunctionTimedOut,
+};
+use crate::func::host_functions::{AsyncContext, TypeErasedHostFunction};
+use crate::{Result, new_error};
+
+/// The most distinct metrics the guest of a sandbox may emit
+const MAX_GUEST_METRICS: usize = 128;
+
+/// Where the chunks of a streamed return value are sent
+pub(crate) type StreamSink = Box<dyn FnMut(&[u8]) -> Result<()> + Send>;
 
 #[derive(Default)]
 /// A Wrapper around details of functions exposed by the Host
 pub struct FunctionRegistry {
     functions_map: HashMap<String, FunctionEntry>,
+    /// The capabilities host functions may require to be called
+    granted_capabilities: Capabilities,
+    /// The limit on how often the guest may call any host function
+    rate_limit: Option<RateLimit>,
+    /// The interceptors every host function call runs through, outermost
+    /// first
+    interceptors: Vec<Interceptor>,
+    /// The namespaces whose host functions may not be called
+    disabled_namespaces: HashSet<String>,
+    /// Where the guest's stdout and stderr are written, if not printed
+    guest_stdout: Option<OutputSink>,
+    guest_stderr: Option<OutputSink>,
+    /// Where the guest's stdin is read from
+    guest_stdin: Option<InputSource>,
+    /// Where the chunks of a streamed return value are sent, during a
+    /// streaming call
+    stream_sink: Option<StreamSink>,
+    /// The context async host functions are run in
+    async_context: Option<AsyncContext>,
+    /// The execution of the sandbox being recorded or replayed, if any
+    execution_log: Option<ExecutionLog>,
+    /// Traces the guest's outcalls, if they are traced
+    outcall_tracer: Option<OutcallTracer>,
+    /// The limit on how many log records the guest may send
+    guest_log_rate_limit: Option<GuestLogRateLimit>,
+    /// The subscribers to the sandbox's lifecycle events
+    event_subscribers: Vec<Arc<dyn EventSubscriber>>,
+    /// Where the host functions the guest invokes are audited, if they are
+    audit_sink: Option<Arc<dyn AuditSink>>,
+    /// The labels of the sandbox, which audit records and guest metrics
+    /// are made with
+    labels: SandboxLabels,
+    /// The names of the metrics the guest has emitted
+    guest_metric_names: HashSet<String>,
+    /// How long the host functions called since this was last taken ran
+    host_call_time: Duration,
+    /// Where the guest's last outcalls are kept for crash reports
+    #[cfg(crashdump)]
+    recent_outcalls: Option<crate::hypervisor::crashdump::RecentOutcalls>,
+    /// The filesystem access host functions are restricted to
+    #[cfg(landlock)]
+    fs_restriction: Option<FilesystemRestriction>,
 }
 
 impl From<&mut FunctionRegistry> for HostFunctionDetails {
     fn from(registry: &mut FunctionRegistry) -> Self {
+        // Wildcards and disabled functions can't be called by name
         let host_functions = registry
             .functions_map
             .iter()
+            .filter(|(name, _)| {
+                !name.ends_with("/*") && registry.disabled_namespace(name).is_none()
+            })
             .map(|(name, entry)| HostFunctionDefinition {
                 function_name: name.clone(),
                 parameter_types: Some(entry.parameter_types.to_vec()),
@@ -57,10 +132,20 @@ pub struct FunctionEntry {
     pub function: TypeErasedHostFunction,
     pub parameter_types: &'static [ParameterType],
     pub return_type: ReturnType,
+    /// The capabilities the sandbox must be granted to call the function
+    pub required_capabilities: Capabilities,
+    /// The limit on how often the guest may call the function
+    pub rate_limit: Option<RateLimit>,
+    /// How long a call to the function may take before it fails
+    pub timeout: Option<Duration>,
+    /// The seccomp filter the function runs under
+    #[cfg(seccomp)]
+    pub seccomp_filter: seccompiler::BpfProgram,
 }
 
 impl FunctionRegistry {
-    /// Register a host function with the sandbox.
+    /// Register a host function with the sandbox, replacing any function
+    /// already registered with the same name.
     #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
     pub(crate) fn register_host_function(
         &mut self,
@@ -72,15 +157,271 @@ impl FunctionRegistry {
         Ok(())
     }
 
+    /// Remove the host function `name`, returning it if it was registered
+    pub(crate) fn unregister_host_function(&mut self, name: &str) -> Option<FunctionEntry> {
+        self.functions_map.remove(name)
+    }
+
+    /// Replace the implementation and signature of the host function
+    /// `name`, keeping the restrictions it was registered with
+    pub(crate) fn replace_host_function(
+        &mut self,
+        name: &str,
+        function: TypeErasedHostFunction,
+        parameter_types: &'static [ParameterType],
+        return_type: ReturnType,
+    ) -> Result<()> {
+        let entry = self
+            .functions_map
+            .get_mut(name)
+            .ok_or_else(|| HostFunctionNotFound(name.to_string()))?;
+        entry.function = function;
+        entry.parameter_types = parameter_types;
+        entry.return_type = return_type;
+        Ok(())
+    }
+
+    /// Allow host functions that require `capabilities` to be called
+    pub(crate) fn grant_capabilities(&mut self, capabilities: Capabilities) {
+        self.granted_capabilities |= capabilities;
+    }
+
+    /// Limit how often the guest may call the host function `name`
+    pub(crate) fn set_function_rate_limit(&mut self, name: &str, limit: RateLimit) -> Result<()> {
+        self.functions_map
+            .get_mut(name)
+            .ok_or_else(|| HostFunctionNotFound(name.to_string()))?
+            .rate_limit = Some(limit);
+        Ok(())
+    }
+
+    /// Fail calls to the host function `name` that take longer than
+    /// `timeout`
+    pub(crate) fn set_function_timeout(&mut self, name: &str, timeout: Duration) -> Result<()> {
+        self.functions_map
+            .get_mut(name)
+            .ok_or_else(|| HostFunctionNotFound(name.to_string()))?
+            .timeout = Some(timeout);
+        Ok(())
+    }
+
+    /// Limit how often the guest may call any host function
+    pub(crate) fn set_rate_limit(&mut self, limit: RateLimit) {
+        self.rate_limit = Some(limit);
+    }
+
+    /// Limit how many log records the guest may send, or remove the limit
+    pub(crate) fn set_guest_log_rate_limit(&mut self, limit: Option<GuestLogRateLimit>) {
+        self.guest_log_rate_limit = limit;
+    }
+
+    /// The limit on how many log records the guest may send, if any
+    pub(crate) fn guest_log_rate_limit(&self) -> Option<&GuestLogRateLimit> {
+        self.guest_log_rate_limit.as_ref()
+    }
+
+    /// Deliver the sandbox's lifecycle events to `subscriber`
+    pub(crate) fn add_event_subscriber(&mut self, subscriber: Arc<dyn EventSubscriber>) {
+        self.event_subscribers.push(subscriber);
+    }
+
+    /// The subscribers to the sandbox's lifecycle events
+    pub(crate) fn event_subscribers(&self) -> &[Arc<dyn EventSubscriber>] {
+        &self.event_subscribers
+    }
+
+    /// Audit the host functions the guest invokes with `sink`, or stop
+    /// auditing them
+    pub(crate) fn set_audit_sink(&mut self, sink: Option<Arc<dyn AuditSink>>) {
+        self.audit_sink = sink;
+    }
+
+    /// Make audit records with the labels of the sandbox, which are only
+    /// final once it is evolved
+    pub(crate) fn set_labels(&mut self, labels: SandboxLabels) {
+        self.labels = labels;
+    }
+
+    /// The labels of the sandbox
+    pub(crate) fn labels(&self) -> &SandboxLabels {
+        &self.labels
+    }
+
+    /// Returns whether the guest may emit the metric `name`, which it may
+    /// unless it has already emitted as many other metrics as a sandbox
+    /// may have, so a guest can't flood the host's recorder with metrics
+    pub(crate) fn admit_guest_metric(&mut self, name: &str) -> bool {
+        if self.guest_metric_names.contains(name) {
+            return true;
+        }
+        if self.guest_metric_names.len() >= MAX_GUEST_METRICS {
+            return false;
+        }
+        self.guest_metric_names.insert(name.to_string());
+        true
+    }
+
+    /// Audit a call to the host function `function`, which failed with
+    /// `error` if it failed
+    pub(crate) fn audit_host_call(&self, function: &str, error: Option<ErrorCode>) {
+        if let Some(sink) = &self.audit_sink {
+            sink.record(&AuditRecord::new(function, &self.labels, error));
+        }
+    }
+
+    /// Stop host functions in any of `namespaces` being called
+    pub(crate) fn disable_namespaces(&mut self, namespaces: impl IntoIterator<Item = String>) {
+        self.disabled_namespaces.extend(namespaces);
+    }
+
+    /// The disabled namespace the host function `name` is in, if any
+    fn disabled_namespace<'n>(&self, name: &'n str) -> Option<&'n str> {
+        namespaces(name).find(|ns| self.disabled_namespaces.contains(*ns))
+    }
+
+    /// The host function `name`, or the wildcard of the innermost
+    /// namespace it is in along with the rest of its name
+    fn resolve<'n>(&self, name: &'n str) -> Option<(&FunctionEntry, Option<&'n str>)> {
+        if let Some(entry) = self.functions_map.get(name) {
+            return Some((entry, None));
+        }
+        namespaces(name).find_map(|ns| {
+            let entry = self.functions_map.get(&format!("{ns}/*"))?;
+            Some((entry, Some(&name[ns.len() + 1..])))
+        })
+    }
+
+    /// Send the guest's stdout to `sink`
+    pub(crate) fn set_guest_stdout(&mut self, sink: OutputSink) {
+        self.guest_stdout = Some(sink);
+    }
+
+    /// Send the guest's stderr to `sink`
+    pub(crate) fn set_guest_stderr(&mut self, sink: OutputSink) {
+        self.guest_stderr = Some(sink);
+    }
+
+    /// Send the chunks of a streamed return value to `sink`, or stop
+    /// accepting them if it is `None`
+    pub(crate) fn set_stream_sink(&mut self, sink: Option<StreamSink>) {
+        self.stream_sink = sink;
+    }
+
+    /// The sink for the chunks of a streamed return value, if a streaming
+    /// call is being made
+    pub(super) fn stream_sink(&mut self) -> Option<&mut StreamSink> {
+        self.stream_sink.as_mut()
+    }
+
+    /// Read the guest's stdin from `source`
+    pub(crate) fn set_guest_stdin(&mut self, source: InputSource) {
+        self.guest_stdin = Some(source);
+    }
+
+    /// Run async host functions in `context`
+    pub(crate) fn set_async_context(&mut self, context: AsyncContext) {
+        self.async_context = Some(context);
+    }
+
+    /// Record the execution of the sandbox to, or replay it from, `log`
+    pub(crate) fn set_execution_log(&mut self, log: ExecutionLog) {
+        self.execution_log = Some(log);
+    }
+
+    /// The execution of the sandbox being recorded or replayed, if any
+    pub(crate) fn execution_log(&mut self) -> Option<&mut ExecutionLog> {
+        self.execution_log.as_mut()
+    }
+
+    /// Read up to `max_len` bytes of the guest's stdin, which is empty if
+    /// no source was set
+    pub(super) fn read_guest_input(&mut self, max_len: usize) -> Result<Vec<u8>> {
+        match &mut self.guest_stdin {
+            Some(source) => Ok(AsyncContext::scope(self.async_context.as_ref(), || {
+                source.read(max_len)
+            })?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Write output from the guest to the sink for its stdout if `fd` is
+    /// 1, or its stderr otherwise. Without a sink, stdout is printed with
+    /// the `"HostPrint"` host function, and stderr is written to the
+    /// host's stderr.
+    pub(super) fn write_guest_output(&mut self, fd: u32, data: &[u8]) -> Result<()> {
+        let sink = match fd {
+            1 => &mut self.guest_stdout,
+            _ => &mut self.guest_stderr,
+        };
+        match sink {
+            Some(sink) => Ok(sink.write(data)?),
+            None if fd == 1 => {
+                self.host_print(String::from_utf8_lossy(data).into_owned())?;
+                Ok(())
+            }
+            None => Ok(std::io::stderr().write_all(data)?),
+        }
+    }
+
+    /// Trace the guest's outcalls with `tracer`, or stop tracing them
+    pub(crate) fn set_outcall_tracer(&mut self, tracer: Option<OutcallTracer>) {
+        self.outcall_tracer = tracer;
+    }
+
+    /// What traces the guest's outcalls, if they are traced
+    pub(crate) fn outcall_tracer(&self) -> Option<&OutcallTracer> {
+        self.outcall_tracer.as_ref()
+    }
+
+    /// Add `duration` to the time spent in host functions
+    pub(crate) fn add_host_call_time(&mut self, duration: Duration) {
+        self.host_call_time += duration;
+    }
+
+    /// The time spent in host functions since this was last called
+    pub(crate) fn take_host_call_time(&mut self) -> Duration {
+        std::mem::take(&mut self.host_call_time)
+    }
+
+    /// Keep the guest's last outcalls in `recent_outcalls`
+    #[cfg(crashdump)]
+    pub(crate) fn set_recent_outcalls(
+        &mut self,
+        recent_outcalls: crate::hypervisor::crashdump::RecentOutcalls,
+    ) {
+        self.recent_outcalls = Some(recent_outcalls);
+    }
+
+    /// Where the guest's last outcalls are kept, if anywhere
+    #[cfg(crashdump)]
+    pub(crate) fn recent_outcalls(&self) -> Option<&crate::hypervisor::crashdump::RecentOutcalls> {
+        self.recent_outcalls.as_ref()
+    }
+
+    /// Run every host function call through `interceptor`, inside any
+    /// interceptors added before it
+    pub(crate) fn add_interceptor(&mut self, interceptor: Interceptor) {
+        self.interceptors.push(interceptor);
+    }
+
+    /// Restrict the filesystem access of all host functions
+    #[cfg(landlock)]
+    pub(crate) fn set_fs_restriction(&mut self, restriction: FilesystemRestriction) {
+        self.fs_restriction = Some(restriction);
+    }
+
     /// Assuming a host function called `"HostPrint"` exists, and takes a
     /// single string parameter, call it with the given `msg` parameter.
     ///
     /// Return `Ok` if the function was found and was of the right signature,
     /// and `Err` otherwise.
     #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
-    #[allow(dead_code)]
     pub(super) fn host_print(&mut self, msg: String) -> Result<i32> {
-        let res = self.call_host_func_impl("HostPrint", vec![ParameterValue::String(msg)])?;
+        let res = self.call_host_func_impl(
+            "HostPrint",
+            vec![ParameterValue::String(msg)],
+            &CancellationStatus::default(),
+        )?;
         res.try_into()
             .map_err(|_| HostFunctionNotFound("HostPrint".to_string()))
     }
@@ -91,28 +432,194 @@ impl FunctionRegistry {
     /// Return `Err` if no such function exists,
     /// its parameter list doesn't match `args`, or there was another error
     /// getting, configuring or calling the function.
-    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    #[cfg(test)]
     pub(super) fn call_host_function(
         &self,
         name: &str,
         args: Vec<ParameterValue>,
     ) -> Result<ReturnValue> {
-        self.call_host_func_impl(name, args)
+        self.call_host_func_impl(name, args, &CancellationStatus::default())
     }
 
+    /// Call the host function `name` like
+    /// [`call_host_function`](Self::call_host_function), on behalf of a
+    /// guest call whose cancellation is reported by `cancellation`
     #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
-    fn call_host_func_impl(&self, name: &str, args: Vec<ParameterValue>) -> Result<ReturnValue> {
-        let FunctionEntry {
-            function,
-            parameter_types: _,
-            return_type: _,
-        } = self
-            .functions_map
-            .get(name)
+    pub(super) fn call_host_function_with_cancellation(
+        &self,
+        name: &str,
+        args: Vec<ParameterValue>,
+        cancellation: &CancellationStatus,
+    ) -> Result<ReturnValue> {
+        self.call_host_func_impl(name, args, cancellation)
+    }
+
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    fn call_host_func_impl(
+        &self,
+        name: &str,
+        args: Vec<ParameterValue>,
+        cancellation: &CancellationStatus,
+    ) -> Result<ReturnValue> {
+        if let Some(namespace) = self.disabled_namespace(name) {
+            return Err(HostFunctionNamespaceDisabled(
+                name.to_string(),
+                namespace.to_string(),
+            ));
+        }
+        let (entry, wildcard_name) = self
+            .resolve(name)
             .ok_or_else(|| HostFunctionNotFound(name.to_string()))?;
+        let missing = entry.required_capabilities - self.granted_capabilities;
+        if !missing.is_empty() {
+            return Err(HostFunctionPermissionDenied(name.to_string(), missing));
+        }
+        // The permits count the call as outstanding until it returns
+        let _sandbox_permit = self
+            .rate_limit
+            .as_ref()
+            .map(|limit| limit.acquire())
+            .transpose()
+            .map_err(|t| {
+                HostFunctionThrottled(name.to_string(), format!("{t} from this sandbox"))
+            })?;
+        let _function_permit = entry
+            .rate_limit
+            .as_ref()
+            .map(|limit| limit.acquire())
+            .transpose()
+            .map_err(|t| {
+                HostFunctionThrottled(name.to_string(), format!("{t} to this function"))
+            })?;
 
         // Make the host function call
-        crate::metrics::maybe_time_and_emit_host_call(name, || function.call(args))
+        let function = |mut args: Vec<ParameterValue>| {
+            if let Some(wildcard_name) = wildcard_name {
+                args.insert(0, ParameterValue::String(wildcard_name.to_string()));
+            }
+            match entry.timeout {
+                Some(timeout) => self.call_with_timeout(name, entry, timeout, args, cancellation),
+                #[cfg(any(seccomp, landlock))]
+                None => self.call_restricted(entry, args, cancellation),
+                #[cfg(not(any(seccomp, landlock)))]
+                None => entry.function.call(args),
+            }
+        };
+        // A panic in the host function or an interceptor is returned as an
+        // error, rather than unwinding through the registry's lock
+        let call = || {
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                AsyncContext::scope(self.async_context.as_ref(), || {
+                    cancellation.scope(|| {
+                        HostCall::new(name, args, &self.interceptors, &function).proceed()
+                    })
+                })
+            }))
+            .unwrap_or_else(|payload| {
+                Err(HostFunctionPanicked(
+                    name.to_string(),
+                    panic_message(&*payload),
+                ))
+            })
+        };
+        crate::metrics::maybe_time_and_emit_host_call(name, call)
+    }
+
+    /// Call a host function on a new worker thread, restricted by the
+    /// sandbox's filesystem restriction and the function's seccomp filter.
+    /// Neither restriction can be lifted once applied, so they are never
+    /// applied to the calling thread.
+    #[cfg(any(seccomp, landlock))]
+    fn call_restricted(
+        &self,
+        entry: &FunctionEntry,
+        args: Vec<ParameterValue>,
+        cancellation: &CancellationStatus,
+    ) -> Result<ReturnValue> {
+        #[cfg(all(landlock, not(seccomp)))]
+        if self.fs_restriction.is_none() {
+            return entry.function.call(args);
+        }
+        // The registry itself is not shared with the worker thread, as the
+        // guest's output sinks and input source can't be
+        #[cfg(landlock)]
+        let fs_restriction = &self.fs_restriction;
+        let async_context = self.async_context.as_ref();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                // Landlock must be applied first, as the seccomp filter
+                // does not allow the syscalls it needs
+                #[cfg(landlock)]
+                if let Some(restriction) = fs_restriction {
+                    restriction.apply()?;
+                }
+                #[cfg(seccomp)]
+                super::seccomp::apply(&entry.seccomp_filter)?;
+                AsyncContext::scope(async_context, || {
+                    cancellation.scope(|| entry.function.call(args))
+                })
+            })
+            .join()
+            .unwrap_or_else(|payload| panic::resume_unwind(payload))
+        })
+    }
+
+    /// Call a host function on a new worker thread, restricted as it would
+    /// be without a timeout, failing if it does not return within
+    /// `timeout`. A function that times out is left running
+    /// on its worker thread, as it cannot be stopped safely, and its
+    /// result is discarded when it does return.
+    fn call_with_timeout(
+        &self,
+        name: &str,
+        entry: &FunctionEntry,
+        timeout: Duration,
+        args: Vec<ParameterValue>,
+        cancellation: &CancellationStatus,
+    ) -> Result<ReturnValue> {
+        let function = entry.function.clone();
+        let cancellation = cancellation.clone();
+        let async_context = self.async_context.clone();
+        #[cfg(landlock)]
+        let fs_restriction = self.fs_restriction.clone();
+        #[cfg(seccomp)]
+        let seccomp_filter = entry.seccomp_filter.clone();
+        let (tx, rx) = mpsc::sync_channel(1);
+        std::thread::Builder::new()
+            .name(format!("host-fn-{name}"))
+            .spawn(move || {
+                let call = || {
+                    #[cfg(landlock)]
+                    if let Some(restriction) = &fs_restriction {
+                        restriction.apply()?;
+                    }
+                    #[cfg(seccomp)]
+                    super::seccomp::apply(&seccomp_filter)?;
+                    AsyncContext::scope(async_context.as_ref(), || {
+                        cancellation.scope(|| function.call(args))
+                    })
+                };
+                // The caller may have stopped waiting for the result
+                let _ = tx.send(panic::catch_unwind(AssertUnwindSafe(call)));
+            })?;
+        match rx.recv_timeout(timeout) {
+            Ok(res) => res.unwrap_or_else(|payload| panic::resume_unwind(payload)),
+            Err(RecvTimeoutError::Timeout) => Err(HostFunctionTimedOut(name.to_string(), timeout)),
+            Err(RecvTimeoutError::Disconnected) => Err(new_error!(
+                "host function worker thread exited without a result"
+            )),
+        }
+    }
+}
+
+/// The message a panic was raised with, if it was raised with one
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic payload".to_string()
     }
 }
 
@@ -135,3 +642,485 @@ pub(super) fn default_writer_func(s: String) -> Result<i32> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use hyperlight_common::flatbuffer_wrappers::function_types::{
+        ParameterType, ParameterValue, ReturnType, ReturnValue,
+    };
+    use hyperlight_common::flatbuffer_wrappers::host_function_details::HostFunctionDetails;
+
+    use super::{FunctionEntry, FunctionRegistry, MAX_GUEST_METRICS};
+    use crate::HyperlightError;
+    use crate::func::host_functions::TypeErasedHostFunction;
+    use crate::func::{AsyncContext, AsyncHostFunction, HostFunction};
+    use crate::sandbox::{CancellationStatus, Capabilities, RateLimit};
+
+    /// An entry for `function`, taking no arguments and returning an `i32`,
+    /// that requires `required_capabilities`
+    fn int_entry(
+        function: HostFunction<i32, ()>,
+        required_capabilities: Capabilities,
+    ) -> FunctionEntry {
+        FunctionEntry {
+            function: function.into(),
+            parameter_types: &[] as &[ParameterType],
+            return_type: ReturnType::Int,
+            required_capabilities,
+            rate_limit: None,
+            timeout: None,
+            #[cfg(seccomp)]
+            seccomp_filter: crate::sandbox::SyscallFilter::default().compile().unwrap(),
+        }
+    }
+
+    #[test]
+    fn calls_require_granted_capabilities() {
+        let mut registry = FunctionRegistry::default();
+        let func: HostFunction<i32, ()> = HostFunction::from(|| Ok(42));
+        registry
+            .register_host_function(
+                "ReadSecret".to_string(),
+                FunctionEntry {
+                    function: func.into(),
+                    parameter_types: &[] as &[ParameterType],
+                    return_type: ReturnType::Int,
+                    required_capabilities: Capabilities::NETWORK | Capabilities::SECRETS,
+                    rate_limit: None,
+                    timeout: None,
+                    #[cfg(seccomp)]
+                    seccomp_filter: crate::sandbox::SyscallFilter::default().compile().unwrap(),
+                },
+            )
+            .unwrap();
+
+        registry.grant_capabilities(Capabilities::NETWORK | Capabilities::FILESYSTEM);
+        let err = registry
+            .call_host_function("ReadSecret", vec![])
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            HyperlightError::HostFunctionPermissionDenied(name, missing)
+                if name == "ReadSecret" && missing == Capabilities::SECRETS
+        ));
+
+        registry.grant_capabilities(Capabilities::SECRETS);
+        let res = registry.call_host_function("ReadSecret", vec![]).unwrap();
+        assert_eq!(res, ReturnValue::Int(42));
+    }
+
+    #[test]
+    fn replaced_functions_keep_their_restrictions() {
+        let mut registry = FunctionRegistry::default();
+        let func = HostFunction::from(|| Ok(42));
+        registry
+            .register_host_function(
+                "ReadSecret".to_string(),
+                int_entry(func, Capabilities::SECRETS),
+            )
+            .unwrap();
+        registry.grant_capabilities(Capabilities::SECRETS);
+
+        let func: HostFunction<String, (String,)> = HostFunction::from(|name: String| Ok(name));
+        registry
+            .replace_host_function(
+                "ReadSecret",
+                func.into(),
+                &[ParameterType::String],
+                ReturnType::String,
+            )
+            .unwrap();
+        let res = registry
+            .call_host_function(
+                "ReadSecret",
+                vec![ParameterValue::String("token".to_string())],
+            )
+            .unwrap();
+        assert_eq!(res, ReturnValue::String("token".to_string()));
+
+        // The new signature is the one the guest is told about
+        let details = HostFunctionDetails::from(&mut registry);
+        let definition = &details.host_functions.unwrap()[0];
+        assert_eq!(
+            definition.parameter_types,
+            Some(vec![ParameterType::String])
+        );
+        assert_eq!(definition.return_type, ReturnType::String);
+
+        // Replacing keeps the capabilities the function was registered with
+        registry.granted_capabilities = Capabilities::empty();
+        assert!(matches!(
+            registry.call_host_function(
+                "ReadSecret",
+                vec![ParameterValue::String("token".to_string())]
+            ),
+            Err(HyperlightError::HostFunctionPermissionDenied(..))
+        ));
+
+        // Only registered functions can be replaced
+        let func: HostFunction<i32, ()> = HostFunction::from(|| Ok(0));
+        assert!(matches!(
+            registry.replace_host_function("NotRegistered", func.into(), &[], ReturnType::Int),
+            Err(HyperlightError::HostFunctionNotFound(name)) if name == "NotRegistered"
+        ));
+    }
+
+    #[test]
+    fn unregistered_functions_are_not_found() {
+        let mut registry = FunctionRegistry::default();
+        for name in ["Lookup", "Store"] {
+            let func = HostFunction::from(|| Ok(0));
+            registry
+                .register_host_function(name.to_string(), int_entry(func, Capabilities::empty()))
+                .unwrap();
+        }
+
+        assert!(registry.unregister_host_function("Lookup").is_some());
+        assert!(registry.unregister_host_function("Lookup").is_none());
+        assert!(matches!(
+            registry.call_host_function("Lookup", vec![]),
+            Err(HyperlightError::HostFunctionNotFound(name)) if name == "Lookup"
+        ));
+        // The guest is no longer told about the function
+        let details = HostFunctionDetails::from(&mut registry);
+        let names: Vec<_> = details
+            .host_functions
+            .unwrap()
+            .into_iter()
+            .map(|f| f.function_name)
+            .collect();
+        assert_eq!(names, ["Store"]);
+
+        // Other functions are unaffected, and the name can be registered again
+        assert_eq!(
+            registry.call_host_function("Store", vec![]).unwrap(),
+            ReturnValue::Int(0)
+        );
+        let func = HostFunction::from(|| Ok(1));
+        registry
+            .register_host_function("Lookup".to_string(), int_entry(func, Capabilities::empty()))
+            .unwrap();
+        assert_eq!(
+            registry.call_host_function("Lookup", vec![]).unwrap(),
+            ReturnValue::Int(1)
+        );
+    }
+
+    #[test]
+    fn calls_are_throttled_by_rate_limits() {
+        let mut registry = FunctionRegistry::default();
+        for name in ["Lookup", "Store"] {
+            let func: HostFunction<i32, ()> = HostFunction::from(|| Ok(0));
+            registry
+                .register_host_function(
+                    name.to_string(),
+                    FunctionEntry {
+                        function: func.into(),
+                        parameter_types: &[] as &[ParameterType],
+                        return_type: ReturnType::Int,
+                        required_capabilities: Capabilities::empty(),
+                        rate_limit: None,
+                        timeout: None,
+                        #[cfg(seccomp)]
+                        seccomp_filter: crate::sandbox::SyscallFilter::default().compile().unwrap(),
+                    },
+                )
+                .unwrap();
+        }
+        registry
+            .set_function_rate_limit("Lookup", RateLimit::new().with_calls_per_second(1))
+            .unwrap();
+        registry.set_rate_limit(RateLimit::new().with_calls_per_second(3));
+        assert!(
+            registry
+                .set_function_rate_limit("Missing", RateLimit::new())
+                .is_err()
+        );
+
+        registry.call_host_function("Lookup", vec![]).unwrap();
+        let err = registry.call_host_function("Lookup", vec![]).unwrap_err();
+        assert!(matches!(
+            err,
+            HyperlightError::HostFunctionThrottled(ref name, ref reason)
+                if name == "Lookup" && reason.ends_with("to this function")
+        ));
+
+        // The throttled call still counted against the sandbox's limit
+        registry.call_host_function("Store", vec![]).unwrap();
+        let err = registry.call_host_function("Store", vec![]).unwrap_err();
+        assert!(matches!(
+            err,
+            HyperlightError::HostFunctionThrottled(_, ref reason)
+                if reason.ends_with("from this sandbox")
+        ));
+    }
+
+    #[test]
+    fn namespaces_have_wildcards_and_can_be_disabled() {
+        let mut registry = FunctionRegistry::default();
+        let entry = |function: TypeErasedHostFunction| FunctionEntry {
+            function,
+            parameter_types: &[],
+            return_type: ReturnType::String,
+            required_capabilities: Capabilities::empty(),
+            rate_limit: None,
+            timeout: None,
+            #[cfg(seccomp)]
+            seccomp_filter: crate::sandbox::SyscallFilter::default().compile().unwrap(),
+        };
+        let read: HostFunction<String, ()> = HostFunction::from(|| Ok("read".to_string()));
+        registry
+            .register_host_function("wasi:fs/read".to_string(), entry(read.into()))
+            .unwrap();
+        let wildcard = TypeErasedHostFunction::wildcard(|name, args| {
+            Ok(ReturnValue::String(format!("{name}{args:?}")))
+        });
+        registry
+            .register_host_function("wasi:fs/*".to_string(), entry(wildcard))
+            .unwrap();
+        let connect: HostFunction<String, ()> = HostFunction::from(|| Ok("connect".to_string()));
+        registry
+            .register_host_function(
+                "wasi:sockets/tcp/connect".to_string(),
+                entry(connect.into()),
+            )
+            .unwrap();
+
+        assert_eq!(
+            registry.call_host_function("wasi:fs/read", vec![]).unwrap(),
+            ReturnValue::String("read".to_string())
+        );
+        assert_eq!(
+            registry
+                .call_host_function("wasi:fs/dir/list", vec![ParameterValue::Int(1)])
+                .unwrap(),
+            ReturnValue::String("dir/list[Int(1)]".to_string())
+        );
+        assert!(matches!(
+            registry.call_host_function("wasi:http/get", vec![]),
+            Err(HyperlightError::HostFunctionNotFound(_))
+        ));
+
+        registry.disable_namespaces(["wasi:sockets".to_string()]);
+        assert!(matches!(
+            registry.call_host_function("wasi:sockets/tcp/connect", vec![]),
+            Err(HyperlightError::HostFunctionNamespaceDisabled(ref name, ref namespace))
+                if name == "wasi:sockets/tcp/connect" && namespace == "wasi:sockets"
+        ));
+
+        // Only functions the guest can call by name are reported to it
+        let details = HostFunctionDetails::from(&mut registry);
+        let mut names: Vec<_> = details
+            .host_functions
+            .unwrap()
+            .into_iter()
+            .map(|f| f.function_name)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["wasi:fs/read"]);
+    }
+
+    #[test]
+    fn panics_are_returned_as_errors() {
+        let mut registry = FunctionRegistry::default();
+        let func: HostFunction<i32, (i32,)> = HostFunction::from(|n: i32| {
+            if n < 0 {
+                panic!("negative argument {n}");
+            }
+            Ok(n)
+        });
+        registry
+            .register_host_function(
+                "Check".to_string(),
+                FunctionEntry {
+                    function: func.into(),
+                    parameter_types: &[ParameterType::Int],
+                    return_type: ReturnType::Int,
+                    required_capabilities: Capabilities::empty(),
+                    rate_limit: None,
+                    timeout: None,
+                    #[cfg(seccomp)]
+                    seccomp_filter: crate::sandbox::SyscallFilter::default().compile().unwrap(),
+                },
+            )
+            .unwrap();
+
+        for timeout in [None, Some(Duration::from_secs(10))] {
+            registry.functions_map.get_mut("Check").unwrap().timeout = timeout;
+            let err = registry
+                .call_host_function("Check", vec![ParameterValue::Int(-1)])
+                .unwrap_err();
+            assert!(matches!(
+                err,
+                HyperlightError::HostFunctionPanicked(ref name, ref msg)
+                    if name == "Check" && msg == "negative argument -1"
+            ));
+            // The registry can still be used after a panic
+            let res = registry
+                .call_host_function("Check", vec![ParameterValue::Int(1)])
+                .unwrap();
+            assert_eq!(res, ReturnValue::Int(1));
+        }
+    }
+
+    #[test]
+    fn calls_fail_when_they_exceed_their_timeout() {
+        let mut registry = FunctionRegistry::default();
+        let func: HostFunction<i32, (u64,)> = HostFunction::from(|millis: u64| {
+            std::thread::sleep(Duration::from_millis(millis));
+            Ok(7)
+        });
+        registry
+            .register_host_function(
+                "Sleep".to_string(),
+                FunctionEntry {
+                    function: func.into(),
+                    parameter_types: &[ParameterType::ULong],
+                    return_type: ReturnType::Int,
+                    required_capabilities: Capabilities::empty(),
+                    rate_limit: None,
+                    timeout: None,
+                    #[cfg(seccomp)]
+                    seccomp_filter: crate::sandbox::SyscallFilter::default().compile().unwrap(),
+                },
+            )
+            .unwrap();
+        registry
+            .set_function_timeout("Sleep", Duration::from_millis(200))
+            .unwrap();
+        assert!(
+            registry
+                .set_function_timeout("Missing", Duration::from_secs(1))
+                .is_err()
+        );
+
+        let res = registry
+            .call_host_function("Sleep", vec![ParameterValue::ULong(0)])
+            .unwrap();
+        assert_eq!(res, ReturnValue::Int(7));
+
+        let err = registry
+            .call_host_function("Sleep", vec![ParameterValue::ULong(5000)])
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            HyperlightError::HostFunctionTimedOut(ref name, timeout)
+                if name == "Sleep" && timeout == Duration::from_millis(200)
+        ));
+    }
+
+    #[test]
+    fn host_functions_see_the_cancellation_of_their_call() {
+        let mut registry = FunctionRegistry::default();
+        let func: HostFunction<bool, ()> =
+            HostFunction::from(|| Ok(CancellationStatus::current().is_cancelled()));
+        registry
+            .register_host_function(
+                "IsCancelled".to_string(),
+                FunctionEntry {
+                    function: func.into(),
+                    parameter_types: &[],
+                    return_type: ReturnType::Bool,
+                    required_capabilities: Capabilities::empty(),
+                    rate_limit: None,
+                    timeout: None,
+                    #[cfg(seccomp)]
+                    seccomp_filter: crate::sandbox::SyscallFilter::default().compile().unwrap(),
+                },
+            )
+            .unwrap();
+
+        let cancelled = CancellationStatus::new(|| true);
+        for timeout in [None, Some(Duration::from_secs(10))] {
+            registry
+                .functions_map
+                .get_mut("IsCancelled")
+                .unwrap()
+                .timeout = timeout;
+            let res = registry
+                .call_host_function_with_cancellation("IsCancelled", vec![], &cancelled)
+                .unwrap();
+            assert_eq!(res, ReturnValue::Bool(true));
+            let res = registry.call_host_function("IsCancelled", vec![]).unwrap();
+            assert_eq!(res, ReturnValue::Bool(false));
+        }
+    }
+
+    #[test]
+    fn async_host_functions_run_in_the_async_context() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let listener = runtime
+            .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        // An echo server
+        runtime.spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (mut reader, mut writer) = stream.split();
+                    let _ = tokio::io::copy(&mut reader, &mut writer).await;
+                });
+            }
+        });
+
+        let mut registry = FunctionRegistry::default();
+        let func: HostFunction<String, (String,)> =
+            AsyncHostFunction::from(move |msg: String| async move {
+                let mut stream = tokio::net::TcpStream::connect(addr).await?;
+                stream.write_all(msg.as_bytes()).await?;
+                stream.shutdown().await?;
+                let mut reply = String::new();
+                stream.read_to_string(&mut reply).await?;
+                Ok::<_, HyperlightError>(reply)
+            })
+            .into();
+        registry
+            .register_host_function(
+                "Echo".to_string(),
+                FunctionEntry {
+                    function: func.into(),
+                    parameter_types: &[ParameterType::String],
+                    return_type: ReturnType::String,
+                    required_capabilities: Capabilities::empty(),
+                    rate_limit: None,
+                    timeout: None,
+                    #[cfg(seccomp)]
+                    seccomp_filter: crate::sandbox::SyscallFilter::default().compile().unwrap(),
+                },
+            )
+            .unwrap();
+        let args = || vec![ParameterValue::String("hello".to_string())];
+
+        // Outside of the runtime, tokio I/O panics
+        let err = registry.call_host_function("Echo", args()).unwrap_err();
+        assert!(
+            matches!(err, HyperlightError::HostFunctionPanicked(ref name, _) if name == "Echo")
+        );
+
+        let handle = runtime.handle().clone();
+        registry.set_async_context(AsyncContext::new(move |run| {
+            let _guard = handle.enter();
+            run()
+        }));
+        for timeout in [None, Some(Duration::from_secs(10))] {
+            registry.functions_map.get_mut("Echo").unwrap().timeout = timeout;
+            let res = registry.call_host_function("Echo", args()).unwrap();
+            assert_eq!(res, ReturnValue::String("hello".to_string()));
+        }
+    }
+
+    #[test]
+    fn guest_metrics_are_capped() {
+        let mut registry = FunctionRegistry::default();
+        for i in 0..MAX_GUEST_METRICS {
+            assert!(registry.admit_guest_metric(&format!("metric_{i}")));
+        }
+        assert!(!registry.admit_guest_metric("one_too_many"));
+        // Metrics already emitted can still be
+        assert!(registry.admit_guest_metric("metric_0"));
+    }
+}
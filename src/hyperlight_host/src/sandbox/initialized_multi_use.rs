@@ -14,34 +14,64 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use flatbuffers::FlatBufferBuilder;
-use hyperlight_common::flatbuffer_wrappers::function_call::{FunctionCall, FunctionCallType};
-use hyperlight_common::flatbuffer_wrappers::function_types::{
-    ParameterValue, ReturnType, ReturnValue,
+use hyperlight_common::call_encoding::WireFormat;
+use hyperlight_common::flatbuffer_wrappers::function_call::{
+    FunctionCallType, GUEST_FUNCTIONS_FUNCTION_NAME, ParameterRef, SHUTDOWN_FUNCTION_NAME,
 };
-use hyperlight_common::flatbuffer_wrappers::util::estimate_flatbuffer_capacity;
+#[cfg(any(feature = "fuzzing", feature = "console"))]
+use hyperlight_common::flatbuffer_wrappers::function_types::ParameterValue;
+use hyperlight_common::flatbuffer_wrappers::function_types::{ReturnType, ReturnValue};
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_common::flatbuffer_wrappers::host_function_details::HostFunctionDetails;
+use hyperlight_common::trace_context::TraceParent;
 use tracing::{Span, instrument};
-
-use super::Callable;
+use tracing_core::LevelFilter;
+
+use super::batch::GuestCall;
+use super::cancellation::CancellationToken;
+#[cfg(target_os = "linux")]
+use super::cgroup::VcpuCgroup;
+use super::control_page::ControlPage;
+use super::coverage::{CoverageCounters, GuestCoverage};
+use super::events::SandboxEvents;
 use super::file_mapping::prepare_file_cow;
-use super::host_funcs::FunctionRegistry;
+use super::guest_functions::GuestFunction;
+use super::host_funcs::{FunctionRegistry, StreamSink};
+use super::labels::SandboxLabels;
+use super::profiler::{GuestProfile, SamplingProfiler};
+use super::recovery::RecoveryPolicy;
+use super::registry::Registration;
+use super::replay::ExecutionRecording;
+use super::retry::RetryPolicy;
+use super::slow_call::SlowCallPolicy;
 use super::snapshot::Snapshot;
+use super::suspend::{ResumeSnapshot, SuspendedSandbox};
+use super::symbolizer::GuestSymbolizer;
+use super::uninitialized::GuestEnvironment;
+use super::uninitialized_evolve::evolve_impl_multi_use;
+use super::{Callable, SandboxConfiguration, UninitializedSandbox};
 use crate::HyperlightError::{self, SnapshotSandboxMismatch};
-use crate::func::{ParameterTuple, SupportedReturnType};
+use crate::func::{
+    HostFunction, ParameterRefTuple, ParameterTuple, Registerable, SupportedReturnType,
+};
 use crate::hypervisor::InterruptHandle;
 use crate::hypervisor::hyperlight_vm::{HyperlightVm, HyperlightVmError};
 use crate::mem::memory_region::{MemoryRegion, MemoryRegionFlags};
 use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::shared_mem::{HostSharedMemory, SharedMemory as _};
 use crate::metrics::{
-    METRIC_GUEST_ERROR, METRIC_GUEST_ERROR_LABEL_CODE, maybe_time_and_emit_guest_call,
+    CallPhaseTimer, METRIC_GUEST_CANCELLATION, METRIC_GUEST_ERROR, METRIC_GUEST_ERROR_LABEL_CODE,
+    call_metric_labels, maybe_time_and_emit_guest_calls,
 };
-use crate::{Result, log_then_return};
+use crate::{Result, etw, log_then_return, new_error, perfmon, usdt};
 
 /// A fully initialized sandbox that can execute guest functions multiple times.
 ///
@@ -84,6 +114,9 @@ use crate::{Result, log_then_return};
 pub struct MultiUseSandbox {
     /// Unique identifier for this sandbox instance
     id: u64,
+    /// The id snapshots of this sandbox are taken with, and must have to
+    /// be restored into it. It changes when the guest is replaced.
+    snapshot_id: u64,
     /// Whether this sandbox is poisoned
     poisoned: bool,
     pub(crate) host_funcs: Arc<Mutex<FunctionRegistry>>,
@@ -94,10 +127,61 @@ pub struct MultiUseSandbox {
     /// If the current state of the sandbox has been captured in a snapshot,
     /// that snapshot is stored here.
     snapshot: Option<Arc<Snapshot>>,
+    /// Snapshot taken immediately after the guest was initialised.
+    /// This is what [`reset()`](Self::reset) restores to.
+    init_snapshot: Option<Arc<Snapshot>>,
     /// Optional callback to discover page table roots from guest memory.
     /// Given (snapshot_mem, scratch_mem, cr3), returns a list of root GPAs.
     /// If not set, only CR3 is used as the single root.
     pt_root_finder: Option<PtRootFinder>,
+    /// The configuration the sandbox was created with
+    config: SandboxConfiguration,
+    /// The maximum guest log level set on the uninitialized sandbox
+    max_guest_log_level: Option<LevelFilter>,
+    /// The labels set on the uninitialized sandbox
+    labels: SandboxLabels,
+    /// Whether guest calls are rolled back when they fail.
+    /// See [`set_transactional_calls()`](Self::set_transactional_calls).
+    transactional_calls: bool,
+    /// Per-function overrides of the configured maximum stack size.
+    /// See [`set_function_stack_size()`](Self::set_function_stack_size).
+    function_stack_sizes: HashMap<String, usize>,
+    /// How the sandbox recovers when it is poisoned
+    recovery_policy: RecoveryPolicy,
+    /// The number of times the sandbox has been recovered since the last
+    /// successful guest call
+    consecutive_restarts: u32,
+    /// When guest calls are reported as slow. See
+    /// [`set_slow_call_policy()`](Self::set_slow_call_policy).
+    slow_call_policy: Option<SlowCallPolicy>,
+    /// The cgroup the vCPU thread is placed in during guest calls.
+    /// See [`set_vcpu_cgroup()`](Self::set_vcpu_cgroup).
+    #[cfg(target_os = "linux")]
+    vcpu_cgroup: Option<VcpuCgroup>,
+    /// Measurement of the guest image and configuration, computed when
+    /// the sandbox was evolved. See [`measurement()`](Self::measurement).
+    measurement: [u8; 32],
+    /// The functions the guest registered, if it reported them. See
+    /// [`guest_functions()`](Self::guest_functions).
+    guest_functions: Option<Vec<GuestFunction>>,
+    /// The symbolizer of the guest binary, which guest backtraces are
+    /// resolved against. See [`guest_symbolizer()`](Self::guest_symbolizer).
+    guest_symbolizer: Arc<GuestSymbolizer>,
+    /// The atomic slots shared with the guest. See
+    /// [`control_page()`](Self::control_page).
+    control_page: ControlPage,
+    /// The guest's coverage counters, if it is instrumented for coverage.
+    /// See [`take_coverage()`](Self::take_coverage).
+    coverage: Option<CoverageCounters>,
+    /// The profiler sampling the guest, if it is being profiled. See
+    /// [`start_profiling()`](Self::start_profiling).
+    profiler: Option<SamplingProfiler>,
+    /// Delivers the sandbox's lifecycle events. See
+    /// [`add_event_subscriber()`](Self::add_event_subscriber).
+    events: SandboxEvents,
+    /// The place of the sandbox in the process-wide registry, once it is
+    /// initialised. See [`registry`](super::registry).
+    registration: Option<Registration>,
 }
 
 /// Callback for discovering page table roots from guest memory.
@@ -119,14 +203,29 @@ impl MultiUseSandbox {
     /// to initialized, and is purposely not exposed publicly outside the crate
     /// (as a `From` implementation would be)
     #[instrument(skip_all, parent = Span::current(), level = "Trace")]
+    #[allow(clippy::too_many_arguments)]
     pub(super) fn from_uninit(
         host_funcs: Arc<Mutex<FunctionRegistry>>,
         mgr: SandboxMemoryManager<HostSharedMemory>,
         vm: HyperlightVm,
         #[cfg(gdb)] dbg_mem_access_fn: Arc<Mutex<SandboxMemoryManager<HostSharedMemory>>>,
+        config: SandboxConfiguration,
+        max_guest_log_level: Option<LevelFilter>,
+        labels: SandboxLabels,
+        measurement: [u8; 32],
+        guest_symbolizer: Arc<GuestSymbolizer>,
+        coverage_counters: Option<std::ops::Range<u64>>,
     ) -> MultiUseSandbox {
+        let control_page = ControlPage::new(mgr.scratch_mem.clone());
+        let id = super::snapshot::SANDBOX_CONFIGURATION_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let events = SandboxEvents::new(host_funcs.clone(), labels.clone(), id);
+        // Host functions are audited with the final labels of the sandbox
+        if let Ok(mut registry) = host_funcs.try_lock() {
+            registry.set_labels(labels.clone());
+        }
         Self {
-            id: super::snapshot::SANDBOX_CONFIGURATION_COUNTER.fetch_add(1, Ordering::Relaxed),
+            id,
+            snapshot_id: id,
             poisoned: false,
             host_funcs,
             mem_mgr: mgr,
@@ -134,10 +233,87 @@ impl MultiUseSandbox {
             #[cfg(gdb)]
             dbg_mem_access_fn,
             snapshot: None,
+            init_snapshot: None,
             pt_root_finder: None,
+            config,
+            max_guest_log_level,
+            labels,
+            transactional_calls: false,
+            function_stack_sizes: HashMap::new(),
+            recovery_policy: RecoveryPolicy::none(),
+            consecutive_restarts: 0,
+            slow_call_policy: None,
+            #[cfg(target_os = "linux")]
+            vcpu_cgroup: None,
+            measurement,
+            guest_functions: None,
+            guest_symbolizer,
+            control_page,
+            coverage: coverage_counters.map(CoverageCounters::new),
+            profiler: None,
+            events,
+            registration: None,
         }
     }
 
+    /// Start delivering the sandbox's lifecycle events, now that its guest
+    /// is initialised
+    pub(super) fn activate_events(&mut self) {
+        usdt::probe!("sandbox__create", self.id);
+        etw::sandbox_create(self.id);
+        perfmon::sandbox_created();
+        self.registration = Some(Registration::register(
+            self.id,
+            self.labels.clone(),
+            self.memory_size(),
+            self.vm.interrupt_handle(),
+        ));
+        self.events.set_active(true);
+        self.events
+            .emit(|subscriber, labels| subscriber.on_initialised(labels));
+    }
+
+    /// Capture the post-initialisation state of the sandbox so that it
+    /// can later be returned to with [`reset()`](Self::reset).
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub(super) fn capture_init_snapshot(&mut self) -> Result<()> {
+        let snapshot = self.snapshot()?;
+        self.init_snapshot = Some(snapshot);
+        Ok(())
+    }
+
+    /// Ask the guest which functions it has registered. Guests that cannot
+    /// report them, such as older guests or raw blobs, are left without a
+    /// list of functions, unless the call poisoned the sandbox. The call
+    /// is internal, so it is not counted in the guest call metrics.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub(super) fn load_guest_functions(&mut self) -> Result<()> {
+        let res = self.call_guest_function_by_name_no_reset(
+            GUEST_FUNCTIONS_FUNCTION_NAME,
+            ReturnType::VecBytes,
+            vec![],
+            None,
+        );
+        let bytes = match res {
+            Ok(ret) => <Vec<u8> as SupportedReturnType>::from_value(ret)?,
+            Err(e) if !self.poisoned => {
+                tracing::debug!("guest did not report its functions: {e}");
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        let details = HostFunctionDetails::try_from(bytes.as_slice())?;
+        self.guest_functions = Some(
+            details
+                .host_functions
+                .unwrap_or_default()
+                .into_iter()
+                .map(GuestFunction::from)
+                .collect(),
+        );
+        Ok(())
+    }
+
     /// Set a callback that discovers page table roots from guest memory.
     /// The callback receives (snapshot_mem, scratch_mem, cr3) and returns
     /// the list of root GPAs to walk during snapshot creation.
@@ -208,7 +384,7 @@ impl MultiUseSandbox {
             .map_err(|e| HyperlightError::HyperlightVmError(e.into()))?;
         let entrypoint = self.vm.get_entrypoint();
         let memory_snapshot = self.mem_mgr.snapshot(
-            self.id,
+            self.snapshot_id,
             mapped_regions_vec,
             &root_pt_gpas,
             stack_top_gpa,
@@ -320,7 +496,7 @@ impl MultiUseSandbox {
         // However, out of an abundance of caution, the optimisation
         // is presently disabled.
 
-        if self.id != snapshot.sandbox_id() {
+        if self.snapshot_id != snapshot.sandbox_id() {
             return Err(SnapshotSandboxMismatch);
         }
 
@@ -334,7 +510,21 @@ impl MultiUseSandbox {
             self.vm
                 .update_scratch_mapping(gscratch)
                 .map_err(|e| HyperlightError::HyperlightVmError(e.into()))?;
+            self.control_page
+                .set_scratch(self.mem_mgr.scratch_mem.clone());
         }
+        // Restoring clears scratch memory, and with it the guest's clock
+        let recorded = self
+            .host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .execution_log()
+            .is_some();
+        self.mem_mgr.write_wall_clock_base(if recorded {
+            0
+        } else {
+            self.vm.wall_clock_base()
+        })?;
 
         let sregs = snapshot.sregs().ok_or_else(|| {
             HyperlightError::Error("snapshot from running sandbox should have sregs".to_string())
@@ -372,6 +562,17 @@ impl MultiUseSandbox {
         // The restored snapshot is now our most current snapshot
         self.snapshot = Some(snapshot.clone());
 
+        // Restoring resets the guest's coverage counters too
+        match self.read_coverage_counters() {
+            Ok(Some(counters)) => {
+                if let Some(coverage) = &mut self.coverage {
+                    coverage.rebase(counters);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to read the guest's coverage counters: {e}"),
+        }
+
         // Clear poison state when successfully restoring from snapshot.
         //
         // # Safety:
@@ -382,10 +583,273 @@ impl MultiUseSandbox {
         //    - All corrupted data structures (overwritten with consistent snapshot data)
         //    - All inconsistent global state (reset to snapshot values)
         self.poisoned = false;
+        self.publish_state();
+
+        Ok(())
+    }
+
+    /// The id of the sandbox, by which it is known in the process-wide
+    /// [`registry`](super::registry) of sandboxes
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The size in bytes of the sandbox's guest memory
+    fn memory_size(&self) -> usize {
+        self.mem_mgr.shared_mem.mem_size() + self.mem_mgr.scratch_mem.mem_size()
+    }
 
+    /// Update the sandbox's entry in the registry after a call or a restore
+    fn publish_state(&self) {
+        if let Some(registration) = &self.registration {
+            registration.set_ready(self.poisoned, self.memory_size());
+        }
+    }
+
+    /// Resets the sandbox to the state it was in immediately after the
+    /// guest was initialised.
+    ///
+    /// This is equivalent to calling [`restore()`](Self::restore) with a
+    /// snapshot taken right after [`evolve()`](crate::UninitializedSandbox::evolve),
+    /// and is intended for reusing a single sandbox across mutually
+    /// untrusting requests without paying for a full rebuild. Guest writes
+    /// never touch the snapshot memory directly (they are copied on write
+    /// into the scratch region), so the guest image is not copied again.
+    ///
+    /// It is still a full restore: which pages the guest wrote to is not
+    /// tracked, so the whole scratch region is zeroed and its page tables
+    /// copied back in, and the vCPU state is reset. Its cost grows with the
+    /// scratch size, not with how much memory the guest touched.
+    ///
+    /// Any regions mapped with [`map_region()`](Self::map_region) or
+    /// [`map_file_cow()`](Self::map_file_cow) after initialisation are
+    /// unmapped.
+    ///
+    /// ## Poison State Recovery
+    ///
+    /// Like [`restore()`](Self::restore), this clears any poison state when
+    /// successful.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// sandbox.call::<i32>("AddToStatic", 5)?;
+    ///
+    /// // Discard everything the previous request did
+    /// sandbox.reset()?;
+    /// let value: i32 = sandbox.call("GetStatic", ())?;
+    /// assert_eq!(value, 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn reset(&mut self) -> Result<()> {
+        let snapshot = self.init_snapshot.clone().ok_or_else(|| {
+            HyperlightError::Error("sandbox has no post-initialisation snapshot".to_string())
+        })?;
+        self.restore(snapshot)
+    }
+
+    /// Replaces the guest running in this sandbox with a new guest binary.
+    ///
+    /// The partition and guest memory are torn down and rebuilt from the
+    /// new guest, which is then initialised exactly as
+    /// [`evolve()`](crate::UninitializedSandbox::evolve) would. The host
+    /// functions registered on the sandbox, its configuration, its maximum
+    /// guest log level, its page table root finder, whether calls are
+    /// transactional and its recovery policy are kept, so rolling out a new
+    /// guest version does not require re-registering anything. A recovery
+    /// policy that restores a snapshot is not kept, as the snapshot belongs
+    /// to the old guest.
+    ///
+    /// The sandbox keeps its [`id()`](Self::id), so metrics, the
+    /// [`registry`](super::registry) and lifecycle event subscribers see
+    /// the same sandbox running the new guest.
+    ///
+    /// If the new guest fails to load or initialise, an error is returned
+    /// and the sandbox keeps running the old guest.
+    ///
+    /// Because the memory layout of the new guest may differ, snapshots
+    /// taken before the replacement can no longer be restored into this
+    /// sandbox, and any [`InterruptHandle`] obtained from
+    /// [`interrupt_handle()`](Self::interrupt_handle) refers to the old
+    /// partition and must be fetched again. Regions mapped with
+    /// [`map_region()`](Self::map_region) or
+    /// [`map_file_cow()`](Self::map_file_cow) are not carried over.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest-v1.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// // Roll out a new version of the guest, keeping the host functions
+    /// sandbox.replace_guest(GuestBinary::FilePath("guest-v2.bin".into()))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn replace_guest<'a, 'b>(
+        &mut self,
+        env: impl Into<GuestEnvironment<'a, 'b>>,
+    ) -> Result<()> {
+        let mut u_sbox = UninitializedSandbox::new(env, Some(self.config))?;
+        u_sbox.host_funcs = self.host_funcs.clone();
+        u_sbox.max_guest_log_level = self.max_guest_log_level;
+        for (key, value) in self.labels.iter() {
+            u_sbox.set_label(key, value)?;
+        }
+        #[cfg(target_os = "linux")]
+        {
+            u_sbox.vcpu_cgroup = self.vcpu_cgroup.clone();
+        }
+        u_sbox.check_verified()?;
+        let mut sbox = evolve_impl_multi_use(u_sbox)?;
+        // The sandbox keeps its identity, but snapshots are taken with the
+        // new guest's id, so those of the old guest can't be restored
+        sbox.id = self.id;
+        sbox.events.set_id(self.id);
+        sbox.pt_root_finder = self.pt_root_finder.take();
+        sbox.transactional_calls = self.transactional_calls;
+        // Snapshots of the old guest cannot be restored into the new one
+        if !self.recovery_policy.restores_snapshot() {
+            sbox.recovery_policy = self.recovery_policy.clone();
+        }
+        sbox.function_stack_sizes = std::mem::take(&mut self.function_stack_sizes);
+        sbox.slow_call_policy = self.slow_call_policy.take();
+        // Existing control page handles follow the sandbox to the new guest
+        sbox.control_page = self.control_page.clone();
+        sbox.control_page
+            .set_scratch(sbox.mem_mgr.scratch_mem.clone());
+        if let Some(registration) = &self.registration {
+            registration.resumed(sbox.vm.interrupt_handle(), sbox.memory_size());
+        }
+        sbox.registration = self.registration.take();
+        // The sandbox lives on with the new guest
+        self.events.set_active(false);
+        sbox.events.set_active(true);
+        *self = sbox;
         Ok(())
     }
 
+    /// Tear down the virtual machine, keeping everything needed to create
+    /// it again from `snapshot`, which must be the sandbox's current
+    /// snapshot
+    pub(super) fn into_suspended(mut self, snapshot: Arc<Snapshot>) -> SuspendedSandbox {
+        // The sampling profiler runs on the virtual machine, so it is
+        // stopped and started again on the new one
+        let profile = self
+            .profiler
+            .take()
+            .and_then(|profiler| match profiler.stop() {
+                Ok(profile) => Some(profile),
+                Err(e) => {
+                    tracing::warn!("Failed to stop profiling the suspended sandbox: {e}");
+                    None
+                }
+            });
+        // The sandbox lives on while it is suspended
+        self.events.set_active(false);
+        if let Some(registration) = &self.registration {
+            registration.suspended();
+        }
+        SuspendedSandbox {
+            snapshot: ResumeSnapshot::InMemory(snapshot),
+            id: self.id,
+            snapshot_id: self.snapshot_id,
+            #[cfg(crashdump)]
+            binary_path: self.vm.binary_path(),
+            host_funcs: self.host_funcs,
+            init_snapshot: self.init_snapshot,
+            pt_root_finder: self.pt_root_finder,
+            config: self.config,
+            max_guest_log_level: self.max_guest_log_level,
+            labels: self.labels,
+            transactional_calls: self.transactional_calls,
+            function_stack_sizes: self.function_stack_sizes,
+            recovery_policy: self.recovery_policy,
+            consecutive_restarts: self.consecutive_restarts,
+            slow_call_policy: self.slow_call_policy,
+            #[cfg(target_os = "linux")]
+            vcpu_cgroup: self.vcpu_cgroup,
+            measurement: self.measurement,
+            guest_functions: self.guest_functions,
+            control_page: self.control_page,
+            registration: self.registration,
+            coverage: self.coverage,
+            profile,
+        }
+    }
+
+    /// Create a new virtual machine for a suspended sandbox and restore
+    /// its state into it. On error, `suspended` can be resumed again.
+    pub(super) fn resume(suspended: &mut SuspendedSandbox) -> Result<Self> {
+        let snapshot = suspended.snapshot()?;
+        let mut u_sbox = UninitializedSandbox::from_snapshot(
+            snapshot.clone(),
+            Some(suspended.config),
+            #[cfg(crashdump)]
+            suspended.binary_path.clone(),
+        )?;
+        u_sbox.host_funcs = suspended.host_funcs.clone();
+        u_sbox.max_guest_log_level = suspended.max_guest_log_level;
+        for (key, value) in suspended.labels.iter() {
+            u_sbox.set_label(key, value)?;
+        }
+        #[cfg(target_os = "linux")]
+        {
+            u_sbox.vcpu_cgroup = suspended.vcpu_cgroup.clone();
+        }
+        let mut sbox = evolve_impl_multi_use(u_sbox)?;
+        // Keep the identity of the suspended sandbox, so that snapshots
+        // taken before it was suspended can still be restored
+        sbox.id = suspended.id;
+        sbox.snapshot_id = suspended.snapshot_id;
+        sbox.events.set_id(suspended.id);
+        sbox.init_snapshot = suspended.init_snapshot.clone();
+        sbox.transactional_calls = suspended.transactional_calls;
+        sbox.function_stack_sizes = suspended.function_stack_sizes.clone();
+        sbox.recovery_policy = suspended.recovery_policy.clone();
+        sbox.consecutive_restarts = suspended.consecutive_restarts;
+        sbox.slow_call_policy = suspended.slow_call_policy.clone();
+        sbox.measurement = suspended.measurement;
+        sbox.guest_functions = suspended.guest_functions.clone();
+        sbox.control_page = suspended.control_page.clone();
+        sbox.control_page
+            .set_scratch(sbox.mem_mgr.scratch_mem.clone());
+        // The new virtual machine starts with the default vCPU state, so
+        // restore the snapshot to get the vCPU state it was taken with. The
+        // guest's clocks are the new VM's, and are not restored.
+        sbox.restore(snapshot)?;
+        // The coverage counters in the restored memory are those the
+        // suspended sandbox had, so coverage carries on from its baseline
+        sbox.coverage = suspended.coverage.clone();
+        if let Some(profile) = suspended.profile.take() {
+            match sbox.vm.start_profiler(profile) {
+                Ok(profiler) => sbox.profiler = Some(profiler),
+                Err(e) => tracing::warn!("Failed to resume profiling the sandbox: {e}"),
+            }
+        }
+        sbox.pt_root_finder = suspended.pt_root_finder.take();
+        if let Some(registration) = &suspended.registration {
+            registration.resumed(sbox.vm.interrupt_handle(), sbox.memory_size());
+        }
+        sbox.registration = suspended.registration.clone();
+        sbox.events.set_active(true);
+        Ok(sbox)
+    }
+
     /// Calls a guest function by name with the specified arguments.
     ///
     /// Changes made to the sandbox during execution are *not* persisted.
@@ -449,8 +913,9 @@ impl MultiUseSandbox {
     /// ## Poisoned Sandbox
     ///
     /// This method will return [`crate::HyperlightError::PoisonedSandbox`] if the sandbox
-    /// is already poisoned before the call. Use [`restore()`](Self::restore) to recover from
-    /// a poisoned state.
+    /// is already poisoned before the call, unless its
+    /// [recovery policy](Self::set_recovery_policy) restarts the guest first. Use
+    /// [`restore()`](Self::restore) to recover from a poisoned state manually.
     ///
     /// ## Sandbox Poisoning
     ///
@@ -524,113 +989,906 @@ impl MultiUseSandbox {
         func_name: &str,
         args: impl ParameterTuple,
     ) -> Result<Output> {
-        if self.poisoned {
-            return Err(crate::HyperlightError::PoisonedSandbox);
-        }
-        // Reset snapshot since we are mutating the sandbox state
-        self.snapshot = None;
-        maybe_time_and_emit_guest_call(func_name, || {
-            let ret = self.call_guest_function_by_name_no_reset(
-                func_name,
-                Output::TYPE,
-                args.into_value(),
-            );
-            // Use the ? operator to allow converting any hyperlight_common::func::Error
-            // returned by from_value into a HyperlightError
-            let ret = Output::from_value(ret?)?;
-            Ok(ret)
-        })
+        self.call_impl(func_name, owned_parameter_refs(args), None)
     }
 
-    /// Maps a region of host memory into the sandbox address space.
+    /// Calls a guest function by name with arguments that may borrow from
+    /// the caller.
     ///
-    /// The base address and length must meet platform alignment requirements
-    /// (typically page-aligned). The `region_type` field is ignored as guest
-    /// page table entries are not created.
+    /// This behaves like [`call()`](Self::call), except that arguments of
+    /// type `&[u8]` are also accepted, which avoids the intermediate
+    /// `Vec<u8>` the caller would otherwise copy them into. They are still
+    /// serialised into a flatbuffer, which is then copied into the guest's
+    /// input buffer. The guest function receives them as `Vec<u8>`.
     ///
-    /// ## Poisoned Sandbox
+    /// Only guest call arguments can be borrowed: the arguments of host
+    /// functions the guest calls are always decoded into owned values, and
+    /// are not exposed as views into the sandbox's memory.
     ///
-    /// This method will return [`crate::HyperlightError::PoisonedSandbox`] if the sandbox
-    /// is currently poisoned. Use [`restore()`](Self::restore) to recover from a poisoned state.
+    /// # Examples
     ///
-    /// # Safety
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
     ///
-    /// The caller must ensure the host memory region remains valid and unmodified
-    /// for the lifetime of `self`.
-    #[instrument(err(Debug), skip(self, rgn), parent = Span::current())]
-    pub unsafe fn map_region(&mut self, rgn: &MemoryRegion) -> Result<()> {
-        if self.poisoned {
-            return Err(crate::HyperlightError::PoisonedSandbox);
-        }
-        if rgn.flags.contains(MemoryRegionFlags::WRITE) {
-            // TODO: Implement support for writable mappings, which
-            // need to be registered with the memory manager so that
-            // writes can be rolled back when necessary.
-            log_then_return!("TODO: Writable mappings not yet supported");
-        }
-        // Reset snapshot since we are mutating the sandbox state
-        self.snapshot = None;
-        unsafe { self.vm.map_region(rgn) }.map_err(HyperlightVmError::MapRegion)?;
-        self.mem_mgr.mapped_rgns += 1;
-        Ok(())
+    /// let image = std::fs::read("image.png")?;
+    /// let thumbnail: Vec<u8> = sandbox.call_borrowed("Thumbnail", (&image[..], 128_u32))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip(self, args), parent = Span::current())]
+    pub fn call_borrowed<'a, Output: SupportedReturnType>(
+        &mut self,
+        func_name: &str,
+        args: impl ParameterRefTuple<'a>,
+    ) -> Result<Output> {
+        self.call_impl(func_name, args.into_parameter_refs(), None)
     }
 
-    /// Map the contents of a file into the guest at a particular address
+    /// Calls a guest function by name, passing it a callback into the host
+    /// that it can call any number of times until the call returns, such
+    /// as to report progress or to pull in more data.
     ///
-    /// An optional `label` identifies this mapping in the PEB's
-    /// `FileMappingInfo` array (max 63 bytes, defaults to the file name).
+    /// `callback` is registered as a host function under a unique name for
+    /// the duration of the call only. `args` is given that name and returns
+    /// the arguments of the call, so that the name can be passed on to the
+    /// guest, which calls the callback like any other host function. The
+    /// callback requires no capabilities, and is removed once the call
+    /// returns, whether it succeeded or not.
     ///
-    /// Returns the length of the mapping in bytes.
+    /// # Examples
     ///
-    /// ## Poisoned Sandbox
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
     ///
-    /// This method will return [`crate::HyperlightError::PoisonedSandbox`] if the sandbox
-    /// is currently poisoned. Use [`restore()`](Self::restore) to recover from a poisoned state.
-    #[instrument(err(Debug), skip(self, file_path, guest_base, label), parent = Span::current())]
-    pub fn map_file_cow(
+    /// let report_progress = |percent: u32| {
+    ///     println!("{percent}% done");
+    ///     Ok(())
+    /// };
+    /// let summary: String = sandbox.call_with_callback(
+    ///     "Process",
+    ///     report_progress,
+    ///     |progress| (progress, "input".to_string()),
+    /// )?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip(self, callback, args), parent = Span::current())]
+    pub fn call_with_callback<Output, Args, CallbackOutput, CallbackArgs>(
         &mut self,
-        file_path: &Path,
-        guest_base: u64,
-        label: Option<&str>,
-    ) -> Result<u64> {
-        if self.poisoned {
-            return Err(crate::HyperlightError::PoisonedSandbox);
-        }
+        func_name: &str,
+        callback: impl Into<HostFunction<CallbackOutput, CallbackArgs>>,
+        args: impl FnOnce(String) -> Args,
+    ) -> Result<Output>
+    where
+        Output: SupportedReturnType,
+        Args: ParameterTuple,
+        CallbackOutput: SupportedReturnType,
+        CallbackArgs: ParameterTuple,
+    {
+        static NEXT_CALLBACK_ID: AtomicU64 = AtomicU64::new(0);
+        let name = format!(
+            "__HyperlightCallback{}",
+            NEXT_CALLBACK_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        self.register_host_function(&name, callback)?;
+        let res = self.call(func_name, args(name.clone()));
+        let unregistered = self.unregister_host_function(&name);
+        res.and_then(|output| unregistered.map(|()| output))
+    }
 
-        // Pre-check the file mapping limit before doing any expensive
-        // OS or VM work. The PEB count is the source of truth.
-        #[cfg(feature = "nanvix-unstable")]
-        let current_count = self
-            .mem_mgr
-            .shared_mem
-            .read::<u64>(self.mem_mgr.layout.get_file_mappings_size_offset())?
-            as usize;
-        #[cfg(feature = "nanvix-unstable")]
-        if current_count >= hyperlight_common::mem::MAX_FILE_MAPPINGS {
-            return Err(crate::HyperlightError::Error(format!(
-                "map_file_cow: file mapping limit reached ({} of {})",
-                current_count,
-                hyperlight_common::mem::MAX_FILE_MAPPINGS,
-            )));
+    /// Calls a guest function by name, allowing it to be cancelled through
+    /// `token`.
+    ///
+    /// This behaves like [`call()`](Self::call), except that calling
+    /// [`CancellationToken::cancel()`] from any thread while the call is in
+    /// progress interrupts it, and the call returns
+    /// [`crate::HyperlightError::ExecutionCanceledByHost`]. The token is
+    /// only attached to this sandbox for the duration of this call, so
+    /// cancelling it afterwards has no effect on later calls.
+    ///
+    /// If the token has already been cancelled, the guest is not entered at
+    /// all and the sandbox is left untouched.
+    ///
+    /// ## Sandbox Poisoning
+    ///
+    /// A call that is cancelled while running leaves the sandbox poisoned,
+    /// exactly as if [`InterruptHandle::kill()`] had been called. Use
+    /// [`restore()`](Self::restore) to recover.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use hyperlight_host::sandbox::CancellationToken;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// // Hand a clone of the token to whatever owns the request
+    /// let token = CancellationToken::new();
+    /// let request_token = token.clone();
+    /// std::thread::spawn(move || {
+    ///     // ... the client went away
+    ///     request_token.cancel();
+    /// });
+    ///
+    /// let result = sandbox.call_with_cancellation::<i32>("LongRunningFunction", (), &token);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip(self, args, token), parent = Span::current())]
+    pub fn call_with_cancellation<Output: SupportedReturnType>(
+        &mut self,
+        func_name: &str,
+        args: impl ParameterTuple,
+        token: &CancellationToken,
+    ) -> Result<Output> {
+        let recovered = self.recover_if_poisoned();
+        self.labelled(recovered)?;
+        if token.is_cancelled() {
+            return self.labelled(Err(HyperlightError::ExecutionCanceledByHost()));
         }
+        self.call_impl(func_name, owned_parameter_refs(args), Some(token))
+    }
 
-        // Phase 1: host-side OS work (open file, create mapping)
-        let mut prepared = prepare_file_cow(file_path, guest_base, label)?;
+    /// Calls a guest function by name, making the call again if it fails in
+    /// a way that `policy` retries.
+    ///
+    /// This behaves like [`call()`](Self::call), except that a call that
+    /// fails with a [`TransientFailure`](crate::sandbox::TransientFailure)
+    /// the policy retries on is made again, up to the policy's maximum
+    /// number of attempts. The error of the last attempt is returned if
+    /// none of them succeed.
+    ///
+    /// A failed attempt may have changed the state of the guest before it
+    /// failed. Use [`set_transactional_calls()`](Self::set_transactional_calls)
+    /// to roll back each failed attempt before the next one is made.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use hyperlight_host::sandbox::{RetryPolicy, TransientFailure};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// // Retries if a rate limited host function the guest calls is throttled
+    /// let policy = RetryPolicy::new()
+    ///     .with_max_attempts(5)
+    ///     .retry_on([TransientFailure::HostFunctionThrottled]);
+    /// let result: String = sandbox.call_with_retry("FetchAndSummarise", 42_u32, &policy)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip(self, args, policy), parent = Span::current())]
+    pub fn call_with_retry<Output: SupportedReturnType>(
+        &mut self,
+        func_name: &str,
+        args: impl ParameterTuple,
+        policy: &RetryPolicy,
+    ) -> Result<Output> {
+        let args = owned_parameter_refs(args);
+        policy.run(|| self.call_impl(func_name, args.clone(), None))
+    }
 
-        // Validate that the full mapped range doesn't overlap the
-        // sandbox's primary shared memory region.
-        let shared_size = self.mem_mgr.shared_mem.mem_size() as u64;
-        let base_addr = crate::mem::layout::SandboxMemoryLayout::BASE_ADDRESS as u64;
-        let shared_end = base_addr.checked_add(shared_size).ok_or_else(|| {
-            crate::HyperlightError::Error("shared memory end overflow".to_string())
-        })?;
-        let mapping_end = guest_base
-            .checked_add(prepared.size as u64)
-            .ok_or_else(|| {
-                crate::HyperlightError::Error(format!(
-                    "map_file_cow: guest address overflow: {:#x} + {:#x}",
-                    guest_base, prepared.size
-                ))
+    /// Calls a guest function that streams its return value back to the
+    /// host in chunks.
+    ///
+    /// The guest function must return nothing, and instead sends its output
+    /// with `hyperlight_guest_bin::host_comm::write_stream_chunk()`. Each
+    /// chunk is passed to `sink` as soon as the guest writes it, before the
+    /// guest carries on, so the output is not limited by the size of the
+    /// output buffer and the host holds no more than one chunk of it at a
+    /// time. To consume the output on another thread, `sink` can send the
+    /// chunks on a bounded channel, which holds the guest back while the
+    /// channel is full.
+    ///
+    /// If `sink` returns an error, the guest is stopped part way through
+    /// the call, which fails and poisons the sandbox.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::io::Write;
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// let mut out = std::fs::File::create("report.csv")?;
+    /// sandbox.call_streaming("GenerateReport", 2025, move |chunk| {
+    ///     Ok(out.write_all(chunk)?)
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_streaming(
+        &mut self,
+        func_name: &str,
+        args: impl ParameterTuple,
+        sink: impl FnMut(&[u8]) -> Result<()> + Send + 'static,
+    ) -> Result<()> {
+        self.set_stream_sink(Some(Box::new(sink)))?;
+        let res = self.call_impl::<()>(func_name, owned_parameter_refs(args), None);
+        let cleared = self.set_stream_sink(None);
+        res.and(cleared)
+    }
+
+    /// Set where the chunks of a streamed return value are sent
+    fn set_stream_sink(&mut self, sink: Option<StreamSink>) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_stream_sink(sink);
+        Ok(())
+    }
+
+    /// Calls several guest functions in turn with a single entry into the
+    /// guest, saving the cost of entering and leaving the guest for each
+    /// call when making many small calls.
+    ///
+    /// All of the calls are written to the input buffer before the guest is
+    /// entered, and all of their results are left in the output buffer
+    /// before it returns, so the batch must fit in both. Returns the result
+    /// of each call in order, or an error if the batch could not be run. A
+    /// call that fails does not stop the calls after it, unless it poisons
+    /// the sandbox.
+    ///
+    /// With [transactional calls](Self::set_transactional_calls), the
+    /// sandbox is rolled back to its state before the batch if any of the
+    /// calls fails. A poisoned sandbox is recovered before the batch as it
+    /// is before a single call, and each call in the batch is recorded in
+    /// the guest call metrics with an equal share of the batch's duration.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use hyperlight_host::sandbox::GuestCall;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// let calls = [
+    ///     GuestCall::new::<i32>("Add", (1_i32, 2_i32)),
+    ///     GuestCall::new::<String>("Echo", "hello".to_string()),
+    /// ];
+    /// for result in sandbox.call_guest_functions_batch(&calls)? {
+    ///     println!("{:?}", result?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip(self, calls), parent = Span::current())]
+    pub fn call_guest_functions_batch(
+        &mut self,
+        calls: &[GuestCall],
+    ) -> Result<Vec<Result<ReturnValue>>> {
+        let res = self.call_guest_functions_batch_unlabelled(calls);
+        let res = res.map(|results| {
+            results
+                .into_iter()
+                .map(|result| self.labelled(result))
+                .collect()
+        });
+        self.labelled(res)
+    }
+
+    fn call_guest_functions_batch_unlabelled(
+        &mut self,
+        calls: &[GuestCall],
+    ) -> Result<Vec<Result<ReturnValue>>> {
+        let calls: Vec<_> = calls
+            .iter()
+            .map(|c| (c.function_name.as_str(), c.return_type, c.args.as_slice()))
+            .collect();
+        let names: Vec<_> = calls.iter().map(|(name, _, _)| *name).collect();
+        self.guest_calls(&names, |sbox| {
+            if calls.is_empty() {
+                return Ok(Vec::new());
+            }
+            sbox.call_guest_functions_no_reset(&calls, None)
+        })
+    }
+
+    pub(super) fn call_impl<Output: SupportedReturnType>(
+        &mut self,
+        func_name: &str,
+        args: Vec<ParameterRef<'_>>,
+        token: Option<&CancellationToken>,
+    ) -> Result<Output> {
+        let res = self
+            .guest_calls(&[func_name], |sbox| {
+                let ret =
+                    sbox.call_guest_function_by_name_no_reset(func_name, Output::TYPE, args, token);
+                // Use the ? operator to allow converting any hyperlight_common::func::Error
+                // returned by from_value into a HyperlightError
+                Ok(vec![ret.and_then(|ret| Ok(Output::from_value(ret)?))])
+            })
+            .and_then(|mut results| {
+                results
+                    .pop()
+                    .ok_or_else(|| new_error!("The guest returned no result"))?
+            });
+        self.labelled(res)
+    }
+
+    /// Attach the sandbox's labels to the error `res` failed with
+    fn labelled<T>(&self, res: Result<T>) -> Result<T> {
+        res.map_err(|e| e.with_labels(&self.labels))
+    }
+
+    /// Make the guest calls to `function_names` with `call`, which returns
+    /// the result of each. The sandbox is recovered first if it is
+    /// poisoned, the calls are timed, and if any of them fails the sandbox
+    /// is restored to its state before them when calls are transactional.
+    fn guest_calls<T>(
+        &mut self,
+        function_names: &[&str],
+        call: impl FnOnce(&mut Self) -> Result<Vec<Result<T>>>,
+    ) -> Result<Vec<Result<T>>> {
+        self.recover_if_poisoned()?;
+        let checkpoint = if self.transactional_calls {
+            Some(self.snapshot()?)
+        } else {
+            None
+        };
+        // Reset snapshot since we are mutating the sandbox state
+        self.snapshot = None;
+        let labels = self.labels.clone();
+        let res = maybe_time_and_emit_guest_calls(function_names, &labels, || call(self));
+        let failed = match &res {
+            Ok(results) => results.iter().any(Result::is_err),
+            Err(_) => true,
+        };
+        match (failed, checkpoint) {
+            (false, _) => self.consecutive_restarts = 0,
+            (true, Some(checkpoint)) => self.restore(checkpoint)?,
+            (true, None) => {}
+        }
+        res
+    }
+
+    /// Restart the guest as the recovery policy says if the sandbox is
+    /// poisoned, or fail with [`HyperlightError::PoisonedSandbox`] if the
+    /// policy does not allow it
+    fn recover_if_poisoned(&mut self) -> Result<()> {
+        if !self.poisoned {
+            return Ok(());
+        }
+        let Some((snapshot, delay)) = self.recovery_policy.next_restart(self.consecutive_restarts)
+        else {
+            return Err(HyperlightError::PoisonedSandbox);
+        };
+        self.consecutive_restarts += 1;
+        tracing::warn!(
+            restarts = self.consecutive_restarts,
+            "restarting poisoned sandbox after {:?}",
+            delay
+        );
+        thread::sleep(delay);
+        match snapshot {
+            Some(snapshot) => self.restore(snapshot),
+            None => self.reset(),
+        }
+    }
+
+    /// Set how the sandbox recovers after a guest call poisons it. See
+    /// [`RecoveryPolicy`].
+    pub fn set_recovery_policy(&mut self, policy: RecoveryPolicy) {
+        self.recovery_policy = policy;
+        self.consecutive_restarts = 0;
+    }
+
+    /// Returns the sandbox's recovery policy. See
+    /// [`set_recovery_policy()`](Self::set_recovery_policy).
+    pub fn recovery_policy(&self) -> &RecoveryPolicy {
+        &self.recovery_policy
+    }
+
+    /// Set when guest calls are reported as slow, or stop reporting them
+    /// with `None`. See [`SlowCallPolicy`].
+    pub fn set_slow_call_policy(&mut self, policy: Option<SlowCallPolicy>) {
+        self.slow_call_policy = policy;
+    }
+
+    /// Returns the sandbox's slow call policy, if it has one. See
+    /// [`set_slow_call_policy()`](Self::set_slow_call_policy).
+    pub fn slow_call_policy(&self) -> Option<&SlowCallPolicy> {
+        self.slow_call_policy.as_ref()
+    }
+
+    /// Enables or disables transactional guest calls.
+    ///
+    /// When enabled, every guest call made through [`call()`](Self::call),
+    /// [`call_with_timeout()`](Self::call_with_timeout) or
+    /// [`call_with_cancellation()`](Self::call_with_cancellation) runs
+    /// against a snapshot of the sandbox taken just before the call. If the
+    /// call returns successfully its changes are kept; if it fails for any
+    /// reason, including a guest panic, a timeout or a cancellation, the
+    /// sandbox is restored to that snapshot before the error is returned.
+    /// A failed call therefore never leaves the guest heap in a half-mutated
+    /// state, and never leaves the sandbox poisoned.
+    ///
+    /// The snapshot is a full one, as taken by [`snapshot()`](Self::snapshot):
+    /// which pages the guest wrote to is not tracked, so every mapped page
+    /// is copied and hashed before each call, and the whole snapshot is
+    /// restored after a failed one. The cost of a call therefore grows with
+    /// the size of guest memory, not with how much of it the call touched,
+    /// so this is disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    /// sandbox.set_transactional_calls(true);
+    ///
+    /// sandbox.call::<i32>("AddToStatic", 5)?;
+    /// // This call fails after modifying the static, so its change is discarded
+    /// assert!(sandbox.call::<i32>("AddToStaticAndFail", ()).is_err());
+    /// assert!(!sandbox.poisoned());
+    /// assert_eq!(sandbox.call::<i32>("GetStatic", ())?, 5);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_transactional_calls(&mut self, enabled: bool) {
+        self.transactional_calls = enabled;
+    }
+
+    /// Returns whether transactional guest calls are enabled. See
+    /// [`set_transactional_calls()`](Self::set_transactional_calls).
+    pub fn transactional_calls(&self) -> bool {
+        self.transactional_calls
+    }
+
+    /// Override the maximum size of the guest's main stack for calls to
+    /// `func_name`, in place of the
+    /// [configured default](SandboxConfiguration::set_max_stack_size).
+    /// The size is rounded up to a whole number of pages, and 0 means no
+    /// limit. A call that overflows its stack is aborted.
+    ///
+    /// The size may be larger or smaller than the default. Stack pages are
+    /// mapped from scratch memory as the stack grows, so a larger size
+    /// lets `func_name` use more of the
+    /// [scratch memory](SandboxConfiguration::set_scratch_size) for its
+    /// stack than other functions may, and a smaller one stops its stack
+    /// from growing past the size, which is useful for functions whose
+    /// stack usage depends on untrusted input. Sizes larger than the
+    /// scratch memory are rejected with
+    /// [`InvalidConfiguration`](HyperlightError::InvalidConfiguration), as
+    /// the stack could never grow to them.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    /// sandbox.set_function_stack_size("ParseDocument", 64 * 1024)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_function_stack_size(
+        &mut self,
+        func_name: impl Into<String>,
+        max_stack_size: usize,
+    ) -> Result<()> {
+        let scratch_size = self.config.get_scratch_size();
+        if max_stack_size > scratch_size {
+            return Err(HyperlightError::InvalidConfiguration(format!(
+                "stack size {max_stack_size} is larger than the scratch memory ({scratch_size} bytes)"
+            )));
+        }
+        self.function_stack_sizes
+            .insert(func_name.into(), max_stack_size);
+        Ok(())
+    }
+
+    /// Replace the implementation of the registered host function `name`
+    /// with `host_func`, keeping the capabilities and syscall filter it was
+    /// registered with.
+    ///
+    /// The old implementation is swapped out in a single step and dropped
+    /// immediately, so the guest never sees the function missing, and any
+    /// values captured by the old closure, such as credentials, are
+    /// released. As guest calls borrow the sandbox mutably, a host function
+    /// call made by a guest is never running while it is replaced; the new
+    /// implementation is used from the next host function call on.
+    ///
+    /// Returns [`HyperlightError::HostFunctionNotFound`] if no host
+    /// function named `name` is registered.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut u_sbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?;
+    /// let token = "old-token".to_string();
+    /// u_sbox.register("GetToken", move || Ok(token.clone()))?;
+    /// let mut sandbox: MultiUseSandbox = u_sbox.evolve()?;
+    ///
+    /// let token = "new-token".to_string();
+    /// sandbox.replace_host_function("GetToken", move || Ok(token.clone()))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn replace_host_function<Args: ParameterTuple, Output: SupportedReturnType>(
+        &mut self,
+        name: impl AsRef<str>,
+        host_func: impl Into<HostFunction<Output, Args>>,
+    ) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .replace_host_function(
+                name.as_ref(),
+                host_func.into().into(),
+                Args::TYPE,
+                Output::TYPE,
+            )
+    }
+
+    /// The time spent in host functions since this was last called
+    fn take_host_call_time(&self) -> Result<Duration> {
+        Ok(self
+            .host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .take_host_call_time())
+    }
+
+    /// Record every host function the guest invokes with `sink`, or stop
+    /// recording them if it is `None`. See
+    /// [`AuditSink`](crate::sandbox::AuditSink).
+    pub fn set_audit_sink(
+        &mut self,
+        sink: Option<Arc<dyn crate::sandbox::AuditSink>>,
+    ) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_audit_sink(sink);
+        Ok(())
+    }
+
+    /// Trace every call the guest makes out to the host with `tracer`, or
+    /// stop tracing them if it is `None`. See
+    /// [`OutcallTracer`](crate::sandbox::OutcallTracer).
+    pub fn set_outcall_tracer(
+        &mut self,
+        tracer: Option<crate::sandbox::OutcallTracer>,
+    ) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_outcall_tracer(tracer);
+        Ok(())
+    }
+
+    /// Limit how many log records the guest may send the host, or remove
+    /// the limit if it is `None`. See
+    /// [`GuestLogRateLimit`](crate::sandbox::GuestLogRateLimit).
+    pub fn set_guest_log_rate_limit(
+        &mut self,
+        limit: Option<crate::sandbox::GuestLogRateLimit>,
+    ) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_guest_log_rate_limit(limit);
+        Ok(())
+    }
+
+    /// Deliver the lifecycle events of the sandbox to `subscriber`. See
+    /// [`EventSubscriber`](crate::sandbox::EventSubscriber).
+    pub fn add_event_subscriber(
+        &mut self,
+        subscriber: impl crate::sandbox::EventSubscriber + 'static,
+    ) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .add_event_subscriber(Arc::new(subscriber));
+        Ok(())
+    }
+
+    /// Remove the registered host function `name`. Later calls to it from
+    /// the guest fail as if it had never been registered.
+    ///
+    /// Returns [`HyperlightError::HostFunctionNotFound`] if no host
+    /// function named `name` is registered.
+    pub fn unregister_host_function(&mut self, name: impl AsRef<str>) -> Result<()> {
+        let name = name.as_ref();
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .unregister_host_function(name)
+            .map(drop)
+            .ok_or_else(|| HyperlightError::HostFunctionNotFound(name.to_string()))
+    }
+
+    /// Returns the labels set on the sandbox with
+    /// [`UninitializedSandbox::set_label()`]
+    pub fn labels(&self) -> &SandboxLabels {
+        &self.labels
+    }
+
+    /// Run the guest's vCPU in the given cgroup during guest calls, or
+    /// stop doing so if `cgroup` is `None`.
+    ///
+    /// The thread making each guest call is moved into the cgroup when the
+    /// call starts and back to its original cgroup when it returns, so the
+    /// kernel bounds the CPU time and memory the guest can use. See
+    /// [`VcpuCgroup`] for the requirements on the cgroup.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use hyperlight_host::sandbox::VcpuCgroup;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// let cgroup = VcpuCgroup::new("/sys/fs/cgroup/my-service/guests")?;
+    /// // Allow the guest at most 20% of one CPU
+    /// cgroup.set_cpu_max(Some(Duration::from_millis(20)), Duration::from_millis(100))?;
+    /// sandbox.set_vcpu_cgroup(Some(cgroup));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn set_vcpu_cgroup(&mut self, cgroup: Option<VcpuCgroup>) {
+        self.vcpu_cgroup = cgroup;
+    }
+
+    /// Calls a guest function by name, cancelling it if it does not
+    /// complete within `timeout`.
+    ///
+    /// This behaves like [`call()`](Self::call), except that the sandbox's
+    /// [`InterruptHandle`] is armed for the duration of the call. If the
+    /// timeout expires before the guest function returns, the call is
+    /// cancelled and [`crate::HyperlightError::GuestCallTimedOut`] is
+    /// returned.
+    ///
+    /// ## Sandbox Poisoning
+    ///
+    /// A call that times out leaves the sandbox poisoned, exactly as if
+    /// [`InterruptHandle::kill()`] had been called. Use
+    /// [`restore()`](Self::restore) to recover.
+    ///
+    /// Note that, as with [`InterruptHandle::kill()`], a host function
+    /// that is executing when the timeout expires is not interrupted; the
+    /// guest is cancelled as soon as the host function returns.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use hyperlight_host::{HyperlightError, MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// let snapshot = sandbox.snapshot()?;
+    /// match sandbox.call_with_timeout::<i32>("Spin", (), Duration::from_millis(100)) {
+    ///     Err(HyperlightError::GuestCallTimedOut(_)) => sandbox.restore(snapshot)?,
+    ///     other => { other?; }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip(self, args), parent = Span::current())]
+    pub fn call_with_timeout<Output: SupportedReturnType>(
+        &mut self,
+        func_name: &str,
+        args: impl ParameterTuple,
+        timeout: Duration,
+    ) -> Result<Output> {
+        let recovered = self.recover_if_poisoned();
+        self.labelled(recovered)?;
+
+        let token = CancellationToken::new();
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+        let watchdog = {
+            let token = token.clone();
+            let timed_out = timed_out.clone();
+            thread::spawn(move || {
+                // The sender is dropped as soon as the call returns, which
+                // wakes us up with `Disconnected` before the timeout fires.
+                if let Err(RecvTimeoutError::Timeout) = done_rx.recv_timeout(timeout) {
+                    // Set the flag before cancelling so that the calling thread
+                    // is guaranteed to observe it once cancellation completes.
+                    timed_out.store(true, Ordering::Release);
+                    token.cancel();
+                }
+            })
+        };
+
+        let res = self.call_impl(func_name, owned_parameter_refs(args), Some(&token));
+
+        drop(done_tx);
+        // The watchdog thread cannot panic, so there is nothing to report here.
+        let _ = watchdog.join();
+
+        match res {
+            Err(e)
+                if matches!(e.unlabelled(), HyperlightError::ExecutionCanceledByHost())
+                    && timed_out.load(Ordering::Acquire) =>
+            {
+                self.labelled(Err(HyperlightError::GuestCallTimedOut(timeout)))
+            }
+            res => res,
+        }
+    }
+
+    /// Shuts the sandbox down, giving the guest a chance to clean up first.
+    ///
+    /// The guest is asked to shut down by calling the shutdown handler it
+    /// registered (see
+    /// [`SHUTDOWN_FUNCTION_NAME`](hyperlight_common::flatbuffer_wrappers::function_call::SHUTDOWN_FUNCTION_NAME)),
+    /// which may in turn call host functions to release any external
+    /// resources it holds. If the handler does not return within `timeout`,
+    /// the guest is killed as if by [`InterruptHandle::kill()`] and
+    /// [`HyperlightError::GuestCallTimedOut`] is returned. Guests that did
+    /// not register a shutdown handler are shut down immediately.
+    ///
+    /// The sandbox is consumed whatever the outcome. A poisoned sandbox
+    /// cannot run the shutdown handler, so shutting one down returns
+    /// [`HyperlightError::PoisonedSandbox`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// sandbox.shutdown(Duration::from_secs(1))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip(self), parent = Span::current())]
+    pub fn shutdown(mut self, timeout: Duration) -> Result<()> {
+        match self.call_with_timeout::<()>(SHUTDOWN_FUNCTION_NAME, (), timeout) {
+            Err(HyperlightError::GuestError {
+                code: ErrorCode::GuestFunctionNotFound,
+                ..
+            }) => Ok(()),
+            res => res,
+        }
+    }
+
+    /// Maps a region of host memory into the sandbox address space.
+    ///
+    /// The base address and length must meet platform alignment requirements
+    /// (typically page-aligned). The `region_type` field is ignored as guest
+    /// page table entries are not created.
+    ///
+    /// ## Poisoned Sandbox
+    ///
+    /// This method will return [`crate::HyperlightError::PoisonedSandbox`] if the sandbox
+    /// is currently poisoned. Use [`restore()`](Self::restore) to recover from a poisoned state.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure the host memory region remains valid and unmodified
+    /// for the lifetime of `self`.
+    #[instrument(err(Debug), skip(self, rgn), parent = Span::current())]
+    pub unsafe fn map_region(&mut self, rgn: &MemoryRegion) -> Result<()> {
+        if self.poisoned {
+            return Err(crate::HyperlightError::PoisonedSandbox);
+        }
+        if rgn.flags.contains(MemoryRegionFlags::WRITE) {
+            // TODO: Implement support for writable mappings, which
+            // need to be registered with the memory manager so that
+            // writes can be rolled back when necessary.
+            log_then_return!("TODO: Writable mappings not yet supported");
+        }
+        // Reset snapshot since we are mutating the sandbox state
+        self.snapshot = None;
+        unsafe { self.vm.map_region(rgn) }.map_err(HyperlightVmError::MapRegion)?;
+        self.mem_mgr.mapped_rgns += 1;
+        Ok(())
+    }
+
+    /// Map the contents of a file into the guest at a particular address
+    ///
+    /// An optional `label` identifies this mapping in the PEB's
+    /// `FileMappingInfo` array (max 63 bytes, defaults to the file name).
+    ///
+    /// Returns the length of the mapping in bytes.
+    ///
+    /// ## Poisoned Sandbox
+    ///
+    /// This method will return [`crate::HyperlightError::PoisonedSandbox`] if the sandbox
+    /// is currently poisoned. Use [`restore()`](Self::restore) to recover from a poisoned state.
+    #[instrument(err(Debug), skip(self, file_path, guest_base, label), parent = Span::current())]
+    pub fn map_file_cow(
+        &mut self,
+        file_path: &Path,
+        guest_base: u64,
+        label: Option<&str>,
+    ) -> Result<u64> {
+        if self.poisoned {
+            return Err(crate::HyperlightError::PoisonedSandbox);
+        }
+
+        // Pre-check the file mapping limit before doing any expensive
+        // OS or VM work. The PEB count is the source of truth.
+        #[cfg(feature = "nanvix-unstable")]
+        let current_count = self
+            .mem_mgr
+            .shared_mem
+            .read::<u64>(self.mem_mgr.layout.get_file_mappings_size_offset())?
+            as usize;
+        #[cfg(feature = "nanvix-unstable")]
+        if current_count >= hyperlight_common::mem::MAX_FILE_MAPPINGS {
+            return Err(crate::HyperlightError::Error(format!(
+                "map_file_cow: file mapping limit reached ({} of {})",
+                current_count,
+                hyperlight_common::mem::MAX_FILE_MAPPINGS,
+            )));
+        }
+
+        // Phase 1: host-side OS work (open file, create mapping)
+        let mut prepared = prepare_file_cow(file_path, guest_base, label)?;
+
+        // Validate that the full mapped range doesn't overlap the
+        // sandbox's primary shared memory region.
+        let shared_size = self.mem_mgr.shared_mem.mem_size() as u64;
+        let base_addr = crate::mem::layout::SandboxMemoryLayout::BASE_ADDRESS as u64;
+        let shared_end = base_addr.checked_add(shared_size).ok_or_else(|| {
+            crate::HyperlightError::Error("shared memory end overflow".to_string())
+        })?;
+        let mapping_end = guest_base
+            .checked_add(prepared.size as u64)
+            .ok_or_else(|| {
+                crate::HyperlightError::Error(format!(
+                    "map_file_cow: guest address overflow: {:#x} + {:#x}",
+                    guest_base, prepared.size
+                ))
             })?;
         if guest_base < shared_end && mapping_end > base_addr {
             return Err(crate::HyperlightError::Error(format!(
@@ -692,7 +1950,7 @@ impl MultiUseSandbox {
     ///
     /// This method will return [`crate::HyperlightError::PoisonedSandbox`] if the sandbox
     /// is currently poisoned. Use [`restore()`](Self::restore) to recover from a poisoned state.
-    #[cfg(feature = "fuzzing")]
+    #[cfg(any(feature = "fuzzing", feature = "console"))]
     #[instrument(err(Debug), skip(self, args), parent = Span::current())]
     pub fn call_type_erased_guest_function_by_name(
         &mut self,
@@ -701,21 +1959,55 @@ impl MultiUseSandbox {
         args: Vec<ParameterValue>,
     ) -> Result<ReturnValue> {
         if self.poisoned {
-            return Err(crate::HyperlightError::PoisonedSandbox);
+            return self.labelled(Err(crate::HyperlightError::PoisonedSandbox));
         }
         // Reset snapshot since we are mutating the sandbox state
         self.snapshot = None;
-        maybe_time_and_emit_guest_call(func_name, || {
-            self.call_guest_function_by_name_no_reset(func_name, ret_type, args)
+        let labels = self.labels.clone();
+        let res = maybe_time_and_emit_guest_calls(&[func_name], &labels, || {
+            let args = args.into_iter().map(ParameterRef::Value).collect();
+            Ok(vec![self.call_guest_function_by_name_no_reset(
+                func_name, ret_type, args, None,
+            )])
         })
+        .and_then(|mut results| {
+            results
+                .pop()
+                .ok_or_else(|| new_error!("The guest returned no result"))?
+        });
+        self.labelled(res)
     }
 
     fn call_guest_function_by_name_no_reset(
         &mut self,
         function_name: &str,
         return_type: ReturnType,
-        args: Vec<ParameterValue>,
+        args: Vec<ParameterRef<'_>>,
+        token: Option<&CancellationToken>,
     ) -> Result<ReturnValue> {
+        #[cfg(feature = "otel")]
+        let span = crate::otel::guest_call_span(function_name, &self.labels);
+        #[cfg(feature = "otel")]
+        let _entered = span.enter();
+        let result = self
+            .call_guest_functions_no_reset(&[(function_name, return_type, &args)], token)
+            .and_then(|mut results| {
+                results
+                    .pop()
+                    .ok_or_else(|| new_error!("The guest returned no result"))?
+            });
+        #[cfg(feature = "otel")]
+        crate::otel::record_guest_call_result(&span, &result);
+        result
+    }
+
+    /// Make each of `calls` in a single entry into the guest, returning the
+    /// result of each call, or an error if the guest could not be run
+    fn call_guest_functions_no_reset(
+        &mut self,
+        calls: &[(&str, ReturnType, &[ParameterRef<'_>])],
+        token: Option<&CancellationToken>,
+    ) -> Result<Vec<Result<ReturnValue>>> {
         if self.poisoned {
             return Err(crate::HyperlightError::PoisonedSandbox);
         }
@@ -724,55 +2016,172 @@ impl MultiUseSandbox {
         // Any kill() that completed (even partially) BEFORE this line has NO effect on this call.
         self.vm.clear_cancel();
 
-        let res = (|| {
-            let estimated_capacity = estimate_flatbuffer_capacity(function_name, &args);
-
-            let fc = FunctionCall::new(
-                function_name.to_string(),
-                Some(args),
-                FunctionCallType::Guest,
-                return_type,
-            );
+        // The token must only be attached after the stale cancellation has
+        // been cleared, otherwise a cancel() racing with the start of the
+        // call could be lost.
+        if let Some(token) = token {
+            token.attach(self.vm.interrupt_handle());
+        }
 
-            let mut builder = FlatBufferBuilder::with_capacity(estimated_capacity);
-            let buffer = fc.encode(&mut builder);
+        // Events emitted during the call, including errors, carry the
+        // sandbox's labels
+        let _span = (!self.labels.is_empty())
+            .then(|| tracing::info_span!("sandbox", labels = %self.labels).entered());
 
-            self.mem_mgr.write_guest_function_call(buffer)?;
+        // Metrics of a batch of calls are not of any one function
+        let function_name = match calls {
+            [(function_name, _, _)] => Some(*function_name),
+            _ => None,
+        };
+        self.events
+            .emit(|subscriber, labels| subscriber.on_call_start(labels, function_name));
+        self.control_page.call_started();
+        if let Some(registration) = &self.registration {
+            registration.call_started();
+        }
+        let probe_name = function_name.unwrap_or_default();
+        usdt::probe!(
+            "guest__entry",
+            self.id,
+            probe_name.as_ptr(),
+            probe_name.len()
+        );
+        etw::guest_call_start(self.id, probe_name);
+        perfmon::guest_call();
+        let call_start = Instant::now();
+        let slow_call_watchdog = self.slow_call_policy.as_ref().and_then(|policy| {
+            self.vm.start_slow_call_watchdog(
+                policy,
+                function_name,
+                &self.labels,
+                call_start,
+                self.guest_symbolizer.clone(),
+            )
+        });
+        let mut phases = CallPhaseTimer::start();
+        let res = (|| {
+            let serialization = Instant::now();
+            let buffers = calls
+                .iter()
+                .map(|(function_name, return_type, args)| {
+                    self.config.get_call_encoding().encode_function_call(
+                        function_name,
+                        args,
+                        FunctionCallType::Guest,
+                        *return_type,
+                    )
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            if let Some(log) = self
+                .host_funcs
+                .try_lock()
+                .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+                .execution_log()
+            {
+                log.guest_calls(&buffers)?;
+            }
+            // The input buffer is a stack, so the calls are pushed last
+            // first for the guest to pop them in order
+            for buffer in buffers.iter().rev() {
+                self.mem_mgr.write_guest_function_call(buffer)?;
+            }
+            phases.serialized(serialization);
 
+            let max_stack_size = calls
+                .iter()
+                .map(|(function_name, _, _)| {
+                    self.function_stack_sizes
+                        .get(*function_name)
+                        .copied()
+                        .unwrap_or(self.config.get_max_stack_size())
+                })
+                .max()
+                .unwrap_or(self.config.get_max_stack_size());
+            self.mem_mgr
+                .write_stack_policy(max_stack_size, self.config.get_zero_stack_between_calls())?;
+            self.mem_mgr
+                .write_trace_context(current_trace_parent().as_ref())?;
+
+            #[cfg(target_os = "linux")]
+            let cgroup_membership = self.vcpu_cgroup.as_ref().map(|c| c.enter()).transpose()?;
+
+            // Host function time left over from a call that failed before
+            // it was taken is not this call's
+            self.take_host_call_time()?;
+            let dispatch = Instant::now();
             let dispatch_res = self.vm.dispatch_call_from_host(
                 &mut self.mem_mgr,
                 &self.host_funcs,
                 #[cfg(gdb)]
                 self.dbg_mem_access_fn.clone(),
             );
+            phases.dispatched(dispatch, self.take_host_call_time()?);
+
+            #[cfg(target_os = "linux")]
+            drop(cgroup_membership);
 
             // Convert dispatch errors to HyperlightErrors to maintain backwards compatibility
             // but first determine if sandbox should be poisoned
             if let Err(e) = dispatch_res {
                 let (error, should_poison) = e.promote();
                 self.poisoned |= should_poison;
+                if matches!(error, HyperlightError::ExecutionCanceledByHost()) {
+                    let labels = call_metric_labels(&self.labels, function_name);
+                    metrics::counter!(METRIC_GUEST_CANCELLATION, labels).increment(1);
+                    perfmon::guest_cancellation();
+                }
                 return Err(error);
             }
 
-            let guest_result = self.mem_mgr.get_guest_function_call_result()?.into_inner();
-
-            match guest_result {
-                Ok(val) => Ok(val),
-                Err(guest_error) => {
-                    metrics::counter!(
-                        METRIC_GUEST_ERROR,
-                        METRIC_GUEST_ERROR_LABEL_CODE => (guest_error.code as u64).to_string()
-                    )
-                    .increment(1);
-
-                    Err(HyperlightError::GuestError(
-                        guest_error.code,
-                        guest_error.message,
-                    ))
-                }
+            // The output buffer is a stack too, so the last call's result
+            // is on top
+            let serialization = Instant::now();
+            let vm = &self.vm;
+            let mut guest_results = Vec::with_capacity(calls.len());
+            for _ in calls {
+                let guest_result = self
+                    .mem_mgr
+                    .get_guest_function_call_result(|| {
+                        vm.get_root_pt()
+                            .map_err(|e| HyperlightVmError::AccessPageTable(e).into())
+                    })?
+                    .into_inner();
+                guest_results.push(guest_result);
             }
+            guest_results.reverse();
+            phases.serialized(serialization);
+
+            Ok(guest_results
+                .into_iter()
+                .zip(calls)
+                .map(|(guest_result, (function_name, _, _))| match guest_result {
+                    Ok(val) => Ok(val),
+                    Err(guest_error) => {
+                        let mut labels = call_metric_labels(&self.labels, Some(function_name));
+                        labels.push(metrics::Label::new(
+                            METRIC_GUEST_ERROR_LABEL_CODE,
+                            (guest_error.code as u64).to_string(),
+                        ));
+                        metrics::counter!(METRIC_GUEST_ERROR, labels).increment(1);
+
+                        Err(HyperlightError::GuestError {
+                            code: guest_error.code,
+                            message: guest_error.message,
+                            guest_backtrace: self
+                                .guest_symbolizer
+                                .backtrace(&guest_error.backtrace),
+                        })
+                    }
+                })
+                .collect::<Vec<_>>())
         })();
 
+        if let Some(token) = token {
+            token.detach();
+        }
+
+        phases.emit(&self.labels, calls.iter().map(|(name, _, _)| *name));
+
         // Clear partial abort bytes so they don't leak across calls.
         self.mem_mgr.abort_buffer.clear();
 
@@ -781,12 +2190,42 @@ impl MultiUseSandbox {
         // - the serialized guest function result is zeroed out by us (the host) during deserialization, see `get_guest_function_call_result`
         // - any serialized host function call are zeroed out by us (the host) during deserialization, see `get_host_function_call`
         // - any serialized host function result is zeroed out by the guest during deserialization, see `get_host_return_value`
-        if let Err(e) = &res {
+        let errors: Vec<&HyperlightError> = match &res {
+            Ok(results) => results.iter().filter_map(|r| r.as_ref().err()).collect(),
+            Err(e) => vec![e],
+        };
+        let was_poisoned = self.poisoned;
+        if !errors.is_empty() {
             self.mem_mgr.clear_io_buffers();
 
             // Determine if we should poison the sandbox.
-            self.poisoned |= e.is_poison_error();
+            self.poisoned |= errors.iter().any(|e| e.is_poison_error());
+        }
+
+        let killed = errors
+            .iter()
+            .any(|e| matches!(e, HyperlightError::ExecutionCanceledByHost()));
+        if killed {
+            self.events
+                .emit(|subscriber, labels| subscriber.on_killed(labels, function_name));
+        } else if self.poisoned
+            && !was_poisoned
+            && let Some(error) = errors.iter().find(|e| e.is_poison_error())
+        {
+            self.events
+                .emit(|subscriber, labels| subscriber.on_crashed(labels, error));
+        }
+        let duration = call_start.elapsed();
+        if let Some(watchdog) = slow_call_watchdog {
+            watchdog.finish(duration);
         }
+        self.control_page.call_ended();
+        self.publish_state();
+        usdt::probe!("guest__exit", self.id, !errors.is_empty());
+        etw::guest_call_stop(self.id, !errors.is_empty());
+        self.events.emit(|subscriber, labels| {
+            subscriber.on_call_end(labels, function_name, duration, errors.first().copied())
+        });
 
         // Note: clear_call_active() is automatically called when _guard is dropped here
 
@@ -826,6 +2265,39 @@ impl MultiUseSandbox {
         self.vm.interrupt_handle()
     }
 
+    /// Returns a handle to the sandbox's control page, through which the
+    /// host can follow the progress of a guest call and ask the guest to
+    /// stop, without the guest exiting the VM.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use std::thread;
+    /// # use std::time::Duration;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// let control = sandbox.control_page();
+    /// thread::spawn(move || {
+    ///     while control.progress().unwrap_or(0) < 100 {
+    ///         thread::sleep(Duration::from_millis(10));
+    ///     }
+    ///     // Enough progress has been made; let the guest wrap up
+    ///     let _ = control.request_soft_cancel();
+    /// });
+    ///
+    /// let result = sandbox.call::<u64>("ProcessUntilCancelled", ());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn control_page(&self) -> ControlPage {
+        self.control_page.clone()
+    }
+
     /// Generate a crash dump of the current state of the VM underlying this sandbox.
     ///
     /// Creates an ELF core dump file that can be used for debugging. The dump
@@ -859,19 +2331,25 @@ impl MultiUseSandbox {
     /// # Call the crashdump function
     /// call sandbox.generate_crashdump()
     /// ```
-    /// The crashdump should be available in crash dump directory (see `HYPERLIGHT_CORE_DUMP_DIR` env var).
+    /// The crashdump is passed to the sandbox's [`CrashDumpSink`](crate::sandbox::CrashDumpSink), which by
+    /// default writes it to the crash dump directory (see `HYPERLIGHT_CORE_DUMP_DIR` env var).
     ///
     #[cfg(crashdump)]
     #[instrument(err(Debug), skip_all, parent = Span::current())]
     pub fn generate_crashdump(&mut self) -> Result<()> {
-        crate::hypervisor::crashdump::generate_crashdump(&self.vm, &mut self.mem_mgr, None)
+        crate::hypervisor::crashdump::generate_crashdump(
+            &self.vm,
+            &mut self.mem_mgr,
+            crate::hypervisor::crashdump::CrashDumpReason::Requested,
+            None,
+        )
     }
 
     /// Generate a crash dump of the current state of the VM, writing to `dir`.
     ///
     /// Like [`generate_crashdump`](Self::generate_crashdump), but the core dump
-    /// file is placed in `dir` instead of consulting the `HYPERLIGHT_CORE_DUMP_DIR`
-    /// environment variable.  This avoids the need for callers to use
+    /// file is placed in `dir` instead of being passed to the sandbox's sink or
+    /// consulting the `HYPERLIGHT_CORE_DUMP_DIR` environment variable.  This avoids the need for callers to use
     /// `unsafe { std::env::set_var(...) }`.
     #[cfg(crashdump)]
     #[instrument(err(Debug), skip_all, parent = Span::current())]
@@ -879,10 +2357,38 @@ impl MultiUseSandbox {
         crate::hypervisor::crashdump::generate_crashdump(
             &self.vm,
             &mut self.mem_mgr,
-            Some(dir.into()),
+            crate::hypervisor::crashdump::CrashDumpReason::Requested,
+            Some(&crate::hypervisor::crashdump::FileCrashDumpSink::in_dir(
+                dir,
+            )),
         )
     }
 
+    /// Write a core dump of the current state of the VM to the file at `path`.
+    ///
+    /// Unlike [`generate_crashdump`](Self::generate_crashdump), the dump is
+    /// always written to `path` rather than passed to the sandbox's sink, and
+    /// an error is returned if it cannot be written or the sandbox is not
+    /// configured to allow core dumps.
+    ///
+    /// To take a core dump while a guest function is running, such as one
+    /// stuck in an infinite loop, use a [`CoreDumpHandle`](crate::sandbox::CoreDumpHandle)
+    /// from [`core_dump_handle()`](Self::core_dump_handle).
+    #[cfg(crashdump)]
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn dump_core(&mut self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        crate::hypervisor::crashdump::dump_core(&self.vm, &mut self.mem_mgr, path.as_ref())
+    }
+
+    /// Returns a handle for taking core dumps of the sandbox from another
+    /// thread while it runs a guest function, without killing the guest.
+    ///
+    /// See [`CoreDumpHandle`](crate::sandbox::CoreDumpHandle).
+    #[cfg(crashdump)]
+    pub fn core_dump_handle(&self) -> crate::sandbox::CoreDumpHandle {
+        self.vm.core_dump_handle()
+    }
+
     /// Returns whether the sandbox is currently poisoned.
     ///
     /// A poisoned sandbox is in an inconsistent state due to the guest not running to completion.
@@ -922,6 +2428,407 @@ impl MultiUseSandbox {
     pub fn poisoned(&self) -> bool {
         self.poisoned
     }
+
+    /// Returns a stable measurement of the code this sandbox runs.
+    ///
+    /// The measurement is a SHA-256 digest of the loaded and relocated
+    /// guest image together with the memory configuration of the sandbox.
+    /// It is computed once, when the sandbox is evolved and before any
+    /// guest code has run, so it does not change as the guest executes or
+    /// when snapshots are restored. Two sandboxes created from the same
+    /// guest binary with the same configuration, init entry point, init
+    /// data, startup configuration and arguments have the same measurement,
+    /// which allows hosts to log or attest exactly what code ran in each
+    /// sandbox.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// let measurement: String = sandbox
+    ///     .measurement()
+    ///     .iter()
+    ///     .map(|b| format!("{b:02x}"))
+    ///     .collect();
+    /// println!("sandbox measurement: {measurement}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn measurement(&self) -> [u8; 32] {
+        self.measurement
+    }
+
+    /// Returns the names and signatures of the functions the guest
+    /// registered while it was being initialised, sorted by name.
+    ///
+    /// This lets a host check up front that a guest has every function it
+    /// needs, or dispatch calls to functions it did not know about in
+    /// advance. It returns `None` if the guest did not report its
+    /// functions, such as a guest built against an older version of
+    /// Hyperlight. Functions that the guest handles in its
+    /// `guest_dispatch_function` without registering them are not listed.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// if let Some(functions) = sandbox.guest_functions() {
+    ///     for required in ["Init", "Process"] {
+    ///         if !functions.iter().any(|f| f.name == required) {
+    ///             return Err(format!("guest does not export {required}").into());
+    ///         }
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn guest_functions(&self) -> Option<&[GuestFunction]> {
+        self.guest_functions.as_deref()
+    }
+
+    /// Returns the symbolizer of the guest binary the sandbox was created
+    /// from, which resolves guest addresses to the functions, source files
+    /// and lines they are in.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// let symbolizer = sandbox.guest_symbolizer();
+    /// println!("guest loaded at {:#x}", symbolizer.load_addr());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn guest_symbolizer(&self) -> Arc<GuestSymbolizer> {
+        self.guest_symbolizer.clone()
+    }
+
+    /// Reads `len` bytes of the guest's memory at the guest virtual
+    /// address `gva`, translating it through the guest's page tables.
+    ///
+    /// Returns an error if any of the range is not mapped. The memory can
+    /// be read while the sandbox is poisoned, such as to inspect the state
+    /// a guest crashed in.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    ///
+    /// let buffer = sandbox.call::<u64>("GetBufferAddress", ())?;
+    /// sandbox.write_guest_memory(buffer, b"hello")?;
+    /// assert_eq!(sandbox.read_guest_memory(buffer, 5)?, b"hello");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip(self), parent = Span::current())]
+    pub fn read_guest_memory(&mut self, gva: u64, len: usize) -> Result<Vec<u8>> {
+        let root_pt = self
+            .vm
+            .get_root_pt()
+            .map_err(|e| HyperlightError::HyperlightVmError(e.into()))?;
+        self.mem_mgr.read_guest_memory_by_gva(gva, len, root_pt)
+    }
+
+    /// Writes `data` to the guest's memory at the guest virtual address
+    /// `gva`, translating it through the guest's page tables.
+    ///
+    /// Every page written must be mapped writable by the guest, and not be
+    /// a copy-on-write page of the snapshot the guest has not written to
+    /// yet, as those are shared with other sandboxes. Returns an error,
+    /// and writes nothing, if any page of the range cannot be written.
+    ///
+    /// The write is undone by restoring a snapshot taken before it.
+    #[instrument(err(Debug), skip(self, data), parent = Span::current())]
+    pub fn write_guest_memory(&mut self, gva: u64, data: &[u8]) -> Result<()> {
+        let root_pt = self
+            .vm
+            .get_root_pt()
+            .map_err(|e| HyperlightError::HyperlightVmError(e.into()))?;
+        self.mem_mgr.write_guest_memory_by_gva(gva, data, root_pt)
+    }
+
+    /// The registers of the guest's vCPU, as it stopped at the end of the
+    /// last guest call
+    #[cfg(all(feature = "console", target_arch = "x86_64"))]
+    pub(crate) fn vcpu_registers(&self) -> Result<crate::hypervisor::regs::CommonRegisters> {
+        self.vm
+            .registers()
+            .map_err(|e| new_error!("Failed to read the vCPU's registers: {}", e))
+    }
+
+    /// Returns the edges of the guest's code that were hit since coverage
+    /// was last taken, or since the sandbox was created or last restored,
+    /// for coverage-guided fuzzing of guest functions.
+    ///
+    /// Returns an error if the guest is not instrumented for coverage. See
+    /// [`GuestCoverage`] for how to build a guest that is.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+    /// # use hyperlight_host::sandbox::GuestCoverage;
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("instrumented_guest.bin".into()),
+    ///     None
+    /// )?.evolve()?;
+    /// let snapshot = sandbox.snapshot()?;
+    /// let mut corpus_coverage = GuestCoverage::default();
+    ///
+    /// for input in [vec![0u8], vec![1, 2, 3]] {
+    ///     let _ = sandbox.call::<i32>("ParseInput", input.clone());
+    ///     if corpus_coverage.merge(&sandbox.take_coverage()?) > 0 {
+    ///         println!("{input:?} found new code");
+    ///     }
+    ///     sandbox.restore(snapshot.clone())?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn take_coverage(&mut self) -> Result<GuestCoverage> {
+        match (self.read_coverage_counters()?, self.coverage.as_mut()) {
+            (Some(counters), Some(coverage)) => Ok(coverage.take(counters)),
+            _ => Err(new_error!("The guest is not instrumented for coverage")),
+        }
+    }
+
+    /// Whether the guest is instrumented for coverage, so that
+    /// [`take_coverage()`](Self::take_coverage) succeeds
+    pub fn has_coverage(&self) -> bool {
+        self.coverage.is_some()
+    }
+
+    /// The current values of the guest's coverage counters, if it is
+    /// instrumented for coverage
+    fn read_coverage_counters(&mut self) -> Result<Option<Vec<u8>>> {
+        let Some(gvas) = self.coverage.as_ref().map(CoverageCounters::gvas) else {
+            return Ok(None);
+        };
+        let root_pt = self
+            .vm
+            .get_root_pt()
+            .map_err(|e| HyperlightError::HyperlightVmError(e.into()))?;
+        let len = (gvas.end - gvas.start) as usize;
+        Ok(Some(
+            self.mem_mgr
+                .read_guest_memory_by_gva(gvas.start, len, root_pt)?,
+        ))
+    }
+
+    /// Start taking a CPU profile of the guest, sampling its stack every
+    /// `interval` while it runs guest functions, until
+    /// [`stop_profiling()`](Self::stop_profiling) is called.
+    ///
+    /// Each sample briefly interrupts the guest's vCPU, so short intervals
+    /// slow the guest down. See [`GuestProfile`] for how stacks are
+    /// unwound.
+    ///
+    /// Returns an error if the sandbox is already being profiled or
+    /// `interval` is zero.
+    #[instrument(err(Debug), skip(self), parent = Span::current())]
+    pub fn start_profiling(&mut self, interval: Duration) -> Result<()> {
+        if self.profiler.is_some() {
+            return Err(new_error!("The sandbox is already being profiled"));
+        }
+        let profile = GuestProfile::new(interval, self.guest_symbolizer.clone());
+        self.profiler = Some(self.vm.start_profiler(profile)?);
+        Ok(())
+    }
+
+    /// Stop the profile started with
+    /// [`start_profiling()`](Self::start_profiling) and return it.
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn stop_profiling(&mut self) -> Result<GuestProfile> {
+        self.profiler
+            .take()
+            .ok_or_else(|| new_error!("The sandbox is not being profiled"))?
+            .stop()
+    }
+
+    /// Start recording a timeline of the guest's trace spans and events,
+    /// the host functions it calls and the VM exits it makes, until
+    /// [`stop_trace_timeline()`](Self::stop_trace_timeline) is called.
+    ///
+    /// See [`TraceTimeline`](crate::sandbox::TraceTimeline).
+    #[cfg(feature = "trace_guest")]
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn start_trace_timeline(&mut self) -> Result<()> {
+        let slot = self.vm.timeline();
+        let mut timeline = slot
+            .lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+        if timeline.is_some() {
+            return Err(new_error!("A trace timeline is already being recorded"));
+        }
+        *timeline = Some(crate::sandbox::TraceTimeline::new());
+        Ok(())
+    }
+
+    /// Stop recording the timeline started with
+    /// [`start_trace_timeline()`](Self::start_trace_timeline) and return it.
+    #[cfg(feature = "trace_guest")]
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn stop_trace_timeline(&mut self) -> Result<crate::sandbox::TraceTimeline> {
+        self.vm
+            .timeline()
+            .lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .take()
+            .ok_or_else(|| new_error!("A trace timeline is not being recorded"))
+    }
+
+    /// The number of times the guest's vCPU exited to the host since the
+    /// sandbox was created or [`reset_exit_stats()`](Self::reset_exit_stats)
+    /// was last called, by why it exited.
+    ///
+    /// See [`ExitStats`](crate::sandbox::ExitStats).
+    pub fn exit_stats(&self) -> &crate::sandbox::ExitStats {
+        self.vm.exit_stats()
+    }
+
+    /// Clear the exits counted by [`exit_stats()`](Self::exit_stats)
+    pub fn reset_exit_stats(&mut self) {
+        self.vm.exit_stats_mut().reset();
+    }
+
+    /// Start or stop recording how long the guest runs before each exit
+    /// in [`exit_stats()`](Self::exit_stats). Stopping drops the latencies
+    /// recorded so far.
+    pub fn record_exit_latencies(&mut self, enabled: bool) {
+        self.vm.exit_stats_mut().set_records_latency(enabled);
+    }
+
+    /// Capture an Intel Processor Trace of the guest's execution during
+    /// each guest call, of up to `buffer_size` bytes, which is rounded up
+    /// to a power of two number of pages. Only the guest is traced, and
+    /// the trace of the last call is returned by
+    /// [`take_intel_pt_trace()`](Self::take_intel_pt_trace).
+    ///
+    /// Fails if the sandbox does not run on KVM, or the host has no Intel
+    /// PT PMU. Opening the trace needs `CAP_PERFMON`, or a
+    /// `perf_event_paranoid` that allows it; if it fails, guest calls
+    /// still run and a warning is logged.
+    #[cfg(all(kvm, target_arch = "x86_64"))]
+    pub fn enable_intel_pt(&mut self, buffer_size: usize) -> Result<()> {
+        self.vm.enable_intel_pt(buffer_size)
+    }
+
+    /// Stop capturing Intel PT traces of guest calls, and drop the trace
+    /// of the last call if it was not taken
+    #[cfg(all(kvm, target_arch = "x86_64"))]
+    pub fn disable_intel_pt(&mut self) {
+        self.vm.disable_intel_pt();
+    }
+
+    /// The Intel PT trace of the last guest call, if
+    /// [`enable_intel_pt()`](Self::enable_intel_pt) was called before it
+    /// and the trace was not taken already
+    #[cfg(all(kvm, target_arch = "x86_64"))]
+    pub fn take_intel_pt_trace(&mut self) -> Option<crate::sandbox::IntelPtTrace> {
+        self.vm.take_intel_pt_trace()
+    }
+
+    /// Returns the recording of the sandbox's execution so far, if it was
+    /// created with
+    /// [`UninitializedSandbox::record_execution()`](crate::UninitializedSandbox::record_execution).
+    ///
+    /// See [`ExecutionRecording`] for details.
+    pub fn execution_recording(&self) -> Option<ExecutionRecording> {
+        self.host_funcs
+            .try_lock()
+            .ok()?
+            .execution_log()?
+            .recording()
+            .cloned()
+    }
+
+    /// Replays the guest calls of the recording the sandbox was created
+    /// with by
+    /// [`UninitializedSandbox::replay_execution()`](crate::UninitializedSandbox::replay_execution),
+    /// that are yet to be replayed, returning the result of each.
+    ///
+    /// Replaying stops at the first call that could not be run, such as
+    /// one that poisons the sandbox or that diverges from the recording,
+    /// whose error is the last result. Recorded calls can also be replayed
+    /// one by one, by making them as they were made when recording.
+    ///
+    /// See [`ExecutionRecording`] for details.
+    pub fn replay(&mut self) -> Result<Vec<Result<ReturnValue>>> {
+        let remaining = self
+            .host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .execution_log()
+            .map(|log| log.remaining_guest_calls())
+            .ok_or_else(|| new_error!("The sandbox is not replaying an execution"))?;
+
+        let encoding = self.config.get_call_encoding();
+        let mut results = Vec::new();
+        for buffers in remaining {
+            let calls = buffers
+                .iter()
+                .map(|buffer| encoding.decode_function_call(buffer))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            let args: Vec<Vec<ParameterRef<'_>>> = calls
+                .iter()
+                .map(|call| {
+                    call.parameters
+                        .iter()
+                        .flatten()
+                        .cloned()
+                        .map(ParameterRef::Value)
+                        .collect()
+                })
+                .collect();
+            let calls: Vec<_> = calls
+                .iter()
+                .zip(&args)
+                .map(|(call, args)| {
+                    (
+                        call.function_name.as_str(),
+                        call.expected_return_type,
+                        args.as_slice(),
+                    )
+                })
+                .collect();
+
+            self.snapshot = None;
+            match self.call_guest_functions_no_reset(&calls, None) {
+                Ok(call_results) => results.extend(call_results),
+                Err(e) => {
+                    results.push(Err(e));
+                    break;
+                }
+            }
+        }
+        Ok(results)
+    }
 }
 
 impl Callable for MultiUseSandbox {
@@ -939,22 +2846,60 @@ impl Callable for MultiUseSandbox {
 
 impl std::fmt::Debug for MultiUseSandbox {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("MultiUseSandbox").finish()
+        f.debug_struct("MultiUseSandbox")
+            .field("labels", &self.labels)
+            .finish()
+    }
+}
+
+/// The W3C trace context of the current span, which the guest can read
+/// during a call, if the span is part of an OpenTelemetry trace
+fn current_trace_parent() -> Option<TraceParent> {
+    #[cfg(any(feature = "trace_guest", feature = "otel"))]
+    {
+        use opentelemetry::trace::TraceContextExt;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let context = Span::current().context();
+        let span = context.span();
+        let span_context = span.span_context();
+        if span_context.is_valid() {
+            return Some(TraceParent {
+                trace_id: span_context.trace_id().to_bytes(),
+                span_id: span_context.span_id().to_bytes(),
+                flags: span_context.trace_flags().to_u8(),
+            });
+        }
     }
+    None
+}
+
+/// The arguments of a guest function call as parameters that borrow
+/// nothing
+pub(super) fn owned_parameter_refs(args: impl ParameterTuple) -> Vec<ParameterRef<'static>> {
+    args.into_value()
+        .into_iter()
+        .map(ParameterRef::Value)
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use std::sync::{Arc, Barrier};
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Barrier, Mutex};
     use std::thread;
 
+    use hyperlight_common::flatbuffer_wrappers::function_types::{
+        ParameterType, ReturnType, ReturnValue,
+    };
     use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
     use hyperlight_testing::sandbox_sizes::{LARGE_HEAP_SIZE, MEDIUM_HEAP_SIZE, SMALL_HEAP_SIZE};
     use hyperlight_testing::simple_guest_as_string;
 
     use crate::mem::memory_region::{MemoryRegion, MemoryRegionFlags, MemoryRegionType};
     use crate::mem::shared_mem::{ExclusiveSharedMemory, GuestSharedMemory, SharedMemory as _};
-    use crate::sandbox::SandboxConfiguration;
+    use crate::sandbox::uninitialized::GuestEnvironment;
+    use crate::sandbox::{GuestCall, RecoveryPolicy, SandboxConfiguration, SlowCallPolicy};
     use crate::{GuestBinary, HyperlightError, MultiUseSandbox, Result, UninitializedSandbox};
 
     #[test]
@@ -972,7 +2917,7 @@ mod tests {
             .call::<()>("guest_panic", "hello".to_string())
             .unwrap_err();
         assert!(
-            matches!(res, HyperlightError::GuestAborted(code, context) if code == ErrorCode::UnknownError as u8 && context.contains("hello"))
+            matches!(res, HyperlightError::GuestPanicked { message, .. } if message == "hello")
         );
         assert!(sbox.poisoned());
 
@@ -1028,7 +2973,7 @@ mod tests {
             .call::<()>("guest_panic", "hello".to_string())
             .unwrap_err();
         assert!(
-            matches!(res, HyperlightError::GuestAborted(code, context) if code == ErrorCode::UnknownError as u8 && context.contains("hello"))
+            matches!(res, HyperlightError::GuestPanicked { message, .. } if message == "hello")
         );
         assert!(sbox.poisoned());
 
@@ -1067,7 +3012,7 @@ mod tests {
                 .unwrap_err();
 
             assert!(
-                matches!(result, HyperlightError::GuestError(code, msg) if code == ErrorCode::HostFunctionError && msg == "hi"),
+                matches!(result, HyperlightError::GuestError { code, message, .. } if code == ErrorCode::HostFunctionError && message == "hi"),
             );
         }
     }
@@ -1100,7 +3045,7 @@ mod tests {
             assert_eq!(result, 15);
             let result = sandbox.call::<i32>("AddToStaticAndFail", ()).unwrap_err();
             assert!(
-                matches!(result, HyperlightError::GuestError (code, msg ) if code == ErrorCode::GuestError && msg == "Crash on purpose")
+                matches!(result, HyperlightError::GuestError { code, message, .. } if code == ErrorCode::GuestError && message == "Crash on purpose")
             );
         }
     }
@@ -1198,6 +3143,537 @@ mod tests {
         assert_eq!(res, 0);
     }
 
+    #[test]
+    fn reset_returns_to_post_initialise_state() {
+        let mut sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None).unwrap();
+            u_sbox.evolve()
+        }
+        .unwrap();
+
+        // A snapshot taken after further calls must not change what
+        // reset() returns to.
+        let _ = sbox.call::<i32>("AddToStatic", 5i32).unwrap();
+        let _ = sbox.snapshot().unwrap();
+        let _ = sbox.call::<i32>("AddToStatic", 5i32).unwrap();
+
+        sbox.reset().unwrap();
+        let res: i32 = sbox.call("GetStatic", ()).unwrap();
+        assert_eq!(res, 0);
+
+        // reset() also recovers a poisoned sandbox
+        let _ = sbox
+            .call::<()>("guest_panic", "hello".to_string())
+            .unwrap_err();
+        assert!(sbox.poisoned());
+        sbox.reset().unwrap();
+        assert!(!sbox.poisoned());
+        let res: i32 = sbox.call("GetStatic", ()).unwrap();
+        assert_eq!(res, 0);
+    }
+
+    #[test]
+    fn measurement_is_stable_and_covers_config() {
+        let path = simple_guest_as_string().unwrap();
+        let evolve = |cfg: Option<SandboxConfiguration>| -> MultiUseSandbox {
+            UninitializedSandbox::new(GuestBinary::FilePath(path.clone()), cfg)
+                .unwrap()
+                .evolve()
+                .unwrap()
+        };
+
+        let mut sbox1 = evolve(None);
+        let sbox2 = evolve(None);
+        assert_eq!(sbox1.measurement(), sbox2.measurement());
+
+        // Running the guest does not change the measurement
+        let measurement = sbox1.measurement();
+        let _ = sbox1.call::<i32>("AddToStatic", 5i32).unwrap();
+        sbox1.reset().unwrap();
+        assert_eq!(sbox1.measurement(), measurement);
+
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_heap_size(SandboxConfiguration::DEFAULT_HEAP_SIZE * 2);
+        assert_ne!(evolve(Some(cfg)).measurement(), measurement);
+    }
+
+    #[test]
+    fn measurement_covers_guest_inputs() {
+        let path = simple_guest_as_string().unwrap();
+        let evolve = |env: GuestEnvironment| -> [u8; 32] {
+            UninitializedSandbox::new(env, None)
+                .unwrap()
+                .evolve()
+                .unwrap()
+                .measurement()
+        };
+        let env = || GuestEnvironment::new(GuestBinary::FilePath(path.clone()), None);
+
+        let measurement = evolve(env());
+        assert_eq!(evolve(env()), measurement);
+
+        // Two entry points give different measurements
+        let bench_main = evolve(env().with_init_entry_point("bench_main"));
+        assert_ne!(bench_main, measurement);
+        assert_eq!(
+            evolve(env().with_init_entry_point("bench_main")),
+            bench_main
+        );
+
+        let init_data = [0xCA, 0xFE, 0xBA, 0xBE];
+        let with_init_data = evolve(GuestEnvironment::new(
+            GuestBinary::FilePath(path.clone()),
+            Some(&init_data),
+        ));
+        assert_ne!(with_init_data, measurement);
+
+        assert_ne!(evolve(env().with_args(["guest"])), measurement);
+        assert_ne!(evolve(env().with_startup_config(b"config")), measurement);
+    }
+
+    #[test]
+    fn replace_guest_keeps_host_functions() {
+        let path = simple_guest_as_string().unwrap();
+        let mut u_sbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path.clone()), None).unwrap();
+        u_sbox.register("HostAdd", |a: i32, b: i32| a + b).unwrap();
+        let mut sbox = u_sbox.evolve().unwrap();
+        let _ = sbox.call::<i32>("AddToStatic", 5i32).unwrap();
+        let snapshot = sbox.snapshot().unwrap();
+
+        // A guest that cannot be loaded leaves the old guest in place
+        let mut missing = path.clone();
+        missing.push_str(".nonexistent");
+        assert!(sbox.replace_guest(GuestBinary::FilePath(missing)).is_err());
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 5);
+
+        let id = sbox.id();
+        sbox.replace_guest(GuestBinary::FilePath(path)).unwrap();
+        assert_eq!(sbox.id(), id);
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 0);
+        assert_eq!(sbox.call::<i32>("Add", (5i32, 10i32)).unwrap(), 15);
+
+        // Snapshots of the old guest cannot be restored into the new one
+        assert!(matches!(
+            sbox.restore(snapshot),
+            Err(HyperlightError::SnapshotSandboxMismatch)
+        ));
+    }
+
+    #[test]
+    fn transactional_calls_roll_back_on_failure() {
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+            .unwrap()
+            .evolve()
+            .unwrap();
+        sbox.set_transactional_calls(true);
+
+        // Successful calls are committed
+        assert_eq!(sbox.call::<i32>("AddToStatic", 5i32).unwrap(), 5);
+
+        // Failed calls are rolled back
+        let res = sbox.call::<i32>("AddToStaticAndFail", ()).unwrap_err();
+        assert!(matches!(
+            res,
+            HyperlightError::GuestError {
+                code: ErrorCode::GuestError,
+                ..
+            }
+        ));
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 5);
+
+        // Including ones that would otherwise poison the sandbox
+        let _ = sbox
+            .call::<()>("guest_panic", "hello".to_string())
+            .unwrap_err();
+        assert!(!sbox.poisoned());
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 5);
+
+        // Without transactional calls, the failed call's changes are kept
+        sbox.set_transactional_calls(false);
+        let _ = sbox.call::<i32>("AddToStaticAndFail", ()).unwrap_err();
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 15);
+    }
+
+    #[test]
+    fn transactional_calls_roll_back_after_abort() {
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+            .unwrap()
+            .evolve()
+            .unwrap();
+        sbox.set_transactional_calls(true);
+        assert_eq!(sbox.call::<i32>("AddToStatic", 5i32).unwrap(), 5);
+
+        let res = sbox.call::<()>("AddToStaticAndAbort", ()).unwrap_err();
+        assert!(matches!(res, HyperlightError::GuestAborted(0, _)));
+        assert!(!sbox.poisoned());
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 5);
+    }
+
+    #[test]
+    fn transactional_calls_roll_back_after_timeout() {
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+            .unwrap()
+            .evolve()
+            .unwrap();
+        sbox.set_transactional_calls(true);
+        assert_eq!(sbox.call::<i32>("AddToStatic", 5i32).unwrap(), 5);
+
+        let timeout = std::time::Duration::from_millis(100);
+        let res = sbox
+            .call_with_timeout::<()>("AddToStaticAndSpin", (), timeout)
+            .unwrap_err();
+        assert!(matches!(res, HyperlightError::GuestCallTimedOut(t) if t == timeout));
+        assert!(!sbox.poisoned());
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 5);
+    }
+
+    #[test]
+    fn guest_calls_replaced_and_unregistered_host_functions() {
+        let path = simple_guest_as_string().unwrap();
+        let mut u_sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None).unwrap();
+        u_sbox.register("GetVersion", || Ok(1i64)).unwrap();
+        let mut sbox = u_sbox.evolve().unwrap();
+        let call = |sbox: &mut MultiUseSandbox| {
+            sbox.call::<i64>(
+                "CallGivenParamlessHostFuncThatReturnsI64",
+                "GetVersion".to_string(),
+            )
+        };
+        assert_eq!(call(&mut sbox).unwrap(), 1);
+
+        // The guest calls the new implementation from the next call on
+        sbox.replace_host_function("GetVersion", || Ok(2i64))
+            .unwrap();
+        assert_eq!(call(&mut sbox).unwrap(), 2);
+
+        sbox.unregister_host_function("GetVersion").unwrap();
+        assert!(call(&mut sbox).is_err());
+        assert!(matches!(
+            sbox.unregister_host_function("GetVersion"),
+            Err(HyperlightError::HostFunctionNotFound(_))
+        ));
+        assert!(matches!(
+            sbox.replace_host_function("GetVersion", || Ok(3i64)),
+            Err(HyperlightError::HostFunctionNotFound(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "windows")]
+    fn frozen_guest_time_does_not_advance_across_host_calls() {
+        let ticks = |freeze: bool| {
+            let mut cfg = SandboxConfiguration::default();
+            cfg.set_freeze_guest_time(freeze);
+            let path = simple_guest_as_string().unwrap();
+            let mut u_sbox =
+                UninitializedSandbox::new(GuestBinary::FilePath(path), Some(cfg)).unwrap();
+            u_sbox
+                .register("Sleep", || {
+                    std::thread::sleep(std::time::Duration::from_millis(200));
+                    Ok(0i64)
+                })
+                .unwrap();
+            let mut sbox = u_sbox.evolve().unwrap();
+            sbox.call::<u64>("TscTicksAcrossHostCall", "Sleep".to_string())
+                .unwrap()
+        };
+
+        // The TSC runs at an unknown frequency, so compare against the
+        // ticks across the same call with guest time running
+        let running = ticks(false);
+        let frozen = ticks(true);
+        assert!(
+            frozen < running / 10,
+            "{frozen} ticks passed with guest time frozen, {running} with it running"
+        );
+    }
+
+    #[test]
+    fn errors_carry_the_sandbox_labels() {
+        let path = simple_guest_as_string().unwrap();
+        let mut u_sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None).unwrap();
+        u_sbox.set_label("tenant", "contoso").unwrap();
+        let mut sbox = u_sbox.evolve().unwrap();
+
+        let err = sbox.call::<i32>("NoSuchFunction", ()).unwrap_err();
+        assert_eq!(err.labels().and_then(|l| l.get("tenant")), Some("contoso"));
+        assert!(matches!(
+            err.unlabelled(),
+            HyperlightError::GuestError {
+                code: ErrorCode::GuestFunctionNotFound,
+                ..
+            }
+        ));
+        assert!(err.to_string().ends_with("(sandbox tenant=contoso)"));
+
+        let results = sbox
+            .call_guest_functions_batch(&[GuestCall::new::<i32>("NoSuchFunction", ())])
+            .unwrap();
+        assert!(results[0].as_ref().unwrap_err().labels().is_some());
+    }
+
+    #[test]
+    fn batched_calls_return_each_result_in_order() {
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+            .unwrap()
+            .evolve()
+            .unwrap();
+
+        let calls = [
+            GuestCall::new::<i32>("AddToStatic", 5i32),
+            GuestCall::new::<i32>("AddToStaticAndFail", ()),
+            GuestCall::new::<i32>("GetStatic", ()),
+            GuestCall::new::<String>("Echo", "hello".to_string()),
+        ];
+        let results = sbox.call_guest_functions_batch(&calls).unwrap();
+        assert_eq!(results.len(), 4);
+        assert!(matches!(results[0], Ok(ReturnValue::Int(5))));
+        assert!(matches!(
+            results[1],
+            Err(HyperlightError::GuestError {
+                code: ErrorCode::GuestError,
+                ..
+            })
+        ));
+        assert!(matches!(results[2], Ok(ReturnValue::Int(15))));
+        assert!(matches!(&results[3], Ok(ReturnValue::String(s)) if s == "hello"));
+
+        // With transactional calls, a failure rolls back the whole batch
+        sbox.set_transactional_calls(true);
+        let results = sbox.call_guest_functions_batch(&calls[..2]).unwrap();
+        assert!(results[1].is_err());
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 15);
+
+        assert!(sbox.call_guest_functions_batch(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn call_borrowed_passes_slices() {
+        let mut sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None).unwrap();
+            u_sbox.evolve()
+        }
+        .unwrap();
+
+        let data: Vec<u8> = (0..=255).collect();
+        let res: Vec<u8> = sbox
+            .call_borrowed("GetSizePrefixedBuffer", &data[..])
+            .unwrap();
+        assert_eq!(res, data);
+
+        sbox.call_borrowed::<()>("LargeParameters", (&data[..], "x".to_string()))
+            .unwrap();
+    }
+
+    #[test]
+    fn guest_functions_lists_registered_functions() {
+        let sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None).unwrap();
+            u_sbox.evolve()
+        }
+        .unwrap();
+
+        let functions = sbox.guest_functions().unwrap();
+        let add = functions.iter().find(|f| f.name == "Add").unwrap();
+        assert_eq!(
+            add.parameter_types,
+            [ParameterType::Int, ParameterType::Int]
+        );
+        assert_eq!(add.return_type, ReturnType::Int);
+        assert!(functions.is_sorted_by(|a, b| a.name < b.name));
+        assert!(!functions.iter().any(|f| f.name == "NoSuchFunction"));
+    }
+
+    #[test]
+    fn call_with_callback_is_scoped_to_the_call() {
+        let mut sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None).unwrap();
+            u_sbox.evolve()
+        }
+        .unwrap();
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let callback = {
+            let calls = calls.clone();
+            move |i: i32| {
+                calls.lock().unwrap().push(i);
+                Ok(i * 2)
+            }
+        };
+        let mut callback_name = String::new();
+        let sum: i32 = sbox
+            .call_with_callback("CallCallback", callback, |name| {
+                callback_name = name.clone();
+                (name, 4)
+            })
+            .unwrap();
+        assert_eq!(sum, 12);
+        assert_eq!(*calls.lock().unwrap(), [0, 1, 2, 3]);
+
+        // The callback is gone once the call returns
+        assert!(matches!(
+            sbox.unregister_host_function(&callback_name),
+            Err(HyperlightError::HostFunctionNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn call_with_structured_values() {
+        let mut sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None).unwrap();
+            u_sbox.evolve()
+        }
+        .unwrap();
+
+        let mut values = BTreeMap::new();
+        values.insert("a".to_string(), Some(1_u64));
+        values.insert("b".to_string(), None);
+        values.insert("c".to_string(), Some(2_u64));
+        let sum: Option<u64> = sbox.call("SumPresent", values).unwrap();
+        assert_eq!(sum, Some(3));
+
+        let sum: Option<u64> = sbox
+            .call("SumPresent", BTreeMap::<String, Option<u64>>::new())
+            .unwrap();
+        assert_eq!(sum, None);
+    }
+
+    #[test]
+    fn call_streaming_returns_chunks() {
+        let mut sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None).unwrap();
+            u_sbox.evolve()
+        }
+        .unwrap();
+
+        let collect = |chunks: &Arc<Mutex<Vec<Vec<u8>>>>| {
+            let chunks = chunks.clone();
+            move |chunk: &[u8]| {
+                chunks.lock().unwrap().push(chunk.to_vec());
+                Ok(())
+            }
+        };
+
+        // Small chunks are received one by one
+        let chunks = Arc::new(Mutex::new(Vec::new()));
+        sbox.call_streaming("StreamBytes", (10_u64, 3_u64), collect(&chunks))
+            .unwrap();
+        assert_eq!(
+            chunks
+                .lock()
+                .unwrap()
+                .iter()
+                .map(Vec::len)
+                .collect::<Vec<_>>(),
+            [3, 3, 3, 1]
+        );
+
+        // Output much larger than the output buffer is split up by the guest
+        let len = 1024 * 1024;
+        let chunks = Arc::new(Mutex::new(Vec::new()));
+        sbox.call_streaming("StreamBytes", (len as u64, len as u64), collect(&chunks))
+            .unwrap();
+        assert_eq!(
+            chunks.lock().unwrap().concat(),
+            (0..len).map(|i| i as u8).collect::<Vec<_>>()
+        );
+
+        // Chunks are only accepted during a streaming call
+        assert!(sbox.call::<()>("StreamBytes", (10_u64, 3_u64)).is_err());
+
+        // A sink that fails stops the call
+        let mut sbox: MultiUseSandbox = {
+            let path = simple_guest_as_string().unwrap();
+            let u_sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None).unwrap();
+            u_sbox.evolve()
+        }
+        .unwrap();
+        let mut received = 0;
+        let res = sbox.call_streaming("StreamBytes", (10_u64, 3_u64), move |_| {
+            received += 1;
+            if received == 2 {
+                return Err(crate::new_error!("sink is full"));
+            }
+            Ok(())
+        });
+        assert!(res.unwrap_err().to_string().contains("sink is full"));
+        assert!(sbox.poisoned());
+    }
+
+    #[test]
+    fn recovery_policy_restarts_poisoned_sandbox() {
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+            .unwrap()
+            .evolve()
+            .unwrap();
+        sbox.call::<i32>("AddToStatic", 5i32).unwrap();
+        let snapshot = sbox.snapshot().unwrap();
+
+        // Without a policy the sandbox stays poisoned
+        let _ = sbox
+            .call::<()>("guest_panic", "hello".to_string())
+            .unwrap_err();
+        assert!(matches!(
+            sbox.call::<i32>("GetStatic", ()),
+            Err(HyperlightError::PoisonedSandbox)
+        ));
+
+        // Recreating starts from the post-initialisation state
+        sbox.set_recovery_policy(RecoveryPolicy::recreate());
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 0);
+
+        // Restoring a snapshot starts from the snapshot
+        sbox.set_recovery_policy(RecoveryPolicy::restore_snapshot(snapshot).with_max_restarts(2));
+        for _ in 0..2 {
+            let _ = sbox
+                .call::<()>("guest_panic", "hello".to_string())
+                .unwrap_err();
+        }
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 5);
+
+        // Too many restarts in a row leave the sandbox poisoned
+        for _ in 0..3 {
+            let _ = sbox
+                .call::<()>("guest_panic", "hello".to_string())
+                .unwrap_err();
+        }
+        assert!(matches!(
+            sbox.call::<i32>("GetStatic", ()),
+            Err(HyperlightError::PoisonedSandbox)
+        ));
+    }
+
+    #[test]
+    fn slow_call_policy_releases_the_profile_slot() {
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+            .unwrap()
+            .evolve()
+            .unwrap();
+        // Every call is slow, and has its stack sampled
+        sbox.set_slow_call_policy(Some(
+            SlowCallPolicy::new(std::time::Duration::ZERO).with_stack_sample(true),
+        ));
+        for _ in 0..10 {
+            assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 0);
+        }
+        sbox.start_profiling(std::time::Duration::from_millis(1))
+            .unwrap();
+        sbox.stop_profiling().unwrap();
+    }
+
     #[test]
     fn test_trigger_exception_on_guest() {
         let usbox = UninitializedSandbox::new(
@@ -1559,7 +4035,6 @@ mod tests {
     }
 
     /// Helper: create a MultiUseSandbox from the simple guest with default config.
-    #[cfg(feature = "trace_guest")]
     fn sandbox_for_gva_tests() -> MultiUseSandbox {
         let path = simple_guest_as_string().unwrap();
         UninitializedSandbox::new(GuestBinary::FilePath(path), None)
@@ -1652,6 +4127,54 @@ mod tests {
         assert_gva_read_matches(&mut sbox, start, 200);
     }
 
+    /// Test that the public API reads the same memory as the memory
+    /// manager, and refuses to write to the read-only code section.
+    #[test]
+    fn guest_memory_is_written_only_where_writable() {
+        let mut sbox = sandbox_for_gva_tests();
+        let code_gva = sbox.mem_mgr.layout.get_guest_code_address() as u64;
+        let root_pt = sbox.vm.get_root_pt().unwrap();
+        let code = sbox
+            .mem_mgr
+            .read_guest_memory_by_gva(code_gva, 64, root_pt)
+            .unwrap();
+        assert_eq!(sbox.read_guest_memory(code_gva, 64).unwrap(), code);
+
+        let err = sbox.write_guest_memory(code_gva, &[0xcc; 64]).unwrap_err();
+        assert!(err.to_string().contains("not mapped writable"), "{err}");
+        assert_eq!(sbox.read_guest_memory(code_gva, 64).unwrap(), code);
+
+        // Unmapped memory can be neither read nor written
+        assert!(sbox.read_guest_memory(u64::MAX - 4095, 16).is_err());
+        assert!(sbox.write_guest_memory(u64::MAX - 4095, &[0; 16]).is_err());
+    }
+
+    /// Test that bytes written by GVA to writable scratch memory, across a
+    /// page boundary, land in the scratch region and read back unchanged.
+    #[test]
+    fn guest_memory_write_read_round_trip() {
+        let mut sbox = sandbox_for_gva_tests();
+        // The output buffer is in scratch memory, mapped writable, and
+        // larger than a page
+        let offset = 4096 - 8;
+        let gva = sbox.mem_mgr.layout.get_output_data_buffer_gva() + offset as u64;
+        let data: Vec<u8> = (0..16).collect();
+        sbox.write_guest_memory(gva, &data).unwrap();
+        assert_eq!(sbox.read_guest_memory(gva, data.len()).unwrap(), data);
+
+        let host_offset = sbox
+            .mem_mgr
+            .layout
+            .get_output_data_buffer_scratch_host_offset()
+            + offset;
+        let in_scratch = sbox
+            .mem_mgr
+            .scratch_mem
+            .with_contents(|scratch| scratch[host_offset..host_offset + data.len()].to_vec())
+            .unwrap();
+        assert_eq!(in_scratch, data);
+    }
+
     /// Helper: create a temp file with known content, padded to be
     /// at least page-aligned (4096 bytes). Returns the path and the
     /// *original* content bytes (before padding).
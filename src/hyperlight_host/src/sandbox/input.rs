@@ -0,0 +1,137 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::future::Future;
+use std::io::{self, Read};
+
+use crate::func::host_functions::run_async;
+
+type ReadFn = dyn FnMut(usize) -> io::Result<Vec<u8>> + Send;
+
+/// A source the guest's stdin is read from.
+///
+/// A source is set with
+/// [`UninitializedSandbox::set_guest_stdin()`](crate::UninitializedSandbox::set_guest_stdin),
+/// and is read from whenever the guest reads its stdin during a call, such
+/// as with `read` or `fread`, rather than the guest being passed all of its
+/// input up front. Input is passed to the guest in pieces no larger than
+/// its input buffer.
+///
+/// A source can be a [`Read`], or an async function that returns the next
+/// piece of input when it is ready. While the guest waits for input it
+/// stays parked, as it does while an
+/// [`AsyncHostFunction`](crate::func::AsyncHostFunction) is pending, and
+/// the future is run in the sandbox's
+/// [`AsyncContext`](crate::func::AsyncContext).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::InputSource;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// let input = std::fs::File::open("input.csv")?;
+/// sandbox.set_guest_stdin(InputSource::new(input))?;
+///
+/// let mut lines = vec!["first\n".to_string(), "second\n".to_string()].into_iter();
+/// sandbox.set_guest_stdin(InputSource::from_async(move |_max_len| {
+///     let next = lines.next();
+///     async move { Ok(next.map(String::into_bytes).unwrap_or_default()) }
+/// }))?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct InputSource {
+    read: Box<ReadFn>,
+}
+
+impl InputSource {
+    /// A source that reads the guest's stdin from `reader`
+    pub fn new(mut reader: impl Read + Send + 'static) -> Self {
+        Self {
+            read: Box::new(move |max_len| {
+                let mut buf = vec![0; max_len];
+                let n = loop {
+                    match reader.read(&mut buf) {
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        res => break res?,
+                    }
+                };
+                buf.truncate(n);
+                Ok(buf)
+            }),
+        }
+    }
+
+    /// A source that reads the guest's stdin by calling `next` with the
+    /// most bytes the guest can be given, and waiting for the future it
+    /// returns. The future resolves to the next bytes of input, or to no
+    /// bytes at the end of the input. Any bytes beyond the size asked for
+    /// are kept for the guest's next read.
+    pub fn from_async<F, Fut>(mut next: F) -> Self
+    where
+        F: FnMut(usize) -> Fut + Send + 'static,
+        Fut: Future<Output = io::Result<Vec<u8>>>,
+    {
+        let mut pending = Vec::new();
+        Self {
+            read: Box::new(move |max_len| {
+                if pending.is_empty() {
+                    pending = run_async(|| next(max_len))?;
+                }
+                let rest = pending.split_off(pending.len().min(max_len));
+                Ok(std::mem::replace(&mut pending, rest))
+            }),
+        }
+    }
+
+    /// Read up to `max_len` bytes of input, or none at the end of the input
+    pub(crate) fn read(&mut self, max_len: usize) -> io::Result<Vec<u8>> {
+        (self.read)(max_len)
+    }
+}
+
+impl std::fmt::Debug for InputSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InputSource").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InputSource;
+
+    #[test]
+    fn sources_are_read_in_pieces() {
+        let mut source = InputSource::new(&b"hello world"[..]);
+        assert_eq!(source.read(6).unwrap(), b"hello ");
+        assert_eq!(source.read(100).unwrap(), b"world");
+        assert!(source.read(100).unwrap().is_empty());
+
+        let mut pieces = vec![b"abcdef".to_vec()].into_iter();
+        let mut source = InputSource::from_async(move |_| {
+            let next = pieces.next().unwrap_or_default();
+            async move { Ok(next) }
+        });
+        assert_eq!(source.read(4).unwrap(), b"abcd");
+        assert_eq!(source.read(4).unwrap(), b"ef");
+        assert!(source.read(4).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+
+use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnValue};
+
+use crate::Result;
+
+/// A function that wraps every call the guest makes to a host function
+pub(crate) type Interceptor = Arc<dyn Fn(HostCall<'_>) -> Result<ReturnValue> + Send + Sync>;
+
+/// A call from the guest to a host function, as seen by an interceptor.
+///
+/// Interceptors are added with
+/// [`UninitializedSandbox::add_host_call_interceptor()`](crate::UninitializedSandbox::add_host_call_interceptor),
+/// and run in the order they were added, each wrapping the ones added
+/// after it. An interceptor is given the `HostCall` being made, and either
+/// continues it with [`HostCall::proceed()`], or returns a value or an
+/// error of its own instead, without calling the host function.
+///
+/// Interceptors only run for calls that are allowed by the sandbox's
+/// [capabilities](crate::sandbox::Capabilities) and
+/// [rate limits](crate::sandbox::RateLimit), and run on the thread that
+/// called into the guest.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::time::Instant;
+/// # use hyperlight_host::{GuestBinary, HyperlightError, UninitializedSandbox};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// // Time every call, without logging its arguments
+/// sandbox.add_host_call_interceptor(|call| {
+///     let name = call.name().to_string();
+///     let start = Instant::now();
+///     let res = call.proceed();
+///     println!("{name} took {:?}", start.elapsed());
+///     res
+/// })?;
+/// // Stop the guest from deleting anything
+/// sandbox.add_host_call_interceptor(|call| {
+///     if call.name().starts_with("Delete") {
+///         return Err(HyperlightError::Error(format!("{} is not allowed", call.name())));
+///     }
+///     call.proceed()
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct HostCall<'a> {
+    name: &'a str,
+    args: Vec<ParameterValue>,
+    interceptors: &'a [Interceptor],
+    function: &'a dyn Fn(Vec<ParameterValue>) -> Result<ReturnValue>,
+}
+
+impl<'a> HostCall<'a> {
+    /// A call to the host function `name` that runs `interceptors` before
+    /// calling `function`
+    pub(crate) fn new(
+        name: &'a str,
+        args: Vec<ParameterValue>,
+        interceptors: &'a [Interceptor],
+        function: &'a dyn Fn(Vec<ParameterValue>) -> Result<ReturnValue>,
+    ) -> Self {
+        Self {
+            name,
+            args,
+            interceptors,
+            function,
+        }
+    }
+
+    /// The name of the host function being called
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// The arguments the host function is called with
+    pub fn args(&self) -> &[ParameterValue] {
+        &self.args
+    }
+
+    /// The arguments the host function is called with, which may be
+    /// changed before the call proceeds. They must still match the
+    /// function's parameter types.
+    pub fn args_mut(&mut self) -> &mut Vec<ParameterValue> {
+        &mut self.args
+    }
+
+    /// Continue the call, running the remaining interceptors and then the
+    /// host function, and return its result
+    pub fn proceed(self) -> Result<ReturnValue> {
+        match self.interceptors.split_first() {
+            Some((interceptor, rest)) => interceptor(HostCall {
+                interceptors: rest,
+                ..self
+            }),
+            None => (self.function)(self.args),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnValue};
+
+    use super::{HostCall, Interceptor};
+    use crate::HyperlightError;
+
+    #[test]
+    fn interceptors_run_in_order_and_can_short_circuit() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let logger: Interceptor = {
+            let log = log.clone();
+            Arc::new(move |call: HostCall<'_>| {
+                log.lock()
+                    .unwrap()
+                    .push(format!("{}{:?}", call.name(), call.args()));
+                call.proceed()
+            })
+        };
+        let doubler: Interceptor = Arc::new(|mut call: HostCall<'_>| {
+            if let [ParameterValue::Int(i)] = call.args_mut().as_mut_slice() {
+                *i *= 2;
+            }
+            call.proceed()
+        });
+        let deny: Interceptor =
+            Arc::new(|call: HostCall<'_>| Err(HyperlightError::Error(call.name().to_string())));
+        let function = |args: Vec<ParameterValue>| match args[..] {
+            [ParameterValue::Int(i)] => Ok(ReturnValue::Int(i + 1)),
+            _ => Ok(ReturnValue::Void(())),
+        };
+
+        let interceptors = [logger.clone(), doubler];
+        let res = HostCall::new(
+            "Inc",
+            vec![ParameterValue::Int(20)],
+            &interceptors,
+            &function,
+        )
+        .proceed()
+        .unwrap();
+        assert_eq!(res, ReturnValue::Int(41));
+        assert_eq!(*log.lock().unwrap(), ["Inc[Int(20)]"]);
+
+        let interceptors = [deny, logger];
+        let res = HostCall::new(
+            "Inc",
+            vec![ParameterValue::Int(1)],
+            &interceptors,
+            &function,
+        )
+        .proceed();
+        assert!(matches!(res, Err(HyperlightError::Error(name)) if name == "Inc"));
+        assert_eq!(log.lock().unwrap().len(), 1);
+    }
+}
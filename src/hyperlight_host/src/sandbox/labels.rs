@@ -0,0 +1,168 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::HyperlightError::InvalidConfiguration;
+use crate::Result;
+
+/// A small set of key/value labels identifying a sandbox, such as a tenant
+/// id or the name of the function it serves.
+///
+/// Labels are attached with
+/// [`UninitializedSandbox::set_label()`](crate::UninitializedSandbox::set_label)
+/// and are included in:
+/// - a `sandbox` tracing span around every guest call, so that all events
+///   emitted during the call, including errors, carry them
+/// - the labels of the guest call duration and guest error metrics
+/// - the file names of crash dumps
+/// - the errors returned from guest calls, as
+///   [`HyperlightError::Labelled`](crate::HyperlightError::Labelled)
+///
+/// The labels are shared rather than copied, so they are cheap to clone.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SandboxLabels {
+    /// The labels, ordered by key
+    labels: Arc<[(String, String)]>,
+}
+
+impl SandboxLabels {
+    /// The maximum number of labels a sandbox may have
+    pub const MAX_LABELS: usize = 8;
+    /// The maximum length of a label value, in bytes
+    pub const MAX_VALUE_LEN: usize = 128;
+
+    /// Set the label `key` to `value`, replacing any previous value.
+    ///
+    /// Keys must be valid metric label names: non-empty, made up of ASCII
+    /// letters, digits and underscores, and not starting with a digit.
+    pub(crate) fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let valid_key = key
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_key {
+            return Err(InvalidConfiguration(format!(
+                "invalid sandbox label key {key:?}"
+            )));
+        }
+        if value.len() > Self::MAX_VALUE_LEN {
+            return Err(InvalidConfiguration(format!(
+                "sandbox label {key:?} is longer than {} bytes",
+                Self::MAX_VALUE_LEN
+            )));
+        }
+        let mut labels = self.labels.to_vec();
+        match labels.binary_search_by(|(k, _)| k.as_str().cmp(key)) {
+            Ok(i) => labels[i].1 = value.to_string(),
+            Err(_) if labels.len() == Self::MAX_LABELS => {
+                return Err(InvalidConfiguration(format!(
+                    "a sandbox may have at most {} labels",
+                    Self::MAX_LABELS
+                )));
+            }
+            Err(i) => labels.insert(i, (key.to_string(), value.to_string())),
+        }
+        self.labels = labels.into();
+        Ok(())
+    }
+
+    /// Returns the value of the label `key`, if it is set
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.labels
+            .binary_search_by(|(k, _)| k.as_str().cmp(key))
+            .ok()
+            .map(|i| self.labels[i].1.as_str())
+    }
+
+    /// Returns whether no labels are set
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// Iterates over the labels, in order of their keys
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.labels.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// The labels in a form that can be used as part of a file name, with
+    /// any characters other than ASCII letters, digits, `-` and `_` in the
+    /// values replaced by `_`
+    #[cfg(crashdump)]
+    pub(crate) fn to_file_name_part(&self) -> String {
+        self.iter()
+            .map(|(k, v)| {
+                let v: String = v
+                    .chars()
+                    .map(|c| {
+                        if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                            c
+                        } else {
+                            '_'
+                        }
+                    })
+                    .collect();
+                format!("{k}-{v}")
+            })
+            .collect::<Vec<_>>()
+            .join("_")
+    }
+}
+
+impl fmt::Display for SandboxLabels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (k, v)) in self.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{k}={v}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SandboxLabels;
+
+    #[test]
+    fn set_validates_labels() {
+        let mut labels = SandboxLabels::default();
+        labels.set("tenant", "contoso").unwrap();
+        labels.set("function_name", "resize-image").unwrap();
+        labels.set("tenant", "fabrikam").unwrap();
+        assert_eq!(labels.get("tenant"), Some("fabrikam"));
+        assert_eq!(
+            labels.to_string(),
+            "function_name=resize-image,tenant=fabrikam"
+        );
+
+        assert!(labels.set("", "value").is_err());
+        assert!(labels.set("1tenant", "value").is_err());
+        assert!(labels.set("ten ant", "value").is_err());
+        let long = "x".repeat(SandboxLabels::MAX_VALUE_LEN + 1);
+        assert!(labels.set("long", &long).is_err());
+
+        for i in labels.iter().count()..SandboxLabels::MAX_LABELS {
+            labels.set(&format!("label{i}"), "value").unwrap();
+        }
+        assert!(labels.set("one_too_many", "value").is_err());
+        // Existing labels can still be replaced
+        labels.set("tenant", "contoso").unwrap();
+    }
+}
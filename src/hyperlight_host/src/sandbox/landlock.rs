@@ -0,0 +1,131 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::path::PathBuf;
+
+use landlock::{
+    ABI, Access, AccessFs, Ruleset, RulesetAttr, RulesetCreatedAttr, RulesetStatus,
+    path_beneath_rules,
+};
+
+use crate::{Result, new_error};
+
+/// The newest Landlock ABI that is used. Kernels that only support an older
+/// ABI enforce as much of the restriction as they can.
+const LANDLOCK_ABI: ABI = ABI::V5;
+
+/// The filesystem access allowed to the host functions of a sandbox.
+///
+/// When set with
+/// [`UninitializedSandbox::restrict_host_function_filesystem()`](crate::UninitializedSandbox::restrict_host_function_filesystem),
+/// every host function of the sandbox is run on a short-lived worker thread
+/// that is restricted with Landlock, so that it can only access files
+/// beneath the allowed paths. All other filesystem access fails with
+/// `EACCES`. Files that were already open before the host function was
+/// called, such as stdout, are not affected.
+///
+/// Landlock is applied on a best-effort basis: on kernels without Landlock
+/// support, host functions run unrestricted and a warning is logged.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::sandbox::FilesystemRestriction;
+/// let restriction = FilesystemRestriction::new()
+///     .allow_read("/usr/share/zoneinfo")
+///     .allow_read_write("/var/lib/my-service/scratch");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FilesystemRestriction {
+    read_only: Vec<PathBuf>,
+    read_write: Vec<PathBuf>,
+}
+
+impl FilesystemRestriction {
+    /// Create a restriction that allows no filesystem access at all
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow reading the file or directory tree at `path`
+    pub fn allow_read(mut self, path: impl Into<PathBuf>) -> Self {
+        self.read_only.push(path.into());
+        self
+    }
+
+    /// Allow reading and writing the file or directory tree at `path`
+    pub fn allow_read_write(mut self, path: impl Into<PathBuf>) -> Self {
+        self.read_write.push(path.into());
+        self
+    }
+
+    /// Restrict the calling thread. This cannot be undone.
+    pub(crate) fn apply(&self) -> Result<RulesetStatus> {
+        let status = Ruleset::default()
+            .handle_access(AccessFs::from_all(LANDLOCK_ABI))
+            .and_then(|ruleset| ruleset.create())
+            .and_then(|ruleset| {
+                ruleset.add_rules(path_beneath_rules(
+                    &self.read_only,
+                    AccessFs::from_read(LANDLOCK_ABI),
+                ))
+            })
+            .and_then(|ruleset| {
+                ruleset.add_rules(path_beneath_rules(
+                    &self.read_write,
+                    AccessFs::from_all(LANDLOCK_ABI),
+                ))
+            })
+            .and_then(|ruleset| ruleset.restrict_self())
+            .map_err(|e| new_error!("failed to apply Landlock ruleset: {e}"))?;
+        if status.ruleset == RulesetStatus::NotEnforced {
+            tracing::warn!("Landlock is not supported, host functions are not restricted");
+        }
+        Ok(status.ruleset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use landlock::RulesetStatus;
+
+    use super::FilesystemRestriction;
+
+    #[test]
+    fn restricts_worker_thread_only() {
+        let allowed = tempfile::tempdir().unwrap();
+        let denied = tempfile::tempdir().unwrap();
+        std::fs::write(allowed.path().join("file"), "allowed").unwrap();
+        std::fs::write(denied.path().join("file"), "denied").unwrap();
+
+        let restriction = FilesystemRestriction::new().allow_read(allowed.path());
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let status = restriction.apply().unwrap();
+                if status == RulesetStatus::NotEnforced {
+                    return;
+                }
+                assert!(std::fs::read(allowed.path().join("file")).is_ok());
+                assert!(std::fs::write(allowed.path().join("file"), "written").is_err());
+                assert!(std::fs::read(denied.path().join("file")).is_err());
+            })
+            .join()
+            .unwrap()
+        });
+
+        assert!(std::fs::read(denied.path().join("file")).is_ok());
+    }
+}
@@ -0,0 +1,133 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use super::{MultiUseSandbox, UninitializedSandbox};
+use crate::func::{ParameterTuple, SupportedReturnType};
+use crate::{Result, new_error};
+
+/// A sandbox whose virtual machine is only created when it is first used.
+///
+/// Created with [`UninitializedSandbox::evolve_lazy()`]. Creating the
+/// hypervisor partition and vCPU, and running the guest's initialisation,
+/// is deferred until the first guest call (or the first call to
+/// [`sandbox()`](Self::sandbox)). Services that create many sandboxes
+/// speculatively therefore only pay for the hypervisor resources of the
+/// sandboxes they actually use.
+///
+/// Errors that [`UninitializedSandbox::evolve()`] would have returned are
+/// returned by the first call instead. If initialisation fails, every
+/// later call fails too.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?.evolve_lazy()?;
+/// assert!(!sandbox.is_initialised());
+///
+/// // The VM is created and the guest initialised here
+/// let result: String = sandbox.call("Echo", "hello".to_string())?;
+/// assert!(sandbox.is_initialised());
+/// # Ok(())
+/// # }
+/// ```
+pub struct LazySandbox {
+    pending: Option<UninitializedSandbox>,
+    sandbox: Option<MultiUseSandbox>,
+}
+
+impl LazySandbox {
+    pub(super) fn new(u_sbox: UninitializedSandbox) -> Self {
+        Self {
+            pending: Some(u_sbox),
+            sandbox: None,
+        }
+    }
+
+    /// Returns whether the virtual machine has been created and the guest
+    /// initialised
+    pub fn is_initialised(&self) -> bool {
+        self.sandbox.is_some()
+    }
+
+    /// Returns the initialised sandbox, creating the virtual machine and
+    /// initialising the guest first if that has not happened yet
+    pub fn sandbox(&mut self) -> Result<&mut MultiUseSandbox> {
+        if let Some(u_sbox) = self.pending.take() {
+            self.sandbox = Some(u_sbox.evolve()?);
+        }
+        self.sandbox
+            .as_mut()
+            .ok_or_else(|| new_error!("the sandbox failed to initialise"))
+    }
+
+    /// Converts this into the initialised sandbox, creating the virtual
+    /// machine and initialising the guest first if that has not happened yet
+    pub fn into_sandbox(mut self) -> Result<MultiUseSandbox> {
+        self.sandbox()?;
+        self.sandbox
+            .ok_or_else(|| new_error!("the sandbox failed to initialise"))
+    }
+
+    /// Calls a guest function by name with the specified arguments.
+    ///
+    /// See [`MultiUseSandbox::call()`].
+    pub fn call<Output: SupportedReturnType>(
+        &mut self,
+        func_name: &str,
+        args: impl ParameterTuple,
+    ) -> Result<Output> {
+        self.sandbox()?.call(func_name, args)
+    }
+}
+
+impl std::fmt::Debug for LazySandbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LazySandbox")
+            .field("initialised", &self.is_initialised())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_testing::simple_guest_as_string;
+
+    use crate::{GuestBinary, UninitializedSandbox};
+
+    #[test]
+    fn evolve_lazy_defers_initialisation() {
+        let path = simple_guest_as_string().unwrap();
+        let mut sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+            .unwrap()
+            .evolve_lazy()
+            .unwrap();
+        assert!(!sbox.is_initialised());
+
+        assert_eq!(
+            sbox.call::<String>("Echo", "hello".to_string()).unwrap(),
+            "hello"
+        );
+        assert!(sbox.is_initialised());
+
+        let mut sbox = sbox.into_sandbox().unwrap();
+        assert_eq!(sbox.call::<i32>("GetStatic", ()).unwrap(), 0);
+    }
+}
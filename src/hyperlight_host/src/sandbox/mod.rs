@@ -14,16 +14,93 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+/// Audit logs of the host functions guests invoke
+pub mod audit;
+/// Backtraces of the guest at the point it returned an error
+pub mod backtrace;
+/// Batches of guest function calls
+pub mod batch;
+/// Request-scoped cancellation of guest calls
+pub mod cancellation;
+/// Capabilities required by host functions
+pub mod capabilities;
+/// Placing vCPU threads into cgroups
+#[cfg(target_os = "linux")]
+pub mod cgroup;
 /// Configuration needed to establish a sandbox.
 pub mod config;
+/// An interactive console for calling into guests during development
+#[cfg(feature = "console")]
+pub mod console;
+/// Atomic slots shared between the host and the guest
+pub mod control_page;
+/// Coverage of the guest's code for fuzzing
+pub mod coverage;
+/// Hooks for the lifecycle events of sandboxes
+pub mod events;
+/// Counts of the reasons a guest's vCPU exits to the host
+pub mod exit_stats;
 /// Host-side file mapping preparation for `map_file_cow`.
 pub(crate) mod file_mapping;
+/// Fuzzing guest functions
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+/// The functions a guest has registered
+pub mod guest_functions;
+/// `tracing` events for the structured log records of guests
+pub(crate) mod guest_log;
 /// Functionality for reading, but not modifying host functions
 pub(crate) mod host_funcs;
 /// Functionality for dealing with initialized sandboxes that can
 /// call 0 or more guest functions
 pub mod initialized_multi_use;
+/// Sources for the guest's stdin
+pub mod input;
+/// Interceptors that wrap host function calls
+pub mod interceptor;
+/// Labels identifying a sandbox in logs, metrics and crash dumps
+pub mod labels;
+/// Landlock filesystem restriction of host functions
+#[cfg(landlock)]
+pub mod landlock;
+/// Sandboxes whose virtual machine is created on first use
+pub mod lazy;
+/// Namespaces that group host functions
+pub mod namespace;
 pub(crate) mod outb;
+/// Tracing of the calls a guest makes out to the host
+pub mod outcall_trace;
+/// Sinks for the guest's stdout and stderr
+pub mod output;
+/// Sampling CPU profiles of guests
+pub mod profiler;
+/// Encoding the protobuf formats the host writes
+pub(crate) mod protobuf;
+/// Rate limits on host function calls
+pub mod rate_limit;
+/// Automatic recovery of poisoned sandboxes
+pub mod recovery;
+/// Process-wide registry of live sandboxes
+pub mod registry;
+/// Recording and replaying the execution of a sandbox
+pub mod replay;
+/// Retrying guest calls that fail transiently
+pub mod retry;
+/// Seccomp filtering of host functions
+#[cfg(seccomp)]
+pub mod seccomp;
+/// Sandboxes that can be shared between threads
+pub mod shared;
+/// Guest images that are loaded once and shared between sandboxes
+pub mod shared_image;
+/// Verification of guest binaries against detached signatures
+pub mod signature;
+/// Reporting guest calls that are slow
+pub mod slow_call;
+/// Suspending idle sandboxes
+pub mod suspend;
+/// Resolving guest addresses to functions, source files and lines
+pub mod symbolizer;
 /// Functionality for creating uninitialized sandboxes, manipulating them,
 /// and converting them to initialized sandboxes.
 pub mod uninitialized;
@@ -41,12 +118,110 @@ mod callable;
 #[cfg(feature = "trace_guest")]
 pub(crate) mod trace;
 
+/// Re-export for `CoreDumpHandle` type
+#[cfg(crashdump)]
+pub use crate::hypervisor::crashdump::CoreDumpHandle;
+/// Re-export for `WerCrashDumpSink` type
+#[cfg(all(crashdump, target_os = "windows"))]
+pub use crate::hypervisor::crashdump::WerCrashDumpSink;
+/// Re-export for `CrashDumpSink` type
+#[cfg(crashdump)]
+pub use crate::hypervisor::crashdump::{
+    CoreDumpFormat, CrashDumpInfo, CrashDumpReason, CrashDumpSink, FileCrashDumpSink,
+};
+/// Re-export for `CrashDumpPolicy` type
+#[cfg(crashdump)]
+pub use crate::hypervisor::crashdump::{CrashDumpArea, CrashDumpPolicy};
+/// Re-export for `CrashReport` type
+#[cfg(crashdump)]
+pub use crate::hypervisor::crashdump::{CrashReport, CrashReportRegion, RecentOutcall};
+/// Re-export for `IntelPtTrace` type
+#[cfg(all(kvm, target_arch = "x86_64"))]
+pub use crate::hypervisor::virtual_machine::kvm::intel_pt::IntelPtTrace;
+/// Re-export for `AuditSink` type
+pub use audit::{AuditRecord, AuditSink, FileAuditSink};
+/// Re-export for `GuestBacktrace` type
+pub use backtrace::{GuestBacktrace, GuestFrame};
+/// Re-export for `GuestCall` type
+pub use batch::GuestCall;
 /// Trait used by the macros to paper over the differences between hyperlight and hyperlight-wasm
 pub use callable::Callable;
+/// Re-export for `CancellationToken` type
+pub use cancellation::{CancellationStatus, CancellationToken};
+/// Re-export for `Capabilities` type
+pub use capabilities::Capabilities;
+/// Re-export for `VcpuCgroup` type
+#[cfg(target_os = "linux")]
+pub use cgroup::VcpuCgroup;
 /// Re-export for `SandboxConfiguration` type
 pub use config::SandboxConfiguration;
+/// Re-export for `SandboxConsole` type
+#[cfg(feature = "console")]
+pub use console::SandboxConsole;
+/// Re-export for `ControlPage` type
+pub use control_page::{CallProgress, ControlPage};
+/// Re-export for `GuestCoverage` type
+pub use coverage::GuestCoverage;
+/// Re-export for `EventSubscriber` type
+pub use events::{EventSubscriber, add_global_event_subscriber};
+/// Re-export for `ExitStats` type
+pub use exit_stats::{ExitLatencyHistogram, ExitStats, HypervisorCounters, VmExitKind};
+/// Re-export for `GuestFuzzer` type
+#[cfg(feature = "fuzzing")]
+pub use fuzzing::GuestFuzzer;
+/// Re-export for `GuestFunction` type
+pub use guest_functions::GuestFunction;
+/// Re-export for `CallEncoding` type
+pub use hyperlight_common::call_encoding::CallEncoding;
 /// Re-export for the `MultiUseSandbox` type
 pub use initialized_multi_use::{MultiUseSandbox, PtRootFinder};
+/// Re-export for `InputSource` type
+pub use input::InputSource;
+/// Re-export for `HostCall` type
+pub use interceptor::HostCall;
+/// Re-export for `SandboxLabels` type
+pub use labels::SandboxLabels;
+/// Re-export for `FilesystemRestriction` type
+#[cfg(landlock)]
+pub use landlock::FilesystemRestriction;
+/// Re-export for `LazySandbox` type
+pub use lazy::LazySandbox;
+/// Re-export for `HostNamespace` type
+pub use namespace::HostNamespace;
+/// Re-export for `OutcallTracer` type
+pub use outcall_trace::{OUTCALL_TARGET, OutcallPart, OutcallTracer};
+/// Re-export for `OutputSink` type
+pub use output::OutputSink;
+/// Re-export for `GuestProfile` type
+pub use profiler::GuestProfile;
+/// Re-export for `RateLimit` type
+pub use rate_limit::{GuestLogRateLimit, RateLimit};
+/// Re-export for `RecoveryPolicy` type
+pub use recovery::RecoveryPolicy;
+/// Re-export for `SandboxInfo` type
+pub use registry::{SandboxInfo, SandboxState, SandboxStateChange};
+/// Re-export for `ExecutionRecording` type
+pub use replay::ExecutionRecording;
+/// Re-export for `RetryPolicy` type
+pub use retry::{RetryPolicy, TransientFailure};
+/// Re-export for `SyscallFilter` type
+#[cfg(seccomp)]
+pub use seccomp::SyscallFilter;
+/// Re-export for `SharedSandbox` type
+pub use shared::{Fairness, Priority, SharedSandbox};
+/// Re-export for `SharedGuestImage` type
+pub use shared_image::SharedGuestImage;
+/// Re-export for `GuestSignature` type
+pub use signature::GuestSignature;
+/// Re-export for `SlowCallPolicy` type
+pub use slow_call::SlowCallPolicy;
+/// Re-export for `IdleSuspend` type
+pub use suspend::IdleSuspend;
+/// Re-export for `GuestSymbolizer` type
+pub use symbolizer::GuestSymbolizer;
+/// Re-export for `TraceTimeline` type
+#[cfg(feature = "trace_guest")]
+pub use trace::TraceTimeline;
 /// Re-export for `GuestBinary` type
 pub use uninitialized::GuestBinary;
 /// Re-export for `UninitializedSandbox` type
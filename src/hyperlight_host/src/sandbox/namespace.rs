@@ -0,0 +1,178 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use hyperlight_common::flatbuffer_wrappers::function_types::{
+    ParameterValue, ReturnType, ReturnValue,
+};
+
+use super::Capabilities;
+use super::host_funcs::FunctionEntry;
+#[cfg(seccomp)]
+use super::seccomp::SyscallFilter;
+use crate::func::host_functions::{HostFunction, TypeErasedHostFunction, register_host_function};
+use crate::func::{ParameterTuple, SupportedReturnType};
+use crate::{Result, UninitializedSandbox, new_error};
+
+/// A group of host functions whose names share a namespace, such as the
+/// `"wasi:fs"` in `"wasi:fs/read"`.
+///
+/// A namespace is obtained with
+/// [`UninitializedSandbox::namespace()`](crate::UninitializedSandbox::namespace),
+/// and registers host functions under its own name, followed by a `/`.
+/// Namespaces can be nested, so `"wasi:fs/dir/list"` is in both the
+/// `"wasi:fs/dir"` and `"wasi:fs"` namespaces.
+///
+/// A wildcard function handles calls to any function in the namespace that
+/// is not registered itself, and is given the rest of the name it was
+/// called by. Whole namespaces can be disabled with
+/// [`UninitializedSandbox::disable_host_namespaces()`](crate::UninitializedSandbox::disable_host_namespaces),
+/// in which case the guest cannot call any of their functions, and they
+/// are not reported to the guest.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::func::ReturnValue;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// sandbox.disable_host_namespaces(["wasi:sockets"])?;
+/// let mut fs = sandbox.namespace("wasi:fs");
+/// fs.register("read", |path: String| Ok(std::fs::read(path).unwrap_or_default()))?;
+/// fs.register_wildcard(|name, _args| {
+///     println!("the guest called the unsupported function wasi:fs/{name}");
+///     Ok(ReturnValue::Void(()))
+/// })?;
+/// // Registered, but cannot be called by the guest
+/// sandbox
+///     .namespace("wasi:sockets")
+///     .register("connect", |_addr: String| Ok(()))?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct HostNamespace<'a> {
+    sandbox: &'a mut UninitializedSandbox,
+    namespace: String,
+}
+
+impl<'a> HostNamespace<'a> {
+    pub(crate) fn new(sandbox: &'a mut UninitializedSandbox, namespace: String) -> Self {
+        Self { sandbox, namespace }
+    }
+
+    /// The name of the namespace
+    pub fn name(&self) -> &str {
+        &self.namespace
+    }
+
+    /// The namespace `name` nested within this one
+    pub fn namespace(&mut self, name: &str) -> HostNamespace<'_> {
+        HostNamespace::new(&mut *self.sandbox, format!("{}/{name}", self.namespace))
+    }
+
+    /// Registers a host function in this namespace, which the guest calls
+    /// by the namespace's name followed by `/name`.
+    pub fn register<Args: ParameterTuple, Output: SupportedReturnType>(
+        &mut self,
+        name: &str,
+        host_func: impl Into<HostFunction<Output, Args>>,
+    ) -> Result<()> {
+        self.register_with_capabilities(name, host_func, Capabilities::empty())
+    }
+
+    /// Registers a host function in this namespace like
+    /// [`register`](Self::register), that can only be called by the guest
+    /// if the sandbox has been granted all of `capabilities`.
+    pub fn register_with_capabilities<Args: ParameterTuple, Output: SupportedReturnType>(
+        &mut self,
+        name: &str,
+        host_func: impl Into<HostFunction<Output, Args>>,
+        capabilities: Capabilities,
+    ) -> Result<()> {
+        let name = self.qualify(name)?;
+        register_host_function(
+            host_func,
+            self.sandbox,
+            &name,
+            capabilities,
+            #[cfg(seccomp)]
+            &SyscallFilter::default(),
+        )
+    }
+
+    /// Registers a function that handles calls to any function in this
+    /// namespace, or a namespace nested within it, that is not registered
+    /// itself. It is called with the part of the name after this
+    /// namespace's, and the arguments the guest passed.
+    ///
+    /// The wildcard is registered as the host function named by the
+    /// namespace followed by `/*`, which can be used to set a rate limit
+    /// or timeout on it.
+    pub fn register_wildcard(
+        &mut self,
+        func: impl Fn(&str, Vec<ParameterValue>) -> Result<ReturnValue> + Send + Sync + 'static,
+    ) -> Result<()> {
+        let entry = FunctionEntry {
+            function: TypeErasedHostFunction::wildcard(func),
+            parameter_types: &[],
+            return_type: ReturnType::Void,
+            required_capabilities: Capabilities::empty(),
+            rate_limit: None,
+            timeout: None,
+            #[cfg(seccomp)]
+            seccomp_filter: SyscallFilter::default().compile()?,
+        };
+        self.sandbox
+            .host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .register_host_function(format!("{}/*", self.namespace), entry)
+    }
+
+    fn qualify(&self, name: &str) -> Result<String> {
+        if name.is_empty() || name.contains('/') || name == "*" {
+            return Err(new_error!(
+                "{name:?} is not a valid name for a host function in namespace {}",
+                self.namespace
+            ));
+        }
+        Ok(format!("{}/{name}", self.namespace))
+    }
+}
+
+/// The namespaces a host function called `name` is in, innermost first
+pub(crate) fn namespaces(name: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(name.rsplit_once('/').map(|(ns, _)| ns), |ns| {
+        ns.rsplit_once('/').map(|(ns, _)| ns)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::namespaces;
+
+    #[test]
+    fn namespaces_are_listed_innermost_first() {
+        assert_eq!(
+            namespaces("wasi:fs/dir/list").collect::<Vec<_>>(),
+            ["wasi:fs/dir", "wasi:fs"]
+        );
+        assert_eq!(namespaces("HostPrint").count(), 0);
+    }
+}
@@ -15,22 +15,31 @@ limitations under the License.
 */
 
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use hyperlight_common::flatbuffer_wrappers::function_types::{FunctionCallResult, ParameterValue};
 use hyperlight_common::flatbuffer_wrappers::guest_error::{ErrorCode, GuestError};
 use hyperlight_common::flatbuffer_wrappers::guest_log_data::GuestLogData;
+use hyperlight_common::guest_metric::GuestMetric;
 use hyperlight_common::outb::{Exception, OutBAction};
 use log::{Level, Record};
 use tracing::{Span, instrument};
-use tracing_log::format_trace;
+use tracing_log::{AsTrace, format_trace};
 
+use super::cancellation::CancellationStatus;
+use super::guest_log;
 use super::host_funcs::FunctionRegistry;
+use crate::hypervisor::InterruptHandleImpl;
 #[cfg(feature = "mem_profile")]
 use crate::hypervisor::regs::CommonRegisters;
 use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::shared_mem::HostSharedMemory;
+use crate::metrics::{METRIC_GUEST_LOG_RECORDS_DROPPED, emit_guest_metric};
 #[cfg(feature = "mem_profile")]
 use crate::sandbox::trace::MemTraceInfo;
+#[cfg(feature = "trace_guest")]
+use crate::sandbox::trace::{HostCallSlice, TimelineSlot};
+use crate::{HyperlightError, etw, usdt};
 
 /// Errors that can occur when handling an outb operation from the guest.
 #[derive(Debug, thiserror::Error)]
@@ -56,6 +65,22 @@ pub enum HandleOutbError {
     WriteHostFunctionResponse(String),
     #[error("Invalid character for debug print: {0}")]
     InvalidDebugPrintChar(u32),
+    #[error("Failed to read stream chunk: {0}")]
+    ReadStreamChunk(String),
+    #[error("Guest sent a stream chunk outside of a streaming call")]
+    UnexpectedStreamChunk,
+    #[error("Failed to write stream chunk: {0}")]
+    WriteStreamChunk(String),
+    #[error("Failed to read guest output: {0}")]
+    ReadOutput(String),
+    #[error("Invalid guest output stream: {0}")]
+    InvalidOutputStream(u32),
+    #[error("Failed to write guest input: {0}")]
+    WriteInput(String),
+    #[error("Failed to read guest metric: {0}")]
+    ReadMetric(String),
+    #[error("Replay diverged from the recording: {0}")]
+    ReplayDiverged(String),
     #[cfg(feature = "mem_profile")]
     #[error("Memory profiling error: {0}")]
     MemProfile(String),
@@ -64,6 +89,7 @@ pub enum HandleOutbError {
 #[instrument(err(Debug), skip_all, parent = Span::current(), level="Trace")]
 pub(super) fn outb_log(
     mgr: &mut SandboxMemoryManager<HostSharedMemory>,
+    host_funcs: &Arc<Mutex<FunctionRegistry>>,
 ) -> Result<(), HandleOutbError> {
     // This code will create either a logging record or a tracing record for the GuestLogData depending on if the host has set up a tracing subscriber.
     // In theory as we have enabled the log feature in the Cargo.toml for tracing this should happen
@@ -76,6 +102,27 @@ pub(super) fn outb_log(
         .read_guest_log_data()
         .map_err(|e| HandleOutbError::ReadLogData(e.to_string()))?;
 
+    // The record is read before it may be dropped, so that it does not
+    // stay in the output buffer
+    let limit = host_funcs
+        .try_lock()
+        .map_err(|e| HandleOutbError::LockFailed(file!(), line!(), e.to_string()))?
+        .guest_log_rate_limit()
+        .cloned();
+    if let Some(limit) = limit {
+        match limit.acquire() {
+            None => {
+                metrics::counter!(METRIC_GUEST_LOG_RECORDS_DROPPED).increment(1);
+                return Ok(());
+            }
+            Some(0) => {}
+            Some(dropped) => tracing::warn!(
+                dropped,
+                "Dropped {dropped} guest log records over the guest log rate limit"
+            ),
+        }
+    }
+
     let record_level: Level = (&log_data.level).into();
 
     // Work out if we need to log or trace
@@ -91,9 +138,22 @@ pub(super) fn outb_log(
     let source_file = Some(log_data.source_file.as_str());
     let line = Some(log_data.line);
     let source = Some(log_data.source.as_str());
+    let target = guest_log::target(&log_data);
+    let fields: Vec<(&str, &str)> = log_data
+        .fields
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
 
     // See https://github.com/rust-lang/rust/issues/42253 for the reason this has to be done this way
 
+    // A record with a target or fields gets an event with that target and
+    // those fields, unless the guest has logged too many kinds of record
+    let structured = !log_data.target.is_empty() || !log_data.fields.is_empty();
+    if should_trace && structured && guest_log::dispatch(&log_data, record_level.as_trace()) {
+        return Ok(());
+    }
+
     if should_trace {
         // Create a tracing event for the GuestLogData
         // Ideally we would create tracing metadata based on the Guest Log Data
@@ -104,10 +164,11 @@ pub(super) fn outb_log(
             &Record::builder()
                 .args(format_args!("{}", log_data.message))
                 .level(record_level)
-                .target("hyperlight_guest")
+                .target(&target)
                 .file(source_file)
                 .line(line)
                 .module_path(source)
+                .key_values(&fields)
                 .build(),
         )
         .map_err(|e| HandleOutbError::TraceFormat(e.to_string()))?;
@@ -117,10 +178,11 @@ pub(super) fn outb_log(
             &Record::builder()
                 .args(format_args!("{}", log_data.message))
                 .level(record_level)
-                .target("hyperlight_guest")
+                .target(&target)
                 .file(Some(&log_data.source_file))
                 .line(Some(log_data.line))
                 .module_path(Some(&log_data.source))
+                .key_values(&fields)
                 .build(),
         );
     }
@@ -145,7 +207,10 @@ fn outb_abort(
             let guest_error_code = *buffer.first().unwrap_or(&0);
 
             let result = {
-                let message = if let Some(&maybe_exception_code) = buffer.get(1) {
+                let message = if guest_error_code == ErrorCode::GuestPanicked as u8 {
+                    // The location and message of a panic, which is not an exception
+                    String::from_utf8_lossy(&buffer[1..]).into()
+                } else if let Some(&maybe_exception_code) = buffer.get(1) {
                     match Exception::try_from(maybe_exception_code) {
                         Ok(exception) => {
                             let extra_msg = String::from_utf8_lossy(&buffer[2..]);
@@ -168,11 +233,21 @@ fn outb_abort(
         }
 
         if buffer.len() >= MAX_ABORT_BUFFER_LEN {
+            // A panic keeps the start of its message, which includes where
+            // the guest panicked, rather than losing all of it
+            let result = if buffer.first() == Some(&(ErrorCode::GuestPanicked as u8)) {
+                HandleOutbError::GuestAborted {
+                    code: ErrorCode::GuestPanicked as u8,
+                    message: format!("{}...", String::from_utf8_lossy(&buffer[1..])),
+                }
+            } else {
+                HandleOutbError::GuestAborted {
+                    code: 0,
+                    message: "Guest abort buffer overflowed".into(),
+                }
+            };
             buffer.clear();
-            return Err(HandleOutbError::GuestAborted {
-                code: 0,
-                message: "Guest abort buffer overflowed".into(),
-            });
+            return Err(result);
         }
 
         buffer.push(b);
@@ -180,13 +255,48 @@ fn outb_abort(
     Ok(())
 }
 
+/// Write output from the guest to its stdout (`fd` 1) or stderr (`fd` 2).
+/// A sink that fails to write is logged, rather than failing the guest
+/// call.
+fn write_guest_output(
+    host_funcs: &Arc<Mutex<FunctionRegistry>>,
+    fd: u32,
+    data: &[u8],
+) -> Result<(), HandleOutbError> {
+    if fd != 1 && fd != 2 {
+        return Err(HandleOutbError::InvalidOutputStream(fd));
+    }
+    let mut registry = host_funcs
+        .try_lock()
+        .map_err(|e| HandleOutbError::LockFailed(file!(), line!(), e.to_string()))?;
+    if let Some(tracer) = registry.outcall_tracer() {
+        tracer.output(fd, data);
+    }
+    let res = registry.write_guest_output(fd, data);
+    if let Err(e) = res {
+        tracing::warn!("Failed to write guest output: {}", e);
+    }
+    Ok(())
+}
+
+/// The error for a replay that diverged from its recording
+fn replay_diverged(e: HyperlightError) -> HandleOutbError {
+    match e {
+        HyperlightError::ReplayDiverged(message) => HandleOutbError::ReplayDiverged(message),
+        e => HandleOutbError::ReplayDiverged(e.to_string()),
+    }
+}
+
 /// Handles OutB operations from the guest.
 #[instrument(err(Debug), skip_all, parent = Span::current(), level= "Trace")]
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn handle_outb(
     mem_mgr: &mut SandboxMemoryManager<HostSharedMemory>,
     host_funcs: &Arc<Mutex<FunctionRegistry>>,
+    interrupt_handle: &Arc<dyn InterruptHandleImpl>,
     port: u16,
     data: u32,
+    #[cfg(feature = "trace_guest")] timeline: &TimelineSlot,
     #[cfg(feature = "mem_profile")] regs: &CommonRegisters,
     #[cfg(feature = "mem_profile")] trace_info: &mut MemTraceInfo,
 ) -> Result<(), HandleOutbError> {
@@ -194,28 +304,121 @@ pub(crate) fn handle_outb(
         .try_into()
         .map_err(|e: anyhow::Error| HandleOutbError::InvalidPort(e.to_string()))?
     {
-        OutBAction::Log => outb_log(mem_mgr),
+        OutBAction::Log => outb_log(mem_mgr, host_funcs),
         OutBAction::CallFunction => {
             let call = mem_mgr
                 .get_host_function_call()
                 .map_err(|e| HandleOutbError::ReadHostFunctionCall(e.to_string()))?;
             let name = call.function_name.clone();
             let args: Vec<ParameterValue> = call.parameters.unwrap_or(vec![]);
-            let res = host_funcs
+            #[cfg(feature = "trace_guest")]
+            let _slice = HostCallSlice::begin(timeline, &name);
+            // Lets the host function see a kill() made while it runs
+            let cancellation = CancellationStatus::new({
+                let handle = interrupt_handle.clone();
+                move || handle.is_cancelled()
+            });
+            let mut registry = host_funcs
                 .try_lock()
-                .map_err(|e| HandleOutbError::LockFailed(file!(), line!(), e.to_string()))?
-                .call_host_function(&name, args)
-                .map_err(|e| GuestError::new(ErrorCode::HostFunctionError, e.to_string()));
+                .map_err(|e| HandleOutbError::LockFailed(file!(), line!(), e.to_string()))?;
+            // While replaying, the host function isn't called, and the
+            // guest gets the response that was recorded
+            let replayed = registry
+                .execution_log()
+                .map(|log| log.replay_host_call(&name))
+                .transpose()
+                .map_err(replay_diverged)?
+                .flatten();
+            if let Some(response) = replayed {
+                drop(registry);
+                return mem_mgr
+                    .write_response_from_host_function_call(&response)
+                    .map_err(|e| HandleOutbError::WriteHostFunctionResponse(e.to_string()));
+            }
+            // The arguments are summarized before the host function takes them
+            let tracer = registry.outcall_tracer().cloned();
+            let traced_args = tracer
+                .as_ref()
+                .map(|tracer| tracer.summarize_args(&name, &args));
+            usdt::probe!("host__call__enter", name.as_ptr(), name.len());
+            etw::host_call_start(&name);
+            let started = Instant::now();
+            let res = registry
+                .call_host_function_with_cancellation(&name, args, &cancellation)
+                .map_err(|e| {
+                    let code = match e {
+                        HyperlightError::HostFunctionPanicked(..) => {
+                            ErrorCode::HostFunctionPanicked
+                        }
+                        HyperlightError::HostFunctionNamespaceDisabled(..)
+                        | HyperlightError::HostFunctionPermissionDenied(..) => {
+                            ErrorCode::HostFunctionPermissionDenied
+                        }
+                        HyperlightError::HostFunctionThrottled(..) => {
+                            ErrorCode::HostFunctionThrottled
+                        }
+                        HyperlightError::HostFunctionTimedOut(..) => {
+                            ErrorCode::HostFunctionTimedOut
+                        }
+                        _ => ErrorCode::HostFunctionError,
+                    };
+                    GuestError::new(code, e.to_string())
+                });
+            registry.add_host_call_time(started.elapsed());
+            usdt::probe!(
+                "host__call__leave",
+                name.as_ptr(),
+                name.len(),
+                res.as_ref().err().map_or(0, |e| e.code as u64)
+            );
+            etw::host_call_stop(&name, res.as_ref().err().map_or(0, |e| e.code as u64));
+            registry.audit_host_call(&name, res.as_ref().err().map(|e| e.code));
+            if let (Some(tracer), Some(args)) = (&tracer, &traced_args) {
+                tracer.host_call(&name, args, started.elapsed(), &res);
+            }
+            #[cfg(crashdump)]
+            if let Some(recent_outcalls) = registry.recent_outcalls() {
+                let error = res.as_ref().err().map(|e| e.message.clone());
+                recent_outcalls.record(&name, started.elapsed(), error);
+            }
 
             let func_result = FunctionCallResult::new(res);
+            let response = mem_mgr
+                .encode_host_function_response(&func_result)
+                .map_err(|e| HandleOutbError::WriteHostFunctionResponse(e.to_string()))?;
+            if let Some(log) = registry.execution_log() {
+                log.record_host_call(&name, &response);
+            }
+            drop(registry);
 
             mem_mgr
-                .write_response_from_host_function_call(&func_result)
+                .write_response_from_host_function_call(&response)
                 .map_err(|e| HandleOutbError::WriteHostFunctionResponse(e.to_string()))?;
 
             Ok(())
         }
-        OutBAction::Abort => outb_abort(mem_mgr, data),
+        OutBAction::Abort => {
+            let res = outb_abort(mem_mgr, data);
+            if let Err(HandleOutbError::GuestAborted { code, message }) = &res
+                && let Ok(registry) = host_funcs.try_lock()
+                && let Some(tracer) = registry.outcall_tracer()
+            {
+                tracer.abort(*code, message);
+            }
+            res
+        }
+        OutBAction::StreamChunk => {
+            let chunk = mem_mgr
+                .read_stream_chunk()
+                .map_err(|e| HandleOutbError::ReadStreamChunk(e.to_string()))?;
+            let mut registry = host_funcs
+                .try_lock()
+                .map_err(|e| HandleOutbError::LockFailed(file!(), line!(), e.to_string()))?;
+            let sink = registry
+                .stream_sink()
+                .ok_or(HandleOutbError::UnexpectedStreamChunk)?;
+            sink(&chunk).map_err(|e| HandleOutbError::WriteStreamChunk(e.to_string()))
+        }
         OutBAction::DebugPrint => {
             let ch: char = match char::from_u32(data) {
                 Some(c) => c,
@@ -224,7 +427,71 @@ pub(crate) fn handle_outb(
                 }
             };
 
-            eprint!("{}", ch);
+            write_guest_output(host_funcs, 2, ch.encode_utf8(&mut [0; 4]).as_bytes())
+        }
+        OutBAction::WriteOutput => {
+            // Output is framed like a chunk of a streamed return value
+            let output = mem_mgr
+                .read_stream_chunk()
+                .map_err(|e| HandleOutbError::ReadOutput(e.to_string()))?;
+            write_guest_output(host_funcs, data, &output)
+        }
+        OutBAction::ReadInput => {
+            let max_len = mem_mgr
+                .input_chunk_capacity()
+                .map_err(|e| HandleOutbError::WriteInput(e.to_string()))?
+                .min(data as usize);
+            let mut registry = host_funcs
+                .try_lock()
+                .map_err(|e| HandleOutbError::LockFailed(file!(), line!(), e.to_string()))?;
+            let replayed = registry
+                .execution_log()
+                .map(|log| log.replay_input())
+                .transpose()
+                .map_err(replay_diverged)?
+                .flatten();
+            let input = match replayed {
+                Some(input) => input,
+                None => {
+                    // A source that fails to read is logged, and ends the input
+                    let input = registry.read_guest_input(max_len).unwrap_or_else(|e| {
+                        tracing::warn!("Failed to read guest input: {}", e);
+                        Vec::new()
+                    });
+                    if let Some(log) = registry.execution_log() {
+                        log.record_input(&input);
+                    }
+                    input
+                }
+            };
+            drop(registry);
+            mem_mgr
+                .write_input_chunk(&input)
+                .map_err(|e| HandleOutbError::WriteInput(e.to_string()))
+        }
+        OutBAction::Metric => {
+            let chunk = mem_mgr
+                .read_stream_chunk()
+                .map_err(|e| HandleOutbError::ReadMetric(e.to_string()))?;
+            // A metric that can't be recorded is not worth failing the
+            // guest call over
+            let Some(metric) =
+                GuestMetric::decode(&chunk).filter(|m| GuestMetric::is_valid_name(m.name()))
+            else {
+                tracing::warn!("Dropped an invalid metric sent by the guest");
+                return Ok(());
+            };
+            let mut registry = host_funcs
+                .try_lock()
+                .map_err(|e| HandleOutbError::LockFailed(file!(), line!(), e.to_string()))?;
+            if registry.admit_guest_metric(metric.name()) {
+                emit_guest_metric(&metric, registry.labels());
+            } else {
+                tracing::debug!(
+                    "Dropped guest metric {}, as the guest emitted too many metrics",
+                    metric.name()
+                );
+            }
             Ok(())
         }
         #[cfg(feature = "trace_guest")]
@@ -280,7 +547,7 @@ mod tests {
             // We set a logger but there is no guest log data
             // in memory, so expect a log operation to fail
             let mut mgr = new_mgr();
-            assert!(outb_log(&mut mgr).is_err());
+            assert!(outb_log(&mut mgr, &Default::default()).is_err());
         }
         {
             // Write a log message so outb_log will succeed.
@@ -298,7 +565,7 @@ mod tests {
                 )
                 .unwrap();
 
-            let res = outb_log(&mut mgr);
+            let res = outb_log(&mut mgr, &Default::default());
             assert!(res.is_ok());
             assert_eq!(0, LOGGER.num_log_calls());
             LOGGER.clear_log_calls();
@@ -335,7 +602,7 @@ mod tests {
                     )
                     .unwrap();
 
-                outb_log(&mut mgr).unwrap();
+                outb_log(&mut mgr, &Default::default()).unwrap();
 
                 LOGGER.test_log_records(|log_calls| {
                     let expected_level: Level = (&level).into();
@@ -411,7 +678,7 @@ mod tests {
                     )
                     .unwrap();
                 subscriber.clear();
-                outb_log(&mut mgr).unwrap();
+                outb_log(&mut mgr, &Default::default()).unwrap();
 
                 subscriber.test_trace_records(|spans, events| {
                     let expected_level = match level {
@@ -0,0 +1,314 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnValue};
+use hyperlight_common::flatbuffer_wrappers::guest_error::GuestError;
+
+/// The target of the events an [`OutcallTracer`] emits
+pub const OUTCALL_TARGET: &str = "hyperlight::outcall";
+
+/// What a summary is redacted with by [`OutcallTracer::redact()`]
+const REDACTED: &str = "<redacted>";
+
+/// The part of an outcall a summary is of, as given to a redactor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutcallPart {
+    /// The argument at this index
+    Arg(usize),
+    /// What the outcall returned, or the error it failed with
+    Result,
+}
+
+type Redactor = Arc<dyn Fn(&str, OutcallPart, &str) -> Option<String> + Send + Sync>;
+
+/// Traces the calls a guest makes out to the host: each host function it
+/// calls, what it writes to its stdout and stderr, and its abort, as
+/// `tracing` events with the target [`OUTCALL_TARGET`], so the
+/// conversation between the guest and the host can be followed without a
+/// debugger.
+///
+/// A host function call is traced with its name, a summary of each
+/// argument, how long it took and a summary of its result. Strings are
+/// shortened to [`with_max_summary_len()`](Self::with_max_summary_len)
+/// characters and byte vectors are only shown by length. Redactors added
+/// with [`with_redactor()`](Self::with_redactor) or
+/// [`redact()`](Self::redact) replace the summaries of values that must not
+/// be logged, such as secrets. Output is traced as an outcall to `stdout`
+/// or `stderr` with the text written as its argument, and an abort as an
+/// outcall to `abort` with its message as its argument. Debug prints,
+/// which the guest makes a character at a time, are not traced.
+///
+/// Tracing is enabled for a sandbox with
+/// [`UninitializedSandbox::trace_outcalls()`](crate::UninitializedSandbox::trace_outcalls)
+/// or [`MultiUseSandbox::set_outcall_tracer()`](crate::MultiUseSandbox::set_outcall_tracer),
+/// and the events are inside the span of the guest call that made them.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::{OutcallPart, OutcallTracer};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// sandbox.trace_outcalls(
+///     OutcallTracer::new()
+///         // Never log the password `Login` is called with
+///         .redact("Login", OutcallPart::Arg(1))
+///         // Nor any token a host function returns
+///         .with_redactor(|_, part, summary| {
+///             (part == OutcallPart::Result && summary.contains("token"))
+///                 .then(|| "<token>".to_string())
+///         }),
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct OutcallTracer {
+    redactors: Vec<Redactor>,
+    max_summary_len: usize,
+}
+
+impl Default for OutcallTracer {
+    fn default() -> Self {
+        Self {
+            redactors: Vec::new(),
+            max_summary_len: 64,
+        }
+    }
+}
+
+impl OutcallTracer {
+    /// Create a tracer that redacts nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shorten strings in summaries to `len` characters. The default is 64.
+    pub fn with_max_summary_len(mut self, len: usize) -> Self {
+        self.max_summary_len = len;
+        self
+    }
+
+    /// Pass every summary through `redactor`, which is given the name of
+    /// the outcall, the part of it summarized and the summary, and returns
+    /// what to log instead, or `None` to log the summary. Redactors run in
+    /// the order they were added, each given the summary the ones before
+    /// it left.
+    pub fn with_redactor(
+        mut self,
+        redactor: impl Fn(&str, OutcallPart, &str) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.redactors.push(Arc::new(redactor));
+        self
+    }
+
+    /// Replace the summary of `part` of every call to `function` with
+    /// `<redacted>`
+    pub fn redact(self, function: impl Into<String>, part: OutcallPart) -> Self {
+        let function = function.into();
+        self.with_redactor(move |name, redacted, _| {
+            (name == function && redacted == part).then(|| REDACTED.to_string())
+        })
+    }
+
+    /// Trace a call to the host function `function`, which took
+    /// `duration`. The arguments are summarized before the call, as the
+    /// host function consumes them.
+    pub(crate) fn host_call(
+        &self,
+        function: &str,
+        args: &str,
+        duration: Duration,
+        result: &Result<ReturnValue, GuestError>,
+    ) {
+        let (ok, result) = match result {
+            Ok(value) => (true, self.summarize_return(function, value)),
+            Err(e) => (
+                false,
+                self.redacted(function, OutcallPart::Result, e.message.clone()),
+            ),
+        };
+        tracing::info!(
+            target: OUTCALL_TARGET,
+            kind = "host_call",
+            function,
+            args,
+            duration_us = duration.as_micros() as u64,
+            ok,
+            result,
+            "{function}({args}) -> {result}"
+        );
+    }
+
+    /// Trace output the guest wrote to `fd`
+    pub(crate) fn output(&self, fd: u32, data: &[u8]) {
+        let stream = if fd == 1 { "stdout" } else { "stderr" };
+        let text = self.redacted(
+            stream,
+            OutcallPart::Arg(0),
+            self.shorten(&String::from_utf8_lossy(data)),
+        );
+        tracing::info!(
+            target: OUTCALL_TARGET,
+            kind = "print",
+            function = stream,
+            len = data.len(),
+            text,
+            "{stream}({text})"
+        );
+    }
+
+    /// Trace the guest aborting with `code` and `message`
+    pub(crate) fn abort(&self, code: u8, message: &str) {
+        let text = self.redacted("abort", OutcallPart::Arg(0), self.shorten(message));
+        tracing::info!(
+            target: OUTCALL_TARGET,
+            kind = "abort",
+            function = "abort",
+            code,
+            text,
+            "abort({code}, {text})"
+        );
+    }
+
+    /// The arguments of a call to `function`, summarized and separated by
+    /// commas
+    pub(crate) fn summarize_args(&self, function: &str, args: &[ParameterValue]) -> String {
+        args.iter()
+            .enumerate()
+            .map(|(i, arg)| self.redacted(function, OutcallPart::Arg(i), self.summarize_arg(arg)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn summarize_arg(&self, arg: &ParameterValue) -> String {
+        match arg {
+            ParameterValue::Int(v) => v.to_string(),
+            ParameterValue::UInt(v) => v.to_string(),
+            ParameterValue::Long(v) => v.to_string(),
+            ParameterValue::ULong(v) => v.to_string(),
+            ParameterValue::Float(v) => v.to_string(),
+            ParameterValue::Double(v) => v.to_string(),
+            ParameterValue::Bool(v) => v.to_string(),
+            ParameterValue::String(v) => self.shorten(v),
+            ParameterValue::VecBytes(v) => format!("<{} bytes>", v.len()),
+            ParameterValue::Value(v) => self.shorten(&format!("{v:?}")),
+            ParameterValue::VecFloat(v) => format!("<{} f32s>", v.len()),
+            ParameterValue::VecDouble(v) => format!("<{} f64s>", v.len()),
+        }
+    }
+
+    fn summarize_return(&self, function: &str, value: &ReturnValue) -> String {
+        let summary = match value {
+            ReturnValue::Int(v) => v.to_string(),
+            ReturnValue::UInt(v) => v.to_string(),
+            ReturnValue::Long(v) => v.to_string(),
+            ReturnValue::ULong(v) => v.to_string(),
+            ReturnValue::Float(v) => v.to_string(),
+            ReturnValue::Double(v) => v.to_string(),
+            ReturnValue::Bool(v) => v.to_string(),
+            ReturnValue::Void(()) => "()".to_string(),
+            ReturnValue::String(v) => self.shorten(v),
+            ReturnValue::VecBytes(v) => format!("<{} bytes>", v.len()),
+            ReturnValue::Value(v) => self.shorten(&format!("{v:?}")),
+        };
+        self.redacted(function, OutcallPart::Result, summary)
+    }
+
+    /// `text` quoted, and shortened to the longest summary
+    fn shorten(&self, text: &str) -> String {
+        match text.char_indices().nth(self.max_summary_len) {
+            Some((end, _)) => format!("{:?}...", &text[..end]),
+            None => format!("{text:?}"),
+        }
+    }
+
+    fn redacted(&self, function: &str, part: OutcallPart, summary: String) -> String {
+        self.redactors.iter().fold(summary, |summary, redactor| {
+            redactor(function, part, &summary).unwrap_or(summary)
+        })
+    }
+}
+
+impl std::fmt::Debug for OutcallTracer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutcallTracer")
+            .field("redactors", &self.redactors.len())
+            .field("max_summary_len", &self.max_summary_len)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_common::flatbuffer_wrappers::function_types::{ParameterValue, ReturnValue};
+
+    use super::{OutcallPart, OutcallTracer};
+
+    #[test]
+    fn arguments_are_summarized() {
+        let tracer = OutcallTracer::new().with_max_summary_len(5);
+        let args = [
+            ParameterValue::Int(-3),
+            ParameterValue::String("hello, world".to_string()),
+            ParameterValue::String("héllo".to_string()),
+            ParameterValue::VecBytes(vec![0; 1024]),
+            ParameterValue::Bool(true),
+        ];
+        assert_eq!(
+            tracer.summarize_args("Write", &args),
+            r#"-3, "hello"..., "héllo", <1024 bytes>, true"#
+        );
+        assert_eq!(
+            tracer.summarize_return("Read", &ReturnValue::VecBytes(vec![1, 2])),
+            "<2 bytes>"
+        );
+    }
+
+    #[test]
+    fn redactors_replace_summaries() {
+        let tracer = OutcallTracer::new()
+            .redact("Login", OutcallPart::Arg(1))
+            .with_redactor(|_, part, summary| {
+                (part == OutcallPart::Result && summary.contains("secret"))
+                    .then(|| "<secret>".to_string())
+            });
+        let args = [
+            ParameterValue::String("user".to_string()),
+            ParameterValue::String("hunter2".to_string()),
+        ];
+        assert_eq!(
+            tracer.summarize_args("Login", &args),
+            r#""user", <redacted>"#
+        );
+        assert_eq!(
+            tracer.summarize_args("Greet", &args),
+            r#""user", "hunter2""#
+        );
+        assert_eq!(
+            tracer.summarize_return("Login", &ReturnValue::String("secret-token".to_string())),
+            "<secret>"
+        );
+        assert_eq!(tracer.summarize_return("Login", &ReturnValue::Int(1)), "1");
+    }
+}
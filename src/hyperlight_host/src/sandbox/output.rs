@@ -0,0 +1,170 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::io::{self, Write};
+
+/// A writer that receives the guest's stdout or stderr.
+///
+/// Sinks are set with
+/// [`UninitializedSandbox::set_guest_stdout()`](crate::UninitializedSandbox::set_guest_stdout)
+/// and
+/// [`UninitializedSandbox::set_guest_stderr()`](crate::UninitializedSandbox::set_guest_stderr),
+/// and receive everything the guest writes to the stream, such as with
+/// `printf`, rather than it being printed by the host. Debug prints from
+/// the guest are sent to the stderr sink.
+///
+/// A sink can buffer output until a whole line has been written, and can
+/// stop passing output on once a given number of bytes has been written,
+/// silently dropping the rest. Anything still buffered is written when the
+/// sink is dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::OutputSink;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// let log = std::fs::File::create("guest.log")?;
+/// sandbox.set_guest_stdout(
+///     OutputSink::new(log)
+///         .with_line_buffering()
+///         .with_max_bytes(1024 * 1024),
+/// )?;
+/// sandbox.set_guest_stderr(OutputSink::new(std::io::sink()))?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct OutputSink {
+    writer: Box<dyn Write + Send>,
+    line_buffered: bool,
+    max_bytes: Option<usize>,
+    /// Output held back until a whole line has been written
+    buffer: Vec<u8>,
+    /// How many bytes have been accepted, counting any that are buffered
+    written: usize,
+}
+
+impl OutputSink {
+    /// A sink that passes output on to `writer` as it is written
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Box::new(writer),
+            line_buffered: false,
+            max_bytes: None,
+            buffer: Vec::new(),
+            written: 0,
+        }
+    }
+
+    /// Only pass output on once a whole line has been written
+    pub fn with_line_buffering(mut self) -> Self {
+        self.line_buffered = true;
+        self
+    }
+
+    /// Drop any output after the first `max_bytes` bytes
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Write `data`, or as much of it as fits under the size cap
+    pub(crate) fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        let len = match self.max_bytes {
+            Some(max) => data.len().min(max.saturating_sub(self.written)),
+            None => data.len(),
+        };
+        let data = &data[..len];
+        self.written += len;
+        if !self.line_buffered {
+            self.writer.write_all(data)?;
+            return self.writer.flush();
+        }
+        self.buffer.extend_from_slice(data);
+        if let Some(end) = self.buffer.iter().rposition(|&b| b == b'\n') {
+            self.writer.write_all(&self.buffer[..=end])?;
+            self.buffer.drain(..=end);
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Write anything that is buffered, even if it is not a whole line
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        self.writer.write_all(&self.buffer)?;
+        self.buffer.clear();
+        self.writer.flush()
+    }
+}
+
+impl Drop for OutputSink {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl std::fmt::Debug for OutputSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputSink")
+            .field("line_buffered", &self.line_buffered)
+            .field("max_bytes", &self.max_bytes)
+            .field("written", &self.written)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use super::OutputSink;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn sinks_buffer_lines_and_cap_their_size() {
+        let out = Shared::default();
+        let mut sink = OutputSink::new(out.clone()).with_line_buffering();
+        sink.write(b"hello ").unwrap();
+        assert!(out.0.lock().unwrap().is_empty());
+        sink.write(b"world\nand ").unwrap();
+        assert_eq!(*out.0.lock().unwrap(), b"hello world\n");
+        drop(sink);
+        assert_eq!(*out.0.lock().unwrap(), b"hello world\nand ");
+
+        let out = Shared::default();
+        let mut sink = OutputSink::new(out.clone()).with_max_bytes(8);
+        sink.write(b"12345").unwrap();
+        sink.write(b"67890").unwrap();
+        sink.write(b"more").unwrap();
+        assert_eq!(*out.0.lock().unwrap(), b"12345678");
+    }
+}
@@ -0,0 +1,432 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::GuestSymbolizer;
+use super::backtrace::GuestFrame;
+use super::protobuf::Message;
+use crate::hypervisor::InterruptHandleImpl;
+use crate::{Result, new_error};
+
+/// The most frames of a sampled stack that are followed
+pub(crate) const MAX_SAMPLE_FRAMES: usize = 128;
+
+/// The profile a sandbox's [`SamplingProfiler`] and its vcpu thread share
+pub(crate) type ProfileSlot = Arc<Mutex<Option<GuestProfile>>>;
+
+/// A CPU profile of the guest, made of samples of its stack taken at a
+/// fixed interval while it ran guest functions.
+///
+/// A profile is taken with
+/// [`MultiUseSandbox::start_profiling()`](crate::MultiUseSandbox::start_profiling)
+/// and [`MultiUseSandbox::stop_profiling()`](crate::MultiUseSandbox::stop_profiling),
+/// which interrupt the guest's vCPU at each interval, the same way
+/// [`InterruptHandle::kill()`](crate::hypervisor::InterruptHandle::kill)
+/// does, to record where it is and let it carry on.
+///
+/// Stacks are unwound by following the guest's frame pointers, so they are
+/// only complete for guests built with `-C force-frame-pointers=yes`.
+/// Otherwise each sample holds at least the function the guest was in.
+/// Time the guest spends waiting for host functions is not sampled.
+///
+/// A profile can be exported as folded stacks, for `inferno` or
+/// `flamegraph.pl`, or in the pprof format, with addresses resolved by
+/// the sandbox's [`GuestSymbolizer`].
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+/// # use std::time::Duration;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?.evolve()?;
+///
+/// sandbox.start_profiling(Duration::from_millis(1))?;
+/// sandbox.call::<u64>("SpinForMs", 100_u32)?;
+/// let profile = sandbox.stop_profiling()?;
+///
+/// std::fs::write("guest.folded", profile.to_folded())?;
+/// std::fs::write("guest.pb", profile.to_pprof())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct GuestProfile {
+    /// How often the guest was sampled
+    interval: Duration,
+    /// When profiling started
+    started_at: SystemTime,
+    /// How long profiling ran for, once it has stopped, not counting any
+    /// time the sandbox was suspended
+    duration: Duration,
+    /// How many times each stack was sampled. A stack is the address the
+    /// guest was at followed by the return addresses of its callers.
+    stacks: HashMap<Vec<u64>, u64>,
+    /// Resolves the addresses in the stacks
+    symbolizer: Arc<GuestSymbolizer>,
+}
+
+impl GuestProfile {
+    pub(crate) fn new(interval: Duration, symbolizer: Arc<GuestSymbolizer>) -> Self {
+        Self {
+            interval,
+            started_at: SystemTime::now(),
+            duration: Duration::ZERO,
+            stacks: HashMap::new(),
+            symbolizer,
+        }
+    }
+
+    /// How often the guest was sampled
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// How long the profile was taken for
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// The number of samples in the profile
+    pub fn sample_count(&self) -> u64 {
+        self.stacks.values().sum()
+    }
+
+    /// The distinct stacks that were sampled, with the number of times each
+    /// was. A stack is the address the guest was at followed by the return
+    /// addresses of its callers, innermost first.
+    pub fn stacks(&self) -> impl Iterator<Item = (&[u64], u64)> {
+        self.stacks
+            .iter()
+            .map(|(stack, &count)| (stack.as_slice(), count))
+    }
+
+    /// The profile as folded stacks, one line per distinct stack with its
+    /// frames outermost first, separated by `;`, and followed by the number
+    /// of times it was sampled, as read by `inferno-flamegraph` and
+    /// `flamegraph.pl`
+    pub fn to_folded(&self) -> String {
+        let mut lines: Vec<_> = self
+            .stacks
+            .iter()
+            .map(|(stack, count)| {
+                let frames: Vec<_> = self
+                    .frames(stack)
+                    .iter()
+                    .rev()
+                    .map(|frame| frame_name(frame).replace(';', ":"))
+                    .collect();
+                format!("{} {}", frames.join(";"), count)
+            })
+            .collect();
+        lines.sort();
+        lines.into_iter().fold(String::new(), |mut folded, line| {
+            let _ = writeln!(folded, "{line}");
+            folded
+        })
+    }
+
+    /// The profile in the uncompressed protobuf encoding of the pprof
+    /// format, as read by `go tool pprof`. Each sample counts once and for
+    /// the profile's interval of CPU time.
+    pub fn to_pprof(&self) -> Vec<u8> {
+        let mut strings = StringTable::default();
+        let mut functions: HashMap<String, u64> = HashMap::new();
+        let mut locations: HashMap<u64, u64> = HashMap::new();
+        let mut function_msgs = Vec::new();
+        let mut location_msgs = Vec::new();
+        let mut sample_msgs = Vec::new();
+        let period = self.interval.as_nanos() as u64;
+
+        let mut stacks: Vec<_> = self.stacks.iter().collect();
+        stacks.sort();
+        for (stack, &count) in stacks {
+            let mut location_ids = Vec::with_capacity(stack.len());
+            for frame in self.frames(stack) {
+                let next_id = locations.len() as u64 + 1;
+                let location_id = *locations.entry(frame.address).or_insert_with(|| {
+                    let name = frame_name(&frame);
+                    let next_function_id = functions.len() as u64 + 1;
+                    let function_id = *functions.entry(name.clone()).or_insert_with(|| {
+                        let mut function = Message::default();
+                        function.varint(1, next_function_id);
+                        function.varint(2, strings.get(&name));
+                        function.varint(3, strings.get(&name));
+                        function.varint(4, strings.get(frame.file.as_deref().unwrap_or("")));
+                        function_msgs.push(function);
+                        next_function_id
+                    });
+
+                    let mut line = Message::default();
+                    line.varint(1, function_id);
+                    line.varint(2, frame.line.unwrap_or(0) as u64);
+                    let mut location = Message::default();
+                    location.varint(1, next_id);
+                    location.varint(3, frame.address);
+                    location.message(4, &line);
+                    location_msgs.push(location);
+                    next_id
+                });
+                location_ids.push(location_id);
+            }
+
+            let mut sample = Message::default();
+            sample.packed(1, location_ids);
+            sample.packed(2, [count, count * period]);
+            sample_msgs.push(sample);
+        }
+
+        let mut profile = Message::default();
+        for (kind, unit) in [("samples", "count"), ("cpu", "nanoseconds")] {
+            let value_type = strings.value_type(kind, unit);
+            profile.message(1, &value_type);
+        }
+        for sample in &sample_msgs {
+            profile.message(2, sample);
+        }
+        for location in &location_msgs {
+            profile.message(4, location);
+        }
+        for function in &function_msgs {
+            profile.message(5, function);
+        }
+        let period_type = strings.value_type("cpu", "nanoseconds");
+        let started_at = self
+            .started_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        for string in &strings.strings {
+            profile.string(6, string);
+        }
+        profile.varint(9, started_at.as_nanos() as u64);
+        profile.varint(10, self.duration.as_nanos() as u64);
+        profile.message(11, &period_type);
+        profile.varint(12, period);
+        profile.into_bytes()
+    }
+
+    /// Record a sample of the guest's stack
+    pub(crate) fn add_sample(&mut self, stack: Vec<u64>) {
+        *self.stacks.entry(stack).or_default() += 1;
+    }
+
+    /// The frames of `stack`, of which only the first is not a return address
+    pub(crate) fn frames(&self, stack: &[u64]) -> Vec<GuestFrame> {
+        let Some((&address, callers)) = stack.split_first() else {
+            return Vec::new();
+        };
+        let mut frames = vec![self.symbolizer.symbolize(address)];
+        frames.extend(self.symbolizer.backtrace(callers).frames);
+        frames
+    }
+}
+
+/// The name a frame is shown with in an exported profile
+fn frame_name(frame: &GuestFrame) -> String {
+    frame
+        .symbol
+        .clone()
+        .unwrap_or_else(|| format!("{:#x}", frame.address))
+}
+
+/// Samples the guest's stack at a fixed interval, from a timer thread that
+/// interrupts the vcpu while it runs
+#[derive(Debug)]
+pub(crate) struct SamplingProfiler {
+    interrupt_handle: Arc<dyn InterruptHandleImpl>,
+    slot: ProfileSlot,
+    started: Instant,
+    stop: Arc<AtomicBool>,
+    timer: Option<JoinHandle<()>>,
+}
+
+impl SamplingProfiler {
+    /// Start sampling into `profile`, which the vcpu thread finds in `slot`
+    pub(crate) fn start(
+        interrupt_handle: Arc<dyn InterruptHandleImpl>,
+        slot: ProfileSlot,
+        profile: GuestProfile,
+    ) -> Result<Self> {
+        if profile.interval.is_zero() {
+            return Err(new_error!("The profiling interval must not be zero"));
+        }
+        let interval = profile.interval;
+        {
+            let mut pending = slot
+                .lock()
+                .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?;
+            if pending.is_some() {
+                return Err(new_error!("The sandbox is already being profiled"));
+            }
+            *pending = Some(profile);
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let timer = {
+            let interrupt_handle = interrupt_handle.clone();
+            let stop = stop.clone();
+            std::thread::Builder::new()
+                .name("hyperlight-profiler".to_string())
+                .spawn(move || {
+                    let mut next = Instant::now() + interval;
+                    while !stop.load(Ordering::Acquire) {
+                        let now = Instant::now();
+                        if now < next {
+                            std::thread::park_timeout(next - now);
+                            continue;
+                        }
+                        // Skip the samples that were missed rather than
+                        // taking them all at once
+                        next = (next + interval).max(now);
+                        // Only the guest's own execution is sampled, so a
+                        // request made while it is not running is dropped
+                        if !interrupt_handle.request_sample() {
+                            interrupt_handle.clear_sample_request();
+                        }
+                    }
+                })?
+        };
+
+        Ok(Self {
+            interrupt_handle,
+            slot,
+            started: Instant::now(),
+            stop,
+            timer: Some(timer),
+        })
+    }
+
+    /// Stop sampling and return the profile
+    pub(crate) fn stop(mut self) -> Result<GuestProfile> {
+        self.halt();
+        let mut profile = self
+            .slot
+            .lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .take()
+            .ok_or_else(|| new_error!("The sandbox is not being profiled"))?;
+        // A profile carried across a suspension was already running
+        profile.duration += self.started.elapsed();
+        Ok(profile)
+    }
+
+    /// Stop the timer thread and drop any sample it requested
+    fn halt(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(timer) = self.timer.take() {
+            timer.thread().unpark();
+            let _ = timer.join();
+        }
+        self.interrupt_handle.clear_sample_request();
+    }
+}
+
+impl Drop for SamplingProfiler {
+    fn drop(&mut self) {
+        self.halt();
+        if let Ok(mut pending) = self.slot.lock() {
+            pending.take();
+        }
+    }
+}
+
+/// The string table of a pprof profile, whose first entry is always empty
+struct StringTable {
+    strings: Vec<String>,
+    indices: HashMap<String, u64>,
+}
+
+impl Default for StringTable {
+    fn default() -> Self {
+        Self {
+            strings: vec![String::new()],
+            indices: HashMap::from([(String::new(), 0)]),
+        }
+    }
+}
+
+impl StringTable {
+    fn get(&mut self, string: &str) -> u64 {
+        if let Some(&index) = self.indices.get(string) {
+            return index;
+        }
+        let index = self.strings.len() as u64;
+        self.strings.push(string.to_string());
+        self.indices.insert(string.to_string(), index);
+        index
+    }
+
+    fn value_type(&mut self, kind: &str, unit: &str) -> Message {
+        let mut value_type = Message::default();
+        value_type.varint(1, self.get(kind));
+        value_type.varint(2, self.get(unit));
+        value_type
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::GuestProfile;
+    use crate::sandbox::GuestSymbolizer;
+
+    fn profile() -> GuestProfile {
+        let symbolizer = GuestSymbolizer::from_functions([
+            (0x1000, 0x100, "hyperlight_main".to_string()),
+            (0x2000, 0x100, "spin".to_string()),
+        ]);
+        let mut profile = GuestProfile::new(Duration::from_millis(1), Arc::new(symbolizer));
+        profile.add_sample(vec![0x2010, 0x1020]);
+        profile.add_sample(vec![0x2010, 0x1020]);
+        profile.add_sample(vec![0x1030]);
+        profile.add_sample(vec![0x3000, 0x2020, 0x1020]);
+        profile
+    }
+
+    #[test]
+    fn samples_are_folded_outermost_first() {
+        let profile = profile();
+        assert_eq!(profile.sample_count(), 4);
+        assert_eq!(
+            profile.to_folded(),
+            "hyperlight_main 1\n\
+             hyperlight_main;spin 2\n\
+             hyperlight_main;spin;0x3000 1\n"
+        );
+    }
+
+    #[test]
+    fn pprof_profile_has_samples_and_functions() {
+        let pprof = profile().to_pprof();
+        for name in ["samples", "count", "cpu", "nanoseconds", "spin", "0x3000"] {
+            assert!(
+                pprof.windows(name.len()).any(|w| w == name.as_bytes()),
+                "{name} is not in the string table"
+            );
+        }
+    }
+}
@@ -0,0 +1,90 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+/// A protobuf message being encoded, for the few protobuf formats, such as
+/// pprof profiles, that the host writes
+#[derive(Default)]
+pub(crate) struct Message(Vec<u8>);
+
+impl Message {
+    fn key(&mut self, field: u32, wire_type: u8) {
+        self.raw_varint(u64::from(field) << 3 | u64::from(wire_type));
+    }
+
+    fn raw_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    /// An integer field, which is left out when it is zero
+    pub(crate) fn varint(&mut self, field: u32, value: u64) {
+        if value != 0 {
+            self.key(field, 0);
+            self.raw_varint(value);
+        }
+    }
+
+    pub(crate) fn bytes(&mut self, field: u32, data: &[u8]) {
+        self.key(field, 2);
+        self.raw_varint(data.len() as u64);
+        self.0.extend_from_slice(data);
+    }
+
+    pub(crate) fn string(&mut self, field: u32, string: &str) {
+        self.bytes(field, string.as_bytes());
+    }
+
+    pub(crate) fn message(&mut self, field: u32, message: &Message) {
+        self.bytes(field, &message.0);
+    }
+
+    /// A repeated integer field in the packed encoding
+    pub(crate) fn packed(&mut self, field: u32, values: impl IntoIterator<Item = u64>) {
+        let mut packed = Message::default();
+        values
+            .into_iter()
+            .for_each(|value| packed.raw_varint(value));
+        self.message(field, &packed);
+    }
+
+    /// The encoded message
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Message;
+
+    #[test]
+    fn fields_are_encoded_with_their_keys() {
+        let mut message = Message::default();
+        message.varint(1, 300);
+        message.varint(2, 0);
+        message.packed(3, [1, 150]);
+        message.string(4, "hl");
+        assert_eq!(
+            message.into_bytes(),
+            [
+                0x08, 0xac, 0x02, 0x1a, 0x03, 0x01, 0x96, 0x01, 0x22, 0x02, b'h', b'l'
+            ]
+        );
+    }
+}
@@ -0,0 +1,287 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// A limit on how often the guest may call host functions.
+///
+/// A limit can be set on a single host function with
+/// [`UninitializedSandbox::set_host_function_rate_limit()`](crate::UninitializedSandbox::set_host_function_rate_limit),
+/// or on all host function calls made by a sandbox with
+/// [`UninitializedSandbox::set_host_call_rate_limit()`](crate::UninitializedSandbox::set_host_call_rate_limit).
+/// A call that would exceed a limit is not made, and the guest instead
+/// receives an error with the code
+/// [`ErrorCode::HostFunctionThrottled`](hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode::HostFunctionThrottled).
+///
+/// The call rate is limited with a token bucket: up to
+/// [`calls_per_second`](Self::with_calls_per_second) calls can be made in a
+/// burst, after which calls are allowed again at that rate.
+///
+/// Clones of a `RateLimit` share their quota, so the same limit can be
+/// applied across several functions or sandboxes.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::RateLimit;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// sandbox.register("Lookup", |key: String| Ok(key.len() as i32))?;
+/// sandbox.set_host_function_rate_limit(
+///     "Lookup",
+///     RateLimit::new().with_calls_per_second(100).with_max_outstanding(4),
+/// )?;
+/// sandbox.set_host_call_rate_limit(RateLimit::new().with_calls_per_second(1000))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RateLimit {
+    calls_per_second: Option<u32>,
+    max_outstanding: Option<u32>,
+    state: Arc<RateLimitState>,
+}
+
+#[derive(Debug, Default)]
+struct RateLimitState {
+    /// The tokens left in the bucket, and when it was last refilled
+    bucket: Mutex<Option<(f64, Instant)>>,
+    outstanding: AtomicU32,
+}
+
+/// Why a call was throttled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Throttled {
+    CallsPerSecond(u32),
+    MaxOutstanding(u32),
+}
+
+impl std::fmt::Display for Throttled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CallsPerSecond(n) => write!(f, "more than {n} calls per second"),
+            Self::MaxOutstanding(n) => write!(f, "more than {n} outstanding calls"),
+        }
+    }
+}
+
+impl RateLimit {
+    /// A limit that allows any number of calls
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow at most `calls_per_second` calls per second, in bursts of up
+    /// to the same number of calls
+    pub fn with_calls_per_second(mut self, calls_per_second: u32) -> Self {
+        self.calls_per_second = Some(calls_per_second);
+        self.state = Arc::default();
+        self
+    }
+
+    /// Allow at most `max_outstanding` calls to be in progress at once
+    pub fn with_max_outstanding(mut self, max_outstanding: u32) -> Self {
+        self.max_outstanding = Some(max_outstanding);
+        self.state = Arc::default();
+        self
+    }
+
+    /// Take a call from the quota, returning a permit that counts the call
+    /// as outstanding until it is dropped, or why the call must not be made
+    pub(crate) fn acquire(&self) -> std::result::Result<RateLimitPermit, Throttled> {
+        if let Some(max) = self.max_outstanding {
+            let acquired =
+                self.state
+                    .outstanding
+                    .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                        (n < max).then_some(n + 1)
+                    });
+            if acquired.is_err() {
+                return Err(Throttled::MaxOutstanding(max));
+            }
+        }
+        let permit = RateLimitPermit {
+            state: self.max_outstanding.map(|_| self.state.clone()),
+        };
+        if let Some(rate) = self.calls_per_second
+            && !take_token(&self.state.bucket, rate, rate)
+        {
+            return Err(Throttled::CallsPerSecond(rate));
+        }
+        Ok(permit)
+    }
+}
+
+/// Take a token from `bucket`, which is refilled at `rate` tokens per
+/// second up to `burst` tokens, returning whether there was one
+fn take_token(bucket: &Mutex<Option<(f64, Instant)>>, rate: u32, burst: u32) -> bool {
+    let now = Instant::now();
+    let mut bucket = bucket.lock().unwrap_or_else(|e| e.into_inner());
+    let (tokens, last) = bucket.get_or_insert((burst as f64, now));
+    let elapsed = now.saturating_duration_since(*last).as_secs_f64();
+    *tokens = (*tokens + elapsed * rate as f64).min(burst as f64);
+    *last = now;
+    if *tokens < 1.0 {
+        return false;
+    }
+    *tokens -= 1.0;
+    true
+}
+
+/// A limit on how many log records a guest may send the host, so that a
+/// guest that logs without pause cannot flood the host's logging
+/// pipeline.
+///
+/// Records are limited with a token bucket: up to
+/// [`burst`](Self::with_burst) records can be logged at once, after which
+/// records are allowed again at `records_per_second`. Records over the
+/// limit are dropped and counted, and the next record that is logged is
+/// preceded by a warning saying how many were dropped.
+///
+/// The limit is set with
+/// [`UninitializedSandbox::set_guest_log_rate_limit()`](crate::UninitializedSandbox::set_guest_log_rate_limit)
+/// or [`MultiUseSandbox::set_guest_log_rate_limit()`](crate::MultiUseSandbox::set_guest_log_rate_limit).
+/// Clones of a `GuestLogRateLimit` share their quota and their count of
+/// dropped records.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::GuestLogRateLimit;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// let limit = GuestLogRateLimit::new(100).with_burst(500);
+/// sandbox.set_guest_log_rate_limit(limit.clone())?;
+/// // ...
+/// println!("{} guest log records dropped", limit.dropped());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct GuestLogRateLimit {
+    records_per_second: u32,
+    burst: u32,
+    state: Arc<GuestLogRateLimitState>,
+}
+
+#[derive(Debug, Default)]
+struct GuestLogRateLimitState {
+    /// The tokens left in the bucket, and when it was last refilled
+    bucket: Mutex<Option<(f64, Instant)>>,
+    dropped: AtomicU64,
+    /// The records dropped since the last one that was logged
+    unreported: AtomicU64,
+}
+
+impl GuestLogRateLimit {
+    /// Allow at most `records_per_second` records per second, in bursts of
+    /// up to the same number of records
+    pub fn new(records_per_second: u32) -> Self {
+        Self {
+            records_per_second,
+            burst: records_per_second,
+            state: Arc::default(),
+        }
+    }
+
+    /// Allow bursts of up to `burst` records
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst;
+        self.state = Arc::default();
+        self
+    }
+
+    /// How many records have been dropped because they were over the limit
+    pub fn dropped(&self) -> u64 {
+        self.state.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Take a record from the quota. Returns `None` if the record must be
+    /// dropped, or else how many records were dropped since the last one
+    /// that was allowed.
+    pub(crate) fn acquire(&self) -> Option<u64> {
+        if !take_token(&self.state.bucket, self.records_per_second, self.burst) {
+            self.state.dropped.fetch_add(1, Ordering::Relaxed);
+            self.state.unreported.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        Some(self.state.unreported.swap(0, Ordering::Relaxed))
+    }
+}
+
+/// A call counted against a [`RateLimit`]'s outstanding calls
+pub(crate) struct RateLimitPermit {
+    state: Option<Arc<RateLimitState>>,
+}
+
+impl Drop for RateLimitPermit {
+    fn drop(&mut self) {
+        if let Some(state) = &self.state {
+            state.outstanding.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GuestLogRateLimit, RateLimit, Throttled};
+
+    #[test]
+    fn limits_rate_and_outstanding_calls() {
+        let limit = RateLimit::new().with_calls_per_second(2);
+        assert!(limit.acquire().is_ok());
+        // Clones share the quota
+        assert!(limit.clone().acquire().is_ok());
+        assert_eq!(limit.acquire().err(), Some(Throttled::CallsPerSecond(2)));
+
+        let limit = RateLimit::new().with_max_outstanding(1);
+        let permit = limit.acquire().unwrap();
+        assert_eq!(limit.acquire().err(), Some(Throttled::MaxOutstanding(1)));
+        drop(permit);
+        assert!(limit.acquire().is_ok());
+
+        let unlimited = RateLimit::new();
+        for _ in 0..1000 {
+            assert!(unlimited.acquire().is_ok());
+        }
+    }
+
+    #[test]
+    fn guest_logs_over_the_limit_are_dropped_and_counted() {
+        let limit = GuestLogRateLimit::new(1).with_burst(3);
+        for _ in 0..3 {
+            assert_eq!(limit.acquire(), Some(0));
+        }
+        assert_eq!(limit.acquire(), None);
+        assert_eq!(limit.clone().acquire(), None);
+        assert_eq!(limit.dropped(), 2);
+
+        // Once a token is back, the drops are reported once
+        *limit.state.bucket.lock().unwrap() = Some((1.0, std::time::Instant::now()));
+        assert_eq!(limit.acquire(), Some(2));
+        assert_eq!(limit.dropped(), 2);
+    }
+}
@@ -0,0 +1,203 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::snapshot::Snapshot;
+
+/// The longest a sandbox waits before a single restart, however many
+/// restarts have happened in a row
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How a sandbox recovers after a guest call poisons it.
+///
+/// A guest abort, an access violation, a timeout or a cancellation leaves
+/// a [`MultiUseSandbox`](crate::MultiUseSandbox) poisoned, and by default
+/// every later call fails with
+/// [`HyperlightError::PoisonedSandbox`](crate::HyperlightError::PoisonedSandbox)
+/// until the sandbox is restored. With a recovery policy set through
+/// [`MultiUseSandbox::set_recovery_policy()`](crate::MultiUseSandbox::set_recovery_policy),
+/// the next call instead restarts the guest first and then runs as normal.
+/// The call that poisoned the sandbox still returns its original error.
+///
+/// To stop a guest that crashes on every call from restarting forever,
+/// at most [`max_restarts`](Self::with_max_restarts) restarts are made in
+/// a row without a successful call in between, after which calls fail with
+/// `PoisonedSandbox` again. Each restart in a row waits twice as long as
+/// the one before it, starting from [`backoff`](Self::with_backoff).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+/// # use hyperlight_host::sandbox::RecoveryPolicy;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?.evolve()?;
+/// sandbox.set_recovery_policy(
+///     RecoveryPolicy::recreate()
+///         .with_max_restarts(5)
+///         .with_backoff(Duration::from_millis(10)),
+/// );
+///
+/// // Poisons the sandbox
+/// assert!(sandbox.call::<()>("guest_panic", "oops".to_string()).is_err());
+/// // Runs in a fresh guest
+/// let value: i32 = sandbox.call("GetStatic", ())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct RecoveryPolicy {
+    strategy: RecoveryStrategy,
+    max_restarts: u32,
+    backoff: Duration,
+}
+
+#[derive(Clone)]
+enum RecoveryStrategy {
+    None,
+    Recreate,
+    Restore(Arc<Snapshot>),
+}
+
+impl std::fmt::Debug for RecoveryStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "None"),
+            Self::Recreate => write!(f, "Recreate"),
+            Self::Restore(snapshot) => write!(f, "Restore(sandbox {})", snapshot.sandbox_id()),
+        }
+    }
+}
+
+impl RecoveryPolicy {
+    /// The default number of restarts in a row
+    pub const DEFAULT_MAX_RESTARTS: u32 = 3;
+
+    /// Never recover: a poisoned sandbox stays poisoned until it is
+    /// restored manually. This is the default.
+    pub fn none() -> Self {
+        Self::with_strategy(RecoveryStrategy::None)
+    }
+
+    /// Recover by returning the guest to the state it was in immediately
+    /// after it was initialised, as [`MultiUseSandbox::reset()`](crate::MultiUseSandbox::reset)
+    /// does
+    pub fn recreate() -> Self {
+        Self::with_strategy(RecoveryStrategy::Recreate)
+    }
+
+    /// Recover by restoring `snapshot`, which must have been taken from
+    /// the sandbox the policy is set on
+    pub fn restore_snapshot(snapshot: Arc<Snapshot>) -> Self {
+        Self::with_strategy(RecoveryStrategy::Restore(snapshot))
+    }
+
+    fn with_strategy(strategy: RecoveryStrategy) -> Self {
+        Self {
+            strategy,
+            max_restarts: Self::DEFAULT_MAX_RESTARTS,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    /// Set the maximum number of restarts in a row without a successful
+    /// call in between
+    pub fn with_max_restarts(mut self, max_restarts: u32) -> Self {
+        self.max_restarts = max_restarts;
+        self
+    }
+
+    /// Set how long to wait before the first restart in a row. Later
+    /// restarts in the same row wait twice as long as the previous one, up
+    /// to 30 seconds.
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Returns whether the policy restores a specific snapshot
+    pub(crate) fn restores_snapshot(&self) -> bool {
+        matches!(self.strategy, RecoveryStrategy::Restore(_))
+    }
+
+    /// The snapshot to restore after `restarts` restarts in a row and how
+    /// long to wait before restoring it, or `None` if the sandbox should
+    /// stay poisoned. `Some((None, _))` means the post-initialisation
+    /// snapshot.
+    pub(crate) fn next_restart(&self, restarts: u32) -> Option<(Option<Arc<Snapshot>>, Duration)> {
+        if restarts >= self.max_restarts {
+            return None;
+        }
+        let snapshot = match &self.strategy {
+            RecoveryStrategy::None => return None,
+            RecoveryStrategy::Recreate => None,
+            RecoveryStrategy::Restore(snapshot) => Some(snapshot.clone()),
+        };
+        let delay = self
+            .backoff
+            .saturating_mul(1 << restarts.min(16))
+            .min(MAX_BACKOFF);
+        Some((snapshot, delay))
+    }
+}
+
+impl Default for RecoveryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::RecoveryPolicy;
+
+    #[test]
+    fn restarts_back_off_until_limit() {
+        assert!(RecoveryPolicy::none().next_restart(0).is_none());
+
+        let policy = RecoveryPolicy::recreate()
+            .with_max_restarts(3)
+            .with_backoff(Duration::from_millis(10));
+        let delays: Vec<_> = (0..4)
+            .map(|restarts| policy.next_restart(restarts).map(|(_, delay)| delay))
+            .collect();
+        assert_eq!(
+            delays,
+            vec![
+                Some(Duration::from_millis(10)),
+                Some(Duration::from_millis(20)),
+                Some(Duration::from_millis(40)),
+                None,
+            ]
+        );
+
+        let policy = RecoveryPolicy::recreate()
+            .with_max_restarts(u32::MAX)
+            .with_backoff(Duration::from_secs(1));
+        assert_eq!(
+            policy.next_restart(100).map(|(_, delay)| delay),
+            Some(Duration::from_secs(30))
+        );
+    }
+}
@@ -0,0 +1,416 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! A process-wide registry of the live sandboxes, so that an admin or
+//! diagnostics endpoint can list and act on sandboxes owned by different
+//! parts of an application.
+//!
+//! A [`MultiUseSandbox`](crate::MultiUseSandbox) is in the registry from
+//! the moment it is evolved until it is dropped, including while it is
+//! suspended. [`sandboxes()`] lists them, [`find_sandbox()`] looks one up
+//! by its [`id`](crate::MultiUseSandbox::id), and
+//! [`subscribe_state_changes()`] follows them as they change state.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use hyperlight_host::sandbox::registry::{self, SandboxState};
+//!
+//! // Kill every call that is running in a sandbox of a tenant
+//! for sandbox in registry::sandboxes() {
+//!     if sandbox.state == SandboxState::Running
+//!         && sandbox.labels.get("tenant") == Some("contoso")
+//!         && let Some(handle) = sandbox.interrupt_handle()
+//!     {
+//!         handle.kill();
+//!     }
+//! }
+//!
+//! // Log every sandbox that becomes poisoned, for as long as
+//! // `subscription` is kept
+//! let subscription = registry::subscribe_state_changes(|change: &registry::SandboxStateChange| {
+//!     if change.state == SandboxState::Poisoned {
+//!         eprintln!("sandbox {} was poisoned", change.id);
+//!     }
+//! });
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, Weak};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::labels::SandboxLabels;
+use crate::hypervisor::InterruptHandle;
+
+/// The live sandboxes, by id. An entry removes itself when the last
+/// [`Registration`] of its sandbox is dropped.
+static SANDBOXES: Mutex<BTreeMap<u64, Weak<Entry>>> = Mutex::new(BTreeMap::new());
+
+/// The subscribers added with [`subscribe_state_changes()`], by the id of
+/// their subscription
+static SUBSCRIBERS: RwLock<Vec<(u64, Arc<StateSubscriber>)>> = RwLock::new(Vec::new());
+
+/// The id of the next subscription
+static NEXT_SUBSCRIPTION: AtomicU64 = AtomicU64::new(0);
+
+type StateSubscriber = dyn Fn(&SandboxStateChange) + Send + Sync;
+
+/// The state of a sandbox in the registry
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SandboxState {
+    /// The sandbox is waiting for a guest call
+    Idle,
+    /// The sandbox is running a guest call
+    Running,
+    /// A guest call poisoned the sandbox, and it must be restored before
+    /// it can be called again
+    Poisoned,
+    /// The sandbox's virtual machine has been torn down until it is next
+    /// used
+    Suspended,
+    /// The sandbox was dropped, and is no longer in the registry
+    Dropped,
+}
+
+/// A live sandbox, as listed by [`sandboxes()`]
+#[derive(Clone, Debug)]
+pub struct SandboxInfo {
+    /// The id of the sandbox
+    pub id: u64,
+    /// The labels of the sandbox
+    pub labels: SandboxLabels,
+    /// What the sandbox is doing
+    pub state: SandboxState,
+    /// When the sandbox was evolved
+    pub created_at: SystemTime,
+    /// How long ago the sandbox was evolved
+    pub uptime: Duration,
+    /// The size in bytes of the guest memory of the sandbox, when it was
+    /// last called, restored or resumed. A suspended sandbox keeps its
+    /// last size.
+    pub memory_size: usize,
+    interrupt_handle: Option<Arc<dyn InterruptHandle>>,
+}
+
+impl SandboxInfo {
+    /// A handle to kill the guest calls the sandbox runs, or `None` if it
+    /// is suspended and has no virtual machine to interrupt
+    pub fn interrupt_handle(&self) -> Option<&Arc<dyn InterruptHandle>> {
+        self.interrupt_handle.as_ref()
+    }
+}
+
+/// A sandbox changing state, as given to the subscribers added with
+/// [`subscribe_state_changes()`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SandboxStateChange {
+    /// The id of the sandbox
+    pub id: u64,
+    /// The labels of the sandbox
+    pub labels: SandboxLabels,
+    /// The state the sandbox was in, or `None` if it was just added to
+    /// the registry
+    pub previous: Option<SandboxState>,
+    /// The state the sandbox is now in
+    pub state: SandboxState,
+}
+
+/// Returns every live sandbox in the process, in the order they were
+/// created
+pub fn sandboxes() -> Vec<SandboxInfo> {
+    // The entries are only read once the lock is released, as dropping
+    // the last reference to one takes the lock
+    let entries: Vec<Arc<Entry>> = lock_sandboxes()
+        .values()
+        .filter_map(Weak::upgrade)
+        .collect();
+    entries.iter().map(|entry| entry.info()).collect()
+}
+
+/// Returns the live sandbox with `id`, if there is one
+pub fn find_sandbox(id: u64) -> Option<SandboxInfo> {
+    let entry = lock_sandboxes().get(&id).and_then(Weak::upgrade)?;
+    Some(entry.info())
+}
+
+/// Call `subscriber` whenever a sandbox is created, changes state or is
+/// dropped, until the returned [`StateSubscription`] is dropped.
+///
+/// Changes are delivered synchronously, on the thread that made them,
+/// which for calls into the guest is the thread making the call, so
+/// subscribers should return quickly. A subscriber must not call back
+/// into the sandbox the change is about.
+pub fn subscribe_state_changes(
+    subscriber: impl Fn(&SandboxStateChange) + Send + Sync + 'static,
+) -> StateSubscription {
+    let id = NEXT_SUBSCRIPTION.fetch_add(1, Ordering::Relaxed);
+    SUBSCRIBERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((id, Arc::new(subscriber)));
+    StateSubscription { id }
+}
+
+/// A subscriber added with [`subscribe_state_changes()`], which is removed
+/// when this is dropped
+#[derive(Debug)]
+#[must_use = "the subscriber is removed when the subscription is dropped"]
+pub struct StateSubscription {
+    id: u64,
+}
+
+impl Drop for StateSubscription {
+    fn drop(&mut self) {
+        SUBSCRIBERS
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|(id, _)| *id != self.id);
+    }
+}
+
+fn lock_sandboxes() -> MutexGuard<'static, BTreeMap<u64, Weak<Entry>>> {
+    SANDBOXES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Deliver a change to the subscribers, without any lock held
+fn notify(id: u64, labels: &SandboxLabels, previous: Option<SandboxState>, state: SandboxState) {
+    let subscribers: Vec<_> = SUBSCRIBERS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(_, subscriber)| subscriber.clone())
+        .collect();
+    if subscribers.is_empty() {
+        return;
+    }
+    let change = SandboxStateChange {
+        id,
+        labels: labels.clone(),
+        previous,
+        state,
+    };
+    for subscriber in &subscribers {
+        subscriber(&change);
+    }
+}
+
+/// What changes about a sandbox in the registry
+#[derive(Debug)]
+struct Status {
+    state: SandboxState,
+    memory_size: usize,
+    interrupt_handle: Option<Arc<dyn InterruptHandle>>,
+}
+
+/// A sandbox in the registry
+#[derive(Debug)]
+struct Entry {
+    id: u64,
+    labels: SandboxLabels,
+    created_at: SystemTime,
+    started: Instant,
+    status: Mutex<Status>,
+}
+
+impl Entry {
+    fn lock_status(&self) -> MutexGuard<'_, Status> {
+        self.status.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn info(&self) -> SandboxInfo {
+        let status = self.lock_status();
+        SandboxInfo {
+            id: self.id,
+            labels: self.labels.clone(),
+            state: status.state,
+            created_at: self.created_at,
+            uptime: self.started.elapsed(),
+            memory_size: status.memory_size,
+            interrupt_handle: status.interrupt_handle.clone(),
+        }
+    }
+
+    /// Change the status of the sandbox with `update`, and tell the
+    /// subscribers if its state changed
+    fn update(&self, update: impl FnOnce(&mut Status)) {
+        let (previous, state) = {
+            let mut status = self.lock_status();
+            let previous = status.state;
+            update(&mut status);
+            (previous, status.state)
+        };
+        if previous != state {
+            notify(self.id, &self.labels, Some(previous), state);
+        }
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        lock_sandboxes().remove(&self.id);
+        let previous = self.lock_status().state;
+        notify(self.id, &self.labels, Some(previous), SandboxState::Dropped);
+    }
+}
+
+/// The place of a sandbox in the registry, which it keeps while it is
+/// suspended. The sandbox is removed from the registry when the last
+/// clone of its registration is dropped.
+#[derive(Clone, Debug)]
+pub(crate) struct Registration(Arc<Entry>);
+
+impl Registration {
+    /// Add an idle sandbox to the registry
+    pub(crate) fn register(
+        id: u64,
+        labels: SandboxLabels,
+        memory_size: usize,
+        interrupt_handle: Arc<dyn InterruptHandle>,
+    ) -> Self {
+        let entry = Arc::new(Entry {
+            id,
+            labels,
+            created_at: SystemTime::now(),
+            started: Instant::now(),
+            status: Mutex::new(Status {
+                state: SandboxState::Idle,
+                memory_size,
+                interrupt_handle: Some(interrupt_handle),
+            }),
+        });
+        lock_sandboxes().insert(id, Arc::downgrade(&entry));
+        notify(id, &entry.labels, None, SandboxState::Idle);
+        Self(entry)
+    }
+
+    /// The sandbox started a guest call
+    pub(crate) fn call_started(&self) {
+        self.0.update(|status| status.state = SandboxState::Running);
+    }
+
+    /// The sandbox is waiting for a call, or poisoned, and its memory has
+    /// `memory_size` bytes
+    pub(crate) fn set_ready(&self, poisoned: bool, memory_size: usize) {
+        self.0.update(|status| {
+            status.state = if poisoned {
+                SandboxState::Poisoned
+            } else {
+                SandboxState::Idle
+            };
+            status.memory_size = memory_size;
+        });
+    }
+
+    /// The sandbox's virtual machine was torn down
+    pub(crate) fn suspended(&self) {
+        self.0.update(|status| {
+            status.state = SandboxState::Suspended;
+            status.interrupt_handle = None;
+        });
+    }
+
+    /// The sandbox has a new virtual machine, interrupted through
+    /// `interrupt_handle`, and its memory has `memory_size` bytes
+    pub(crate) fn resumed(&self, interrupt_handle: Arc<dyn InterruptHandle>, memory_size: usize) {
+        self.0.update(|status| {
+            status.state = SandboxState::Idle;
+            status.memory_size = memory_size;
+            status.interrupt_handle = Some(interrupt_handle);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{Registration, SandboxState, find_sandbox, subscribe_state_changes};
+    use crate::hypervisor::InterruptHandle;
+    use crate::sandbox::SandboxLabels;
+
+    #[derive(Debug)]
+    struct NoInterrupt;
+
+    impl InterruptHandle for NoInterrupt {
+        fn kill(&self) -> bool {
+            false
+        }
+
+        #[cfg(gdb)]
+        fn kill_from_debugger(&self) -> bool {
+            false
+        }
+
+        fn dropped(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn sandboxes_are_followed_until_dropped() {
+        // Ids far from those of real sandboxes, as tests share the registry
+        const ID: u64 = u64::MAX - 1;
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let subscription = {
+            let changes = changes.clone();
+            subscribe_state_changes(move |change| {
+                if change.id == ID {
+                    changes
+                        .lock()
+                        .unwrap()
+                        .push((change.previous, change.state));
+                }
+            })
+        };
+        let mut labels = SandboxLabels::default();
+        labels.set("tenant", "contoso").unwrap();
+
+        let registration = Registration::register(ID, labels, 4096, Arc::new(NoInterrupt));
+        let info = find_sandbox(ID).unwrap();
+        assert_eq!(info.labels.get("tenant"), Some("contoso"));
+        assert_eq!(info.state, SandboxState::Idle);
+        assert_eq!(info.memory_size, 4096);
+
+        registration.call_started();
+        registration.set_ready(true, 8192);
+        registration.suspended();
+        // A suspended sandbox stays in the registry, but can't be killed
+        let suspended = registration.clone();
+        assert!(find_sandbox(ID).unwrap().interrupt_handle().is_none());
+        suspended.resumed(Arc::new(NoInterrupt), 8192);
+        drop(suspended);
+        assert_eq!(find_sandbox(ID).unwrap().state, SandboxState::Idle);
+        drop(registration);
+        assert!(find_sandbox(ID).is_none());
+
+        drop(subscription);
+        Registration::register(ID, SandboxLabels::default(), 0, Arc::new(NoInterrupt));
+
+        use SandboxState::*;
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![
+                (None, Idle),
+                (Some(Idle), Running),
+                (Some(Running), Poisoned),
+                (Some(Poisoned), Suspended),
+                (Some(Suspended), Idle),
+                (Some(Idle), Dropped),
+            ]
+        );
+    }
+}
@@ -0,0 +1,408 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use crate::{HyperlightError, Result, new_error};
+
+/// The nondeterministic inputs of a sandbox's execution, which can be
+/// replayed to run the guest through exactly the same execution again.
+///
+/// Recording is turned on with
+/// [`UninitializedSandbox::record_execution()`](crate::UninitializedSandbox::record_execution),
+/// and covers the sandbox from when it is evolved. It holds the seed the
+/// guest was initialised with, the contents of the input buffer for each
+/// guest call, the result of each host function call, and each piece of
+/// input the guest read from its stdin. The guest's paravirtual clock is
+/// not recorded, so a sandbox that is recording or replaying has no
+/// clock, and the time it gets from host functions is covered instead.
+///
+/// A recording is replayed in a new sandbox created from the same guest
+/// binary and configuration, with
+/// [`UninitializedSandbox::replay_execution()`](crate::UninitializedSandbox::replay_execution)
+/// and then [`MultiUseSandbox::replay()`](crate::MultiUseSandbox::replay),
+/// for example under a debugger to chase a bug that only happens
+/// sometimes. While replaying, host functions are not called and the
+/// guest's stdin is not read, their recorded results are passed to the
+/// guest instead. If the guest does anything other than what was recorded,
+/// the call fails with [`HyperlightError::ReplayDiverged`].
+///
+/// Restoring a snapshot of, or resetting, a sandbox that is recording is
+/// not recorded, so such executions can't be replayed.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{GuestBinary, MultiUseSandbox, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::ExecutionRecording;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut u_sbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// u_sbox.record_execution()?;
+/// let mut sandbox: MultiUseSandbox = u_sbox.evolve()?;
+/// let _ = sandbox.call::<String>("Process", "input".to_string());
+/// if let Some(recording) = sandbox.execution_recording() {
+///     std::fs::write("execution.hlrec", recording.to_bytes())?;
+/// }
+///
+/// // Later, possibly in another process
+/// let recording = ExecutionRecording::from_bytes(&std::fs::read("execution.hlrec")?)?;
+/// let mut u_sbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?;
+/// u_sbox.replay_execution(recording)?;
+/// let mut sandbox: MultiUseSandbox = u_sbox.evolve()?;
+/// for result in sandbox.replay()? {
+///     println!("{:?}", result);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExecutionRecording {
+    /// The seed the guest was initialised with
+    seed: u64,
+    /// What the guest was given, in the order it was given it
+    events: Vec<RecordedEvent>,
+}
+
+/// An input to the guest in an [`ExecutionRecording`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum RecordedEvent {
+    /// The guest function calls made in one entry into the guest, in call
+    /// order, each as it was written to the input buffer
+    GuestCalls(Vec<Vec<u8>>),
+    /// A call to the host function `name`, with its result as it was
+    /// written to the input buffer
+    HostCall { name: String, response: Vec<u8> },
+    /// A piece of input read from the guest's stdin
+    Input(Vec<u8>),
+}
+
+/// The first bytes of an encoded recording, with its format version
+const MAGIC: &[u8; 8] = b"HLREC\0\0\x01";
+
+impl ExecutionRecording {
+    /// The seed the guest was initialised with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// How many times the host called into the guest, each with one guest
+    /// function call or a batch of them
+    pub fn guest_calls(&self) -> usize {
+        self.events
+            .iter()
+            .filter(|event| matches!(event, RecordedEvent::GuestCalls(_)))
+            .count()
+    }
+
+    /// Encode the recording, to be saved and replayed later
+    pub fn to_bytes(&self) -> Vec<u8> {
+        fn put(out: &mut Vec<u8>, data: &[u8]) {
+            out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            out.extend_from_slice(data);
+        }
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&self.seed.to_le_bytes());
+        for event in &self.events {
+            match event {
+                RecordedEvent::GuestCalls(calls) => {
+                    out.push(0);
+                    out.extend_from_slice(&(calls.len() as u64).to_le_bytes());
+                    for call in calls {
+                        put(&mut out, call);
+                    }
+                }
+                RecordedEvent::HostCall { name, response } => {
+                    out.push(1);
+                    put(&mut out, name.as_bytes());
+                    put(&mut out, response);
+                }
+                RecordedEvent::Input(input) => {
+                    out.push(2);
+                    put(&mut out, input);
+                }
+            }
+        }
+        out
+    }
+
+    /// Decode a recording encoded with [`to_bytes()`](Self::to_bytes)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(new_error!("Not an execution recording"));
+        }
+        let seed = reader.u64()?;
+        let mut events = Vec::new();
+        while let Some(tag) = reader.tag() {
+            events.push(match tag {
+                0 => {
+                    let n = reader.u64()?;
+                    let calls = (0..n)
+                        .map(|_| reader.bytes().map(<[u8]>::to_vec))
+                        .collect::<Result<_>>()?;
+                    RecordedEvent::GuestCalls(calls)
+                }
+                1 => RecordedEvent::HostCall {
+                    name: String::from_utf8(reader.bytes()?.to_vec())
+                        .map_err(|_| new_error!("Invalid host function name in recording"))?,
+                    response: reader.bytes()?.to_vec(),
+                },
+                2 => RecordedEvent::Input(reader.bytes()?.to_vec()),
+                tag => return Err(new_error!("Invalid event {} in recording", tag)),
+            });
+        }
+        Ok(Self { seed, events })
+    }
+}
+
+/// Reads the parts of an encoded [`ExecutionRecording`]
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.0.len() < n {
+            return Err(new_error!("Execution recording is truncated"));
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn tag(&mut self) -> Option<u8> {
+        let (&tag, tail) = self.0.split_first()?;
+        self.0 = tail;
+        Some(tag)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into()?))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let n = usize::try_from(self.u64()?)?;
+        self.take(n)
+    }
+}
+
+/// A sandbox's [`ExecutionRecording`], either being recorded or being
+/// replayed
+#[derive(Debug)]
+pub(crate) enum ExecutionLog {
+    Recording(ExecutionRecording),
+    Replaying {
+        recording: ExecutionRecording,
+        /// The index of the next event to be replayed
+        next: usize,
+    },
+}
+
+impl ExecutionLog {
+    /// The seed to initialise the guest with, which is `seed` unless
+    /// replaying
+    pub(crate) fn seed(&mut self, seed: u64) -> u64 {
+        match self {
+            Self::Recording(recording) => {
+                recording.seed = seed;
+                seed
+            }
+            Self::Replaying { recording, .. } => recording.seed,
+        }
+    }
+
+    /// The recording so far, if recording
+    pub(crate) fn recording(&self) -> Option<&ExecutionRecording> {
+        match self {
+            Self::Recording(recording) => Some(recording),
+            Self::Replaying { .. } => None,
+        }
+    }
+
+    /// Record an entry into the guest with `calls`, or check that it is the
+    /// next one that was recorded
+    pub(crate) fn guest_calls(&mut self, calls: &[impl AsRef<[u8]>]) -> Result<()> {
+        match self.replay(
+            |event| matches!(event, RecordedEvent::GuestCalls(_)),
+            "a guest call",
+        )? {
+            None => {
+                let calls = calls.iter().map(|call| call.as_ref().to_vec()).collect();
+                self.record(RecordedEvent::GuestCalls(calls));
+                Ok(())
+            }
+            Some(RecordedEvent::GuestCalls(recorded))
+                if recorded
+                    .iter()
+                    .map(Vec::as_slice)
+                    .eq(calls.iter().map(AsRef::as_ref)) =>
+            {
+                Ok(())
+            }
+            Some(_) => Err(HyperlightError::ReplayDiverged(
+                "a guest call was made with different arguments than recorded".to_string(),
+            )),
+        }
+    }
+
+    /// The recorded response of the call to the host function `name` the
+    /// guest is making, if replaying
+    pub(crate) fn replay_host_call(&mut self, name: &str) -> Result<Option<Vec<u8>>> {
+        let description = format!("a call to host function {name}");
+        match self.replay(
+            |event| matches!(event, RecordedEvent::HostCall { name: n, .. } if n == name),
+            &description,
+        )? {
+            Some(RecordedEvent::HostCall { response, .. }) => Ok(Some(response)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Record the response of a call to the host function `name`, if
+    /// recording
+    pub(crate) fn record_host_call(&mut self, name: &str, response: &[u8]) {
+        self.record(RecordedEvent::HostCall {
+            name: name.to_string(),
+            response: response.to_vec(),
+        });
+    }
+
+    /// The recorded input of the read of stdin the guest is making, if
+    /// replaying
+    pub(crate) fn replay_input(&mut self) -> Result<Option<Vec<u8>>> {
+        match self.replay(
+            |event| matches!(event, RecordedEvent::Input(_)),
+            "a read of stdin",
+        )? {
+            Some(RecordedEvent::Input(input)) => Ok(Some(input)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Record input read from the guest's stdin, if recording
+    pub(crate) fn record_input(&mut self, input: &[u8]) {
+        self.record(RecordedEvent::Input(input.to_vec()));
+    }
+
+    /// The guest function calls of each entry into the guest that is yet
+    /// to be replayed
+    pub(crate) fn remaining_guest_calls(&self) -> Vec<Vec<Vec<u8>>> {
+        match self {
+            Self::Recording(_) => Vec::new(),
+            Self::Replaying { recording, next } => recording.events[*next..]
+                .iter()
+                .filter_map(|event| match event {
+                    RecordedEvent::GuestCalls(calls) => Some(calls.clone()),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    fn record(&mut self, event: RecordedEvent) {
+        if let Self::Recording(recording) = self {
+            recording.events.push(event);
+        }
+    }
+
+    /// The next recorded event if replaying, which must match `expected`,
+    /// or `None` if recording
+    fn replay(
+        &mut self,
+        expected: impl Fn(&RecordedEvent) -> bool,
+        description: &str,
+    ) -> Result<Option<RecordedEvent>> {
+        let Self::Replaying { recording, next } = self else {
+            return Ok(None);
+        };
+        match recording.events.get(*next) {
+            Some(event) if expected(event) => {
+                *next += 1;
+                Ok(Some(event.clone()))
+            }
+            Some(event) => Err(HyperlightError::ReplayDiverged(format!(
+                "{description} was made, but {} was recorded",
+                event.description()
+            ))),
+            None => Err(HyperlightError::ReplayDiverged(format!(
+                "{description} was made after the end of the recording"
+            ))),
+        }
+    }
+}
+
+impl RecordedEvent {
+    fn description(&self) -> String {
+        match self {
+            Self::GuestCalls(_) => "a guest call".to_string(),
+            Self::HostCall { name, .. } => format!("a call to host function {name}"),
+            Self::Input(_) => "a read of stdin".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExecutionLog, ExecutionRecording};
+    use crate::HyperlightError;
+
+    #[test]
+    fn recorded_execution_replays_and_detects_divergence() {
+        let mut log = ExecutionLog::Recording(ExecutionRecording::default());
+        assert_eq!(log.seed(42), 42);
+        log.guest_calls(&[b"call".to_vec()]).unwrap();
+        assert_eq!(log.replay_host_call("Now").unwrap(), None);
+        log.record_host_call("Now", b"12:00");
+        assert_eq!(log.replay_input().unwrap(), None);
+        log.record_input(b"line\n");
+
+        let recording = log.recording().unwrap().clone();
+        assert_eq!(recording.guest_calls(), 1);
+        let recording = ExecutionRecording::from_bytes(&recording.to_bytes()).unwrap();
+        assert_eq!(Some(&recording), log.recording());
+
+        let mut log = ExecutionLog::Replaying {
+            recording: recording.clone(),
+            next: 0,
+        };
+        assert_eq!(log.seed(7), 42);
+        assert_eq!(log.remaining_guest_calls(), [[b"call".to_vec()]]);
+        log.guest_calls(&[b"call".to_vec()]).unwrap();
+        assert_eq!(
+            log.replay_host_call("Now").unwrap().as_deref(),
+            Some(&b"12:00"[..])
+        );
+        assert_eq!(log.replay_input().unwrap().as_deref(), Some(&b"line\n"[..]));
+        assert!(matches!(
+            log.replay_input(),
+            Err(HyperlightError::ReplayDiverged(_))
+        ));
+
+        let mut log = ExecutionLog::Replaying { recording, next: 0 };
+        assert!(matches!(
+            log.guest_calls(&[b"other".to_vec()]),
+            Err(HyperlightError::ReplayDiverged(_))
+        ));
+        assert!(matches!(
+            log.replay_host_call("Other"),
+            Err(HyperlightError::ReplayDiverged(_))
+        ));
+        assert!(ExecutionRecording::from_bytes(b"HLREC").is_err());
+    }
+}
@@ -0,0 +1,251 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::time::Duration;
+
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+
+use crate::{HyperlightError, Result};
+
+/// The longest a call waits before a single retry, however many attempts
+/// have been made
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// A kind of guest call failure that is known to be transient: the same
+/// call can succeed if it is made again a little later, and the failed
+/// attempt did not poison the sandbox.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransientFailure {
+    /// A host function called by the guest was throttled by a
+    /// [`RateLimit`](crate::sandbox::RateLimit), and the guest returned
+    /// the resulting error
+    HostFunctionThrottled,
+}
+
+impl TransientFailure {
+    /// All kinds of transient failure
+    pub const ALL: [Self; 1] = [Self::HostFunctionThrottled];
+
+    /// The kind of transient failure `error` is, or `None` if it is not
+    /// known to be transient
+    pub fn of(error: &HyperlightError) -> Option<Self> {
+        match error.unlabelled() {
+            HyperlightError::HostFunctionThrottled(..)
+            | HyperlightError::GuestError {
+                code: ErrorCode::HostFunctionThrottled,
+                ..
+            } => Some(Self::HostFunctionThrottled),
+            _ => None,
+        }
+    }
+}
+
+/// How a guest call is retried when it fails with a
+/// [`TransientFailure`].
+///
+/// A policy is passed to
+/// [`MultiUseSandbox::call_with_retry()`](crate::MultiUseSandbox::call_with_retry)
+/// or [`SharedSandbox::call_with_retry()`](crate::sandbox::SharedSandbox::call_with_retry).
+/// The call is made at most [`max_attempts`](Self::with_max_attempts)
+/// times, and is only made again if it failed in one of the ways the
+/// policy [retries on](Self::retry_on), which must be chosen for each
+/// policy: a new policy retries nothing. Any other error is returned
+/// straight away. Each retry waits twice as long as the one before it,
+/// starting from [`backoff`](Self::with_backoff).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::{Fairness, RetryPolicy, SharedSandbox, TransientFailure};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?.evolve()?;
+/// let shared = SharedSandbox::new(sandbox, 16, Fairness::Fifo);
+///
+/// // Retries if a rate limited host function the guest calls is throttled
+/// let policy = RetryPolicy::new()
+///     .with_max_attempts(5)
+///     .with_backoff(Duration::from_millis(10))
+///     .retry_on([TransientFailure::HostFunctionThrottled]);
+/// let summary: String = shared.call_with_retry("FetchAndSummarise", 42_u32, &policy)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Duration,
+    retry_on: Vec<TransientFailure>,
+}
+
+impl RetryPolicy {
+    /// The default number of attempts
+    pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+    /// A policy that makes at most
+    /// [`DEFAULT_MAX_ATTEMPTS`](Self::DEFAULT_MAX_ATTEMPTS) attempts with
+    /// no wait in between, and retries nothing until the failures to retry
+    /// are chosen with [`retry_on()`](Self::retry_on)
+    pub fn new() -> Self {
+        Self {
+            max_attempts: Self::DEFAULT_MAX_ATTEMPTS,
+            backoff: Duration::ZERO,
+            retry_on: Vec::new(),
+        }
+    }
+
+    /// Set the maximum number of attempts, including the first one
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Set how long to wait before the first retry. Later retries wait
+    /// twice as long as the previous one, up to 30 seconds.
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Retry the given kinds of transient failure, and no others
+    pub fn retry_on(mut self, failures: impl IntoIterator<Item = TransientFailure>) -> Self {
+        self.retry_on = failures.into_iter().collect();
+        self
+    }
+
+    /// Returns whether a call that failed with `error` is retried
+    pub fn retries(&self, error: &HyperlightError) -> bool {
+        TransientFailure::of(error).is_some_and(|failure| self.retry_on.contains(&failure))
+    }
+
+    /// Run `attempt` until it succeeds, fails with an error the policy does
+    /// not retry, or the maximum number of attempts has been made
+    pub(crate) fn run<T>(&self, mut attempt: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut attempts = 1;
+        loop {
+            match attempt() {
+                Err(e) if attempts < self.max_attempts && self.retries(&e) => {
+                    let delay = self
+                        .backoff
+                        .saturating_mul(1 << (attempts - 1).min(16))
+                        .min(MAX_BACKOFF);
+                    tracing::debug!(attempts, ?delay, "retrying guest call after {e}");
+                    std::thread::sleep(delay);
+                    attempts += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+
+    use super::{RetryPolicy, TransientFailure};
+    use crate::HyperlightError;
+    use crate::sandbox::GuestBacktrace;
+
+    fn throttled() -> HyperlightError {
+        HyperlightError::HostFunctionThrottled("Fetch".to_string(), "1 call/s".to_string())
+    }
+
+    #[test]
+    fn classifies_transient_failures() {
+        assert_eq!(
+            TransientFailure::of(&throttled()),
+            Some(TransientFailure::HostFunctionThrottled)
+        );
+        assert_eq!(
+            TransientFailure::of(&HyperlightError::GuestError {
+                code: ErrorCode::HostFunctionThrottled,
+                message: "throttled".to_string(),
+                guest_backtrace: GuestBacktrace::default(),
+            }),
+            Some(TransientFailure::HostFunctionThrottled)
+        );
+        assert_eq!(
+            TransientFailure::of(&HyperlightError::ExecutionCanceledByHost()),
+            None
+        );
+        assert_eq!(TransientFailure::of(&HyperlightError::SandboxBusy), None);
+    }
+
+    #[test]
+    fn retries_until_success_or_limit() {
+        let policy = RetryPolicy::new()
+            .with_max_attempts(3)
+            .retry_on(TransientFailure::ALL);
+
+        let mut attempts = 0;
+        let res = policy.run(|| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(throttled())
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(res.unwrap(), 3);
+
+        let mut attempts = 0;
+        let res = policy.run::<()>(|| {
+            attempts += 1;
+            Err(throttled())
+        });
+        assert!(matches!(
+            res,
+            Err(HyperlightError::HostFunctionThrottled(..))
+        ));
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn only_retries_chosen_failures() {
+        // Nothing is retried unless it is chosen
+        let mut attempts = 0;
+        let res = RetryPolicy::new().run::<()>(|| {
+            attempts += 1;
+            Err(throttled())
+        });
+        assert!(matches!(
+            res,
+            Err(HyperlightError::HostFunctionThrottled(..))
+        ));
+        assert_eq!(attempts, 1);
+
+        let policy = RetryPolicy::new().retry_on([TransientFailure::HostFunctionThrottled]);
+
+        let mut attempts = 0;
+        let res = policy.run::<()>(|| {
+            attempts += 1;
+            Err(HyperlightError::PoisonedSandbox)
+        });
+        assert!(matches!(res, Err(HyperlightError::PoisonedSandbox)));
+        assert_eq!(attempts, 1);
+    }
+}
@@ -0,0 +1,156 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use seccompiler::{BpfProgram, SeccompAction, SeccompFilter, TargetArch};
+
+use crate::{Result, new_error};
+
+/// The syscalls every host function worker thread may make: enough to
+/// allocate memory, write to already-open files (such as stdout) and exit.
+const DEFAULT_ALLOWED_SYSCALLS: &[i64] = &[
+    libc::SYS_brk,
+    libc::SYS_clock_gettime,
+    libc::SYS_exit,
+    libc::SYS_futex,
+    libc::SYS_madvise,
+    libc::SYS_mmap,
+    libc::SYS_mprotect,
+    libc::SYS_munmap,
+    libc::SYS_rt_sigprocmask,
+    libc::SYS_rt_sigreturn,
+    libc::SYS_sched_yield,
+    libc::SYS_sigaltstack,
+    libc::SYS_write,
+];
+
+/// Adjustments to the seccomp filter that a host function runs under.
+///
+/// When the `seccomp` feature is enabled, every host function is run on a
+/// short-lived worker thread that only allows a small default set of
+/// syscalls. Any other syscall fails with `EPERM`. A `SyscallFilter`
+/// passed to
+/// [`UninitializedSandbox::register_with_syscall_filter()`](crate::UninitializedSandbox::register_with_syscall_filter)
+/// adds syscalls to, or removes them from, that set for a single host
+/// function. Syscalls are identified by their number, e.g.
+/// `libc::SYS_openat`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::sandbox::SyscallFilter;
+/// // Allow the host function to open and read files, but not to write them
+/// let filter = SyscallFilter::new()
+///     .allow(libc::SYS_openat)
+///     .allow(libc::SYS_read)
+///     .allow(libc::SYS_close)
+///     .deny(libc::SYS_write);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SyscallFilter {
+    allowed: BTreeSet<i64>,
+    denied: BTreeSet<i64>,
+}
+
+impl SyscallFilter {
+    /// Create a filter that leaves the default set of syscalls unchanged
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow the host function to make `syscall`
+    pub fn allow(mut self, syscall: i64) -> Self {
+        self.allowed.insert(syscall);
+        self
+    }
+
+    /// Prevent the host function from making `syscall`, even if it is in
+    /// the default set. Denying a syscall takes precedence over allowing it.
+    pub fn deny(mut self, syscall: i64) -> Self {
+        self.denied.insert(syscall);
+        self
+    }
+
+    /// Compile the filter to a BPF program
+    pub(crate) fn compile(&self) -> Result<BpfProgram> {
+        let rules = DEFAULT_ALLOWED_SYSCALLS
+            .iter()
+            .chain(&self.allowed)
+            .filter(|syscall| !self.denied.contains(syscall))
+            .map(|&syscall| (syscall, vec![]))
+            .collect::<BTreeMap<_, _>>();
+        let arch = TargetArch::try_from(std::env::consts::ARCH)
+            .map_err(|e| new_error!("unsupported seccomp architecture: {e}"))?;
+        let filter = SeccompFilter::new(
+            rules,
+            SeccompAction::Errno(libc::EPERM as u32),
+            SeccompAction::Allow,
+            arch,
+        )
+        .map_err(|e| new_error!("failed to create seccomp filter: {e}"))?;
+        filter
+            .try_into()
+            .map_err(|e| new_error!("failed to compile seccomp filter: {e}"))
+    }
+}
+
+/// Restrict the calling thread with `filter`. This cannot be undone.
+pub(crate) fn apply(filter: &BpfProgram) -> Result<()> {
+    seccompiler::apply_filter(filter).map_err(|e| new_error!("failed to apply seccomp filter: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use seccompiler::BpfProgram;
+
+    use super::{SyscallFilter, apply};
+
+    fn getppid() -> i64 {
+        unsafe { libc::syscall(libc::SYS_getppid) }
+    }
+
+    fn call_filtered(filter: &BpfProgram, f: impl FnOnce() -> i64 + Send) -> i64 {
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                apply(filter).unwrap();
+                f()
+            })
+            .join()
+            .unwrap()
+        })
+    }
+
+    #[test]
+    fn filter_restricts_worker_thread_only() {
+        let default = SyscallFilter::new().compile().unwrap();
+        assert_eq!(call_filtered(&default, getppid), -1);
+        assert!(getppid() > 0);
+
+        let allowed = SyscallFilter::new()
+            .allow(libc::SYS_getppid)
+            .compile()
+            .unwrap();
+        assert!(call_filtered(&allowed, getppid) > 0);
+
+        let denied = SyscallFilter::new()
+            .allow(libc::SYS_getppid)
+            .deny(libc::SYS_getppid)
+            .compile()
+            .unwrap();
+        assert_eq!(call_filtered(&denied, getppid), -1);
+    }
+}
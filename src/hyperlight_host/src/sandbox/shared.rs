@@ -0,0 +1,666 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::MultiUseSandbox;
+use super::initialized_multi_use::owned_parameter_refs;
+use super::retry::RetryPolicy;
+use super::suspend::{IdleSuspend, SuspendedSandbox};
+use crate::HyperlightError::{CallQueueFull, SandboxBusy};
+use crate::func::{ParameterTuple, SupportedReturnType};
+use crate::{Result, new_error};
+
+/// The order in which callers waiting for a [`SharedSandbox`] are let in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Fairness {
+    /// Callers are let in in the order they started waiting
+    #[default]
+    Fifo,
+    /// Callers are let in in no particular order. This has slightly less
+    /// overhead than [`Fairness::Fifo`], but a caller may wait indefinitely
+    /// under constant contention.
+    Unordered,
+}
+
+/// How urgently a call into a [`SharedSandbox`] should be let in, relative
+/// to the other callers waiting for it.
+///
+/// Waiting callers with a higher priority are let in first, and callers
+/// with the same priority are let in according to the sandbox's
+/// [`Fairness`]. To stop a steady stream of urgent calls from starving
+/// the others, a caller that has waited for longer than the
+/// [starvation limit](SharedSandbox::set_starvation_limit) is let in ahead
+/// of every caller that has not, whatever its priority.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Batch work that can wait for more urgent calls
+    Low,
+    /// The priority of [`SharedSandbox::call()`]
+    #[default]
+    Normal,
+    /// Latency-sensitive calls that should jump ahead of the others
+    High,
+}
+
+/// A [`MultiUseSandbox`] that can be shared between threads, with a queue
+/// for the calls made into it.
+///
+/// A sandbox can only run one guest call at a time. A `SharedSandbox` is
+/// cheap to clone and may be called from any number of threads: calls
+/// wait in a queue until the sandbox is free. The queue holds at most
+/// `max_queued` waiting callers, and a call made while it is full fails
+/// immediately with
+/// [`HyperlightError::CallQueueFull`](crate::HyperlightError::CallQueueFull),
+/// so that an overloaded server sheds load instead of building up an
+/// unbounded backlog. [`try_call()`](Self::try_call) never waits, and fails
+/// with [`HyperlightError::SandboxBusy`](crate::HyperlightError::SandboxBusy)
+/// if the sandbox is in use.
+///
+/// Calls can be given a [`Priority`] with
+/// [`call_with_priority()`](Self::call_with_priority), so that
+/// latency-sensitive calls are let in ahead of batch calls.
+///
+/// A `SharedSandbox` can also suspend the sandbox when it has not been
+/// called for a while, see [`set_idle_suspend()`](Self::set_idle_suspend).
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::{Fairness, SharedSandbox};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?.evolve()?;
+/// let shared = SharedSandbox::new(sandbox, 16, Fairness::Fifo);
+///
+/// let handles: Vec<_> = (0..4)
+///     .map(|i| {
+///         let shared = shared.clone();
+///         std::thread::spawn(move || shared.call::<String>("Echo", format!("hello {i}")))
+///     })
+///     .collect();
+/// for handle in handles {
+///     println!("{}", handle.join().unwrap()?);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SharedSandbox {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    queue: CallQueue,
+    sandbox: Mutex<Slot>,
+    idle_suspend: Mutex<Option<IdleSuspend>>,
+}
+
+/// The longest the idle timer sleeps for, so that it notices when it is
+/// disabled or the sandbox is dropped
+const MAX_IDLE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a caller waits before it is let in ahead of callers with a
+/// higher priority, unless changed with
+/// [`SharedSandbox::set_starvation_limit()`]
+const DEFAULT_STARVATION_LIMIT: Duration = Duration::from_secs(1);
+
+/// The sandbox, which is either running or suspended
+struct Slot {
+    running: Option<MultiUseSandbox>,
+    suspended: Option<SuspendedSandbox>,
+    last_used: Instant,
+}
+
+impl SharedSandbox {
+    /// Share `sandbox` between threads, allowing at most `max_queued`
+    /// callers to wait for it at a time
+    pub fn new(sandbox: MultiUseSandbox, max_queued: usize, fairness: Fairness) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                queue: CallQueue::new(max_queued, fairness),
+                sandbox: Mutex::new(Slot {
+                    running: Some(sandbox),
+                    suspended: None,
+                    last_used: Instant::now(),
+                }),
+                idle_suspend: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Calls a guest function by name, waiting for any calls ahead of it to
+    /// finish first.
+    ///
+    /// See [`MultiUseSandbox::call()`].
+    pub fn call<Output: SupportedReturnType>(
+        &self,
+        func_name: &str,
+        args: impl ParameterTuple,
+    ) -> Result<Output> {
+        self.with_sandbox(|sbox| sbox.call(func_name, args))
+    }
+
+    /// Calls a guest function by name like [`call()`](Self::call), waiting
+    /// in the queue with the given `priority`.
+    ///
+    /// See [`MultiUseSandbox::call()`].
+    pub fn call_with_priority<Output: SupportedReturnType>(
+        &self,
+        func_name: &str,
+        args: impl ParameterTuple,
+        priority: Priority,
+    ) -> Result<Output> {
+        self.with_sandbox_priority(priority, |sbox| sbox.call(func_name, args))
+    }
+
+    /// Calls a guest function by name if the sandbox is free, and fails
+    /// with [`HyperlightError::SandboxBusy`](crate::HyperlightError::SandboxBusy)
+    /// otherwise.
+    ///
+    /// See [`MultiUseSandbox::call()`].
+    pub fn try_call<Output: SupportedReturnType>(
+        &self,
+        func_name: &str,
+        args: impl ParameterTuple,
+    ) -> Result<Output> {
+        let _turn = self.inner.queue.try_acquire()?;
+        self.inner.lock_sandbox()?.running()?.call(func_name, args)
+    }
+
+    /// Calls a guest function by name like [`call()`](Self::call), making
+    /// the call again if it fails in a way that `policy` retries. Like
+    /// [`call()`](Self::call), each attempt waits its turn in the queue.
+    ///
+    /// See [`MultiUseSandbox::call_with_retry()`].
+    pub fn call_with_retry<Output: SupportedReturnType>(
+        &self,
+        func_name: &str,
+        args: impl ParameterTuple,
+        policy: &RetryPolicy,
+    ) -> Result<Output> {
+        let args = owned_parameter_refs(args);
+        policy.run(|| self.with_sandbox(|sbox| sbox.call_impl(func_name, args.clone(), None)))
+    }
+
+    /// Runs `f` with exclusive access to the sandbox, waiting in the queue
+    /// like [`call()`](Self::call). This can be used for any operation on
+    /// the sandbox, such as taking a snapshot.
+    pub fn with_sandbox<R>(&self, f: impl FnOnce(&mut MultiUseSandbox) -> Result<R>) -> Result<R> {
+        self.with_sandbox_priority(Priority::Normal, f)
+    }
+
+    /// Runs `f` with exclusive access to the sandbox like
+    /// [`with_sandbox()`](Self::with_sandbox), waiting in the queue with
+    /// the given `priority`.
+    pub fn with_sandbox_priority<R>(
+        &self,
+        priority: Priority,
+        f: impl FnOnce(&mut MultiUseSandbox) -> Result<R>,
+    ) -> Result<R> {
+        let _turn = self.inner.queue.acquire(priority)?;
+        f(self.inner.lock_sandbox()?.running()?)
+    }
+
+    /// Returns the number of callers currently waiting for the sandbox
+    pub fn queued(&self) -> usize {
+        self.inner.queue.queued()
+    }
+
+    /// Set how long a caller may wait before it is let in ahead of callers
+    /// with a higher [`Priority`]. This defaults to one second.
+    ///
+    /// Waiting callers are checked against the limit whenever the sandbox
+    /// becomes free, so a caller that has waited for too long is let in
+    /// after at most one more call.
+    pub fn set_starvation_limit(&self, limit: Duration) {
+        self.inner.queue.lock().starvation_limit = limit;
+    }
+
+    /// Suspend the sandbox once it has not been called for a while, or
+    /// stop doing so if `idle_suspend` is `None`. See [`IdleSuspend`].
+    ///
+    /// A background thread checks when the sandbox was last used, and
+    /// suspends it when it is not in use. A suspended sandbox is resumed
+    /// by the next call into it, or by
+    /// [`with_sandbox()`](Self::with_sandbox).
+    pub fn set_idle_suspend(&self, idle_suspend: Option<IdleSuspend>) {
+        let mut current = self
+            .inner
+            .idle_suspend
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let start_timer = current.is_none() && idle_suspend.is_some();
+        *current = idle_suspend;
+        if start_timer {
+            let inner = Arc::downgrade(&self.inner);
+            thread::spawn(move || run_idle_timer(inner));
+        }
+    }
+
+    /// Returns whether the sandbox is currently suspended
+    pub fn is_suspended(&self) -> bool {
+        // A sandbox that is locked is in use, so cannot be suspended
+        match self.inner.sandbox.try_lock() {
+            Ok(slot) => slot.suspended.is_some(),
+            Err(_) => false,
+        }
+    }
+}
+
+impl Inner {
+    fn lock_sandbox(&self) -> Result<MutexGuard<'_, Slot>> {
+        // The lock is only contended if the queue is bypassed, and is only
+        // poisoned if a host function panicked during a call.
+        self.sandbox
+            .lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))
+    }
+
+    /// Suspend the sandbox if it is running and has been idle for long
+    /// enough. Returns how long to wait before checking again.
+    fn suspend_if_idle(&self, idle_suspend: &IdleSuspend) -> Result<Duration> {
+        // Only suspend the sandbox if nobody is using or waiting for it
+        let Ok(_turn) = self.queue.try_acquire() else {
+            return Ok(idle_suspend.idle_timeout);
+        };
+        let mut slot = self.lock_sandbox()?;
+        let idle = slot.last_used.elapsed();
+        if idle < idle_suspend.idle_timeout {
+            return Ok(idle_suspend.idle_timeout - idle);
+        }
+        slot.suspend(idle_suspend.swap_dir.as_deref())?;
+        Ok(idle_suspend.idle_timeout)
+    }
+}
+
+/// Suspend the sandbox whenever it becomes idle, until it is dropped or
+/// idle suspension is disabled
+fn run_idle_timer(inner: Weak<Inner>) {
+    let mut wait = Duration::ZERO;
+    loop {
+        thread::sleep(wait.min(MAX_IDLE_POLL_INTERVAL));
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        let idle_suspend = inner
+            .idle_suspend
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        let Some(idle_suspend) = idle_suspend else {
+            return;
+        };
+        wait = match inner.suspend_if_idle(&idle_suspend) {
+            Ok(wait) => wait,
+            Err(e) => {
+                tracing::warn!("failed to suspend idle sandbox: {e}");
+                idle_suspend.idle_timeout
+            }
+        };
+    }
+}
+
+impl Slot {
+    /// Returns the running sandbox, resuming it first if it is suspended
+    fn running(&mut self) -> Result<&mut MultiUseSandbox> {
+        if let Some(suspended) = &mut self.suspended {
+            self.running = Some(MultiUseSandbox::resume(suspended)?);
+            self.suspended = None;
+        }
+        self.last_used = Instant::now();
+        self.running
+            .as_mut()
+            .ok_or_else(|| new_error!("the sandbox is neither running nor suspended"))
+    }
+
+    /// Suspend the sandbox if it is running and not poisoned
+    fn suspend(&mut self, swap_dir: Option<&Path>) -> Result<()> {
+        let Some(sandbox) = &mut self.running else {
+            return Ok(());
+        };
+        if sandbox.poisoned() {
+            return Ok(());
+        }
+        let snapshot = sandbox.snapshot()?;
+        if let Some(sandbox) = self.running.take() {
+            let mut suspended = sandbox.into_suspended(snapshot);
+            if let Some(dir) = swap_dir
+                && let Err(e) = suspended.swap_out(dir)
+            {
+                // The sandbox is still suspended, just without saving memory
+                tracing::warn!("failed to swap out suspended sandbox: {e}");
+            }
+            self.suspended = Some(suspended);
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for SharedSandbox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedSandbox")
+            .field("queued", &self.queued())
+            .finish()
+    }
+}
+
+/// A bounded queue of callers waiting for their turn to use a sandbox
+struct CallQueue {
+    state: Mutex<QueueState>,
+    turn_available: Condvar,
+    max_queued: usize,
+    fairness: Fairness,
+}
+
+struct QueueState {
+    /// Whether a caller currently has its turn
+    in_use: bool,
+    /// The callers waiting for their turn, in the order they started
+    /// waiting
+    waiting: Vec<Waiter>,
+    /// The ticket that will be given to the next waiting caller
+    next_ticket: u64,
+    /// How long a caller waits before it is let in ahead of callers with a
+    /// higher priority
+    starvation_limit: Duration,
+}
+
+/// A caller waiting for its turn
+struct Waiter {
+    ticket: u64,
+    priority: Priority,
+    since: Instant,
+    /// Whether the caller had waited for longer than the starvation limit
+    /// when the sandbox last became free
+    starving: bool,
+}
+
+/// The order in which waiting callers are let in, lowest first
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Rank {
+    Waiting(Priority),
+    /// Starving callers are let in first, whatever their priority
+    Starving,
+}
+
+impl Waiter {
+    fn rank(&self) -> Rank {
+        if self.starving {
+            Rank::Starving
+        } else {
+            Rank::Waiting(self.priority)
+        }
+    }
+}
+
+/// A caller's turn to use the sandbox, which ends when this is dropped
+struct Turn<'a> {
+    queue: &'a CallQueue,
+}
+
+impl CallQueue {
+    fn new(max_queued: usize, fairness: Fairness) -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                in_use: false,
+                waiting: Vec::new(),
+                next_ticket: 0,
+                starvation_limit: DEFAULT_STARVATION_LIMIT,
+            }),
+            turn_available: Condvar::new(),
+            max_queued,
+            fairness,
+        }
+    }
+
+    /// Wait for a turn to use the sandbox
+    fn acquire(&self, priority: Priority) -> Result<Turn<'_>> {
+        let mut state = self.lock();
+        if !state.in_use && state.waiting.is_empty() {
+            return Ok(self.take_turn(&mut state));
+        }
+        if state.waiting.len() >= self.max_queued {
+            return Err(CallQueueFull(state.waiting.len()));
+        }
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.waiting.push(Waiter {
+            ticket,
+            priority,
+            since: Instant::now(),
+            starving: false,
+        });
+        let mut state = self
+            .turn_available
+            .wait_while(state, |state| {
+                state.in_use || !state.is_next(ticket, self.fairness)
+            })
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.waiting.retain(|waiter| waiter.ticket != ticket);
+        Ok(self.take_turn(&mut state))
+    }
+
+    /// Take a turn to use the sandbox if nobody else is using or waiting
+    /// for it
+    fn try_acquire(&self) -> Result<Turn<'_>> {
+        let mut state = self.lock();
+        if state.in_use || !state.waiting.is_empty() {
+            return Err(SandboxBusy);
+        }
+        Ok(self.take_turn(&mut state))
+    }
+
+    fn take_turn(&self, state: &mut QueueState) -> Turn<'_> {
+        state.in_use = true;
+        Turn { queue: self }
+    }
+
+    fn queued(&self) -> usize {
+        self.lock().waiting.len()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        // The state is always left consistent, so a panic while holding the
+        // lock does not need to be propagated.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl QueueState {
+    /// Returns whether the caller holding `ticket` may take the next turn.
+    ///
+    /// Only waiters with the highest rank may, and with [`Fairness::Fifo`]
+    /// only the one among them that has waited the longest. This only
+    /// changes when a caller starts waiting or the sandbox becomes free,
+    /// so that every waiter agrees on who is next.
+    fn is_next(&self, ticket: u64, fairness: Fairness) -> bool {
+        let Some(best) = self.waiting.iter().map(Waiter::rank).max() else {
+            return false;
+        };
+        let mut candidates = self.waiting.iter().filter(|waiter| waiter.rank() == best);
+        match fairness {
+            Fairness::Fifo => candidates
+                .next()
+                .is_some_and(|waiter| waiter.ticket == ticket),
+            Fairness::Unordered => candidates.any(|waiter| waiter.ticket == ticket),
+        }
+    }
+}
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        let mut state = self.queue.lock();
+        state.in_use = false;
+        let limit = state.starvation_limit;
+        for waiter in &mut state.waiting {
+            waiter.starving = waiter.since.elapsed() >= limit;
+        }
+        drop(state);
+        self.queue.turn_available.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use hyperlight_testing::simple_guest_as_string;
+
+    use super::{CallQueue, Fairness, Priority, SharedSandbox};
+    use crate::sandbox::IdleSuspend;
+    use crate::{GuestBinary, HyperlightError, UninitializedSandbox};
+
+    fn wait_for_queued(queue: &CallQueue, n: usize) {
+        while queue.queued() < n {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn rejects_calls_when_busy_or_full() {
+        let queue = CallQueue::new(1, Fairness::Fifo);
+        let turn = queue.acquire(Priority::Normal).unwrap();
+        assert!(matches!(
+            queue.try_acquire(),
+            Err(HyperlightError::SandboxBusy)
+        ));
+
+        std::thread::scope(|s| {
+            let waiter = s.spawn(|| queue.acquire(Priority::Normal).map(drop));
+            wait_for_queued(&queue, 1);
+            assert!(matches!(
+                queue.acquire(Priority::Normal),
+                Err(HyperlightError::CallQueueFull(1))
+            ));
+            drop(turn);
+            waiter.join().unwrap().unwrap();
+        });
+
+        queue.try_acquire().unwrap();
+    }
+
+    #[test]
+    fn fifo_queue_serves_callers_in_order() {
+        let queue = CallQueue::new(8, Fairness::Fifo);
+        let order = Mutex::new(Vec::new());
+        let turn = queue.acquire(Priority::Normal).unwrap();
+
+        std::thread::scope(|s| {
+            for i in 0..4 {
+                let (queue, order) = (&queue, &order);
+                s.spawn(move || {
+                    let _turn = queue.acquire(Priority::Normal).unwrap();
+                    order.lock().unwrap().push(i);
+                });
+                wait_for_queued(queue, i + 1);
+            }
+            drop(turn);
+        });
+
+        assert_eq!(*order.lock().unwrap(), vec![0, 1, 2, 3]);
+    }
+
+    fn serve_in_order(queue: &CallQueue, priorities: &[Priority]) -> Vec<usize> {
+        let order = Mutex::new(Vec::new());
+        let turn = queue.acquire(Priority::Normal).unwrap();
+
+        std::thread::scope(|s| {
+            for (i, &priority) in priorities.iter().enumerate() {
+                let order = &order;
+                s.spawn(move || {
+                    let _turn = queue.acquire(priority).unwrap();
+                    order.lock().unwrap().push(i);
+                });
+                wait_for_queued(queue, i + 1);
+            }
+            drop(turn);
+        });
+
+        order.into_inner().unwrap()
+    }
+
+    #[test]
+    fn higher_priority_callers_are_served_first() {
+        let priorities = [
+            Priority::Low,
+            Priority::Normal,
+            Priority::High,
+            Priority::Normal,
+        ];
+        for fairness in [Fairness::Fifo, Fairness::Unordered] {
+            let queue = CallQueue::new(8, fairness);
+            let order = serve_in_order(&queue, &priorities);
+            assert_eq!(order[0], 2);
+            assert_eq!(order[3], 0);
+        }
+
+        let queue = CallQueue::new(8, Fairness::Fifo);
+        assert_eq!(serve_in_order(&queue, &priorities), vec![2, 1, 3, 0]);
+    }
+
+    #[test]
+    fn starving_callers_are_served_in_order() {
+        let queue = CallQueue::new(8, Fairness::Fifo);
+        queue.lock().starvation_limit = Duration::ZERO;
+        let order = serve_in_order(&queue, &[Priority::Low, Priority::High, Priority::Normal]);
+        assert_eq!(order, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn idle_sandbox_is_suspended_and_resumed() {
+        let path = simple_guest_as_string().unwrap();
+        let sbox = UninitializedSandbox::new(GuestBinary::FilePath(path), None)
+            .unwrap()
+            .evolve()
+            .unwrap();
+        let shared = SharedSandbox::new(sbox, 1, Fairness::Fifo);
+        let swap_dir = tempfile::tempdir().unwrap();
+        shared
+            .with_sandbox(|sbox| sbox.start_profiling(Duration::from_millis(1)))
+            .unwrap();
+        shared.set_idle_suspend(Some(
+            IdleSuspend::new(Duration::from_millis(10)).swap_to(swap_dir.path()),
+        ));
+
+        for expected in [5, 10] {
+            assert_eq!(shared.call::<i32>("AddToStatic", 5i32).unwrap(), expected);
+            while !shared.is_suspended() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            assert_eq!(std::fs::read_dir(swap_dir.path()).unwrap().count(), 1);
+        }
+
+        // The state of the guest survives being suspended
+        shared.set_idle_suspend(None);
+        assert_eq!(shared.call::<i32>("GetStatic", ()).unwrap(), 10);
+        assert!(!shared.is_suspended());
+        assert_eq!(std::fs::read_dir(swap_dir.path()).unwrap().count(), 0);
+
+        // So does the profile being taken
+        let profile = shared.with_sandbox(|sbox| sbox.stop_profiling()).unwrap();
+        assert_eq!(profile.interval(), Duration::from_millis(1));
+    }
+}
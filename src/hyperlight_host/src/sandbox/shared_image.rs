@@ -0,0 +1,164 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::Arc;
+
+use tracing::{Span, instrument};
+
+use super::SandboxConfiguration;
+use super::snapshot::Snapshot;
+use super::uninitialized::{GuestBinary, GuestEnvironment};
+use crate::Result;
+
+/// A guest image that has been loaded once and can be used to create many
+/// sandboxes.
+///
+/// Loading a guest parses and relocates the binary and lays out its initial
+/// memory. A `SharedGuestImage` does this work once; every sandbox created
+/// from it with
+/// [`UninitializedSandbox::from_shared_image()`](crate::UninitializedSandbox::from_shared_image)
+/// maps the same host memory for the guest code and initial data, read-only,
+/// instead of holding its own copy. Each sandbox still gets its own private
+/// scratch region for the stack, heap and any pages the guest writes to, so
+/// sandboxes created from the same image are fully isolated from each other.
+///
+/// The image is immutable and cheap to clone, and may be shared between
+/// threads.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::SharedGuestImage;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let image = SharedGuestImage::new(GuestBinary::FilePath("guest.bin".into()), None)?;
+///
+/// let sandboxes = (0..100)
+///     .map(|_| UninitializedSandbox::from_shared_image(&image)?.evolve())
+///     .collect::<Result<Vec<_>, _>>()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SharedGuestImage {
+    pub(super) snapshot: Arc<Snapshot>,
+    pub(super) config: SandboxConfiguration,
+    /// Whether the guest binary was checked against a
+    /// [`GuestSignature`](super::GuestSignature) when it was loaded
+    pub(super) verified: bool,
+    #[cfg(crashdump)]
+    pub(super) binary_path: Option<String>,
+}
+
+impl SharedGuestImage {
+    /// Load a guest image for the given guest environment.
+    ///
+    /// The memory layout of the image is determined by `cfg`, so all
+    /// sandboxes created from the image share this configuration. If the
+    /// environment carries a [`GuestSignature`](super::GuestSignature), the
+    /// guest binary is verified against it before being loaded.
+    #[instrument(skip(env), parent = Span::current())]
+    pub fn new<'a, 'b>(
+        env: impl Into<GuestEnvironment<'a, 'b>>,
+        cfg: Option<SandboxConfiguration>,
+    ) -> Result<Self> {
+        let config = cfg.unwrap_or_default();
+        let env = env.into();
+        #[cfg(crashdump)]
+        let binary_path = match &env.guest_binary {
+            GuestBinary::FilePath(path) => Some(path.clone()),
+            GuestBinary::Buffer(_) | GuestBinary::RawBlob { .. } => None,
+        };
+        let verified = env.signature.is_some();
+        let snapshot = match &env.signature {
+            Some(signature) => {
+                // Read the binary once and load from the verified bytes, so
+                // the file cannot change between verification and loading.
+                let contents = env.guest_binary.contents()?;
+                signature.verify(&contents)?;
+                let env = GuestEnvironment {
+                    guest_binary: match env.guest_binary {
+                        GuestBinary::RawBlob { entry_offset, .. } => GuestBinary::RawBlob {
+                            data: &contents,
+                            entry_offset,
+                        },
+                        _ => GuestBinary::Buffer(&contents),
+                    },
+                    init_data: env.init_data,
+                    startup_config: env.startup_config,
+                    init_entry_point: env.init_entry_point,
+                    args: env.args,
+                    signature: None,
+                };
+                Snapshot::from_env(env, config)?
+            }
+            None => Snapshot::from_env(env, config)?,
+        };
+        Ok(Self {
+            snapshot: Arc::new(snapshot),
+            config,
+            verified,
+            #[cfg(crashdump)]
+            binary_path,
+        })
+    }
+
+    /// The configuration sandboxes created from this image will use
+    pub fn config(&self) -> &SandboxConfiguration {
+        &self.config
+    }
+}
+
+impl std::fmt::Debug for SharedGuestImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedGuestImage")
+            .field("config", &self.config)
+            .field("verified", &self.verified)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperlight_testing::simple_guest_as_string;
+
+    use super::SharedGuestImage;
+    #[cfg(not(unshared_snapshot_mem))]
+    use crate::mem::shared_mem::SharedMemory;
+    use crate::{GuestBinary, UninitializedSandbox};
+
+    #[test]
+    fn sandboxes_share_image_memory() {
+        let path = simple_guest_as_string().unwrap();
+        let image = SharedGuestImage::new(GuestBinary::FilePath(path), None).unwrap();
+
+        let u_sbox1 = UninitializedSandbox::from_shared_image(&image).unwrap();
+        let u_sbox2 = UninitializedSandbox::from_shared_image(&image).unwrap();
+        #[cfg(not(unshared_snapshot_mem))]
+        assert_eq!(
+            u_sbox1.mgr.shared_mem.base_addr(),
+            u_sbox2.mgr.shared_mem.base_addr()
+        );
+
+        // Each sandbox has its own private writable state
+        let mut sbox1 = u_sbox1.evolve().unwrap();
+        let mut sbox2 = u_sbox2.evolve().unwrap();
+        let _ = sbox1.call::<i32>("AddToStatic", 5i32).unwrap();
+        assert_eq!(sbox1.call::<i32>("GetStatic", ()).unwrap(), 5);
+        assert_eq!(sbox2.call::<i32>("GetStatic", ()).unwrap(), 0);
+        assert_eq!(sbox1.measurement(), sbox2.measurement());
+    }
+}
@@ -0,0 +1,183 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use ed25519_dalek::{Signature, VerifyingKey};
+
+use crate::HyperlightError::GuestBinaryVerificationFailed;
+use crate::Result;
+
+/// A detached Ed25519 signature over a guest binary, together with the
+/// public key it should be verified against.
+///
+/// The signature must cover the exact bytes of the guest binary file (or
+/// buffer). Attach it to a guest with
+/// [`GuestEnvironment::with_signature()`](super::uninitialized::GuestEnvironment::with_signature)
+/// and it will be checked by [`UninitializedSandbox::new()`](crate::UninitializedSandbox::new)
+/// before the binary is loaded.
+#[derive(Clone, Debug)]
+pub struct GuestSignature {
+    signature: Signature,
+    public_key: VerifyingKey,
+}
+
+impl GuestSignature {
+    /// The length in bytes of an Ed25519 signature
+    pub const SIGNATURE_LENGTH: usize = ed25519_dalek::SIGNATURE_LENGTH;
+    /// The length in bytes of an Ed25519 public key
+    pub const PUBLIC_KEY_LENGTH: usize = ed25519_dalek::PUBLIC_KEY_LENGTH;
+
+    /// Create a new `GuestSignature` from a raw 64-byte Ed25519 signature and
+    /// a raw 32-byte Ed25519 public key.
+    pub fn new(signature: &[u8], public_key: &[u8]) -> Result<Self> {
+        let signature = Signature::from_slice(signature).map_err(|_| {
+            GuestBinaryVerificationFailed(format!(
+                "signature must be {} bytes, got {}",
+                Self::SIGNATURE_LENGTH,
+                signature.len()
+            ))
+        })?;
+        let public_key: &[u8; ed25519_dalek::PUBLIC_KEY_LENGTH] =
+            public_key.try_into().map_err(|_| {
+                GuestBinaryVerificationFailed(format!(
+                    "public key must be {} bytes, got {}",
+                    Self::PUBLIC_KEY_LENGTH,
+                    public_key.len()
+                ))
+            })?;
+        let public_key = VerifyingKey::from_bytes(public_key)
+            .map_err(|e| GuestBinaryVerificationFailed(format!("invalid public key: {e}")))?;
+        Ok(Self {
+            signature,
+            public_key,
+        })
+    }
+
+    /// Verify that this signature is a valid signature over `data`.
+    ///
+    /// Uses strict verification, which rejects weak public keys and
+    /// malleable signatures.
+    pub(crate) fn verify(&self, data: &[u8]) -> Result<()> {
+        self.public_key
+            .verify_strict(data, &self.signature)
+            .map_err(|_| {
+                GuestBinaryVerificationFailed(
+                    "signature does not match the guest binary".to_string(),
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Signer, SigningKey};
+
+    use super::GuestSignature;
+    use crate::HyperlightError;
+    use crate::sandbox::uninitialized::{GuestBinary, GuestEnvironment};
+    use crate::sandbox::{SandboxConfiguration, UninitializedSandbox};
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn sign(key: &SigningKey, data: &[u8]) -> GuestSignature {
+        GuestSignature::new(&key.sign(data).to_bytes(), key.verifying_key().as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn verify_buffer() {
+        let data = b"not really a guest binary";
+        let key = signing_key(1);
+        let signature = key.sign(data).to_bytes();
+        let public_key = key.verifying_key().to_bytes();
+
+        let bin = GuestBinary::Buffer(data);
+        bin.verify(&signature, &public_key).unwrap();
+
+        // wrong key
+        let other_key = signing_key(2).verifying_key().to_bytes();
+        assert!(matches!(
+            bin.verify(&signature, &other_key),
+            Err(HyperlightError::GuestBinaryVerificationFailed(_))
+        ));
+
+        // tampered binary
+        let bin = GuestBinary::Buffer(b"not really a guest binarY");
+        assert!(matches!(
+            bin.verify(&signature, &public_key),
+            Err(HyperlightError::GuestBinaryVerificationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_signature_and_key() {
+        let key = signing_key(1);
+        let public_key = key.verifying_key().to_bytes();
+        let signature = key.sign(b"data").to_bytes();
+
+        assert!(GuestSignature::new(&signature[..63], &public_key).is_err());
+        assert!(GuestSignature::new(&signature, &public_key[..31]).is_err());
+    }
+
+    #[test]
+    fn verify_file() {
+        let data = b"not really a guest binary either";
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), data).unwrap();
+
+        let key = signing_key(3);
+        let bin = GuestBinary::FilePath(file.path().to_str().unwrap().to_string());
+        bin.verify(&key.sign(data).to_bytes(), key.verifying_key().as_bytes())
+            .unwrap();
+    }
+
+    #[test]
+    fn sandbox_creation_rejects_bad_signature() {
+        // Verification happens before the binary is parsed, so the contents
+        // do not need to be a valid guest.
+        let data = b"not really a guest binary";
+        let signature = sign(&signing_key(1), b"something else");
+
+        let env = GuestEnvironment::new(GuestBinary::Buffer(data), None).with_signature(signature);
+        let res = UninitializedSandbox::new(env, None);
+        assert!(matches!(
+            res,
+            Err(HyperlightError::GuestBinaryVerificationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn evolve_requires_signed_guest() {
+        let path = hyperlight_testing::simple_guest_as_string().unwrap();
+        let data = std::fs::read(&path).unwrap();
+        let mut cfg = SandboxConfiguration::default();
+        cfg.set_require_signed_guests(true);
+
+        // unsigned guests are refused
+        let u_sbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(path.clone()), Some(cfg)).unwrap();
+        assert!(matches!(
+            u_sbox.evolve(),
+            Err(HyperlightError::GuestBinaryVerificationFailed(_))
+        ));
+
+        // signed guests are accepted
+        let env = GuestEnvironment::new(GuestBinary::FilePath(path), None)
+            .with_signature(sign(&signing_key(1), &data));
+        let u_sbox = UninitializedSandbox::new(env, Some(cfg)).unwrap();
+        u_sbox.evolve().unwrap();
+    }
+}
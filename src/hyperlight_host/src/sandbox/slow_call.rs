@@ -0,0 +1,297 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::GuestSymbolizer;
+use super::backtrace::GuestFrame;
+use super::labels::SandboxLabels;
+use super::profiler::{GuestProfile, ProfileSlot};
+use crate::hypervisor::InterruptHandleImpl;
+use crate::metrics::{METRIC_SLOW_GUEST_CALLS, call_metric_labels};
+
+/// When a guest call is slow enough to be reported, before it is slow
+/// enough to time out.
+///
+/// Once a call set with
+/// [`MultiUseSandbox::set_slow_call_policy()`](crate::MultiUseSandbox::set_slow_call_policy)
+/// has run for longer than its threshold, a warning is logged and the
+/// `guest_slow_calls_total` counter is incremented, while the call is
+/// still running. The call is not interrupted, and when it returns, a
+/// second warning gives how long it took. With
+/// [`with_stack_sample()`](Self::with_stack_sample), the guest's stack is
+/// also sampled as the threshold passes and included in that warning,
+/// unless the sandbox is being profiled.
+///
+/// Each call is watched from its own thread, so a policy adds the cost of
+/// starting a thread to every guest call.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use hyperlight_host::{GuestBinary, MultiUseSandbox, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::SlowCallPolicy;
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?.evolve()?;
+/// sandbox.set_slow_call_policy(Some(
+///     SlowCallPolicy::new(Duration::from_millis(50))
+///         .with_function_threshold("Render", Duration::from_millis(500))
+///         .with_stack_sample(true),
+/// ));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SlowCallPolicy {
+    threshold: Duration,
+    function_thresholds: HashMap<String, Duration>,
+    sample_stack: bool,
+}
+
+impl SlowCallPolicy {
+    /// Report guest calls that run for longer than `threshold`
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            function_thresholds: HashMap::new(),
+            sample_stack: false,
+        }
+    }
+
+    /// Report calls to the guest function `function_name` once they run
+    /// for longer than `threshold`, instead of the policy's threshold
+    pub fn with_function_threshold(
+        mut self,
+        function_name: impl Into<String>,
+        threshold: Duration,
+    ) -> Self {
+        self.function_thresholds
+            .insert(function_name.into(), threshold);
+        self
+    }
+
+    /// Whether to sample the guest's stack when a call becomes slow
+    pub fn with_stack_sample(mut self, sample_stack: bool) -> Self {
+        self.sample_stack = sample_stack;
+        self
+    }
+
+    /// How long a call to `function_name` runs before it is reported.
+    /// Batches of calls, whose function is `None`, use the policy's
+    /// threshold.
+    pub fn threshold(&self, function_name: Option<&str>) -> Duration {
+        function_name
+            .and_then(|name| self.function_thresholds.get(name))
+            .copied()
+            .unwrap_or(self.threshold)
+    }
+
+    /// Whether the guest's stack is sampled when a call becomes slow
+    pub fn samples_stack(&self) -> bool {
+        self.sample_stack
+    }
+}
+
+/// What a [`SlowCallWatchdog`] shares with its timer thread
+#[derive(Debug, Default)]
+struct WatchdogState {
+    /// Set when the call ends, to stop the timer
+    done: AtomicBool,
+    /// Set by the timer once the call has passed its threshold
+    slow: AtomicBool,
+    /// Set by the timer if it put a profile in the slot for a sample
+    sampling: AtomicBool,
+}
+
+/// Watches a single guest call, from a timer thread that reports it if it
+/// passes the threshold of a [`SlowCallPolicy`]
+#[derive(Debug)]
+pub(crate) struct SlowCallWatchdog {
+    interrupt_handle: Arc<dyn InterruptHandleImpl>,
+    slot: ProfileSlot,
+    state: Arc<WatchdogState>,
+    timer: Option<JoinHandle<()>>,
+    function_name: Option<String>,
+    threshold: Duration,
+}
+
+impl SlowCallWatchdog {
+    /// Start watching a call to `function_name` that started at `started`.
+    /// A stack sample is taken into a profile put in `slot`, which the
+    /// vcpu thread finds there. Returns `None` if the timer thread can't
+    /// be started, as the call can still be made without it.
+    pub(crate) fn start(
+        policy: &SlowCallPolicy,
+        function_name: Option<&str>,
+        labels: &SandboxLabels,
+        started: Instant,
+        interrupt_handle: Arc<dyn InterruptHandleImpl>,
+        slot: ProfileSlot,
+        symbolizer: Arc<GuestSymbolizer>,
+    ) -> Option<Self> {
+        let threshold = policy.threshold(function_name);
+        let state = Arc::new(WatchdogState::default());
+        let timer = {
+            let interrupt_handle = interrupt_handle.clone();
+            let slot = slot.clone();
+            let state = state.clone();
+            let function_name = function_name.map(str::to_string);
+            let labels = labels.clone();
+            let sample_stack = policy.sample_stack;
+            std::thread::Builder::new()
+                .name("hyperlight-slow-call".to_string())
+                .spawn(move || {
+                    let deadline = started + threshold;
+                    loop {
+                        if state.done.load(Ordering::Acquire) {
+                            return;
+                        }
+                        let now = Instant::now();
+                        if now >= deadline {
+                            break;
+                        }
+                        std::thread::park_timeout(deadline - now);
+                    }
+                    state.slow.store(true, Ordering::Release);
+                    tracing::warn!(
+                        function = function_name.as_deref().unwrap_or("<batch>"),
+                        threshold = ?threshold,
+                        labels = %labels,
+                        "Guest call is slow"
+                    );
+                    metrics::counter!(
+                        METRIC_SLOW_GUEST_CALLS,
+                        call_metric_labels(&labels, function_name.as_deref())
+                    )
+                    .increment(1);
+
+                    if !sample_stack {
+                        return;
+                    }
+                    // A running profiler already takes the samples
+                    match slot.lock() {
+                        Ok(mut profile) if profile.is_none() => {
+                            *profile = Some(GuestProfile::new(threshold, symbolizer));
+                            state.sampling.store(true, Ordering::Release);
+                        }
+                        _ => return,
+                    }
+                    // Only the guest's own execution is sampled, so a
+                    // request made while it is not running is dropped
+                    if !interrupt_handle.request_sample() {
+                        interrupt_handle.clear_sample_request();
+                    }
+                })
+        };
+        let timer = match timer {
+            Ok(timer) => timer,
+            Err(e) => {
+                tracing::error!("Failed to start the slow call watchdog: {}", e);
+                return None;
+            }
+        };
+
+        Some(Self {
+            interrupt_handle,
+            slot,
+            state,
+            timer: Some(timer),
+            function_name: function_name.map(str::to_string),
+            threshold,
+        })
+    }
+
+    /// Stop watching the call, which took `duration`, and report it if it
+    /// was slow, with the stack sampled when it became slow, if any
+    pub(crate) fn finish(mut self, duration: Duration) {
+        self.halt();
+        if !self.state.slow.load(Ordering::Acquire) {
+            return;
+        }
+        let stack = self
+            .take_sample()
+            .map(|frames| {
+                frames
+                    .iter()
+                    .map(|frame| format!("\n  {frame}"))
+                    .collect::<String>()
+            })
+            .unwrap_or_default();
+        tracing::warn!(
+            function = self.function_name.as_deref().unwrap_or("<batch>"),
+            threshold = ?self.threshold,
+            duration = ?duration,
+            "Slow guest call returned{}",
+            stack
+        );
+    }
+
+    /// Take the profile the timer put in the slot, if it did, and return
+    /// the frames of the stack sampled into it
+    fn take_sample(&self) -> Option<Vec<GuestFrame>> {
+        if !self.state.sampling.swap(false, Ordering::AcqRel) {
+            return None;
+        }
+        let profile = self.slot.lock().ok()?.take()?;
+        let (stack, _) = profile.stacks().next()?;
+        Some(profile.frames(stack))
+    }
+
+    /// Stop the timer thread and drop any sample it requested
+    fn halt(&mut self) {
+        self.state.done.store(true, Ordering::Release);
+        if let Some(timer) = self.timer.take() {
+            timer.thread().unpark();
+            let _ = timer.join();
+        }
+        if self.state.sampling.load(Ordering::Acquire) {
+            self.interrupt_handle.clear_sample_request();
+        }
+    }
+}
+
+impl Drop for SlowCallWatchdog {
+    fn drop(&mut self) {
+        self.halt();
+        let _ = self.take_sample();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::SlowCallPolicy;
+
+    #[test]
+    fn functions_can_have_their_own_threshold() {
+        let policy = SlowCallPolicy::new(Duration::from_millis(50))
+            .with_function_threshold("Render", Duration::from_secs(1));
+        assert_eq!(policy.threshold(Some("Render")), Duration::from_secs(1));
+        assert_eq!(policy.threshold(Some("Echo")), Duration::from_millis(50));
+        assert_eq!(policy.threshold(None), Duration::from_millis(50));
+        assert!(!policy.samples_stack());
+        assert!(policy.with_stack_sample(true).samples_stack());
+    }
+}
@@ -15,6 +15,8 @@ limitations under the License.
 */
 
 use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 
 use hyperlight_common::layout::{scratch_base_gpa, scratch_base_gva};
@@ -31,7 +33,7 @@ use crate::mem::exe::{ExeInfo, LoadInfo};
 use crate::mem::layout::SandboxMemoryLayout;
 use crate::mem::memory_region::{GuestMemoryRegion, MemoryRegion, MemoryRegionFlags};
 use crate::mem::mgr::{GuestPageTableBuffer, SnapshotSharedMemory};
-use crate::mem::shared_mem::{ReadonlySharedMemory, SharedMemory};
+use crate::mem::shared_mem::{ReadonlySharedMemory, SharedMemory, SwappedSharedMemory};
 use crate::sandbox::SandboxConfiguration;
 use crate::sandbox::uninitialized::{GuestBinary, GuestEnvironment};
 
@@ -324,10 +326,17 @@ impl Snapshot {
         let mut bin = env.guest_binary;
         bin.canonicalize()?;
         let blob = env.init_data;
+        let startup_config = env.startup_config.unwrap_or_default();
+        if env.args.iter().any(|arg| arg.contains('\0')) {
+            return Err(crate::HyperlightError::InvalidConfiguration(
+                "guest arguments may not contain NUL characters".to_string(),
+            ));
+        }
 
         let exe_info = match bin {
             GuestBinary::FilePath(bin_path_str) => ExeInfo::from_file(&bin_path_str)?,
             GuestBinary::Buffer(buffer) => ExeInfo::from_buf(buffer)?,
+            GuestBinary::RawBlob { data, entry_offset } => ExeInfo::from_raw(data, entry_offset)?,
         };
 
         // Check guest/host version compatibility.
@@ -350,11 +359,24 @@ impl Snapshot {
             exe_info.loaded_size(),
             guest_blob_size,
             guest_blob_mem_flags,
+            startup_config.len(),
+            crate::mem::layout::SandboxMemoryLayout::args_size(&env.args),
         )?;
 
         let load_addr = layout.get_guest_code_address() as u64;
         let base_va = exe_info.base_va();
         let entrypoint_va: u64 = exe_info.entrypoint().into();
+        let init_entry_va = match env.init_entry_point {
+            Some(name) => match exe_info.function_symbol(name) {
+                Some(va) => Some(u64::from(va)),
+                None => {
+                    return Err(crate::HyperlightError::InvalidConfiguration(format!(
+                        "guest binary has no init entry point named {name:?}"
+                    )));
+                }
+            },
+            None => None,
+        };
 
         let mut memory = vec![0; layout.get_memory_size()?];
 
@@ -364,6 +386,11 @@ impl Snapshot {
         )?;
 
         layout.write_peb(&mut memory)?;
+        layout.write_startup_config(&mut memory, startup_config)?;
+        layout.write_args(&mut memory, &env.args)?;
+        if let Some(va) = init_entry_va {
+            layout.write_init_entry(&mut memory, load_addr + va - base_va)?;
+        }
 
         blob.map(|x| layout.write_init_data(&mut memory, x.data))
             .transpose()?;
@@ -665,6 +692,90 @@ impl Snapshot {
     }
 }
 
+/// A snapshot whose memory has been written out to a file, to save host
+/// memory while the sandbox it belongs to is suspended. The file is
+/// removed when this is dropped.
+pub(crate) struct SwappedSnapshot {
+    sandbox_id: u64,
+    layout: SandboxMemoryLayout,
+    memory: SwappedSharedMemory,
+    regions: Vec<MemoryRegion>,
+    load_info: LoadInfo,
+    hash: [u8; 32],
+    stack_top_gva: u64,
+    sregs: Option<CommonSpecialRegisters>,
+    entrypoint: NextAction,
+    snapshot_generation: u64,
+    file: File,
+    path: PathBuf,
+}
+
+static SWAP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl Snapshot {
+    /// Write the memory of the snapshot to a new file in `dir`
+    pub(crate) fn swap_out(&self, dir: &Path) -> Result<SwappedSnapshot> {
+        let path = dir.join(format!(
+            "hyperlight-{}-{}-{}.snapshot",
+            std::process::id(),
+            self.sandbox_id,
+            SWAP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        let memory = match self.memory.swap_out(&mut file) {
+            Ok(memory) => memory,
+            Err(e) => {
+                let _ = std::fs::remove_file(&path);
+                return Err(e);
+            }
+        };
+        Ok(SwappedSnapshot {
+            sandbox_id: self.sandbox_id,
+            layout: self.layout,
+            memory,
+            regions: self.regions.clone(),
+            load_info: self.load_info.clone(),
+            hash: self.hash,
+            stack_top_gva: self.stack_top_gva,
+            sregs: self.sregs,
+            entrypoint: self.entrypoint,
+            snapshot_generation: self.snapshot_generation,
+            file,
+            path,
+        })
+    }
+}
+
+impl SwappedSnapshot {
+    /// Read the memory of the snapshot back from its file
+    pub(crate) fn swap_in(&mut self) -> Result<Snapshot> {
+        Ok(Snapshot {
+            sandbox_id: self.sandbox_id,
+            layout: self.layout,
+            memory: ReadonlySharedMemory::swap_in(&mut self.file, self.memory)?,
+            regions: self.regions.clone(),
+            load_info: self.load_info.clone(),
+            hash: self.hash,
+            stack_top_gva: self.stack_top_gva,
+            sregs: self.sregs,
+            entrypoint: self.entrypoint,
+            snapshot_generation: self.snapshot_generation,
+        })
+    }
+}
+
+impl Drop for SwappedSnapshot {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            tracing::warn!("failed to remove snapshot swap file {:?}: {e}", self.path);
+        }
+    }
+}
+
 impl PartialEq for Snapshot {
     fn eq(&self, other: &Snapshot) -> bool {
         self.hash == other.hash
@@ -720,7 +831,7 @@ mod tests {
         let cfg = crate::sandbox::SandboxConfiguration::default();
         let scratch_mem = ExclusiveSharedMemory::new(cfg.get_scratch_size()).unwrap();
         let mgr = SandboxMemoryManager::new(
-            SandboxMemoryLayout::new(cfg, 4096, 0x3000, None).unwrap(),
+            SandboxMemoryLayout::new(cfg, 4096, 0x3000, None, 0, 0).unwrap(),
             make_simple_pt_mem(&[0u8; PAGE_SIZE]),
             scratch_mem,
             super::NextAction::None,
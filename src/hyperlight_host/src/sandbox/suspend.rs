@@ -0,0 +1,159 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tracing_core::LevelFilter;
+
+use super::SandboxConfiguration;
+#[cfg(target_os = "linux")]
+use super::cgroup::VcpuCgroup;
+use super::control_page::ControlPage;
+use super::coverage::CoverageCounters;
+use super::guest_functions::GuestFunction;
+use super::host_funcs::FunctionRegistry;
+use super::initialized_multi_use::PtRootFinder;
+use super::labels::SandboxLabels;
+use super::profiler::GuestProfile;
+use super::recovery::RecoveryPolicy;
+use super::registry::Registration;
+use super::slow_call::SlowCallPolicy;
+use super::snapshot::{Snapshot, SwappedSnapshot};
+use crate::Result;
+
+/// When a [`SharedSandbox`](crate::sandbox::SharedSandbox) that has not
+/// been called for a while is suspended.
+///
+/// Suspending a sandbox captures its state in a snapshot and then tears
+/// down its virtual machine, releasing the vCPU, the hypervisor partition
+/// and the guest's scratch memory. If a swap directory is set, the
+/// snapshot's memory is also written out to a file there, so that a
+/// suspended sandbox uses almost no host memory. The next call into the
+/// sandbox transparently creates a new virtual machine from the snapshot
+/// before running, at the cost of some extra latency on that call.
+///
+/// Sandboxes are only suspended while they are not poisoned. Any
+/// [`InterruptHandle`](crate::hypervisor::InterruptHandle) obtained before
+/// a sandbox was suspended refers to the old virtual machine, and must be
+/// fetched again once it has been resumed.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+/// # use hyperlight_host::sandbox::{Fairness, IdleSuspend, SharedSandbox};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let sandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?.evolve()?;
+/// let shared = SharedSandbox::new(sandbox, 16, Fairness::Fifo);
+/// shared.set_idle_suspend(Some(
+///     IdleSuspend::new(Duration::from_secs(30)).swap_to("/var/tmp/my-service"),
+/// ));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct IdleSuspend {
+    pub(super) idle_timeout: Duration,
+    pub(super) swap_dir: Option<PathBuf>,
+}
+
+impl IdleSuspend {
+    /// Suspend the sandbox once it has not been called for `idle_timeout`
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            idle_timeout,
+            swap_dir: None,
+        }
+    }
+
+    /// Write the memory of suspended sandboxes to files in `dir`
+    pub fn swap_to(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.swap_dir = Some(dir.into());
+        self
+    }
+}
+
+/// The snapshot a suspended sandbox is resumed from
+pub(super) enum ResumeSnapshot {
+    InMemory(Arc<Snapshot>),
+    Swapped(Box<SwappedSnapshot>),
+}
+
+/// A [`MultiUseSandbox`](crate::MultiUseSandbox) whose virtual machine has
+/// been torn down, holding everything needed to create it again.
+///
+/// Created with `MultiUseSandbox::into_suspended()` and turned back into a
+/// running sandbox with `MultiUseSandbox::resume()`.
+pub(crate) struct SuspendedSandbox {
+    pub(super) snapshot: ResumeSnapshot,
+    pub(super) id: u64,
+    pub(super) snapshot_id: u64,
+    pub(super) host_funcs: Arc<Mutex<FunctionRegistry>>,
+    pub(super) init_snapshot: Option<Arc<Snapshot>>,
+    pub(super) pt_root_finder: Option<PtRootFinder>,
+    pub(super) config: SandboxConfiguration,
+    pub(super) max_guest_log_level: Option<LevelFilter>,
+    pub(super) labels: SandboxLabels,
+    pub(super) transactional_calls: bool,
+    pub(super) function_stack_sizes: HashMap<String, usize>,
+    pub(super) recovery_policy: RecoveryPolicy,
+    pub(super) consecutive_restarts: u32,
+    pub(super) slow_call_policy: Option<SlowCallPolicy>,
+    #[cfg(target_os = "linux")]
+    pub(super) vcpu_cgroup: Option<VcpuCgroup>,
+    pub(super) measurement: [u8; 32],
+    pub(super) guest_functions: Option<Vec<GuestFunction>>,
+    pub(super) control_page: ControlPage,
+    pub(super) registration: Option<Registration>,
+    pub(super) coverage: Option<CoverageCounters>,
+    /// The profile that was being taken when the sandbox was suspended,
+    /// which carries on when it is resumed
+    pub(super) profile: Option<GuestProfile>,
+    #[cfg(crashdump)]
+    pub(super) binary_path: Option<String>,
+}
+
+impl SuspendedSandbox {
+    /// Write the memory of the snapshot the sandbox resumes from to a file
+    /// in `dir`. Nothing is written if the snapshot is also used elsewhere,
+    /// as its memory could not be released.
+    pub(crate) fn swap_out(&mut self, dir: &Path) -> Result<()> {
+        if let ResumeSnapshot::InMemory(snapshot) = &self.snapshot
+            && Arc::strong_count(snapshot) == 1
+        {
+            self.snapshot = ResumeSnapshot::Swapped(Box::new(snapshot.swap_out(dir)?));
+        }
+        Ok(())
+    }
+
+    /// Returns the snapshot the sandbox resumes from, reading it back in if
+    /// it was swapped out
+    pub(super) fn snapshot(&mut self) -> Result<Arc<Snapshot>> {
+        let snapshot = match &mut self.snapshot {
+            ResumeSnapshot::InMemory(snapshot) => return Ok(snapshot.clone()),
+            ResumeSnapshot::Swapped(swapped) => Arc::new(swapped.swap_in()?),
+        };
+        self.snapshot = ResumeSnapshot::InMemory(snapshot.clone());
+        Ok(snapshot)
+    }
+}
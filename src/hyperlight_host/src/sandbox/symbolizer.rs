@@ -0,0 +1,283 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use gimli::{EndianArcSlice, RunTimeEndian};
+use goblin::elf::Elf;
+use goblin::elf::note::NT_GNU_BUILD_ID;
+use goblin::elf::program_header::PT_LOAD;
+use goblin::elf::section_header::SHF_COMPRESSED;
+
+use super::backtrace::{GuestBacktrace, GuestFrame};
+use crate::{Result, new_error};
+
+type DwarfReader = EndianArcSlice<RunTimeEndian>;
+
+/// Resolves addresses in the guest to the functions, source files and
+/// lines they are in.
+///
+/// A symbolizer is built from the guest binary's symbol table and, where
+/// the binary has it, its DWARF debug information. Function names come
+/// from the symbol table and file and line numbers from the DWARF line
+/// tables, so a guest built without debug information is resolved to
+/// functions only. Compressed debug sections are not supported.
+///
+/// Every sandbox has a symbolizer for the guest binary it was created
+/// from, which resolves the backtraces of
+/// [`HyperlightError::GuestError`](crate::HyperlightError::GuestError)s
+/// and the crashing instruction in crash dumps, and is available from
+/// [`MultiUseSandbox::guest_symbolizer()`](crate::MultiUseSandbox::guest_symbolizer).
+/// One can also be created for a guest binary on disk, for example to
+/// resolve the addresses in a crash dump after the sandbox is gone.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::sandbox::GuestSymbolizer;
+/// # fn example() -> hyperlight_host::Result<()> {
+/// let symbolizer = GuestSymbolizer::from_file("guest.bin", 0x1000)?;
+/// println!("{}", symbolizer.symbolize(0x1234));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct GuestSymbolizer {
+    /// The start, size and demangled name of each function, by start, at
+    /// the addresses they are loaded at
+    functions: Vec<(u64, u64, String)>,
+    /// The DWARF debug information of the binary, if it has any
+    dwarf: Option<Mutex<addr2line::Context<DwarfReader>>>,
+    /// The address the binary is loaded at
+    load_addr: u64,
+    /// The lowest virtual address of the binary's loadable segments, which
+    /// addresses in the DWARF debug information are relative to
+    base_va: u64,
+    /// The GNU build id of the binary, if it has one
+    build_id: Option<Vec<u8>>,
+}
+
+impl GuestSymbolizer {
+    /// A symbolizer for the guest binary in `bytes`, loaded at `load_addr`
+    pub fn new(bytes: &[u8], load_addr: u64) -> Result<Self> {
+        let elf = Elf::parse(bytes)?;
+        let base_va = elf
+            .program_headers
+            .iter()
+            .find(|phdr| phdr.p_type == PT_LOAD)
+            .map(|phdr| phdr.p_vaddr)
+            .ok_or_else(|| new_error!("ELF must have at least one PT_LOAD header"))?;
+
+        let mut functions: Vec<_> = elf
+            .syms
+            .iter()
+            .filter(|sym| sym.is_function() && sym.st_value >= base_va)
+            .filter_map(|sym| {
+                let name = elf.strtab.get_at(sym.st_name)?;
+                let start = load_addr + (sym.st_value - base_va);
+                Some((
+                    start,
+                    sym.st_size,
+                    format!("{:#}", rustc_demangle::demangle(name)),
+                ))
+            })
+            .collect();
+        functions.sort_by_key(|&(start, _, _)| start);
+
+        Ok(Self {
+            functions,
+            dwarf: load_dwarf(&elf, bytes).map(Mutex::new),
+            load_addr,
+            base_va,
+            build_id: load_build_id(&elf, bytes),
+        })
+    }
+
+    /// A symbolizer for the guest binary at `path`, loaded at `load_addr`
+    pub fn from_file(path: impl AsRef<Path>, load_addr: u64) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Self::new(&bytes, load_addr)
+    }
+
+    /// The address the guest binary is loaded at
+    pub fn load_addr(&self) -> u64 {
+        self.load_addr
+    }
+
+    /// The GNU build id of the guest binary, which identifies the build
+    /// it came from, if it has one
+    pub fn build_id(&self) -> Option<&[u8]> {
+        self.build_id.as_deref()
+    }
+
+    /// Whether the guest binary has DWARF debug information, so addresses
+    /// are resolved to source files and lines
+    pub fn has_debug_info(&self) -> bool {
+        self.dwarf.is_some()
+    }
+
+    /// The function and source location of the instruction at `address`
+    pub fn symbolize(&self, address: u64) -> GuestFrame {
+        self.frame(address, address)
+    }
+
+    /// A backtrace of the return addresses in `addresses`, innermost first
+    pub fn backtrace(&self, addresses: &[u64]) -> GuestBacktrace {
+        let frames = addresses
+            .iter()
+            // A return address is just past its call, which can be the
+            // last instruction of a function or be on the next line
+            .map(|&address| self.frame(address, address.saturating_sub(1)))
+            .collect();
+        GuestBacktrace { frames }
+    }
+
+    /// The frame of `address`, resolved as if it were at `lookup`
+    fn frame(&self, address: u64, lookup: u64) -> GuestFrame {
+        let idx = self
+            .functions
+            .partition_point(|&(start, _, _)| start <= lookup);
+        let function = idx
+            .checked_sub(1)
+            .map(|i| &self.functions[i])
+            .filter(|&&(start, size, _)| lookup - start < size.max(1));
+        let (file, line) = self.location(lookup).unwrap_or_default();
+        GuestFrame {
+            address,
+            symbol: function.map(|(_, _, name)| name.clone()),
+            offset: function.map_or(0, |&(start, _, _)| address - start),
+            file,
+            line,
+        }
+    }
+
+    /// The source file and line of `address` from the DWARF debug
+    /// information
+    fn location(&self, address: u64) -> Option<(Option<String>, Option<u32>)> {
+        let dwarf = self.dwarf.as_ref()?.lock().ok()?;
+        let svma = address.checked_sub(self.load_addr)? + self.base_va;
+        let location = dwarf.find_location(svma).ok()??;
+        Some((location.file.map(str::to_string), location.line))
+    }
+
+    #[cfg(test)]
+    pub(super) fn from_functions(functions: impl IntoIterator<Item = (u64, u64, String)>) -> Self {
+        let mut functions: Vec<_> = functions
+            .into_iter()
+            .map(|(start, size, name)| {
+                (
+                    start,
+                    size,
+                    format!("{:#}", rustc_demangle::demangle(&name)),
+                )
+            })
+            .collect();
+        functions.sort_by_key(|&(start, _, _)| start);
+        Self {
+            functions,
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Debug for GuestSymbolizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GuestSymbolizer")
+            .field("functions", &self.functions.len())
+            .field("has_debug_info", &self.has_debug_info())
+            .field("load_addr", &format_args!("{:#x}", self.load_addr))
+            .finish()
+    }
+}
+
+/// The GNU build id of `elf`, from its notes, if it has one
+fn load_build_id(elf: &Elf, bytes: &[u8]) -> Option<Vec<u8>> {
+    let segments = elf.iter_note_headers(bytes).into_iter().flatten();
+    let sections = elf.iter_note_sections(bytes, None).into_iter().flatten();
+    segments
+        .chain(sections)
+        .flatten()
+        .find(|note| note.n_type == NT_GNU_BUILD_ID && note.name == "GNU")
+        .map(|note| note.desc.to_vec())
+}
+
+/// The DWARF debug information of `elf`, if it has any
+fn load_dwarf(elf: &Elf, bytes: &[u8]) -> Option<addr2line::Context<DwarfReader>> {
+    let endian = if elf.little_endian {
+        RunTimeEndian::Little
+    } else {
+        RunTimeEndian::Big
+    };
+    let section_data = |name: &str| {
+        elf.section_headers
+            .iter()
+            .filter(|sh| sh.sh_flags & u64::from(SHF_COMPRESSED) == 0)
+            .find(|sh| elf.shdr_strtab.get_at(sh.sh_name) == Some(name))
+            .and_then(|sh| bytes.get(sh.file_range()?))
+    };
+    section_data(".debug_info")?;
+
+    let dwarf = gimli::Dwarf::load(|id| {
+        let data = section_data(id.name()).unwrap_or_default();
+        Ok::<_, gimli::Error>(EndianArcSlice::new(Arc::from(data), endian))
+    })
+    .ok()?;
+    addr2line::Context::from_dwarf(dwarf).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GuestSymbolizer;
+
+    #[test]
+    fn addresses_are_resolved_to_demangled_functions() {
+        let symbolizer = GuestSymbolizer::from_functions([
+            (
+                0x2000,
+                0x40,
+                "_ZN11simpleguest4main17h0123456789abcdefE".to_string(),
+            ),
+            (0x1000, 0x100, "hyperlight_main".to_string()),
+        ]);
+        let backtrace = symbolizer.backtrace(&[0x1010, 0x2040, 0x3000]);
+
+        let symbols: Vec<_> = backtrace
+            .frames
+            .iter()
+            .map(|f| f.symbol.as_deref())
+            .collect();
+        assert_eq!(
+            symbols,
+            [Some("hyperlight_main"), Some("simpleguest::main"), None]
+        );
+        assert_eq!(backtrace.frames[0].offset, 0x10);
+        assert_eq!(backtrace.frames[1].offset, 0x40);
+        assert_eq!(
+            backtrace.to_string().lines().next(),
+            Some("   0: 0x1010 - hyperlight_main+0x10")
+        );
+
+        // An instruction address is looked up as is, so the end of a
+        // function is not part of it
+        assert_eq!(symbolizer.symbolize(0x2040).symbol, None);
+        assert_eq!(
+            symbolizer.symbolize(0x203f).symbol.as_deref(),
+            Some("simpleguest::main")
+        );
+    }
+}
@@ -27,6 +27,7 @@ use opentelemetry::{Context, KeyValue, global};
 use tracing::span::{EnteredSpan, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
+use super::TimelineSlot;
 use crate::hypervisor::regs::CommonRegisters;
 use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::shared_mem::HostSharedMemory;
@@ -105,6 +106,9 @@ pub struct TraceContext {
     /// The frequency of the timestamp counter.
     tsc_freq: Option<u64>,
     current_parent_ctx: Option<Context>,
+    /// The timeline guest spans and events are also recorded into, if
+    /// one is being recorded
+    timeline: Option<TimelineSlot>,
 }
 
 impl TraceContext {
@@ -134,9 +138,16 @@ impl TraceContext {
             start_tsc: None,
             tsc_freq: None,
             current_parent_ctx: None,
+            timeline: None,
         }
     }
 
+    /// Also record guest spans and events into the timeline in `slot`
+    /// while it holds one
+    pub(crate) fn record_timeline(&mut self, slot: TimelineSlot) {
+        self.timeline = Some(slot);
+    }
+
     /// Calculate the frequency of the TimeStamp Counter.
     /// This is done by:
     /// - first reading a timestamp and an `Instant`
@@ -221,6 +232,10 @@ impl TraceContext {
         // Stack to keep track of open spans
         let mut spans_stack = vec![];
 
+        let timeline_slot = self.timeline.clone();
+        let mut timeline_guard = timeline_slot.as_ref().and_then(|slot| slot.lock().ok());
+        let mut timeline = timeline_guard.as_mut().and_then(|guard| guard.as_mut());
+
         // Process each event
         for ev in events.into_iter() {
             match ev {
@@ -277,6 +292,14 @@ impl TraceContext {
                         Span::current().context().clone()
                     };
 
+                    if let Some(timeline) = timeline.as_mut() {
+                        let args = fields
+                            .iter()
+                            .map(|kv| (kv.key.clone(), kv.value.clone()))
+                            .collect();
+                        timeline.guest_span_begin(start_ts, &name, args);
+                    }
+
                     // Create the span with calculated start time
                     let mut sb = tracer
                         .span_builder(name.to_string())
@@ -307,6 +330,9 @@ impl TraceContext {
                     if let Some(mut span) = self.guest_spans.remove(&id) {
                         let end_ts = self.calculate_guest_time_relative_to_host(start_tsc, tsc)?;
                         span.end_with_timestamp(end_ts);
+                        if let Some(timeline) = timeline.as_mut() {
+                            timeline.guest_span_end(end_ts);
+                        }
 
                         // The span ids should be closed in order
                         if let Some(stack_id) = spans_stack.pop()
@@ -329,6 +355,14 @@ impl TraceContext {
                     ))?;
                     let ts = self.calculate_guest_time_relative_to_host(start_tsc, tsc)?;
 
+                    if let Some(timeline) = timeline.as_mut() {
+                        let args = fields
+                            .iter()
+                            .map(|kv| (kv.key.clone(), kv.value.clone()))
+                            .collect();
+                        timeline.guest_event(ts, &name, args);
+                    }
+
                     // Add the event to the parent span
                     // It should always have a parent span
                     if let Some(span) = self.guest_spans.get_mut(&parent_id) {
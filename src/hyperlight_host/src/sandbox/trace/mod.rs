@@ -18,6 +18,11 @@ limitations under the License.
 mod context;
 pub(crate) use context::TraceContext;
 
+/// Timelines of guest traces for trace viewers.
+mod timeline;
+pub use timeline::TraceTimeline;
+pub(crate) use timeline::{HostCallSlice, TimelineSlot};
+
 /// Tracing and profiling support for sandboxes.
 #[cfg(feature = "mem_profile")]
 mod mem_profile;
@@ -0,0 +1,415 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use serde_json::{Map, Value, json};
+
+use crate::sandbox::protobuf::Message;
+
+/// The timeline a sandbox's vcpu thread records into while it is enabled
+pub(crate) type TimelineSlot = Arc<Mutex<Option<TraceTimeline>>>;
+
+/// The process id of the sandbox in exported traces
+const PID: u64 = 1;
+
+/// The track an event of a [`TraceTimeline`] is shown on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Track {
+    /// The guest's spans and events
+    Guest = 1,
+    /// The host functions the guest called and the guest's VM exits
+    Host = 2,
+}
+
+impl Track {
+    const ALL: [Track; 2] = [Track::Guest, Track::Host];
+
+    fn name(self) -> &'static str {
+        match self {
+            Track::Guest => "guest",
+            Track::Host => "host",
+        }
+    }
+}
+
+/// Whether an event starts a slice, ends one, or is an instant
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Phase {
+    Begin,
+    End,
+    Instant,
+}
+
+#[derive(Clone, Debug)]
+struct TimelineEvent {
+    time: SystemTime,
+    track: Track,
+    phase: Phase,
+    name: String,
+    category: &'static str,
+    args: Vec<(String, String)>,
+}
+
+/// A timeline of what a guest did, recorded while the `trace_guest`
+/// feature is enabled, for viewing in `chrome://tracing`, Perfetto or
+/// other trace viewers.
+///
+/// The guest's spans and events are shown on one track, and the host
+/// functions it called and the VM exits it made on another, on the same
+/// timeline. Guest timestamps are converted from the guest's TSC, so they
+/// line up with the host's only as closely as the TSC frequency the host
+/// measures allows.
+///
+/// A timeline is recorded with
+/// [`MultiUseSandbox::start_trace_timeline()`](crate::MultiUseSandbox::start_trace_timeline)
+/// and [`MultiUseSandbox::stop_trace_timeline()`](crate::MultiUseSandbox::stop_trace_timeline),
+/// alongside the spans the guest exports through OpenTelemetry.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use hyperlight_host::{MultiUseSandbox, UninitializedSandbox, GuestBinary};
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(
+///     GuestBinary::FilePath("guest.bin".into()),
+///     None
+/// )?.evolve()?;
+///
+/// sandbox.start_trace_timeline()?;
+/// sandbox.call::<String>("Echo", "hello".to_string())?;
+/// let timeline = sandbox.stop_trace_timeline()?;
+///
+/// std::fs::write("guest.json", timeline.to_chrome_trace())?;
+/// std::fs::write("guest.perfetto-trace", timeline.to_perfetto())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct TraceTimeline {
+    /// When recording started, which exported timestamps are relative to
+    started_at: SystemTime,
+    events: Vec<TimelineEvent>,
+}
+
+impl TraceTimeline {
+    pub(crate) fn new() -> Self {
+        Self {
+            started_at: SystemTime::now(),
+            events: Vec::new(),
+        }
+    }
+
+    /// The number of events in the timeline
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Whether the timeline has no events
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// The timeline in the Chrome trace event JSON format, as read by
+    /// `chrome://tracing` and the Perfetto UI
+    pub fn to_chrome_trace(&self) -> String {
+        let mut trace_events = vec![json!({
+            "ph": "M",
+            "name": "process_name",
+            "pid": PID,
+            "args": { "name": "hyperlight sandbox" },
+        })];
+        trace_events.extend(Track::ALL.iter().map(|&track| {
+            json!({
+                "ph": "M",
+                "name": "thread_name",
+                "pid": PID,
+                "tid": track as u64,
+                "args": { "name": track.name() },
+            })
+        }));
+
+        for event in self.sorted_events() {
+            let mut value = json!({
+                "name": event.name,
+                "cat": event.category,
+                "ph": match event.phase {
+                    Phase::Begin => "B",
+                    Phase::End => "E",
+                    Phase::Instant => "i",
+                },
+                "ts": self.offset_nanos(event) as f64 / 1000.0,
+                "pid": PID,
+                "tid": event.track as u64,
+            });
+            if event.phase == Phase::Instant {
+                value["s"] = json!("t");
+            }
+            if !event.args.is_empty() {
+                let args: Map<String, Value> = event
+                    .args
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+                    .collect();
+                value["args"] = Value::Object(args);
+            }
+            trace_events.push(value);
+        }
+
+        json!({ "traceEvents": trace_events, "displayTimeUnit": "ns" }).to_string()
+    }
+
+    /// The timeline in the Perfetto protobuf trace format, as read by the
+    /// Perfetto UI and `trace_processor`
+    pub fn to_perfetto(&self) -> Vec<u8> {
+        // Every packet is written by the same sequence
+        const SEQUENCE_ID: u64 = 1;
+
+        let mut trace = Message::default();
+        for track in Track::ALL {
+            let mut descriptor = Message::default();
+            descriptor.varint(1, track as u64);
+            descriptor.string(2, track.name());
+            let mut packet = Message::default();
+            packet.varint(10, SEQUENCE_ID);
+            packet.message(60, &descriptor);
+            trace.message(1, &packet);
+        }
+
+        for event in self.sorted_events() {
+            let mut track_event = Message::default();
+            for (key, value) in &event.args {
+                let mut annotation = Message::default();
+                annotation.string(10, key);
+                annotation.string(6, value);
+                track_event.message(4, &annotation);
+            }
+            track_event.varint(
+                9,
+                match event.phase {
+                    Phase::Begin => 1,
+                    Phase::End => 2,
+                    Phase::Instant => 3,
+                },
+            );
+            track_event.varint(11, event.track as u64);
+            track_event.string(22, event.category);
+            if event.phase != Phase::End {
+                track_event.string(23, &event.name);
+            }
+
+            let mut packet = Message::default();
+            packet.varint(8, self.offset_nanos(event));
+            packet.varint(10, SEQUENCE_ID);
+            packet.message(11, &track_event);
+            trace.message(1, &packet);
+        }
+        trace.into_bytes()
+    }
+
+    /// Record the start of a guest span
+    pub(crate) fn guest_span_begin(
+        &mut self,
+        time: SystemTime,
+        name: &str,
+        args: Vec<(String, String)>,
+    ) {
+        self.push(time, Track::Guest, Phase::Begin, name, "guest", args);
+    }
+
+    /// Record the end of the innermost open guest span
+    pub(crate) fn guest_span_end(&mut self, time: SystemTime) {
+        self.push(time, Track::Guest, Phase::End, "", "guest", Vec::new());
+    }
+
+    /// Record an event the guest logged
+    pub(crate) fn guest_event(
+        &mut self,
+        time: SystemTime,
+        name: &str,
+        args: Vec<(String, String)>,
+    ) {
+        self.push(time, Track::Guest, Phase::Instant, name, "guest", args);
+    }
+
+    /// Record that the guest exited the VM for `reason`
+    pub(crate) fn vm_exit(&mut self, reason: &str) {
+        self.push(
+            SystemTime::now(),
+            Track::Host,
+            Phase::Instant,
+            "vm-exit",
+            "vm",
+            vec![("reason".to_string(), reason.to_string())],
+        );
+    }
+
+    fn push(
+        &mut self,
+        time: SystemTime,
+        track: Track,
+        phase: Phase,
+        name: &str,
+        category: &'static str,
+        args: Vec<(String, String)>,
+    ) {
+        self.events.push(TimelineEvent {
+            time,
+            track,
+            phase,
+            name: name.to_string(),
+            category,
+            args,
+        });
+    }
+
+    /// The events in the order they happened. Guest events are recorded
+    /// when the guest hands them over rather than when they happen.
+    fn sorted_events(&self) -> Vec<&TimelineEvent> {
+        let mut events: Vec<_> = self.events.iter().collect();
+        events.sort_by_key(|event| event.time);
+        events
+    }
+
+    fn offset_nanos(&self, event: &TimelineEvent) -> u64 {
+        event
+            .time
+            .duration_since(self.started_at)
+            .unwrap_or_default()
+            .as_nanos() as u64
+    }
+}
+
+/// Records a host function call on the timeline of a sandbox, if one is
+/// being recorded, for as long as the slice is alive
+pub(crate) struct HostCallSlice<'a> {
+    timeline: &'a TimelineSlot,
+}
+
+impl<'a> HostCallSlice<'a> {
+    pub(crate) fn begin(timeline: &'a TimelineSlot, function_name: &str) -> Self {
+        if let Ok(mut timeline) = timeline.lock()
+            && let Some(timeline) = timeline.as_mut()
+        {
+            timeline.push(
+                SystemTime::now(),
+                Track::Host,
+                Phase::Begin,
+                function_name,
+                "host_call",
+                Vec::new(),
+            );
+        }
+        Self { timeline }
+    }
+}
+
+impl Drop for HostCallSlice<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut timeline) = self.timeline.lock()
+            && let Some(timeline) = timeline.as_mut()
+        {
+            timeline.push(
+                SystemTime::now(),
+                Track::Host,
+                Phase::End,
+                "",
+                "host_call",
+                Vec::new(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::{HostCallSlice, TraceTimeline};
+
+    fn timeline() -> TraceTimeline {
+        let mut timeline = TraceTimeline::new();
+        let start = timeline.started_at;
+        timeline.guest_span_begin(
+            start + Duration::from_micros(10),
+            "guest_function",
+            vec![("name".to_string(), "Echo".to_string())],
+        );
+        timeline.guest_event(start + Duration::from_micros(15), "log", vec![]);
+        timeline.guest_span_end(start + Duration::from_micros(30));
+        // Recorded after the guest events it happened between
+        timeline.push(
+            start + Duration::from_micros(20),
+            super::Track::Host,
+            super::Phase::Instant,
+            "vm-exit",
+            "vm",
+            vec![],
+        );
+        timeline
+    }
+
+    #[test]
+    fn chrome_trace_interleaves_host_and_guest_events() {
+        let trace: serde_json::Value = serde_json::from_str(&timeline().to_chrome_trace()).unwrap();
+        let events: Vec<_> = trace["traceEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|event| event["ph"] != "M")
+            .map(|event| {
+                (
+                    event["ph"].as_str().unwrap(),
+                    event["tid"].as_u64().unwrap(),
+                    event["ts"].as_f64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            events,
+            [
+                ("B", 1, 10.0),
+                ("i", 1, 15.0),
+                ("i", 2, 20.0),
+                ("E", 1, 30.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn perfetto_trace_has_tracks_and_events() {
+        let trace = timeline().to_perfetto();
+        for name in ["guest", "host", "guest_function", "vm-exit", "Echo"] {
+            assert!(
+                trace.windows(name.len()).any(|w| w == name.as_bytes()),
+                "{name} is not in the trace"
+            );
+        }
+    }
+
+    #[test]
+    fn host_calls_are_recorded_only_while_enabled() {
+        let slot = Arc::new(Mutex::new(None));
+        drop(HostCallSlice::begin(&slot, "Add"));
+        assert!(slot.lock().unwrap().is_none());
+
+        *slot.lock().unwrap() = Some(TraceTimeline::new());
+        drop(HostCallSlice::begin(&slot, "Add"));
+        assert_eq!(slot.lock().unwrap().as_ref().unwrap().len(), 2);
+    }
+}
@@ -14,19 +14,44 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 
+use std::borrow::Cow;
 use std::fmt::Debug;
 use std::option::Option;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use tracing::{Span, instrument};
 use tracing_core::LevelFilter;
 
+use super::audit::AuditSink;
+use super::capabilities::Capabilities;
+#[cfg(target_os = "linux")]
+use super::cgroup::VcpuCgroup;
+use super::events::{self, EventSubscriber};
+use super::guest_functions::GuestFunction;
 use super::host_funcs::{FunctionRegistry, default_writer_func};
+use super::input::InputSource;
+use super::interceptor::HostCall;
+use super::labels::SandboxLabels;
+#[cfg(landlock)]
+use super::landlock::FilesystemRestriction;
+use super::lazy::LazySandbox;
+use super::namespace::HostNamespace;
+use super::outcall_trace::OutcallTracer;
+use super::output::OutputSink;
+use super::rate_limit::{GuestLogRateLimit, RateLimit};
+use super::replay::{ExecutionLog, ExecutionRecording};
+#[cfg(seccomp)]
+use super::seccomp::SyscallFilter;
+use super::shared_image::SharedGuestImage;
+use super::signature::GuestSignature;
 use super::snapshot::Snapshot;
 use super::uninitialized_evolve::evolve_impl_multi_use;
-use crate::func::host_functions::{HostFunction, register_host_function};
-use crate::func::{ParameterTuple, SupportedReturnType};
+use crate::func::host_functions::{
+    AsyncContext, AsyncHostFunction, HostFunction, register_host_function,
+};
+use crate::func::{ParameterTuple, ReturnValue, SupportedReturnType};
 #[cfg(feature = "build-metadata")]
 use crate::log_build_details;
 use crate::mem::memory_region::{DEFAULT_GUEST_BLOB_MEM_FLAGS, MemoryRegionFlags};
@@ -35,7 +60,7 @@ use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::shared_mem::HostSharedMemory;
 use crate::mem::shared_mem::{ExclusiveSharedMemory, SharedMemory};
 use crate::sandbox::SandboxConfiguration;
-use crate::{MultiUseSandbox, Result, new_error};
+use crate::{HyperlightError, MultiUseSandbox, Result, new_error};
 
 #[cfg(any(crashdump, gdb))]
 #[derive(Clone, Debug, Default)]
@@ -44,6 +69,10 @@ pub(crate) struct SandboxRuntimeConfig {
     pub(crate) binary_path: Option<String>,
     #[cfg(gdb)]
     pub(crate) debug_info: Option<super::config::DebugInfo>,
+    /// The unix domain socket to listen on for a debugger, instead of the
+    /// address and port in `debug_info`
+    #[cfg(gdb)]
+    pub(crate) debug_socket: Option<std::path::PathBuf>,
     #[cfg(crashdump)]
     pub(crate) guest_core_dump: bool,
     /// The original entry point address of the loaded guest binary
@@ -54,6 +83,24 @@ pub(crate) struct SandboxRuntimeConfig {
     /// in `set_up_hypervisor_partition`.
     #[cfg(crashdump)]
     pub(crate) entry_point: Option<u64>,
+    /// The labels of the sandbox, included in core dump file names
+    #[cfg(crashdump)]
+    pub(crate) labels: SandboxLabels,
+    /// What the crash dumps of the sandbox contain
+    #[cfg(crashdump)]
+    pub(crate) crashdump_policy: crate::sandbox::CrashDumpPolicy,
+    /// Where the crash dumps of the sandbox are written, or `None` to
+    /// write them to files
+    #[cfg(crashdump)]
+    pub(crate) crashdump_sink: Option<Arc<dyn crate::sandbox::CrashDumpSink>>,
+    /// The symbolizer of the guest binary, which the crashing instruction
+    /// in core dumps is resolved against
+    #[cfg(crashdump)]
+    pub(crate) guest_symbolizer: Arc<crate::sandbox::GuestSymbolizer>,
+    /// The last host functions the guest called, which are listed in
+    /// crash reports
+    #[cfg(crashdump)]
+    pub(crate) recent_outcalls: crate::hypervisor::crashdump::RecentOutcalls,
 }
 
 /// A host-authoritative shared counter exposed to the guest via a `u64`
@@ -184,12 +231,23 @@ pub struct UninitializedSandbox {
     /// File mappings prepared by [`Self::map_file_cow`] that will be
     /// applied to the VM during [`Self::evolve`].
     pub(crate) pending_file_mappings: Vec<super::file_mapping::PreparedFileMapping>,
+    /// Whether the guest binary was checked against a [`GuestSignature`]
+    /// when this sandbox was created.
+    pub(crate) guest_verified: bool,
+    /// The cgroup the vCPU thread is placed in while the guest runs
+    #[cfg(target_os = "linux")]
+    pub(crate) vcpu_cgroup: Option<VcpuCgroup>,
+    /// Labels identifying the sandbox in logs, metrics and crash dumps
+    pub(crate) labels: SandboxLabels,
+    /// The functions the guest must have registered once it is initialised
+    pub(crate) required_guest_functions: Vec<GuestFunction>,
 }
 
 impl Debug for UninitializedSandbox {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("UninitializedSandbox")
             .field("memory_layout", &self.mgr.layout)
+            .field("labels", &self.labels)
             .finish()
     }
 }
@@ -201,12 +259,29 @@ pub enum GuestBinary<'a> {
     Buffer(&'a [u8]),
     /// A path to the GuestBinary
     FilePath(String),
+    /// A flat, position-independent binary with no ELF headers, such as
+    /// code produced by a JIT or a custom linker script.
+    ///
+    /// The blob is copied as-is to the start of the guest code region, which
+    /// is mapped readable and executable, and execution starts
+    /// `entry_offset` bytes into it. The entrypoint is called in the same
+    /// way as the entrypoint of an ELF guest, and must follow the same
+    /// initialisation protocol. The blob must not need relocating, and has
+    /// no symbols, so it cannot be combined with
+    /// [`GuestEnvironment::with_init_entry_point`].
+    RawBlob {
+        /// The bytes of the blob
+        data: &'a [u8],
+        /// The offset of the entrypoint from the start of the blob
+        entry_offset: u64,
+    },
 }
 impl<'a> GuestBinary<'a> {
     /// If the guest binary is identified by a file, canonicalise the path
     ///
     /// For [`GuestBinary::FilePath`], this resolves the path to its canonical
-    /// form. For [`GuestBinary::Buffer`], this method is a no-op.
+    /// form. For [`GuestBinary::Buffer`] and [`GuestBinary::RawBlob`], this
+    /// method is a no-op.
     /// TODO: Maybe we should make the GuestEnvironment or
     ///       GuestBinary constructors crate-private and turn this
     ///       into an invariant on one of those types.
@@ -222,6 +297,28 @@ impl<'a> GuestBinary<'a> {
         }
         Ok(())
     }
+
+    /// Verify the guest binary against a detached Ed25519 `signature`
+    /// made with the private key matching `public_key`.
+    ///
+    /// The signature must cover the exact bytes of the binary. Returns
+    /// [`HyperlightError::GuestBinaryVerificationFailed`]
+    /// if the signature or key is malformed or the signature does not match.
+    pub fn verify(&self, signature: &[u8], public_key: &[u8]) -> Result<()> {
+        GuestSignature::new(signature, public_key)?.verify(&self.contents()?)
+    }
+
+    /// Read the bytes of the guest binary
+    pub(super) fn contents(&self) -> Result<Cow<'a, [u8]>> {
+        match self {
+            GuestBinary::Buffer(buffer) | GuestBinary::RawBlob { data: buffer, .. } => {
+                Ok(Cow::Borrowed(buffer))
+            }
+            GuestBinary::FilePath(path) => std::fs::read(path)
+                .map(Cow::Owned)
+                .map_err(|e| new_error!("Failed to read guest binary '{}': {}", path, e)),
+        }
+    }
 }
 
 /// A `GuestBlob` containing data and the permissions for its use.
@@ -253,6 +350,18 @@ pub struct GuestEnvironment<'a, 'b> {
     pub guest_binary: GuestBinary<'a>,
     /// An optional guest blob, which can be used to provide additional data to the guest.
     pub init_data: Option<GuestBlob<'b>>,
+    /// An optional detached signature that the guest binary must match.
+    pub signature: Option<GuestSignature>,
+    /// Optional configuration bytes the guest can read from the moment it
+    /// starts. See [`with_startup_config`](Self::with_startup_config).
+    pub startup_config: Option<&'b [u8]>,
+    /// The name of the function to run instead of `hyperlight_main` when
+    /// the guest is initialised. See
+    /// [`with_init_entry_point`](Self::with_init_entry_point).
+    pub init_entry_point: Option<&'b str>,
+    /// Command-line style arguments for the guest. See
+    /// [`with_args`](Self::with_args).
+    pub args: Vec<String>,
 }
 
 impl<'a, 'b> GuestEnvironment<'a, 'b> {
@@ -261,8 +370,67 @@ impl<'a, 'b> GuestEnvironment<'a, 'b> {
         GuestEnvironment {
             guest_binary,
             init_data: init_data.map(GuestBlob::from),
+            signature: None,
+            startup_config: None,
+            init_entry_point: None,
+            args: Vec::new(),
         }
     }
+
+    /// Require the guest binary to match the given detached signature.
+    ///
+    /// The signature is checked by [`UninitializedSandbox::new`] before the
+    /// binary is loaded.
+    pub fn with_signature(mut self, signature: GuestSignature) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    /// Hand the guest an opaque configuration blob.
+    ///
+    /// The bytes are placed in read-only guest memory and described in the
+    /// PEB before the guest is initialised, so the guest can read them with
+    /// `hyperlight_guest_bin::host_comm::startup_config()` from
+    /// `hyperlight_main` onwards, without the host having to make a
+    /// configuration call first. The format is up to the host and guest;
+    /// a key/value environment can be passed by serialising it.
+    pub fn with_startup_config(mut self, config: &'b [u8]) -> Self {
+        self.startup_config = Some(config);
+        self
+    }
+
+    /// Run the guest function `name` instead of `hyperlight_main` when the
+    /// guest is initialised.
+    ///
+    /// This lets one guest binary export several entry points, for example
+    /// `hyperlight_main` for normal use and `bench_main` to set the guest up
+    /// for benchmarking, with the host choosing between them when the
+    /// sandbox is created. The function must be exported from the guest as
+    /// `#[unsafe(no_mangle)] pub extern "C" fn name()`, and the guest
+    /// binary must keep its symbol table. [`UninitializedSandbox::new`]
+    /// fails if the binary has no such function.
+    pub fn with_init_entry_point(mut self, name: &'b str) -> Self {
+        self.init_entry_point = Some(name);
+        self
+    }
+
+    /// Hand the guest a list of command-line style arguments.
+    ///
+    /// The arguments are placed in read-only guest memory before the guest
+    /// is initialised, as a C-style `argc`/`argv` pair, so that code written
+    /// for a command line can be ported into a guest with few changes.
+    /// Rust guests read them with `hyperlight_guest_bin::host_comm::args()`
+    /// and C guests with `hl_get_argc()` and `hl_get_argv()`. By convention
+    /// the first argument is the program name. Arguments may not contain
+    /// NUL characters.
+    pub fn with_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
 }
 
 impl<'a> From<GuestBinary<'a>> for GuestEnvironment<'a, '_> {
@@ -270,6 +438,10 @@ impl<'a> From<GuestBinary<'a>> for GuestEnvironment<'a, '_> {
         GuestEnvironment {
             guest_binary,
             init_data: None,
+            signature: None,
+            startup_config: None,
+            init_entry_point: None,
+            args: Vec::new(),
         }
     }
 }
@@ -326,7 +498,7 @@ impl UninitializedSandbox {
     // `InterruptHandler` on Linux.
     //
     // This is ok for now as this is not a public function
-    fn from_snapshot(
+    pub(super) fn from_snapshot(
         snapshot: Arc<Snapshot>,
         cfg: Option<SandboxConfiguration>,
         #[cfg(crashdump)] binary_path: Option<String>,
@@ -353,19 +525,37 @@ impl UninitializedSandbox {
                 binary_path,
                 #[cfg(gdb)]
                 debug_info,
+                #[cfg(gdb)]
+                debug_socket: None,
                 #[cfg(crashdump)]
                 guest_core_dump,
                 // entry_point is set later in set_up_hypervisor_partition
                 // once the entrypoint is resolved from the snapshot
                 #[cfg(crashdump)]
                 entry_point: None,
+                #[cfg(crashdump)]
+                labels: SandboxLabels::default(),
+                #[cfg(crashdump)]
+                crashdump_policy: Default::default(),
+                #[cfg(crashdump)]
+                crashdump_sink: None,
+                // guest_symbolizer is also set in set_up_hypervisor_partition,
+                // once the binary is loaded
+                #[cfg(crashdump)]
+                guest_symbolizer: Default::default(),
+                #[cfg(crashdump)]
+                recent_outcalls: Default::default(),
             }
         };
 
         let mem_mgr_wrapper =
             SandboxMemoryManager::<ExclusiveSharedMemory>::from_snapshot(snapshot.as_ref())?;
 
-        let host_funcs = Arc::new(Mutex::new(FunctionRegistry::default()));
+        #[cfg_attr(not(crashdump), allow(unused_mut))]
+        let mut host_funcs = FunctionRegistry::default();
+        #[cfg(crashdump)]
+        host_funcs.set_recent_outcalls(rt_cfg.recent_outcalls.clone());
+        let host_funcs = Arc::new(Mutex::new(host_funcs));
 
         let mut sandbox = Self {
             host_funcs,
@@ -381,6 +571,11 @@ impl UninitializedSandbox {
             #[cfg(feature = "guest-counter")]
             counter_taken: std::sync::atomic::AtomicBool::new(false),
             pending_file_mappings: Vec::new(),
+            guest_verified: false,
+            #[cfg(target_os = "linux")]
+            vcpu_cgroup: None,
+            labels: SandboxLabels::default(),
+            required_guest_functions: Vec::new(),
         };
 
         // If we were passed a writer for host print register it otherwise use the default.
@@ -397,6 +592,9 @@ impl UninitializedSandbox {
     /// An optional configuration can customize memory sizes and sandbox settings.
     /// After creation, register host functions using [`register`](Self::register)
     /// before calling [`evolve`](Self::evolve) to complete initialization and create the VM.
+    ///
+    /// If the environment carries a [`GuestSignature`], the guest binary is
+    /// verified against it before being loaded.
     #[instrument(
         err(Debug),
         skip(env),
@@ -406,20 +604,26 @@ impl UninitializedSandbox {
         env: impl Into<GuestEnvironment<'a, 'b>>,
         cfg: Option<SandboxConfiguration>,
     ) -> Result<Self> {
-        let cfg = cfg.unwrap_or_default();
-        let env = env.into();
-        #[cfg(crashdump)]
-        let binary_path = match &env.guest_binary {
-            GuestBinary::FilePath(path) => Some(path.clone()),
-            GuestBinary::Buffer(_) => None,
-        };
-        let snapshot = Snapshot::from_env(env, cfg)?;
-        Self::from_snapshot(
-            Arc::new(snapshot),
-            Some(cfg),
+        let image = SharedGuestImage::new(env, cfg)?;
+        Self::from_shared_image(&image)
+    }
+
+    /// Creates a new uninitialized sandbox from a [`SharedGuestImage`].
+    ///
+    /// The sandbox maps the guest code and initial data of the image
+    /// read-only rather than loading its own copy, and uses the
+    /// configuration the image was loaded with.
+    #[instrument(err(Debug), skip_all, parent = Span::current())]
+    pub fn from_shared_image(image: &SharedGuestImage) -> Result<Self> {
+        let mut sandbox = Self::from_snapshot(
+            image.snapshot.clone(),
+            Some(image.config),
             #[cfg(crashdump)]
-            binary_path,
-        )
+            image.binary_path.clone(),
+        )?;
+        sandbox.guest_verified = image.verified;
+        events::emit(None, |subscriber| subscriber.on_created(&sandbox.labels));
+        Ok(sandbox)
     }
 
     /// Creates and initializes the virtual machine, transforming this into a ready-to-use sandbox.
@@ -427,9 +631,106 @@ impl UninitializedSandbox {
     /// This method consumes the `UninitializedSandbox` and performs the final initialization
     /// steps to create the underlying virtual machine. Once evolved, the resulting
     /// [`MultiUseSandbox`] can execute guest code and handle function calls.
+    ///
+    /// If [`SandboxConfiguration::set_require_signed_guests`] is enabled, this
+    /// fails unless the guest binary was verified when the sandbox was created.
     #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
     pub fn evolve(self) -> Result<MultiUseSandbox> {
-        evolve_impl_multi_use(self)
+        self.check_verified()?;
+        let mut sandbox = evolve_impl_multi_use(self)?;
+        sandbox.activate_events();
+        Ok(sandbox)
+    }
+
+    /// Fail if signed guests are required and the guest was not verified
+    pub(super) fn check_verified(&self) -> Result<()> {
+        if self.config.get_require_signed_guests() && !self.guest_verified {
+            Err(HyperlightError::GuestBinaryVerificationFailed(
+                "signed guests are required but the guest binary was not verified".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Like [`evolve()`](Self::evolve), but defers creating the virtual
+    /// machine and initialising the guest until the sandbox is first used.
+    ///
+    /// See [`LazySandbox`] for details.
+    #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
+    pub fn evolve_lazy(self) -> Result<LazySandbox> {
+        self.check_verified()?;
+        Ok(LazySandbox::new(self))
+    }
+
+    /// Attach the label `key` with the given `value` to the sandbox,
+    /// replacing any previous value. The labels are carried over to the
+    /// [`MultiUseSandbox`] this sandbox evolves into.
+    ///
+    /// See [`SandboxLabels`] for where labels are used and which keys are
+    /// valid.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut u_sbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?;
+    /// u_sbox.set_label("tenant", "contoso")?;
+    /// u_sbox.set_label("function_name", "resize-image")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_label(&mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Result<()> {
+        self.labels.set(key.as_ref(), value.as_ref())?;
+        #[cfg(crashdump)]
+        {
+            self.rt_cfg.labels = self.labels.clone();
+        }
+        Ok(())
+    }
+
+    /// Listen for a debugger on the unix domain socket at `path`, instead of
+    /// the address and port in the sandbox's
+    /// [`DebugInfo`](crate::sandbox::config::DebugInfo). This only has an
+    /// effect if guest debugging is enabled with
+    /// [`SandboxConfiguration::set_guest_debug_info()`].
+    ///
+    /// A socket left at `path` by an earlier process is replaced.
+    #[cfg(all(gdb, unix))]
+    pub fn set_guest_debug_socket(&mut self, path: impl Into<std::path::PathBuf>) {
+        self.rt_cfg.debug_socket = Some(path.into());
+    }
+
+    /// Set what the crash dumps of the sandbox contain, such as areas of
+    /// guest memory to leave out or redact.
+    ///
+    /// See [`CrashDumpPolicy`](crate::sandbox::CrashDumpPolicy).
+    #[cfg(crashdump)]
+    pub fn set_crashdump_policy(&mut self, policy: crate::sandbox::CrashDumpPolicy) {
+        self.rt_cfg.crashdump_policy = policy;
+    }
+
+    /// Send the crash dumps of the sandbox to `sink`, instead of writing
+    /// them to files in the core dump directory.
+    ///
+    /// See [`CrashDumpSink`](crate::sandbox::CrashDumpSink).
+    #[cfg(crashdump)]
+    pub fn set_crashdump_sink(&mut self, sink: impl crate::sandbox::CrashDumpSink + 'static) {
+        self.rt_cfg.crashdump_sink = Some(Arc::new(sink));
+    }
+
+    /// Run the guest's vCPU in the given cgroup, starting with the guest's
+    /// initialisation in [`evolve()`](Self::evolve). The cgroup is carried
+    /// over to the resulting [`MultiUseSandbox`].
+    ///
+    /// See [`VcpuCgroup`] for the requirements on the cgroup.
+    #[cfg(target_os = "linux")]
+    pub fn set_vcpu_cgroup(&mut self, cgroup: VcpuCgroup) {
+        self.vcpu_cgroup = Some(cgroup);
     }
 
     /// Map the contents of a file into the guest at a particular address.
@@ -534,7 +835,356 @@ impl UninitializedSandbox {
         name: impl AsRef<str>,
         host_func: impl Into<HostFunction<Output, Args>>,
     ) -> Result<()> {
-        register_host_function(host_func, self, name.as_ref())
+        self.register_with_capabilities(name, host_func, Capabilities::empty())
+    }
+
+    /// The namespace `namespace`, which host functions can be registered
+    /// in, such as `"wasi:fs"`.
+    ///
+    /// See [`HostNamespace`] for details.
+    pub fn namespace(&mut self, namespace: impl Into<String>) -> HostNamespace<'_> {
+        HostNamespace::new(self, namespace.into())
+    }
+
+    /// Registers a host function like [`register`](Self::register) that
+    /// can only be called by the guest if the sandbox has been granted all
+    /// of `capabilities`.
+    ///
+    /// See [`Capabilities`] for details.
+    pub fn register_with_capabilities<Args: ParameterTuple, Output: SupportedReturnType>(
+        &mut self,
+        name: impl AsRef<str>,
+        host_func: impl Into<HostFunction<Output, Args>>,
+        capabilities: Capabilities,
+    ) -> Result<()> {
+        register_host_function(
+            host_func,
+            self,
+            name.as_ref(),
+            capabilities,
+            #[cfg(seccomp)]
+            &SyscallFilter::default(),
+        )
+    }
+
+    /// Grant the sandbox `capabilities`, in addition to any it has already
+    /// been granted, allowing the guest to call host functions that
+    /// require them. The capabilities are kept by the [`MultiUseSandbox`]
+    /// this sandbox evolves into.
+    ///
+    /// See [`Capabilities`] for details.
+    pub fn grant_capabilities(&mut self, capabilities: Capabilities) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .grant_capabilities(capabilities);
+        Ok(())
+    }
+
+    /// Limit how often the guest may call the host function `name`, which
+    /// must already be registered. The limit is kept if the function is
+    /// later replaced.
+    ///
+    /// See [`RateLimit`] for details.
+    pub fn set_host_function_rate_limit(&mut self, name: &str, limit: RateLimit) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_function_rate_limit(name, limit)
+    }
+
+    /// Fail calls to the host function `name`, which must already be
+    /// registered, that do not return within `timeout`. The guest receives
+    /// an error with the code
+    /// [`ErrorCode::HostFunctionTimedOut`](hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode::HostFunctionTimedOut),
+    /// rather than waiting on a host function that has hung.
+    ///
+    /// A function with a timeout is called on a worker thread. If it times
+    /// out it is left running there, and whatever it returns is discarded.
+    /// The timeout is kept if the function is later replaced.
+    pub fn set_host_function_timeout(&mut self, name: &str, timeout: Duration) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_function_timeout(name, timeout)
+    }
+
+    /// Limit how often the guest may call host functions, across all of
+    /// them. This applies in addition to any limits set on individual
+    /// functions.
+    ///
+    /// See [`RateLimit`] for details.
+    pub fn set_host_call_rate_limit(&mut self, limit: RateLimit) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_rate_limit(limit);
+        Ok(())
+    }
+
+    /// Limit how many log records the guest may send the host. Records
+    /// over the limit are dropped and counted. The limit is kept by the
+    /// [`MultiUseSandbox`] this sandbox evolves into.
+    ///
+    /// See [`GuestLogRateLimit`] for details.
+    pub fn set_guest_log_rate_limit(&mut self, limit: GuestLogRateLimit) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_guest_log_rate_limit(Some(limit));
+        Ok(())
+    }
+
+    /// Deliver the lifecycle events of the sandbox, from its
+    /// initialisation on, to `subscriber`. The subscriber is kept by the
+    /// [`MultiUseSandbox`] this sandbox evolves into.
+    ///
+    /// See [`EventSubscriber`] for details.
+    pub fn add_event_subscriber(
+        &mut self,
+        subscriber: impl EventSubscriber + 'static,
+    ) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .add_event_subscriber(Arc::new(subscriber));
+        Ok(())
+    }
+
+    /// Record every host function the guest invokes with `sink`, along
+    /// with the labels of the sandbox, replacing any previous sink. The
+    /// sink is kept by the [`MultiUseSandbox`] this sandbox evolves into.
+    ///
+    /// See [`AuditSink`] for details.
+    pub fn set_audit_sink(&mut self, sink: impl AuditSink + 'static) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_audit_sink(Some(Arc::new(sink)));
+        Ok(())
+    }
+
+    /// Run every call the guest makes to a host function through
+    /// `interceptor`, which can inspect the call, change its arguments,
+    /// time it, or fail it without calling the host function. Interceptors
+    /// are kept by the [`MultiUseSandbox`] this sandbox evolves into.
+    ///
+    /// See [`HostCall`] for details.
+    pub fn add_host_call_interceptor(
+        &mut self,
+        interceptor: impl Fn(HostCall<'_>) -> Result<ReturnValue> + Send + Sync + 'static,
+    ) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .add_interceptor(Arc::new(interceptor));
+        Ok(())
+    }
+
+    /// Trace every call the guest makes out to the host with `tracer`. The
+    /// tracer is kept by the [`MultiUseSandbox`] this sandbox evolves into.
+    ///
+    /// See [`OutcallTracer`] for details.
+    pub fn trace_outcalls(&mut self, tracer: OutcallTracer) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_outcall_tracer(Some(tracer));
+        Ok(())
+    }
+
+    /// Require the guest to register a function called `name`, taking
+    /// `Args` and returning `Output`.
+    ///
+    /// [`evolve()`](Self::evolve) checks the functions the guest registered
+    /// against every required function, and fails with
+    /// [`HyperlightError::GuestInterfaceMismatch`] listing each one that is
+    /// missing or has a different signature, rather than the first call to
+    /// it failing later. It also fails if the guest does not report its
+    /// functions. See [`MultiUseSandbox::guest_functions()`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use hyperlight_host::{GuestBinary, UninitializedSandbox};
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut sandbox = UninitializedSandbox::new(
+    ///     GuestBinary::FilePath("guest.bin".into()),
+    ///     None
+    /// )?;
+    /// sandbox.require_guest_function::<(i32, i32), i32>("Add");
+    /// sandbox.require_guest_function::<String, ()>("PrintOutput");
+    /// // Fails if the guest has no `Add(i32, i32) -> i32` or
+    /// // `PrintOutput(String)` function
+    /// let sandbox = sandbox.evolve()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn require_guest_function<Args: ParameterTuple, Output: SupportedReturnType>(
+        &mut self,
+        name: impl AsRef<str>,
+    ) {
+        self.required_guest_functions.push(GuestFunction {
+            name: name.as_ref().to_string(),
+            parameter_types: Args::TYPE.to_vec(),
+            return_type: Output::TYPE,
+        });
+    }
+
+    /// Require the guest to register each of `functions`, like
+    /// [`require_guest_function()`](Self::require_guest_function), for
+    /// interfaces that are only known at runtime.
+    pub fn require_guest_functions(&mut self, functions: impl IntoIterator<Item = GuestFunction>) {
+        self.required_guest_functions.extend(functions);
+    }
+
+    /// Registers an async host function that the guest can call.
+    ///
+    /// While the future returned by the function is pending, the guest
+    /// stays parked, and it resumes once the future resolves. See
+    /// [`AsyncHostFunction`] for details.
+    pub fn register_async<Args: ParameterTuple, Output: SupportedReturnType>(
+        &mut self,
+        name: impl AsRef<str>,
+        host_func: impl Into<AsyncHostFunction<Output, Args>>,
+    ) -> Result<()> {
+        self.register(name, host_func.into())
+    }
+
+    /// Registers a host function like [`register`](Self::register), with
+    /// changes to the set of syscalls it is allowed to make.
+    ///
+    /// See [`SyscallFilter`] for details.
+    #[cfg(seccomp)]
+    pub fn register_with_syscall_filter<Args: ParameterTuple, Output: SupportedReturnType>(
+        &mut self,
+        name: impl AsRef<str>,
+        host_func: impl Into<HostFunction<Output, Args>>,
+        syscall_filter: SyscallFilter,
+    ) -> Result<()> {
+        register_host_function(
+            host_func,
+            self,
+            name.as_ref(),
+            Capabilities::empty(),
+            &syscall_filter,
+        )
+    }
+
+    /// Restrict the filesystem access of every host function called by
+    /// this sandbox, using Landlock.
+    ///
+    /// Host functions are then run on a worker thread that can only access
+    /// the paths allowed by `restriction`. The restriction also applies to
+    /// the [`MultiUseSandbox`] this sandbox evolves into. See
+    /// [`FilesystemRestriction`] for details.
+    #[cfg(landlock)]
+    pub fn restrict_host_function_filesystem(
+        &mut self,
+        restriction: FilesystemRestriction,
+    ) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_fs_restriction(restriction);
+        Ok(())
+    }
+
+    /// Send everything the guest writes to its stdout to `sink`, instead of
+    /// printing it with the `"HostPrint"` host function. The sink is kept
+    /// by the [`MultiUseSandbox`] this sandbox evolves into.
+    ///
+    /// See [`OutputSink`] for details.
+    pub fn set_guest_stdout(&mut self, sink: OutputSink) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_guest_stdout(sink);
+        Ok(())
+    }
+
+    /// Send everything the guest writes to its stderr, and its debug
+    /// prints, to `sink`, instead of the host's stderr. The sink is kept by
+    /// the [`MultiUseSandbox`] this sandbox evolves into.
+    ///
+    /// See [`OutputSink`] for details.
+    pub fn set_guest_stderr(&mut self, sink: OutputSink) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_guest_stderr(sink);
+        Ok(())
+    }
+
+    /// Read the guest's stdin from `source` whenever the guest reads it
+    /// during a call. Without a source, the guest's stdin is empty. The
+    /// source is kept by the [`MultiUseSandbox`] this sandbox evolves into.
+    ///
+    /// See [`InputSource`] for details.
+    pub fn set_guest_stdin(&mut self, source: InputSource) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_guest_stdin(source);
+        Ok(())
+    }
+
+    /// Stop the guest calling host functions in any of `namespaces`, or in
+    /// namespaces nested within them, even if they are registered. A
+    /// namespace is the part of a host function's name before its last
+    /// `/`, so `"wasi:fs"` disables `"wasi:fs/read"`. Disabled namespaces
+    /// are kept by the [`MultiUseSandbox`] this sandbox evolves into.
+    ///
+    /// See [`HostNamespace`] for details.
+    pub fn disable_host_namespaces(
+        &mut self,
+        namespaces: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .disable_namespaces(namespaces.into_iter().map(Into::into));
+        Ok(())
+    }
+
+    /// Run the futures of async host functions, and of an async
+    /// [`InputSource`], in `context`. The context is kept by the
+    /// [`MultiUseSandbox`] this sandbox evolves into.
+    ///
+    /// See [`AsyncContext`] for details.
+    pub fn set_async_context(&mut self, context: AsyncContext) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_async_context(context);
+        Ok(())
+    }
+
+    /// Record the sandbox's execution from when it is evolved, to be
+    /// replayed later. The recording is taken with
+    /// [`MultiUseSandbox::execution_recording()`].
+    ///
+    /// See [`ExecutionRecording`] for details.
+    pub fn record_execution(&mut self) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_execution_log(ExecutionLog::Recording(ExecutionRecording::default()));
+        Ok(())
+    }
+
+    /// Replay `recording` in the sandbox, which must be created from the
+    /// same guest binary and with the same configuration as the sandbox it
+    /// was recorded in. The recorded guest calls are replayed with
+    /// [`MultiUseSandbox::replay()`].
+    ///
+    /// See [`ExecutionRecording`] for details.
+    pub fn replay_execution(&mut self, recording: ExecutionRecording) -> Result<()> {
+        self.host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .set_execution_log(ExecutionLog::Replaying { recording, next: 0 });
+        Ok(())
     }
 
     /// Registers the special "HostPrint" function for guest printing.
@@ -617,6 +1267,123 @@ mod tests {
         assert_eq!(res, buffer.to_vec());
     }
 
+    #[test]
+    fn test_startup_config() {
+        let binary_path = simple_guest_as_string().unwrap();
+        let config = b"log_level=debug\nregion=westeurope".repeat(200);
+        let guest_env = GuestEnvironment::new(GuestBinary::FilePath(binary_path.clone()), None)
+            .with_startup_config(&config);
+
+        let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(guest_env, None)
+            .unwrap()
+            .evolve()
+            .unwrap();
+        let res = sandbox.call::<Vec<u8>>("GetStartupConfig", ()).unwrap();
+        assert_eq!(res, config);
+
+        // Sandboxes without a startup config see an empty one
+        let mut sandbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(binary_path), None)
+                .unwrap()
+                .evolve()
+                .unwrap();
+        let res = sandbox.call::<Vec<u8>>("GetStartupConfig", ()).unwrap();
+        assert!(res.is_empty());
+    }
+
+    #[test]
+    fn test_args() {
+        let binary_path = simple_guest_as_string().unwrap();
+        let guest_env = GuestEnvironment::new(GuestBinary::FilePath(binary_path.clone()), None)
+            .with_args(["simpleguest", "--input", "file name.txt"]);
+
+        let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(guest_env, None)
+            .unwrap()
+            .evolve()
+            .unwrap();
+        let res = sandbox.call::<String>("GetArgs", ()).unwrap();
+        assert_eq!(res, "simpleguest\n--input\nfile name.txt");
+
+        // Sandboxes without arguments see none
+        let mut sandbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(binary_path.clone()), None)
+                .unwrap()
+                .evolve()
+                .unwrap();
+        let res = sandbox.call::<String>("GetArgs", ()).unwrap();
+        assert!(res.is_empty());
+
+        let guest_env =
+            GuestEnvironment::new(GuestBinary::FilePath(binary_path), None).with_args(["bad\0arg"]);
+        let res = UninitializedSandbox::new(guest_env, None);
+        assert!(matches!(
+            res,
+            Err(crate::HyperlightError::InvalidConfiguration(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_raw_blob() {
+        // Padding before the entrypoint, then code that returns the address
+        // of a (halting) dispatch function and leaves the stack 16-byte
+        // aligned, as the initialisation protocol requires
+        let mut blob = vec![0xcc; 16];
+        blob.extend([
+            0x48, 0x8d, 0x05, 0x02, 0x00, 0x00, 0x00, // lea rax, [rip + 2]
+            0x50, // push rax
+            0xf4, // hlt
+            0xf4, // dispatch: hlt
+        ]);
+
+        let sandbox = UninitializedSandbox::new(
+            GuestBinary::RawBlob {
+                data: &blob,
+                entry_offset: 16,
+            },
+            None,
+        )
+        .unwrap();
+        sandbox.evolve().unwrap();
+
+        let res = UninitializedSandbox::new(
+            GuestBinary::RawBlob {
+                data: &blob,
+                entry_offset: blob.len() as u64,
+            },
+            None,
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_init_entry_point() {
+        let binary_path = simple_guest_as_string().unwrap();
+
+        let mut sandbox: MultiUseSandbox =
+            UninitializedSandbox::new(GuestBinary::FilePath(binary_path.clone()), None)
+                .unwrap()
+                .evolve()
+                .unwrap();
+        assert!(!sandbox.call::<bool>("BenchMainRan", ()).unwrap());
+
+        let guest_env = GuestEnvironment::new(GuestBinary::FilePath(binary_path.clone()), None)
+            .with_init_entry_point("bench_main");
+        let mut sandbox: MultiUseSandbox = UninitializedSandbox::new(guest_env, None)
+            .unwrap()
+            .evolve()
+            .unwrap();
+        assert!(sandbox.call::<bool>("BenchMainRan", ()).unwrap());
+
+        let guest_env = GuestEnvironment::new(GuestBinary::FilePath(binary_path), None)
+            .with_init_entry_point("no_such_main");
+        let res = UninitializedSandbox::new(guest_env, None);
+        assert!(matches!(
+            res,
+            Err(crate::HyperlightError::InvalidConfiguration(_))
+        ));
+    }
+
     #[test]
     fn test_new_sandbox() {
         // Guest Binary exists at path
@@ -17,26 +17,38 @@ limitations under the License.
 use std::sync::{Arc, Mutex};
 
 use rand::RngExt;
+use sha2::{Digest, Sha256};
 use tracing::{Span, instrument};
 
 use super::SandboxConfiguration;
+use super::guest_functions::check_interface;
 #[cfg(any(crashdump, gdb))]
 use super::uninitialized::SandboxRuntimeConfig;
-use crate::hypervisor::hyperlight_vm::{HyperlightVm, HyperlightVmError};
+use crate::hypervisor::hyperlight_vm::{HyperlightVm, HyperlightVmError, InitializeError};
 use crate::mem::exe::LoadInfo;
 use crate::mem::mgr::SandboxMemoryManager;
 use crate::mem::ptr::RawPtr;
 use crate::mem::shared_mem::GuestSharedMemory;
-#[cfg(gdb)]
-use crate::sandbox::config::DebugInfo;
+use crate::sandbox::snapshot::NextAction;
 #[cfg(feature = "mem_profile")]
 use crate::sandbox::trace::MemTraceInfo;
 #[cfg(target_os = "linux")]
 use crate::signal_handlers::setup_signal_handlers;
-use crate::{MultiUseSandbox, Result, UninitializedSandbox};
+use crate::{MultiUseSandbox, Result, UninitializedSandbox, new_error};
 
 #[instrument(err(Debug), skip_all, parent = Span::current(), level = "Trace")]
 pub(super) fn evolve_impl_multi_use(u_sbox: UninitializedSandbox) -> Result<MultiUseSandbox> {
+    // A sandbox created from the snapshot of a running sandbox (when
+    // resuming a suspended sandbox) is already initialised. Its snapshot
+    // memory no longer has the layout of a freshly loaded guest, so it is
+    // not measured, and it keeps the measurement and post-initialisation
+    // snapshot of the sandbox it was taken from, which the caller sets.
+    let initialising = matches!(u_sbox.mgr.entrypoint, NextAction::Initialise(_));
+    let measurement = if initialising {
+        measure(&u_sbox)?
+    } else {
+        [0; 32]
+    };
     let (mut hshm, gshm) = u_sbox.mgr.build()?;
 
     // Publish the HostSharedMemory for scratch so any pre-existing
@@ -58,6 +70,8 @@ pub(super) fn evolve_impl_multi_use(u_sbox: UninitializedSandbox) -> Result<Mult
     // for host-side alignment calculations in set_up_hypervisor_partition.
     let page_size = u32::try_from(page_size::get())?;
 
+    let guest_symbolizer = u_sbox.load_info.symbolizer.clone();
+    let coverage_counters = u_sbox.load_info.coverage_counters.clone();
     let mut vm = set_up_hypervisor_partition(
         gshm,
         &u_sbox.config,
@@ -70,8 +84,28 @@ pub(super) fn evolve_impl_multi_use(u_sbox: UninitializedSandbox) -> Result<Mult
 
     let seed = {
         let mut rng = rand::rng();
-        rng.random::<u64>()
+        let seed = rng.random::<u64>();
+        // A replayed execution is initialised with the recorded seed
+        match u_sbox
+            .host_funcs
+            .try_lock()
+            .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+            .execution_log()
+        {
+            Some(log) => log.seed(seed),
+            None => seed,
+        }
     };
+    // The guest's clock is not part of a recorded execution, so a guest
+    // whose execution is recorded or replayed can't read the time from it
+    let recorded = u_sbox
+        .host_funcs
+        .try_lock()
+        .map_err(|e| new_error!("Error locking at {}:{}: {}", file!(), line!(), e))?
+        .execution_log()
+        .is_some();
+    hshm.write_wall_clock_base(if recorded { 0 } else { vm.wall_clock_base() })?;
+
     let peb_addr = {
         let peb_u64 = u64::try_from(hshm.layout.peb_address)?;
         RawPtr::from(peb_u64)
@@ -119,6 +153,11 @@ pub(super) fn evolve_impl_multi_use(u_sbox: UninitializedSandbox) -> Result<Mult
         hshm.mapped_rgns += 1;
     }
 
+    #[cfg(target_os = "linux")]
+    let vcpu_cgroup = u_sbox.vcpu_cgroup;
+    #[cfg(target_os = "linux")]
+    let cgroup_membership = vcpu_cgroup.as_ref().map(|c| c.enter()).transpose()?;
+
     vm.initialise(
         peb_addr,
         seed,
@@ -129,18 +168,82 @@ pub(super) fn evolve_impl_multi_use(u_sbox: UninitializedSandbox) -> Result<Mult
         #[cfg(gdb)]
         dbg_mem_access_hdl,
     )
-    .map_err(HyperlightVmError::Initialize)?;
+    .map_err(InitializeError::promote)?;
+
+    #[cfg(target_os = "linux")]
+    drop(cgroup_membership);
 
     #[cfg(gdb)]
     let dbg_mem_wrapper = Arc::new(Mutex::new(hshm.clone()));
 
-    Ok(MultiUseSandbox::from_uninit(
+    let mut sbox = MultiUseSandbox::from_uninit(
         u_sbox.host_funcs,
         hshm,
         vm,
         #[cfg(gdb)]
         dbg_mem_wrapper,
-    ))
+        u_sbox.config,
+        u_sbox.max_guest_log_level,
+        u_sbox.labels,
+        measurement,
+        guest_symbolizer,
+        coverage_counters,
+    );
+    #[cfg(target_os = "linux")]
+    sbox.set_vcpu_cgroup(vcpu_cgroup);
+
+    // Find out which functions the guest registered and check them against
+    // the required ones, then record the post-initialisation state so that
+    // `reset()` can return to it. This also becomes the sandbox's current
+    // snapshot, so an immediate call to `snapshot()` is free.
+    if initialising {
+        sbox.load_guest_functions()?;
+        check_interface(&u_sbox.required_guest_functions, sbox.guest_functions())?;
+        sbox.capture_init_snapshot()?;
+    }
+
+    Ok(sbox)
+}
+
+/// Compute the measurement of the guest that is about to be run, before
+/// any guest code has executed.
+///
+/// This is the SHA-256 digest of the following, in order:
+/// - the input data size, output data size, heap size and scratch size
+///   from the sandbox configuration, each as a little-endian `u64`
+/// - the loaded and relocated guest image
+/// - the inputs passed to the guest through the PEB: the address of the
+///   init entry point (zero when `hyperlight_main` is run), the init
+///   data, the startup configuration and the guest arguments, each
+///   preceded by its length as a little-endian `u64`
+fn measure(u_sbox: &UninitializedSandbox) -> Result<[u8; 32]> {
+    let layout = &u_sbox.mgr.layout;
+    let memory = u_sbox.mgr.shared_mem.as_slice();
+    let code_start = layout.get_guest_code_offset();
+    let code_end = code_start + layout.get_code_size();
+    let image = memory
+        .get(code_start..code_end)
+        .ok_or_else(|| new_error!("guest image is outside of sandbox memory"))?;
+
+    let cfg = &u_sbox.config;
+    let mut hasher = Sha256::new();
+    for size in [
+        cfg.get_input_data_size() as u64,
+        cfg.get_output_data_size() as u64,
+        cfg.get_heap_size(),
+        cfg.get_scratch_size() as u64,
+    ] {
+        hasher.update(size.to_le_bytes());
+    }
+    hasher.update(image);
+    for range in layout.get_guest_input_ranges() {
+        let input = memory
+            .get(range)
+            .ok_or_else(|| new_error!("guest input is outside of sandbox memory"))?;
+        hasher.update((input.len() as u64).to_le_bytes());
+        hasher.update(input);
+    }
+    Ok(hasher.finalize().into())
 }
 
 pub(crate) fn set_up_hypervisor_partition(
@@ -153,10 +256,10 @@ pub(crate) fn set_up_hypervisor_partition(
 ) -> Result<HyperlightVm> {
     // Create gdb thread if gdb is enabled and the configuration is provided
     #[cfg(gdb)]
-    let gdb_conn = if let Some(DebugInfo { port }) = rt_cfg.debug_info {
-        use crate::hypervisor::gdb::create_gdb_thread;
+    let gdb_conn = if let Some(debug_info) = &rt_cfg.debug_info {
+        use crate::hypervisor::gdb::create_debug_thread;
 
-        let gdb_conn = create_gdb_thread(port);
+        let gdb_conn = create_debug_thread(debug_info, rt_cfg.debug_socket.as_deref());
 
         // in case the gdb thread creation fails, we still want to continue
         // without gdb
@@ -185,6 +288,7 @@ pub(crate) fn set_up_hypervisor_partition(
         if let crate::sandbox::snapshot::NextAction::Initialise(addr) = mgr.entrypoint {
             rt_cfg.entry_point = Some(addr);
         }
+        rt_cfg.guest_symbolizer = _load_info.symbolizer.clone();
         rt_cfg
     };
 
@@ -0,0 +1,130 @@
+/*
+Copyright 2025  The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+//! USDT probes, in the SystemTap SDT format that `bpftrace`, `perf` and
+//! BCC understand, with the provider `hyperlight`:
+//!
+//! | Probe               | Arguments                                   |
+//! |---------------------|---------------------------------------------|
+//! | `sandbox__create`   | sandbox id                                  |
+//! | `sandbox__destroy`  | sandbox id                                  |
+//! | `guest__entry`      | sandbox id, function name, name length      |
+//! | `guest__exit`       | sandbox id, whether the call failed         |
+//! | `host__call__enter` | function name, name length                  |
+//! | `host__call__leave` | function name, name length, error code      |
+//! | `kill`              | whether the vCPU was running                |
+//!
+//! Function names are not NUL-terminated, so they are read with their
+//! length, as in `str(arg1, arg2)`. A probe is a single `nop` until a
+//! tracer attaches to it. Without the `usdt` feature, or on other
+//! platforms than x86-64 Linux, probes compile to nothing.
+
+/// Fire the probe `$name` of the `hyperlight` provider with up to three
+/// arguments, each converted to a `u64` with `as`
+#[cfg(usdt)]
+macro_rules! probe {
+    ($name:literal) => {
+        $crate::usdt::probe!(@emit $name, "")
+    };
+    ($name:literal, $a0:expr) => {
+        $crate::usdt::probe!(@emit $name, "8@{0}", $a0)
+    };
+    ($name:literal, $a0:expr, $a1:expr) => {
+        $crate::usdt::probe!(@emit $name, "8@{0} 8@{1}", $a0, $a1)
+    };
+    ($name:literal, $a0:expr, $a1:expr, $a2:expr) => {
+        $crate::usdt::probe!(@emit $name, "8@{0} 8@{1} 8@{2}", $a0, $a1, $a2)
+    };
+    (@emit $name:literal, $args:literal $(, $arg:expr)*) => {
+        // SAFETY: the probe site is a `nop`, and the rest only adds a note
+        // describing it to the binary
+        #[allow(named_asm_labels)]
+        let () = unsafe {
+            ::core::arch::asm!(
+                "990: nop",
+                ".pushsection .note.stapsdt, \"\", \"note\"",
+                ".balign 4",
+                ".4byte 992f-991f, 994f-993f, 3",
+                "991: .asciz \"stapsdt\"",
+                "992: .balign 4",
+                "993: .8byte 990b",
+                ".8byte _.stapsdt.base",
+                ".8byte 0",
+                ".asciz \"hyperlight\"",
+                concat!(".asciz \"", $name, "\""),
+                concat!(".asciz \"", $args, "\""),
+                "994: .balign 4",
+                ".popsection",
+                // The base tracers use to find where the binary was loaded
+                ".ifndef _.stapsdt.base",
+                ".pushsection .stapsdt.base, \"aG\", \"progbits\", .stapsdt.base, comdat",
+                ".weak _.stapsdt.base",
+                ".hidden _.stapsdt.base",
+                "_.stapsdt.base: .space 1",
+                ".size _.stapsdt.base, 1",
+                ".popsection",
+                ".endif",
+                $(in(reg) ($arg) as u64,)*
+                options(att_syntax, nomem, nostack, preserves_flags),
+            )
+        };
+    };
+}
+
+/// The arguments are only borrowed, so that the values computed for
+/// probes are still used
+#[cfg(not(usdt))]
+macro_rules! probe {
+    ($name:literal $(, $arg:expr)*) => {
+        $(let _ = &$arg;)*
+    };
+}
+
+pub(crate) use probe;
+
+#[cfg(all(test, usdt))]
+mod tests {
+    /// Returns the names of the `hyperlight` probes in the note section
+    /// of the running test binary
+    fn probe_names() -> Vec<String> {
+        let exe = std::fs::read("/proc/self/exe").unwrap();
+        let mut names = Vec::new();
+        // Each note names the owner, provider, probe and argument format
+        let marker = b"stapsdt\0";
+        let mut rest = &exe[..];
+        while let Some(pos) = rest.windows(marker.len()).position(|w| w == marker) {
+            let note = &rest[pos + 8..];
+            // The location, base and semaphore come before the strings
+            let strings = &note[24.min(note.len())..];
+            let mut parts = strings.split(|b| *b == 0);
+            if parts.next() == Some(b"hyperlight")
+                && let Some(name) = parts.next()
+            {
+                names.push(String::from_utf8_lossy(name).into_owned());
+            }
+            rest = &rest[pos + marker.len()..];
+        }
+        names
+    }
+
+    #[test]
+    fn probes_are_in_the_binary() {
+        let value = 7u32;
+        super::probe!("test__probe", value, 1);
+        let names = probe_names();
+        assert!(names.iter().any(|name| name == "test__probe"), "{names:?}");
+    }
+}
@@ -20,7 +20,7 @@ use std::time::Duration;
 
 use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
 use hyperlight_common::log_level::GuestLogFilter;
-use hyperlight_host::sandbox::SandboxConfiguration;
+use hyperlight_host::sandbox::{CancellationToken, SandboxConfiguration};
 use hyperlight_host::{HyperlightError, MultiUseSandbox};
 use hyperlight_testing::simplelogger::{LOGGER, SimpleLogger};
 use serial_test::serial;
@@ -116,6 +116,166 @@ fn interrupt_in_progress_guest_call() {
     });
 }
 
+/// Makes sure a core dump can be taken of a running guest call without interrupting it
+#[test]
+#[cfg(all(feature = "crashdump", target_arch = "x86_64"))]
+fn live_core_dump_of_in_progress_guest_call() {
+    with_rust_sandbox(|mut sbox| {
+        let dump_path = std::env::temp_dir().join(format!("hl_live_{}.core", std::process::id()));
+        let dumper = sbox.core_dump_handle();
+        let interrupt_handle = sbox.interrupt_handle();
+
+        // The guest is not running yet
+        assert!(dumper.dump_core(&dump_path).is_err());
+
+        let path = dump_path.clone();
+        let thread = thread::spawn(move || {
+            thread::sleep(Duration::from_secs(1));
+            dumper.dump_core(&path).unwrap();
+            // The guest carried on spinning after the dump was taken
+            thread::sleep(Duration::from_millis(100));
+            assert!(interrupt_handle.kill());
+        });
+
+        let res = sbox.call::<i32>("Spin", ()).unwrap_err();
+        assert!(
+            matches!(&res, HyperlightError::ExecutionCanceledByHost()),
+            "unexpected error: {res:?}"
+        );
+        thread.join().expect("Thread should finish");
+
+        assert!(std::fs::metadata(&dump_path).unwrap().len() > 0);
+        std::fs::remove_file(&dump_path).unwrap();
+    });
+}
+
+/// Makes sure a guest call that exceeds its timeout is cancelled and reported as timed out,
+/// and that a call finishing within its timeout is unaffected
+#[test]
+fn guest_call_with_timeout() {
+    with_rust_sandbox(|mut sbox| {
+        let snapshot = sbox.snapshot().unwrap();
+
+        let res = sbox
+            .call_with_timeout::<i32>("Spin", (), Duration::from_millis(500))
+            .unwrap_err();
+        assert!(
+            matches!(&res, HyperlightError::GuestCallTimedOut(t) if *t == Duration::from_millis(500)),
+            "unexpected error: {res:?}"
+        );
+        assert!(sbox.poisoned());
+
+        sbox.restore(snapshot).unwrap();
+        let res = sbox
+            .call_with_timeout::<String>("Echo", "hello".to_string(), Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(res, "hello");
+
+        // the expired watchdog from the first call must not affect later calls
+        sbox.call::<String>("Echo", "hello".to_string()).unwrap();
+    });
+}
+
+/// Makes sure shutting down a sandbox runs the guest's shutdown handler, and that guests
+/// without a handler shut down immediately
+#[test]
+fn guest_graceful_shutdown() {
+    with_rust_uninit_sandbox(|mut usbox| {
+        let shut_down = Arc::new(AtomicBool::new(false));
+        let shut_down_clone = shut_down.clone();
+        usbox
+            .register("HostShutdown", move || {
+                shut_down_clone.store(true, Ordering::Relaxed);
+                Ok(())
+            })
+            .unwrap();
+        let sbox = usbox.evolve().unwrap();
+
+        sbox.shutdown(Duration::from_secs(10)).unwrap();
+        assert!(shut_down.load(Ordering::Relaxed));
+    });
+
+    with_c_sandbox(|sbox| {
+        sbox.shutdown(Duration::from_secs(10)).unwrap();
+    });
+}
+
+/// Makes sure cancelling a token interrupts the call it is attached to, and nothing else
+#[test]
+fn guest_call_with_cancellation_token() {
+    with_rust_sandbox(|mut sbox| {
+        let snapshot = sbox.snapshot().unwrap();
+
+        // a token that is never cancelled does not affect the call
+        let token = CancellationToken::new();
+        sbox.call_with_cancellation::<String>("Echo", "hello".to_string(), &token)
+            .unwrap();
+        // cancelling after the call has finished must not affect later calls
+        token.cancel();
+        sbox.call::<String>("Echo", "hello".to_string()).unwrap();
+
+        // an already cancelled token fails without entering the guest
+        let res = sbox
+            .call_with_cancellation::<String>("Echo", "hello".to_string(), &token)
+            .unwrap_err();
+        assert!(matches!(res, HyperlightError::ExecutionCanceledByHost()));
+        assert!(!sbox.poisoned());
+
+        let token = CancellationToken::new();
+        let thread = thread::spawn({
+            let token = token.clone();
+            move || {
+                thread::sleep(Duration::from_secs(1));
+                token.cancel();
+            }
+        });
+        let res = sbox
+            .call_with_cancellation::<i32>("Spin", (), &token)
+            .unwrap_err();
+        assert!(
+            matches!(&res, HyperlightError::ExecutionCanceledByHost()),
+            "unexpected error: {res:?}"
+        );
+        assert!(sbox.poisoned());
+        thread.join().unwrap();
+
+        sbox.restore(snapshot).unwrap();
+        sbox.call::<String>("Echo", "hello".to_string()).unwrap();
+    });
+}
+
+/// Makes sure the host sees the guest's progress through the control page, and
+/// that the guest stops when asked to without the call being interrupted
+#[test]
+fn guest_call_with_control_page() {
+    with_rust_sandbox(|mut sbox| {
+        let snapshot = sbox.snapshot().unwrap();
+        let control = sbox.control_page();
+        assert_eq!(control.progress().unwrap(), 0);
+
+        let thread = thread::spawn({
+            let control = control.clone();
+            move || {
+                while control.progress().unwrap() < 1000 {
+                    thread::yield_now();
+                }
+                control.request_soft_cancel().unwrap();
+            }
+        });
+        let progress = sbox.call::<u64>("SpinUntilSoftCancel", ()).unwrap();
+        thread.join().unwrap();
+        assert!(progress >= 1000);
+        assert_eq!(control.progress().unwrap(), progress);
+        assert_eq!(control.heartbeat().unwrap(), progress);
+        assert!(!sbox.poisoned());
+
+        // the slots are cleared when the sandbox is restored
+        sbox.restore(snapshot).unwrap();
+        assert!(!control.soft_cancel_requested().unwrap());
+        assert_eq!(control.progress().unwrap(), 0);
+    });
+}
+
 /// Makes sure interrupting a vm before the guest call has started does not prevent the guest call from running
 #[test]
 fn interrupt_guest_call_in_advance() {
@@ -490,7 +650,7 @@ fn guest_panic() {
             .call::<()>("guest_panic", "Error... error...".to_string())
             .unwrap_err();
         assert!(
-            matches!(&res, HyperlightError::GuestAborted(code, context) if *code == ErrorCode::UnknownError as u8 && context.contains("\nError... error...")),
+            matches!(&res, HyperlightError::GuestPanicked { message, location: Some(location) } if message == "Error... error..." && location.contains("simpleguest/src/main.rs")),
             "unexpected error: {res:?}"
         );
     });
@@ -554,8 +714,8 @@ fn guest_malloc_abort() {
         assert!(
             matches!(
                 &err,
-                // OOM memory errors in rust allocator are panics. Our panic handler returns ErrorCode::UnknownError on panic
-                HyperlightError::GuestAborted(code, msg) if *code == ErrorCode::UnknownError as u8 && msg.contains("memory allocation of ")
+                // OOM memory errors in rust allocator are panics
+                HyperlightError::GuestPanicked { message, .. } if message.contains("memory allocation of ")
             ),
             "unexpected error: {err:?}"
         );
@@ -731,6 +891,45 @@ fn recursive_stack_allocate_overflow() {
     });
 }
 
+// checks that a per-function stack limit stops the stack from growing past it
+#[test]
+fn function_stack_size_limit() {
+    with_rust_sandbox(|mut sbox1| {
+        let snapshot = sbox1.snapshot().unwrap();
+        sbox1
+            .set_function_stack_size("StackOverflow", 16 * 1024)
+            .unwrap();
+        assert!(sbox1.call::<i32>("StackOverflow", 8_i32).is_err());
+
+        sbox1.restore(snapshot).unwrap();
+        sbox1.set_function_stack_size("StackOverflow", 0).unwrap();
+        sbox1.call::<i32>("StackOverflow", 8_i32).unwrap();
+
+        // The stack can never grow larger than the scratch memory
+        assert!(matches!(
+            sbox1.set_function_stack_size("StackOverflow", usize::MAX),
+            Err(HyperlightError::InvalidConfiguration(_))
+        ));
+    });
+}
+
+// checks that a per-function stack size can be larger than the default
+#[test]
+fn function_stack_size_above_default() {
+    let mut cfg = SandboxConfiguration::default();
+    cfg.set_max_stack_size(16 * 1024);
+    with_rust_sandbox_cfg(cfg, |mut sbox1| {
+        let snapshot = sbox1.snapshot().unwrap();
+        assert!(sbox1.call::<i32>("StackOverflow", 8_i32).is_err());
+
+        sbox1.restore(snapshot).unwrap();
+        sbox1
+            .set_function_stack_size("StackOverflow", 256 * 1024)
+            .unwrap();
+        sbox1.call::<i32>("StackOverflow", 8_i32).unwrap();
+    });
+}
+
 // Check that log messages are emitted correctly from the guest
 // This test is ignored as it sets a logger and therefore maybe impacted by other tests running concurrently
 // or it may impact other tests.
@@ -828,6 +1027,89 @@ fn log_test_messages(levelfilter: Option<tracing_core::LevelFilter>) {
     }
 }
 
+/// Makes sure a recorded execution replays with the recorded host function
+/// results, without calling the host functions again
+#[test]
+fn profiling_samples_the_running_guest() {
+    let mut sbox = new_rust_sandbox();
+    sbox.start_profiling(Duration::from_millis(1)).unwrap();
+    // Only one profile can be taken at a time
+    assert!(sbox.start_profiling(Duration::from_millis(1)).is_err());
+    sbox.call::<u64>("SpinForMs", 200_u32).unwrap();
+    let profile = sbox.stop_profiling().unwrap();
+
+    assert!(profile.sample_count() > 0);
+    assert!(profile.duration() >= Duration::from_millis(200));
+    assert_eq!(
+        profile.to_folded().lines().count(),
+        profile.stacks().count()
+    );
+    assert!(!profile.to_pprof().is_empty());
+
+    // The guest runs as normal once profiling stops
+    assert!(sbox.stop_profiling().is_err());
+    sbox.call::<u64>("SpinForMs", 10_u32).unwrap();
+}
+
+#[test]
+fn recorded_execution_replays_host_function_results() {
+    use std::sync::atomic::{AtomicI64, Ordering};
+
+    use hyperlight_host::sandbox::ExecutionRecording;
+
+    let clock = Arc::new(AtomicI64::new(100));
+    let mut usbox = new_rust_uninit_sandbox();
+    usbox
+        .register("Now", {
+            let clock = clock.clone();
+            move || clock.fetch_add(7, Ordering::Relaxed)
+        })
+        .unwrap();
+    usbox.record_execution().unwrap();
+    let mut sbox = usbox.evolve().unwrap();
+    let recorded: Vec<i64> = (0..3)
+        .map(|_| {
+            sbox.call(
+                "CallGivenParamlessHostFuncThatReturnsI64",
+                "Now".to_string(),
+            )
+            .unwrap()
+        })
+        .collect();
+    assert_eq!(recorded, [100, 107, 114]);
+    let recording = sbox.execution_recording().unwrap();
+
+    let recording = ExecutionRecording::from_bytes(&recording.to_bytes()).unwrap();
+    let mut usbox = new_rust_uninit_sandbox();
+    usbox
+        .register("Now", || -> i64 {
+            panic!("host functions are not called in a replay")
+        })
+        .unwrap();
+    usbox.replay_execution(recording).unwrap();
+    let mut sbox = usbox.evolve().unwrap();
+    let replayed: Vec<_> = sbox
+        .replay()
+        .unwrap()
+        .into_iter()
+        .map(|res| res.unwrap())
+        .collect();
+    assert_eq!(
+        replayed,
+        recorded
+            .into_iter()
+            .map(hyperlight_host::func::ReturnValue::Long)
+            .collect::<Vec<_>>()
+    );
+
+    // Anything beyond the recording diverges from it
+    let res = sbox.call::<i64>(
+        "CallGivenParamlessHostFuncThatReturnsI64",
+        "Now".to_string(),
+    );
+    assert!(matches!(res, Err(HyperlightError::ReplayDiverged(_))));
+}
+
 /// Tests whether host is able to return Bool as return type
 /// or not
 #[test]
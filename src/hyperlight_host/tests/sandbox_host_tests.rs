@@ -14,10 +14,12 @@ See the License for the specific language governing permissions and
 limitations under the License.
 */
 use core::f64;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::channel;
 use std::sync::{Arc, Mutex};
 
-use hyperlight_host::sandbox::SandboxConfiguration;
+use hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode;
+use hyperlight_host::sandbox::{InputSource, OutputSink, SandboxConfiguration};
 use hyperlight_host::{
     GuestBinary, HyperlightError, MultiUseSandbox, Result, UninitializedSandbox, new_error,
 };
@@ -26,7 +28,7 @@ use hyperlight_testing::simple_guest_as_string;
 pub mod common; // pub to disable dead_code warning
 use crate::common::{
     with_all_sandboxes, with_all_sandboxes_cfg, with_all_sandboxes_with_writer,
-    with_all_uninit_sandboxes,
+    with_all_uninit_sandboxes, with_rust_uninit_sandbox,
 };
 
 #[test]
@@ -109,17 +111,53 @@ fn float_roundtrip() {
     });
 }
 
+#[test]
+fn float_vector_parameters() {
+    let mut sandbox = common::new_rust_sandbox();
+    let sum: f32 = sandbox
+        .call("SumFloats", vec![1.5f32, 2.25, -0.75])
+        .unwrap();
+    assert_eq!(sum, 3.0);
+    let dot: f64 = sandbox
+        .call("DotDoubles", (vec![1.0, 2.0, 3.0], vec![0.5, -1.0, 2.0]))
+        .unwrap();
+    assert_eq!(dot, 4.5);
+    let empty: f32 = sandbox.call("SumFloats", Vec::<f32>::new()).unwrap();
+    assert_eq!(empty, 0.0);
+}
+
 #[test]
 fn invalid_guest_function_name() {
     with_all_sandboxes(|mut sandbox| {
         let fn_name = "FunctionDoesntExist";
         let res = sandbox.call::<i32>(fn_name, ());
         assert!(
-            matches!(res.unwrap_err(), HyperlightError::GuestError(hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode::GuestFunctionNotFound, error_name) if error_name == fn_name)
+            matches!(res.unwrap_err(), HyperlightError::GuestError { code: hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode::GuestFunctionNotFound, message, .. } if message == fn_name)
         );
     });
 }
 
+#[test]
+fn guest_error_backtrace_is_resolved_against_guest_symbols() {
+    let mut sandbox = common::new_rust_sandbox();
+    let err = sandbox.call::<i32>("FunctionDoesntExist", ()).unwrap_err();
+    let HyperlightError::GuestError {
+        guest_backtrace, ..
+    } = err
+    else {
+        panic!("expected a guest error but got {err:?}");
+    };
+    // The guest is not necessarily built with frame pointers, so only the
+    // frames that are found are checked
+    if let Some(frame) = guest_backtrace.frames.first() {
+        let symbol = frame.symbol.as_deref().unwrap_or_default();
+        assert!(
+            symbol.contains("HyperlightGuestError"),
+            "unexpected innermost frame in\n{guest_backtrace}"
+        );
+    }
+}
+
 #[test]
 fn set_static() {
     let mut cfg: SandboxConfiguration = Default::default();
@@ -187,10 +225,11 @@ fn incorrect_parameter_type() {
 
         assert!(matches!(
             res.unwrap_err(),
-            HyperlightError::GuestError(
-                hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode::GuestFunctionParameterTypeMismatch,
-                msg
-            ) if msg == "Expected parameter type String for parameter index 0 of function Echo but got Int."
+            HyperlightError::GuestError {
+                code: hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode::GuestFunctionParameterTypeMismatch,
+                message,
+                ..
+            } if message == "Expected parameter type String for parameter index 0 of function Echo but got Int."
         ));
     });
 }
@@ -201,10 +240,11 @@ fn incorrect_parameter_num() {
         let res = sandbox.call::<i32>("Echo", ("1".to_string(), 2_i32));
         assert!(matches!(
             res.unwrap_err(),
-            HyperlightError::GuestError(
-                hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode::GuestFunctionIncorrecNoOfParameters,
-                msg
-            ) if msg == "Called function Echo with 2 parameters but it takes 1."
+            HyperlightError::GuestError {
+                code: hyperlight_common::flatbuffer_wrappers::guest_error::ErrorCode::GuestFunctionIncorrecNoOfParameters,
+                message,
+                ..
+            } if message == "Called function Echo with 2 parameters but it takes 1."
         ));
     });
 }
@@ -343,6 +383,107 @@ fn callback_test_parallel() {
     }
 }
 
+#[derive(Clone, Default)]
+struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn guest_output_is_sent_to_sinks() {
+    with_rust_uninit_sandbox(|mut sandbox| {
+        let stdout = SharedOutput::default();
+        let stderr = SharedOutput::default();
+        sandbox
+            .set_guest_stdout(OutputSink::new(stdout.clone()).with_line_buffering())
+            .unwrap();
+        sandbox
+            .set_guest_stderr(OutputSink::new(stderr.clone()).with_max_bytes(8))
+            .unwrap();
+        let mut sandbox: MultiUseSandbox = sandbox.evolve().unwrap();
+
+        sandbox
+            .call::<()>(
+                "WriteOutput",
+                ("hello\nwor".to_string(), "error: ".to_string()),
+            )
+            .unwrap();
+        sandbox
+            .call::<()>("WriteOutput", ("ld\n".to_string(), "more".to_string()))
+            .unwrap();
+
+        assert_eq!(*stdout.0.lock().unwrap(), b"hello\nworld\n");
+        assert_eq!(*stderr.0.lock().unwrap(), b"error: m");
+    });
+}
+
+#[test]
+fn guest_reads_stdin_from_source() {
+    // More input than fits in the input buffer at once
+    let input: Vec<u8> = (0..40_000u32).map(|i| i as u8).collect();
+    with_rust_uninit_sandbox(|mut sandbox| {
+        sandbox
+            .set_guest_stdin(InputSource::new(std::io::Cursor::new(input.clone())))
+            .unwrap();
+        let mut sandbox: MultiUseSandbox = sandbox.evolve().unwrap();
+        let res: Vec<u8> = sandbox.call("ReadStdin", 1000u64).unwrap();
+        assert_eq!(res, input);
+        // The input has been used up
+        let res: Vec<u8> = sandbox.call("ReadStdin", 1000u64).unwrap();
+        assert!(res.is_empty());
+    });
+
+    with_rust_uninit_sandbox(|sandbox| {
+        let mut sandbox: MultiUseSandbox = sandbox.evolve().unwrap();
+        let res: Vec<u8> = sandbox.call("ReadStdin", 16u64).unwrap();
+        assert!(res.is_empty());
+    });
+}
+
+#[test]
+fn host_function_panic() {
+    with_all_uninit_sandboxes(|mut sandbox| {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        sandbox
+            .register("HostMethod1", move |msg: String| {
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    panic!("Host function panic!");
+                }
+                Ok(msg.len() as i32)
+            })
+            .unwrap();
+
+        let mut init_sandbox: MultiUseSandbox = sandbox.evolve().unwrap();
+        let snapshot = init_sandbox.snapshot().unwrap();
+        let res = init_sandbox
+            .call::<i32>("GuestMethod1", "Hello world".to_string())
+            .unwrap_err();
+        assert!(
+            matches!(&res, HyperlightError::GuestError { code: ErrorCode::HostFunctionPanicked, message, .. } if message.contains("Host function panic!")) // rust guest
+            || matches!(&res, HyperlightError::GuestAborted(_, msg) if msg.contains("Host function panic!")), // c guest
+            "expected a host function panic but got {}",
+            res
+        );
+        if init_sandbox.poisoned() {
+            init_sandbox.restore(snapshot).unwrap();
+        }
+
+        // The panic did not leave the sandbox unusable
+        init_sandbox
+            .call::<i32>("GuestMethod1", "Hello world".to_string())
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    });
+}
+
 #[test]
 fn host_function_error() {
     with_all_uninit_sandboxes(|mut sandbox| {
@@ -363,7 +504,7 @@ fn host_function_error() {
                 .call::<i32>("GuestMethod1", msg.to_string())
                 .unwrap_err();
             assert!(
-                matches!(&res, HyperlightError::GuestError(_, msg) if msg == "Host function error!") // rust guest
+                matches!(&res, HyperlightError::GuestError { message, .. } if message == "Host function error!") // rust guest
                 || matches!(&res, HyperlightError::GuestAborted(_, msg) if msg.contains("Host function error!")), // c guest
                 "expected something but got {}",
                 res
@@ -0,0 +1,224 @@
+/*
+Copyright 2025 The Hyperlight Authors.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+*/
+
+use proc_macro::TokenStream;
+use proc_macro_crate::{FoundCrate, crate_name};
+use quote::{format_ident, quote};
+use syn::parse::{Error, Parse, ParseStream, Result};
+use syn::spanned::Spanned as _;
+use syn::{FnArg, ItemFn, LitStr, Pat, Type, parse_macro_input};
+
+/// Represents the optional name argument for the hyperlight_host_fn macro.
+enum NameArg {
+    None,
+    Name(LitStr),
+}
+
+impl Parse for NameArg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        // accepts either nothing or a single string literal
+        // anything else is an error
+        if input.is_empty() {
+            return Ok(NameArg::None);
+        }
+        let name: LitStr = input.parse()?;
+        if !input.is_empty() {
+            return Err(Error::new(input.span(), "expected a single identifier"));
+        }
+        Ok(NameArg::Name(name))
+    }
+}
+
+/// Returns the owned type a borrowed argument of type `ty` is received as,
+/// or `None` if `ty` is not borrowed. Only `&[u8]` and `&str` can be
+/// borrowed.
+fn owned_type(ty: &Type) -> Result<Option<proc_macro2::TokenStream>> {
+    let Type::Reference(reference) = ty else {
+        return Ok(None);
+    };
+    if reference.mutability.is_some() {
+        return Err(Error::new(
+            ty.span(),
+            "Mutable references are not allowed in host functions",
+        ));
+    }
+    match &*reference.elem {
+        Type::Slice(slice) if quote!(#slice).to_string() == "[u8]" => Ok(Some(quote! { Vec<u8> })),
+        Type::Path(path) if path.path.is_ident("str") => Ok(Some(quote! { String })),
+        _ => Err(Error::new(
+            ty.span(),
+            "Only &[u8] and &str references are allowed in host functions",
+        )),
+    }
+}
+
+/// Attribute macro to mark a function as a host function.
+/// This will generate a `register_<name>` function that registers it with
+/// a sandbox, so that it can be called by the guest.
+///
+/// If a name is provided as an argument, that name will be used to register the function.
+/// Otherwise, the function's identifier will be used.
+///
+/// The function arguments must be supported parameter types, `&[u8]` or `&str`, and the
+/// return type must be a supported return type or a `hyperlight_host::Result<T>` with T
+/// being a supported return type.
+///
+/// The generated function takes any sandbox implementing `Registerable`, such as an
+/// `UninitializedSandbox` or a `MultiUseSandbox`. On the guest side, the function can be
+/// called through a declaration marked with `#[host_function]` from `hyperlight-guest-bin`.
+///
+/// # Example
+/// ```ignore
+/// use hyperlight_host::hyperlight_host_fn;
+/// #[hyperlight_host_fn]
+/// fn checksum(data: &[u8]) -> u32 {
+///     data.iter().map(|b| *b as u32).sum()
+/// }
+///
+/// register_checksum(&mut sandbox)?;
+/// ```
+///
+/// or with a custom name and a Result return type:
+/// ```ignore
+/// use hyperlight_host::{hyperlight_host_fn, new_error};
+/// #[hyperlight_host_fn("ReadConfig")]
+/// fn read_config(key: &str) -> hyperlight_host::Result<String> {
+///     std::env::var(key).map_err(|e| new_error!("{}", e))
+/// }
+///
+/// register_read_config(&mut sandbox)?;
+/// ```
+#[proc_macro_attribute]
+pub fn hyperlight_host_fn(attr: TokenStream, item: TokenStream) -> TokenStream {
+    // Obtain the crate name for hyperlight-host. Within hyperlight-host itself
+    // the crate is referred to by its own name, which it declares with
+    // `extern crate self`, so that the same path also works in its doctests.
+    let crate_name = match crate_name("hyperlight-host") {
+        Ok(FoundCrate::Itself) => quote! {::hyperlight_host},
+        Ok(FoundCrate::Name(name)) => {
+            let ident = syn::Ident::new(&name, proc_macro2::Span::call_site());
+            quote! {::#ident}
+        }
+        Err(e) => {
+            return Error::new(
+                proc_macro2::Span::call_site(),
+                format!("hyperlight-host must be a dependency: {e}"),
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+
+    // Parse the function definition that we will be working with, and
+    // early return if parsing as `ItemFn` fails.
+    let fn_declaration = parse_macro_input!(item as ItemFn);
+
+    // Obtain the name of the function being decorated.
+    let ident = fn_declaration.sig.ident.clone();
+    let vis = fn_declaration.vis.clone();
+    let register_ident = format_ident!("register_{}", ident);
+
+    // Determine the name used to register the function, either
+    // the provided name or the function's identifier.
+    let exported_name = match parse_macro_input!(attr as NameArg) {
+        NameArg::None => LitStr::new(&ident.to_string(), ident.span()),
+        NameArg::Name(name) => name,
+    };
+
+    // Check that the function is not async.
+    if fn_declaration.sig.asyncness.is_some() {
+        return Error::new(
+            fn_declaration.sig.asyncness.span(),
+            "Async functions are not allowed in host functions, use register_async instead",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    // Check that the function is not generic, as it must have a single signature.
+    if !fn_declaration.sig.generics.params.is_empty() {
+        return Error::new(
+            fn_declaration.sig.generics.span(),
+            "Generic functions are not allowed in host functions",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    // Build the parameters of the closure that is registered, and the
+    // arguments it passes to the decorated function. Borrowed arguments are
+    // received as their owned counterpart and passed on by reference.
+    let mut params = vec![];
+    let mut args = vec![];
+    for arg in fn_declaration.sig.inputs.iter() {
+        match arg {
+            // Reject receiver arguments (i.e., `self`, `&self`, `Box<Self>`, etc).
+            FnArg::Receiver(_) => {
+                return Error::new(
+                    arg.span(),
+                    "Receiver (self) argument is not allowed in host functions",
+                )
+                .to_compile_error()
+                .into();
+            }
+            FnArg::Typed(arg) => {
+                // Only allow simple identifiers, so that they can be reused
+                // as the names of the closure parameters.
+                let Pat::Ident(pat) = &*arg.pat else {
+                    return Error::new(
+                        arg.span(),
+                        "Only named arguments are allowed in host functions",
+                    )
+                    .to_compile_error()
+                    .into();
+                };
+                let name = &pat.ident;
+                let ty = &arg.ty;
+                match owned_type(ty) {
+                    Ok(Some(owned)) => {
+                        params.push(quote! { #name: #owned });
+                        args.push(quote! { &#name });
+                    }
+                    Ok(None) => {
+                        params.push(quote! { #name: #ty });
+                        args.push(quote! { #name });
+                    }
+                    Err(e) => return e.to_compile_error().into(),
+                }
+            }
+        }
+    }
+
+    let doc = format!(
+        "Register [`{ident}`] as the host function `{}` on `sandbox`",
+        exported_name.value()
+    );
+
+    // The generated code will replace the decorated code, so we need to
+    // include the original function declaration in the output.
+    let output = quote! {
+        #fn_declaration
+
+        #[doc = #doc]
+        #vis fn #register_ident(
+            sandbox: &mut impl #crate_name::func::Registerable,
+        ) -> #crate_name::Result<()> {
+            sandbox.register_host_function(#exported_name, |#(#params),*| #ident(#(#args),*))
+        }
+    };
+
+    output.into()
+}
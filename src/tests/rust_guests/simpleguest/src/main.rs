@@ -26,13 +26,14 @@ const MAX_BUFFER_SIZE: usize = 1024;
 extern crate alloc;
 
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use alloc::{format, vec};
 use core::alloc::Layout;
 use core::ffi::c_char;
 use core::hint::black_box;
-use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 
 use hyperlight_common::flatbuffer_wrappers::function_call::{FunctionCall, FunctionCallType};
 use hyperlight_common::flatbuffer_wrappers::function_types::{
@@ -47,10 +48,13 @@ use hyperlight_guest::error::{HyperlightGuestError, Result};
 use hyperlight_guest::exit::{abort_with_code, abort_with_code_and_message};
 use hyperlight_guest_bin::exception::arch::{Context, ExceptionInfo};
 use hyperlight_guest_bin::guest_function::definition::{GuestFunc, GuestFunctionDefinition};
-use hyperlight_guest_bin::guest_function::register::register_function;
+use hyperlight_guest_bin::guest_function::register::{
+    register_function, register_shutdown_handler,
+};
 use hyperlight_guest_bin::host_comm::{
-    call_host_function, call_host_function_without_returning_result, get_host_return_value_raw,
-    print_output_with_host_print, read_n_bytes_from_user_memory,
+    args, call_host, call_host_function, call_host_function_without_returning_result,
+    get_host_return_value_raw, print_output_with_host_print, read_n_bytes_from_user_memory,
+    read_stdin, startup_config, write_stderr, write_stdout, write_stream_chunk,
 };
 use hyperlight_guest_bin::memory::malloc;
 use hyperlight_guest_bin::{GUEST_HANDLE, guest_function, guest_logger, host_function};
@@ -188,6 +192,16 @@ fn echo_float(value: f32) -> f32 {
     value
 }
 
+#[guest_function("SumFloats")]
+fn sum_floats(values: Vec<f32>) -> f32 {
+    values.iter().sum()
+}
+
+#[guest_function("DotDoubles")]
+fn dot_doubles(a: Vec<f64>, b: Vec<f64>) -> f64 {
+    a.iter().zip(&b).map(|(x, y)| x * y).sum()
+}
+
 #[host_function("HostPrint")]
 fn host_print(msg: String) -> i32;
 
@@ -396,6 +410,19 @@ fn spin() {
     }
 }
 
+/// Spins, reporting progress and heartbeats through the control page,
+/// until the host asks for it to stop, and returns the progress made
+#[guest_function("SpinUntilSoftCancel")]
+fn spin_until_soft_cancel() -> u64 {
+    let mut progress = 0;
+    while !hyperlight_guest::control::soft_cancel_requested() {
+        progress += 1;
+        hyperlight_guest::control::set_progress(progress);
+        hyperlight_guest::control::heartbeat();
+    }
+    progress
+}
+
 /// Spins the CPU for approximately the specified number of milliseconds
 #[guest_function("SpinForMs")]
 fn spin_for_ms(milliseconds: u32) -> u64 {
@@ -688,6 +715,24 @@ fn add_to_static_and_fail() -> Result<i32> {
     ))
 }
 
+#[guest_function("AddToStaticAndAbort")]
+fn add_to_static_and_abort() {
+    unsafe { COUNTER += 10 };
+    abort_with_code(&[0]);
+}
+
+#[expect(
+    clippy::empty_loop,
+    reason = "This function is used to keep the CPU busy"
+)]
+#[guest_function("AddToStaticAndSpin")]
+fn add_to_static_and_spin() {
+    unsafe { COUNTER += 10 };
+    loop {
+        // Spin until the host cancels the call
+    }
+}
+
 #[guest_function("24K_in_8K_out")]
 fn twenty_four_k_in_eight_k_out(input: Vec<u8>) -> Vec<u8> {
     assert!(input.len() == 24 * 1024, "Input must be 24K bytes");
@@ -699,6 +744,21 @@ fn call_given_paramless_hostfunc_that_returns_i64(hostfuncname: String) -> Resul
     call_host_function::<i64>(&hostfuncname, None, ReturnType::Long)
 }
 
+/// The guest's TSC ticks across a call to the given host function
+#[guest_function("TscTicksAcrossHostCall")]
+fn tsc_ticks_across_host_call(hostfuncname: String) -> Result<u64> {
+    // SAFETY: RDTSC has no side effects
+    let start = unsafe { core::arch::x86_64::_rdtsc() };
+    call_host::<i64>(&hostfuncname, ())?;
+    let end = unsafe { core::arch::x86_64::_rdtsc() };
+    Ok(end.wrapping_sub(start))
+}
+
+#[guest_function("CallCallback")]
+fn call_callback(callback: String, times: i32) -> Result<i32> {
+    (0..times).try_fold(0, |sum, i| Ok(sum + call_host::<i32>(&callback, i)?))
+}
+
 #[guest_function("UseSSE2Registers")]
 fn use_sse2_registers() {
     let val: f32 = 1.2f32;
@@ -747,6 +807,63 @@ fn read_from_user_memory(num: u64, expected: Vec<u8>) -> Result<Vec<u8>> {
     Ok(bytes)
 }
 
+#[guest_function("GetStartupConfig")]
+fn get_startup_config() -> Vec<u8> {
+    startup_config().to_vec()
+}
+
+#[guest_function("StreamBytes")]
+fn stream_bytes(len: u64, chunk_len: u64) -> Result<()> {
+    let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+    for chunk in data.chunks(chunk_len as usize) {
+        write_stream_chunk(chunk)?;
+    }
+    Ok(())
+}
+
+#[guest_function("WriteOutput")]
+fn write_output(stdout: String, stderr: String) -> Result<()> {
+    write_stdout(stdout.as_bytes())?;
+    write_stderr(stderr.as_bytes())
+}
+
+#[guest_function("ReadStdin")]
+fn read_all_stdin(buf_len: u64) -> Result<Vec<u8>> {
+    let mut input = Vec::new();
+    let mut buf = vec![0; buf_len as usize];
+    loop {
+        let n = read_stdin(&mut buf)?;
+        if n == 0 {
+            return Ok(input);
+        }
+        input.extend_from_slice(&buf[..n]);
+    }
+}
+
+#[guest_function("SumPresent")]
+fn sum_present(values: BTreeMap<String, Option<u64>>) -> Option<u64> {
+    values.into_values().flatten().reduce(|a, b| a + b)
+}
+
+#[guest_function("GetArgs")]
+fn get_args() -> String {
+    args().collect::<Vec<_>>().join("\n")
+}
+
+static BENCH_MAIN_RAN: AtomicBool = AtomicBool::new(false);
+
+/// An alternate init entry point, run instead of `hyperlight_main` when the
+/// host selects it with `GuestEnvironment::with_init_entry_point`
+#[unsafe(no_mangle)]
+pub extern "C" fn bench_main() {
+    BENCH_MAIN_RAN.store(true, Ordering::Relaxed);
+}
+
+#[guest_function("BenchMainRan")]
+fn bench_main_ran() -> bool {
+    BENCH_MAIN_RAN.load(Ordering::Relaxed)
+}
+
 #[guest_function("ReadMappedBuffer")]
 fn read_mapped_buffer(base: u64, len: u64, do_map: bool) -> Vec<u8> {
     let base = base as usize as *const u8;
@@ -865,6 +982,16 @@ fn main() {
         print_output_with_host_print,
     );
     register_function(print_output_def);
+    register_shutdown_handler(shutdown);
+}
+
+#[host_function("HostShutdown")]
+fn host_shutdown() -> Result<()>;
+
+// Notifies the host that the guest is shutting down, so that tests can
+// observe that the shutdown handler ran.
+fn shutdown() -> Result<()> {
+    host_shutdown()
 }
 
 #[host_function("HostMethod")]